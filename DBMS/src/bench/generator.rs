@@ -0,0 +1,73 @@
+use crate::common::Result;
+use crate::sql::engine::{Local, Session};
+use crate::storage::HeapTableManager;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+/// The number of rows each INSERT statement of the generator holds.
+const INSERT_BATCH_ROWS: usize = 500;
+
+/// The number of distinct labels, so sorts and groupings on them see many duplicates.
+const LABELS: usize = 16;
+
+/// The shape of a generated database: `tables` tables named t0, t1, ..., each with `rows` rows
+/// whose values come from a random number generator seeded with `seed`.
+///
+/// Each table has the columns:
+///
+/// * `id INT PRIMARY KEY`: 0 to `rows` - 1.
+/// * `k INT`: uniformly distributed over the ids, to join on.
+/// * `value INT`: skewed towards 0, ranging up to `rows`.
+/// * `score FLOAT`: uniformly distributed between 0 and 1000.
+/// * `label TEXT`: one of 16 labels.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DataSpec {
+    pub tables: usize,
+    pub rows: usize,
+    pub seed: u64,
+}
+
+impl DataSpec {
+    /// Returns the spec of the given scale: two tables, of 1000 rows per unit of scale.
+    pub fn scale(scale: usize, seed: u64) -> Self {
+        Self {
+            tables: 2,
+            rows: scale * 1000,
+            seed,
+        }
+    }
+
+    /// Returns the name of the i-th table.
+    pub fn table(&self, i: usize) -> String {
+        format!("t{i}")
+    }
+}
+
+/// Creates and fills the tables of the spec. The same spec always generates the same rows.
+pub fn generate(session: &mut Session<Local<HeapTableManager>>, spec: &DataSpec) -> Result<()> {
+    let mut rng = ChaCha8Rng::seed_from_u64(spec.seed);
+    for i in 0..spec.tables {
+        let table = spec.table(i);
+        session.execute(&format!(
+            "CREATE TABLE {table} (id INT PRIMARY KEY, k INT, value INT, score FLOAT, label TEXT)"
+        ))?;
+        for start in (0..spec.rows).step_by(INSERT_BATCH_ROWS) {
+            let end = (start + INSERT_BATCH_ROWS).min(spec.rows);
+            let values: Vec<String> = (start..end)
+                .map(|id| row(&mut rng, spec, id as i32))
+                .collect();
+            session.execute(&format!("INSERT INTO {table} VALUES {}", values.join(", ")))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the VALUES tuple of a random row with the given id.
+pub(super) fn row(rng: &mut ChaCha8Rng, spec: &DataSpec, id: i32) -> String {
+    let k = rng.gen_range(0..spec.rows.max(1));
+    let value = (spec.rows as f64 * rng.gen::<f64>().powi(3)) as i32;
+    let score = rng.gen_range(0.0..1000.0_f32);
+    let label = rng.gen_range(0..LABELS);
+    format!("({id}, {k}, {value}, {score:?}, 'label{label}')")
+}
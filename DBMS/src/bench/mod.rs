@@ -0,0 +1,10 @@
+//! A benchmark harness, driven by the rustydb-bench binary: generates a database of a given
+//! scale from a seeded random number generator, runs a named workload against it, and reports
+//! throughput, latency percentiles and storage counters.
+mod generator;
+mod runner;
+mod workload;
+
+pub use generator::{generate, DataSpec};
+pub use runner::{run, Latency, Limit, Report, Stats};
+pub use workload::{find, Context, Workload, WORKLOADS};
@@ -0,0 +1,134 @@
+use super::generator::{generate, DataSpec};
+use super::workload::{Context, Workload};
+use crate::common::Result;
+use crate::errinput;
+use crate::sql::engine::{Engine as _, Local};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::HeapTableManager;
+use hdrhistogram::Histogram;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long a workload runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    /// Runs the operation this many times.
+    Operations(u64),
+    /// Runs the operation until this much time has passed.
+    Duration(Duration),
+}
+
+/// The results of a workload run, serialized as the JSON report of rustydb-bench.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub workload: &'static str,
+    pub data: DataSpec,
+    pub pool_size: usize,
+    pub operations: u64,
+    pub elapsed_secs: f64,
+    pub ops_per_sec: f64,
+    pub latency_us: Latency,
+    /// The storage counters of the run, which don't include generating the data.
+    pub stats: Stats,
+}
+
+/// Percentiles of operation latencies, in microseconds.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Latency {
+    pub min: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+/// Storage counters, from the write-ahead log, and the sizes of the files at the end of a run.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Stats {
+    pub log_commits: u64,
+    pub log_fsyncs: u64,
+    pub data_file_bytes: u64,
+    pub log_file_bytes: u64,
+}
+
+/// Generates the spec's database in a new file at `path`, with a buffer pool of `pool_size`
+/// pages, and runs the workload against it until the limit.
+pub fn run(
+    path: &Path,
+    pool_size: usize,
+    spec: DataSpec,
+    workload: &Workload,
+    limit: Limit,
+) -> Result<Report> {
+    if spec.tables == 0 || spec.rows == 0 {
+        return errinput!("the database needs at least one table, with at least one row");
+    }
+    if path.exists() {
+        return errinput!("{} already exists", path.display());
+    }
+    let disk_manager = DiskManager::open(path)?;
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(pool_size)
+        .replacer_k(2)
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+    generate(&mut session, &spec)?;
+    // Start from clean pages, and a short log.
+    engine.checkpoint()?;
+
+    let log = bpm.log_manager();
+    let log_before = log.stats();
+    let mut histogram = Histogram::<u64>::new(3)?;
+    let mut ctx = Context::new(&mut session, spec, ChaCha8Rng::seed_from_u64(spec.seed));
+    let start = Instant::now();
+    loop {
+        let done = match limit {
+            Limit::Operations(operations) => histogram.len() >= operations,
+            Limit::Duration(duration) => start.elapsed() >= duration,
+        };
+        if done {
+            break;
+        }
+        let op_start = Instant::now();
+        (workload.operation)(&mut ctx)?;
+        histogram.record(op_start.elapsed().as_micros() as u64)?;
+    }
+    let elapsed = start.elapsed();
+    let log_after = log.stats();
+
+    let file_size = |path: &Path| -> Result<u64> { Ok(std::fs::metadata(path)?.len()) };
+    let mut log_path = path.as_os_str().to_owned();
+    log_path.push(".wal");
+    Ok(Report {
+        workload: workload.name,
+        data: spec,
+        pool_size,
+        operations: histogram.len(),
+        elapsed_secs: elapsed.as_secs_f64(),
+        ops_per_sec: histogram.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        latency_us: Latency {
+            min: histogram.min(),
+            mean: histogram.mean(),
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            p999: histogram.value_at_quantile(0.999),
+            max: histogram.max(),
+        },
+        stats: Stats {
+            log_commits: log_after.commits - log_before.commits,
+            log_fsyncs: log_after.fsyncs - log_before.fsyncs,
+            data_file_bytes: file_size(path)?,
+            log_file_bytes: file_size(Path::new(&log_path))?,
+        },
+    })
+}
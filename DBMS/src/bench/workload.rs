@@ -0,0 +1,130 @@
+use super::generator::{self, DataSpec};
+use crate::common::Result;
+use crate::errdata;
+use crate::sql::engine::{Local, Session, StatementResult};
+use crate::storage::HeapTableManager;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+/// A named workload: an operation that the runner repeats, timing each run.
+pub struct Workload {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub operation: fn(&mut Context) -> Result<()>,
+}
+
+/// The workloads rustydb-bench runs. A new workload is a function of a [`Context`], listed here.
+pub const WORKLOADS: &[Workload] = &[
+    Workload {
+        name: "point",
+        description: "looks up a random row by primary key",
+        operation: point,
+    },
+    Workload {
+        name: "scan",
+        description: "counts the rows of a table matching a filter on an unindexed column",
+        operation: scan,
+    },
+    Workload {
+        name: "join",
+        description: "counts the rows of an equi-join of two tables, planned as a nested loop join",
+        operation: join,
+    },
+    Workload {
+        name: "sort",
+        description: "sorts a table by its label and score",
+        operation: sort,
+    },
+    Workload {
+        name: "insert",
+        description: "inserts a random row, in a transaction of its own",
+        operation: insert,
+    },
+];
+
+/// Returns the workload of the given name.
+pub fn find(name: &str) -> Option<&'static Workload> {
+    WORKLOADS.iter().find(|workload| workload.name == name)
+}
+
+/// What a workload's operations run against: a session on the generated database, and a random
+/// number generator seeded from its spec, so runs repeat the same operations.
+pub struct Context<'a, 'b> {
+    pub session: &'b mut Session<'a, Local<HeapTableManager>>,
+    pub spec: DataSpec,
+    pub rng: ChaCha8Rng,
+    /// The id the next inserted row gets, past those of the generated rows.
+    next_id: usize,
+}
+
+impl<'a, 'b> Context<'a, 'b> {
+    pub(super) fn new(
+        session: &'b mut Session<'a, Local<HeapTableManager>>,
+        spec: DataSpec,
+        rng: ChaCha8Rng,
+    ) -> Self {
+        Self {
+            session,
+            spec,
+            rng,
+            next_id: spec.rows,
+        }
+    }
+
+    /// Returns the name of a random table.
+    pub fn random_table(&mut self) -> String {
+        let i = self.rng.gen_range(0..self.spec.tables);
+        self.spec.table(i)
+    }
+
+    /// Runs a query, returning its rows.
+    pub fn query(&mut self, query: &str) -> Result<usize> {
+        match self.session.execute(query)? {
+            StatementResult::Select { rows, .. } => Ok(rows.len()),
+            result => errdata!("unexpected result {result:?}"),
+        }
+    }
+}
+
+fn point(ctx: &mut Context) -> Result<()> {
+    let table = ctx.random_table();
+    let id = ctx.rng.gen_range(0..ctx.spec.rows);
+    match ctx.query(&format!("SELECT * FROM {table} WHERE id = {id}"))? {
+        1 => Ok(()),
+        count => errdata!("found {count} rows with id {id} in {table}"),
+    }
+}
+
+fn scan(ctx: &mut Context) -> Result<()> {
+    let table = ctx.random_table();
+    let score = ctx.rng.gen_range(0.0..1000.0_f32);
+    ctx.query(&format!(
+        "SELECT COUNT(*) FROM {table} WHERE score > {score:?}"
+    ))?;
+    Ok(())
+}
+
+fn join(ctx: &mut Context) -> Result<()> {
+    let (left, right) = (ctx.random_table(), ctx.random_table());
+    ctx.query(&format!(
+        "SELECT COUNT(*) FROM {left} l JOIN {right} r ON l.k = r.id"
+    ))?;
+    Ok(())
+}
+
+fn sort(ctx: &mut Context) -> Result<()> {
+    let table = ctx.random_table();
+    ctx.query(&format!(
+        "SELECT id, label, score FROM {table} ORDER BY label, score DESC"
+    ))?;
+    Ok(())
+}
+
+fn insert(ctx: &mut Context) -> Result<()> {
+    let table = ctx.random_table();
+    let row = generator::row(&mut ctx.rng, &ctx.spec, ctx.next_id as i32);
+    ctx.next_id += 1;
+    ctx.session
+        .execute(&format!("INSERT INTO {table} VALUES {row}"))?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Runs a benchmark workload against a generated database, see [`rustydb::bench`], printing a
+//! JSON report.
+use rustydb::bench::{self, DataSpec, Limit, WORKLOADS};
+use rustydb::config::config::DEFAULT_POOL_SIZE;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+const USAGE: &str = "usage: rustydb-bench --workload NAME [--scale N] [--tables N] [--rows N]
+                     [--seed N] [--ops N | --duration SECONDS] [--pool-size N] [--path FILE]
+       rustydb-bench --list
+
+Generates a database of N tables of --rows rows each, whose values come from a random number
+generator seeded with --seed, 42 by default. --scale N is 2 tables of 1000 * N rows, and the
+default is 1. Then runs the workload --ops times, 1000 by default, or for --duration seconds, and
+prints a JSON report of its throughput, latencies, and storage counters.
+
+The database is written to a temporary directory, or to the new file --path. --list lists the
+workloads.";
+
+fn main() -> ExitCode {
+    let mut workload = None;
+    let mut spec = DataSpec::scale(1, 42);
+    let mut limit = Limit::Operations(1000);
+    let mut pool_size = DEFAULT_POOL_SIZE;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs an argument"));
+        let result = match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "--list" => {
+                for workload in WORKLOADS {
+                    println!("{:<8} {}", workload.name, workload.description);
+                }
+                return ExitCode::SUCCESS;
+            }
+            "--workload" => value().and_then(|name| match bench::find(&name) {
+                Some(found) => Ok(workload = Some(found)),
+                None => Err(format!("unknown workload {name}, see --list")),
+            }),
+            "--scale" => value().and_then(parse).map(|scale| {
+                spec = DataSpec::scale(scale, spec.seed);
+            }),
+            "--tables" => value().and_then(parse).map(|tables| spec.tables = tables),
+            "--rows" => value().and_then(parse).map(|rows| spec.rows = rows),
+            "--seed" => value().and_then(parse).map(|seed| spec.seed = seed),
+            "--ops" => value()
+                .and_then(parse)
+                .map(|ops| limit = Limit::Operations(ops)),
+            "--duration" => value()
+                .and_then(parse)
+                .map(|secs| limit = Limit::Duration(Duration::from_secs_f64(secs))),
+            "--pool-size" => value().and_then(parse).map(|size| pool_size = size),
+            "--path" => value().map(|arg| path = Some(PathBuf::from(arg))),
+            _ => Err(format!("unknown argument {arg}")),
+        };
+        if let Err(message) = result {
+            return usage_error(&message);
+        }
+    }
+    let Some(workload) = workload else {
+        return usage_error("--workload is required");
+    };
+
+    let dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(error) => {
+            eprintln!("ERROR: can't create a temporary directory: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let path = path.unwrap_or_else(|| dir.path().join("bench"));
+    let report = bench::run(&path, pool_size, spec, workload, limit).and_then(|report| {
+        serde_json::to_string_pretty(&report).map_err(|error| {
+            rustydb::common::Error::InvalidData(format!("can't serialize the report: {error}"))
+        })
+    });
+    match report {
+        Ok(json) => {
+            println!("{json}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("ERROR: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse<T: FromStr>(arg: String) -> Result<T, String> {
+    arg.parse().map_err(|_| format!("invalid number {arg}"))
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{message}\n\n{USAGE}");
+    ExitCode::from(2)
+}
@@ -0,0 +1,398 @@
+//! An interactive SQL shell over a database file.
+//!
+//! Statements end with a semicolon, and may span lines. Outside of a BEGIN/COMMIT block, each runs
+//! in a transaction of its own, see [`Session::execute`]. Lines starting with a backslash are
+//! meta-commands, which `\?` lists.
+use itertools::Itertools;
+use rustydb::common::Error;
+use rustydb::config::config::{CHECKPOINT_INTERVAL_MS, VACUUM_INTERVAL_MS};
+use rustydb::sql::engine::{Catalog, Engine, Local, Session, StatementResult};
+use rustydb::sql::parser::split_statements;
+use rustydb::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use rustydb::storage::disk::disk_manager::DiskManager;
+use rustydb::storage::tuple::Row;
+use rustydb::storage::HeapTableManager;
+use rustydb::types::field::{Field, Label};
+use rustydb::types::Table;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::{stdin, stdout, IsTerminal, Read};
+use std::process::ExitCode;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: rustydb-cli [-c SQL]... [FILE]
+       rustydb-cli --dump [FILE]
+
+Opens the database FILE, \"main\" by default, relative to the data directory. Runs the SQL of each
+-c option if any are given, or else reads statements from standard input, interactively if it is
+a terminal. With --dump, writes a script that recreates the database to standard output instead;
+restore it by running the script on an empty database.";
+
+const HELP: &str = "\\d [TABLE]   describe TABLE, or list the tables
+\\dt          list the tables
+\\timing      toggle printing how long each statement takes
+\\q           quit";
+
+const DEFAULT_FILENAME: &str = "main";
+
+fn main() -> ExitCode {
+    let mut filename = None;
+    let mut commands = Vec::new();
+    let mut dump = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "-c" => match args.next() {
+                Some(command) => commands.push(command),
+                None => return usage_error("-c needs an argument"),
+            },
+            "--dump" => dump = true,
+            _ if arg.starts_with('-') => return usage_error(&format!("unknown option {arg}")),
+            _ if filename.is_some() => return usage_error("more than one database file given"),
+            _ => filename = Some(arg),
+        }
+    }
+
+    if dump && !commands.is_empty() {
+        return usage_error("--dump can't be combined with -c");
+    }
+
+    let engine = Local::new(create_storage_engine(
+        filename.as_deref().unwrap_or(DEFAULT_FILENAME),
+    ));
+    if dump {
+        let result = engine
+            .session()
+            .with_txn(|txn| rustydb::sql::engine::dump(txn, &mut stdout().lock()));
+        if let Err(error) = result {
+            eprintln!("ERROR: {error}");
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+    let _checkpointer = engine
+        .simple
+        .start_checkpointer(Duration::from_millis(CHECKPOINT_INTERVAL_MS));
+    let _vacuum = engine
+        .simple
+        .start_vacuum(Duration::from_millis(VACUUM_INTERVAL_MS));
+
+    let mut shell = Shell::new(engine.session());
+    if !commands.is_empty() {
+        for command in &commands {
+            shell.run_script(command);
+        }
+    } else if stdin().is_terminal() {
+        shell.run_interactive();
+    } else {
+        let mut script = String::new();
+        match stdin().read_to_string(&mut script) {
+            Ok(_) => shell.run_script(&script),
+            Err(error) => shell.fail(error.into()),
+        }
+    }
+    let failed = shell.failed;
+    // Rolls back a transaction left open, before its changes would be written out.
+    drop(shell);
+
+    if let Err(error) = engine.checkpoint() {
+        eprintln!("ERROR: can't write the database to disk: {error}");
+        return ExitCode::FAILURE;
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{message}\n\n{USAGE}");
+    ExitCode::from(2)
+}
+
+fn create_storage_engine(filename: &str) -> HeapTableManager {
+    let disk_manager = DiskManager::new(filename);
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(500)
+        .replacer_k(15)
+        .build_with_handle();
+    HeapTableManager::new(&bpm)
+}
+
+/// What the shell does after a line of input.
+#[derive(PartialEq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Runs statements and meta-commands in a session, printing their results.
+struct Shell<'a> {
+    session: Session<'a, Local<HeapTableManager>>,
+    /// The text of a statement that hasn't been ended with a semicolon yet.
+    pending: String,
+    /// Whether to print how long each statement takes.
+    timing: bool,
+    /// Whether a statement or meta-command has failed.
+    failed: bool,
+}
+
+impl<'a> Shell<'a> {
+    fn new(session: Session<'a, Local<HeapTableManager>>) -> Self {
+        Self {
+            session,
+            pending: String::new(),
+            timing: false,
+            failed: false,
+        }
+    }
+
+    /// Reads lines from the terminal, with line editing and history, until `\q` or end of input.
+    /// Interrupting drops the statement being typed.
+    fn run_interactive(&mut self) {
+        let mut editor = match DefaultEditor::new() {
+            Ok(editor) => editor,
+            Err(error) => {
+                eprintln!("ERROR: can't read from the terminal: {error}");
+                self.failed = true;
+                return;
+            }
+        };
+        loop {
+            let prompt = match self.pending.trim().is_empty() {
+                true => "rustydb=> ",
+                false => "rustydb-> ",
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        editor.add_history_entry(line.as_str()).ok();
+                    }
+                    if self.feed(&line) == Flow::Quit {
+                        return;
+                    }
+                }
+                Err(ReadlineError::Interrupted) => self.pending.clear(),
+                Err(ReadlineError::Eof) => return,
+                Err(error) => {
+                    eprintln!("ERROR: {error}");
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Runs every statement and meta-command of a script. A final statement may leave out its
+    /// semicolon.
+    fn run_script(&mut self, script: &str) {
+        for line in script.lines() {
+            if self.feed(line) == Flow::Quit {
+                return;
+            }
+        }
+        let statement = std::mem::take(&mut self.pending);
+        if !statement.trim().is_empty() {
+            self.execute(&statement);
+        }
+    }
+
+    /// Handles a line of input: runs it as a meta-command, or adds it to the pending statement
+    /// text and runs the statements it completes.
+    fn feed(&mut self, line: &str) -> Flow {
+        if self.pending.trim().is_empty() {
+            if let Some(command) = line.trim().strip_prefix('\\') {
+                self.pending.clear();
+                return self.meta_command(command);
+            }
+        }
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        let pending = std::mem::take(&mut self.pending);
+        let (statements, rest) = split_statements(&pending);
+        for statement in statements {
+            self.execute(statement);
+        }
+        self.pending = rest.to_string();
+        Flow::Continue
+    }
+
+    fn execute(&mut self, statement: &str) {
+        let start = Instant::now();
+        match self.session.execute(statement) {
+            Ok(result) => print!("{}", format_result(&result)),
+            Err(error) => self.fail(error),
+        }
+        if self.timing {
+            println!("Time: {:.3} ms", start.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+
+    fn meta_command(&mut self, command: &str) -> Flow {
+        let mut words = command.split_whitespace();
+        let result = match (words.next().unwrap_or(""), words.next(), words.next()) {
+            ("q", None, _) => return Flow::Quit,
+            ("?", None, _) => Ok(format!("{HELP}\n")),
+            ("timing", None, _) => {
+                self.timing = !self.timing;
+                let state = if self.timing { "on" } else { "off" };
+                Ok(format!("Timing is {state}.\n"))
+            }
+            ("d" | "dt", None, _) => self
+                .session
+                .with_txn(|txn| txn.list_tables())
+                .map(|tables| {
+                    let rows = tables.iter().map(|table| {
+                        let columns = table.col_count() as i32;
+                        vec![
+                            Field::String(table.name().to_string()),
+                            Field::Integer(columns),
+                        ]
+                    });
+                    format_table(&["name", "columns"], rows.collect())
+                }),
+            ("d", Some(table), None) => self
+                .session
+                .with_txn(|txn| txn.must_get_table(table))
+                .map(|table| format!("Table \"{}\"\n{}", table.name(), describe(&table))),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown command \\{command}, \\? lists the commands"
+            ))),
+        };
+        match result {
+            Ok(output) => print!("{output}"),
+            Err(error) => self.fail(error),
+        }
+        Flow::Continue
+    }
+
+    fn fail(&mut self, error: Error) {
+        eprintln!("ERROR: {error}");
+        self.failed = true;
+    }
+}
+
+/// Formats a statement result the way the shell prints it: query results as a table, and other
+/// results as the name of the statement, with the number of rows it changed if any.
+fn format_result(result: &StatementResult) -> String {
+    match result {
+        StatementResult::Select { columns, rows } => format_rows(columns, rows),
+        StatementResult::Explain(plan) => format!("{plan}\n"),
+        StatementResult::Begin { .. } => "BEGIN\n".to_string(),
+        StatementResult::Commit => "COMMIT\n".to_string(),
+        StatementResult::Rollback => "ROLLBACK\n".to_string(),
+        StatementResult::SetTransaction { .. } => "SET TRANSACTION\n".to_string(),
+        StatementResult::Set { .. } => "SET\n".to_string(),
+        StatementResult::Checkpoint { lsn } => format!("CHECKPOINT {lsn}\n"),
+        StatementResult::Vacuum(stats) => format!(
+            "VACUUM {} versions, {} bytes, {} pages\n",
+            stats.versions, stats.bytes, stats.pages
+        ),
+        StatementResult::CreateTable { .. } => "CREATE TABLE\n".to_string(),
+        StatementResult::DropTable { existed: true, .. } => "DROP TABLE\n".to_string(),
+        StatementResult::DropTable {
+            name,
+            existed: false,
+        } => {
+            format!("DROP TABLE, table {name} does not exist\n")
+        }
+        StatementResult::Insert { count, .. } => format!("INSERT {count}\n"),
+        StatementResult::Update { count } => format!("UPDATE {count}\n"),
+        StatementResult::Delete { count } => format!("DELETE {count}\n"),
+    }
+}
+
+fn format_rows(columns: &[Label], rows: &[Row]) -> String {
+    let headers: Vec<&str> = columns.iter().map(|label| label.as_header()).collect();
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().cloned().collect())
+        .collect();
+    format_table(&headers, rows)
+}
+
+/// Describes a table's columns.
+fn describe(table: &Table) -> String {
+    let rows = table
+        .columns()
+        .iter()
+        .map(|column| {
+            let index = match (column.is_unique(), column.is_indexed()) {
+                (true, _) => "unique",
+                (false, true) => "index",
+                (false, false) => "",
+            };
+            let default = column.default().map(|default| default.to_string());
+            vec![
+                Field::String(column.get_name()),
+                Field::String(column.get_data_type().to_string()),
+                Field::Boolean(column.is_nullable()),
+                Field::String(default.unwrap_or_default()),
+                Field::String(index.to_string()),
+            ]
+        })
+        .collect();
+    format_table(&["column", "type", "nullable", "default", "index"], rows)
+}
+
+/// Formats rows as a table with aligned columns under a header, followed by the row count.
+/// Numbers are aligned right, and everything else left.
+fn format_table(headers: &[&str], rows: Vec<Vec<Field>>) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(format_field).collect())
+        .collect();
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &cells {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    let header = headers
+        .iter()
+        .zip(&widths)
+        .map(|(header, width)| format!(" {header:^width$} "))
+        .join("|");
+    output.push_str(header.trim_end());
+    output.push('\n');
+    let rule = widths.iter().map(|width| "-".repeat(width + 2)).join("+");
+    output.push_str(&rule);
+    output.push('\n');
+    for (row, row_cells) in rows.iter().zip(&cells) {
+        let line = row
+            .iter()
+            .zip(row_cells)
+            .zip(&widths)
+            .map(|((field, cell), width)| match field {
+                Field::Integer(_) | Field::Float(_) => format!(" {cell:>width$} "),
+                _ => format!(" {cell:<width$} "),
+            })
+            .join("|");
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    match rows.len() {
+        1 => output.push_str("(1 row)\n"),
+        count => output.push_str(&format!("({count} rows)\n")),
+    }
+    output
+}
+
+/// Formats a field as a table cell, with strings unquoted.
+fn format_field(field: &Field) -> String {
+    match field {
+        Field::String(string) => string.clone(),
+        field => field.to_string(),
+    }
+}
@@ -0,0 +1,104 @@
+//! Serves a database file to clients connected over TCP, see [`rustydb::server`].
+use rustydb::config::config::{CHECKPOINT_INTERVAL_MS, DEFAULT_SERVER_ADDRESS, VACUUM_INTERVAL_MS};
+use rustydb::server::{Protocol, Server};
+use rustydb::sql::engine::Local;
+use rustydb::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use rustydb::storage::disk::disk_manager::DiskManager;
+use rustydb::storage::HeapTableManager;
+use std::process::ExitCode;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const USAGE: &str = "usage: rustydb-server [--listen ADDRESS] [--protocol native|postgres] [FILE]
+
+Serves the database FILE, \"main\" by default, relative to the data directory, on ADDRESS,
+127.0.0.1:9605 by default. Speaks the native protocol, or with --protocol postgres, enough of the
+PostgreSQL protocol for psql and PostgreSQL drivers. Shuts down on Ctrl-C, writing modified pages
+to disk.";
+
+const DEFAULT_FILENAME: &str = "main";
+
+fn main() -> ExitCode {
+    let mut filename = None;
+    let mut address = DEFAULT_SERVER_ADDRESS.to_string();
+    let mut protocol = Protocol::Native;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "--listen" => match args.next() {
+                Some(arg) => address = arg,
+                None => return usage_error("--listen needs an argument"),
+            },
+            "--protocol" => match args.next().as_deref() {
+                Some("native") => protocol = Protocol::Native,
+                Some("postgres") => protocol = Protocol::Postgres,
+                Some(arg) => return usage_error(&format!("unknown protocol {arg}")),
+                None => return usage_error("--protocol needs an argument"),
+            },
+            _ if arg.starts_with('-') => return usage_error(&format!("unknown option {arg}")),
+            _ if filename.is_some() => return usage_error("more than one database file given"),
+            _ => filename = Some(arg),
+        }
+    }
+
+    let engine = Local::new(create_storage_engine(
+        filename.as_deref().unwrap_or(DEFAULT_FILENAME),
+    ));
+    let server = match Server::bind(&address, engine) {
+        Ok(server) => server.with_protocol(protocol),
+        Err(error) => {
+            eprintln!("ERROR: can't listen on {address}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let shutdown = match server.shutdown_handle() {
+        Ok(shutdown) => shutdown,
+        Err(error) => {
+            eprintln!("ERROR: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = ctrlc::set_handler(move || shutdown.shutdown()) {
+        eprintln!("ERROR: can't handle Ctrl-C: {error}");
+        return ExitCode::FAILURE;
+    }
+    let _checkpointer = server
+        .engine()
+        .simple
+        .start_checkpointer(Duration::from_millis(CHECKPOINT_INTERVAL_MS));
+    let _vacuum = server
+        .engine()
+        .simple
+        .start_vacuum(Duration::from_millis(VACUUM_INTERVAL_MS));
+
+    println!("[server] Listening on {address}.");
+    match server.serve() {
+        Ok(()) => {
+            println!("[server] Shut down.");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("ERROR: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{message}\n\n{USAGE}");
+    ExitCode::from(2)
+}
+
+fn create_storage_engine(filename: &str) -> HeapTableManager {
+    let disk_manager = DiskManager::new(filename);
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(500)
+        .replacer_k(15)
+        .build_with_handle();
+    HeapTableManager::new(&bpm)
+}
@@ -11,8 +11,6 @@ pub const NO_CORRESPONDING_PAGE_MSG: &str =
     "No page corresponding to page_id {page_id} exists in the buffer pool.";
 
 // TableHeap
-pub const COULD_NOT_UNWRAP_BPM_MSG: &str =
-    "Could not unwrap buffer pool manager from RwLock instance";
 pub const COULD_NOT_UNWRAP_SYSTEM_CATALOG_MSG: &str =
     "Could not unwrap buffer pool manager from RwLock instance";
 pub const NO_PAGE_EXISTS_MSG: &str = "No page exists corresponding to {page_id}";
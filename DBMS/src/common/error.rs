@@ -1,3 +1,6 @@
+use crate::storage::buffer::buffer_pool_manager::FrameId;
+use crate::storage::disk::disk_manager::PageId;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 ///
@@ -15,6 +18,11 @@ pub enum Error {
     InvalidInput(String),
     /// An IO error.
     IO(String),
+    /// A transaction gave up waiting for a lock held by another transaction.
+    /// The transaction should be rolled back and retried.
+    LockTimeout,
+    /// The transaction was terminated by an operator, and has been rolled back.
+    Cancelled,
     /// Arithmetic integer overflow occurred.
     OverflowError,
     /// A write action was attempted in a read-only transaction.
@@ -26,6 +34,45 @@ pub enum Error {
     OutOfBounds,
     /// A creation event failed.
     CreationError,
+    /// A write would violate a constraint, e.g. a unique index.
+    Constraint(String),
+    /// A page was looked up in the buffer pool, but isn't resident in it.
+    PageNotInPool(PageId),
+    /// A page was unpinned more often than it was pinned.
+    PageNotPinned(PageId),
+    /// A page needed a frame of the buffer pool, but none was free or could be evicted: `pinned`
+    /// of the `pool_size` frames it could have used are pinned. Debug builds also list the pages
+    /// pinned, by page ID.
+    NoEvictableFrame {
+        pinned: usize,
+        pool_size: usize,
+        pinned_pages: Vec<PageId>,
+    },
+    /// A replacer was given a frame it doesn't have, or doesn't track.
+    InvalidFrame(FrameId),
+    /// A replacer was asked to remove a frame that isn't evictable.
+    FrameNotEvictable(FrameId),
+    /// A page read from disk doesn't match the checksum it was written with: `expected` is the
+    /// one stored on the page, `actual` the one of the bytes read.
+    ChecksumMismatch {
+        page_id: PageId,
+        expected: u32,
+        actual: u32,
+    },
+    /// The database file ended `len` bytes into a page read from it.
+    ShortRead { page_id: PageId, len: usize },
+    /// A page was read from disk that isn't allocated: it is past the end of its file, or was
+    /// deallocated since it was.
+    PageNotAllocated(PageId),
+    /// An encrypted page read from disk failed to authenticate: it was tampered with, or isn't
+    /// what was written to it with the key it was read with.
+    DecryptionFailed(PageId),
+    /// A tuple of `len` bytes didn't fit in the `free_space` bytes left on its page, even once
+    /// compacted.
+    TupleTooLarge { len: usize, free_space: usize },
+    /// A statement or transaction kept failing with a retryable error, and
+    /// gave up after the given number of attempts.
+    RetriesExhausted { attempts: u32, error: Box<Error> },
 }
 
 impl std::error::Error for Error {}
@@ -37,11 +84,50 @@ impl std::fmt::Display for Error {
             Error::InvalidData(msg) => write!(f, "invalid data: {msg}"),
             Error::InvalidInput(msg) => write!(f, "invalid input: {msg}"),
             Error::IO(msg) => write!(f, "io error: {msg}"),
+            Error::LockTimeout => write!(f, "lock wait timeout, retry transaction"),
+            Error::Cancelled => write!(f, "transaction cancelled"),
             Error::OverflowError => write!(f, "integer overflow occurred"),
             Error::ReadOnly => write!(f, "read-only transaction"),
             Error::Serialization => write!(f, "serialization failure, retry transaction"),
             Error::OutOfBounds => write!(f, "out-of-bounds access occurred"),
             Error::CreationError => write!(f, "a creation event failed"),
+            Error::Constraint(msg) => write!(f, "{msg}"),
+            Error::PageNotInPool(page_id) => write!(f, "page {page_id} is not in the buffer pool"),
+            Error::PageNotPinned(page_id) => write!(f, "page {page_id} is not pinned"),
+            Error::NoEvictableFrame {
+                pinned,
+                pool_size,
+                pinned_pages,
+            } => {
+                write!(f, "no buffer pool frame available, {pinned} of {pool_size} are pinned")?;
+                match pinned_pages.as_slice() {
+                    [] => Ok(()),
+                    pages => write!(f, ", by pages {}", pages.iter().join(", ")),
+                }
+            }
+            Error::InvalidFrame(frame_id) => write!(f, "frame {frame_id} is not in the replacer"),
+            Error::FrameNotEvictable(frame_id) => write!(f, "frame {frame_id} is not evictable"),
+            Error::ChecksumMismatch {
+                page_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch on page {page_id}: expected {expected:#010x}, got {actual:#010x}"
+            ),
+            Error::ShortRead { page_id, len } => {
+                write!(f, "short read of page {page_id}, the file ends {len} bytes into it")
+            }
+            Error::PageNotAllocated(page_id) => write!(f, "page {page_id} is not allocated"),
+            Error::DecryptionFailed(page_id) => {
+                write!(f, "page {page_id} failed to decrypt, tampered with or under another key")
+            }
+            Error::TupleTooLarge { len, free_space } => {
+                write!(f, "tuple of {len} bytes doesn't fit in the {free_space} free on its page")
+            }
+            Error::RetriesExhausted { attempts, error } => {
+                write!(f, "{error}, gave up after {attempts} attempts")
+            }
         }
     }
 }
@@ -65,6 +151,10 @@ impl Error {
             Error::InvalidInput(_) => true,
             // IO errors are typically local to the node (e.g. faulty disk).
             Error::IO(_) => false,
+            // Lock waits depend on the timing of concurrent transactions.
+            Error::LockTimeout => false,
+            // Terminations come from outside the transaction.
+            Error::Cancelled => false,
             // Possible data corruption local to this node.
             Error::OverflowError => false,
             // Write commands in read-only transactions are deterministic.
@@ -75,6 +165,25 @@ impl Error {
             Error::OutOfBounds => false,
             // Memory might not have been allocated properly by the operating system
             Error::CreationError => false,
+            // Constraint checks only depend on the data already written.
+            Error::Constraint(_) => true,
+            // Buffer pool residency depends on what else was cached and evicted.
+            Error::PageNotInPool(_) => false,
+            // Mismatched pins are a bug local to this node.
+            Error::PageNotPinned(_) => false,
+            // Which pages are pinned depends on what else runs on this node.
+            Error::NoEvictableFrame { .. } => false,
+            // The pool and its replacer disagreeing on frames is a bug local to this node.
+            Error::InvalidFrame(_) | Error::FrameNotEvictable(_) => false,
+            // Possible data corruption local to this node.
+            Error::ChecksumMismatch { .. } | Error::ShortRead { .. } => false,
+            Error::DecryptionFailed(_) => false,
+            // Reading a page nothing refers to any longer is a bug local to this node.
+            Error::PageNotAllocated(_) => false,
+            // Whether a tuple fits only depends on the data already written.
+            Error::TupleTooLarge { .. } => true,
+            // Retries end the way their last attempt did.
+            Error::RetriesExhausted { error, .. } => error.is_deterministic(),
         }
     }
 }
@@ -124,6 +233,13 @@ impl serde::ser::Error for Error {
     }
 }
 
+#[cfg(feature = "arrow")]
+impl From<arrow::error::ArrowError> for Error {
+    fn from(err: arrow::error::ArrowError) -> Self {
+        Error::InvalidData(err.to_string())
+    }
+}
+
 impl From<Box<bincode::ErrorKind>> for Error {
     fn from(err: Box<bincode::ErrorKind>) -> Self {
         Error::InvalidData(err.to_string())
@@ -196,6 +312,12 @@ impl From<rustyline::error::ReadlineError> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::InvalidData(err.to_string())
+    }
+}
+
 impl From<std::array::TryFromSliceError> for Error {
     fn from(err: std::array::TryFromSliceError) -> Self {
         Error::InvalidData(err.to_string())
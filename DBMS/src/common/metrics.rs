@@ -0,0 +1,488 @@
+//! Counters for monitoring the engine, from the buffer pool and disk up to the statements run.
+//!
+//! A [`Metrics`] registry is shared by the components it counts for: the buffer pool hands it to
+//! its replacer and disk manager, and the SQL engine to its sessions and transactions. Each
+//! counter is an atomic, bumped where the component's tracing spans and events are, so counting
+//! costs next to nothing. [`Metrics::snapshot`] reads them all at once.
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A counter that only goes up.
+#[derive(Debug, Default)]
+pub(crate) struct Counter(AtomicU64);
+
+impl Counter {
+    pub(crate) fn incr(&self) {
+        self.add(1);
+    }
+
+    pub(crate) fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that goes up and down, e.g. the number of running transactions.
+#[derive(Debug, Default)]
+pub(crate) struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub(crate) fn incr(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn decr(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sub(&self, value: u64) {
+        self.0.fetch_sub(value, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A registry of the engine's counters, see the [module docs](self).
+///
+/// ```
+/// use rustydb::common::Metrics;
+///
+/// let metrics = Metrics::default();
+/// let snapshot = metrics.snapshot();
+/// assert_eq!(0, snapshot.statements.selects);
+/// assert!(snapshot
+///     .to_prometheus_text()
+///     .contains("rustydb_statements_total{kind=\"select\"} 0"));
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub(crate) buffer_pool_hits: Counter,
+    pub(crate) buffer_pool_misses: Counter,
+    pub(crate) buffer_pool_evictions: Counter,
+    pub(crate) buffer_pool_flushes: Counter,
+    pub(crate) buffer_pool_writebacks: Counter,
+    pub(crate) buffer_pool_pages_created: Counter,
+    pub(crate) buffer_pool_pages_deleted: Counter,
+    pub(crate) disk_pages_read: Counter,
+    pub(crate) disk_reads: Counter,
+    pub(crate) disk_pages_written: Counter,
+    pub(crate) disk_writes: Counter,
+    pub(crate) disk_syncs: Counter,
+    pub(crate) disk_log_bytes_appended: Counter,
+    pub(crate) disk_log_syncs: Counter,
+    pub(crate) replacer_accesses: Counter,
+    pub(crate) replacer_evictions: Counter,
+    pub(crate) replacer_evictable_frames: Gauge,
+    pub(crate) replacer_cold_evictions: Counter,
+    pub(crate) replacer_eviction_distance_sum: Counter,
+    pub(crate) replacer_eviction_distances: [Counter; EVICTION_DISTANCE_BUCKETS],
+    pub(crate) replacer_readmissions: Counter,
+    pub(crate) statement_selects: Counter,
+    pub(crate) statement_inserts: Counter,
+    pub(crate) statement_updates: Counter,
+    pub(crate) statement_deletes: Counter,
+    pub(crate) statement_errors: Counter,
+    pub(crate) statement_retries: Counter,
+    pub(crate) plan_cache_hits: Counter,
+    pub(crate) plan_cache_misses: Counter,
+    pub(crate) transactions_active: Gauge,
+}
+
+/// The counters of a [`Metrics`] registry at a point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub buffer_pool: BufferPoolStats,
+    pub disk: DiskStats,
+    pub replacer: ReplacerStats,
+    pub statements: StatementStats,
+    pub plan_cache: PlanCacheStats,
+    /// Transactions begun and not yet committed or rolled back.
+    pub active_transactions: u64,
+}
+
+/// Number of buckets of [`ReplacerStats::eviction_distances`].
+pub const EVICTION_DISTANCE_BUCKETS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Page fetches served by a resident page.
+    pub hits: u64,
+    /// Page fetches that had to read the page from disk.
+    pub misses: u64,
+    /// Pages evicted to free up their frame.
+    pub evictions: u64,
+    /// Pages written back to disk.
+    pub flushes: u64,
+    /// Dirty pages written back to disk as they were evicted.
+    pub writebacks: u64,
+    /// Pages allocated in the buffer pool.
+    pub pages_created: u64,
+    /// Pages deleted from the buffer pool.
+    pub pages_deleted: u64,
+    /// What the pool's replacers counted, for the victims they picked in particular.
+    pub replacer: ReplacerStats,
+}
+
+impl BufferPoolStats {
+    /// Returns the counts since an earlier snapshot of the same counters.
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            hits: self.hits - earlier.hits,
+            misses: self.misses - earlier.misses,
+            evictions: self.evictions - earlier.evictions,
+            flushes: self.flushes - earlier.flushes,
+            writebacks: self.writebacks - earlier.writebacks,
+            pages_created: self.pages_created - earlier.pages_created,
+            pages_deleted: self.pages_deleted - earlier.pages_deleted,
+            replacer: self.replacer.since(&earlier.replacer),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiskStats {
+    /// Pages read from the database file, including those read ahead.
+    pub pages_read: u64,
+    /// Reads issued to the database file, each of a page or a run of consecutive pages.
+    pub reads: u64,
+    /// Pages written to the database file.
+    pub pages_written: u64,
+    /// Writes issued to the database file, each of a page or a run of consecutive pages.
+    pub writes: u64,
+    /// Times the database file was forced to stable storage.
+    pub syncs: u64,
+    /// Bytes appended to the write-ahead log.
+    pub log_bytes_appended: u64,
+    /// Times the write-ahead log was forced to stable storage.
+    pub log_syncs: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplacerStats {
+    /// Frame accesses recorded.
+    pub accesses: u64,
+    /// Frames chosen for eviction.
+    pub evictions: u64,
+    /// Frames that may currently be evicted.
+    pub evictable_frames: u64,
+    /// Frames evicted with fewer than k accesses, i.e. an infinite backwards k-distance. Only
+    /// counted by LRU-K replacers, like the eviction distances.
+    pub cold_evictions: u64,
+    /// Sum of the backwards k-distances of the frames evicted with k accesses.
+    pub eviction_distance_sum: u64,
+    /// Frames evicted with k accesses, by backwards k-distance: bucket 0 counts distance 0, and
+    /// bucket `i` distances from `2^(i-1)` to `2^i - 1`, except for the last, which counts all
+    /// distances from `2^(i-1)` up.
+    pub eviction_distances: [u64; EVICTION_DISTANCE_BUCKETS],
+    /// Pages brought back into the pool shortly after being evicted, within as many evictions
+    /// as the replacer has frames.
+    pub readmissions: u64,
+}
+
+impl ReplacerStats {
+    /// Returns the counts since an earlier snapshot of the same counters. The number of
+    /// evictable frames is the current one.
+    pub fn since(&self, earlier: &Self) -> Self {
+        let mut eviction_distances = self.eviction_distances;
+        for (count, earlier) in eviction_distances.iter_mut().zip(earlier.eviction_distances) {
+            *count -= earlier;
+        }
+        Self {
+            accesses: self.accesses - earlier.accesses,
+            evictions: self.evictions - earlier.evictions,
+            evictable_frames: self.evictable_frames,
+            cold_evictions: self.cold_evictions - earlier.cold_evictions,
+            eviction_distance_sum: self.eviction_distance_sum - earlier.eviction_distance_sum,
+            eviction_distances,
+            readmissions: self.readmissions - earlier.readmissions,
+        }
+    }
+
+    /// Returns the mean backwards k-distance of the frames evicted with k accesses, or `None` if
+    /// there were none.
+    pub fn mean_eviction_distance(&self) -> Option<f64> {
+        let warm_evictions: u64 = self.eviction_distances.iter().sum();
+        (warm_evictions > 0).then(|| self.eviction_distance_sum as f64 / warm_evictions as f64)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatementStats {
+    pub selects: u64,
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    /// Statements of any kind that failed.
+    pub errors: u64,
+    /// Statements and transactions run again after a serialization failure or lock timeout.
+    pub retries: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlanCacheStats {
+    /// Statements run with a cached plan, skipping parsing and planning.
+    pub hits: u64,
+    /// Statements planned, since no current plan was cached for them.
+    pub misses: u64,
+}
+
+impl Metrics {
+    /// Counts a frame evicted with k accesses, and the backwards k-distance it had.
+    pub(crate) fn record_eviction_distance(&self, distance: u64) {
+        let bucket = (u64::BITS - distance.leading_zeros()) as usize;
+        self.replacer_eviction_distances[bucket.min(EVICTION_DISTANCE_BUCKETS - 1)].incr();
+        self.replacer_eviction_distance_sum.add(distance);
+    }
+
+    /// Reads every counter. Counters bumped while reading may or may not be included.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let replacer = ReplacerStats {
+            accesses: self.replacer_accesses.get(),
+            evictions: self.replacer_evictions.get(),
+            evictable_frames: self.replacer_evictable_frames.get(),
+            cold_evictions: self.replacer_cold_evictions.get(),
+            eviction_distance_sum: self.replacer_eviction_distance_sum.get(),
+            eviction_distances: self.replacer_eviction_distances.each_ref().map(Counter::get),
+            readmissions: self.replacer_readmissions.get(),
+        };
+        MetricsSnapshot {
+            buffer_pool: BufferPoolStats {
+                hits: self.buffer_pool_hits.get(),
+                misses: self.buffer_pool_misses.get(),
+                evictions: self.buffer_pool_evictions.get(),
+                flushes: self.buffer_pool_flushes.get(),
+                writebacks: self.buffer_pool_writebacks.get(),
+                pages_created: self.buffer_pool_pages_created.get(),
+                pages_deleted: self.buffer_pool_pages_deleted.get(),
+                replacer,
+            },
+            disk: DiskStats {
+                pages_read: self.disk_pages_read.get(),
+                reads: self.disk_reads.get(),
+                pages_written: self.disk_pages_written.get(),
+                writes: self.disk_writes.get(),
+                syncs: self.disk_syncs.get(),
+                log_bytes_appended: self.disk_log_bytes_appended.get(),
+                log_syncs: self.disk_log_syncs.get(),
+            },
+            replacer,
+            statements: StatementStats {
+                selects: self.statement_selects.get(),
+                inserts: self.statement_inserts.get(),
+                updates: self.statement_updates.get(),
+                deletes: self.statement_deletes.get(),
+                errors: self.statement_errors.get(),
+                retries: self.statement_retries.get(),
+            },
+            plan_cache: PlanCacheStats {
+                hits: self.plan_cache_hits.get(),
+                misses: self.plan_cache_misses.get(),
+            },
+            active_transactions: self.transactions_active.get(),
+        }
+    }
+
+    /// Renders a snapshot in the Prometheus text format, see
+    /// [`MetricsSnapshot::to_prometheus_text`].
+    pub fn to_prometheus_text(&self) -> String {
+        self.snapshot().to_prometheus_text()
+    }
+}
+
+impl MetricsSnapshot {
+    /// Renders the snapshot in the Prometheus text exposition format, one metric family per
+    /// counter, with statements counted by a `kind` label.
+    pub fn to_prometheus_text(&self) -> String {
+        let (pool, disk, replacer) = (&self.buffer_pool, &self.disk, &self.replacer);
+        let mut text = Families::default();
+        text.counter(
+            "buffer_pool_hits",
+            "Page fetches served by a resident page.",
+            pool.hits,
+        );
+        text.counter(
+            "buffer_pool_misses",
+            "Page fetches that read from disk.",
+            pool.misses,
+        );
+        text.counter("buffer_pool_evictions", "Pages evicted.", pool.evictions);
+        text.counter("buffer_pool_flushes", "Pages written back.", pool.flushes);
+        text.counter(
+            "buffer_pool_writebacks",
+            "Dirty pages written back on eviction.",
+            pool.writebacks,
+        );
+        text.counter(
+            "buffer_pool_pages_created",
+            "Pages allocated.",
+            pool.pages_created,
+        );
+        text.counter(
+            "buffer_pool_pages_deleted",
+            "Pages deleted.",
+            pool.pages_deleted,
+        );
+        text.counter(
+            "disk_pages_read",
+            "Pages read from the database file.",
+            disk.pages_read,
+        );
+        text.counter(
+            "disk_reads",
+            "Reads issued to the database file.",
+            disk.reads,
+        );
+        text.counter(
+            "disk_pages_written",
+            "Pages written to it.",
+            disk.pages_written,
+        );
+        text.counter(
+            "disk_writes",
+            "Writes issued to it.",
+            disk.writes,
+        );
+        text.counter(
+            "disk_syncs",
+            "Forces of the database file to stable storage.",
+            disk.syncs,
+        );
+        text.counter(
+            "disk_log_bytes_appended",
+            "Bytes appended to the log.",
+            disk.log_bytes_appended,
+        );
+        text.counter(
+            "disk_log_syncs",
+            "Forces of the log to stable storage.",
+            disk.log_syncs,
+        );
+        text.counter(
+            "replacer_accesses",
+            "Frame accesses recorded.",
+            replacer.accesses,
+        );
+        text.counter(
+            "replacer_evictions",
+            "Frames chosen for eviction.",
+            replacer.evictions,
+        );
+        text.gauge(
+            "replacer_evictable_frames",
+            "Frames that may be evicted.",
+            replacer.evictable_frames,
+        );
+        text.counter(
+            "replacer_cold_evictions",
+            "Frames evicted with fewer than k accesses.",
+            replacer.cold_evictions,
+        );
+        text.histogram(
+            "replacer_eviction_distance",
+            "Backwards k-distances of the frames evicted with k accesses.",
+            &replacer.eviction_distances,
+            replacer.eviction_distance_sum,
+        );
+        text.counter(
+            "replacer_readmissions",
+            "Pages brought back shortly after being evicted.",
+            replacer.readmissions,
+        );
+
+        let statements = &self.statements;
+        text.family(
+            "statements_total",
+            "counter",
+            "Statements that succeeded, by kind.",
+        );
+        for (kind, count) in [
+            ("select", statements.selects),
+            ("insert", statements.inserts),
+            ("update", statements.updates),
+            ("delete", statements.deletes),
+        ] {
+            writeln!(
+                text.0,
+                "rustydb_statements_total{{kind=\"{kind}\"}} {count}"
+            )
+            .unwrap();
+        }
+        text.counter(
+            "statement_errors",
+            "Statements that failed.",
+            statements.errors,
+        );
+        text.counter(
+            "statement_retries",
+            "Statements and transactions retried.",
+            statements.retries,
+        );
+        text.counter(
+            "plan_cache_hits",
+            "Statements run with a cached plan.",
+            self.plan_cache.hits,
+        );
+        text.counter(
+            "plan_cache_misses",
+            "Statements planned for the cache.",
+            self.plan_cache.misses,
+        );
+        text.gauge(
+            "transactions_active",
+            "Transactions not yet ended.",
+            self.active_transactions,
+        );
+        text.0
+    }
+}
+
+/// Metric families rendered in the Prometheus text format, with names prefixed by `rustydb_`.
+#[derive(Default)]
+struct Families(String);
+
+impl Families {
+    /// Starts a family, with its help text and type.
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.0, "# HELP rustydb_{name} {help}").unwrap();
+        writeln!(self.0, "# TYPE rustydb_{name} {kind}").unwrap();
+    }
+
+    /// Adds a counter, whose name gets the conventional `_total` suffix.
+    fn counter(&mut self, name: &str, help: &str, value: u64) {
+        let name = format!("{name}_total");
+        self.family(&name, "counter", help);
+        writeln!(self.0, "rustydb_{name} {value}").unwrap();
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: u64) {
+        self.family(name, "gauge", help);
+        writeln!(self.0, "rustydb_{name} {value}").unwrap();
+    }
+
+    /// Adds a histogram of the counts of [`ReplacerStats::eviction_distances`], each bucket
+    /// `i` but the last bounded by `2^i - 1`, with the cumulative counts Prometheus expects.
+    fn histogram(&mut self, name: &str, help: &str, buckets: &[u64], sum: u64) {
+        self.family(name, "histogram", help);
+        let mut count = 0;
+        for (i, bucket) in buckets.iter().enumerate() {
+            count += bucket;
+            let bound = if i == buckets.len() - 1 {
+                "+Inf".to_string()
+            } else {
+                ((1_u64 << i) - 1).to_string()
+            };
+            writeln!(self.0, "rustydb_{name}_bucket{{le=\"{bound}\"}} {count}").unwrap();
+        }
+        writeln!(self.0, "rustydb_{name}_sum {sum}").unwrap();
+        writeln!(self.0, "rustydb_{name}_count {count}").unwrap();
+    }
+}
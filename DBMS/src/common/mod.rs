@@ -1,5 +1,12 @@
 pub mod constants;
 mod error;
+mod metrics;
+pub(crate) mod sched;
+mod trace;
 pub mod utility;
 
 pub use error::{Error, Result};
+pub use metrics::{
+    BufferPoolStats, DiskStats, Metrics, MetricsSnapshot, PlanCacheStats, ReplacerStats,
+    StatementStats, EVICTION_DISTANCE_BUCKETS,
+};
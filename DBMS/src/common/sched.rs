@@ -0,0 +1,53 @@
+//! Scheduling points, where the deterministic simulation tests in `crate::sim` may switch to
+//! another logical thread. Outside of tests, [`sched_point!`] expands to nothing.
+//!
+//! A scheduling point must not be reached while holding a lock that another thread could block
+//! on without reaching a scheduling point itself, e.g. a lock manager's table mutex: the thread
+//! switched to would block for good.
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Marks a place where a simulated thread may be preempted, e.g. `sched_point!("bpm.unpin")`.
+macro_rules! sched_point {
+    ($point:literal) => {
+        #[cfg(test)]
+        $crate::common::sched::yield_point($point);
+    };
+}
+pub(crate) use sched_point;
+
+/// Decides which thread runs next whenever the thread it is installed on reaches a scheduling
+/// point.
+#[cfg(test)]
+pub(crate) trait SchedHook: Send + Sync {
+    fn yield_point(&self, point: &'static str);
+}
+
+#[cfg(test)]
+thread_local! {
+    static HOOK: RefCell<Option<Arc<dyn SchedHook>>> = const { RefCell::new(None) };
+}
+
+/// Installs the hook for the current thread, or removes it with `None`.
+#[cfg(test)]
+pub(crate) fn set_hook(hook: Option<Arc<dyn SchedHook>>) {
+    HOOK.with(|current| *current.borrow_mut() = hook);
+}
+
+/// Returns whether the current thread runs under a simulation, and must yield at a scheduling
+/// point rather than block on a condition variable.
+#[cfg(test)]
+pub(crate) fn is_simulated() -> bool {
+    HOOK.with(|current| current.borrow().is_some())
+}
+
+/// Hands control to the current thread's hook, if it has one.
+#[cfg(test)]
+pub(crate) fn yield_point(point: &'static str) {
+    let hook = HOOK.with(|current| current.borrow().clone());
+    if let Some(hook) = hook {
+        hook.yield_point(point);
+    }
+}
@@ -0,0 +1,22 @@
+//! Structured tracing of query execution and storage I/O, with the `trace` feature. Without
+//! it, the macros expand to nothing, and their arguments aren't evaluated.
+
+/// Enters a span until the end of the enclosing block. Takes the arguments of
+/// `tracing::info_span!`, e.g. `trace_span!("fetch_page", page_id)`.
+#[macro_export]
+macro_rules! trace_span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::info_span!($($args)*).entered();
+    };
+}
+
+/// Records an event in the current span. Takes the arguments of `tracing::info!`, e.g.
+/// `trace_event!(name: "evict", page_id)`.
+#[macro_export]
+macro_rules! trace_event {
+    ($($args:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::info!($($args)*);
+    };
+}
@@ -1,4 +1,4 @@
-use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
 use crate::storage::heap::TableHeap;
 use crate::storage::page::{Page, RecordId, TablePage};
 use crate::storage::tuple::{Row, TupleMetadata};
@@ -19,7 +19,10 @@ pub fn create_n_rows(
         .map(|row| {
             (
                 heap_file
-                    .insert_tuple(row.to_tuple(&table_schema).unwrap())
+                    .insert_tuple(
+                        TupleMetadata::new(false),
+                        row.to_tuple(&table_schema).unwrap(),
+                    )
                     .unwrap(),
                 row,
             )
@@ -119,11 +122,11 @@ pub fn create_random_full_page(schema: &Arc<Table>, seed: Option<u64>) -> TableP
         let tuple = row.to_tuple(schema).unwrap();
 
         let tuple_byte_size = tuple.data.len();
-        if payload_size + tuple_byte_size + 4 > RUSTY_DB_PAGE_SIZE_BYTES {
+        if payload_size + tuple_byte_size + TablePage::SLOT_BYTES > RUSTY_DB_PAGE_PAYLOAD_BYTES {
             break;
         }
         page.insert_tuple(TupleMetadata::new(false), tuple);
-        payload_size += tuple_byte_size + 4; // 4 bytes for tuple metadata;
+        payload_size += tuple_byte_size + TablePage::SLOT_BYTES; // for the tuple's slot
 
         // Make each tuple different
         local_seed += 1;
@@ -255,3 +258,122 @@ pub fn create_table_definition_mixed_fields(count: usize) -> Table {
     }
     table
 }
+
+#[cfg(feature = "trace")]
+pub use capture::{capture_trace, Trace, Traced};
+
+/// A subscriber recording the spans and events of the `trace` feature, so tests can assert on
+/// them.
+#[cfg(feature = "trace")]
+mod capture {
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// The spans and events recorded while running a function, in the order they were created.
+    #[derive(Clone, Debug, Default)]
+    pub struct Trace {
+        pub spans: Vec<Traced>,
+        pub events: Vec<Traced>,
+    }
+
+    impl Trace {
+        /// Returns the spans of the given name.
+        pub fn spans(&self, name: &str) -> Vec<&Traced> {
+            self.spans.iter().filter(|span| span.name == name).collect()
+        }
+
+        /// Returns the events of the given name.
+        pub fn events(&self, name: &str) -> Vec<&Traced> {
+            self.events
+                .iter()
+                .filter(|event| event.name == name)
+                .collect()
+        }
+    }
+
+    /// A span or event, with its fields formatted as strings. Strings aren't quoted.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Traced {
+        pub name: String,
+        pub fields: BTreeMap<String, String>,
+    }
+
+    impl Traced {
+        /// Returns the value of a field, if it was recorded.
+        pub fn field(&self, name: &str) -> Option<&str> {
+            self.fields.get(name).map(String::as_str)
+        }
+    }
+
+    impl Visit for Traced {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    /// Runs `f` with a subscriber on the current thread that records its spans and events.
+    /// Spans and events on other threads aren't recorded.
+    pub fn capture_trace<T>(f: impl FnOnce() -> T) -> (T, Trace) {
+        let subscriber = Capture::default();
+        let trace = subscriber.trace.clone();
+        let result = tracing::subscriber::with_default(subscriber, f);
+        let trace = trace.lock().unwrap().clone();
+        (result, trace)
+    }
+
+    #[derive(Default)]
+    struct Capture {
+        trace: Arc<Mutex<Trace>>,
+        next_id: AtomicU64,
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let mut span = Traced {
+                name: attributes.metadata().name().to_string(),
+                ..Traced::default()
+            };
+            attributes.record(&mut span);
+            self.trace.lock().unwrap().spans.push(span);
+            // Span IDs must be non-zero.
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, id: &Id, values: &Record<'_>) {
+            let mut trace = self.trace.lock().unwrap();
+            if let Some(span) = trace.spans.get_mut(id.into_u64() as usize - 1) {
+                values.record(span);
+            }
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut traced = Traced {
+                name: event.metadata().name().to_string(),
+                ..Traced::default()
+            };
+            event.record(&mut traced);
+            self.trace.lock().unwrap().events.push(traced);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+}
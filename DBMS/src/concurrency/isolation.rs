@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// How a transaction is isolated from the writes of concurrent transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IsolationLevel {
+    /// An alias for [`IsolationLevel::ReadCommitted`]. Uncommitted versions
+    /// are never visible to other transactions, so dirty reads can't happen.
+    ReadUncommitted,
+    /// Each statement sees the writes committed before it started, so
+    /// repeating a read within the transaction may return different rows.
+    ReadCommitted,
+    /// The whole transaction sees the writes committed before it began. Of two
+    /// concurrent writers of the same row, only the first to commit succeeds.
+    #[default]
+    SnapshotIsolation,
+    /// Snapshot isolation, plus a shared lock on every row read, held until
+    /// the transaction finishes. A transaction may then only write a row once
+    /// no concurrent transaction has read it, which rules out write skew
+    /// between existing rows. Inserted rows (phantoms) are not covered.
+    Serializable,
+}
+
+impl IsolationLevel {
+    /// Returns whether each statement takes a new snapshot.
+    pub fn snapshot_per_statement(self) -> bool {
+        matches!(self, Self::ReadUncommitted | Self::ReadCommitted)
+    }
+
+    /// Returns whether reads take shared locks on the rows they return.
+    pub fn takes_read_locks(self) -> bool {
+        self == Self::Serializable
+    }
+}
+
+impl std::fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ReadUncommitted => "READ UNCOMMITTED",
+            Self::ReadCommitted => "READ COMMITTED",
+            Self::SnapshotIsolation => "SNAPSHOT",
+            Self::Serializable => "SERIALIZABLE",
+        })
+    }
+}
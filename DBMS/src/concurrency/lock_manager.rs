@@ -0,0 +1,224 @@
+use crate::common::sched::sched_point;
+use crate::common::{Error, Result};
+use crate::config::config::LOCK_WAIT_TIMEOUT_MS;
+use crate::storage::page::RecordId;
+use crate::storage::simple::TxnId;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The mode a row lock is held in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Held by readers. Any number of transactions may share it.
+    Shared,
+    /// Held by a writer. No other transaction may hold any lock on the row.
+    Exclusive,
+}
+
+impl LockMode {
+    /// Returns whether two transactions may hold locks in these modes at once.
+    fn is_compatible(self, other: LockMode) -> bool {
+        self == LockMode::Shared && other == LockMode::Shared
+    }
+}
+
+/// A transaction's request for a lock on a row.
+#[derive(Debug)]
+struct LockRequest {
+    txn_id: TxnId,
+    mode: LockMode,
+    granted: bool,
+}
+
+/// The requests for a single row, in arrival order. Requests are granted in
+/// order, so the granted ones always come before those still waiting.
+#[derive(Debug, Default)]
+struct LockQueue {
+    requests: Vec<LockRequest>,
+    /// A shared holder waiting to upgrade its lock to exclusive. New requests
+    /// queue up behind it rather than starving it.
+    upgrading: Option<TxnId>,
+}
+
+impl LockQueue {
+    /// Returns the position of the transaction's request, if it has one.
+    fn position(&self, txn_id: TxnId) -> Option<usize> {
+        self.requests.iter().position(|r| r.txn_id == txn_id)
+    }
+
+    /// Returns whether the transaction's pending request can be granted.
+    fn grantable(&self, txn_id: TxnId) -> bool {
+        let Some(index) = self.position(txn_id) else {
+            return false;
+        };
+        if self.upgrading == Some(txn_id) {
+            // An upgrade waits until every other holder has released the row.
+            return self
+                .requests
+                .iter()
+                .all(|r| r.txn_id == txn_id || !r.granted);
+        }
+        let mode = self.requests[index].mode;
+        self.upgrading.is_none()
+            && self.requests[..index]
+                .iter()
+                .all(|r| r.granted && r.mode.is_compatible(mode))
+    }
+}
+
+/// All row locks, by row and by holder.
+#[derive(Debug, Default)]
+struct LockTable {
+    queues: HashMap<RecordId, LockQueue>,
+    /// The rows each transaction holds a lock on.
+    held: HashMap<TxnId, HashSet<RecordId>>,
+}
+
+/// Grants shared and exclusive row locks to transactions.
+///
+/// Each row has a FIFO queue of lock requests. A transaction asking for a lock
+/// that conflicts with one already held, or with a request that arrived
+/// earlier, blocks until the lock is granted or the wait timeout expires, in
+/// which case it gets [`Error::LockTimeout`]. Since locks are only released
+/// all at once by [`LockManager::unlock_all`], the timeout also breaks
+/// deadlocks.
+#[derive(Debug)]
+pub struct LockManager {
+    table: Mutex<LockTable>,
+    /// Notified whenever locks are released or a request gives up waiting.
+    waiters: Condvar,
+    /// How long a transaction waits for a lock before giving up.
+    timeout: Duration,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(LOCK_WAIT_TIMEOUT_MS))
+    }
+}
+
+impl LockManager {
+    /// Creates a lock manager whose lock waits give up after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            table: Mutex::new(LockTable::default()),
+            waiters: Condvar::new(),
+            timeout,
+        }
+    }
+
+    /// Returns how long a transaction waits for a lock before giving up.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Acquires a shared lock on the row for the transaction, blocking while
+    /// another transaction holds or is waiting for an exclusive lock on it.
+    pub fn lock_shared(&self, txn_id: TxnId, rid: &RecordId) -> Result<()> {
+        self.lock(txn_id, rid, LockMode::Shared)
+    }
+
+    /// Acquires an exclusive lock on the row for the transaction, blocking
+    /// while any other transaction holds a lock on it. A shared lock already
+    /// held by the transaction is upgraded.
+    pub fn lock_exclusive(&self, txn_id: TxnId, rid: &RecordId) -> Result<()> {
+        self.lock(txn_id, rid, LockMode::Exclusive)
+    }
+
+    /// Returns the mode the transaction holds its lock on the row in, if any.
+    pub fn lock_mode(&self, txn_id: TxnId, rid: &RecordId) -> Result<Option<LockMode>> {
+        let table = self.table.lock()?;
+        Ok(table.queues.get(rid).and_then(|queue| {
+            queue
+                .requests
+                .iter()
+                .find(|r| r.txn_id == txn_id && r.granted)
+                .map(|r| r.mode)
+        }))
+    }
+
+    /// Releases every lock held by the transaction, waking up any waiters.
+    pub fn unlock_all(&self, txn_id: TxnId) -> Result<()> {
+        sched_point!("lock.unlock_all");
+        let mut table = self.table.lock()?;
+        for rid in table.held.remove(&txn_id).unwrap_or_default() {
+            let Some(queue) = table.queues.get_mut(&rid) else {
+                continue;
+            };
+            queue.requests.retain(|r| r.txn_id != txn_id);
+            if queue.upgrading == Some(txn_id) {
+                queue.upgrading = None;
+            }
+            if queue.requests.is_empty() {
+                table.queues.remove(&rid);
+            }
+        }
+        drop(table);
+        self.waiters.notify_all();
+        Ok(())
+    }
+
+    /// Acquires a lock on the row in the given mode, waiting up to the timeout.
+    fn lock(&self, txn_id: TxnId, rid: &RecordId, mode: LockMode) -> Result<()> {
+        sched_point!("lock.acquire");
+        let mut table = self.table.lock()?;
+        let queue = table.queues.entry(rid.clone()).or_default();
+        match queue.position(txn_id).map(|i| &queue.requests[i]) {
+            // A held lock covers any request that isn't stronger than it.
+            Some(held) if held.mode == LockMode::Exclusive || mode == LockMode::Shared => {
+                return Ok(())
+            }
+            Some(_) if queue.upgrading.is_some() => {
+                // Two shared holders upgrading at once would wait on each
+                // other forever, so the later one gives up straight away.
+                return Err(Error::LockTimeout);
+            }
+            Some(_) => queue.upgrading = Some(txn_id),
+            None => queue.requests.push(LockRequest {
+                txn_id,
+                mode,
+                granted: false,
+            }),
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let queue = table.queues.get_mut(rid).expect("lock queue vanished");
+            if queue.grantable(txn_id) {
+                let index = queue.position(txn_id).unwrap();
+                queue.requests[index].mode = mode;
+                queue.requests[index].granted = true;
+                if queue.upgrading == Some(txn_id) {
+                    queue.upgrading = None;
+                }
+                table.held.entry(txn_id).or_default().insert(rid.clone());
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                // Withdraw the request, which may unblock requests behind it.
+                if queue.upgrading == Some(txn_id) {
+                    queue.upgrading = None;
+                } else {
+                    queue.requests.retain(|r| r.txn_id != txn_id);
+                    if queue.requests.is_empty() {
+                        table.queues.remove(rid);
+                    }
+                }
+                drop(table);
+                self.waiters.notify_all();
+                return Err(Error::LockTimeout);
+            }
+            #[cfg(test)]
+            if crate::common::sched::is_simulated() {
+                // A simulated holder only gets to release the lock once this thread yields.
+                drop(table);
+                sched_point!("lock.wait");
+                table = self.table.lock()?;
+                continue;
+            }
+            table = self.waiters.wait_timeout(table, deadline - now)?.0;
+        }
+    }
+}
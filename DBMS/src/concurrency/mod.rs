@@ -0,0 +1,11 @@
+mod isolation;
+mod lock_manager;
+#[cfg(test)]
+mod tests;
+mod transaction_manager;
+
+pub use isolation::IsolationLevel;
+pub use lock_manager::{LockManager, LockMode};
+pub use transaction_manager::{
+    TransactionEntry, TransactionInfo, TransactionManager, TransactionState,
+};
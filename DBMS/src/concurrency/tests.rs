@@ -0,0 +1,149 @@
+use crate::common::Error;
+use crate::concurrency::{LockManager, LockMode};
+use crate::storage::page::RecordId;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait before concluding that a lock request is blocked.
+const BLOCKED: Duration = Duration::from_millis(100);
+
+#[test]
+fn test_shared_locks_are_compatible() {
+    let locks = &LockManager::default();
+    let rid = &RecordId::new(1, 0);
+    locks.lock_shared(1, rid).unwrap();
+    locks.lock_shared(2, rid).unwrap();
+    assert_eq!(Some(LockMode::Shared), locks.lock_mode(1, rid).unwrap());
+    assert_eq!(Some(LockMode::Shared), locks.lock_mode(2, rid).unwrap());
+
+    // Re-acquiring a held lock, or a weaker one, is a no-op.
+    locks.lock_exclusive(3, &RecordId::new(1, 1)).unwrap();
+    locks.lock_shared(3, &RecordId::new(1, 1)).unwrap();
+    assert_eq!(
+        Some(LockMode::Exclusive),
+        locks.lock_mode(3, &RecordId::new(1, 1)).unwrap()
+    );
+}
+
+#[test]
+fn test_exclusive_locks_serialize() {
+    let locks = &LockManager::default();
+    let rid = &RecordId::new(1, 0);
+    locks.lock_exclusive(1, rid).unwrap();
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || {
+            locks.lock_exclusive(2, rid).unwrap();
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+        assert_eq!(None, locks.lock_mode(2, rid).unwrap());
+
+        locks.unlock_all(1).unwrap();
+        rx.recv().unwrap();
+    });
+    assert_eq!(None, locks.lock_mode(1, rid).unwrap());
+    assert_eq!(Some(LockMode::Exclusive), locks.lock_mode(2, rid).unwrap());
+}
+
+#[test]
+fn test_shared_waits_behind_exclusive() {
+    let locks = &LockManager::default();
+    let rid = &RecordId::new(1, 0);
+    locks.lock_shared(1, rid).unwrap();
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        let writer_tx = tx.clone();
+        s.spawn(move || {
+            locks.lock_exclusive(2, rid).unwrap();
+            writer_tx.send(2).unwrap();
+        });
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+
+        // A reader arriving after the waiting writer queues up behind it,
+        // even though its lock is compatible with the one currently held.
+        s.spawn(move || {
+            locks.lock_shared(3, rid).unwrap();
+            tx.send(3).unwrap();
+        });
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+
+        locks.unlock_all(1).unwrap();
+        assert_eq!(2, rx.recv().unwrap());
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+        locks.unlock_all(2).unwrap();
+        assert_eq!(3, rx.recv().unwrap());
+    });
+}
+
+#[test]
+fn test_upgrade() {
+    let locks = &LockManager::default();
+    let rid = &RecordId::new(1, 0);
+
+    // A sole shared holder upgrades immediately.
+    locks.lock_shared(1, rid).unwrap();
+    locks.lock_exclusive(1, rid).unwrap();
+    assert_eq!(Some(LockMode::Exclusive), locks.lock_mode(1, rid).unwrap());
+    locks.unlock_all(1).unwrap();
+
+    // Otherwise the upgrade waits for the other holders to release the row.
+    locks.lock_shared(1, rid).unwrap();
+    locks.lock_shared(2, rid).unwrap();
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || {
+            locks.lock_exclusive(1, rid).unwrap();
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+        assert_eq!(Some(LockMode::Shared), locks.lock_mode(1, rid).unwrap());
+
+        locks.unlock_all(2).unwrap();
+        rx.recv().unwrap();
+    });
+    assert_eq!(Some(LockMode::Exclusive), locks.lock_mode(1, rid).unwrap());
+}
+
+#[test]
+fn test_concurrent_upgrades_do_not_deadlock() {
+    let locks = &LockManager::default();
+    let rid = &RecordId::new(1, 0);
+    locks.lock_shared(1, rid).unwrap();
+    locks.lock_shared(2, rid).unwrap();
+
+    thread::scope(|s| {
+        let first = s.spawn(move || locks.lock_exclusive(1, rid));
+        thread::sleep(BLOCKED);
+        assert_eq!(Err(Error::LockTimeout), locks.lock_exclusive(2, rid));
+        locks.unlock_all(2).unwrap();
+        first.join().unwrap().unwrap();
+    });
+}
+
+#[test]
+fn test_timeout() {
+    let timeout = Duration::from_millis(200);
+    let locks = &LockManager::new(timeout);
+    let rid = &RecordId::new(1, 0);
+    locks.lock_exclusive(1, rid).unwrap();
+
+    for mode in [LockMode::Shared, LockMode::Exclusive] {
+        let start = Instant::now();
+        let result = match mode {
+            LockMode::Shared => locks.lock_shared(2, rid),
+            LockMode::Exclusive => locks.lock_exclusive(2, rid),
+        };
+        assert_eq!(Err(Error::LockTimeout), result);
+        assert!(start.elapsed() >= timeout);
+        assert!(start.elapsed() < timeout * 10);
+    }
+
+    // The timed out requests were withdrawn, so they don't hold up others.
+    locks.unlock_all(1).unwrap();
+    locks.lock_exclusive(3, rid).unwrap();
+    assert_eq!(None, locks.lock_mode(2, rid).unwrap());
+}
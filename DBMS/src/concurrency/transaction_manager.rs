@@ -0,0 +1,183 @@
+use crate::common::Result;
+use crate::concurrency::IsolationLevel;
+use crate::storage::simple::TxnId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// What a registered transaction is currently doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionState {
+    /// Running statements.
+    Active,
+    /// Checking for conflicts and logging its commit.
+    Committing,
+    /// Undoing its writes.
+    Aborting,
+    /// Prepared for two-phase commit, waiting for the coordinator to commit
+    /// or roll it back.
+    Prepared,
+}
+
+impl std::fmt::Display for TransactionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Active => "ACTIVE",
+            Self::Committing => "COMMITTING",
+            Self::Aborting => "ABORTING",
+            Self::Prepared => "PREPARED",
+        })
+    }
+}
+
+/// A point-in-time copy of a registered transaction's entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionInfo {
+    pub id: TxnId,
+    /// When the transaction began.
+    pub started: SystemTime,
+    pub isolation: IsolationLevel,
+    pub read_only: bool,
+    pub state: TransactionState,
+    /// Rows returned to the transaction by reads and scans.
+    pub rows_read: u64,
+    /// Rows inserted, updated or deleted by the transaction.
+    pub rows_written: u64,
+    /// Whether the transaction has been asked to terminate, but hasn't
+    /// noticed yet.
+    pub terminated: bool,
+}
+
+/// A running transaction's entry in the registry. The transaction holds on
+/// to it to report its progress without going through the registry's mutex.
+#[derive(Debug)]
+pub struct TransactionEntry {
+    id: TxnId,
+    started: SystemTime,
+    isolation: IsolationLevel,
+    read_only: bool,
+    state: Mutex<TransactionState>,
+    rows_read: AtomicU64,
+    rows_written: AtomicU64,
+    terminated: AtomicBool,
+}
+
+impl TransactionEntry {
+    /// Returns the transaction's id.
+    pub fn id(&self) -> TxnId {
+        self.id
+    }
+
+    /// Moves the transaction into the given state.
+    pub fn set_state(&self, state: TransactionState) -> Result<()> {
+        *self.state.lock()? = state;
+        Ok(())
+    }
+
+    /// Counts rows returned to the transaction.
+    pub fn record_reads(&self, rows: u64) {
+        self.rows_read.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    /// Counts rows written by the transaction.
+    pub fn record_writes(&self, rows: u64) {
+        self.rows_written.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    /// Returns whether the transaction has been asked to terminate.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::Acquire)
+    }
+
+    /// Returns a copy of the entry.
+    pub fn info(&self) -> Result<TransactionInfo> {
+        Ok(TransactionInfo {
+            id: self.id,
+            started: self.started,
+            isolation: self.isolation,
+            read_only: self.read_only,
+            state: *self.state.lock()?,
+            rows_read: self.rows_read.load(Ordering::Relaxed),
+            rows_written: self.rows_written.load(Ordering::Relaxed),
+            terminated: self.is_terminated(),
+        })
+    }
+}
+
+/// A registry of the transactions that have begun but not yet finished, for
+/// introspection and for terminating runaway transactions.
+///
+/// Transactions register themselves when they begin and unregister once they
+/// have committed or rolled back. Terminating a transaction only flags it:
+/// its next operation fails with [`crate::common::Error::Cancelled`] and
+/// rolls it back.
+#[derive(Debug, Default)]
+pub struct TransactionManager {
+    txns: Mutex<BTreeMap<TxnId, Arc<TransactionEntry>>>,
+}
+
+impl TransactionManager {
+    /// Registers a transaction that has just begun, returning its entry.
+    pub fn register(
+        &self,
+        id: TxnId,
+        isolation: IsolationLevel,
+        read_only: bool,
+    ) -> Result<Arc<TransactionEntry>> {
+        let entry = Arc::new(TransactionEntry {
+            id,
+            started: SystemTime::now(),
+            isolation,
+            read_only,
+            state: Mutex::new(TransactionState::Active),
+            rows_read: AtomicU64::new(0),
+            rows_written: AtomicU64::new(0),
+            terminated: AtomicBool::new(false),
+        });
+        self.txns.lock()?.insert(id, Arc::clone(&entry));
+        Ok(entry)
+    }
+
+    /// Removes a finished transaction from the registry.
+    pub fn unregister(&self, id: TxnId) -> Result<()> {
+        self.txns.lock()?.remove(&id);
+        Ok(())
+    }
+
+    /// Forgets every registered transaction, e.g. after a crash.
+    pub fn clear(&self) -> Result<()> {
+        self.txns.lock()?.clear();
+        Ok(())
+    }
+
+    /// Returns the registered transactions, in id order.
+    pub fn list_transactions(&self) -> Result<Vec<TransactionInfo>> {
+        self.txns
+            .lock()?
+            .values()
+            .map(|entry| entry.info())
+            .collect()
+    }
+
+    /// Returns the registered transaction with the given id, if any.
+    pub fn get(&self, id: TxnId) -> Result<Option<TransactionInfo>> {
+        self.txns
+            .lock()?
+            .get(&id)
+            .map(|entry| entry.info())
+            .transpose()
+    }
+
+    /// Flags the transaction for termination. Returns whether it was
+    /// registered.
+    pub fn terminate(&self, id: TxnId) -> Result<bool> {
+        let txns = self.txns.lock()?;
+        let Some(entry) = txns.get(&id) else {
+            return Ok(false);
+        };
+        entry.terminated.store(true, Ordering::Release);
+        Ok(true)
+    }
+}
@@ -1,4 +1,43 @@
 pub const RUSTY_DB_PAGE_SIZE_BYTES: usize = 4096;
+// the bytes of each page after its contents, reserved for the disk manager to keep the write counter
+// and authentication tag of an encrypted page in
+pub const RUSTY_DB_PAGE_TRAILER_BYTES: usize = 24;
+// the bytes of each page left to its contents, the last 4 on disk holding a CRC32 of the others
+pub const RUSTY_DB_PAGE_PAYLOAD_BYTES: usize =
+    RUSTY_DB_PAGE_SIZE_BYTES - RUSTY_DB_PAGE_TRAILER_BYTES - 4;
 pub const MAX_STRING_LENGTH: usize = 2048;
 // relative path from the project root, i.e., the root of the repository that contains `cargo.toml`
 pub const RUST_DB_DATA_DIR: &str = "data";
+// how long a transaction waits for a row lock before giving up
+pub const LOCK_WAIT_TIMEOUT_MS: u64 = 1000;
+// how often the console takes a checkpoint in the background
+pub const CHECKPOINT_INTERVAL_MS: u64 = 60_000;
+// how often the console vacuums every table in the background
+pub const VACUUM_INTERVAL_MS: u64 = 60_000;
+// the longest text key, in bytes, an index stores for a column declared without a length bound
+pub const MAX_INDEX_KEY_LENGTH: usize = 256;
+// how many pages ahead of the page it is on a table scan reads into the buffer pool
+pub const SCAN_READAHEAD_PAGES: usize = 8;
+// the most pages the disk manager keeps in memory after reading them ahead of time
+pub const READ_AHEAD_PAGES: usize = 64;
+// how many pages a backup copies at a time, holding up writes to the database file meanwhile
+pub const BACKUP_CHUNK_PAGES: usize = 64;
+// the percentage of each table page inserts fill, unless the table sets its own
+pub const DEFAULT_FILL_FACTOR: u8 = 100;
+// the lowest fill factor a table may set
+pub const MIN_FILL_FACTOR: u8 = 10;
+// the number of pages the buffer pool of a `Database` holds unless told otherwise
+pub const DEFAULT_POOL_SIZE: usize = 500;
+// the K of the LRU-K replacer of a `Database`, unless told otherwise
+pub const DEFAULT_REPLACER_K: usize = 15;
+// the address the server listens on unless told otherwise
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:9605";
+// the number of plans the SQL engine caches, keyed by normalized statement text
+pub const PLAN_CACHE_SIZE: usize = 128;
+// the most times a `Database` runs a statement or transaction that keeps failing with a retryable
+// error, unless told otherwise
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 5;
+// how long a `Database` waits before its first retry, doubling the wait after each one
+pub const RETRY_INITIAL_BACKOFF_MS: u64 = 5;
+// the longest a `Database` waits between retries
+pub const RETRY_MAX_BACKOFF_MS: u64 = 200;
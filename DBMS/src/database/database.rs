@@ -0,0 +1,470 @@
+use super::de::{from_row, with_context};
+use crate::common::{Error, Metrics, Result};
+use crate::config::config::{
+    DEFAULT_POOL_SIZE, DEFAULT_REPLACER_K, DEFAULT_RETRY_ATTEMPTS, RETRY_INITIAL_BACKOFF_MS,
+    RETRY_MAX_BACKOFF_MS,
+};
+use crate::errinput;
+use crate::sql::engine::{Engine as _, Local, Session, StatementResult};
+use crate::sql::parser::{ast, Parser};
+use crate::storage::backup::{BackupInfo, Manifest, DATA_FILE};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::tuple::Row;
+use crate::storage::wal::SyncPolicy;
+use crate::storage::HeapTableManager;
+use crate::types::field::Label;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// A database file, opened with the disk manager, buffer pool and SQL engine that serve it.
+///
+/// Statements run with [`Self::execute`] and [`Self::query`] commit on their own, and those of
+/// [`Self::transaction`] together. Dropping the database, or closing it with [`Self::close`],
+/// takes a checkpoint, which writes every modified page to the file.
+///
+/// Like the rest of the engine, the catalog is kept in memory: tables last as long as the
+/// `Database` that created them. Backups are the exception, see [`Self::backup_to`]: they record
+/// the catalog, and a database opened from one keeps recording it as it closes.
+///
+/// ```
+/// use rustydb::database::{Database, Options};
+/// use rustydb::types::field::Field;
+///
+/// # let dir = tempfile::tempdir()?;
+/// let db = Database::open(dir.path().join("example"), Options::default())?;
+/// db.execute("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)")?;
+/// db.execute("INSERT INTO users VALUES (1, 'ada'), (2, 'grace')")?;
+///
+/// let names: Vec<Field> = db
+///     .query("SELECT name FROM users ORDER BY id")?
+///     .map(|row| row.iter().next().unwrap().clone())
+///     .collect();
+/// assert_eq!(vec![Field::from("ada".to_string()), Field::from("grace".to_string())], names);
+/// db.close()?;
+/// # Ok::<(), rustydb::common::Error>(())
+/// ```
+pub struct Database {
+    engine: Local<HeapTableManager>,
+    /// The registry the engine and its storage count in.
+    metrics: Arc<Metrics>,
+    /// Whether [`Self::close`] took the final checkpoint, which dropping then skips.
+    closed: bool,
+    /// The backup directory the database was opened from, whose manifest the final checkpoint
+    /// updates.
+    backup_dir: Option<PathBuf>,
+}
+
+/// How [`Database::open`] sets up the engine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    /// The number of pages the buffer pool holds.
+    pub pool_size: usize,
+    /// The K of the buffer pool's LRU-K replacer.
+    pub replacer_k: usize,
+    /// When committing transactions force the write-ahead log to disk.
+    pub sync_policy: SyncPolicy,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            pool_size: DEFAULT_POOL_SIZE,
+            replacer_k: DEFAULT_REPLACER_K,
+            sync_policy: SyncPolicy::default(),
+        }
+    }
+}
+
+/// How [`Database::execute_with_retry`] and [`Database::transaction_with_retry`] retry a
+/// statement or transaction that failed with [`Error::Serialization`], because a concurrent
+/// writer won, or [`Error::LockTimeout`], which is how the engine breaks deadlocks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The most times to run the statement or transaction, including the first.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. The wait doubles after each one.
+    pub initial_backoff: Duration,
+    /// The longest wait between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_RETRY_ATTEMPTS,
+            initial_backoff: Duration::from_millis(RETRY_INITIAL_BACKOFF_MS),
+            max_backoff: Duration::from_millis(RETRY_MAX_BACKOFF_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Runs `f` until it succeeds, fails with an error that isn't retryable, or has run
+    /// `max_attempts` times, in which case the last error is wrapped in
+    /// [`Error::RetriesExhausted`]. Each retry is counted in `metrics`.
+    fn run<T>(&self, metrics: &Metrics, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut backoff = self.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match f() {
+                Err(error @ (Error::Serialization | Error::LockTimeout)) => {
+                    if attempts >= self.max_attempts {
+                        return Err(Error::RetriesExhausted {
+                            attempts,
+                            error: Box::new(error),
+                        });
+                    }
+                }
+                result => return result,
+            }
+            metrics.statement_retries.incr();
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+impl Database {
+    /// Opens the database file at `path`, creating it if it doesn't exist. Its write-ahead log
+    /// lives next to it, at `path` with `.wal` appended.
+    ///
+    /// `path` may also be a directory written by [`Self::backup_to`], in which case the database
+    /// is restored from the backup, in place: its tables are brought back to the point the
+    /// backup ends at.
+    pub fn open(path: impl AsRef<Path>, options: Options) -> Result<Self> {
+        if options.pool_size == 0 || options.replacer_k == 0 {
+            return errinput!("the pool size and the replacer's K must be positive");
+        }
+        let path = path.as_ref();
+        let backup_dir = Manifest::exists(path).then(|| path.to_path_buf());
+        let disk_manager = match &backup_dir {
+            Some(dir) => DiskManager::open(&dir.join(DATA_FILE))?,
+            None => DiskManager::open(path)?,
+        };
+        let metrics = Arc::new(Metrics::default());
+        let bpm = BufferPoolManager::builder()
+            .disk_manager(Arc::new(RwLock::new(disk_manager)))
+            .pool_size(options.pool_size)
+            .replacer_k(options.replacer_k)
+            .sync_policy(options.sync_policy)
+            .metrics(Arc::clone(&metrics))
+            .build_with_handle();
+        let engine = Local::new(HeapTableManager::new(&bpm)).with_metrics(Arc::clone(&metrics));
+        if let Some(dir) = &backup_dir {
+            engine.simple.restore(dir)?;
+        }
+        Ok(Self {
+            engine,
+            metrics,
+            closed: false,
+            backup_dir,
+        })
+    }
+
+    /// Executes a statement in a transaction of its own, which commits if the statement
+    /// succeeds. BEGIN, COMMIT and ROLLBACK are refused: see [`Self::transaction`].
+    pub fn execute(&self, statement: &str) -> Result<StatementResult> {
+        refuse_transaction_control(&self.metrics, statement)?;
+        self.engine.session().execute(statement)
+    }
+
+    /// Executes a statement like [`Self::execute`], running it again as the policy allows if it
+    /// fails with a serialization failure or lock timeout. Since the statement commits on its
+    /// own, a failed attempt was rolled back entirely, and running it again is safe.
+    pub fn execute_with_retry(
+        &self,
+        statement: &str,
+        policy: RetryPolicy,
+    ) -> Result<StatementResult> {
+        refuse_transaction_control(&self.metrics, statement)?;
+        policy.run(&self.metrics, || self.engine.session().execute(statement))
+    }
+
+    /// Runs a SELECT query in a transaction of its own, returning its rows.
+    pub fn query(&self, query: &str) -> Result<QueryResult> {
+        run_query(&mut self.engine.session(), &self.metrics, query)
+    }
+
+    /// Runs a SELECT query in a transaction of its own, deserializing its rows into `T`s as
+    /// [`super::from_row`] does.
+    ///
+    /// ```
+    /// use rustydb::database::{Database, Options};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct User {
+    ///     id: i32,
+    ///     name: String,
+    ///     age: Option<u8>,
+    /// }
+    ///
+    /// # let dir = tempfile::tempdir()?;
+    /// let db = Database::open(dir.path().join("example"), Options::default())?;
+    /// db.execute("CREATE TABLE users (id INT PRIMARY KEY, name TEXT, age INT NULL)")?;
+    /// db.execute("INSERT INTO users VALUES (1, 'ada', 36)")?;
+    ///
+    /// let users: Vec<User> = db.query_as("SELECT id, name, age FROM users")?;
+    /// assert_eq!(vec![User { id: 1, name: "ada".to_string(), age: Some(36) }], users);
+    /// # Ok::<(), rustydb::common::Error>(())
+    /// ```
+    pub fn query_as<T: DeserializeOwned>(&self, query: &str) -> Result<Vec<T>> {
+        self.query(query)?.deserialize()
+    }
+
+    /// Runs a closure in a transaction, which commits if the closure returns Ok, and rolls back
+    /// if it returns an error. Statements that fail don't end the transaction by themselves.
+    ///
+    /// ```
+    /// use rustydb::database::{Database, Options};
+    /// use rustydb::common::Error;
+    ///
+    /// # let dir = tempfile::tempdir()?;
+    /// let db = Database::open(dir.path().join("example"), Options::default())?;
+    /// db.execute("CREATE TABLE accounts (id INT PRIMARY KEY, balance INT)")?;
+    /// db.execute("INSERT INTO accounts VALUES (1, 100), (2, 0)")?;
+    ///
+    /// let transfer = |amount: i32| {
+    ///     db.transaction(|txn| {
+    ///         let add = |id, change| {
+    ///             format!("UPDATE accounts SET balance = balance + {change} WHERE id = {id}")
+    ///         };
+    ///         txn.execute(&add(1, -amount))?;
+    ///         txn.execute(&add(2, amount))?;
+    ///         let overdrawn = txn.query("SELECT id FROM accounts WHERE balance < 0")?.count();
+    ///         match overdrawn {
+    ///             0 => Ok(()),
+    ///             _ => Err(Error::InvalidInput("insufficient funds".to_string())),
+    ///         }
+    ///     })
+    /// };
+    /// transfer(30)?;
+    /// assert!(transfer(500).is_err());
+    /// assert_eq!(1, db.query("SELECT id FROM accounts WHERE balance = 30")?.count());
+    /// # Ok::<(), rustydb::common::Error>(())
+    /// ```
+    pub fn transaction<T>(&self, f: impl FnOnce(&mut Transaction) -> Result<T>) -> Result<T> {
+        let mut txn = Transaction {
+            session: self.engine.session(),
+            metrics: &self.metrics,
+        };
+        txn.session.execute("BEGIN")?;
+        match f(&mut txn) {
+            Ok(value) => {
+                txn.session.execute("COMMIT")?;
+                Ok(value)
+            }
+            Err(error) => {
+                txn.session.execute("ROLLBACK")?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Runs a closure in a transaction like [`Self::transaction`], rolling back and running the
+    /// whole closure again in a new transaction, as the policy allows, if it fails with a
+    /// serialization failure or lock timeout. Statements are never replayed within a
+    /// transaction, so the closure must be safe to run more than once: any effect it has besides
+    /// its statements happens for every attempt.
+    ///
+    /// ```
+    /// use rustydb::database::{Database, Options, RetryPolicy};
+    ///
+    /// # let dir = tempfile::tempdir()?;
+    /// let db = Database::open(dir.path().join("example"), Options::default())?;
+    /// db.execute("CREATE TABLE counters (id INT PRIMARY KEY, value INT)")?;
+    /// db.execute("INSERT INTO counters VALUES (1, 0)")?;
+    ///
+    /// db.transaction_with_retry(RetryPolicy::default(), |txn| {
+    ///     txn.execute("UPDATE counters SET value = value + 1 WHERE id = 1")
+    /// })?;
+    /// assert_eq!(1, db.query("SELECT id FROM counters WHERE value = 1")?.count());
+    /// # Ok::<(), rustydb::common::Error>(())
+    /// ```
+    pub fn transaction_with_retry<T>(
+        &self,
+        policy: RetryPolicy,
+        mut f: impl FnMut(&mut Transaction) -> Result<T>,
+    ) -> Result<T> {
+        policy.run(&self.metrics, || self.transaction(&mut f))
+    }
+
+    /// Returns the registry counting the database's activity, from page fetches and disk I/O to
+    /// the statements run, e.g. for a server to expose with [`Metrics::to_prometheus_text`].
+    ///
+    /// ```
+    /// use rustydb::database::{Database, Options};
+    ///
+    /// # let dir = tempfile::tempdir()?;
+    /// let db = Database::open(dir.path().join("example"), Options::default())?;
+    /// db.execute("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)")?;
+    /// db.execute("INSERT INTO users VALUES (1, 'ada')")?;
+    /// db.query("SELECT * FROM users")?;
+    ///
+    /// let snapshot = db.metrics().snapshot();
+    /// assert_eq!((1, 1), (snapshot.statements.inserts, snapshot.statements.selects));
+    /// # Ok::<(), rustydb::common::Error>(())
+    /// ```
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Backs the database up into the directory `path`, creating it if it doesn't exist, while
+    /// statements keep running. [`Self::open`] restores the backup: the database as it was when
+    /// the backup finished, with the transactions that had committed by then, and no others.
+    ///
+    /// ```
+    /// use rustydb::database::{Database, Options};
+    ///
+    /// # let dir = tempfile::tempdir()?;
+    /// let db = Database::open(dir.path().join("example"), Options::default())?;
+    /// db.execute("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)")?;
+    /// db.execute("INSERT INTO users VALUES (1, 'ada')")?;
+    /// db.backup_to(&dir.path().join("backup"))?;
+    /// db.execute("INSERT INTO users VALUES (2, 'grace')")?;
+    ///
+    /// let restored = Database::open(dir.path().join("backup"), Options::default())?;
+    /// assert_eq!(1, restored.query("SELECT * FROM users")?.count());
+    /// # Ok::<(), rustydb::common::Error>(())
+    /// ```
+    pub fn backup_to(&self, path: &Path) -> Result<BackupInfo> {
+        self.engine.simple.backup(path)
+    }
+
+    /// Closes the database, taking a checkpoint. Unlike dropping it, returns the error if the
+    /// checkpoint fails.
+    pub fn close(mut self) -> Result<()> {
+        self.checkpoint()?;
+        self.closed = true;
+        Ok(())
+    }
+
+    /// Takes the final checkpoint, recording the catalog in the manifest of the backup the
+    /// database was opened from, if any.
+    fn checkpoint(&self) -> Result<()> {
+        match &self.backup_dir {
+            Some(dir) => self.engine.simple.save_manifest(dir),
+            None => self.engine.checkpoint().map(|_| ()),
+        }
+    }
+}
+
+impl Drop for Database {
+    /// Takes a checkpoint, unless [`Database::close`] did.
+    fn drop(&mut self) {
+        if !self.closed {
+            self.checkpoint().ok();
+        }
+    }
+}
+
+/// The transaction of a [`Database::transaction`] closure.
+pub struct Transaction<'a> {
+    session: Session<'a, Local<HeapTableManager>>,
+    metrics: &'a Metrics,
+}
+
+impl Transaction<'_> {
+    /// Executes a statement in the transaction. BEGIN, COMMIT and ROLLBACK are refused: the
+    /// transaction ends when the closure returns.
+    pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
+        refuse_transaction_control(self.metrics, statement)?;
+        self.session.execute(statement)
+    }
+
+    /// Runs a SELECT query in the transaction, returning its rows.
+    pub fn query(&mut self, query: &str) -> Result<QueryResult> {
+        run_query(&mut self.session, self.metrics, query)
+    }
+
+    /// Runs a SELECT query in the transaction, deserializing its rows into `T`s.
+    pub fn query_as<T: DeserializeOwned>(&mut self, query: &str) -> Result<Vec<T>> {
+        self.query(query)?.deserialize()
+    }
+}
+
+/// The rows of a query, which iterating over returns. They belong to the result, which may
+/// outlive the database.
+#[derive(Debug)]
+pub struct QueryResult {
+    columns: Vec<Label>,
+    rows: std::vec::IntoIter<Row>,
+}
+
+impl QueryResult {
+    /// Returns the labels of the result's columns.
+    pub fn columns(&self) -> &[Label] {
+        &self.columns
+    }
+
+    /// Deserializes the remaining rows into `T`s as [`super::from_row`] does. Errors name the
+    /// row, counting from 1.
+    pub fn deserialize<T: DeserializeOwned>(self) -> Result<Vec<T>> {
+        let columns = self.columns;
+        self.rows
+            .enumerate()
+            .map(|(i, row)| {
+                from_row(&columns, row)
+                    .map_err(|error| with_context(error, format!("row {}", i + 1)))
+            })
+            .collect()
+    }
+}
+
+impl Iterator for QueryResult {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        self.rows.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl ExactSizeIterator for QueryResult {}
+
+/// Errors if the statement would begin or end a transaction, or doesn't parse. The error is
+/// counted in `metrics`, since the statement never reaches a session to be counted by.
+fn refuse_transaction_control(metrics: &Metrics, statement: &str) -> Result<()> {
+    let refused = match Parser::new(statement).parse() {
+        Ok(ast::Statement::Begin { .. } | ast::Statement::Commit | ast::Statement::Rollback) => {
+            errinput!("transactions begin and end with Database::transaction")
+        }
+        Ok(_) => return Ok(()),
+        Err(error) => Err(error),
+    };
+    metrics.statement_errors.incr();
+    refused
+}
+
+/// Runs a query in the session, refusing statements other than SELECT before they run, and
+/// counting those in `metrics`.
+fn run_query(
+    session: &mut Session<Local<HeapTableManager>>,
+    metrics: &Metrics,
+    query: &str,
+) -> Result<QueryResult> {
+    let refused = match Parser::new(query).parse() {
+        Ok(ast::Statement::Select { .. }) => None,
+        Ok(_) => Some(errinput!("only SELECT statements return rows")),
+        Err(error) => Some(Err(error)),
+    };
+    if let Some(refused) = refused {
+        metrics.statement_errors.incr();
+        return refused;
+    }
+    match session.execute(query)? {
+        StatementResult::Select { columns, rows } => Ok(QueryResult {
+            columns,
+            rows: rows.into_iter(),
+        }),
+        result => errinput!("unexpected result {result:?}"),
+    }
+}
@@ -0,0 +1,501 @@
+use crate::common::{Error, Result};
+use crate::storage::tuple::Row;
+use crate::types::field::{Field, Label};
+use serde::de::value::{StrDeserializer, StringDeserializer};
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, EnumAccess, Error as _, Expected, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::fmt::Display;
+
+/// Deserializes a row of a query result, whose columns have the given labels, into a `T`:
+///
+/// * A struct takes its fields from the columns of the same name, ignoring case. Every field
+///   needs exactly one column, and every column a field.
+/// * A map takes the column names as keys.
+/// * A tuple or sequence, e.g. `Vec<Field>`, takes the columns in order.
+/// * Anything else, e.g. an `i64` or an `Option<String>`, takes the row's only column.
+///
+/// Integers convert to any integer type they fit in, floats to `f32` and `f64`, and NULL to
+/// `None`. [`Field`] deserializes from any column.
+pub fn from_row<T: DeserializeOwned>(columns: &[Label], row: Row) -> Result<T> {
+    if columns.len() != row.size() {
+        return Err(Error::InvalidData(format!(
+            "{} column labels for {} values",
+            columns.len(),
+            row.size()
+        )));
+    }
+    T::deserialize(RowDeserializer {
+        columns,
+        values: row.into_iter().collect(),
+    })
+}
+
+/// Prefixes a deserialization error with e.g. the row or column it occurred at.
+pub(super) fn with_context(error: Error, context: impl Display) -> Error {
+    match error {
+        Error::InvalidData(message) => Error::InvalidData(format!("{context}: {message}")),
+        error => error,
+    }
+}
+
+/// Deserializes a whole row.
+struct RowDeserializer<'a> {
+    columns: &'a [Label],
+    values: Vec<Field>,
+}
+
+impl<'a> RowDeserializer<'a> {
+    /// Returns the row's only column, for types that deserialize from a single value.
+    fn into_column(self) -> Result<ColumnDeserializer> {
+        match <[Field; 1]>::try_from(self.values) {
+            Ok([value]) => Ok(ColumnDeserializer {
+                name: self.columns[0].as_header().to_string(),
+                value: FieldDeserializer(value),
+            }),
+            Err(values) => Err(Error::custom(format!(
+                "expected 1 column, got {}",
+                values.len()
+            ))),
+        }
+    }
+
+    /// Pairs the values with their column names.
+    fn into_columns(self) -> impl ExactSizeIterator<Item = (String, Field)> + 'a {
+        let names = self
+            .columns
+            .iter()
+            .map(|label| label.as_header().to_string());
+        names.zip(self.values)
+    }
+}
+
+/// Forwards row deserializer methods to the row's only column.
+macro_rules! forward_to_column {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            self.into_column()?.deserialize(|value| value.$method(visitor))
+        }
+    )*};
+}
+
+impl<'de> serde::Deserializer<'de> for RowDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(ColumnsAccess::new(self.into_columns()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        if len != self.values.len() {
+            return Err(Error::custom(format!(
+                "expected {len} columns, got {}",
+                self.values.len()
+            )));
+        }
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(ColumnsAccess::new(self.into_columns()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        // Match every column to a field, and key the column by its field's exact name.
+        let mut columns = Vec::with_capacity(fields.len());
+        let mut extra = Vec::new();
+        for (name, value) in self.into_columns() {
+            match fields
+                .iter()
+                .find(|field| field.eq_ignore_ascii_case(&name))
+            {
+                Some(field) if columns.iter().any(|(f, _)| f == field) => {
+                    return Err(Error::custom(format!(
+                        "more than one column for field {field}"
+                    )))
+                }
+                Some(field) => columns.push((field.to_string(), value)),
+                None => extra.push(name),
+            }
+        }
+        let missing: Vec<_> = fields
+            .iter()
+            .filter(|field| !columns.iter().any(|(f, _)| f == *field))
+            .collect();
+        let mut problems = Vec::new();
+        if !missing.is_empty() {
+            problems.push(format!("no column for fields {}", join(&missing)));
+        }
+        if !extra.is_empty() {
+            problems.push(format!("no field for columns {}", join(&extra)));
+        }
+        if !problems.is_empty() {
+            return Err(Error::custom(problems.join("; ")));
+        }
+        visitor.visit_map(ColumnsAccess::new(columns.into_iter()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.into_column()?
+            .deserialize(|value| value.deserialize_enum(name, variants, visitor))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.into_column()?
+            .deserialize(|value| value.deserialize_unit_struct(name, visitor))
+    }
+
+    forward_to_column! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_option
+        deserialize_unit deserialize_identifier
+    }
+
+    forward_to_deserialize_any! { ignored_any }
+}
+
+/// A column's value, and its name for error messages.
+struct ColumnDeserializer {
+    name: String,
+    value: FieldDeserializer,
+}
+
+impl ColumnDeserializer {
+    /// Deserializes the value, naming the column in errors.
+    fn deserialize<T>(self, f: impl FnOnce(FieldDeserializer) -> Result<T>) -> Result<T> {
+        let name = self.name;
+        f(self.value).map_err(|error| with_context(error, format!("column {name}")))
+    }
+}
+
+/// Hands out the columns of a row, as a sequence of values or a map keyed by name.
+struct ColumnsAccess<I: Iterator<Item = (String, Field)>> {
+    columns: I,
+    len: usize,
+    /// The column whose key the map access returned last, and whose value it returns next.
+    next: Option<ColumnDeserializer>,
+}
+
+impl<I: ExactSizeIterator<Item = (String, Field)>> ColumnsAccess<I> {
+    fn new(columns: I) -> Self {
+        Self {
+            len: columns.len(),
+            columns,
+            next: None,
+        }
+    }
+}
+
+impl<'de, I: Iterator<Item = (String, Field)>> SeqAccess<'de> for ColumnsAccess<I> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        let Some((name, value)) = self.columns.next() else {
+            return Ok(None);
+        };
+        self.len -= 1;
+        let column = ColumnDeserializer {
+            name,
+            value: FieldDeserializer(value),
+        };
+        column
+            .deserialize(|value| seed.deserialize(value))
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, I: Iterator<Item = (String, Field)>> MapAccess<'de> for ColumnsAccess<I> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((name, value)) = self.columns.next() else {
+            return Ok(None);
+        };
+        self.len -= 1;
+        let key = seed.deserialize(StringDeserializer::<Error>::new(name.clone()))?;
+        self.next = Some(ColumnDeserializer {
+            name,
+            value: FieldDeserializer(value),
+        });
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let column = self
+            .next
+            .take()
+            .ok_or_else(|| Error::custom("value requested before its key"))?;
+        column.deserialize(|value| seed.deserialize(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+/// Deserializes a single value.
+struct FieldDeserializer(Field);
+
+impl FieldDeserializer {
+    /// Returns an error for a value the visitor doesn't expect.
+    fn invalid_type(&self, expected: &dyn Expected) -> Error {
+        let unexpected = match &self.0 {
+            Field::Null => Unexpected::Other("NULL"),
+            Field::Boolean(boolean) => Unexpected::Bool(*boolean),
+            Field::Integer(integer) => Unexpected::Signed((*integer).into()),
+            Field::Float(float) => Unexpected::Float((*float).into()),
+            Field::String(string) => Unexpected::Str(string),
+        };
+        Error::invalid_type(unexpected, expected)
+    }
+}
+
+/// Deserializes integers into integer types they may not fit in.
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident: $type:ty,)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let Field::Integer(integer) = self.0 else {
+                return Err(self.invalid_type(&visitor));
+            };
+            match <$type>::try_from(integer) {
+                Ok(integer) => visitor.$visit(integer),
+                Err(_) => Err(Error::custom(format!(
+                    "{integer} is out of range for {}",
+                    stringify!($type)
+                ))),
+            }
+        }
+    )*};
+}
+
+impl<'de> serde::Deserializer<'de> for FieldDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Null => visitor.visit_none(),
+            Field::Boolean(boolean) => visitor.visit_bool(boolean),
+            Field::Integer(integer) => visitor.visit_i32(integer),
+            Field::Float(float) => visitor.visit_f32(float),
+            Field::String(string) => visitor.visit_string(string),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Boolean(boolean) => visitor.visit_bool(boolean),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Integer(integer) => visitor.visit_i32(integer),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Integer(integer) => visitor.visit_i64(integer.into()),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Integer(integer) => visitor.visit_i128(integer.into()),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Float(float) => visitor.visit_f32(float),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    /// Integers convert to f64 without losing precision, so it takes them too.
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Float(float) => visitor.visit_f64(float.into()),
+            Field::Integer(integer) => visitor.visit_f64(integer.into()),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Field::String(string) = &self.0 {
+            let mut chars = string.chars();
+            if let (Some(char), None) = (chars.next(), chars.next()) {
+                return visitor.visit_char(char);
+            }
+        }
+        Err(self.invalid_type(&visitor))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::String(string) => visitor.visit_string(string),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Field::Null => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Enums deserialize as a [`Field`] serializes: tagged with the name of its variant.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> EnumAccess<'de> for FieldDeserializer {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = match self.0 {
+            Field::Null => "Null",
+            Field::Boolean(_) => "Boolean",
+            Field::Integer(_) => "Integer",
+            Field::Float(_) => "Float",
+            Field::String(_) => "String",
+        };
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for FieldDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            Field::Null => Ok(()),
+            _ => Err(self.invalid_type(&"a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value> {
+        Err(self.invalid_type(&visitor))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        Err(self.invalid_type(&visitor))
+    }
+}
+
+fn join(names: &[impl Display]) -> String {
+    names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
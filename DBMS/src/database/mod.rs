@@ -0,0 +1,9 @@
+//! An embeddable database: a database file, and the engine that runs SQL over it, behind a
+//! single type.
+#[allow(clippy::module_inception)]
+mod database;
+mod de;
+
+pub use crate::storage::backup::BackupInfo;
+pub use database::{Database, Options, QueryResult, RetryPolicy, Transaction};
+pub use de::from_row;
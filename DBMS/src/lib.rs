@@ -1,8 +1,14 @@
 #![crate_type = "lib"]
 #![crate_name = "rustydb"]
 
+pub mod bench;
 pub mod common;
+pub mod concurrency;
 pub mod config;
+pub mod database;
+pub mod server;
+#[cfg(test)]
+mod sim;
 pub mod sql;
 pub mod storage;
 pub mod types;
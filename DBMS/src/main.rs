@@ -1,5 +1,6 @@
 use itertools::Itertools;
 use rustydb::common::Result;
+use rustydb::config::config::{CHECKPOINT_INTERVAL_MS, VACUUM_INTERVAL_MS};
 use rustydb::sql::engine::{Engine, Local, Session, StatementResult};
 use rustydb::storage::buffer::buffer_pool_manager::BufferPoolManager;
 use rustydb::storage::disk::disk_manager::DiskManager;
@@ -9,12 +10,19 @@ use rustydb::types::field::Label;
 use std::cell::RefCell;
 use std::io::{stdin, stdout, Write};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 const FILENAME: &str = "main";
 
 fn main() -> Result<()> {
     let storage = create_storage_engine();
     let engine = Local::new(storage);
+    let _checkpointer = engine
+        .simple
+        .start_checkpointer(Duration::from_millis(CHECKPOINT_INTERVAL_MS));
+    let _vacuum = engine
+        .simple
+        .start_vacuum(Duration::from_millis(VACUUM_INTERVAL_MS));
     let session = RefCell::new(engine.session());
 
     loop {
@@ -31,9 +39,27 @@ fn main() -> Result<()> {
 
 fn execute<'a, E: Engine<'a>>(command: &str, session: &mut Session<'a, E>) -> Result<()> {
     match session.execute(command)? {
-        StatementResult::Explain(_) => {
-            todo!();
+        StatementResult::Begin {
+            isolation,
+            read_only,
+        } => {
+            let mode = if read_only { "read-only " } else { "" };
+            println!("[console] Began {}transaction at isolation level {}.", mode, isolation)
         }
+        StatementResult::Commit => println!("[console] Committed transaction."),
+        StatementResult::Rollback => println!("[console] Rolled back transaction."),
+        StatementResult::SetTransaction { isolation } => {
+            println!("[console] Next transaction will run at isolation level {}.", isolation)
+        }
+        StatementResult::Set { name, value } => println!("[console] Set {} to {}.", name, value),
+        StatementResult::Checkpoint { lsn } => {
+            println!("[console] Checkpointed at LSN {}.", lsn)
+        }
+        StatementResult::Vacuum(stats) => println!(
+            "[console] Vacuumed {} versions, {} bytes and {} pages.",
+            stats.versions, stats.bytes, stats.pages
+        ),
+        StatementResult::Explain(plan) => println!("{}", plan),
         StatementResult::CreateTable { name } => println!("[console] Created table '{}'.", name),
         StatementResult::DropTable { name, existed } => match existed {
             true => println!("[console] Dropped table '{}'.", name),
@@ -76,13 +102,11 @@ fn input() -> Result<String> {
 
 fn create_storage_engine() -> HeapTableManager {
     let disk_manager = DiskManager::new(FILENAME);
-    let bpm = Arc::new(RwLock::new(
-        BufferPoolManager::builder()
-            .disk_manager(Arc::new(RwLock::new(disk_manager)))
-            .pool_size(500)
-            .replacer_k(15)
-            .build(),
-    ));
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(500)
+        .replacer_k(15)
+        .build_with_handle();
     HeapTableManager::new(&bpm)
 }
 
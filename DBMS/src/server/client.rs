@@ -0,0 +1,61 @@
+use super::protocol::{read_frame, write_frame, Request, Response};
+use crate::common::{Error, Result};
+use crate::storage::tuple::Row;
+use crate::types::field::Label;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+/// A client of a [`Server`](super::Server), whose statements run in a session of their own.
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+/// The result of a statement executed by a [`Client`].
+#[derive(Debug, PartialEq)]
+pub enum ClientResult {
+    /// A statement that doesn't return rows completed, see [`Response::Done`].
+    Done { command: String, count: u64 },
+    /// A query returned rows.
+    Rows { columns: Vec<Label>, rows: Vec<Row> },
+}
+
+impl Client {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    /// Executes a SQL statement, returning the server's error if it failed.
+    pub fn execute(&mut self, statement: &str) -> Result<ClientResult> {
+        write_frame(&mut self.writer, &Request::Execute(statement.to_string()))?;
+        self.writer.flush()?;
+        match self.receive()? {
+            Response::Error(error) => Err(error),
+            Response::Done { command, count } => Ok(ClientResult::Done { command, count }),
+            Response::Columns(columns) => {
+                let mut rows = Vec::new();
+                loop {
+                    match self.receive()? {
+                        Response::Row(row) => rows.push(row),
+                        Response::RowsEnd => return Ok(ClientResult::Rows { columns, rows }),
+                        response => return Err(unexpected(response)),
+                    }
+                }
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn receive(&mut self) -> Result<Response> {
+        read_frame(&mut self.reader)?
+            .ok_or_else(|| Error::IO("the server closed the connection".to_string()))
+    }
+}
+
+fn unexpected(response: Response) -> Error {
+    Error::InvalidData(format!("unexpected response {response:?}"))
+}
@@ -0,0 +1,8 @@
+mod client;
+pub mod postgres;
+pub mod protocol;
+#[allow(clippy::module_inception)]
+mod server;
+
+pub use client::{Client, ClientResult};
+pub use server::{Protocol, Server, ShutdownHandle};
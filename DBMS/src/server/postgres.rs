@@ -0,0 +1,483 @@
+//! A subset of version 3 of the PostgreSQL frontend/backend protocol, which lets psql and
+//! PostgreSQL drivers connect to a [`Server`](super::Server) speaking it, see [`Protocol`].
+//!
+//! Clients start a session without authenticating, and run statements with the simple query
+//! protocol. A query may hold several statements, each answered with its rows, if any, and a
+//! command tag, or with an error that ends the query. Rows are sent in text format. The extended
+//! query protocol isn't supported yet: its messages are answered with an error, and ignored until
+//! the next Sync, as PostgreSQL does after a failed extended query.
+//!
+//! See <https://www.postgresql.org/docs/current/protocol.html>.
+//!
+//! [`Protocol`]: super::Protocol
+use super::protocol::MAX_FRAME_BYTES;
+use crate::common::{Error, Result};
+use crate::sql::engine::{Local, Session, StatementResult};
+use crate::sql::parser::split_statements;
+use crate::storage;
+use crate::storage::tuple::Row;
+use crate::types::field::{Field, Label};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+
+/// The protocol version of a startup message: 3.0.
+const PROTOCOL_VERSION: i32 = 196608;
+/// The codes a client sends in place of a protocol version, to ask for an encrypted connection or
+/// to cancel another connection's query.
+const SSL_REQUEST_CODE: i32 = 80877103;
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+/// The OIDs of the types rows are sent as.
+const BOOL_OID: i32 = 16;
+const INT4_OID: i32 = 23;
+const TEXT_OID: i32 = 25;
+const FLOAT4_OID: i32 = 700;
+
+/// The run-time parameters reported to clients when their session starts.
+const PARAMETERS: [(&str, &str); 6] = [
+    ("server_version", "14.0"),
+    ("server_encoding", "UTF8"),
+    ("client_encoding", "UTF8"),
+    ("DateStyle", "ISO, MDY"),
+    ("integer_datetimes", "on"),
+    ("standard_conforming_strings", "on"),
+];
+
+/// A message that opens a connection. These are the only messages sent without a type byte.
+#[derive(Debug, PartialEq)]
+enum StartupMessage {
+    /// Starts a session. The parameters it carries, e.g. the user and database, are ignored.
+    Startup,
+    /// Asks for an SSL encrypted connection.
+    SslRequest,
+    /// Asks for a GSSAPI encrypted connection.
+    GssEncRequest,
+    /// Asks to cancel the query another connection is running.
+    CancelRequest,
+}
+
+/// A message from the client, once its session has started.
+#[derive(Debug, PartialEq)]
+enum FrontendMessage {
+    /// A simple query, holding one or more statements separated by semicolons.
+    Query(String),
+    /// Ends an exchange of the extended query protocol.
+    Sync,
+    /// Closes the connection.
+    Terminate,
+    /// A message of a type the server doesn't handle, e.g. Parse, Bind or Execute of the extended
+    /// query protocol, with its type byte.
+    Unsupported(u8),
+}
+
+impl FrontendMessage {
+    /// Decodes the body of a message of the given type.
+    fn decode(tag: u8, mut body: &[u8]) -> Result<Self> {
+        Ok(match tag {
+            b'Q' => Self::Query(read_cstring(&mut body)?),
+            b'S' => Self::Sync,
+            b'X' => Self::Terminate,
+            tag => Self::Unsupported(tag),
+        })
+    }
+}
+
+/// The state of a session's transaction, as reported by [`BackendMessage::ReadyForQuery`]. A
+/// statement that fails inside a transaction doesn't end it, so transactions never fail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TransactionStatus {
+    Idle,
+    InTransaction,
+}
+
+/// A column of a [`BackendMessage::RowDescription`].
+#[derive(Debug, PartialEq)]
+struct FieldDescription {
+    name: String,
+    type_oid: i32,
+    /// The size of the type in bytes, or -1 for types of variable size.
+    type_size: i16,
+}
+
+/// A message from the server.
+#[derive(Debug, PartialEq)]
+enum BackendMessage {
+    AuthenticationOk,
+    ParameterStatus {
+        name: String,
+        value: String,
+    },
+    /// The server is ready for the next query.
+    ReadyForQuery(TransactionStatus),
+    /// Describes the columns of the rows that follow.
+    RowDescription(Vec<FieldDescription>),
+    /// A row, with the text of each field, or None for NULL.
+    DataRow(Vec<Option<String>>),
+    /// A statement completed. The tag names it, e.g. "INSERT 0 2".
+    CommandComplete(String),
+    /// The query held no statements.
+    EmptyQueryResponse,
+    /// A statement failed, with the SQLSTATE code of the error and its message.
+    ErrorResponse {
+        code: &'static str,
+        message: String,
+    },
+}
+
+impl BackendMessage {
+    /// Writes the message, preceded by its type byte and length. The writer isn't flushed.
+    fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let mut body = Vec::new();
+        let tag = match self {
+            Self::AuthenticationOk => {
+                body.extend(0i32.to_be_bytes());
+                b'R'
+            }
+            Self::ParameterStatus { name, value } => {
+                write_cstring(&mut body, name);
+                write_cstring(&mut body, value);
+                b'S'
+            }
+            Self::ReadyForQuery(status) => {
+                body.push(match status {
+                    TransactionStatus::Idle => b'I',
+                    TransactionStatus::InTransaction => b'T',
+                });
+                b'Z'
+            }
+            Self::RowDescription(fields) => {
+                body.extend((fields.len() as i16).to_be_bytes());
+                for field in fields {
+                    write_cstring(&mut body, &field.name);
+                    body.extend(0i32.to_be_bytes()); // the OID of the column's table, if any
+                    body.extend(0i16.to_be_bytes()); // the column's number in its table, if any
+                    body.extend(field.type_oid.to_be_bytes());
+                    body.extend(field.type_size.to_be_bytes());
+                    body.extend((-1i32).to_be_bytes()); // the type modifier
+                    body.extend(0i16.to_be_bytes()); // text format
+                }
+                b'T'
+            }
+            Self::DataRow(values) => {
+                body.extend((values.len() as i16).to_be_bytes());
+                for value in values {
+                    match value {
+                        Some(value) => {
+                            body.extend((value.len() as i32).to_be_bytes());
+                            body.extend(value.as_bytes());
+                        }
+                        None => body.extend((-1i32).to_be_bytes()),
+                    }
+                }
+                b'D'
+            }
+            Self::CommandComplete(tag) => {
+                write_cstring(&mut body, tag);
+                b'C'
+            }
+            Self::EmptyQueryResponse => b'I',
+            Self::ErrorResponse { code, message } => {
+                let fields = [
+                    (b'S', "ERROR"),
+                    (b'V', "ERROR"),
+                    (b'C', *code),
+                    (b'M', message.as_str()),
+                ];
+                for (field, value) in fields {
+                    body.push(field);
+                    write_cstring(&mut body, value);
+                }
+                body.push(0);
+                b'E'
+            }
+        };
+        writer.write_all(&[tag])?;
+        writer.write_all(&(body.len() as i32 + 4).to_be_bytes())?;
+        writer.write_all(&body)?;
+        Ok(())
+    }
+
+    /// Returns the error response to an error.
+    fn error(error: &Error) -> Self {
+        let code = match error {
+            Error::InvalidInput(_) => "42000",
+            Error::Constraint(_) => "23000",
+            Error::Serialization => "40001",
+            Error::ReadOnly => "25006",
+            Error::LockTimeout => "55P03",
+            Error::Cancelled => "57014",
+            Error::OverflowError => "22003",
+            _ => "XX000",
+        };
+        Self::ErrorResponse {
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Starts a session for a client, and executes the queries it sends until it disconnects.
+pub(super) fn serve_connection<E: storage::Engine>(
+    engine: &Local<E>,
+    stream: TcpStream,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    // Clients ask for encryption before starting a session; they are told to go without.
+    loop {
+        match read_startup(&mut reader)? {
+            Some(StartupMessage::Startup) => break,
+            Some(StartupMessage::SslRequest | StartupMessage::GssEncRequest) => {
+                writer.write_all(b"N")?;
+                writer.flush()?;
+            }
+            Some(StartupMessage::CancelRequest) | None => return Ok(()),
+        }
+    }
+
+    let mut session = engine.session();
+    BackendMessage::AuthenticationOk.write(&mut writer)?;
+    for (name, value) in PARAMETERS {
+        BackendMessage::ParameterStatus {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+        .write(&mut writer)?;
+    }
+    BackendMessage::ReadyForQuery(transaction_status(&session)).write(&mut writer)?;
+    writer.flush()?;
+
+    // After an unsupported message, those that follow it are ignored until the next Sync.
+    let mut skipping = false;
+    while let Some(message) = read_message(&mut reader)? {
+        match message {
+            FrontendMessage::Query(query) => execute_query(&mut session, &query, &mut writer)?,
+            FrontendMessage::Sync => {
+                skipping = false;
+                BackendMessage::ReadyForQuery(transaction_status(&session)).write(&mut writer)?;
+            }
+            // The server holds on to a handle of the stream, to close it when shutting down.
+            FrontendMessage::Terminate => {
+                writer.get_ref().shutdown(Shutdown::Both)?;
+                break;
+            }
+            FrontendMessage::Unsupported(_) if skipping => {}
+            FrontendMessage::Unsupported(tag) => {
+                skipping = true;
+                BackendMessage::ErrorResponse {
+                    code: "0A000",
+                    message: format!("unsupported message type '{}'", tag as char),
+                }
+                .write(&mut writer)?;
+            }
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Executes the statements of a simple query, each as it would run on its own, stopping at the
+/// first that fails. Then tells the client the session is ready for the next query.
+fn execute_query<'a, E: storage::Engine>(
+    session: &mut Session<'a, Local<E>>,
+    query: &str,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let (mut statements, rest) = split_statements(query);
+    if !rest.trim().is_empty() {
+        statements.push(rest);
+    }
+    if statements.is_empty() {
+        BackendMessage::EmptyQueryResponse.write(writer)?;
+    }
+    for statement in statements {
+        match session.execute(statement) {
+            Ok(result) => {
+                for message in messages(result) {
+                    message.write(writer)?;
+                }
+            }
+            Err(error) => {
+                BackendMessage::error(&error).write(writer)?;
+                break;
+            }
+        }
+    }
+    BackendMessage::ReadyForQuery(transaction_status(session)).write(writer)
+}
+
+fn transaction_status<'a, E: storage::Engine>(
+    session: &Session<'a, Local<E>>,
+) -> TransactionStatus {
+    match session.in_transaction() {
+        true => TransactionStatus::InTransaction,
+        false => TransactionStatus::Idle,
+    }
+}
+
+/// Returns the messages that answer a statement with the given result. EXPLAIN is answered like
+/// a query, with a row for each line of the plan, as PostgreSQL does.
+fn messages(result: StatementResult) -> Vec<BackendMessage> {
+    let complete = |tag: String| vec![BackendMessage::CommandComplete(tag)];
+    match result {
+        StatementResult::Select { columns, rows } => {
+            let tag = format!("SELECT {}", rows.len());
+            rows_messages(&columns, rows, tag)
+        }
+        StatementResult::Explain(plan) => {
+            let lines = plan
+                .to_string()
+                .lines()
+                .map(|line| Row::from(vec![Field::String(line.to_string())]))
+                .collect();
+            let columns = [Label::Unqualified("QUERY PLAN".to_string())];
+            rows_messages(&columns, lines, "EXPLAIN".to_string())
+        }
+        StatementResult::Begin { .. } => complete("BEGIN".to_string()),
+        StatementResult::Commit => complete("COMMIT".to_string()),
+        StatementResult::Rollback => complete("ROLLBACK".to_string()),
+        StatementResult::SetTransaction { .. } | StatementResult::Set { .. } => {
+            complete("SET".to_string())
+        }
+        StatementResult::Checkpoint { .. } => complete("CHECKPOINT".to_string()),
+        StatementResult::Vacuum(_) => complete("VACUUM".to_string()),
+        StatementResult::CreateTable { .. } => complete("CREATE TABLE".to_string()),
+        StatementResult::DropTable { .. } => complete("DROP TABLE".to_string()),
+        // The 0 is the OID of the inserted row, which PostgreSQL no longer gives.
+        StatementResult::Insert { count, .. } => complete(format!("INSERT 0 {count}")),
+        StatementResult::Update { count } => complete(format!("UPDATE {count}")),
+        StatementResult::Delete { count } => complete(format!("DELETE {count}")),
+    }
+}
+
+/// Returns the messages that send rows: their description, the rows, and the command tag.
+fn rows_messages(columns: &[Label], rows: Vec<Row>, tag: String) -> Vec<BackendMessage> {
+    let fields = columns
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            let (type_oid, type_size) = column_type(&rows, index);
+            let name = match label {
+                Label::None => "?column?".to_string(),
+                label => label.as_header().to_string(),
+            };
+            FieldDescription {
+                name,
+                type_oid,
+                type_size,
+            }
+        })
+        .collect();
+    let mut messages = vec![BackendMessage::RowDescription(fields)];
+    for row in rows {
+        messages.push(BackendMessage::DataRow(row.iter().map(text).collect()));
+    }
+    messages.push(BackendMessage::CommandComplete(tag));
+    messages
+}
+
+/// Returns the OID and size of the type of a column, going by its first value that isn't NULL.
+/// Columns without one are sent as text.
+fn column_type(rows: &[Row], index: usize) -> (i32, i16) {
+    let value = rows
+        .iter()
+        .filter_map(|row| row.iter().nth(index))
+        .find(|field| **field != Field::Null);
+    match value {
+        Some(Field::Boolean(_)) => (BOOL_OID, 1),
+        Some(Field::Integer(_)) => (INT4_OID, 4),
+        Some(Field::Float(_)) => (FLOAT4_OID, 4),
+        Some(Field::String(_) | Field::Null) | None => (TEXT_OID, -1),
+    }
+}
+
+/// Returns the text format of a field, or None for NULL.
+fn text(field: &Field) -> Option<String> {
+    Some(match field {
+        Field::Null => return None,
+        Field::Boolean(true) => "t".to_string(),
+        Field::Boolean(false) => "f".to_string(),
+        Field::Integer(integer) => integer.to_string(),
+        Field::Float(float) if float.is_infinite() && *float > 0.0 => "Infinity".to_string(),
+        Field::Float(float) if float.is_infinite() => "-Infinity".to_string(),
+        Field::Float(float) => float.to_string(),
+        Field::String(string) => string.clone(),
+    })
+}
+
+/// Reads a message that opens a connection, or returns None if the stream ended before it began.
+fn read_startup(reader: &mut impl Read) -> Result<Option<StartupMessage>> {
+    let mut length = [0; 4];
+    if !read_or_eof(reader, &mut length)? {
+        return Ok(None);
+    }
+    let body = read_body(reader, i32::from_be_bytes(length))?;
+    let Some(code) = body.first_chunk::<4>() else {
+        return Err(Error::InvalidData(
+            "startup message without a code".to_string(),
+        ));
+    };
+    Ok(Some(match i32::from_be_bytes(*code) {
+        PROTOCOL_VERSION => StartupMessage::Startup,
+        SSL_REQUEST_CODE => StartupMessage::SslRequest,
+        GSSENC_REQUEST_CODE => StartupMessage::GssEncRequest,
+        CANCEL_REQUEST_CODE => StartupMessage::CancelRequest,
+        code => {
+            return Err(Error::InvalidData(format!(
+                "unsupported protocol version {}.{}",
+                code >> 16,
+                code & 0xffff
+            )))
+        }
+    }))
+}
+
+/// Reads a message of a started session, or returns None if the stream ended before it began.
+fn read_message(reader: &mut impl Read) -> Result<Option<FrontendMessage>> {
+    let mut header = [0; 5];
+    if !read_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+    let [tag, length @ ..] = header;
+    let body = read_body(reader, i32::from_be_bytes(length))?;
+    Ok(Some(FrontendMessage::decode(tag, &body)?))
+}
+
+/// Reads the body of a message, given the length of the message, which counts the 4 bytes of the
+/// length itself.
+fn read_body(reader: &mut impl Read, length: i32) -> Result<Vec<u8>> {
+    let length = usize::try_from(length)
+        .ok()
+        .and_then(|length| length.checked_sub(4))
+        .filter(|length| *length <= MAX_FRAME_BYTES)
+        .ok_or_else(|| Error::InvalidData(format!("invalid message length {length}")))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Fills the buffer, returning false if the stream ended before the first byte.
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Reads a null-terminated string off the start of a message body.
+fn read_cstring(body: &mut &[u8]) -> Result<String> {
+    let Some(end) = body.iter().position(|b| *b == 0) else {
+        return Err(Error::InvalidData("unterminated string".to_string()));
+    };
+    let string = String::from_utf8(body[..end].to_vec())
+        .map_err(|error| Error::InvalidData(error.to_string()))?;
+    *body = &body[end + 1..];
+    Ok(string)
+}
+
+fn write_cstring(body: &mut Vec<u8>, string: &str) {
+    body.extend(string.as_bytes());
+    body.push(0);
+}
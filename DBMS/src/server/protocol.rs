@@ -0,0 +1,68 @@
+//! The protocol clients and the [`Server`](super::Server) speak over TCP.
+//!
+//! Both sides send frames: the bincode encoding of a message, preceded by its length as a 4-byte
+//! big-endian integer. A client sends a [`Request`], and the server replies to it with one or more
+//! [`Response`] frames before reading the next request.
+use crate::common::{Error, Result};
+use crate::storage::tuple::Row;
+use crate::types::field::Label;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+
+/// The largest frame either side reads, so that a corrupt length can't make it allocate without
+/// bound.
+pub const MAX_FRAME_BYTES: usize = 64 << 20;
+
+/// A request from a client.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    /// Executes a SQL statement in the connection's session.
+    Execute(String),
+}
+
+/// A frame of the server's reply to a request. A statement that failed gets a single
+/// [`Response::Error`], and one that didn't return rows a single [`Response::Done`]. A query gets
+/// [`Response::Columns`], then a [`Response::Row`] for each row, and finally
+/// [`Response::RowsEnd`]. EXPLAIN is answered like a query, with a row for each line of the plan.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Error(Error),
+    /// The statement completed. `command` names it, e.g. "INSERT", and `count` is the number of
+    /// rows it changed, 0 for statements that don't change rows.
+    Done {
+        command: String,
+        count: u64,
+    },
+    Columns(Vec<Label>),
+    Row(Row),
+    RowsEnd,
+}
+
+/// Writes a frame holding a message. The writer isn't flushed.
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<()> {
+    let bytes = bincode::serialize(message)?;
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Reads a frame written by [`write_frame`], returning the message it holds, or None if the
+/// stream ended before the frame began.
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_BYTES {
+        return Err(Error::InvalidData(format!(
+            "frame of {length} bytes exceeds the limit of {MAX_FRAME_BYTES}"
+        )));
+    }
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(bincode::deserialize(&bytes)?))
+}
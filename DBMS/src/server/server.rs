@@ -0,0 +1,187 @@
+use super::postgres;
+use super::protocol::{read_frame, write_frame, Request, Response};
+use crate::common::Result;
+use crate::sql::engine::{Engine as _, Local, StatementResult};
+use crate::storage;
+use crate::storage::tuple::Row;
+use crate::types::field::{Field, Label};
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// A server running the SQL statements of clients connected over TCP, speaking one of the
+/// [`Protocol`]s.
+///
+/// Every connection gets a session of its own, served by a thread of its own, over the engine all
+/// connections share. A connection's open transaction is rolled back when it closes.
+pub struct Server<E: storage::Engine + 'static> {
+    engine: Arc<Local<E>>,
+    listener: TcpListener,
+    protocol: Protocol,
+    shutdown: Arc<AtomicBool>,
+}
+
+/// The protocol a [`Server`] speaks with its clients.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Protocol {
+    /// The server's own protocol, see [`super::protocol`] and [`super::Client`].
+    #[default]
+    Native,
+    /// A subset of the PostgreSQL protocol, for psql and PostgreSQL drivers, see
+    /// [`super::postgres`].
+    Postgres,
+}
+
+impl<E: storage::Engine + Send + 'static> Server<E> {
+    /// Listens on the given address, serving statements with the given engine once
+    /// [`Self::serve`] is called. The server speaks the native protocol, unless told otherwise
+    /// with [`Self::with_protocol`].
+    pub fn bind(addr: impl ToSocketAddrs, engine: Local<E>) -> Result<Self> {
+        Ok(Self {
+            engine: Arc::new(engine),
+            listener: TcpListener::bind(addr)?,
+            protocol: Protocol::default(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Sets the protocol the server speaks.
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Returns the address the server listens on, e.g. to find the port it was given when bound
+    /// to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns the engine the server runs statements with.
+    pub fn engine(&self) -> &Arc<Local<E>> {
+        &self.engine
+    }
+
+    /// Returns a handle that shuts the server down from another thread.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        Ok(ShutdownHandle {
+            shutdown: Arc::clone(&self.shutdown),
+            addr: self.local_addr()?,
+        })
+    }
+
+    /// Accepts connections until the server is shut down, see [`ShutdownHandle`]. Then closes the
+    /// open connections, waits for the statements they are running to finish, and takes a
+    /// checkpoint, which writes the buffer pool's modified pages to disk.
+    pub fn serve(self) -> Result<()> {
+        let mut connections: Vec<(TcpStream, JoinHandle<()>)> = Vec::new();
+        for stream in self.listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            // A connection that failed before it was accepted concerns no one else.
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let Ok(closer) = stream.try_clone() else {
+                continue;
+            };
+            let engine = Arc::clone(&self.engine);
+            let protocol = self.protocol;
+            // A connection ends when the client disconnects or breaks the protocol, which is
+            // none of the server's business either.
+            let thread = thread::spawn(move || {
+                match protocol {
+                    Protocol::Native => serve_connection(&engine, stream),
+                    Protocol::Postgres => postgres::serve_connection(&engine, stream),
+                }
+                .unwrap_or(())
+            });
+            connections.retain(|(_, thread)| !thread.is_finished());
+            connections.push((closer, thread));
+        }
+
+        for (stream, thread) in connections {
+            stream.shutdown(Shutdown::Both).ok();
+            thread.join().ok();
+        }
+        self.engine.checkpoint()?;
+        Ok(())
+    }
+}
+
+/// Shuts down a [`Server`], see [`Server::serve`].
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<AtomicBool>,
+    addr: SocketAddr,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wakes the server up from waiting for a connection.
+        TcpStream::connect(self.addr).ok();
+    }
+}
+
+/// Executes the statements a client sends in a session of its own, until it disconnects.
+fn serve_connection<E: storage::Engine>(engine: &Local<E>, stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut session = engine.session();
+    while let Some(Request::Execute(statement)) = read_frame(&mut reader)? {
+        match session.execute(&statement) {
+            Ok(result) => {
+                for response in responses(result) {
+                    write_frame(&mut writer, &response)?;
+                }
+            }
+            Err(error) => write_frame(&mut writer, &Response::Error(error))?,
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Returns the frames that answer a statement with the given result.
+fn responses(result: StatementResult) -> Vec<Response> {
+    let done = |command: &str, count| {
+        vec![Response::Done {
+            command: command.to_string(),
+            count,
+        }]
+    };
+    let rows = |columns, rows: Vec<Row>| {
+        let rows = rows.into_iter().map(Response::Row);
+        std::iter::once(Response::Columns(columns))
+            .chain(rows)
+            .chain([Response::RowsEnd])
+            .collect()
+    };
+    match result {
+        StatementResult::Select { columns, rows: r } => rows(columns, r),
+        StatementResult::Explain(plan) => {
+            let lines = plan
+                .to_string()
+                .lines()
+                .map(|line| Row::from(vec![Field::String(line.to_string())]))
+                .collect();
+            rows(vec![Label::Unqualified("plan".to_string())], lines)
+        }
+        StatementResult::Begin { .. } => done("BEGIN", 0),
+        StatementResult::Commit => done("COMMIT", 0),
+        StatementResult::Rollback => done("ROLLBACK", 0),
+        StatementResult::SetTransaction { .. } => done("SET TRANSACTION", 0),
+        StatementResult::Set { .. } => done("SET", 0),
+        StatementResult::Checkpoint { .. } => done("CHECKPOINT", 0),
+        StatementResult::Vacuum(stats) => done("VACUUM", stats.versions),
+        StatementResult::CreateTable { .. } => done("CREATE TABLE", 0),
+        StatementResult::DropTable { .. } => done("DROP TABLE", 0),
+        StatementResult::Delete { count } => done("DELETE", count),
+        StatementResult::Insert { count, .. } => done("INSERT", count),
+        StatementResult::Update { count } => done("UPDATE", count),
+    }
+}
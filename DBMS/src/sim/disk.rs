@@ -0,0 +1,193 @@
+use crate::storage::disk::disk_file::DiskFile;
+use crate::storage::disk::disk_manager::DiskManager;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// The database file on a simulated disk.
+const DATA: usize = 0;
+/// Its write-ahead log.
+const LOG: usize = 1;
+
+/// An in-memory disk holding a database file and its log, which can lose power.
+///
+/// Writes only reach a file's volatile image, which a sync copies to its durable one. After
+/// [`Self::crash_at`]'s crash point every write and sync fails, as if the power had been cut
+/// mid-way, and [`Self::restart`] brings up a disk with the durable images only.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SimDisk {
+    state: Arc<Mutex<DiskState>>,
+}
+
+#[derive(Debug, Default)]
+struct DiskState {
+    files: [FileImage; 2],
+    /// Writes and syncs done so far.
+    ops: usize,
+    /// The write or sync the power is cut at, counting from 0.
+    crash_at: Option<usize>,
+    /// How long each write takes.
+    write_latency: Duration,
+}
+
+#[derive(Debug, Default)]
+struct FileImage {
+    volatile: Vec<u8>,
+    durable: Vec<u8>,
+}
+
+impl DiskState {
+    /// Counts a write or sync, failing it if the power is out.
+    fn op(&mut self) -> Result<()> {
+        if self.crashed() {
+            return Err(Error::new(ErrorKind::BrokenPipe, "simulated power cut"));
+        }
+        self.ops += 1;
+        Ok(())
+    }
+
+    fn crashed(&self) -> bool {
+        self.crash_at.is_some_and(|crash_at| self.ops >= crash_at)
+    }
+}
+
+impl SimDisk {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cuts the power at the `op`'th write or sync from now, counting from 0.
+    pub(crate) fn crash_at(&self, op: usize) {
+        let mut state = self.lock();
+        state.crash_at = Some(state.ops + op);
+    }
+
+    /// Makes every write from now on take `latency`, as on a slow disk.
+    pub(crate) fn set_write_latency(&self, latency: Duration) {
+        self.lock().write_latency = latency;
+    }
+
+    /// Returns a disk holding what this one had synced, as found after a power cut.
+    pub(crate) fn restart(&self) -> Self {
+        let state = self.lock();
+        let image = |file: usize| FileImage {
+            volatile: state.files[file].durable.clone(),
+            durable: state.files[file].durable.clone(),
+        };
+        Self {
+            state: Arc::new(Mutex::new(DiskState {
+                files: [image(DATA), image(LOG)],
+                ..DiskState::default()
+            })),
+        }
+    }
+
+    /// Returns the durable image of the log.
+    pub(crate) fn durable_log(&self) -> Vec<u8> {
+        self.lock().files[LOG].durable.clone()
+    }
+
+    /// Returns a disk manager for the disk's files.
+    pub(crate) fn disk_manager(&self) -> DiskManager {
+        DiskManager::new_simulated(self)
+    }
+
+    /// Returns the database file and its log.
+    pub(crate) fn files(&self) -> (DiskFile, DiskFile) {
+        let file = |file| {
+            DiskFile::Sim(SimFile {
+                state: Arc::clone(&self.state),
+                file,
+                pos: 0,
+            })
+        };
+        (file(DATA), file(LOG))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DiskState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// A file on a [`SimDisk`], with its own cursor.
+#[derive(Clone, Debug)]
+pub(crate) struct SimFile {
+    state: Arc<Mutex<DiskState>>,
+    file: usize,
+    pos: u64,
+}
+
+impl SimFile {
+    pub(crate) fn len(&self) -> Result<u64> {
+        Ok(self.lock().files[self.file].volatile.len() as u64)
+    }
+
+    pub(crate) fn set_len(&self, len: u64) -> Result<()> {
+        let mut state = self.lock();
+        state.op()?;
+        state.files[self.file].volatile.resize(len as usize, 0);
+        Ok(())
+    }
+
+    pub(crate) fn sync_data(&self) -> Result<()> {
+        let mut state = self.lock();
+        state.op()?;
+        let image = &mut state.files[self.file];
+        image.durable.clone_from(&image.volatile);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DiskState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Read for SimFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let state = self.lock();
+        let image = &state.files[self.file].volatile;
+        let start = (self.pos as usize).min(image.len());
+        let len = buf.len().min(image.len() - start);
+        buf[..len].copy_from_slice(&image[start..start + len]);
+        drop(state);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Write for SimFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut state = self.lock();
+        state.op()?;
+        let image = &mut state.files[self.file].volatile;
+        let start = self.pos as usize;
+        if image.len() < start + buf.len() {
+            image.resize(start + buf.len(), 0);
+        }
+        image[start..start + buf.len()].copy_from_slice(buf);
+        let latency = state.write_latency;
+        drop(state);
+        if !latency.is_zero() {
+            thread::sleep(latency);
+        }
+        self.pos += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SimFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "seek before start"))?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,281 @@
+use crate::common::sched::{self, SchedHook};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// Steps after which a run is considered stuck, e.g. with every thread spinning on a lock.
+const MAX_STEPS: usize = 100_000;
+
+/// A check of some shared state, returning what is wrong with it.
+type Check = Box<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+/// A step of a run: the logical thread that reached a scheduling point, and the point.
+pub(crate) type Step = (usize, &'static str);
+
+/// Runs logical threads one at a time, switching between them at scheduling points in an order
+/// picked by a seeded random number generator. The same seed gives the same interleaving, as
+/// long as the threads only share state guarded by locks taken with [`super::write`], or by the
+/// engine's own scheduling points.
+///
+/// Each logical thread is an OS thread, but only the one holding the baton runs: at every
+/// scheduling point, it checks the invariants and hands the baton to the next thread picked.
+pub(crate) struct SimExecutor {
+    seed: u64,
+    threads: Vec<(&'static str, Box<dyn FnOnce() + Send>)>,
+    invariants: Vec<Check>,
+    postconditions: Vec<Check>,
+}
+
+/// Why a run failed, along with the steps that led there.
+#[derive(Debug)]
+pub(crate) struct SimFailure {
+    pub(crate) seed: u64,
+    pub(crate) message: String,
+    pub(crate) trace: Vec<Step>,
+}
+
+impl fmt::Display for SimFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tail = &self.trace[self.trace.len().saturating_sub(10)..];
+        write!(
+            f,
+            "seed {} failed after {} steps: {}, last steps {tail:?}",
+            self.seed,
+            self.trace.len(),
+            self.message
+        )
+    }
+}
+
+/// Unwinds a thread parked at a scheduling point once the run has failed.
+struct Aborted;
+
+struct Scheduler {
+    state: Mutex<State>,
+    /// Notified whenever the baton is handed over, or a thread finishes.
+    turn: Condvar,
+    invariants: Vec<Check>,
+}
+
+struct State {
+    rng: ChaCha8Rng,
+    /// The thread holding the baton.
+    running: Option<usize>,
+    finished: Vec<bool>,
+    trace: Vec<Step>,
+    failure: Option<String>,
+}
+
+/// The hook of a logical thread.
+struct ThreadHook {
+    scheduler: Arc<Scheduler>,
+    id: usize,
+}
+
+impl SimExecutor {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            threads: Vec::new(),
+            invariants: Vec::new(),
+            postconditions: Vec::new(),
+        }
+    }
+
+    /// Adds a logical thread.
+    pub(crate) fn spawn(&mut self, name: &'static str, f: impl FnOnce() + Send + 'static) {
+        self.threads.push((name, Box::new(f)));
+    }
+
+    /// Adds a check run at every scheduling point, and once every thread has finished. It must
+    /// not reach a scheduling point, nor block on a lock a parked thread may hold.
+    pub(crate) fn invariant(
+        &mut self,
+        check: impl Fn() -> Result<(), String> + Send + Sync + 'static,
+    ) {
+        self.invariants.push(Box::new(check));
+    }
+
+    /// Adds a check run once every thread has finished.
+    pub(crate) fn postcondition(
+        &mut self,
+        check: impl Fn() -> Result<(), String> + Send + Sync + 'static,
+    ) {
+        self.postconditions.push(Box::new(check));
+    }
+
+    /// Runs the threads to completion, returning the steps taken, or why the run failed: a
+    /// check failed, a thread panicked, or the threads got stuck.
+    pub(crate) fn run(self) -> Result<Vec<Step>, SimFailure> {
+        let scheduler = Arc::new(Scheduler {
+            state: Mutex::new(State {
+                rng: ChaCha8Rng::seed_from_u64(self.seed),
+                running: None,
+                finished: vec![false; self.threads.len()],
+                trace: Vec::new(),
+                failure: None,
+            }),
+            turn: Condvar::new(),
+            invariants: self.invariants,
+        });
+
+        let handles: Vec<_> = (self.threads.into_iter().enumerate())
+            .map(|(id, (name, f))| {
+                let hook = ThreadHook {
+                    scheduler: Arc::clone(&scheduler),
+                    id,
+                };
+                thread::Builder::new()
+                    .name(name.to_string())
+                    .spawn(move || hook.run(f))
+                    .expect("Unable to spawn simulated thread")
+            })
+            .collect();
+
+        let mut state = scheduler.lock();
+        state.pass_baton();
+        scheduler.turn.notify_all();
+        while state.finished.contains(&false) {
+            state = scheduler.turn.wait(state).unwrap();
+        }
+        drop(state);
+        for handle in handles {
+            handle
+                .join()
+                .expect("Simulated thread panicked outside the executor");
+        }
+
+        let mut state = scheduler.lock();
+        if state.failure.is_none() {
+            let checks = scheduler.invariants.iter().chain(&self.postconditions);
+            state.failure = checks.map(|check| check()).find_map(Result::err);
+        }
+        let trace = std::mem::take(&mut state.trace);
+        match state.failure.take() {
+            None => Ok(trace),
+            Some(message) => Err(SimFailure {
+                seed: self.seed,
+                message,
+                trace,
+            }),
+        }
+    }
+}
+
+/// Runs the scenario built by `build` with each of the given seeds, stopping at the first one
+/// that fails.
+pub(crate) fn explore(
+    seeds: impl IntoIterator<Item = u64>,
+    build: impl Fn(u64) -> SimExecutor,
+) -> Result<(), SimFailure> {
+    for seed in seeds {
+        build(seed).run()?;
+    }
+    Ok(())
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Waits for the thread's turn, or unwinds it if the run has failed meanwhile.
+    fn wait_turn<'a>(&'a self, mut state: MutexGuard<'a, State>, id: usize) {
+        while state.running != Some(id) && state.failure.is_none() {
+            state = self.turn.wait(state).unwrap();
+        }
+        if state.failure.is_some() {
+            drop(state);
+            panic::resume_unwind(Box::new(Aborted));
+        }
+    }
+
+    /// Fails the run, waking up every thread so that they unwind.
+    fn fail(&self, message: String) {
+        let mut state = self.lock();
+        state.failure.get_or_insert(message);
+        state.running = None;
+        self.turn.notify_all();
+    }
+}
+
+impl State {
+    /// Hands the baton to a random unfinished thread, if any.
+    fn pass_baton(&mut self) {
+        let unfinished: Vec<usize> = (0..self.finished.len())
+            .filter(|id| !self.finished[*id])
+            .collect();
+        self.running = match unfinished.len() {
+            0 => None,
+            len => Some(unfinished[self.rng.gen_range(0..len)]),
+        };
+    }
+}
+
+impl ThreadHook {
+    fn run(self, f: Box<dyn FnOnce() + Send>) {
+        let scheduler = Arc::clone(&self.scheduler);
+        let id = self.id;
+        sched::set_hook(Some(Arc::new(self)));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            scheduler.wait_turn(scheduler.lock(), id);
+            f();
+        }));
+        sched::set_hook(None);
+        if let Err(payload) = result {
+            if !payload.is::<Aborted>() {
+                let name = thread::current().name().unwrap_or_default().to_string();
+                scheduler.fail(format!(
+                    "thread {name} panicked: {}",
+                    panic_message(&payload)
+                ));
+            }
+        }
+
+        let mut state = scheduler.lock();
+        state.finished[id] = true;
+        if state.failure.is_none() {
+            state.pass_baton();
+        }
+        scheduler.turn.notify_all();
+    }
+}
+
+impl SchedHook for ThreadHook {
+    fn yield_point(&self, point: &'static str) {
+        let scheduler = &self.scheduler;
+        // Only this thread runs, so the checks see the state it left at the scheduling point.
+        if let Some(message) = scheduler.invariants.iter().find_map(|check| check().err()) {
+            scheduler.fail(format!("at {point}: {message}"));
+        }
+
+        let mut state = scheduler.lock();
+        if state.failure.is_some() {
+            drop(state);
+            panic::resume_unwind(Box::new(Aborted));
+        }
+        state.trace.push((self.id, point));
+        if state.trace.len() >= MAX_STEPS {
+            drop(state);
+            scheduler.fail(format!("no progress after {MAX_STEPS} steps"));
+            panic::resume_unwind(Box::new(Aborted));
+        }
+        state.pass_baton();
+        scheduler.turn.notify_all();
+        scheduler.wait_turn(state, self.id);
+    }
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
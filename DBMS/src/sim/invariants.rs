@@ -0,0 +1,190 @@
+//! Invariants of the buffer pool and its replacer, to check with
+//! [`SimExecutor::invariant`](super::SimExecutor::invariant).
+//!
+//! The checks skip whatever is locked, since a thread parked while holding a lock may be in the
+//! middle of changing it.
+use crate::storage::buffer::buffer_pool_manager::{BufferPoolManager, FrameId, Frames, Shard};
+use crate::storage::buffer::lru_k_replacer::{EvictionKey, LRUKReplacer};
+use crate::storage::disk::disk_manager::PageId;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+/// Checks that the shards share out the pool's frames, that every frame is either free and
+/// empty, or holds the page mapped to it, and that exactly the frames of unpinned pages are
+/// evictable.
+pub(crate) fn check_pool(bpm: &BufferPoolManager) -> Result<(), String> {
+    let frames: usize = bpm.shards.iter().map(|shard| shard.pool_size).sum();
+    if frames != bpm.pool_size {
+        return Err(format!(
+            "{frames} frames in shards for the pool's {} frames",
+            bpm.pool_size
+        ));
+    }
+    for (index, shard) in bpm.shards.iter().enumerate() {
+        check_shard(bpm, index, shard).map_err(|error| format!("shard {index}: {error}"))?;
+    }
+    Ok(())
+}
+
+/// Checks a shard of the pool, see [`check_pool`].
+fn check_shard(bpm: &BufferPoolManager, index: usize, shard: &Shard) -> Result<(), String> {
+    let Ok(frames) = shard.frames.try_lock() else {
+        return Ok(());
+    };
+    let Ok(replacer) = shard.replacer.try_read() else {
+        return Ok(());
+    };
+
+    if frames.pages.len() != shard.pool_size {
+        return Err(format!(
+            "{} frame slots for the shard's {} frames",
+            frames.pages.len(),
+            shard.pool_size
+        ));
+    }
+    let occupied = (frames.free_list.iter()).find(|frame_id| frames.frame(**frame_id).is_some());
+    if let Some(frame_id) = occupied {
+        return Err(format!("free frame {frame_id} holds a page"));
+    }
+    let tracked = (frames.free_list.iter())
+        .find(|frame_id| replacer.is_evictable(frame_id).is_some());
+    if let Some(frame_id) = tracked {
+        return Err(format!("free frame {frame_id} is tracked by the replacer"));
+    }
+    let mut frame_ids: Vec<FrameId> = (frames.page_table.values())
+        .map(|frame_metadata| *frame_metadata.frame_id())
+        .chain(frames.free_list.iter().copied())
+        .collect();
+    frame_ids.sort_unstable();
+    if frame_ids != (0..shard.pool_size).collect::<Vec<_>>() {
+        return Err(format!(
+            "frames in use and free {frame_ids:?} aren't the shard's {} frames",
+            shard.pool_size
+        ));
+    }
+
+    for (page_id, frame_metadata) in &frames.page_table {
+        if bpm.shard_index(page_id) != index {
+            return Err(format!(
+                "page {page_id} belongs to shard {}",
+                bpm.shard_index(page_id)
+            ));
+        }
+        let frame_id = *frame_metadata.frame_id();
+        let page = frames.frame(frame_id).and_then(|page| page.as_table());
+        if let Some(Ok(page)) = page.as_ref().map(|page| page.try_read()) {
+            if page.page_id != *page_id {
+                return Err(format!(
+                    "frame {frame_id} of page {page_id} holds page {}",
+                    page.page_id
+                ));
+            }
+        }
+        let pin_count = frame_metadata.pin_count();
+        let evictable = replacer.is_evictable(&frame_id) == Some(true);
+        if evictable != (pin_count == 0) {
+            return Err(format!(
+                "page {page_id} has {pin_count} pins, but its frame {frame_id} is{} evictable",
+                if evictable { "" } else { " not" }
+            ));
+        }
+    }
+    let evictable = (0..shard.pool_size)
+        .filter(|frame_id| replacer.is_evictable(frame_id) == Some(true))
+        .count();
+    if replacer.size() != evictable {
+        return Err(format!(
+            "replacer size is {}, but {evictable} frames are evictable",
+            replacer.size()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that an LRU-K replacer's size is the number of evictable frames, that its eviction order
+/// holds exactly the evictable frames, and that no frame has more history than it keeps.
+pub(crate) fn check_replacer(replacer: &LRUKReplacer) -> Result<(), String> {
+    let evictable = (replacer.node_store.values())
+        .filter(|node| node.is_evictable)
+        .count();
+    if replacer.curr_size != evictable {
+        return Err(format!(
+            "replacer size is {}, but {evictable} frames are evictable",
+            replacer.curr_size
+        ));
+    }
+    let order: BTreeSet<(EvictionKey, FrameId)> = (replacer.node_store.iter())
+        .filter(|(_, node)| node.is_evictable)
+        .map(|(frame_id, node)| (node.eviction_key(), *frame_id))
+        .collect();
+    if replacer.eviction_order != order {
+        return Err(format!(
+            "replacer eviction order is {:?}, but the evictable frames rank {order:?}",
+            replacer.eviction_order
+        ));
+    }
+    match replacer
+        .node_store
+        .iter()
+        .find(|(_, node)| node.history.len() > replacer.k)
+    {
+        Some((frame_id, node)) => Err(format!(
+            "frame {frame_id} has {} accesses of history, more than k = {}",
+            node.history.len(),
+            replacer.k
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The pins the simulated threads hold, by page, to check the buffer pool's pin counts against.
+/// A thread records a pin right after fetching a page, and an unpin right after unpinning it,
+/// with no scheduling point in between.
+#[derive(Debug, Default)]
+pub(crate) struct PinLedger(Mutex<HashMap<PageId, usize>>);
+
+impl PinLedger {
+    pub(crate) fn pin(&self, page_id: PageId) {
+        *self.0.lock().unwrap().entry(page_id).or_default() += 1;
+    }
+
+    pub(crate) fn unpin(&self, page_id: PageId) {
+        let mut pins = self.0.lock().unwrap();
+        let count = pins.get_mut(&page_id).expect("page was never pinned");
+        *count -= 1;
+        if *count == 0 {
+            pins.remove(&page_id);
+        }
+    }
+
+    /// Checks that every resident page is pinned as often as the ledger says, and that no page
+    /// the ledger has pinned is missing from the pool.
+    pub(crate) fn check(&self, bpm: &BufferPoolManager) -> Result<(), String> {
+        let shards: Vec<MutexGuard<Frames>> = match bpm
+            .shards
+            .iter()
+            .map(|shard| shard.frames.try_lock())
+            .collect()
+        {
+            Ok(shards) => shards,
+            Err(_) => return Ok(()),
+        };
+        let pins = self.0.lock().unwrap();
+        let page_tables = shards.iter().flat_map(|frames| &frames.page_table);
+        for (page_id, frame_metadata) in page_tables {
+            let expected = pins.get(page_id).copied().unwrap_or_default();
+            if frame_metadata.pin_count() != expected {
+                return Err(format!(
+                    "page {page_id} has {} pins, but threads hold {expected}",
+                    frame_metadata.pin_count()
+                ));
+            }
+        }
+        match pins.keys().find(|page_id| {
+            !shards[bpm.shard_index(page_id)].page_table.contains_key(page_id)
+        }) {
+            Some(page_id) => Err(format!("pinned page {page_id} was evicted")),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Deterministic simulation of concurrent storage code, for tests.
+//!
+//! A [`SimExecutor`] runs closures as logical threads, one at a time, switching between them at
+//! the scheduling points the buffer pool, replacer and lock manager mark with `sched_point!`.
+//! Which thread runs next is picked by a seeded random number generator, so a seed always
+//! replays the same interleaving, and a failure found by [`explore`]-ing many seeds can be
+//! debugged by rerunning its seed. Invariants, e.g. those in [`invariants`], are checked at every
+//! step. A [`SimDisk`] keeps the database file and log in memory, and can lose power at a chosen
+//! write.
+//!
+//! Threads must take the locks they share with [`write`], which yields rather than blocks: a
+//! thread blocking while another one holds the baton would never wake up.
+mod disk;
+mod executor;
+pub(crate) mod invariants;
+mod tests;
+
+pub(crate) use disk::{SimDisk, SimFile};
+pub(crate) use executor::{explore, SimExecutor};
+
+use crate::common::sched;
+use std::sync::{RwLock, RwLockWriteGuard, TryLockError};
+
+/// Yields to another simulated thread, e.g. between the steps of a scenario.
+pub(crate) fn yield_point(point: &'static str) {
+    sched::yield_point(point);
+}
+
+/// Takes a write lock, yielding while it is held by another simulated thread.
+pub(crate) fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    loop {
+        match lock.try_write() {
+            Ok(guard) => return guard,
+            Err(TryLockError::WouldBlock) => yield_point("sim.write_lock"),
+            Err(TryLockError::Poisoned(error)) => panic!("{error}"),
+        }
+    }
+}
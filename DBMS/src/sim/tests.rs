@@ -0,0 +1,307 @@
+use super::invariants::{check_pool, PinLedger};
+use super::*;
+use crate::concurrency::{LockManager, LockMode};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::storage::page::{Page, RecordId, TablePageHandle};
+use crate::storage::simple::TxnId;
+use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::{LogManager, LogRecord, LogRecordBody, SyncPolicy};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const THREADS: [&str; 3] = ["t0", "t1", "t2"];
+
+/// Threads pinning and unpinning random pages of a pool too small to hold them all keep the
+/// frames, pin counts and replacer consistent.
+#[test]
+fn test_concurrent_fetch_and_unpin() {
+    explore(0..40, fetch_and_unpin).unwrap_or_else(|failure| panic!("{failure}"));
+}
+
+/// The same seed replays the same interleaving.
+#[test]
+fn test_seed_replays_interleaving() {
+    let trace = fetch_and_unpin(7).run().unwrap();
+    assert_eq!(trace, fetch_and_unpin(7).run().unwrap());
+    assert_ne!(trace, fetch_and_unpin(8).run().unwrap());
+    assert!(trace.iter().any(|(_, point)| *point == "bpm.evict"));
+}
+
+/// Writers incrementing a counter under an exclusive row lock, and a reader upgrading its shared
+/// lock to do the same, never lose an increment, and never hold conflicting locks.
+#[test]
+fn test_lock_manager_serializes_increments() {
+    explore(0..40, locked_increments).unwrap_or_else(|failure| panic!("{failure}"));
+}
+
+/// Pages written by their threads and evicted to make room for others keep every write, as long
+/// as the writers mark them dirty.
+#[test]
+fn test_eviction_keeps_dirty_pages() {
+    explore(0..40, contended_writes).unwrap_or_else(|failure| panic!("{failure}"));
+}
+
+/// Log records whose flush returned are still in the log after the power is cut at any write,
+/// and the log manager never claims more is durable than is.
+#[test]
+fn test_flushed_log_survives_power_cut() {
+    explore(0..40, power_cut_during_flushes).unwrap_or_else(|failure| panic!("{failure}"));
+}
+
+/// A reader unpinning a shared page after a writer did leaves the page dirty, so the write
+/// reaches disk in every interleaving.
+#[test]
+fn test_shared_page_keeps_writes() {
+    explore(0..40, shared_page_writes).unwrap_or_else(|failure| panic!("{failure}"));
+}
+
+fn fetch_and_unpin(seed: u64) -> SimExecutor {
+    let (bpm, page_ids) = setup_pool(3, 6);
+    let ledger = Arc::new(PinLedger::default());
+    let mut sim = SimExecutor::new(seed);
+    for (thread, name) in THREADS.into_iter().enumerate() {
+        let (bpm, ledger, page_ids) = (Arc::clone(&bpm), Arc::clone(&ledger), page_ids.clone());
+        let mut rng = ChaCha8Rng::seed_from_u64(seed << 8 | thread as u64);
+        sim.spawn(name, move || {
+            let mut held = Vec::new();
+            for _ in 0..10 {
+                if held.len() < 2 && rng.gen_bool(0.6) {
+                    let page_id = page_ids[rng.gen_range(0..page_ids.len())];
+                    if bpm.fetch_page(&page_id).is_ok() {
+                        ledger.pin(page_id);
+                        held.push(page_id);
+                    }
+                } else if let Some(page_id) = held.pop() {
+                    bpm.unpin_page(&page_id, false).unwrap();
+                    ledger.unpin(page_id);
+                }
+                yield_point("scenario.step");
+            }
+            for page_id in held {
+                bpm.unpin_page(&page_id, false).unwrap();
+                ledger.unpin(page_id);
+            }
+        });
+    }
+    let check_bpm = Arc::clone(&bpm);
+    sim.invariant(move || check_pool(&check_bpm));
+    sim.invariant(move || ledger.check(&bpm));
+    sim
+}
+
+fn locked_increments(seed: u64) -> SimExecutor {
+    const ROUNDS: u64 = 4;
+    let locks = Arc::new(LockManager::new(Duration::from_secs(3600)));
+    let counter = Arc::new(AtomicU64::new(0));
+    let rid = RecordId::new(1, 0);
+    let txn_id = |thread: usize, round: u64| (thread as TxnId + 1) * 100 + round;
+    let mut sim = SimExecutor::new(seed);
+    for (thread, name) in THREADS.into_iter().enumerate() {
+        let (locks, counter, rid) = (Arc::clone(&locks), Arc::clone(&counter), rid.clone());
+        sim.spawn(name, move || {
+            for round in 0..ROUNDS {
+                let txn_id = txn_id(thread, round);
+                locks.lock_exclusive(txn_id, &rid).unwrap();
+                let value = counter.load(Ordering::SeqCst);
+                yield_point("scenario.increment");
+                counter.store(value + 1, Ordering::SeqCst);
+                locks.unlock_all(txn_id).unwrap();
+            }
+        });
+    }
+    {
+        let (locks, counter, rid) = (Arc::clone(&locks), Arc::clone(&counter), rid.clone());
+        sim.spawn("upgrader", move || {
+            for round in 0..ROUNDS {
+                let txn_id = txn_id(THREADS.len(), round);
+                locks.lock_shared(txn_id, &rid).unwrap();
+                let value = counter.load(Ordering::SeqCst);
+                yield_point("scenario.read");
+                assert_eq!(
+                    value,
+                    counter.load(Ordering::SeqCst),
+                    "read isn't repeatable"
+                );
+                locks.lock_exclusive(txn_id, &rid).unwrap();
+                counter.store(value + 1, Ordering::SeqCst);
+                locks.unlock_all(txn_id).unwrap();
+            }
+        });
+    }
+
+    let txn_ids: Vec<TxnId> = (0..=THREADS.len())
+        .flat_map(|thread| (0..ROUNDS).map(move |round| txn_id(thread, round)))
+        .collect();
+    let check_rid = rid.clone();
+    let check_locks = Arc::clone(&locks);
+    sim.invariant(move || {
+        let modes: Vec<LockMode> = (txn_ids.iter())
+            .filter_map(|txn_id| check_locks.lock_mode(*txn_id, &check_rid).unwrap())
+            .collect();
+        if modes.contains(&LockMode::Exclusive) && modes.len() > 1 {
+            return Err(format!("conflicting locks held: {modes:?}"));
+        }
+        Ok(())
+    });
+    sim.postcondition(move || match counter.load(Ordering::SeqCst) {
+        count if count == (THREADS.len() as u64 + 1) * ROUNDS => Ok(()),
+        count => Err(format!("lost increments, counter is {count}")),
+    });
+    sim
+}
+
+fn contended_writes(seed: u64) -> SimExecutor {
+    let (bpm, page_ids) = setup_pool(2, 4);
+    let writes = Arc::new(Mutex::new(HashMap::<PageId, u16>::new()));
+    let mut sim = SimExecutor::new(seed);
+    for (thread, name) in THREADS[..2].iter().copied().enumerate() {
+        let (bpm, writes) = (Arc::clone(&bpm), Arc::clone(&writes));
+        let own_pages = [page_ids[thread], page_ids[thread + 2]];
+        let mut rng = ChaCha8Rng::seed_from_u64(seed << 8 | thread as u64);
+        sim.spawn(name, move || {
+            for round in 0..5u8 {
+                let page_id = own_pages[rng.gen_range(0..own_pages.len())];
+                let Ok(page) = bpm.fetch_page(&page_id) else {
+                    continue;
+                };
+                // Like the table heap, mark the page dirty right away.
+                insert(&page, round);
+                write(&page).is_dirty = true;
+                *writes.lock().unwrap().entry(page_id).or_default() += 1;
+                yield_point("scenario.write");
+                bpm.unpin_page(&page_id, true).unwrap();
+            }
+        });
+    }
+    let check_bpm = Arc::clone(&bpm);
+    sim.invariant(move || check_pool(&check_bpm));
+    sim.postcondition(move || check_writes(&bpm, &writes.lock().unwrap()));
+    sim
+}
+
+fn shared_page_writes(seed: u64) -> SimExecutor {
+    let (bpm, page_ids) = setup_pool(2, 2);
+    let page_id = page_ids[0];
+    let writes = Arc::new(Mutex::new(HashMap::<PageId, u16>::new()));
+    let mut sim = SimExecutor::new(seed);
+    for (thread, name) in ["writer", "reader"].into_iter().enumerate() {
+        let (bpm, writes) = (Arc::clone(&bpm), Arc::clone(&writes));
+        sim.spawn(name, move || {
+            for round in 0..3u8 {
+                let page = bpm.fetch_page(&page_id).unwrap();
+                yield_point("scenario.access");
+                let is_writer = thread == 0;
+                if is_writer {
+                    insert(&page, round);
+                    *writes.lock().unwrap().entry(page_id).or_default() += 1;
+                }
+                bpm.unpin_page(&page_id, is_writer).unwrap();
+            }
+        });
+    }
+    sim.postcondition(move || check_writes(&bpm, &writes.lock().unwrap()));
+    sim
+}
+
+fn power_cut_during_flushes(seed: u64) -> SimExecutor {
+    let disk = SimDisk::new();
+    let disk_manager = Arc::new(RwLock::new(disk.disk_manager()));
+    let log = Arc::new(LogManager::new(disk_manager, SyncPolicy::Commit));
+    disk.crash_at(seed as usize % 16);
+    let acknowledged = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = SimExecutor::new(seed);
+    for (thread, name) in THREADS.into_iter().enumerate() {
+        let (log, acknowledged) = (Arc::clone(&log), Arc::clone(&acknowledged));
+        sim.spawn(name, move || {
+            for round in 0..3 {
+                let lsn = log
+                    .append(thread as TxnId * 10 + round, LogRecordBody::Begin)
+                    .unwrap();
+                yield_point("scenario.append");
+                if log.flush(lsn).is_err() {
+                    return;
+                }
+                acknowledged.lock().unwrap().push(lsn);
+            }
+        });
+    }
+
+    let check_disk = disk.clone();
+    sim.invariant(move || {
+        let durable =
+            LogRecord::decode_all(&check_disk.durable_log()).map_err(|e| e.to_string())?;
+        let last = durable.last().map_or(0, |record| record.lsn);
+        if log.durable_lsn() > last {
+            return Err(format!(
+                "durable LSN {} is past the log's {last}",
+                log.durable_lsn()
+            ));
+        }
+        Ok(())
+    });
+    sim.postcondition(move || {
+        let mut disk_manager = DiskManager::new_simulated(&disk.restart());
+        let bytes = disk_manager.read_log().map_err(|e| e.to_string())?;
+        let records = LogRecord::decode_all(&bytes).map_err(|e| e.to_string())?;
+        let lost: Vec<_> = (acknowledged.lock().unwrap().iter())
+            .filter(|lsn| !records.iter().any(|record| record.lsn == **lsn))
+            .copied()
+            .collect();
+        if !lost.is_empty() {
+            return Err(format!("flushed records {lost:?} lost in the power cut"));
+        }
+        Ok(())
+    });
+    sim
+}
+
+/// Returns a buffer pool over a simulated disk with `pages` unpinned pages, evicting as needed.
+fn setup_pool(pool_size: usize, pages: usize) -> (Arc<BufferPoolManager>, Vec<PageId>) {
+    let disk_manager = Arc::new(RwLock::new(SimDisk::new().disk_manager()));
+    let bpm = BufferPoolManager::builder()
+        .pool_size(pool_size)
+        .replacer_k(2)
+        .disk_manager(disk_manager)
+        .build_with_handle();
+    let page_ids = (0..pages)
+        .map(|_| {
+            let page_id = bpm.new_page().unwrap();
+            bpm.unpin_page(&page_id, false).unwrap();
+            page_id
+        })
+        .collect();
+    (bpm, page_ids)
+}
+
+fn insert(page: &TablePageHandle, round: u8) {
+    let mut page = write(page);
+    page.insert_tuple(TupleMetadata::new(false), Tuple::from(&[round][..]))
+        .unwrap();
+}
+
+/// Writes back the dirty pages, drops everything else the pool holds, and checks that each
+/// page holds as many tuples as were written to it.
+fn check_writes(
+    bpm: &BufferPoolManager,
+    writes: &HashMap<PageId, u16>,
+) -> Result<(), String> {
+    bpm.checkpoint().map_err(|e| e.to_string())?;
+    bpm.crash_for_test();
+    for (page_id, count) in writes {
+        let page = bpm.fetch_page(page_id).map_err(|e| e.to_string())?;
+        let tuples = page.read().unwrap().tuple_cnt;
+        bpm.unpin_page(page_id, false).map_err(|e| e.to_string())?;
+        if tuples != *count {
+            return Err(format!(
+                "page {page_id} lost {} of {count} writes",
+                count - tuples
+            ));
+        }
+    }
+    Ok(())
+}
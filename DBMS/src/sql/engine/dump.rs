@@ -0,0 +1,108 @@
+use super::{Catalog, Transaction};
+use crate::common::Result;
+use crate::config::config::DEFAULT_FILL_FACTOR;
+use crate::errdata;
+use crate::sql::parser::Keyword;
+use crate::sql::planner::Direction;
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+use std::io::Write;
+
+/// The number of rows each INSERT statement of a dump holds.
+const INSERT_BATCH_ROWS: usize = 100;
+
+/// Writes a logical dump of the database, as the transaction sees it: a
+/// script that recreates its tables, and then inserts their rows, which
+/// [`super::Session::execute_script`] restores into an empty database.
+///
+/// Tables are dumped in name order; the catalog records no foreign keys, so
+/// no table needs another to exist first. Rows are dumped in record id order,
+/// in INSERT statements of up to 100 rows each, written out as they are
+/// scanned.
+pub fn dump(txn: &(impl Transaction + Catalog), writer: &mut impl Write) -> Result<()> {
+    for table in txn.list_tables()? {
+        writeln!(writer, "{};", create_table(&table)?)?;
+        let name = quote_identifier(table.name());
+        let mut batch = Vec::with_capacity(INSERT_BATCH_ROWS);
+        for row in txn.scan(table.name(), None, Some(Direction::Ascending))? {
+            let (_, row) = row?;
+            let values: Vec<_> = row.iter().map(Field::to_sql_literal).collect();
+            batch.push(format!("({})", values.join(", ")));
+            if batch.len() == INSERT_BATCH_ROWS {
+                write_insert(writer, &name, &batch)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            write_insert(writer, &name, &batch)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_insert(writer: &mut impl Write, table: &str, values: &[String]) -> Result<()> {
+    writeln!(
+        writer,
+        "INSERT INTO {table} VALUES\n    {};",
+        values.join(",\n    ")
+    )?;
+    Ok(())
+}
+
+/// Returns the CREATE TABLE statement that creates a table like the given
+/// one, along with its indexes.
+fn create_table(table: &Table) -> Result<String> {
+    let mut columns = Vec::new();
+    for column in table.columns() {
+        let data_type = match column.get_data_type() {
+            DataType::Bool => "BOOLEAN",
+            DataType::Int => "INTEGER",
+            DataType::Float => "FLOAT",
+            DataType::Text => "TEXT",
+            DataType::Invalid => {
+                return errdata!("column {} has an invalid type", column.get_name())
+            }
+        };
+        let mut definition = format!("{} {data_type}", quote_identifier(&column.get_name()));
+        match column.is_nullable() {
+            true => definition.push_str(" NULL"),
+            false => definition.push_str(" NOT NULL"),
+        }
+        // Nullable columns default to NULL without saying so.
+        match column.default() {
+            Some(Field::Null) if column.is_nullable() => {}
+            Some(default) => definition.push_str(&format!(" DEFAULT {}", default.to_sql_literal())),
+            None => {}
+        }
+        // Unique columns are indexed anyway.
+        if column.is_unique() {
+            definition.push_str(" UNIQUE");
+        } else if column.is_indexed() {
+            definition.push_str(" INDEX");
+        }
+        columns.push(definition);
+    }
+    let mut statement = format!(
+        "CREATE TABLE {} (\n    {}\n)",
+        quote_identifier(table.name()),
+        columns.join(",\n    ")
+    );
+    if table.fill_factor() != DEFAULT_FILL_FACTOR {
+        statement.push_str(&format!(" WITH (fillfactor = {})", table.fill_factor()));
+    }
+    Ok(statement)
+}
+
+/// Returns an identifier as the lexer reads it back: bare if it is lowercase
+/// and not a keyword, or else quoted.
+fn quote_identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let bare = chars.next().is_some_and(|c| c.is_lowercase())
+        && chars.all(|c| c == '_' || c.is_numeric() || c.is_lowercase())
+        && Keyword::try_from(name).is_err();
+    match bare {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
@@ -1,10 +1,16 @@
-use crate::common::Result;
+use super::PlanCache;
+use crate::common::{Metrics, Result};
+use crate::concurrency::IsolationLevel;
 use crate::errinput;
-use crate::sql::planner::Expression;
+use crate::sql::planner::{Direction, Expression};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::{Row, Rows};
+use crate::storage::wal::Lsn;
+use crate::storage::VacuumStats;
+use crate::types::field::Field;
 use crate::types::Table;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 
 /// A SQL query engine.
 ///
@@ -15,26 +21,92 @@ pub trait Engine<'a>: Sized {
     /// to table rows and schemas. It does not outlive the engine.
     type Transaction: Transaction + Catalog + 'a;
 
-    /// Begins a read-write transaction.
-    fn begin(&'a self) -> Result<Self::Transaction>;
+    /// Begins a read-write transaction at the default isolation level.
+    fn begin(&'a self) -> Result<Self::Transaction> {
+        self.begin_with(IsolationLevel::default())
+    }
+
+    /// Begins a read-write transaction at the given isolation level.
+    fn begin_with(&'a self, isolation: IsolationLevel) -> Result<Self::Transaction>;
+
+    /// Begins a read-only transaction at the default isolation level.
+    fn begin_read_only(&'a self) -> Result<Self::Transaction> {
+        self.begin_read_only_with(IsolationLevel::default())
+    }
+
+    /// Begins a read-only transaction at the given isolation level. Writes in
+    /// it fail with [`crate::common::Error::ReadOnly`].
+    fn begin_read_only_with(&'a self, isolation: IsolationLevel) -> Result<Self::Transaction>;
+
+    /// Writes modified pages back to disk and truncates the write-ahead log,
+    /// returning the LSN of the checkpoint record.
+    fn checkpoint(&'a self) -> Result<Lsn>;
+
+    /// Removes the tuple versions no transaction can see anymore from the
+    /// given table, or from every table.
+    fn vacuum(&'a self, table: Option<&str>) -> Result<VacuumStats>;
+
+    /// Like [`Self::vacuum`], but also rewrites the tables into as few pages
+    /// as their rows fit. Fails while other transactions are running.
+    fn vacuum_full(&'a self, table: Option<&str>) -> Result<VacuumStats>;
+
+    /// Returns the registry the engine counts its statements and transactions in.
+    fn metrics(&'a self) -> &'a Metrics;
+
+    /// Returns the cache of plans the engine's sessions share.
+    fn plan_cache(&'a self) -> &'a PlanCache;
 }
 
 /// A SQL transaction.
 ///
 /// Tuples are passed around as serialized byte streams, which can be deserialized
 /// into `Tuple` instances with their corresponding Table schema definition.
-///
-/// Currently, all query execution tasks occur in a singleton transaction instance.
-/// TODO(eyoon): Provide transactional execution with snapshot isolation (MVCC)
 pub trait Transaction {
+    /// Returns the transaction's isolation level.
+    fn isolation(&self) -> IsolationLevel;
+    /// Marks the start of a statement. Under read committed, the statement
+    /// then sees every write committed before it.
+    fn start_statement(&self) -> Result<()>;
+    /// Commits the transaction, making its writes durable according to the
+    /// write-ahead log's sync policy.
+    fn commit(self) -> Result<()>
+    where
+        Self: Sized;
+    /// Rolls back the transaction, undoing all of its writes.
+    fn rollback(self) -> Result<()>
+    where
+        Self: Sized;
     /// Deletes tuples of a table by record id (RID), if they exist.
     fn delete(&self, table: &str, ids: &[RecordId]) -> Result<()>;
     /// Inserts tuples into a table, and returns a vector of their corresponding record ids.
     fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<RecordId>>;
     /// Sequentially scans a table's tuples, applying a filter if specified.
-    fn scan(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows>;
+    /// Given a direction, emits them in record id order, or in reverse when
+    /// it is descending; otherwise in any order.
+    fn scan(
+        &self,
+        table_name: &str,
+        filter: Option<Expression>,
+        direction: Option<Direction>,
+    ) -> Result<Rows>;
+    /// Returns the number of rows in a table, like counting the rows of a
+    /// scan, but without reading them where possible.
+    fn count(&self, table_name: &str) -> Result<u64>;
+    /// Scans a table's tuples through the index on the given column, emitting
+    /// those with a column value in `range` in index order, or in reverse when
+    /// `direction` is descending. Errors if the column isn't indexed.
+    fn index_scan(
+        &self,
+        table_name: &str,
+        column: usize,
+        range: (Bound<Field>, Bound<Field>),
+        direction: Direction,
+    ) -> Result<Rows>;
     /// Updates the table's tuples with record id in `rows` to the corresponding given tuple.
     fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<()>;
+    /// Sets the number of threads later table scans split a table's pages
+    /// between. With more than one, scans emit rows in no particular order.
+    fn set_parallel_scan_workers(&self, workers: usize);
 }
 
 /// Stores table schema information.
@@ -48,6 +120,17 @@ pub trait Catalog {
     /// Fetches the schema for the table corresponding to `table_name`.
     /// Returns `None` if no such table exists.
     fn get_table(&self, table_name: &str) -> Result<Option<Table>>;
+    /// Fetches the schemas of all stored tables, ordered by name. Virtual
+    /// tables aren't listed.
+    fn list_tables(&self) -> Result<Vec<Table>>;
+    /// Returns the catalog's version, which goes up whenever a table is
+    /// created or dropped.
+    fn catalog_version(&self) -> Result<u64>;
+    /// Returns the version of a table's schema: the catalog version it was
+    /// last created or dropped at, or 0 if neither happened since the engine
+    /// started. Cached plans are stale once the version of a table they
+    /// reference changes.
+    fn schema_version(&self, table_name: &str) -> Result<u64>;
 
     /// Fetches the schema for the table corresponding to `table_id`.
     /// Errors if no such table exists.
@@ -0,0 +1,64 @@
+use crate::common::Result;
+use crate::concurrency::TransactionManager;
+use crate::storage::page::INVALID_RID;
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+use std::time::SystemTime;
+
+/// The virtual table listing the running transactions.
+pub const TRANSACTIONS: &str = "information_schema.transactions";
+
+/// Returns whether the table is a read-only virtual table, whose rows are
+/// generated on every scan rather than stored.
+pub fn is_virtual(table_name: &str) -> bool {
+    table_name == TRANSACTIONS
+}
+
+/// Returns the schema of the virtual table with the given name, if any.
+pub fn get_table(table_name: &str) -> Option<Table> {
+    if table_name != TRANSACTIONS {
+        return None;
+    }
+    let table = Table::builder()
+        .name(TRANSACTIONS)
+        .column("id", DataType::Int, false, None, None)
+        .column("state", DataType::Text, false, None, None)
+        .column("isolation_level", DataType::Text, false, None, None)
+        .column("read_only", DataType::Bool, false, None, None)
+        .column("age_ms", DataType::Int, false, None, None)
+        .column("rows_read", DataType::Int, false, None, None)
+        .column("rows_written", DataType::Int, false, None, None)
+        .column("terminated", DataType::Bool, false, None, None)
+        .build();
+    Some(table)
+}
+
+/// Returns a row for every transaction in the registry.
+pub fn transactions(registry: &TransactionManager) -> Result<Rows> {
+    let now = SystemTime::now();
+    let rows: Vec<_> = registry
+        .list_transactions()?
+        .into_iter()
+        .map(|txn| {
+            let age = now.duration_since(txn.started).unwrap_or_default();
+            let row = Row::from(vec![
+                integer(txn.id),
+                Field::String(txn.state.to_string()),
+                Field::String(txn.isolation.to_string()),
+                Field::Boolean(txn.read_only),
+                integer(age.as_millis() as u64),
+                integer(txn.rows_read),
+                integer(txn.rows_written),
+                Field::Boolean(txn.terminated),
+            ]);
+            Ok((INVALID_RID, row))
+        })
+        .collect();
+    Ok(Box::new(rows.into_iter()))
+}
+
+/// Converts a counter to an integer field, saturating at the largest integer.
+fn integer(value: u64) -> Field {
+    Field::Integer(i32::try_from(value).unwrap_or(i32::MAX))
+}
@@ -1,15 +1,22 @@
-use crate::common::Result;
-use crate::sql::engine::{Catalog, Session};
-use crate::sql::planner::Expression;
+use crate::common::{Error, Metrics, Result};
+use crate::concurrency::{IsolationLevel, TransactionManager};
+use crate::config::config::PLAN_CACHE_SIZE;
+use crate::sql::engine::plan_cache::SchemaVersions;
+use crate::sql::engine::{information_schema, Catalog, PlanCache, Session};
+use crate::sql::planner::{Direction, Expression};
 use crate::storage::page::RecordId;
-use crate::storage::simple::Simple;
-use crate::storage::tuple::{Row, Rows};
-use crate::storage::{simple, Key};
+use crate::storage::simple::{ScanMap, Simple};
+use crate::storage::tuple::{get_field, Row, Rows, Tuple};
+use crate::storage::wal::Lsn;
+use crate::storage::{simple, Key, VacuumStats};
 use crate::types::field::Field;
 use crate::types::Table;
 use crate::{errinput, storage};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::ErrorKind;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use crate::common::Error::InvalidInput;
 
 /// A SQL engine using local storage. This is a single-transaction,
@@ -17,6 +24,12 @@ use crate::common::Error::InvalidInput;
 pub struct Local<E: storage::Engine + 'static> {
     /// The local non-concurrent storage engine.
     pub simple: Simple<E>,
+    /// Counts the statements run and the transactions running.
+    metrics: Arc<Metrics>,
+    /// The plans of the statements the sessions ran last.
+    plan_cache: PlanCache,
+    /// The table schema versions the cached plans are checked against.
+    schema_versions: Arc<SchemaVersions>,
 }
 
 impl<'a, E: storage::Engine> Local<E> {
@@ -24,9 +37,19 @@ impl<'a, E: storage::Engine> Local<E> {
     pub fn new(engine: E) -> Self {
         Self {
             simple: Simple::new(engine),
+            metrics: Arc::default(),
+            plan_cache: PlanCache::new(PLAN_CACHE_SIZE),
+            schema_versions: Arc::default(),
         }
     }
 
+    /// Counts statements and transactions in the given registry, e.g. the one the storage
+    /// engine's buffer pool counts in.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Creates a session which executes SQL statements.
     /// Does not outlive engine.
     pub fn session(&'a self) -> Session<'a, Self> {
@@ -37,75 +60,234 @@ impl<'a, E: storage::Engine> Local<E> {
 impl<'a, E: storage::Engine> super::Engine<'a> for Local<E> {
     type Transaction = Transaction<E>;
 
-    fn begin(&'a self) -> Result<Self::Transaction> {
-        Ok(Transaction::new(self.simple.begin()?))
+    fn begin_with(&'a self, isolation: IsolationLevel) -> Result<Self::Transaction> {
+        Ok(Transaction::new(
+            self.simple.begin_with(isolation)?,
+            self.simple.transaction_manager(),
+            &self.metrics,
+            Arc::clone(&self.schema_versions),
+        ))
+    }
+
+    fn begin_read_only_with(&'a self, isolation: IsolationLevel) -> Result<Self::Transaction> {
+        Ok(Transaction::new(
+            self.simple.begin_read_only_with(isolation)?,
+            self.simple.transaction_manager(),
+            &self.metrics,
+            Arc::clone(&self.schema_versions),
+        ))
+    }
+
+    fn checkpoint(&'a self) -> Result<Lsn> {
+        self.simple.checkpoint()
+    }
+
+    fn vacuum(&'a self, table: Option<&str>) -> Result<VacuumStats> {
+        self.simple.vacuum(table)
+    }
+
+    fn vacuum_full(&'a self, table: Option<&str>) -> Result<VacuumStats> {
+        self.simple.vacuum_full(table)
+    }
+
+    fn metrics(&'a self) -> &'a Metrics {
+        &self.metrics
+    }
+
+    fn plan_cache(&'a self) -> &'a PlanCache {
+        &self.plan_cache
     }
 }
 
 /// A SQL transaction, wrapping a simple transaction.
 pub struct Transaction<E: storage::Engine + 'static> {
     txn: simple::Transaction<E>,
+    /// The registry of running transactions, listed by the
+    /// `information_schema.transactions` virtual table.
+    registry: Arc<TransactionManager>,
+    /// The number of threads table scans split a table's pages between.
+    parallel_scan_workers: AtomicUsize,
+    /// The table schema versions, bumped by creating and dropping tables.
+    schema_versions: Arc<SchemaVersions>,
+    /// Counts the transaction as active until it is dropped, on commit or rollback.
+    _active: ActiveTransaction,
+}
+
+/// Counts a transaction in [`Metrics`] as active for as long as it is held.
+struct ActiveTransaction(Arc<Metrics>);
+
+impl ActiveTransaction {
+    fn new(metrics: &Arc<Metrics>) -> Self {
+        metrics.transactions_active.incr();
+        Self(Arc::clone(metrics))
+    }
+}
+
+impl Drop for ActiveTransaction {
+    fn drop(&mut self) {
+        self.0.transactions_active.decr();
+    }
 }
 
 #[allow(dead_code)]
 impl<E: storage::Engine> Transaction<E> {
     /// Creates a new SQL transaction using the given simple transaction.
     /// This "transaction" is just a reference to the engine wrapped in a mutex.
-    fn new(txn: simple::Transaction<E>) -> Self {
-        Self { txn }
+    fn new(
+        txn: simple::Transaction<E>,
+        registry: Arc<TransactionManager>,
+        metrics: &Arc<Metrics>,
+        schema_versions: Arc<SchemaVersions>,
+    ) -> Self {
+        Self {
+            txn,
+            registry,
+            parallel_scan_workers: AtomicUsize::new(1),
+            schema_versions,
+            _active: ActiveTransaction::new(metrics),
+        }
+    }
+
+    /// Errors if the table is a virtual table, which can't be written to.
+    fn check_writable(table_name: &str) -> Result<()> {
+        match information_schema::is_virtual(table_name) {
+            true => Err(Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// Runs a write statement atomically: if it fails partway, the writes it
+    /// already made are rolled back before the error is returned.
+    fn atomically<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let savepoint = self.txn.savepoint()?;
+        f().or_else(|error| {
+            self.txn.rollback_to(savepoint)?;
+            Err(error)
+        })
     }
 }
 
 /// See `[super::Transaction]` for method documentation.
 impl<E: storage::Engine> super::Transaction for Transaction<E> {
+    fn isolation(&self) -> IsolationLevel {
+        self.txn.isolation()
+    }
+
+    fn start_statement(&self) -> Result<()> {
+        self.txn.start_statement()
+    }
+
+    fn commit(self) -> Result<()> {
+        self.txn.commit()
+    }
+
+    fn rollback(self) -> Result<()> {
+        self.txn.rollback()
+    }
+
     fn delete(&self, table_name: &str, ids: &[RecordId]) -> Result<()> {
-        for rid in ids.iter() {
-            self.txn.delete(Key::new(table_name, rid))?;
-        }
-        Ok(())
+        Self::check_writable(table_name)?;
+        self.atomically(|| {
+            for rid in ids.iter() {
+                self.txn.delete(Key::new(table_name, rid))?;
+            }
+            Ok(())
+        })
     }
 
     fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<RecordId>> {
+        Self::check_writable(table_name)?;
         let schema = self.txn.fetch_table(table_name)?.unwrap();
-        rows.into_iter()
-            .map(|row| self.txn.insert(table_name, row.to_tuple(&schema)?))
-            .collect()
+        self.atomically(|| {
+            rows.into_iter()
+                .map(|row| self.txn.insert(table_name, row.to_tuple(&schema)?))
+                .collect()
+        })
     }
 
-    fn scan(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows> {
-        let schema = self.txn.fetch_table(table_name)?.unwrap();
-        let unpack = move |(rid, tuple)| (rid, Row::from_tuple(tuple, &schema).unwrap());
-        let iter = self.txn.scan(table_name);
-
-        // No filter; just return a row iterator
-        let Some(filter) = filter else {
-            return Ok(Box::new(
-                iter.map(move |result| result.and_then(|item| Ok(unpack(item)))),
-            ));
+    fn scan(
+        &self,
+        table_name: &str,
+        filter: Option<Expression>,
+        direction: Option<Direction>,
+    ) -> Result<Rows> {
+        if information_schema::is_virtual(table_name) {
+            let mut iter = information_schema::transactions(&self.registry)?;
+            if direction == Some(Direction::Descending) {
+                let rows: Vec<_> = iter.collect();
+                iter = Box::new(rows.into_iter().rev());
+            }
+            let Some(filter) = filter else {
+                return Ok(iter);
+            };
+            // Return a row iterator that filters out rows that do not satisfy the predicate.
+            let iter = iter.filter_map(move |result| {
+                result
+                    .and_then(|(rid, row)| Ok(satisfies(&filter, &row)?.then_some((rid, row))))
+                    .transpose()
+            });
+            return Ok(Box::new(iter));
+        }
+
+        let decoder = ScanDecoder::new(self.must_get_table(table_name)?, filter);
+        let workers = self.parallel_scan_workers.load(Ordering::Relaxed);
+        if workers > 1 && direction.is_none() {
+            // The workers decode and filter the rows too, not just read them.
+            let map: ScanMap<(RecordId, Row)> =
+                Arc::new(move |rid, tuple| Ok(decoder.decode(tuple)?.map(|row| (rid, row))));
+            return Ok(Box::new(self.txn.parallel_scan(table_name, workers, map)?));
+        }
+        let iter = match direction {
+            Some(Direction::Descending) => self.txn.scan_rev(table_name)?,
+            Some(Direction::Ascending) | None => self.txn.scan(table_name)?,
         };
-        // Return a row iterator that filters out tuples that do not satisfy the predicate.
         let iter = iter.filter_map(move |result| {
             result
-                .and_then(|item| {
-                    let (rid, row) = unpack(item);
-                    match filter.evaluate(Some(&row))? {
-                        Field::Boolean(true) => Ok(Some((rid, row))),
-                        Field::Boolean(false) | Field::Null => Ok(None),
-                        value => errinput!("filter returned {value}, expected boolean."),
-                    }
-                })
+                .and_then(|(rid, tuple)| Ok(decoder.decode(tuple)?.map(|row| (rid, row))))
                 .transpose()
         });
         Ok(Box::new(iter))
     }
 
+    fn count(&self, table_name: &str) -> Result<u64> {
+        if information_schema::is_virtual(table_name) {
+            return self.scan(table_name, None, None)?.try_fold(0, |count, result| {
+                result?;
+                Ok(count + 1)
+            });
+        }
+        self.txn.count(table_name)
+    }
+
+    fn index_scan(
+        &self,
+        table_name: &str,
+        column: usize,
+        range: (Bound<Field>, Bound<Field>),
+        direction: Direction,
+    ) -> Result<Rows> {
+        let schema = self.must_get_table(table_name)?;
+        let reverse = direction == Direction::Descending;
+        let iter = self.txn.index_scan(table_name, column, range, reverse)?;
+        Ok(Box::new(iter.map(move |result| {
+            result.and_then(|(rid, tuple)| Ok((rid, Row::from_tuple(tuple, &schema)?)))
+        })))
+    }
+
     fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<()> {
+        Self::check_writable(table_name)?;
         let schema = self.must_get_table(table_name)?;
-        for (rid, row) in rows {
-            self.txn
-                .update(Key::new(table_name, &rid), row.to_tuple(&schema)?)?;
-        }
-        Ok(())
+        self.atomically(|| {
+            for (rid, row) in rows {
+                self.txn
+                    .update(Key::new(table_name, &rid), row.to_tuple(&schema)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn set_parallel_scan_workers(&self, workers: usize) {
+        self.parallel_scan_workers.store(workers, Ordering::Relaxed);
     }
 }
 
@@ -115,19 +297,113 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
 /// e.g. Transaction::create_table(). You also might need `Error::InvalidInput`.
 impl<E: storage::Engine> Catalog for Transaction<E> {
     fn create_table(&self, table: Table) -> Result<()> {
-        self.txn.create_table(table)
+        if information_schema::is_virtual(table.name()) {
+            return errinput!("table {} already exists", table.name());
+        }
+        let name = table.name().to_string();
+        self.txn.create_table(table)?;
+        self.schema_versions.bump(&name)
     }
 
     fn drop_table(&self, table_name: &str, if_exists: bool) -> Result<bool> {
+        Self::check_writable(table_name)?;
 
         if self.txn.fetch_table(table_name)?.is_none() && !if_exists {
             panic!("Invalid Input")
         } else {
-            self.txn.delete_table(table_name)
+            let existed = self.txn.delete_table(table_name)?;
+            self.schema_versions.bump(table_name)?;
+            Ok(existed)
         }
     }
 
     fn get_table(&self, table_name: &str) -> Result<Option<Table>> {
+        if let Some(table) = information_schema::get_table(table_name) {
+            return Ok(Some(table));
+        }
         self.txn.fetch_table(table_name)
     }
+
+    fn list_tables(&self) -> Result<Vec<Table>> {
+        self.txn.list_tables()
+    }
+
+    fn catalog_version(&self) -> Result<u64> {
+        self.schema_versions.catalog()
+    }
+
+    fn schema_version(&self, table_name: &str) -> Result<u64> {
+        self.schema_versions.table(table_name)
+    }
+}
+
+/// Returns whether a row satisfies a filter predicate, which NULL doesn't.
+fn satisfies(filter: &Expression, row: &Row) -> Result<bool> {
+    match filter.evaluate(Some(row))? {
+        Field::Boolean(value) => Ok(value),
+        Field::Null => Ok(false),
+        value => errinput!("filter returned {value}, expected boolean."),
+    }
+}
+
+/// Decodes the tuples of a table scan into rows, keeping those that satisfy the
+/// scan's filter, if any. The filter is evaluated on a partially decoded row,
+/// with only the columns it references decoded and the others left NULL, so
+/// tuples that don't satisfy it are never decoded in full. Filters referencing
+/// every column gain nothing from this, and are evaluated on the full row.
+#[derive(Clone)]
+struct ScanDecoder {
+    schema: Table,
+    filter: Option<Expression>,
+    /// The columns the filter references, in order, or None to decode the
+    /// whole row before evaluating it.
+    columns: Option<Vec<usize>>,
+}
+
+impl ScanDecoder {
+    fn new(schema: Table, filter: Option<Expression>) -> Self {
+        let columns = filter.as_ref().and_then(|filter| {
+            let mut columns = BTreeSet::new();
+            filter.walk(&mut |expr| {
+                if let Expression::Column(index) = expr {
+                    columns.insert(*index);
+                }
+                true
+            });
+            (columns.len() < schema.col_count()).then(|| columns.into_iter().collect())
+        });
+        Self {
+            schema,
+            filter,
+            columns,
+        }
+    }
+
+    /// Decodes a tuple into a row, or returns None if it doesn't satisfy the
+    /// filter.
+    fn decode(&self, tuple: Tuple) -> Result<Option<Row>> {
+        let (Some(filter), Some(columns)) = (&self.filter, &self.columns) else {
+            let row = Row::from_tuple(tuple, &self.schema)?;
+            return match &self.filter {
+                Some(filter) if !satisfies(filter, &row)? => Ok(None),
+                _ => Ok(Some(row)),
+            };
+        };
+        let mut values = vec![Field::Null; self.schema.col_count()];
+        for &column in columns {
+            let value = get_field(&tuple.data, &self.schema, column)?;
+            values[column] = value;
+        }
+        let row = Row::from(values);
+        if !satisfies(filter, &row)? {
+            return Ok(None);
+        }
+        let mut values: Vec<Field> = row.into_iter().collect();
+        for (column, value) in values.iter_mut().enumerate() {
+            if columns.binary_search(&column).is_err() {
+                *value = get_field(&tuple.data, &self.schema, column)?;
+            }
+        }
+        Ok(Some(Row::from(values)))
+    }
 }
@@ -1,7 +1,13 @@
+mod dump;
 mod engine;
+mod information_schema;
 mod local;
+mod plan_cache;
 mod session;
 
+pub use dump::dump;
 pub use engine::{Catalog, Engine, Transaction};
+pub use information_schema::TRANSACTIONS;
 pub use local::Local;
+pub use plan_cache::PlanCache;
 pub use session::{Session, StatementResult};
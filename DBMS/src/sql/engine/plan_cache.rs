@@ -0,0 +1,207 @@
+use super::Catalog;
+use crate::common::Result;
+use crate::sql::parser::{Keyword, Lexer, Token};
+use crate::sql::planner::Plan;
+use crate::types::field::Field;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+/// A cache of the plans of queries and writes, keyed by their normalized text
+/// (see [`normalize`]), so that running a statement again skips parsing and
+/// planning it. It's shared by the engine's sessions, and evicts the least
+/// recently used plan once it holds `capacity` of them.
+pub struct PlanCache(Mutex<Plans>);
+
+struct Plans {
+    capacity: usize,
+    entries: HashMap<String, (CachedPlan, u64)>,
+    /// Ticks on every lookup, to stamp the entries with when they were used.
+    clock: u64,
+}
+
+/// A cached plan, along with the schema versions of the tables it references.
+/// It's stale once one of them changes.
+#[derive(Clone)]
+pub(crate) struct CachedPlan {
+    /// The optimized plan or, if parameterized, the unoptimized plan to bind
+    /// the statement's literals to.
+    pub(crate) plan: Plan,
+    pub(crate) parameterized: bool,
+    pub(crate) versions: Vec<(String, u64)>,
+}
+
+impl CachedPlan {
+    /// Returns whether the tables the plan references still have the schemas
+    /// it was planned for.
+    pub(crate) fn is_current(&self, catalog: &impl Catalog) -> Result<bool> {
+        for (table, version) in &self.versions {
+            if catalog.schema_version(table)? != *version {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl PlanCache {
+    /// Creates a cache holding up to `capacity` plans. With none, nothing is
+    /// cached.
+    pub fn new(capacity: usize) -> Self {
+        Self(Mutex::new(Plans {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }))
+    }
+
+    pub fn capacity(&self) -> Result<usize> {
+        Ok(self.0.lock()?.capacity)
+    }
+
+    /// Sets the number of plans the cache holds, evicting the least recently
+    /// used ones over it.
+    pub fn set_capacity(&self, capacity: usize) -> Result<()> {
+        let mut plans = self.0.lock()?;
+        plans.capacity = capacity;
+        plans.evict();
+        Ok(())
+    }
+
+    /// Returns the number of plans cached.
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.0.lock()?.entries.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Looks up the plan cached under a key, marking it as recently used.
+    pub(crate) fn get(&self, key: &str) -> Result<Option<CachedPlan>> {
+        let mut plans = self.0.lock()?;
+        plans.clock += 1;
+        let clock = plans.clock;
+        Ok(plans.entries.get_mut(key).map(|(plan, used)| {
+            *used = clock;
+            plan.clone()
+        }))
+    }
+
+    /// Caches a plan under a key, evicting the least recently used plan if
+    /// the cache is full.
+    pub(crate) fn insert(&self, key: String, plan: CachedPlan) -> Result<()> {
+        let mut plans = self.0.lock()?;
+        if plans.capacity == 0 {
+            return Ok(());
+        }
+        let clock = plans.clock;
+        plans.entries.insert(key, (plan, clock));
+        plans.evict();
+        Ok(())
+    }
+
+    pub(crate) fn remove(&self, key: &str) -> Result<()> {
+        self.0.lock()?.entries.remove(key);
+        Ok(())
+    }
+}
+
+impl Plans {
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some(key) = (self.entries.iter())
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone())
+            else {
+                return;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+/// The versions of the table schemas, see [`Catalog::schema_version`]. Every
+/// CREATE or DROP TABLE ticks the catalog version, and stamps the table with
+/// it. Tables aren't created or dropped transactionally, so a version changes
+/// as soon as the statement runs.
+#[derive(Default)]
+pub(crate) struct SchemaVersions(Mutex<(u64, HashMap<String, u64>)>);
+
+impl SchemaVersions {
+    /// Stamps the table with a new version. Called once the table has been
+    /// created or dropped, so that a plan made before then is found stale.
+    pub(crate) fn bump(&self, table: &str) -> Result<()> {
+        let mut versions = self.0.lock()?;
+        versions.0 += 1;
+        let version = versions.0;
+        versions.1.insert(table.to_string(), version);
+        Ok(())
+    }
+
+    pub(crate) fn catalog(&self) -> Result<u64> {
+        Ok(self.0.lock()?.0)
+    }
+
+    pub(crate) fn table(&self, table: &str) -> Result<u64> {
+        Ok(self.0.lock()?.1.get(table).copied().unwrap_or_default())
+    }
+}
+
+/// A statement's cache key, and the literals taken out of it, if any.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CacheKey {
+    /// The statement's tokens, separated by single spaces, with keywords in
+    /// uppercase and identifiers quoted. Parses as the statement, with ?
+    /// parameters in place of the literals taken out.
+    pub(crate) text: String,
+    /// The literals taken out, by parameter index.
+    pub(crate) values: Vec<Field>,
+}
+
+/// Normalizes a statement into its cache key, so that statements differing in
+/// whitespace or keyword case share a plan. With `parameterize`, number and
+/// string literals are taken out too, so that statements differing only in
+/// those share a plan as well. Those of LIMIT and OFFSET are kept, since
+/// they're planned as constants.
+///
+/// Returns None if the statement doesn't lex, or has ? parameters of its own.
+pub(crate) fn normalize(statement: &str, parameterize: bool) -> Option<CacheKey> {
+    let mut key = CacheKey {
+        text: String::new(),
+        values: Vec::new(),
+    };
+    let mut previous = None;
+    for token in Lexer::new(statement) {
+        let token = token.ok()?;
+        let constant = matches!(
+            previous,
+            Some(Token::Keyword(Keyword::Limit | Keyword::Offset))
+        );
+        if !key.text.is_empty() {
+            key.text.push(' ');
+        }
+        match &token {
+            Token::Question => return None,
+            Token::Number(n) if parameterize && !constant => {
+                key.values.push(match n.chars().all(|c| c.is_ascii_digit()) {
+                    true => Field::Integer(n.parse().ok()?),
+                    false => Field::Float(n.parse().ok()?),
+                });
+                key.text.push('?');
+            }
+            Token::String(s) if parameterize && !constant => {
+                key.values.push(Field::String(s.clone()));
+                key.text.push('?');
+            }
+            Token::String(s) => write!(key.text, "'{}'", s.replace('\'', "''")).unwrap(),
+            Token::Ident(ident) => write!(key.text, "\"{}\"", ident.replace('"', "\"\"")).unwrap(),
+            token => write!(key.text, "{token}").unwrap(),
+        }
+        previous = Some(token);
+    }
+    if key.text.ends_with(" ;") {
+        key.text.truncate(key.text.len() - 2);
+    }
+    Some(key)
+}
@@ -1,38 +1,372 @@
-use super::Engine;
+use super::plan_cache::{normalize, CacheKey, CachedPlan};
+use super::{Catalog as _, Engine, Transaction};
 use crate::common::{Error, Result};
+use crate::concurrency::IsolationLevel;
+use crate::errinput;
 use crate::sql::execution::ExecutionResult;
-use crate::sql::parser::Parser;
+use crate::sql::parser::{ast, split_statements, Parser};
 use crate::sql::planner::Plan;
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Row;
-use crate::types::field::Label;
+use crate::storage::wal::Lsn;
+use crate::storage::VacuumStats;
+use crate::types::field::{Field, Label};
 use serde::{Deserialize, Serialize};
 
 /// A SQL session, which executes raw SQL statements against a query engine.
 pub struct Session<'a, E: Engine<'a>> {
-    txn: E::Transaction,
+    engine: &'a E,
+    /// The transaction opened by BEGIN, if any.
+    txn: Option<E::Transaction>,
+    /// The isolation level chosen by SET TRANSACTION for the next transaction.
+    next_isolation: Option<IsolationLevel>,
+    /// The number of threads table scans are split between, chosen by SET
+    /// parallel_scan_workers.
+    parallel_scan_workers: usize,
+    /// Whether statements differing only in their literals share a cached
+    /// plan, chosen by SET plan_cache_parameterize.
+    parameterize_plans: bool,
 }
 
 impl<'a, E: Engine<'a>> Session<'a, E> {
     /// Creates a new session with the given query engine.
     pub fn new(engine: &'a E) -> Self {
         Self {
-            txn: engine.begin().expect("Could not begin new transaction."),
+            engine,
+            txn: None,
+            next_isolation: None,
+            parallel_scan_workers: 1,
+            parameterize_plans: false,
         }
     }
 
-    /// Executes a raw SQL statement.
+    /// Returns the isolation level of the session's open transaction or, if
+    /// there is none, the level the next transaction will run at.
+    pub fn current_isolation(&self) -> IsolationLevel {
+        match &self.txn {
+            Some(txn) => txn.isolation(),
+            None => self.next_isolation.unwrap_or_default(),
+        }
+    }
+
+    /// Returns true if the session has a transaction open, begun by BEGIN and
+    /// not yet ended by COMMIT or ROLLBACK.
+    pub fn in_transaction(&self) -> bool {
+        self.txn.is_some()
+    }
+
+    /// Runs a closure in the session's open transaction or, outside of a
+    /// BEGIN/COMMIT block, in a read-only transaction of its own. Lets callers
+    /// read the catalog as the session's statements see it.
+    pub fn with_txn<T>(&mut self, f: impl FnOnce(&E::Transaction) -> Result<T>) -> Result<T> {
+        if let Some(txn) = &self.txn {
+            return f(txn);
+        }
+        let txn = self.engine.begin_read_only()?;
+        let result = f(&txn);
+        txn.commit()?;
+        result
+    }
+
+    /// Executes a raw SQL statement. Outside of a BEGIN/COMMIT block, the
+    /// statement runs in its own transaction, which is committed once the
+    /// statement's results have been collected, or rolled back if it fails.
     pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
-        Plan::build(Parser::new(statement).parse()?, &self.txn)?
-            .optimize()?
-            .execute(&self.txn)?
-            .try_into()
+        let result = self.execute_statement(statement);
+        let metrics = self.engine.metrics();
+        match &result {
+            Ok(StatementResult::Select { .. }) => metrics.statement_selects.incr(),
+            Ok(StatementResult::Insert { .. }) => metrics.statement_inserts.incr(),
+            Ok(StatementResult::Update { .. }) => metrics.statement_updates.incr(),
+            Ok(StatementResult::Delete { .. }) => metrics.statement_deletes.incr(),
+            Ok(_) => {}
+            Err(_) => metrics.statement_errors.incr(),
+        }
+        result
+    }
+
+    /// Executes a statement for [`Self::execute`], which counts it. Queries
+    /// and writes whose plan is cached skip parsing and planning.
+    fn execute_statement(&mut self, statement: &str) -> Result<StatementResult> {
+        let engine = self.engine;
+        let key = match engine.plan_cache().capacity()? {
+            0 => None,
+            _ => normalize(statement, self.parameterize_plans),
+        };
+        if let Some(key) = &key {
+            if let Some(cached) = engine.plan_cache().get(&key.text)? {
+                return self.in_statement_txn(|txn| {
+                    Self::execute_cached(engine, statement, key, cached, txn)
+                });
+            }
+        }
+        self.execute_parsed(Parser::new(statement).parse()?, key.as_ref())
+    }
+
+    /// Executes a parsed statement, caching its plan under the key, if any.
+    fn execute_parsed(
+        &mut self,
+        statement: ast::Statement,
+        key: Option<&CacheKey>,
+    ) -> Result<StatementResult> {
+        match statement {
+            ast::Statement::Begin {
+                read_only,
+                as_of,
+                isolation,
+            } => {
+                if self.txn.is_some() {
+                    return errinput!("already in a transaction");
+                }
+                if as_of.is_some() {
+                    return errinput!("AS OF SYSTEM TIME is not supported");
+                }
+                let isolation = isolation.or(self.next_isolation.take()).unwrap_or_default();
+                self.txn = Some(match read_only {
+                    true => self.engine.begin_read_only_with(isolation)?,
+                    false => self.engine.begin_with(isolation)?,
+                });
+                Ok(StatementResult::Begin {
+                    isolation,
+                    read_only,
+                })
+            }
+            ast::Statement::Commit => {
+                let Some(txn) = self.txn.take() else {
+                    return errinput!("not in a transaction");
+                };
+                txn.commit()?;
+                Ok(StatementResult::Commit)
+            }
+            ast::Statement::Rollback => {
+                let Some(txn) = self.txn.take() else {
+                    return errinput!("not in a transaction");
+                };
+                txn.rollback()?;
+                Ok(StatementResult::Rollback)
+            }
+            ast::Statement::SetTransaction { isolation } => {
+                if self.txn.is_some() {
+                    return errinput!("can't change the isolation level of an open transaction");
+                }
+                self.next_isolation = Some(isolation);
+                Ok(StatementResult::SetTransaction { isolation })
+            }
+            ast::Statement::Set { name, value } => {
+                let value = match (name.as_str(), value) {
+                    ("parallel_scan_workers", ast::Literal::Integer(workers @ 1..)) => {
+                        self.parallel_scan_workers = workers as usize;
+                        Field::Integer(workers)
+                    }
+                    ("parallel_scan_workers", value) => {
+                        return errinput!(
+                            "parallel_scan_workers must be a positive integer, got {value:?}"
+                        )
+                    }
+                    ("plan_cache_size", ast::Literal::Integer(size @ 0..)) => {
+                        self.engine.plan_cache().set_capacity(size as usize)?;
+                        Field::Integer(size)
+                    }
+                    ("plan_cache_size", value) => {
+                        return errinput!(
+                            "plan_cache_size must be a non-negative integer, got {value:?}"
+                        )
+                    }
+                    ("plan_cache_parameterize", ast::Literal::Boolean(parameterize)) => {
+                        self.parameterize_plans = parameterize;
+                        Field::Boolean(parameterize)
+                    }
+                    ("plan_cache_parameterize", value) => {
+                        return errinput!(
+                            "plan_cache_parameterize must be a boolean, got {value:?}"
+                        )
+                    }
+                    (name, _) => return errinput!("unknown setting {name}"),
+                };
+                Ok(StatementResult::Set { name, value })
+            }
+            ast::Statement::Checkpoint => {
+                let lsn = self.engine.checkpoint()?;
+                Ok(StatementResult::Checkpoint { lsn })
+            }
+            ast::Statement::Vacuum { table, full } => {
+                // The transaction's own snapshot would hold back the horizon.
+                if self.txn.is_some() {
+                    return errinput!("VACUUM can't run inside a transaction");
+                }
+                let stats = match full {
+                    true => self.engine.vacuum_full(table.as_deref())?,
+                    false => self.engine.vacuum(table.as_deref())?,
+                };
+                Ok(StatementResult::Vacuum(stats))
+            }
+            statement => {
+                let engine = self.engine;
+                self.in_statement_txn(|txn| Self::execute_in(engine, statement, key, txn))
+            }
+        }
+    }
+
+    /// Runs a statement in the session's open transaction or, outside of a
+    /// BEGIN/COMMIT block, in a transaction of its own, which is committed if
+    /// the statement succeeds and rolled back otherwise.
+    fn in_statement_txn(
+        &mut self,
+        f: impl FnOnce(&E::Transaction) -> Result<StatementResult>,
+    ) -> Result<StatementResult> {
+        match &self.txn {
+            Some(txn) => {
+                txn.start_statement()?;
+                txn.set_parallel_scan_workers(self.parallel_scan_workers);
+                f(txn)
+            }
+            None => {
+                let isolation = self.next_isolation.take().unwrap_or_default();
+                let txn = self.engine.begin_with(isolation)?;
+                txn.set_parallel_scan_workers(self.parallel_scan_workers);
+                match f(&txn) {
+                    Ok(result) => {
+                        txn.commit()?;
+                        Ok(result)
+                    }
+                    Err(error) => {
+                        txn.rollback()?;
+                        Err(error)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes a script of statements separated by semicolons, e.g. one
+    /// written by [`super::dump`], returning their results. Stops at the first
+    /// statement that fails, returning its error.
+    pub fn execute_script(&mut self, script: &str) -> Result<Vec<StatementResult>> {
+        let (mut statements, rest) = split_statements(script);
+        if !rest.trim().is_empty() {
+            statements.push(rest);
+        }
+        statements
+            .into_iter()
+            .map(|statement| self.execute(statement))
+            .collect()
+    }
+
+    /// Plans and executes a statement in the given transaction, caching the
+    /// plan of a query or write under the key, if any. EXPLAIN only plans the
+    /// statement, and returns the optimized plan.
+    fn execute_in(
+        engine: &'a E,
+        statement: ast::Statement,
+        key: Option<&CacheKey>,
+        txn: &E::Transaction,
+    ) -> Result<StatementResult> {
+        if let ast::Statement::Explain(statement) = statement {
+            let plan = Plan::build(*statement, txn)?.optimize()?;
+            return Ok(StatementResult::Explain(plan));
+        }
+        let plan = match key {
+            Some(key) => Self::plan_cached(engine, statement, key, txn)?,
+            None => Plan::build(statement, txn)?.optimize()?,
+        };
+        plan.execute(txn)?.try_into()
+    }
+
+    /// Plans a statement, and caches the plan under its key unless it's a
+    /// CREATE or DROP TABLE. With literals taken out of the key, the cached
+    /// plan is the one of the key itself, with parameters in their place. If
+    /// that can't be planned, e.g. since a literal is part of a GROUP BY
+    /// expression, nothing is cached.
+    fn plan_cached(
+        engine: &'a E,
+        statement: ast::Statement,
+        key: &CacheKey,
+        txn: &E::Transaction,
+    ) -> Result<Plan> {
+        // Versions of tables created or dropped while planning may not match
+        // the schemas the plan was made for.
+        let catalog_version = txn.catalog_version()?;
+        let parameterized = !key.values.is_empty();
+        let (plan, cached) = match parameterized {
+            true => match Parser::new(&key.text).parse().and_then(|s| Plan::build(s, txn)) {
+                Ok(template) => (template.clone().bind(&key.values)?.optimize()?, template),
+                Err(_) => return Plan::build(statement, txn)?.optimize(),
+            },
+            false => {
+                let plan = Plan::build(statement, txn)?.optimize()?;
+                (plan.clone(), plan)
+            }
+        };
+        if matches!(plan, Plan::CreateTable { .. } | Plan::DropTable { .. }) {
+            return Ok(plan);
+        }
+        engine.metrics().plan_cache_misses.incr();
+        let versions = (cached.tables().into_iter())
+            .map(|table| Ok((table.clone(), txn.schema_version(&table)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if versions.iter().all(|(_, version)| *version <= catalog_version) {
+            let cached = CachedPlan {
+                plan: cached,
+                parameterized,
+                versions,
+            };
+            engine.plan_cache().insert(key.text.clone(), cached)?;
+        }
+        Ok(plan)
+    }
+
+    /// Executes a cached plan, binding the key's literals to its parameters,
+    /// if any. If a table it references has changed schema since, the plan is
+    /// dropped from the cache and the statement planned again.
+    fn execute_cached(
+        engine: &'a E,
+        statement: &str,
+        key: &CacheKey,
+        cached: CachedPlan,
+        txn: &E::Transaction,
+    ) -> Result<StatementResult> {
+        if !cached.is_current(txn)? {
+            engine.plan_cache().remove(&key.text)?;
+            return Self::execute_in(engine, Parser::new(statement).parse()?, Some(key), txn);
+        }
+        engine.metrics().plan_cache_hits.incr();
+        let plan = match cached.parameterized {
+            true => cached.plan.bind(&key.values)?.optimize()?,
+            false => cached.plan,
+        };
+        plan.execute(txn)?.try_into()
+    }
+}
+
+impl<'a, E: Engine<'a>> Drop for Session<'a, E> {
+    /// Rolls back the open transaction, if any, releasing its locks.
+    fn drop(&mut self) {
+        if let Some(txn) = self.txn.take() {
+            txn.rollback().ok();
+        }
     }
 }
 
 /// A session statement result. Sent across the wire to SQL clients.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum StatementResult {
+    Begin {
+        isolation: IsolationLevel,
+        read_only: bool,
+    },
+    Commit,
+    Rollback,
+    SetTransaction {
+        isolation: IsolationLevel,
+    },
+    Set {
+        name: String,
+        value: Field,
+    },
+    Checkpoint {
+        lsn: Lsn,
+    },
+    Vacuum(VacuumStats),
     Explain(Plan),
     CreateTable {
         name: String,
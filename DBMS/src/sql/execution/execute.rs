@@ -1,5 +1,6 @@
 use crate::common::Result;
 use crate::errinput;
+use crate::trace_span;
 use crate::sql::engine::{Catalog, Transaction};
 use crate::sql::execution::{aggregate, join, source, transform, write};
 use crate::sql::execution::source::scan;
@@ -27,6 +28,7 @@ pub fn execute_plan(
     catalog: &impl Catalog,
     txn: &impl Transaction,
 ) -> Result<ExecutionResult> {
+    trace_span!("execute_plan");
     Ok(match plan {
         // Creates a table with the given schema, returning a `CreateTable` execution
         // result if the table creation is successful.
@@ -102,11 +104,13 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             group_by,
             aggregates,
         } => {
+            trace_span!("execute", node = "Aggregate");
             let source = execute(source, txn)?;
             aggregate::aggregate(source, group_by, aggregates)?
         }
 
         Node::Filter { source, predicate } => {
+            trace_span!("execute", node = "Filter");
             let source = execute(source, txn)?;
             filter(source, predicate)
         }
@@ -118,6 +122,7 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             right_column,
             outer,
         } => {
+            trace_span!("execute", node = "HashJoin", left_column, right_column);
             let right_size = right.columns();
             let left = execute(left, txn)?;
             let right = execute(right, txn)?;
@@ -130,6 +135,13 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             values: _values,
             alias: _,
         } => {
+            trace_span!(
+                "execute",
+                node = "IndexLookup",
+                table = _table.name(),
+                column = _column,
+                values = ?_values
+            );
             let columns = _table.columns();
             return if _column >= columns.len() {
                 Err(errinput!("Invalid column index"))
@@ -139,15 +151,29 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             };
         }
 
+        Node::IndexRangeScan {
+            table,
+            column,
+            start,
+            end,
+            direction,
+            alias: _,
+        } => {
+            trace_span!("execute", node = "IndexRangeScan", table = table.name(), column);
+            source::index_range_scan(txn, table, column, start, end, direction)?
+        }
+
         Node::KeyLookup {
             table: _table,
             keys: _keys,
             alias: _,
         } => {
+            trace_span!("execute", node = "KeyLookup", table = _table.name(), keys = ?_keys);
             todo!();
         }
 
         Node::Limit { source, limit } => {
+            trace_span!("execute", node = "Limit", limit);
             let source = execute(source, txn)?;
             transform::limit(source, limit)
 
@@ -159,18 +185,23 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             predicate,
             outer,
         } => {
+            trace_span!("execute", node = "NestedLoopJoin");
             let right_size = right.columns();
             let left = execute(left, txn)?;
             let right = execute(right, txn)?;
             join::nested_loop(left, right, right_size, predicate, outer)?
         }
 
-        Node::Nothing { .. } => source::nothing(),
+        Node::Nothing { .. } => {
+            trace_span!("execute", node = "Nothing");
+            source::nothing()
+        }
 
         Node::Offset {
             source: _source,
             offset: _offset,
         } => {
+            trace_span!("execute", node = "Offset", offset = _offset);
             let source = execute(_source, txn)?;
             offset(source, _offset)
         }
@@ -179,6 +210,7 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             source,
             key: orders,
         } => {
+            trace_span!("execute", node = "Order");
             let source = execute(source, txn)?;
             transform::order(source, orders)?
         }
@@ -188,11 +220,13 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             expressions,
             aliases: _,
         } => {
+            trace_span!("execute", node = "Projection");
             let source = execute(source, txn)?;
             project(source, expressions)
         }
 
         Node::Remap { source, targets } => {
+            trace_span!("execute", node = "Remap");
             let source = execute(source, txn)?;
             transform::remap(source, targets)
         }
@@ -200,12 +234,22 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
         Node::Scan {
             table,
             filter,
+            direction,
             alias: _,
         } => {
-            scan(txn, table, filter)?
+            trace_span!("execute", node = "Scan", table = table.name());
+            scan(txn, table, filter, direction)?
+        }
+
+        Node::TableCount { table, alias: _ } => {
+            trace_span!("execute", node = "TableCount", table = table.name());
+            source::table_count(txn, table)?
         }
 
-        Node::Values { rows } => source::values(rows),
+        Node::Values { rows } => {
+            trace_span!("execute", node = "Values", rows = rows.len());
+            source::values(rows)
+        }
     })
 }
 
@@ -1,14 +1,52 @@
 use crate::common::Result;
+use crate::errinput;
 use crate::sql::engine::Transaction;
-use crate::sql::planner::Expression;
+use crate::sql::planner::{Direction, Expression};
 use crate::storage::page::INVALID_RID;
 use crate::storage::tuple::{Row, Rows};
 use crate::types::field::Field;
 use crate::types::Table;
+use std::ops::Bound;
 
 /// A table source via sequential scan
-pub fn scan(txn: &impl Transaction, table: Table, filter: Option<Expression>) -> Result<Rows> {
-    txn.scan(table.name(), filter)
+pub fn scan(
+    txn: &impl Transaction,
+    table: Table,
+    filter: Option<Expression>,
+    direction: Option<Direction>,
+) -> Result<Rows> {
+    txn.scan(table.name(), filter, direction)
+}
+
+/// A table source via an index range scan. The bounds are evaluated once,
+/// before the scan starts.
+pub fn index_range_scan(
+    txn: &impl Transaction,
+    table: Table,
+    column: usize,
+    start: Bound<Expression>,
+    end: Bound<Expression>,
+    direction: Direction,
+) -> Result<Rows> {
+    let evaluate = |bound: Bound<Expression>| -> Result<Bound<Field>> {
+        Ok(match bound {
+            Bound::Included(expr) => Bound::Included(expr.evaluate(None)?),
+            Bound::Excluded(expr) => Bound::Excluded(expr.evaluate(None)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    };
+    let range = (evaluate(start)?, evaluate(end)?);
+    txn.index_scan(table.name(), column, range, direction)
+}
+
+/// Emits the number of rows in the table, without reading them.
+pub fn table_count(txn: &impl Transaction, table: Table) -> Result<Rows> {
+    let count = txn.count(table.name())?;
+    let Ok(count) = i32::try_from(count) else {
+        return errinput!("table {} has too many rows to count: {count}", table.name());
+    };
+    let row = Row::from(vec![Field::Integer(count)]);
+    Ok(Box::new(std::iter::once(Ok((INVALID_RID, row)))))
 }
 
 /// Returns nothing. Used to short-circuit nodes that can't produce any rows.
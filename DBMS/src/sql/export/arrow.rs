@@ -0,0 +1,198 @@
+//! Converts query results to Apache Arrow record batches, for analytics tools that read
+//! columnar data. Requires the `arrow` feature.
+use crate::common::Result;
+use crate::errdata;
+use crate::errinput;
+use crate::storage::tuple::Rows;
+use crate::types::field::{Field, Label};
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int32Array, NullArray, StringArray};
+use arrow::datatypes::{DataType, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+/// Converts the rows of a SELECT result into record batches of up to `batch_size` rows each,
+/// reading rows from the result only as batches are taken.
+///
+/// Integers become Int32 columns, floats Float64, strings Utf8 and booleans Boolean, and NULLs
+/// are nulls in any of them. The result doesn't record its column types, so each column takes
+/// the type of its first non-NULL value in the first batch, or Null if there is none. A value
+/// of another type, in any batch, is an error; so is a value in a column that was all NULL in
+/// the first batch. A result without rows returns no batches.
+pub fn record_batches(columns: Vec<Label>, rows: Rows, batch_size: usize) -> Result<RecordBatches> {
+    if batch_size == 0 {
+        return errinput!("the batch size must be positive");
+    }
+    Ok(RecordBatches {
+        columns,
+        rows,
+        batch_size,
+        schema: None,
+        row_count: 0,
+        done: false,
+    })
+}
+
+/// An iterator over the record batches of a query result. Stops after the first error.
+pub struct RecordBatches {
+    columns: Vec<Label>,
+    rows: Rows,
+    batch_size: usize,
+    /// The schema inferred from the first batch, which later ones must match.
+    schema: Option<SchemaRef>,
+    /// The number of rows converted so far, to number rows in errors.
+    row_count: usize,
+    done: bool,
+}
+
+impl RecordBatches {
+    /// Returns the schema of the batches, once the first one has been taken.
+    pub fn schema(&self) -> Option<SchemaRef> {
+        self.schema.clone()
+    }
+
+    /// Reads and converts the next batch of rows, if any.
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let mut rows = Vec::with_capacity(self.batch_size);
+        for row in self.rows.by_ref().take(self.batch_size) {
+            let (_, row) = row?;
+            if row.size() != self.columns.len() {
+                return errdata!(
+                    "row {} has {} values for {} columns",
+                    self.row_count + rows.len() + 1,
+                    row.size(),
+                    self.columns.len()
+                );
+            }
+            rows.push(row.into_iter().collect::<Vec<_>>());
+        }
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        let schema = match &self.schema {
+            Some(schema) => schema.clone(),
+            None => {
+                let fields = self.columns.iter().enumerate().map(|(i, label)| {
+                    let data_type = rows
+                        .iter()
+                        .find_map(|row| data_type(&row[i]))
+                        .unwrap_or(DataType::Null);
+                    arrow::datatypes::Field::new(label.as_header(), data_type, true)
+                });
+                let schema = Arc::new(Schema::new(fields.collect::<Vec<_>>()));
+                self.schema = Some(schema.clone());
+                schema
+            }
+        };
+        let arrays = schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| self.column(&rows, i, field))
+            .collect::<Result<Vec<_>>>()?;
+        self.row_count += rows.len();
+        Ok(Some(RecordBatch::try_new(schema, arrays)?))
+    }
+
+    /// Builds the array of a column of the batch.
+    fn column(
+        &self,
+        rows: &[Vec<Field>],
+        i: usize,
+        field: &arrow::datatypes::Field,
+    ) -> Result<ArrayRef> {
+        Ok(match field.data_type() {
+            DataType::Boolean => Arc::new(BooleanArray::from(self.values(
+                rows,
+                i,
+                field,
+                |value| match value {
+                    Field::Boolean(boolean) => Some(*boolean),
+                    _ => None,
+                },
+            )?)),
+            DataType::Int32 => Arc::new(Int32Array::from(self.values(
+                rows,
+                i,
+                field,
+                |value| match value {
+                    Field::Integer(integer) => Some(*integer),
+                    _ => None,
+                },
+            )?)),
+            DataType::Float64 => Arc::new(Float64Array::from(self.values(
+                rows,
+                i,
+                field,
+                |value| match value {
+                    Field::Float(float) => Some(f64::from(*float)),
+                    _ => None,
+                },
+            )?)),
+            DataType::Utf8 => Arc::new(StringArray::from(self.values(
+                rows,
+                i,
+                field,
+                |value| match value {
+                    Field::String(string) => Some(string.clone()),
+                    _ => None,
+                },
+            )?)),
+            DataType::Null => {
+                self.values(rows, i, field, |_| None::<()>)?;
+                Arc::new(NullArray::new(rows.len()))
+            }
+            data_type => return errdata!("unexpected column type {data_type}"),
+        })
+    }
+
+    /// Returns the values of a column of the batch, with None for NULLs, or an error if one
+    /// isn't of the column's type.
+    fn values<T>(
+        &self,
+        rows: &[Vec<Field>],
+        i: usize,
+        field: &arrow::datatypes::Field,
+        extract: impl Fn(&Field) -> Option<T>,
+    ) -> Result<Vec<Option<T>>> {
+        let mut values = Vec::with_capacity(rows.len());
+        for (n, row) in rows.iter().enumerate() {
+            match (&row[i], extract(&row[i])) {
+                (Field::Null, _) => values.push(None),
+                (_, Some(value)) => values.push(Some(value)),
+                (value, None) => {
+                    return errdata!(
+                        "column {}: row {} has {value}, but the column is {}",
+                        field.name(),
+                        self.row_count + n + 1,
+                        field.data_type()
+                    )
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl Iterator for RecordBatches {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch>> {
+        if self.done {
+            return None;
+        }
+        let batch = self.next_batch().transpose();
+        self.done = !matches!(batch, Some(Ok(_)));
+        batch
+    }
+}
+
+/// Returns the Arrow type of a value, or None for NULL.
+fn data_type(value: &Field) -> Option<DataType> {
+    match value {
+        Field::Null => None,
+        Field::Boolean(_) => Some(DataType::Boolean),
+        Field::Integer(_) => Some(DataType::Int32),
+        Field::Float(_) => Some(DataType::Float64),
+        Field::String(_) => Some(DataType::Utf8),
+    }
+}
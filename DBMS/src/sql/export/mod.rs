@@ -0,0 +1,3 @@
+//! Exports query results to formats other tools read.
+#[cfg(feature = "arrow")]
+pub mod arrow;
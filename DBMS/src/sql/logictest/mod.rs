@@ -0,0 +1,38 @@
+//! A runner for SQL logic tests: scripts of statements and queries with their expected results,
+//! in the style of sqllogictest. The scripts of `tests/slt` run with `cargo test`.
+//!
+//! A script is a sequence of records separated by blank lines. Lines starting with `#` are
+//! comments. Each record starts with one of:
+//!
+//! - `statement ok`: the SQL on the following lines must succeed.
+//! - `statement error [text]`: the SQL must fail, with an error containing `text` if given.
+//! - `query [nosort|rowsort|valuesort]`: the SQL must return the rows below a `----` line, one
+//!   per line, with values separated by spaces. With `nosort`, the default, rows must come in
+//!   order; `rowsort` sorts the rows of both the result and the expectation before comparing
+//!   them, and `valuesort` sorts their individual values. A query with no `----` returns no rows.
+//! - `query error [text]`: like `statement error`.
+//! - `skip reason`: skips the next record, e.g. one using a feature that isn't implemented yet.
+//! - `halt`: skips the rest of the script.
+//!
+//! Values are rendered as SQL literals, except strings, which aren't quoted; the empty string is
+//! `(empty)`, and NULL is `NULL`.
+//!
+//! ```text
+//! statement ok
+//! CREATE TABLE t (id INT PRIMARY KEY, name TEXT)
+//!
+//! statement error already exists
+//! CREATE TABLE t (id INT PRIMARY KEY)
+//!
+//! query rowsort
+//! SELECT id, name FROM t
+//! ----
+//! ```
+mod runner;
+mod script;
+
+pub use runner::{run_file, run_script, Failure, Report};
+pub use script::{parse, Expect, Record, SortMode};
+
+#[cfg(test)]
+mod tests;
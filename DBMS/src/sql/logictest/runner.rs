@@ -0,0 +1,191 @@
+use super::script::{normalize, parse, Expect, Record, SortMode};
+use crate::common::Result;
+use crate::config::config::{DEFAULT_POOL_SIZE, DEFAULT_REPLACER_K};
+use crate::sql::engine::{Local, Session, StatementResult};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::tuple::Row;
+use crate::storage::HeapTableManager;
+use crate::types::field::Field;
+use itertools::Itertools;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tempfile::TempDir;
+
+/// The results of running a script.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// The script's name, e.g. its path.
+    pub name: String,
+    /// The number of records that ran as expected.
+    pub passed: usize,
+    /// The lines of the skipped records, and why they were skipped.
+    pub skipped: Vec<(usize, String)>,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Returns true if every record that ran did as expected.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for Report {
+    /// Formats the failures, each starting with the script's name and the record's line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "{}:{}: {}", self.name, failure.line, failure.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A record that didn't run as expected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    /// The 1-based line of the record.
+    pub line: usize,
+    /// What went wrong, followed by the record's SQL and, for queries returning the wrong rows,
+    /// a `----` line and a diff of the rows.
+    pub message: String,
+}
+
+/// Runs the script at `path`, see [`run_script`].
+pub fn run_file(path: &Path) -> Result<Report> {
+    let script = std::fs::read_to_string(path)?;
+    run_script(&path.display().to_string(), &script)
+}
+
+/// Runs a script against a new, empty database in a temporary directory. Errors if the script
+/// doesn't parse, or the database can't be created; records that don't run as expected are
+/// failures of the report, and don't stop the script.
+pub fn run_script(name: &str, script: &str) -> Result<Report> {
+    let records = parse(name, script)?;
+    let dir = TempDir::new()?;
+    let disk_manager = DiskManager::open(&dir.path().join("logictest"))?;
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(DEFAULT_POOL_SIZE)
+        .replacer_k(DEFAULT_REPLACER_K)
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+
+    let mut report = Report {
+        name: name.to_string(),
+        ..Report::default()
+    };
+    for record in records {
+        if let Some(reason) = record.skip {
+            report.skipped.push((record.line, reason));
+            continue;
+        }
+        match run_record(&mut session, &record) {
+            Ok(()) => report.passed += 1,
+            Err((message, None)) => report.failures.push(Failure {
+                line: record.line,
+                message: format!("{message}\n{}", record.sql),
+            }),
+            Err((message, Some(diff))) => report.failures.push(Failure {
+                line: record.line,
+                message: format!("{message}\n{}\n----\n{diff}", record.sql),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Runs a record, returning what went wrong if it didn't run as expected, and for queries
+/// returning the wrong rows, a diff of the rows.
+fn run_record(
+    session: &mut Session<Local<HeapTableManager>>,
+    record: &Record,
+) -> std::result::Result<(), (String, Option<String>)> {
+    let result = session.execute(&record.sql);
+    match (&record.expect, result) {
+        (Expect::Error(None), Err(_)) => Ok(()),
+        (Expect::Error(Some(text)), Err(error)) if error.to_string().contains(text.as_str()) => {
+            Ok(())
+        }
+        (Expect::Error(Some(text)), Err(error)) => Err((
+            format!("expected an error containing {text:?}, got: {error}"),
+            None,
+        )),
+        (Expect::Error(_), Ok(_)) => Err((
+            "expected an error, but the statement succeeded".to_string(),
+            None,
+        )),
+        (_, Err(error)) => Err((format!("statement failed: {error}"), None)),
+        (Expect::Ok, Ok(_)) => Ok(()),
+        (Expect::Rows { sort, rows }, Ok(StatementResult::Select { rows: actual, .. })) => {
+            let expected = sorted(*sort, rows.clone());
+            let actual = sorted(*sort, actual.iter().map(render).collect());
+            match expected == actual {
+                true => Ok(()),
+                false => Err((
+                    "query results differ (- expected, + actual)".to_string(),
+                    Some(diff(&expected, &actual)),
+                )),
+            }
+        }
+        (Expect::Rows { .. }, Ok(result)) => Err((format!("expected rows, got {result:?}"), None)),
+    }
+}
+
+/// Renders a row's values, separated by spaces.
+fn render(row: &Row) -> String {
+    let mut values = row.iter().map(|field| match field {
+        Field::String(string) if string.is_empty() => "(empty)".to_string(),
+        Field::String(string) => string.clone(),
+        field => format!("{field}"),
+    });
+    normalize(&values.join(" "))
+}
+
+/// Orders rows for comparison. With [`SortMode::ValueSort`], each value becomes a row.
+fn sorted(sort: SortMode, rows: Vec<String>) -> Vec<String> {
+    match sort {
+        SortMode::NoSort => rows,
+        SortMode::RowSort => rows.into_iter().sorted().collect(),
+        SortMode::ValueSort => rows
+            .iter()
+            .flat_map(|row| row.split(' '))
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .sorted()
+            .collect(),
+    }
+}
+
+/// Diffs two lists of rows by their longest common subsequence, prefixing rows only in
+/// `expected` with `-`, rows only in `actual` with `+`, and rows in both with a space.
+pub(super) fn diff(expected: &[String], actual: &[String]) -> String {
+    // common[i][j] is the length of the longest common subsequence of expected[i..], actual[j..].
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = match expected[i] == actual[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,117 @@
+use crate::common::Result;
+use crate::errinput;
+
+/// A record of a logic test script, see the [module docs](super).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// The 1-based line of the record's first line.
+    pub line: usize,
+    /// The SQL to run, with its lines joined.
+    pub sql: String,
+    pub expect: Expect,
+    /// Why the record is skipped, if it is.
+    pub skip: Option<String>,
+}
+
+/// What running a record's SQL must result in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expect {
+    /// The statement succeeds.
+    Ok,
+    /// The statement fails, with an error containing the text, if any.
+    Error(Option<String>),
+    /// The query returns these rows, each a line of space-separated values.
+    Rows { sort: SortMode, rows: Vec<String> },
+}
+
+/// How a query's rows are ordered before comparing them with the expected ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortMode {
+    /// The rows must come in the expected order.
+    #[default]
+    NoSort,
+    /// The rows are sorted.
+    RowSort,
+    /// The values are sorted, regardless of the rows they're in.
+    ValueSort,
+}
+
+/// Parses a script into its records. `name` is the script's name for errors, e.g. its path.
+pub fn parse(name: &str, script: &str) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut lines = script
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .peekable();
+    let mut skip = None;
+    while let Some((line, header)) = lines.next() {
+        let header = header.trim();
+        if header.is_empty() || header.starts_with('#') {
+            continue;
+        }
+        let (keyword, args) = header.split_once(' ').unwrap_or((header, ""));
+        let args = args.trim();
+        let expect = match (keyword, args) {
+            ("halt", "") => break,
+            ("skip", "") => return errinput!("{name}:{line}: skip needs a reason"),
+            ("skip", reason) => {
+                skip = Some(reason.to_string());
+                continue;
+            }
+            ("statement", "ok") => Expect::Ok,
+            ("statement" | "query", "error") => Expect::Error(None),
+            ("statement" | "query", args) if args.starts_with("error ") => {
+                Expect::Error(Some(args["error ".len()..].trim().to_string()))
+            }
+            ("query", sort) => Expect::Rows {
+                sort: match sort {
+                    "" | "nosort" => SortMode::NoSort,
+                    "rowsort" => SortMode::RowSort,
+                    "valuesort" => SortMode::ValueSort,
+                    sort => return errinput!("{name}:{line}: unknown sort mode {sort}"),
+                },
+                rows: Vec::new(),
+            },
+            _ => return errinput!("{name}:{line}: unknown record {header}"),
+        };
+
+        let mut sql = Vec::new();
+        let mut has_results = false;
+        while let Some((_, text)) = lines.next_if(|(_, text)| !text.trim().is_empty()) {
+            if text.trim() == "----" {
+                has_results = true;
+                break;
+            }
+            sql.push(text);
+        }
+        if sql.is_empty() {
+            return errinput!("{name}:{line}: {header} has no SQL");
+        }
+        let expect = match expect {
+            Expect::Rows { sort, mut rows } => {
+                while let Some((_, text)) = lines.next_if(|(_, text)| !text.trim().is_empty()) {
+                    rows.push(normalize(text));
+                }
+                Expect::Rows { sort, rows }
+            }
+            _ if has_results => return errinput!("{name}:{line}: only queries have results"),
+            expect => expect,
+        };
+        records.push(Record {
+            line,
+            sql: sql.join("\n"),
+            expect,
+            skip: skip.take(),
+        });
+    }
+    if skip.is_some() {
+        return errinput!("{name}: skip at the end of the script");
+    }
+    Ok(records)
+}
+
+/// Collapses runs of whitespace in a row into single spaces.
+pub(super) fn normalize(row: &str) -> String {
+    row.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,170 @@
+use super::runner::diff;
+use super::*;
+
+#[test]
+fn test_parse() {
+    let script = "# A comment.
+statement ok
+CREATE TABLE t (id INT PRIMARY KEY,
+  value INT)
+
+skip UPDATE ... FROM isn't supported
+statement error
+UPDATE t SET value = 1 FROM t
+
+query error doesn't exist
+SELECT * FROM missing
+
+query rowsort
+SELECT id,   value FROM t
+----
+1   10
+2 20
+
+query
+SELECT * FROM t WHERE id = 3
+
+halt
+
+query
+SELECT 1
+";
+    let records = parse("test.slt", script).unwrap();
+    assert_eq!(
+        vec![
+            Record {
+                line: 2,
+                sql: "CREATE TABLE t (id INT PRIMARY KEY,\n  value INT)".to_string(),
+                expect: Expect::Ok,
+                skip: None,
+            },
+            Record {
+                line: 7,
+                sql: "UPDATE t SET value = 1 FROM t".to_string(),
+                expect: Expect::Error(None),
+                skip: Some("UPDATE ... FROM isn't supported".to_string()),
+            },
+            Record {
+                line: 10,
+                sql: "SELECT * FROM missing".to_string(),
+                expect: Expect::Error(Some("doesn't exist".to_string())),
+                skip: None,
+            },
+            Record {
+                line: 13,
+                sql: "SELECT id,   value FROM t".to_string(),
+                expect: Expect::Rows {
+                    sort: SortMode::RowSort,
+                    rows: vec!["1 10".to_string(), "2 20".to_string()],
+                },
+                skip: None,
+            },
+            Record {
+                line: 19,
+                sql: "SELECT * FROM t WHERE id = 3".to_string(),
+                expect: Expect::Rows {
+                    sort: SortMode::NoSort,
+                    rows: vec![],
+                },
+                skip: None,
+            },
+        ],
+        records
+    );
+
+    for (script, error) in [
+        (
+            "statemnt ok\nSELECT 1",
+            "test.slt:1: unknown record statemnt ok",
+        ),
+        (
+            "\nquery sorted\nSELECT 1",
+            "test.slt:2: unknown sort mode sorted",
+        ),
+        (
+            "statement ok\n\nSELECT 1",
+            "test.slt:1: statement ok has no SQL",
+        ),
+        (
+            "statement ok\nSELECT 1\n----\n1",
+            "test.slt:1: only queries have results",
+        ),
+        (
+            "skip\nstatement ok\nSELECT 1",
+            "test.slt:1: skip needs a reason",
+        ),
+        ("skip later", "test.slt: skip at the end of the script"),
+    ] {
+        assert_eq!(
+            crate::common::Error::InvalidInput(error.to_string()),
+            parse("test.slt", script).unwrap_err(),
+            "{script:?}"
+        );
+    }
+}
+
+/// Mismatches are failures of the report, with their line, SQL and a diff, and don't stop the
+/// script.
+#[test]
+fn test_run_script() {
+    let script = "statement ok
+CREATE TABLE t (id INT PRIMARY KEY, name TEXT)
+
+statement ok
+INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, '')
+
+query
+SELECT id, name FROM t ORDER BY id
+----
+1 a
+3 c
+3 (empty)
+
+statement error
+SELECT * FROM t
+
+statement error no such table
+SELECT * FROM missing
+
+skip not yet
+statement ok
+SELECT * FROM t
+
+query valuesort
+SELECT id, id * 10 FROM t
+----
+1 2 3 10 20 30
+";
+    let report = run_script("test.slt", script).unwrap();
+    assert_eq!(3, report.passed);
+    assert_eq!(vec![(21, "not yet".to_string())], report.skipped);
+    assert_eq!(
+        vec![7, 14, 17],
+        report.failures.iter().map(|f| f.line).collect::<Vec<_>>()
+    );
+    let output = report.to_string();
+    assert!(output.starts_with(
+        "test.slt:7: query results differ (- expected, + actual)
+SELECT id, name FROM t ORDER BY id
+----
+  1 a
+- 3 c
++ 2 b
+  3 (empty)
+test.slt:14: expected an error, but the statement succeeded
+SELECT * FROM t
+test.slt:17: expected an error containing \"no such table\", got: "
+    ));
+}
+
+#[test]
+fn test_diff() {
+    let rows = |rows: &[&str]| rows.iter().map(|row| row.to_string()).collect::<Vec<_>>();
+    assert_eq!("", diff(&[], &[]));
+    assert_eq!(
+        "  a\n- b\n  c\n+ d",
+        diff(&rows(&["a", "b", "c"]), &rows(&["a", "c", "d"]))
+    );
+    assert_eq!("- a\n- b", diff(&rows(&["a", "b"]), &[]));
+    assert_eq!("+ a", diff(&[], &rows(&["a"])));
+}
@@ -1,6 +1,8 @@
 //! All credit to Erik Grinaker: (https://github.com/erikgrinaker/toydb), covered under Apache license.
 pub mod engine;
 pub mod execution;
-mod parser;
+pub mod export;
+pub mod logictest;
+pub mod parser;
 pub mod planner;
 mod tests;
@@ -1,3 +1,4 @@
+use crate::concurrency::IsolationLevel;
 use crate::types::DataType;
 use std::collections::BTreeMap;
 
@@ -7,15 +8,33 @@ use std::collections::BTreeMap;
 #[derive(Debug)]
 pub enum Statement {
     /// Begin a new transaction.
-    Begin { read_only: bool, as_of: Option<u64> },
+    Begin {
+        read_only: bool,
+        as_of: Option<u64>,
+        isolation: Option<IsolationLevel>,
+    },
     /// Commit a transaction.
     Commit,
     /// Roll back a transaction.
     Rollback,
+    /// Set the isolation level of the next transaction.
+    SetTransaction { isolation: IsolationLevel },
+    /// Change a setting of the session.
+    Set { name: String, value: Literal },
+    /// Write modified pages back to disk and truncate the write-ahead log.
+    Checkpoint,
+    /// Remove tuple versions no transaction can see, from one table or all.
+    /// A full vacuum rewrites the tables to shrink them.
+    Vacuum { table: Option<String>, full: bool },
     /// Explain a statement.
     Explain(Box<Statement>),
     /// Create a new table.
-    CreateTable { name: String, columns: Vec<Column> },
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+        /// The table's fill factor, if given.
+        fill_factor: Option<Expression>,
+    },
     /// Drop a table.
     DropTable { name: String, if_exists: bool },
     /// Delete matching rows.
@@ -110,6 +129,9 @@ pub enum Expression {
     Column(Option<String>, String),
     /// A literal value.
     Literal(Literal),
+    /// A query parameter, i.e. ?, numbered from 0 in the order they appear.
+    /// Their values are bound to the planned statement.
+    Parameter(usize),
     /// A function call (name and parameters).
     Function(String, Vec<Expression>),
     /// An operator.
@@ -223,7 +245,7 @@ impl Expression {
 
             Self::Function(_, exprs) => exprs.iter().any(|expr| expr.walk(visitor)),
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => true,
+            Self::All | Self::Column(_, _) | Self::Literal(_) | Self::Parameter(_) => true,
         }
     }
 
@@ -269,7 +291,7 @@ impl Expression {
 
             Self::Function(_, exprs) => exprs.iter().for_each(|expr| expr.collect(visitor, c)),
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => {}
+            Self::All | Self::Column(_, _) | Self::Literal(_) | Self::Parameter(_) => {}
         }
     }
 }
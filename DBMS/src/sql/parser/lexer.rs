@@ -91,10 +91,13 @@ pub enum Keyword {
     As,
     Asc,
     Begin,
+    Between,
     Bool,
     Boolean,
     By,
+    Checkpoint,
     Commit,
+    Committed,
     Create,
     Cross,
     Default,
@@ -107,6 +110,7 @@ pub enum Keyword {
     False,
     Float,
     From,
+    Full,
     Group,
     Having,
     If,
@@ -118,9 +122,11 @@ pub enum Keyword {
     Integer,
     Into,
     Is,
+    Isolation,
     Join,
     Key,
     Left,
+    Level,
     Like,
     Limit,
     NaN,
@@ -136,10 +142,14 @@ pub enum Keyword {
     Primary,
     Read,
     References,
+    Repeatable,
     Right,
     Rollback,
     Select,
+    Serializable,
     Set,
+    Snapshot,
+    Start,
     String,
     System,
     Table,
@@ -147,11 +157,14 @@ pub enum Keyword {
     Time,
     Transaction,
     True,
+    Uncommitted,
     Unique,
     Update,
+    Vacuum,
     Values,
     Varchar,
     Where,
+    With,
     Write,
 }
 
@@ -171,10 +184,13 @@ impl TryFrom<&str> for Keyword {
             "asc" => Self::Asc,
             "and" => Self::And,
             "begin" => Self::Begin,
+            "between" => Self::Between,
             "bool" => Self::Bool,
             "boolean" => Self::Boolean,
             "by" => Self::By,
+            "checkpoint" => Self::Checkpoint,
             "commit" => Self::Commit,
+            "committed" => Self::Committed,
             "create" => Self::Create,
             "cross" => Self::Cross,
             "default" => Self::Default,
@@ -187,6 +203,7 @@ impl TryFrom<&str> for Keyword {
             "false" => Self::False,
             "float" => Self::Float,
             "from" => Self::From,
+            "full" => Self::Full,
             "group" => Self::Group,
             "having" => Self::Having,
             "if" => Self::If,
@@ -198,9 +215,11 @@ impl TryFrom<&str> for Keyword {
             "integer" => Self::Integer,
             "into" => Self::Into,
             "is" => Self::Is,
+            "isolation" => Self::Isolation,
             "join" => Self::Join,
             "key" => Self::Key,
             "left" => Self::Left,
+            "level" => Self::Level,
             "like" => Self::Like,
             "limit" => Self::Limit,
             "nan" => Self::NaN,
@@ -216,10 +235,14 @@ impl TryFrom<&str> for Keyword {
             "primary" => Self::Primary,
             "read" => Self::Read,
             "references" => Self::References,
+            "repeatable" => Self::Repeatable,
             "right" => Self::Right,
             "rollback" => Self::Rollback,
             "select" => Self::Select,
+            "serializable" => Self::Serializable,
             "set" => Self::Set,
+            "snapshot" => Self::Snapshot,
+            "start" => Self::Start,
             "string" => Self::String,
             "system" => Self::System,
             "table" => Self::Table,
@@ -227,11 +250,14 @@ impl TryFrom<&str> for Keyword {
             "time" => Self::Time,
             "transaction" => Self::Transaction,
             "true" => Self::True,
+            "uncommitted" => Self::Uncommitted,
             "unique" => Self::Unique,
             "update" => Self::Update,
+            "vacuum" => Self::Vacuum,
             "values" => Self::Values,
             "varchar" => Self::Varchar,
             "where" => Self::Where,
+            "with" => Self::With,
             "write" => Self::Write,
             _ => return Err("not a keyword"),
         })
@@ -246,10 +272,13 @@ impl std::fmt::Display for Keyword {
             Self::Asc => "ASC",
             Self::And => "AND",
             Self::Begin => "BEGIN",
+            Self::Between => "BETWEEN",
             Self::Bool => "BOOL",
             Self::Boolean => "BOOLEAN",
             Self::By => "BY",
+            Self::Checkpoint => "CHECKPOINT",
             Self::Commit => "COMMIT",
+            Self::Committed => "COMMITTED",
             Self::Create => "CREATE",
             Self::Cross => "CROSS",
             Self::Default => "DEFAULT",
@@ -262,6 +291,7 @@ impl std::fmt::Display for Keyword {
             Self::False => "FALSE",
             Self::Float => "FLOAT",
             Self::From => "FROM",
+            Self::Full => "FULL",
             Self::Group => "GROUP",
             Self::Having => "HAVING",
             Self::If => "IF",
@@ -273,9 +303,11 @@ impl std::fmt::Display for Keyword {
             Self::Integer => "INTEGER",
             Self::Into => "INTO",
             Self::Is => "IS",
+            Self::Isolation => "ISOLATION",
             Self::Join => "JOIN",
             Self::Key => "KEY",
             Self::Left => "LEFT",
+            Self::Level => "LEVEL",
             Self::Like => "LIKE",
             Self::Limit => "LIMIT",
             Self::NaN => "NAN",
@@ -291,10 +323,14 @@ impl std::fmt::Display for Keyword {
             Self::Primary => "PRIMARY",
             Self::Read => "READ",
             Self::References => "REFERENCES",
+            Self::Repeatable => "REPEATABLE",
             Self::Right => "RIGHT",
             Self::Rollback => "ROLLBACK",
             Self::Select => "SELECT",
+            Self::Serializable => "SERIALIZABLE",
             Self::Set => "SET",
+            Self::Snapshot => "SNAPSHOT",
+            Self::Start => "START",
             Self::String => "STRING",
             Self::System => "SYSTEM",
             Self::Table => "TABLE",
@@ -302,11 +338,14 @@ impl std::fmt::Display for Keyword {
             Self::Time => "TIME",
             Self::Transaction => "TRANSACTION",
             Self::True => "TRUE",
+            Self::Uncommitted => "UNCOMMITTED",
             Self::Unique => "UNIQUE",
             Self::Update => "UPDATE",
+            Self::Vacuum => "VACUUM",
             Self::Values => "VALUES",
             Self::Varchar => "VARCHAR",
             Self::Where => "WHERE",
+            Self::With => "WITH",
             Self::Write => "WRITE",
         })
     }
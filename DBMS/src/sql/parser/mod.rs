@@ -4,4 +4,4 @@ mod lexer;
 mod parser;
 
 pub use lexer::{Keyword, Lexer, Token};
-pub use parser::Parser;
+pub use parser::{split_statements, Parser};
@@ -2,6 +2,7 @@
 
 use super::{ast, Keyword, Lexer, Token};
 use crate::common::Result;
+use crate::concurrency::IsolationLevel;
 use crate::errinput;
 use crate::types::DataType;
 
@@ -13,6 +14,8 @@ use crate::types::DataType;
 /// or which kind of join to use -- that is the job of the planner.
 pub struct Parser<'a> {
     pub lexer: std::iter::Peekable<Lexer<'a>>,
+    /// The number of ? parameters parsed so far.
+    parameters: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -20,6 +23,7 @@ impl<'a> Parser<'a> {
     pub fn new(statement: &str) -> Parser {
         Parser {
             lexer: Lexer::new(statement).peekable(),
+            parameters: 0,
         }
     }
 
@@ -108,9 +112,12 @@ impl<'a> Parser<'a> {
             return errinput!("unexpected end of input");
         };
         match token {
-            Token::Keyword(Keyword::Begin) => self.parse_begin(),
+            Token::Keyword(Keyword::Begin | Keyword::Start) => self.parse_begin(),
             Token::Keyword(Keyword::Commit) => self.parse_commit(),
             Token::Keyword(Keyword::Rollback) => self.parse_rollback(),
+            Token::Keyword(Keyword::Set) => self.parse_set(),
+            Token::Keyword(Keyword::Checkpoint) => self.parse_checkpoint(),
+            Token::Keyword(Keyword::Vacuum) => self.parse_vacuum(),
             Token::Keyword(Keyword::Explain) => self.parse_explain(),
 
             Token::Keyword(Keyword::Create) => self.parse_create_table(),
@@ -125,18 +132,31 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parses a BEGIN statement.
+    /// Parses a BEGIN or START TRANSACTION statement. The isolation level and
+    /// access mode may be given in either order, optionally comma-separated.
     fn parse_begin(&mut self) -> Result<ast::Statement> {
-        self.expect(Keyword::Begin.into())?;
-        self.skip(Keyword::Transaction.into());
+        if self.next_is(Keyword::Start.into()) {
+            self.expect(Keyword::Transaction.into())?;
+        } else {
+            self.expect(Keyword::Begin.into())?;
+            self.skip(Keyword::Transaction.into());
+        }
 
+        let mut isolation = None;
         let mut read_only = false;
-        if self.next_is(Keyword::Read.into()) {
-            match self.next()? {
-                Token::Keyword(Keyword::Only) => read_only = true,
-                Token::Keyword(Keyword::Write) => {}
-                token => return errinput!("unexpected token {token}"),
+        loop {
+            if isolation.is_none() && self.next_is(Keyword::Isolation.into()) {
+                isolation = Some(self.parse_isolation_level()?);
+            } else if self.next_is(Keyword::Read.into()) {
+                match self.next()? {
+                    Token::Keyword(Keyword::Only) => read_only = true,
+                    Token::Keyword(Keyword::Write) => read_only = false,
+                    token => return errinput!("unexpected token {token}"),
+                }
+            } else {
+                break;
             }
+            self.skip(Token::Comma);
         }
 
         let mut as_of = None;
@@ -149,7 +169,48 @@ impl<'a> Parser<'a> {
                 token => return errinput!("unexpected token {token}, wanted number"),
             }
         }
-        Ok(ast::Statement::Begin { read_only, as_of })
+        Ok(ast::Statement::Begin {
+            read_only,
+            as_of,
+            isolation,
+        })
+    }
+
+    /// Parses a SET TRANSACTION ISOLATION LEVEL statement, or a SET statement
+    /// changing a session setting to a literal value.
+    fn parse_set(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Set.into())?;
+        if self.next_is(Keyword::Transaction.into()) {
+            self.expect(Keyword::Isolation.into())?;
+            let isolation = self.parse_isolation_level()?;
+            return Ok(ast::Statement::SetTransaction { isolation });
+        }
+        let name = self.next_ident()?;
+        self.expect(Token::Equal)?;
+        match self.parse_expression()? {
+            ast::Expression::Literal(value) => Ok(ast::Statement::Set { name, value }),
+            expr => errinput!("expected a literal value for {name}, got {expr:?}"),
+        }
+    }
+
+    /// Parses an isolation level following the ISOLATION keyword. REPEATABLE
+    /// READ is taken to mean snapshot isolation, which provides it.
+    fn parse_isolation_level(&mut self) -> Result<IsolationLevel> {
+        self.expect(Keyword::Level.into())?;
+        Ok(match self.next()? {
+            Token::Keyword(Keyword::Read) => match self.next()? {
+                Token::Keyword(Keyword::Uncommitted) => IsolationLevel::ReadUncommitted,
+                Token::Keyword(Keyword::Committed) => IsolationLevel::ReadCommitted,
+                token => return errinput!("unexpected token {token}"),
+            },
+            Token::Keyword(Keyword::Repeatable) => {
+                self.expect(Keyword::Read.into())?;
+                IsolationLevel::SnapshotIsolation
+            }
+            Token::Keyword(Keyword::Snapshot) => IsolationLevel::SnapshotIsolation,
+            Token::Keyword(Keyword::Serializable) => IsolationLevel::Serializable,
+            token => return errinput!("unexpected token {token}, wanted isolation level"),
+        })
     }
 
     /// Parses a COMMIT statement.
@@ -164,6 +225,23 @@ impl<'a> Parser<'a> {
         Ok(ast::Statement::Rollback)
     }
 
+    /// Parses a CHECKPOINT statement.
+    fn parse_checkpoint(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Checkpoint.into())?;
+        Ok(ast::Statement::Checkpoint)
+    }
+
+    /// Parses a VACUUM statement.
+    fn parse_vacuum(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Vacuum.into())?;
+        let full = self.next_is(Keyword::Full.into());
+        let table = self.next_if_map(|token| match token {
+            Token::Ident(ident) => Some(ident.clone()),
+            _ => None,
+        });
+        Ok(ast::Statement::Vacuum { table, full })
+    }
+
     /// Parses an EXPLAIN statement.
     fn parse_explain(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Explain.into())?;
@@ -187,7 +265,22 @@ impl<'a> Parser<'a> {
             }
         }
         self.expect(Token::CloseParen)?;
-        Ok(ast::Statement::CreateTable { name, columns })
+        let mut fill_factor = None;
+        if self.next_is(Keyword::With.into()) {
+            self.expect(Token::OpenParen)?;
+            match self.next_ident()?.as_str() {
+                "fillfactor" => {}
+                option => return errinput!("unknown table option {option}"),
+            }
+            self.expect(Token::Equal)?;
+            fill_factor = Some(self.parse_expression()?);
+            self.expect(Token::CloseParen)?;
+        }
+        Ok(ast::Statement::CreateTable {
+            name,
+            columns,
+            fill_factor,
+        })
     }
 
     /// Parses a CREATE TABLE column definition.
@@ -406,7 +499,11 @@ impl<'a> Parser<'a> {
 
     // Parses a FROM table.
     fn parse_from_table(&mut self) -> Result<ast::From> {
-        let name = self.next_ident()?;
+        let mut name = self.next_ident()?;
+        // A schema-qualified name, e.g. information_schema.transactions.
+        if self.next_is(Token::Period) {
+            name = format!("{name}.{}", self.next_ident()?);
+        }
         let mut alias = None;
         if self.next_is(Keyword::As.into()) || matches!(self.peek()?, Some(Token::Ident(_))) {
             alias = Some(self.next_ident()?)
@@ -535,6 +632,7 @@ impl<'a> Parser<'a> {
     /// Parses an expression atom. This is either:
     ///
     /// * A literal value.
+    /// * A query parameter.
     /// * A column name.
     /// * A function call.
     /// * A parenthesized expression.
@@ -555,6 +653,12 @@ impl<'a> Parser<'a> {
             Token::Keyword(Keyword::NaN) => ast::Literal::Float(f32::NAN).into(),
             Token::Keyword(Keyword::Null) => ast::Literal::Null.into(),
 
+            // Query parameter.
+            Token::Question => {
+                self.parameters += 1;
+                ast::Expression::Parameter(self.parameters - 1)
+            }
+
             // Function call.
             Token::Ident(name) if self.next_is(Token::OpenParen) => {
                 let mut args = Vec::new();
@@ -652,6 +756,22 @@ impl<'a> Parser<'a> {
             return Ok(Some(operator));
         }
 
+        // Handle BETWEEN separately too, since its bounds are expressions. They
+        // bind tighter than BETWEEN, so the AND between them isn't mistaken for
+        // a conjunction.
+        if let Some(Token::Keyword(Keyword::Between)) = self.peek()? {
+            let null = || ast::Literal::Null.into();
+            let precedence = PostfixOperator::Between(null(), null()).precedence();
+            if precedence < min_precedence {
+                return Ok(None);
+            }
+            self.expect(Keyword::Between.into())?;
+            let low = self.parse_expression_at(precedence + 1)?;
+            self.expect(Keyword::And.into())?;
+            let high = self.parse_expression_at(precedence + 1)?;
+            return Ok(Some(PostfixOperator::Between(low, high)));
+        }
+
         Ok(self.next_if_map(|token| {
             let operator = match token {
                 Token::Exclamation => PostfixOperator::Factorial,
@@ -774,16 +894,17 @@ impl InfixOperator {
 
 /// Postfix operators.
 enum PostfixOperator {
-    Factorial,           // a!
-    Is(ast::Literal),    // a IS NULL | NAN
-    IsNot(ast::Literal), // a IS NOT NULL | NAN
+    Between(ast::Expression, ast::Expression), // a BETWEEN b AND c
+    Factorial,                                 // a!
+    Is(ast::Literal),                          // a IS NULL | NAN
+    IsNot(ast::Literal),                       // a IS NOT NULL | NAN
 }
 
 impl PostfixOperator {
     // The operator precedence.
     fn precedence(&self) -> Precedence {
         match self {
-            Self::Is(_) | Self::IsNot(_) => 4,
+            Self::Between(..) | Self::Is(_) | Self::IsNot(_) => 4,
             Self::Factorial => 9,
         }
     }
@@ -792,9 +913,38 @@ impl PostfixOperator {
     fn build(self, lhs: ast::Expression) -> ast::Expression {
         let lhs = Box::new(lhs);
         match self {
+            Self::Between(low, high) => ast::Operator::And(
+                ast::Expression::from(ast::Operator::GreaterThanOrEqual(lhs.clone(), low.into()))
+                    .into(),
+                ast::Expression::from(ast::Operator::LessThanOrEqual(lhs, high.into())).into(),
+            )
+            .into(),
             Self::Factorial => ast::Operator::Factorial(lhs).into(),
             Self::Is(v) => ast::Operator::Is(lhs, v).into(),
             Self::IsNot(v) => ast::Operator::Not(ast::Operator::Is(lhs, v).into()).into(),
         }
     }
 }
+
+/// Splits the statements `input` ends, each with a semicolon outside of quotes, off its start.
+/// Returns them along with the rest of the input.
+pub fn split_statements(input: &str) -> (Vec<&str>, &str) {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, ';') => {
+                let statement = &input[start..=i];
+                if !statement[..statement.len() - 1].trim().is_empty() {
+                    statements.push(statement);
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    (statements, &input[start..])
+}
@@ -5,6 +5,7 @@ use crate::sql::planner::Node;
 use crate::storage::tuple::Row;
 use crate::types::field::{Field, Label};
 use serde::{Deserialize, Serialize};
+use std::ops::Bound;
 
 /// An expression, made up of nested operations and values. Values are either
 /// constants or dynamic column references. Evaluates to a final value during
@@ -19,6 +20,9 @@ pub enum Expression {
     Constant(Field),
     /// A column reference. Used as row index when evaluating expressions.
     Column(usize),
+    /// A query parameter, replaced by a constant when values are bound to the
+    /// plan (see [`Plan::bind`](super::Plan::bind)). Can't be evaluated.
+    Parameter(usize),
 
     /// Logical AND of two booleans: a AND b.
     And(Box<Expression>, Box<Expression>),
@@ -70,7 +74,7 @@ impl Expression {
         // Precedence levels, for grouping. Matches the parser precedence.
         fn precedence(expr: &Expression) -> u8 {
             match expr {
-                Column(_) | Constant(_) | SquareRoot(_) => 11,
+                Column(_) | Constant(_) | Parameter(_) | SquareRoot(_) => 11,
                 Identity(_) | Negate(_) => 10,
                 Factorial(_) => 9,
                 Exponentiate(_, _) => 8,
@@ -99,6 +103,7 @@ impl Expression {
                 Label::None => format!("#{index}"),
                 label => format!("{label}"),
             },
+            Parameter(_) => "?".to_string(),
 
             And(lhs, rhs) => format!("{} AND {}", format(lhs), format(rhs)),
             Or(lhs, rhs) => format!("{} OR {}", format(lhs), format(rhs)),
@@ -148,6 +153,9 @@ impl Expression {
                 None => panic!("can't reference column {index} with constant evaluation"),
             },
 
+            // Parameters must be bound before execution.
+            Self::Parameter(index) => return errinput!("no value bound to parameter {index}"),
+
             // Logical AND. Inputs must be boolean or NULL. NULLs generally
             // yield NULL, except the special case NULL AND false == false.
             Self::And(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
@@ -301,7 +309,7 @@ impl Expression {
             | Self::Not(expr)
             | Self::SquareRoot(expr) => expr.walk(visitor),
 
-            Self::Constant(_) | Self::Column(_) => true,
+            Self::Constant(_) | Self::Column(_) | Self::Parameter(_) => true,
         }
     }
 
@@ -347,7 +355,7 @@ impl Expression {
             Self::Negate(expr) => Self::Negate(xform(expr)?),
             Self::Not(expr) => Self::Not(xform(expr)?),
 
-            expr @ (Self::Constant(_) | Self::Column(_)) => expr,
+            expr @ (Self::Constant(_) | Self::Column(_) | Self::Parameter(_)) => expr,
         };
         self = after(self)?;
         Ok(self)
@@ -482,6 +490,58 @@ impl Expression {
         }
     }
 
+    /// Checks if an expression bounds a single column by a constant (i.e. a
+    /// comparison =, >, >=, < or <= between a column and a constant),
+    /// returning the column index and the range of values it allows. NULL and
+    /// NaN constants don't match anything, so they aren't considered bounds.
+    pub fn column_bounds(&self) -> Option<(usize, Bound<Field>, Bound<Field>)> {
+        use Expression::*;
+        // Normalizes the comparison to column OP value, returning whether the
+        // operands were swapped.
+        let operands = |lhs: &Expression, rhs: &Expression| match (lhs, rhs) {
+            (Column(c), Constant(value)) if !value.is_undefined() => {
+                Some((*c, value.clone(), false))
+            }
+            (Constant(value), Column(c)) if !value.is_undefined() => {
+                Some((*c, value.clone(), true))
+            }
+            _ => None,
+        };
+        let (column, start, end) = match self {
+            Equal(lhs, rhs) => {
+                let (column, value, _) = operands(lhs, rhs)?;
+                (
+                    column,
+                    Bound::Included(value.clone()),
+                    Bound::Included(value),
+                )
+            }
+            GreaterThan(lhs, rhs) | LessThan(lhs, rhs) => {
+                let (column, value, swapped) = operands(lhs, rhs)?;
+                // Whether the column is above the value: column > value or value < column.
+                let above = matches!(self, GreaterThan(..)) != swapped;
+                match above {
+                    true => (column, Bound::Excluded(value), Bound::Unbounded),
+                    false => (column, Bound::Unbounded, Bound::Excluded(value)),
+                }
+            }
+            // a >= b and a <= b are planned as a > b OR a = b, and likewise.
+            Or(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
+                (GreaterThan(a, b) | LessThan(a, b), Equal(c, d)) if a == c && b == d => {
+                    let (column, start, end) = lhs.column_bounds()?;
+                    let include = |bound| match bound {
+                        Bound::Excluded(value) => Bound::Included(value),
+                        bound => bound,
+                    };
+                    (column, include(start), include(end))
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some((column, start, end))
+    }
+
     /// Replaces column references with the given column.
     pub fn replace_column(self, from: usize, to: usize) -> Self {
         let xform = |expr| match expr {
@@ -1,9 +1,10 @@
 use crate::common::Result;
+use crate::sql::planner::plan::remap_sources;
 use crate::sql::planner::{Aggregate, Direction, Expression};
 use crate::types::field::{Field, Label};
 use crate::types::Table;
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
+use std::ops::{Bound, Deref};
 
 /// A wrapper object holding a query plan node.
 ///
@@ -66,6 +67,18 @@ pub enum Node {
         values: Vec<Field>,
         alias: Option<String>,
     },
+    /// Scans the index on the given column for values within the bounds, and
+    /// emits the rows holding them in index order, or in reverse when the
+    /// direction is descending. The bounds are evaluated when execution
+    /// starts. The alias is only used for formatting.
+    IndexRangeScan {
+        table: Table,
+        column: usize,
+        start: Bound<Expression>,
+        end: Bound<Expression>,
+        direction: Direction,
+        alias: Option<String>,
+    },
     /// Looks up the given primary keys and emits their rows.
     KeyLookup {
         table: Table,
@@ -111,13 +124,22 @@ pub enum Node {
         source: BoxedNode,
         targets: Vec<Option<usize>>,
     },
-    /// A full table scan, with an optional pushed-down filter. The schema is
-    /// used during plan optimization. The alias is only used for formatting.
+    /// A full table scan, with an optional pushed-down filter. The filter is
+    /// evaluated on just the columns it references, before the rest of the row
+    /// is decoded. Given a direction, the rows are emitted in the order they
+    /// are stored (by rowid), or in reverse when descending; otherwise in any
+    /// order. The schema is used during plan optimization. The alias is only
+    /// used for formatting.
     Scan {
         table: Table,
         filter: Option<Expression>,
+        direction: Option<Direction>,
         alias: Option<String>,
     },
+    /// Emits a single row with the number of rows in the table, counted
+    /// without reading the rows themselves. Replaces COUNT(*) over a full
+    /// table scan. The alias is only used for formatting.
+    TableCount { table: Table, alias: Option<String> },
     /// A constant set of values.
     Values { rows: Vec<Vec<Expression>> },
 }
@@ -128,6 +150,7 @@ impl Node {
         match self {
             // Source nodes emit all table columns.
             Self::IndexLookup { table, .. }
+            | Self::IndexRangeScan { table, .. }
             | Self::KeyLookup { table, .. }
             | Self::Scan { table, .. } => table.col_count(),
 
@@ -158,6 +181,7 @@ impl Node {
 
             // And some are trivial.
             Self::Nothing { columns } => columns.len(),
+            Self::TableCount { .. } => 1,
             Self::Values { rows } => rows.first().map(|row| row.len()).unwrap_or(0),
         }
     }
@@ -172,6 +196,9 @@ impl Node {
             Self::IndexLookup {
                 table, alias: _, ..
             }
+            | Self::IndexRangeScan {
+                table, alias: _, ..
+            }
             | Self::KeyLookup {
                 table, alias: _, ..
             }
@@ -229,7 +256,36 @@ impl Node {
             Self::Nothing { columns } => columns.get(index).cloned().unwrap_or(Label::None),
 
             // And some don't have any names at all.
-            Self::Values { .. } => Label::None,
+            Self::TableCount { .. } | Self::Values { .. } => Label::None,
+        }
+    }
+
+    /// Recursively walks the node tree depth-first, calling the given closure
+    /// until it returns false. Returns true otherwise.
+    pub fn walk(&self, visitor: &mut impl FnMut(&Node) -> bool) -> bool {
+        if !visitor(self) {
+            return false;
+        }
+        match self {
+            Self::Aggregate { source, .. }
+            | Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. }
+            | Self::Projection { source, .. }
+            | Self::Remap { source, .. } => source.walk(visitor),
+
+            Self::HashJoin { left, right, .. } | Self::NestedLoopJoin { left, right, .. } => {
+                left.walk(visitor) && right.walk(visitor)
+            }
+
+            Self::IndexLookup { .. }
+            | Self::IndexRangeScan { .. }
+            | Self::KeyLookup { .. }
+            | Self::Nothing { .. }
+            | Self::Scan { .. }
+            | Self::TableCount { .. }
+            | Self::Values { .. } => true,
         }
     }
 
@@ -312,9 +368,11 @@ impl Node {
             },
 
             Self::IndexLookup { .. }
+            | Self::IndexRangeScan { .. }
             | Self::KeyLookup { .. }
             | Self::Nothing { .. }
             | Self::Scan { .. }
+            | Self::TableCount { .. }
             | Self::Values { .. } => self,
         };
         self = after(self)?;
@@ -329,6 +387,25 @@ impl Node {
         after: &impl Fn(Expression) -> Result<Expression>,
     ) -> Result<Self> {
         Ok(match self {
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => {
+                let group_by = group_by
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?;
+                let aggregates = aggregates
+                    .into_iter()
+                    .map(|aggregate| aggregate.transform(before, after))
+                    .collect::<Result<_>>()?;
+                Self::Aggregate {
+                    source,
+                    group_by,
+                    aggregates,
+                }
+            }
             Self::Filter {
                 source,
                 mut predicate,
@@ -350,6 +427,30 @@ impl Node {
                     outer,
                 }
             }
+            Self::IndexRangeScan {
+                table,
+                column,
+                start,
+                end,
+                direction,
+                alias,
+            } => {
+                let xform = |bound: Bound<Expression>| -> Result<Bound<Expression>> {
+                    Ok(match bound {
+                        Bound::Included(expr) => Bound::Included(expr.transform(before, after)?),
+                        Bound::Excluded(expr) => Bound::Excluded(expr.transform(before, after)?),
+                        Bound::Unbounded => Bound::Unbounded,
+                    })
+                };
+                Self::IndexRangeScan {
+                    table,
+                    column,
+                    start: xform(start)?,
+                    end: xform(end)?,
+                    direction,
+                    alias,
+                }
+            }
             Self::Order { source, mut key } => {
                 key = key
                     .into_iter()
@@ -377,12 +478,14 @@ impl Node {
                 table,
                 alias,
                 filter: Some(filter),
+                direction,
             } => {
                 let filter = Some(filter.transform(before, after)?);
                 Self::Scan {
                     table,
                     alias,
                     filter,
+                    direction,
                 }
             }
             Self::Values { mut rows } => {
@@ -398,8 +501,7 @@ impl Node {
                 Self::Values { rows }
             }
 
-            Self::Aggregate { .. }
-            | Self::HashJoin { .. }
+            Self::HashJoin { .. }
             | Self::IndexLookup { .. }
             | Self::KeyLookup { .. }
             | Self::Limit { .. }
@@ -409,7 +511,229 @@ impl Node {
             | Self::Nothing { .. }
             | Self::Offset { .. }
             | Self::Remap { .. }
-            | Self::Scan { filter: None, .. } => self,
+            | Self::Scan { filter: None, .. }
+            | Self::TableCount { .. } => self,
         })
     }
 }
+
+/// Formats the plan as an EXPLAIN tree, one node per line.
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.format(f, "", true, true)
+    }
+}
+
+impl Node {
+    /// Recursively formats the node. The prefix is used for tree branch lines.
+    /// root is true if this is the root (first) node, and last_child is true
+    /// if this is the last child node of the parent.
+    pub fn format(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        prefix: &str,
+        root: bool,
+        last_child: bool,
+    ) -> std::fmt::Result {
+        // If this is not the root node, emit a newline after the previous node.
+        // This avoids a spurious newline at the end of the plan.
+        if !root {
+            writeln!(f)?;
+        }
+
+        // Prefix the node with a tree branch line. Modify the prefix for any
+        // child nodes we'll recurse into.
+        let prefix = if !last_child {
+            write!(f, "{prefix}├─ ")?;
+            format!("{prefix}│  ")
+        } else if !root {
+            write!(f, "{prefix}└─ ")?;
+            format!("{prefix}   ")
+        } else {
+            write!(f, "{prefix}")?;
+            prefix.to_string()
+        };
+
+        // Formats a table name with its alias, if any.
+        let table_name = |table: &Table, alias: &Option<String>| match alias {
+            Some(alias) => format!("{} as {alias}", table.name()),
+            None => table.name().to_string(),
+        };
+
+        // Format the node.
+        match self {
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => {
+                let expressions = group_by
+                    .iter()
+                    .map(|expr| expr.format(source))
+                    .chain(aggregates.iter().map(|agg| agg.format(source)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "Aggregate: {expressions}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Filter { source, predicate } => {
+                write!(f, "Filter: {}", predicate.format(source))?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::HashJoin {
+                left,
+                left_column,
+                right,
+                right_column,
+                outer,
+            } => {
+                let kind = if *outer { "outer" } else { "inner" };
+                let left_label = match left.column_label(*left_column) {
+                    Label::None => format!("left #{left_column}"),
+                    label => label.to_string(),
+                };
+                let right_label = match right.column_label(*right_column) {
+                    Label::None => format!("right #{right_column}"),
+                    label => label.to_string(),
+                };
+                write!(f, "HashJoin: {kind} on {left_label} = {right_label}")?;
+                left.format(f, &prefix, false, false)?;
+                right.format(f, &prefix, false, true)?;
+            }
+            Self::IndexLookup {
+                table,
+                column,
+                values,
+                alias,
+            } => {
+                let column = table.get_column(*column).get_name();
+                write!(f, "IndexLookup: {}.{column}", table_name(table, alias))?;
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, " ({})", values.join(", "))?;
+            }
+            Self::IndexRangeScan {
+                table,
+                column,
+                start,
+                end,
+                direction,
+                alias,
+            } => {
+                let column = table.get_column(*column).get_name();
+                let start = match start {
+                    Bound::Included(expr) => format!("[{}", expr.format_constant()),
+                    Bound::Excluded(expr) => format!("({}", expr.format_constant()),
+                    Bound::Unbounded => "(-∞".to_string(),
+                };
+                let end = match end {
+                    Bound::Included(expr) => format!("{}]", expr.format_constant()),
+                    Bound::Excluded(expr) => format!("{})", expr.format_constant()),
+                    Bound::Unbounded => "∞)".to_string(),
+                };
+                write!(
+                    f,
+                    "IndexRangeScan: {}.{column} {start}, {end} {direction}",
+                    table_name(table, alias)
+                )?;
+            }
+            Self::KeyLookup { table, keys, alias } => {
+                write!(f, "KeyLookup: {}", table_name(table, alias))?;
+                let keys = keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+                write!(f, " ({})", keys.join(", "))?;
+            }
+            Self::Limit { source, limit } => {
+                write!(f, "Limit: {limit}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                outer,
+            } => {
+                let kind = if *outer { "outer" } else { "inner" };
+                write!(f, "NestedLoopJoin: {kind}")?;
+                if let Some(predicate) = predicate {
+                    write!(f, " on {}", predicate.format(self))?;
+                }
+                left.format(f, &prefix, false, false)?;
+                right.format(f, &prefix, false, true)?;
+            }
+            Self::Nothing { .. } => write!(f, "Nothing")?,
+            Self::Offset { source, offset } => {
+                write!(f, "Offset: {offset}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Order { source, key } => {
+                let key = key
+                    .iter()
+                    .map(|(expr, dir)| format!("{} {dir}", expr.format(source)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "Order: {key}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Projection {
+                source,
+                expressions,
+                aliases,
+            } => {
+                let expressions = expressions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, expr)| match aliases.get(i) {
+                        Some(Label::None) | None => expr.format(source),
+                        Some(alias) => format!("{} as {alias}", expr.format(source)),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "Projection: {expressions}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Remap { source, targets } => {
+                let remap = remap_sources(targets)
+                    .into_iter()
+                    .map(|from| match from {
+                        Some(from) => source.column_label(from).to_string(),
+                        None => "NULL".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "Remap: {remap}")?;
+                source.format(f, &prefix, false, true)?;
+            }
+            Self::Scan {
+                table,
+                filter,
+                direction,
+                alias,
+            } => {
+                write!(f, "Scan: {}", table_name(table, alias))?;
+                if let Some(direction) = direction {
+                    write!(f, " by rowid {direction}")?;
+                }
+                if let Some(filter) = filter {
+                    write!(f, " ({})", filter.format(self))?;
+                }
+            }
+            Self::TableCount { table, alias } => {
+                write!(f, "TableCount: {}", table_name(table, alias))?;
+            }
+            Self::Values { rows } => {
+                write!(f, "Values: ")?;
+                match rows.len() {
+                    1 => {
+                        let row = rows[0]
+                            .iter()
+                            .map(|expr| expr.format(self))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        write!(f, "{row}")?;
+                    }
+                    n => write!(f, "{n} rows")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,303 @@
 use crate::common::Result;
-use crate::sql::planner::BoxedNode;
+use crate::sql::planner::{Aggregate, BoxedNode, Direction, Expression, Node};
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+use std::cmp::Ordering;
+use std::ops::Bound;
 //
 // /// A plan optimizer, which recursively transforms a plan node to make plan
 // /// execution more efficient where possible.
 pub type Optimizer = fn(BoxedNode) -> Result<BoxedNode>;
 //
 // /// The set of optimizers, and the order in which they are applied.
-pub static OPTIMIZERS: &[(&str, Optimizer)] = &[];
+pub static OPTIMIZERS: &[(&str, Optimizer)] = &[
+    ("Index range scan", index_range_scan),
+    ("Index order", index_order),
+    ("Filter pushdown", filter_pushdown),
+    ("Table count", table_count),
+];
+
+/// Replaces a filtered full table scan with an index range scan, when the
+/// filter bounds an indexed column by constants. The filter is kept above the
+/// index scan, since the bounds only narrow down the rows it has to check:
+/// the filter may have other conditions, and NULLs lie within a range that
+/// is open at the bottom. Scans that emit rows in rowid order are kept.
+pub fn index_range_scan(node: BoxedNode) -> Result<BoxedNode> {
+    let xform = |node| match node {
+        Node::Filter { source, predicate } => match *source.inner {
+            Node::Scan {
+                table,
+                filter: None,
+                direction: None,
+                alias,
+            } => into_index_range_scan(table, predicate, alias),
+            source => Node::Filter {
+                source: source.into(),
+                predicate,
+            },
+        },
+        Node::Scan {
+            table,
+            filter: Some(filter),
+            direction: None,
+            alias,
+        } => into_index_range_scan(table, filter, alias),
+        node => node,
+    };
+    Ok(node.inner.transform(&Ok, &|node| Ok(xform(node)))?.into())
+}
+
+/// Builds a scan of the table filtered by the predicate, using the index on
+/// the first indexed column the predicate bounds, if any.
+fn into_index_range_scan(
+    table: Table,
+    predicate: Expression,
+    alias: Option<String>,
+) -> Node {
+    let bounds: Vec<_> = predicate
+        .clone()
+        .into_cnf_vec()
+        .iter()
+        .filter_map(|expr| expr.column_bounds())
+        .filter(|(column, start, end)| {
+            let definition = table.get_column(*column);
+            let value = match (start, end) {
+                (Bound::Included(value) | Bound::Excluded(value), _)
+                | (_, Bound::Included(value) | Bound::Excluded(value)) => value,
+                (Bound::Unbounded, Bound::Unbounded) => return false,
+            };
+            // Keys are only comparable to values of the column's type.
+            definition.is_indexed() && value.get_type() == definition.get_data_type()
+        })
+        .collect();
+    let Some(&(column, _, _)) = bounds.first() else {
+        return Node::Filter {
+            source: Node::Scan {
+                table,
+                filter: None,
+                direction: None,
+                alias,
+            }
+            .into(),
+            predicate,
+        };
+    };
+
+    // Intersect the bounds on the column.
+    let (mut start, mut end) = (Bound::Unbounded, Bound::Unbounded);
+    for (_, lower, upper) in bounds.into_iter().filter(|(c, _, _)| *c == column) {
+        start = tighter(start, lower, Ordering::Greater);
+        end = tighter(end, upper, Ordering::Less);
+    }
+    let constant = |bound| match bound {
+        Bound::Included(value) => Bound::Included(Expression::Constant(value)),
+        Bound::Excluded(value) => Bound::Excluded(Expression::Constant(value)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    Node::Filter {
+        source: Node::IndexRangeScan {
+            table,
+            column,
+            start: constant(start),
+            end: constant(end),
+            direction: Direction::Ascending,
+            alias,
+        }
+        .into(),
+        predicate,
+    }
+}
+
+/// Returns the tighter of two bounds on the same side of a range, where
+/// `inward` is how a tighter value compares to a looser one.
+fn tighter(a: Bound<Field>, b: Bound<Field>, inward: Ordering) -> Bound<Field> {
+    let value = |bound: &Bound<Field>| match bound {
+        Bound::Included(value) | Bound::Excluded(value) => Some(value.clone()),
+        Bound::Unbounded => None,
+    };
+    match (value(&a), value(&b)) {
+        (None, _) => b,
+        (_, None) => a,
+        (Some(x), Some(y)) if x.cmp(&y) == inward => a,
+        (Some(x), Some(y)) if y.cmp(&x) == inward => b,
+        // Equal values: an excluded bound is the tighter one.
+        _ => match a {
+            Bound::Excluded(_) => a,
+            _ => b,
+        },
+    }
+}
+
+/// Removes a sort on the column of an index range scan the rows come from,
+/// having the scan emit them in the sort direction instead. Filters and
+/// projections in between keep the scan order.
+pub fn index_order(node: BoxedNode) -> Result<BoxedNode> {
+    let xform = |node| match node {
+        Node::Order { source, key } => {
+            let ordered = match key.as_slice() {
+                [(Expression::Column(column), direction)] => {
+                    order_by_index(&source, *column, direction)
+                }
+                _ => None,
+            };
+            ordered.unwrap_or(Node::Order { source, key })
+        }
+        node => node,
+    };
+    Ok(node.inner.transform(&Ok, &|node| Ok(xform(node)))?.into())
+}
+
+/// Returns the node with the index range scan it reads from scanning in the
+/// given direction, if the node emits the scan's rows in scan order and the
+/// given column of its rows is the indexed one. Otherwise returns None.
+fn order_by_index(node: &Node, column: usize, direction: &Direction) -> Option<Node> {
+    Some(match node {
+        Node::IndexRangeScan {
+            table,
+            column: indexed,
+            start,
+            end,
+            direction: _,
+            alias,
+        } if *indexed == column => Node::IndexRangeScan {
+            table: table.clone(),
+            column,
+            start: start.clone(),
+            end: end.clone(),
+            direction: direction.clone(),
+            alias: alias.clone(),
+        },
+        Node::Filter { source, predicate } => Node::Filter {
+            source: order_by_index(source, column, direction)?.into(),
+            predicate: predicate.clone(),
+        },
+        Node::Projection {
+            source,
+            expressions,
+            aliases,
+        } => match expressions.get(column)? {
+            Expression::Column(source_column) => Node::Projection {
+                source: order_by_index(source, *source_column, direction)?.into(),
+                expressions: expressions.clone(),
+                aliases: aliases.clone(),
+            },
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Pushes the simple conjuncts of a filter on a full table scan down into the
+/// scan, which evaluates them before decoding the whole row (see
+/// [`Node::Scan`]). Other conjuncts stay in a filter above the scan. Runs after
+/// the index optimizers, which match filters on scans too.
+pub fn filter_pushdown(node: BoxedNode) -> Result<BoxedNode> {
+    let xform = |node| match node {
+        Node::Filter { source, predicate } => match *source.inner {
+            Node::Scan {
+                table,
+                filter,
+                direction,
+                alias,
+            } => {
+                let (pushed, kept): (Vec<_>, Vec<_>) = predicate
+                    .into_cnf_vec()
+                    .into_iter()
+                    .partition(|expr| is_simple(expr, &table));
+                let filter = Expression::and_vec(filter.into_iter().chain(pushed).collect());
+                let scan = Node::Scan {
+                    table,
+                    filter,
+                    direction,
+                    alias,
+                };
+                match Expression::and_vec(kept) {
+                    Some(predicate) => Node::Filter {
+                        source: scan.into(),
+                        predicate,
+                    },
+                    None => scan,
+                }
+            }
+            source => Node::Filter {
+                source: source.into(),
+                predicate,
+            },
+        },
+        node => node,
+    };
+    Ok(node.inner.transform(&Ok, &|node| Ok(xform(node)))?.into())
+}
+
+/// Returns whether a predicate on a table's rows is simple enough to push into
+/// a scan of it: it only compares columns with constants or each other, using
+/// values of comparable types, and combines such comparisons with AND, OR and
+/// NOT. These evaluate without error on any row, which keeps the results the
+/// same: a scan fails on errors, where a filter skips the row.
+fn is_simple(expr: &Expression, table: &Table) -> bool {
+    use Expression::*;
+    let data_type = |expr: &Expression| match expr {
+        Column(index) => table.columns().get(*index).map(|c| c.get_data_type()),
+        Constant(value) => Some(value.get_type()),
+        _ => None,
+    };
+    let comparable = |lhs: &Expression, rhs: &Expression| {
+        use DataType::{Float, Int, Invalid};
+        match (data_type(lhs), data_type(rhs)) {
+            // NULL constants compare to anything.
+            (Some(Invalid), Some(_)) | (Some(_), Some(Invalid)) => true,
+            (Some(Int | Float), Some(Int | Float)) => true,
+            (Some(lhs), Some(rhs)) => lhs == rhs,
+            _ => false,
+        }
+    };
+    match expr {
+        And(lhs, rhs) | Or(lhs, rhs) => is_simple(lhs, table) && is_simple(rhs, table),
+        Not(expr) => is_simple(expr, table),
+        Equal(lhs, rhs) | GreaterThan(lhs, rhs) | LessThan(lhs, rhs) => comparable(lhs, rhs),
+        Like(lhs, rhs) => {
+            comparable(lhs, rhs) && [lhs, rhs].iter().all(|expr| {
+                matches!(data_type(expr), Some(DataType::Text | DataType::Invalid))
+            })
+        }
+        Is(expr, Field::Null) => data_type(expr).is_some(),
+        Column(_) | Constant(_) => {
+            matches!(data_type(expr), Some(DataType::Bool | DataType::Invalid))
+        }
+        _ => false,
+    }
+}
+
+/// Replaces COUNT(*) over a full table scan, without a filter or GROUP BY,
+/// with a table count, which counts the rows without reading them. COUNT of
+/// any other non-NULL constant counts every row too.
+pub fn table_count(node: BoxedNode) -> Result<BoxedNode> {
+    let xform = |node| match node {
+        Node::Aggregate {
+            source,
+            group_by,
+            aggregates,
+        } => match (&*source.inner, group_by.as_slice(), aggregates.as_slice()) {
+            (
+                Node::Scan {
+                    table,
+                    filter: None,
+                    alias,
+                    ..
+                },
+                [],
+                [Aggregate::Count(Expression::Constant(value))],
+            ) if *value != Field::Null => Node::TableCount {
+                table: table.clone(),
+                alias: alias.clone(),
+            },
+            _ => Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            },
+        },
+        node => node,
+    };
+    Ok(node.inner.transform(&Ok, &|node| Ok(xform(node)))?.into())
+}
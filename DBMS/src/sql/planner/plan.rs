@@ -1,4 +1,5 @@
 use crate::common::Result;
+use crate::errinput;
 use crate::sql::engine::{Catalog, Transaction};
 use crate::sql::execution;
 use crate::sql::execution::ExecutionResult;
@@ -6,8 +7,10 @@ use crate::sql::parser::ast;
 use crate::sql::planner::expression::Expression;
 use crate::sql::planner::optimizer::OPTIMIZERS;
 use crate::sql::planner::{BoxedNode, Node, Planner};
+use crate::types::field::Field;
 use crate::types::Table;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Plan {
@@ -53,6 +56,86 @@ impl Plan {
         execution::execute_plan(self, txn, txn)
     }
 
+    /// Binds values to the plan's parameters, replacing each with the value at
+    /// its index. Errors if a parameter has no value.
+    pub fn bind(self, values: &[Field]) -> Result<Self> {
+        let bind = |expr| match expr {
+            Expression::Parameter(index) => match values.get(index) {
+                Some(value) => Ok(Expression::Constant(value.clone())),
+                None => errinput!("no value bound to parameter {index}"),
+            },
+            expr => Ok(expr),
+        };
+        let bind_node = |node: BoxedNode| -> Result<BoxedNode> {
+            let xform = |node: Node| node.transform_expressions(&bind, &Ok);
+            Ok(node.inner.transform(&Ok, &xform)?.into())
+        };
+        Ok(match self {
+            Self::CreateTable { .. } | Self::DropTable { .. } => self,
+            Self::Delete { table, source } => Self::Delete {
+                table,
+                source: bind_node(source)?,
+            },
+            Self::Insert { table, source } => Self::Insert {
+                table,
+                source: bind_node(source)?,
+            },
+            Self::Update {
+                table,
+                source,
+                expressions,
+            } => Self::Update {
+                table,
+                source: bind_node(source)?,
+                expressions: expressions
+                    .into_iter()
+                    .map(|(column, expr)| Ok((column, expr.transform(&bind, &Ok)?)))
+                    .collect::<Result<_>>()?,
+            },
+            Self::Select(root) => Self::Select(bind_node(root)?),
+        })
+    }
+
+    /// Returns the names of the tables the plan reads or writes.
+    pub fn tables(&self) -> BTreeSet<String> {
+        let mut tables = BTreeSet::new();
+        let source = match self {
+            Self::CreateTable { schema } => {
+                tables.insert(schema.name().to_string());
+                None
+            }
+            Self::DropTable { table, .. } => {
+                tables.insert(table.clone());
+                None
+            }
+            Self::Delete { table, source } => {
+                tables.insert(table.clone());
+                Some(source)
+            }
+            Self::Insert { table, source } | Self::Update { table, source, .. } => {
+                tables.insert(table.name().to_string());
+                Some(source)
+            }
+            Self::Select(root) => Some(root),
+        };
+        if let Some(source) = source {
+            source.walk(&mut |node| {
+                match node {
+                    Node::IndexLookup { table, .. }
+                    | Node::IndexRangeScan { table, .. }
+                    | Node::KeyLookup { table, .. }
+                    | Node::Scan { table, .. }
+                    | Node::TableCount { table, .. } => {
+                        tables.insert(table.name().to_string());
+                    }
+                    _ => {}
+                }
+                true
+            });
+        }
+        tables
+    }
+
     /// Optimizes the plan, consuming it.
     pub fn optimize(self) -> Result<Self> {
         let optimize = |node| OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node));
@@ -80,6 +163,41 @@ impl Plan {
     }
 }
 
+/// Formats the plan as an EXPLAIN tree.
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateTable { schema } => write!(f, "CreateTable: {}", schema.name()),
+            Self::DropTable { table, .. } => write!(f, "DropTable: {table}"),
+            Self::Delete { table, source } => {
+                write!(f, "Delete: {table}")?;
+                source.format(f, "", false, true)
+            }
+            Self::Insert { table, source } => {
+                write!(f, "Insert: {}", table.name())?;
+                source.format(f, "", false, true)
+            }
+            Self::Update {
+                table,
+                source,
+                expressions,
+            } => {
+                let expressions = expressions
+                    .iter()
+                    .map(|(i, expr)| {
+                        let column = table.get_column(*i).get_name();
+                        format!("{column}={}", expr.format(source))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "Update: {} ({expressions})", table.name())?;
+                source.format(f, "", false, true)
+            }
+            Self::Select(root) => root.fmt(f),
+        }
+    }
+}
+
 /// An aggregate function.
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -91,9 +209,23 @@ pub enum Aggregate {
     Sum(Expression),
 }
 
-#[allow(dead_code)]
 impl Aggregate {
-    fn format(&self, node: &Node) -> String {
+    /// Transforms the aggregate's expression, like [`Expression::transform`].
+    pub(super) fn transform(
+        self,
+        before: &impl Fn(Expression) -> Result<Expression>,
+        after: &impl Fn(Expression) -> Result<Expression>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Average(expr) => Self::Average(expr.transform(before, after)?),
+            Self::Count(expr) => Self::Count(expr.transform(before, after)?),
+            Self::Max(expr) => Self::Max(expr.transform(before, after)?),
+            Self::Min(expr) => Self::Min(expr.transform(before, after)?),
+            Self::Sum(expr) => Self::Sum(expr.transform(before, after)?),
+        })
+    }
+
+    pub(super) fn format(&self, node: &Node) -> String {
         match self {
             Self::Average(expr) => format!("avg({})", expr.format(node)),
             Self::Count(expr) => format!("count({})", expr.format(node)),
@@ -1,10 +1,11 @@
 use crate::common::Result;
+use crate::config::config::{DEFAULT_FILL_FACTOR, MIN_FILL_FACTOR};
 use crate::errinput;
 use crate::sql::engine::Catalog;
 use crate::sql::parser::ast;
 use crate::sql::parser::ast::Statement;
 use crate::sql::planner::plan::remap_sources;
-use crate::sql::planner::{Aggregate, Expression, Node, Plan};
+use crate::sql::planner::{Aggregate, Direction, Expression, Node, Plan};
 use crate::types::field::{Field, Label};
 use crate::types::{Column, Table};
 use itertools::Itertools as _;
@@ -29,7 +30,11 @@ impl<'a, C: Catalog> Planner<'a, C> {
             Explain(_) => {
                 todo!()
             }
-            CreateTable { name, columns } => self.build_create_table(name, columns),
+            CreateTable {
+                name,
+                columns,
+                fill_factor,
+            } => self.build_create_table(name, columns, fill_factor),
             DropTable { name, if_exists } => Ok(Plan::DropTable {
                 table: name,
                 if_exists,
@@ -139,15 +144,32 @@ impl<'a, C: Catalog> Planner<'a, C> {
     }
 
     /// Builds a CREATE TABLE plan.
-    fn build_create_table(&self, name: String, columns: Vec<ast::Column>) -> Result<Plan> {
+    fn build_create_table(
+        &self,
+        name: String,
+        columns: Vec<ast::Column>,
+        fill_factor: Option<ast::Expression>,
+    ) -> Result<Plan> {
+        let fill_factor = match fill_factor.map(Self::evaluate_constant).transpose()? {
+            None => DEFAULT_FILL_FACTOR,
+            Some(Field::Integer(percent)) if (MIN_FILL_FACTOR as i32..=100).contains(&percent) => {
+                percent as u8
+            }
+            Some(value) => {
+                return errinput!(
+                    "fill factor must be between {MIN_FILL_FACTOR} and 100, got {value}"
+                )
+            }
+        };
         let table = Table::builder()
             .name(&name)
+            .fill_factor(fill_factor)
             .columns(
                 columns
                     .into_iter()
                     .map(|c| {
                         let nullable = c.nullable.unwrap_or(false);
-                        Ok(Column::new(
+                        let mut column = Column::new(
                             &c.name,
                             c.datatype,
                             nullable,
@@ -157,7 +179,10 @@ impl<'a, C: Catalog> Planner<'a, C> {
                                 None => None,
                             },
                             None,
-                        ))
+                        );
+                        column.set_indexed(c.index);
+                        column.set_unique(c.unique);
+                        Ok(column)
                     })
                     .collect::<Result<_>>()?,
             )
@@ -178,6 +203,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 table,
                 alias: None,
                 filter,
+                direction: None,
             }
             .into(),
         })
@@ -299,6 +325,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 ast::Literal::Float(f) => Field::Float(f),
                 ast::Literal::String(s) => Field::String(s),
             }),
+            ast::Expression::Parameter(index) => Parameter(index),
             ast::Expression::Column(table, name) => {
                 Column(scope.lookup_column(table.as_deref(), &name)?)
             }
@@ -378,6 +405,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 table,
                 alias: None,
                 filter,
+                direction: None,
             }
             .into(),
             expressions,
@@ -393,7 +421,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
         r#where: Option<ast::Expression>,
         group_by: Vec<ast::Expression>,
         having: Option<ast::Expression>,
-        order_by: Vec<(ast::Expression, ast::Direction)>,
+        mut order_by: Vec<(ast::Expression, ast::Direction)>,
         offset: Option<ast::Expression>,
         limit: Option<ast::Expression>,
     ) -> Result<Plan> {
@@ -408,6 +436,25 @@ impl<'a, C: Catalog> Planner<'a, C> {
             Node::Values { rows: vec![vec![]] }
         };
 
+        // ORDER BY rowid sorts the rows by where they are stored, which a table
+        // scan can emit them in without sorting them. rowid is only a pseudo
+        // column when no column or SELECT alias has the name.
+        let aliased = select
+            .iter()
+            .any(|(_, alias)| alias.as_deref() == Some("rowid"));
+        let by_rowid = match order_by.as_slice() {
+            [(ast::Expression::Column(table, name), _)] => {
+                name == "rowid" && !aliased && scope.lookup_column(table.as_deref(), name).is_err()
+            }
+            _ => false,
+        };
+        let rowid_order = match by_rowid {
+            true => order_by
+                .pop()
+                .map(|(_, direction)| Direction::from(direction)),
+            false => None,
+        };
+
         // Expand SELECT * to all FROM columns if there are multiple SELECT expressions or a
         // GROUP BY clause (to ensure all columns are in GROUP BY). For simplicity, expressions
         // only support scalar values, so we special-case the * tuple here.
@@ -482,7 +529,9 @@ impl<'a, C: Catalog> Planner<'a, C> {
         }
 
         // Build ORDER BY clause.
-        if !order_by.is_empty() {
+        if let Some(direction) = rowid_order {
+            node = Self::build_rowid_order(node, direction)?;
+        } else if !order_by.is_empty() {
             let key = order_by
                 .into_iter()
                 .map(|(expr, dir)| Ok((Self::build_expression(expr, &scope)?, dir.into())))
@@ -528,6 +577,39 @@ impl<'a, C: Catalog> Planner<'a, C> {
         Ok(Plan::Select(node.into()))
     }
 
+    /// Orders the rows of a node by rowid, by having the table scan they come
+    /// from emit them in rowid order. Filters and projections in between keep
+    /// the scan order, anything else is an error.
+    fn build_rowid_order(node: Node, direction: Direction) -> Result<Node> {
+        Ok(match node {
+            Node::Scan {
+                table,
+                filter,
+                direction: _,
+                alias,
+            } => Node::Scan {
+                table,
+                filter,
+                direction: Some(direction),
+                alias,
+            },
+            Node::Filter { source, predicate } => Node::Filter {
+                source: Self::build_rowid_order(*source.inner, direction)?.into(),
+                predicate,
+            },
+            Node::Projection {
+                source,
+                expressions,
+                aliases,
+            } => Node::Projection {
+                source: Self::build_rowid_order(*source.inner, direction)?.into(),
+                expressions,
+                aliases,
+            },
+            _ => return errinput!("ORDER BY rowid requires a single table without aggregates"),
+        })
+    }
+
     /// Builds a FROM clause consisting of one or more items. Each item is
     /// either a table or a join of two or more tables. All items are implicitly
     /// joined, e.g. "SELECT * FROM a, b" is an implicit full join of a and b.
@@ -568,6 +650,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     table,
                     alias,
                     filter: None,
+                    direction: None,
                 }
             }
 
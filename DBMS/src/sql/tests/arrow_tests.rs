@@ -0,0 +1,227 @@
+use crate::common::Error;
+use crate::sql::engine::{Engine as _, Local, Transaction as _};
+use crate::sql::execution::ExecutionResult;
+use crate::sql::export::arrow::record_batches;
+use crate::sql::parser::Parser;
+use crate::sql::planner::Plan;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::page::RecordId;
+use crate::storage::tuple::{Row, Rows};
+use crate::storage::HeapTableManager;
+use crate::types::field::{Field, Label};
+use arrow::array::{Array, BooleanArray, Float64Array, Int32Array, StringArray};
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+
+/// Streams a query's result through the engine, and reads every type back.
+#[test]
+fn test_query_round_trip() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(50)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    engine
+        .session()
+        .execute_script(
+            "CREATE TABLE t (id INT PRIMARY KEY, score FLOAT, active BOOLEAN, name TEXT);
+            INSERT INTO t VALUES (1, 1.5, TRUE, 'ada'), (2, -0.25, FALSE, 'ünïcödé'),
+                (3, 1e10, TRUE, '')",
+        )
+        .unwrap();
+
+    let txn = engine.begin().unwrap();
+    let statement = Parser::new("SELECT * FROM t ORDER BY id").parse().unwrap();
+    let result = Plan::build(statement, &txn)
+        .unwrap()
+        .optimize()
+        .unwrap()
+        .execute(&txn)
+        .unwrap();
+    let ExecutionResult::Select { rows, columns } = result else {
+        panic!("expected a select result");
+    };
+    let batches: Vec<RecordBatch> = record_batches(columns, rows, 2)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        vec![2, 1],
+        batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>()
+    );
+    let schema = batches[0].schema();
+    let fields: Vec<_> = schema
+        .fields()
+        .iter()
+        .map(|field| (field.name().as_str(), field.data_type().clone()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("id", DataType::Int32),
+            ("score", DataType::Float64),
+            ("active", DataType::Boolean),
+            ("name", DataType::Utf8),
+        ],
+        fields
+    );
+    assert_eq!(schema, batches[1].schema());
+
+    let batch = &batches[0];
+    let ids = column::<Int32Array>(batch, 0);
+    assert_eq!(vec![Some(1), Some(2)], ids.iter().collect::<Vec<_>>());
+    let scores = column::<Float64Array>(batch, 1);
+    assert_eq!(
+        vec![Some(1.5), Some(-0.25)],
+        scores.iter().collect::<Vec<_>>()
+    );
+    let active = column::<BooleanArray>(batch, 2);
+    assert_eq!(
+        vec![Some(true), Some(false)],
+        active.iter().collect::<Vec<_>>()
+    );
+    let names = column::<StringArray>(batch, 3);
+    assert_eq!(
+        vec![Some("ada"), Some("ünïcödé")],
+        names.iter().collect::<Vec<_>>()
+    );
+    let names = column::<StringArray>(&batches[1], 3);
+    assert_eq!(vec![Some("")], names.iter().collect::<Vec<_>>());
+}
+
+/// NULLs are nulls in columns of any type, and columns of only NULLs are Null.
+#[test]
+fn test_nulls() {
+    let rows: Vec<Vec<Field>> = (0..10)
+        .map(|i| {
+            vec![
+                // Dense: every row but the first is NULL.
+                match i {
+                    0 => Field::Integer(i),
+                    _ => Field::Null,
+                },
+                // Sparse: every third row is NULL.
+                match i % 3 {
+                    0 => Field::Null,
+                    _ => Field::String(i.to_string()),
+                },
+                Field::Null,
+            ]
+        })
+        .collect();
+    let batches = convert(&["dense", "sparse", "none"], rows, 10).unwrap();
+    assert_eq!(1, batches.len());
+    let batch = &batches[0];
+
+    let dense = column::<Int32Array>(batch, 0);
+    assert_eq!(9, dense.null_count());
+    assert_eq!(Some(0), dense.iter().next().unwrap());
+    let sparse = column::<StringArray>(batch, 1);
+    assert_eq!(4, sparse.null_count());
+    assert_eq!(Some("1"), sparse.iter().nth(1).unwrap());
+    assert_eq!(&DataType::Null, batch.column(2).data_type());
+    assert_eq!(10, batch.column(2).len());
+
+    // A type is inferred from the first non-NULL value of the first batch only.
+    let rows = vec![
+        vec![Field::Null],
+        vec![Field::Null],
+        vec![Field::Integer(1)],
+    ];
+    assert_eq!(
+        Err(Error::InvalidData(
+            "column c: row 3 has 1, but the column is Null".to_string()
+        )),
+        convert(&["c"], rows.clone(), 2)
+    );
+    assert_eq!(
+        &DataType::Int32,
+        convert(&["c"], rows, 3).unwrap()[0].column(0).data_type()
+    );
+}
+
+/// Batches hold up to the batch size, and only the last one holds fewer.
+#[test]
+fn test_batch_sizes() {
+    for (row_count, batch_size, sizes) in [
+        (0, 3, vec![]),
+        (1, 3, vec![1]),
+        (3, 3, vec![3]),
+        (4, 3, vec![3, 1]),
+        (6, 3, vec![3, 3]),
+        (7, 1, vec![1; 7]),
+        (5, 100, vec![5]),
+    ] {
+        let rows: Vec<_> = (0..row_count).map(|i| vec![Field::Integer(i)]).collect();
+        let batches = convert(&["id"], rows, batch_size).unwrap();
+        assert_eq!(
+            sizes,
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            "{row_count} rows in batches of {batch_size}"
+        );
+        let ids: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| column::<Int32Array>(batch, 0).values().to_vec())
+            .collect();
+        assert_eq!((0..row_count).collect::<Vec<_>>(), ids);
+    }
+    assert!(convert(&["id"], vec![], 0).is_err());
+}
+
+/// Values of another type than their column's are errors, in any batch.
+#[test]
+fn test_inconsistent_types() {
+    let rows = vec![
+        vec![Field::Integer(1)],
+        vec![Field::Float(1.5)],
+        vec![Field::Integer(3)],
+    ];
+    assert_eq!(
+        Err(Error::InvalidData(
+            "column c: row 2 has 1.5, but the column is Int32".to_string()
+        )),
+        convert(&["c"], rows.clone(), 3)
+    );
+    assert_eq!(
+        Err(Error::InvalidData(
+            "column c: row 2 has 1.5, but the column is Int32".to_string()
+        )),
+        convert(&["c"], rows.clone(), 1)
+    );
+
+    // The iterator stops after the error.
+    let mut batches = record_batches(vec![label("c")], to_rows(rows), 1).unwrap();
+    assert!(batches.next().unwrap().is_ok());
+    assert!(batches.next().unwrap().is_err());
+    assert!(batches.next().is_none());
+
+    let rows = vec![vec![Field::Integer(1), Field::Integer(2)]];
+    assert!(convert(&["c"], rows, 1).is_err());
+}
+
+fn convert(
+    columns: &[&str],
+    rows: Vec<Vec<Field>>,
+    batch_size: usize,
+) -> crate::common::Result<Vec<RecordBatch>> {
+    let columns = columns.iter().map(|name| label(name)).collect();
+    record_batches(columns, to_rows(rows), batch_size)?.collect()
+}
+
+fn to_rows(rows: Vec<Vec<Field>>) -> Rows {
+    Box::new(
+        rows.into_iter()
+            .map(|values| Ok((RecordId::new(0, 0), Row::from(values)))),
+    )
+}
+
+fn label(name: &str) -> Label {
+    Label::Unqualified(name.to_string())
+}
+
+fn column<T: 'static>(batch: &RecordBatch, i: usize) -> &T {
+    batch.column(i).as_any().downcast_ref().unwrap()
+}
@@ -0,0 +1,98 @@
+use crate::sql::engine::{dump, Catalog as _, Engine as _, Local, Transaction};
+use crate::sql::planner::Direction;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::tuple::Row;
+use crate::storage::HeapTableManager;
+
+/// Dumps a database, restores the dump into an empty one, and compares the two
+/// table by table.
+#[test]
+fn test_dump_restore() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session
+        .execute_script(
+            "CREATE TABLE accounts (
+                id INT PRIMARY KEY,
+                balance FLOAT DEFAULT 0.5,
+                active BOOLEAN NOT NULL DEFAULT TRUE,
+                code INT UNIQUE,
+                rank INT NULL INDEX
+            ) WITH (fillfactor = 70);
+            INSERT INTO accounts VALUES
+                (1, -1.5, FALSE, 10, 3),
+                (2, 0.0000001, TRUE, -2147483647 - 1, 2),
+                (3, INFINITY, TRUE, 2147483647, 1);
+            CREATE TABLE notes (id INT, body TEXT DEFAULT 'it''s empty');
+            INSERT INTO notes VALUES
+                (1, 'it''s'),
+                (2, 'semi;colon'),
+                (3, 'line
+break'),
+                (4, '\"double\" quotes'),
+                (5, 'ünïcödé ✓'),
+                (6, ''),
+                (7, '-- not a comment');
+            CREATE TABLE \"Select\" (\"Key\" INT, \"two words\" BOOL);
+            INSERT INTO \"Select\" VALUES (1, TRUE);
+            CREATE TABLE empty (id INT);
+            CREATE TABLE big (id INT, value INT)",
+        )
+        .unwrap();
+    let values: Vec<String> = (0..250).map(|id| format!("({id}, {})", id * 7)).collect();
+    session
+        .execute(&format!("INSERT INTO big VALUES {}", values.join(", ")))
+        .unwrap();
+
+    let script = dump_to_string(&engine);
+    // Rows are inserted in batches.
+    assert_eq!(3, script.matches("INSERT INTO big VALUES").count());
+    assert!(script.contains("CREATE TABLE \"Select\" (\n    \"Key\" INTEGER NOT NULL,"));
+
+    let restored = create_engine();
+    restored.session().execute_script(&script).unwrap();
+
+    let (txn, restored_txn) = (engine.begin().unwrap(), restored.begin().unwrap());
+    let tables = txn.list_tables().unwrap();
+    assert_eq!(tables, restored_txn.list_tables().unwrap());
+    assert_eq!(5, tables.len());
+    for table in tables {
+        assert_eq!(
+            rows(&txn, table.name()),
+            rows(&restored_txn, table.name()),
+            "{}",
+            table.name()
+        );
+    }
+    txn.commit().unwrap();
+    restored_txn.commit().unwrap();
+
+    // A dump of the restored database is the same script.
+    assert_eq!(script, dump_to_string(&restored));
+}
+
+fn rows(txn: &impl Transaction, table: &str) -> Vec<Row> {
+    txn.scan(table, None, Some(Direction::Ascending))
+        .unwrap()
+        .map(|row| row.unwrap().1)
+        .collect()
+}
+
+fn dump_to_string(engine: &Local<HeapTableManager>) -> String {
+    let mut script = Vec::new();
+    engine
+        .session()
+        .with_txn(|txn| dump(txn, &mut script))
+        .unwrap();
+    String::from_utf8(script).unwrap()
+}
+
+fn create_engine() -> Local<HeapTableManager> {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(50)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    Local::new(HeapTableManager::new(&bpm))
+}
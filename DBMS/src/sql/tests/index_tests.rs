@@ -0,0 +1,280 @@
+use crate::common::Error;
+use crate::sql::engine::{Engine, Local, Session, StatementResult};
+use crate::sql::parser::Parser;
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::tuple::Row;
+use crate::storage::HeapTableManager;
+use crate::types::field::Field;
+use std::ops::Bound;
+
+/// Indexed and unindexed tables with the same rows, to check index scans
+/// against full scans.
+const SETUP: &[&str] = &[
+    "CREATE TABLE indexed (id INT PRIMARY KEY, k INT INDEX, v TEXT)",
+    "CREATE TABLE plain (id INT PRIMARY KEY, k INT, v TEXT)",
+];
+
+#[test]
+fn test_index_range_scan_matches_full_scan() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate(&mut session);
+
+    for predicate in [
+        "k BETWEEN 10 AND 20",
+        "k > 10 AND k < 20",
+        "k >= 10 AND k <= 20",
+        "k > 95",
+        "k >= 95",
+        "k < 3",
+        "k <= 3",
+        "k = 42",
+        "k = 1000",
+        "20 > k",
+        "k > 50 AND k > 60 AND k <= 70 AND k < 70",
+        "k >= 10 AND k > 10 AND k < 12",
+        "k BETWEEN 30 AND 20",
+        "k BETWEEN 5 AND 8 AND v = 'even'",
+    ] {
+        let (indexed, plain) = select_both(&mut session, &format!("SELECT * WHERE {predicate}"));
+        assert_eq!(sorted(plain), sorted(indexed), "{predicate}");
+        let node = plan(&engine, &format!("SELECT * FROM indexed WHERE {predicate}"));
+        assert!(find_index_scan(&node).is_some(), "{predicate}");
+    }
+
+    // Predicates that don't bound the indexed column by constants scan the
+    // whole table.
+    for predicate in ["k > 10 OR k < 5", "v = 'odd'", "k > id", "k IS NULL"] {
+        let (indexed, plain) = select_both(&mut session, &format!("SELECT * WHERE {predicate}"));
+        assert_eq!(sorted(plain), sorted(indexed), "{predicate}");
+        let node = plan(&engine, &format!("SELECT * FROM indexed WHERE {predicate}"));
+        assert!(find_index_scan(&node).is_none(), "{predicate}");
+    }
+
+    // Changes are visible through the index.
+    session
+        .execute("UPDATE indexed SET k = 15 WHERE k = 99")
+        .unwrap();
+    session
+        .execute("UPDATE plain SET k = 15 WHERE k = 99")
+        .unwrap();
+    session.execute("DELETE FROM indexed WHERE k = 12").unwrap();
+    session.execute("DELETE FROM plain WHERE k = 12").unwrap();
+    let (indexed, plain) = select_both(&mut session, "SELECT * WHERE k BETWEEN 10 AND 99");
+    assert_eq!(sorted(plain), sorted(indexed));
+}
+
+#[test]
+fn test_index_range_scan_bounds() {
+    let engine = create_engine();
+    let node = plan(
+        &engine,
+        "SELECT * FROM indexed WHERE k > 50 AND k >= 50 AND k <= 70 AND k < 80",
+    );
+    let Some(Node::IndexRangeScan { start, end, .. }) = find_index_scan(&node) else {
+        panic!("expected an index range scan");
+    };
+    assert_eq!(
+        (&Bound::Excluded(50), &Bound::Included(70)),
+        (&constant(start), &constant(end))
+    );
+
+    let node = plan(&engine, "SELECT * FROM indexed WHERE k BETWEEN 10 AND 99");
+    let Some(Node::IndexRangeScan { start, end, .. }) = find_index_scan(&node) else {
+        panic!("expected an index range scan");
+    };
+    assert_eq!(
+        (&Bound::Included(10), &Bound::Included(99)),
+        (&constant(start), &constant(end))
+    );
+
+    let node = plan(&engine, "SELECT * FROM indexed WHERE k < 5");
+    let Some(Node::IndexRangeScan { start, end, .. }) = find_index_scan(&node) else {
+        panic!("expected an index range scan");
+    };
+    assert_eq!(
+        (&Bound::Unbounded, &Bound::Excluded(5)),
+        (&constant(start), &constant(end))
+    );
+}
+
+#[test]
+fn test_index_order_by() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate(&mut session);
+
+    // Rows with equal keys may come in any order, so only the keys are
+    // compared in order, where the query returns them.
+    for (query, key, sorted_by_index) in [
+        (
+            "SELECT * WHERE k BETWEEN 10 AND 20 ORDER BY k DESC",
+            Some(1),
+            true,
+        ),
+        (
+            "SELECT * WHERE k BETWEEN 10 AND 20 ORDER BY k",
+            Some(1),
+            true,
+        ),
+        ("SELECT k, v WHERE k > 90 ORDER BY k DESC", Some(0), true),
+        ("SELECT v WHERE k < 10 ORDER BY k DESC", None, true),
+        ("SELECT * WHERE k < 10 ORDER BY id DESC", Some(0), false),
+        ("SELECT * WHERE k < 10 ORDER BY k DESC, id", Some(1), false),
+    ] {
+        let (indexed, plain) = select_both(&mut session, query);
+        if let Some(key) = key {
+            let keys = |rows: &[Row]| -> Vec<Field> {
+                rows.iter().map(|row| row.get_field(key).unwrap()).collect()
+            };
+            assert_eq!(keys(&plain), keys(&indexed), "{query}");
+        }
+        assert_eq!(sorted(plain), sorted(indexed), "{query}");
+        let node = plan(&engine, &query.replacen(" WHERE", " FROM indexed WHERE", 1));
+        assert!(find_index_scan(&node).is_some(), "{query}");
+        assert_eq!(!sorted_by_index, has_order(&node), "{query}");
+    }
+}
+
+#[test]
+fn test_unique_index() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session
+        .execute("CREATE TABLE users (id INT PRIMARY KEY, email TEXT UNIQUE)")
+        .unwrap();
+    session
+        .execute("INSERT INTO users VALUES (1, 'a@example.com'), (2, 'b@example.com')")
+        .unwrap();
+
+    // A failed statement leaves none of its rows behind.
+    assert_eq!(
+        Err(Error::Constraint(
+            "duplicate key value violates unique index users_email_idx: 'a@example.com'"
+                .to_string()
+        )),
+        session.execute("INSERT INTO users VALUES (3, 'c@example.com'), (4, 'a@example.com')")
+    );
+    assert!(matches!(
+        session.execute("UPDATE users SET email = 'b@example.com' WHERE id = 1"),
+        Err(Error::Constraint(_))
+    ));
+    session
+        .execute("UPDATE users SET email = 'a@example.com' WHERE id = 1")
+        .unwrap();
+    session
+        .execute("DELETE FROM users WHERE email = 'b@example.com'")
+        .unwrap();
+    session
+        .execute("INSERT INTO users VALUES (3, 'b@example.com')")
+        .unwrap();
+    let StatementResult::Select { rows, .. } =
+        session.execute("SELECT id FROM users ORDER BY id").unwrap()
+    else {
+        panic!("expected a select result");
+    };
+    assert_eq!(
+        vec![
+            Row::from(vec![Field::Integer(1)]),
+            Row::from(vec![Field::Integer(3)])
+        ],
+        rows
+    );
+}
+
+fn create_engine() -> Local<HeapTableManager> {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(50)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+    for statement in SETUP {
+        session.execute(statement).unwrap();
+    }
+    drop(session);
+    engine
+}
+
+/// Inserts the same rows into both tables. Keys repeat, and some are NULL.
+fn populate(session: &mut Session<Local<HeapTableManager>>) {
+    let values = (0..300)
+        .map(|id| {
+            let k = match id % 13 {
+                0 => "NULL".to_string(),
+                _ => (id * 37 % 100).to_string(),
+            };
+            let v = ["even", "odd"][id as usize % 2];
+            format!("({id}, {k}, '{v}')")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    for table in ["indexed", "plain"] {
+        session
+            .execute(&format!("INSERT INTO {table} VALUES {values}"))
+            .unwrap();
+    }
+}
+
+/// Runs a query without a FROM clause against both tables.
+fn select_both(
+    session: &mut Session<Local<HeapTableManager>>,
+    query: &str,
+) -> (Vec<Row>, Vec<Row>) {
+    let mut select = |table| {
+        let query = query.replacen(" WHERE", &format!(" FROM {table} WHERE"), 1);
+        match session.execute(&query).unwrap() {
+            StatementResult::Select { rows, .. } => rows,
+            result => panic!("expected a select result, got {result:?}"),
+        }
+    };
+    (select("indexed"), select("plain"))
+}
+
+fn sorted(mut rows: Vec<Row>) -> Vec<Row> {
+    rows.sort_by_key(|row| row.to_string(None));
+    rows
+}
+
+/// Returns the optimized plan of a SELECT query.
+fn plan(engine: &Local<HeapTableManager>, query: &str) -> Node {
+    let txn = engine.begin().unwrap();
+    let statement = Parser::new(query).parse().unwrap();
+    match Plan::build(statement, &txn).unwrap().optimize().unwrap() {
+        Plan::Select(root) => *root.inner,
+        plan => panic!("expected a select plan, got {plan:?}"),
+    }
+}
+
+fn find_index_scan(node: &Node) -> Option<&Node> {
+    match node {
+        Node::IndexRangeScan { .. } => Some(node),
+        Node::Filter { source, .. }
+        | Node::Order { source, .. }
+        | Node::Projection { source, .. }
+        | Node::Remap { source, .. } => find_index_scan(&source.inner),
+        _ => None,
+    }
+}
+
+fn has_order(node: &Node) -> bool {
+    match node {
+        Node::Order { .. } => true,
+        Node::Filter { source, .. }
+        | Node::Projection { source, .. }
+        | Node::Remap { source, .. } => has_order(&source.inner),
+        _ => false,
+    }
+}
+
+fn constant(bound: &Bound<Expression>) -> Bound<i32> {
+    match bound {
+        Bound::Included(Expression::Constant(Field::Integer(value))) => Bound::Included(*value),
+        Bound::Excluded(Expression::Constant(Field::Integer(value))) => Bound::Excluded(*value),
+        Bound::Unbounded => Bound::Unbounded,
+        bound => panic!("unexpected bound {bound:?}"),
+    }
+}
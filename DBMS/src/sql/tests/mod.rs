@@ -1,3 +1,15 @@
+#[cfg(all(test, feature = "arrow"))]
+mod arrow_tests;
+#[cfg(test)]
+mod dump_tests;
+#[cfg(test)]
+mod index_tests;
 #[cfg(test)]
 mod lab3_student_tests;
+#[cfg(test)]
+mod pushdown_tests;
+#[cfg(test)]
+mod session_tests;
+#[cfg(all(test, feature = "trace"))]
+mod trace_tests;
 mod utility;
@@ -0,0 +1,674 @@
+use crate::common::Error;
+use crate::concurrency::IsolationLevel;
+use crate::sql::engine::{
+    Catalog as _, Engine as _, Local, Session, StatementResult, Transaction as _,
+};
+use crate::sql::planner::Expression;
+use crate::sql::tests::utility::handle;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::tuple::Row;
+use crate::storage::{Engine, HeapTableManager};
+use crate::types::field::Field;
+
+const CREATE_TABLE: &str = "CREATE TABLE test (id INT PRIMARY KEY, value INT)";
+const SELECT: &str = "SELECT * FROM test";
+
+#[test]
+fn test_set_transaction_isolation_level() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    assert_eq!(
+        IsolationLevel::SnapshotIsolation,
+        session.current_isolation()
+    );
+
+    // SET TRANSACTION applies to the next transaction only.
+    assert_eq!(
+        StatementResult::SetTransaction {
+            isolation: IsolationLevel::ReadCommitted
+        },
+        session
+            .execute("SET TRANSACTION ISOLATION LEVEL READ COMMITTED")
+            .unwrap()
+    );
+    assert_eq!(IsolationLevel::ReadCommitted, session.current_isolation());
+    assert_eq!(
+        StatementResult::Begin {
+            isolation: IsolationLevel::ReadCommitted,
+            read_only: false,
+        },
+        session.execute("BEGIN").unwrap()
+    );
+    assert_eq!(IsolationLevel::ReadCommitted, session.current_isolation());
+    assert!(matches!(
+        session.execute("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE"),
+        Err(Error::InvalidInput(_))
+    ));
+    session.execute("COMMIT").unwrap();
+    assert_eq!(
+        IsolationLevel::SnapshotIsolation,
+        session.current_isolation()
+    );
+
+    for (level, expected) in [
+        ("READ UNCOMMITTED", IsolationLevel::ReadUncommitted),
+        ("READ COMMITTED", IsolationLevel::ReadCommitted),
+        ("REPEATABLE READ", IsolationLevel::SnapshotIsolation),
+        ("SNAPSHOT", IsolationLevel::SnapshotIsolation),
+        ("SERIALIZABLE", IsolationLevel::Serializable),
+    ] {
+        session
+            .execute(&format!("BEGIN TRANSACTION ISOLATION LEVEL {level}"))
+            .unwrap();
+        assert_eq!(expected, session.current_isolation());
+        session.execute("ROLLBACK").unwrap();
+    }
+    assert!(session
+        .execute("SET TRANSACTION ISOLATION LEVEL BOGUS")
+        .is_err());
+}
+
+#[test]
+fn test_non_repeatable_read() {
+    let engine = create_engine();
+    let mut writer = engine.session();
+    writer.execute(CREATE_TABLE).unwrap();
+    writer.execute("INSERT INTO test VALUES (1, 10)").unwrap();
+
+    let mut rc = engine.session();
+    rc.execute("BEGIN ISOLATION LEVEL READ COMMITTED").unwrap();
+    let mut si = engine.session();
+    si.execute("BEGIN ISOLATION LEVEL SNAPSHOT").unwrap();
+    for session in [&mut rc, &mut si] {
+        handle(
+            session.execute(SELECT).unwrap(),
+            "test.id, test.value ; 1, 10",
+        );
+    }
+
+    writer
+        .execute("UPDATE test SET value = 20 WHERE id = 1")
+        .unwrap();
+
+    // Read committed sees the update, snapshot isolation doesn't.
+    handle(rc.execute(SELECT).unwrap(), "test.id, test.value ; 1, 20");
+    handle(si.execute(SELECT).unwrap(), "test.id, test.value ; 1, 10");
+    rc.execute("COMMIT").unwrap();
+    si.execute("COMMIT").unwrap();
+}
+
+#[test]
+fn test_checkpoint() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session.execute(CREATE_TABLE).unwrap();
+    session.execute("INSERT INTO test VALUES (1, 10)").unwrap();
+
+    let StatementResult::Checkpoint { lsn } = session.execute("CHECKPOINT").unwrap() else {
+        panic!("expected a checkpoint result");
+    };
+    let log = engine.simple.engine.lock().unwrap().log_manager();
+    assert_eq!(Some(lsn), log.last_checkpoint().unwrap());
+    handle(
+        session.execute(SELECT).unwrap(),
+        "test.id, test.value ; 1, 10",
+    );
+}
+
+#[test]
+fn test_vacuum() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session.execute(CREATE_TABLE).unwrap();
+    session
+        .execute("INSERT INTO test VALUES (1, 10), (2, 20)")
+        .unwrap();
+    session.execute("DELETE FROM test WHERE id = 1").unwrap();
+
+    let StatementResult::Vacuum(stats) = session.execute("VACUUM test").unwrap() else {
+        panic!("expected a vacuum result");
+    };
+    assert_eq!(1, stats.versions);
+    assert!(stats.bytes > 0);
+    handle(
+        session.execute(SELECT).unwrap(),
+        "test.id, test.value ; 2, 20",
+    );
+
+    // A full vacuum rewrites the table, with nothing left to remove.
+    let StatementResult::Vacuum(stats) = session.execute("VACUUM FULL test").unwrap() else {
+        panic!("expected a vacuum result");
+    };
+    assert_eq!(0, stats.versions);
+    handle(
+        session.execute(SELECT).unwrap(),
+        "test.id, test.value ; 2, 20",
+    );
+
+    assert!(matches!(
+        session.execute("VACUUM missing"),
+        Err(Error::InvalidInput(_))
+    ));
+    session.execute("BEGIN").unwrap();
+    assert!(matches!(
+        session.execute("VACUUM"),
+        Err(Error::InvalidInput(_))
+    ));
+    session.execute("ROLLBACK").unwrap();
+    assert!(matches!(
+        session.execute("VACUUM").unwrap(),
+        StatementResult::Vacuum(_)
+    ));
+}
+
+#[test]
+fn test_read_only_transaction() {
+    let engine = create_engine();
+    let mut writer = engine.session();
+    writer.execute(CREATE_TABLE).unwrap();
+    writer.execute("INSERT INTO test VALUES (1, 10)").unwrap();
+
+    let mut reader = engine.session();
+    assert_eq!(
+        StatementResult::Begin {
+            isolation: IsolationLevel::SnapshotIsolation,
+            read_only: true,
+        },
+        reader.execute("START TRANSACTION READ ONLY").unwrap()
+    );
+    for statement in [
+        "INSERT INTO test VALUES (2, 20)",
+        "UPDATE test SET value = 20",
+        "DELETE FROM test",
+        "DROP TABLE test",
+    ] {
+        assert_eq!(Err(Error::ReadOnly), reader.execute(statement));
+    }
+    writer
+        .execute("UPDATE test SET value = 20 WHERE id = 1")
+        .unwrap();
+    handle(
+        reader.execute(SELECT).unwrap(),
+        "test.id, test.value ; 1, 10",
+    );
+    reader.execute("COMMIT").unwrap();
+    handle(
+        reader.execute(SELECT).unwrap(),
+        "test.id, test.value ; 1, 20",
+    );
+
+    // The access mode and isolation level may come in either order.
+    assert_eq!(
+        StatementResult::Begin {
+            isolation: IsolationLevel::ReadCommitted,
+            read_only: true,
+        },
+        reader
+            .execute("BEGIN READ ONLY, ISOLATION LEVEL READ COMMITTED")
+            .unwrap()
+    );
+    reader.execute("ROLLBACK").unwrap();
+    assert!(matches!(
+        reader.execute("BEGIN READ WRITE").unwrap(),
+        StatementResult::Begin {
+            read_only: false,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_information_schema_transactions() {
+    let engine = create_engine();
+    let mut writer = engine.session();
+    writer.execute(CREATE_TABLE).unwrap();
+    writer
+        .execute("BEGIN ISOLATION LEVEL READ COMMITTED")
+        .unwrap();
+    writer.execute("INSERT INTO test VALUES (1, 10)").unwrap();
+
+    // The reader's own statement runs in a transaction too.
+    let mut reader = engine.session();
+    let result = reader
+        .execute(
+            "SELECT state, isolation_level, rows_written, terminated \
+             FROM information_schema.transactions WHERE rows_written > 0",
+        )
+        .unwrap();
+    let StatementResult::Select { rows, .. } = result else {
+        panic!("expected a select result");
+    };
+    assert_eq!(
+        vec![Row::from(vec![
+            Field::String("ACTIVE".to_string()),
+            Field::String("READ COMMITTED".to_string()),
+            Field::Integer(1),
+            Field::Boolean(false),
+        ])],
+        rows
+    );
+
+    // Terminating the writer rolls it back at its next statement.
+    let registry = engine.simple.transaction_manager();
+    let id = registry.list_transactions().unwrap()[0].id;
+    assert!(registry.terminate(id).unwrap());
+    assert_eq!(Err(Error::Cancelled), writer.execute(SELECT));
+    assert!(registry.list_transactions().unwrap().is_empty());
+    writer.execute("ROLLBACK").unwrap();
+    handle(reader.execute(SELECT).unwrap(), "test.id, test.value");
+}
+
+/// Scans a table of about 100 pages through a buffer pool of 4 frames, which only works if the
+/// scan holds at most one page pinned at a time.
+#[test]
+fn test_scan_larger_than_pool() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+    session
+        .execute("CREATE TABLE big (id INT, value TEXT)")
+        .unwrap();
+    let value = "x".repeat(200);
+    for batch in 0..20 {
+        let rows: Vec<String> = (0..100)
+            .map(|i| format!("({}, '{value}')", batch * 100 + i))
+            .collect();
+        session
+            .execute(&format!("INSERT INTO big VALUES {}", rows.join(", ")))
+            .unwrap();
+    }
+
+    let StatementResult::Select { rows, .. } = session
+        .execute("SELECT id FROM big WHERE value != ''")
+        .unwrap()
+    else {
+        panic!("expected a select result");
+    };
+    let ids: Vec<Row> = (0..2000)
+        .map(|id| Row::from(vec![Field::Integer(id)]))
+        .collect();
+    assert_eq!(ids, rows);
+    assert!(bpm.pinned_pages().is_empty());
+}
+
+/// Parallel scans return the same rows as serial ones, in some order.
+#[test]
+fn test_parallel_scan() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate_big(&mut session, 1000);
+
+    assert_eq!(
+        StatementResult::Set {
+            name: "parallel_scan_workers".to_string(),
+            value: Field::Integer(4),
+        },
+        session.execute("SET parallel_scan_workers = 4").unwrap()
+    );
+    let queries = [
+        "SELECT * FROM big",
+        "SELECT id FROM big WHERE id % 3 = 0",
+        "SELECT id, value FROM big WHERE id > 990 OR id < 10",
+        "SELECT * FROM big WHERE id < 0",
+    ];
+    let select_both = |session: &mut Session<_>, query: &str| {
+        session.execute("SET parallel_scan_workers = 4").unwrap();
+        let parallel = select(session, query);
+        session.execute("SET parallel_scan_workers = 1").unwrap();
+        (parallel, select(session, query))
+    };
+    for query in queries {
+        let (parallel, serial) = select_both(&mut session, query);
+        assert_eq!(sorted(serial), sorted(parallel), "{query}");
+    }
+    // A sort puts the rows back in order.
+    let (parallel, serial) = select_both(&mut session, "SELECT id FROM big ORDER BY id DESC");
+    assert_eq!(serial, parallel);
+
+    // The transaction's own writes are visible, and the versions they replaced
+    // aren't.
+    session.execute("BEGIN").unwrap();
+    session
+        .execute("UPDATE big SET value = 'changed' WHERE id % 7 = 0")
+        .unwrap();
+    session
+        .execute("DELETE FROM big WHERE id % 11 = 0")
+        .unwrap();
+    for query in queries {
+        let (parallel, serial) = select_both(&mut session, query);
+        assert_eq!(sorted(serial), sorted(parallel), "{query}");
+    }
+    session.execute("ROLLBACK").unwrap();
+}
+
+#[test]
+fn test_parallel_scan_errors() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate_big(&mut session, 200);
+    session.execute("SET parallel_scan_workers = 3").unwrap();
+
+    // An error evaluating the filter in a worker comes out of the scan. The
+    // planner doesn't push filters into scans yet, so this calls it directly.
+    let txn = engine.begin().unwrap();
+    txn.set_parallel_scan_workers(3);
+    let remainder = Expression::Remainder(
+        Expression::Column(0).into(),
+        Expression::Constant(Field::Integer(1)).into(),
+    );
+    let results: Vec<_> = txn.scan("big", Some(remainder), None).unwrap().collect();
+    assert_eq!(3, results.len());
+    for result in results {
+        assert_eq!(
+            Err(Error::InvalidInput(
+                "filter returned 0, expected boolean.".to_string()
+            )),
+            result
+        );
+    }
+    txn.rollback().unwrap();
+
+    for (statement, error) in [
+        (
+            "SET parallel_scan_workers = 0",
+            "parallel_scan_workers must be a positive integer, got Integer(0)",
+        ),
+        (
+            "SET parallel_scan_workers = 'many'",
+            "parallel_scan_workers must be a positive integer, got String(\"many\")",
+        ),
+        (
+            "SET parallel_workers = 2",
+            "unknown setting parallel_workers",
+        ),
+    ] {
+        assert_eq!(
+            Err(Error::InvalidInput(error.to_string())),
+            session.execute(statement),
+            "{statement}"
+        );
+    }
+}
+
+#[test]
+fn test_count() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate_big(&mut session, 500);
+
+    // Counts what a full scan sees, through updates, deletes, and inserts
+    // rolled back into tombstones.
+    let count = |session: &mut Session<Local<HeapTableManager>>| {
+        let rows = select(session, "SELECT * FROM big").len() as i32;
+        let count = select(session, "SELECT COUNT(*) FROM big");
+        assert_eq!(vec![Row::from(vec![Field::Integer(rows)])], count);
+        rows
+    };
+    assert_eq!(500, count(&mut session));
+    session
+        .execute("UPDATE big SET value = 'changed' WHERE id % 7 = 0")
+        .unwrap();
+    session
+        .execute("DELETE FROM big WHERE id % 11 = 0")
+        .unwrap();
+    assert_eq!(454, count(&mut session));
+    session.execute("BEGIN").unwrap();
+    session
+        .execute("INSERT INTO big VALUES (1000, 'a'), (1001, 'b')")
+        .unwrap();
+    session.execute("DELETE FROM big WHERE id < 10").unwrap();
+    assert_eq!(447, count(&mut session));
+    session.execute("ROLLBACK").unwrap();
+    assert_eq!(454, count(&mut session));
+
+    // Another transaction's uncommitted writes aren't counted.
+    let mut other = engine.session();
+    other.execute("BEGIN").unwrap();
+    other.execute("DELETE FROM big WHERE id < 100").unwrap();
+    assert_eq!(454, count(&mut session));
+    other.execute("COMMIT").unwrap();
+    assert_eq!(364, count(&mut session));
+    session.execute("VACUUM big").unwrap();
+    assert_eq!(364, count(&mut session));
+
+    // EXPLAIN shows which queries count the table without scanning it.
+    for (query, plan) in [
+        (
+            "SELECT COUNT(*) FROM big",
+            "Projection: #0\n└─ TableCount: big",
+        ),
+        (
+            "SELECT COUNT(1) FROM big b",
+            "Projection: #0\n└─ TableCount: big as b",
+        ),
+        (
+            "SELECT COUNT(*) FROM big WHERE id > 10",
+            "Projection: #0\n└─ Aggregate: count(TRUE)\n   └─ Scan: big (big.id > 10)",
+        ),
+        (
+            "SELECT COUNT(*) FROM big GROUP BY value",
+            "Projection: #1\n└─ Aggregate: big.value, count(TRUE)\n   └─ Scan: big",
+        ),
+        (
+            "SELECT COUNT(id) FROM big",
+            "Projection: #0\n└─ Aggregate: count(big.id)\n   └─ Scan: big",
+        ),
+    ] {
+        match session.execute(&format!("EXPLAIN {query}")).unwrap() {
+            StatementResult::Explain(explain) => assert_eq!(plan, explain.to_string(), "{query}"),
+            result => panic!("expected an explain result, got {result:?}"),
+        }
+    }
+    let count = select(&mut session, "SELECT COUNT(1) FROM big b");
+    assert_eq!(vec![Row::from(vec![Field::Integer(364)])], count);
+}
+
+/// ORDER BY rowid emits rows in the order the table stores them, or in
+/// reverse, straight from the table scan.
+#[test]
+fn test_order_by_rowid() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    populate_big(&mut session, 500);
+    session
+        .execute("DELETE FROM big WHERE id % 11 = 0")
+        .unwrap();
+    let ids = |ids: &mut dyn Iterator<Item = i32>| -> Vec<Row> {
+        ids.filter(|id| id % 11 != 0)
+            .map(|id| Row::from(vec![Field::Integer(id)]))
+            .collect()
+    };
+
+    // Serial and parallel scans alike.
+    for workers in [1, 4] {
+        session
+            .execute(&format!("SET parallel_scan_workers = {workers}"))
+            .unwrap();
+        let query = "SELECT id FROM big ORDER BY rowid DESC LIMIT 10";
+        assert_eq!(
+            ids(&mut (480..500).rev())[..10],
+            select(&mut session, query)
+        );
+        let query = "SELECT id FROM big WHERE id < 30 ORDER BY rowid";
+        assert_eq!(ids(&mut (0..30)), select(&mut session, query));
+        let query = "SELECT id FROM big WHERE id < 30 ORDER BY rowid DESC";
+        assert_eq!(ids(&mut (0..30).rev()), select(&mut session, query));
+    }
+
+    // The scan emits the rows in order, so there is no sort.
+    for (query, plan) in [
+        (
+            "SELECT id FROM big ORDER BY rowid DESC LIMIT 10",
+            "Limit: 10\n└─ Projection: big.id\n   └─ Scan: big by rowid desc",
+        ),
+        (
+            "SELECT * FROM big b WHERE id > 10 ORDER BY rowid",
+            "Scan: big as b by rowid asc (big.id > 10)",
+        ),
+    ] {
+        match session.execute(&format!("EXPLAIN {query}")).unwrap() {
+            StatementResult::Explain(explain) => assert_eq!(plan, explain.to_string(), "{query}"),
+            result => panic!("expected an explain result, got {result:?}"),
+        }
+    }
+
+    // rowid is only a pseudo column of single table scans.
+    for query in [
+        "SELECT * FROM big a, big b ORDER BY rowid",
+        "SELECT COUNT(*) FROM big ORDER BY rowid",
+    ] {
+        assert_eq!(
+            Err(Error::InvalidInput(
+                "ORDER BY rowid requires a single table without aggregates".to_string()
+            )),
+            session.execute(query),
+            "{query}"
+        );
+    }
+}
+
+#[test]
+fn test_create_table_fill_factor() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session.execute("CREATE TABLE packed (id INT)").unwrap();
+    session
+        .execute("CREATE TABLE sparse (id INT) WITH (fillfactor = 40 + 10)")
+        .unwrap();
+    let txn = engine.begin().unwrap();
+    assert_eq!(100, txn.must_get_table("packed").unwrap().fill_factor());
+    assert_eq!(50, txn.must_get_table("sparse").unwrap().fill_factor());
+    txn.commit().unwrap();
+
+    for query in [
+        "CREATE TABLE t (id INT) WITH (fillfactor = 5)",
+        "CREATE TABLE t (id INT) WITH (fillfactor = 101)",
+        "CREATE TABLE t (id INT) WITH (fillfactor = 'full')",
+        "CREATE TABLE t (id INT) WITH (pages = 50)",
+        "CREATE TABLE t (id INT) WITH fillfactor = 50",
+    ] {
+        assert!(session.execute(query).is_err(), "{query}");
+    }
+}
+
+#[test]
+fn test_plan_cache() {
+    let engine = create_engine();
+    let mut session = engine.session();
+    session.execute(CREATE_TABLE).unwrap();
+    session
+        .execute("INSERT INTO test VALUES (1, 10), (2, 20), (3, 30)")
+        .unwrap();
+    let stats = || engine.metrics().snapshot().plan_cache;
+    let expect = |session: &mut Session<Local<HeapTableManager>>, query: &str, hit: bool| {
+        let before = stats();
+        let rows = select(session, query);
+        let after = stats();
+        assert_eq!(
+            (hit, !hit),
+            (after.hits > before.hits, after.misses > before.misses),
+            "{query}"
+        );
+        rows
+    };
+
+    // Statements differing in whitespace, keyword case and a trailing
+    // semicolon share a plan, in any session.
+    let query = "SELECT value FROM test WHERE id = 2";
+    assert_eq!(vec![Row::from(vec![Field::Integer(20)])], expect(&mut session, query, false));
+    expect(&mut session, query, true);
+    expect(&mut engine.session(), "select  value\nFROM Test where ID = 2;", true);
+    // Literals are part of the key.
+    assert_eq!(
+        vec![Row::from(vec![Field::Integer(30)])],
+        expect(&mut session, "SELECT value FROM test WHERE id = 3", false)
+    );
+
+    // Recreating the table invalidates the plans referencing it.
+    session.execute("DROP TABLE test").unwrap();
+    session
+        .execute("CREATE TABLE test (value INT, id INT PRIMARY KEY)")
+        .unwrap();
+    session.execute("INSERT INTO test VALUES (200, 2)").unwrap();
+    assert_eq!(vec![Row::from(vec![Field::Integer(200)])], expect(&mut session, query, false));
+    expect(&mut session, query, true);
+
+    // With literals parameterized out, statements differing only in those
+    // share a plan, except for the LIMIT.
+    session.execute("INSERT INTO test VALUES (300, 3)").unwrap();
+    session.execute("SET plan_cache_parameterize = true").unwrap();
+    expect(&mut session, "SELECT value FROM test WHERE id = 2", false);
+    assert_eq!(
+        vec![Row::from(vec![Field::Integer(300)])],
+        expect(&mut session, "SELECT value FROM test WHERE id = 3", true)
+    );
+    expect(&mut session, "SELECT id FROM test WHERE value > 0 LIMIT 1", false);
+    assert_eq!(
+        2,
+        expect(&mut session, "SELECT id FROM test WHERE value > 1 LIMIT 2", false).len()
+    );
+    expect(&mut session, "SELECT id FROM test WHERE value > 2 LIMIT 2", true);
+    // Statements that can't be planned without their literals aren't cached.
+    for _ in 0..2 {
+        let before = stats();
+        let query = "SELECT value + 1 FROM test GROUP BY value + 1";
+        assert_eq!(2, select(&mut session, query).len());
+        assert_eq!(before, stats());
+    }
+
+    // The cache holds the most recently used plans.
+    session.execute("SET plan_cache_size = 1").unwrap();
+    assert_eq!(1, engine.plan_cache().len().unwrap());
+    expect(&mut session, "SELECT * FROM test WHERE id = 2", false);
+    expect(&mut session, "SELECT * FROM test WHERE id = 3", true);
+    expect(&mut session, "SELECT id FROM test", false);
+    expect(&mut session, "SELECT * FROM test WHERE id = 2", false);
+    session.execute("SET plan_cache_size = 0").unwrap();
+    assert!(engine.plan_cache().is_empty().unwrap());
+    let before = stats();
+    select(&mut session, "SELECT id FROM test");
+    assert_eq!(before, stats());
+    assert!(session.execute("SET plan_cache_size = -1").is_err());
+}
+
+fn create_engine() -> Local<HeapTableManager> {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(50)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    Local::new(HeapTableManager::new(&bpm))
+}
+
+/// Creates table big, with rows of about 200 bytes each.
+fn populate_big(session: &mut Session<Local<HeapTableManager>>, rows: i32) {
+    session
+        .execute("CREATE TABLE big (id INT, value TEXT)")
+        .unwrap();
+    let value = "x".repeat(200);
+    for batch in (0..rows).step_by(100) {
+        let values: Vec<String> = (batch..rows.min(batch + 100))
+            .map(|id| format!("({id}, '{value}')"))
+            .collect();
+        session
+            .execute(&format!("INSERT INTO big VALUES {}", values.join(", ")))
+            .unwrap();
+    }
+}
+
+fn select(session: &mut Session<Local<HeapTableManager>>, query: &str) -> Vec<Row> {
+    match session.execute(query).unwrap() {
+        StatementResult::Select { rows, .. } => rows,
+        result => panic!("expected a select result, got {result:?}"),
+    }
+}
+
+fn sorted(mut rows: Vec<Row>) -> Vec<Row> {
+    rows.sort_by_key(|row| row.to_string(None));
+    rows
+}
@@ -0,0 +1,78 @@
+use crate::common::utility::capture_trace;
+use crate::sql::engine::Local;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::HeapTableManager;
+
+/// A query produces a span for each node of its plan, with the node's table.
+#[test]
+fn test_query_spans() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(50)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+    session
+        .execute_script(
+            "CREATE TABLE t (id INT PRIMARY KEY, value INT);
+            INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)",
+        )
+        .unwrap();
+
+    let (result, trace) = capture_trace(|| session.execute("SELECT id FROM t WHERE value > 15"));
+    result.unwrap();
+    assert_eq!(1, trace.spans("execute_plan").len());
+    let scans: Vec<_> = trace
+        .spans("execute")
+        .into_iter()
+        .filter(|span| span.field("node") == Some("Scan"))
+        .collect();
+    assert_eq!(1, scans.len());
+    assert_eq!(Some("t"), scans[0].field("table"));
+    assert!(!trace.spans("fetch_page").is_empty());
+}
+
+/// Fetches record whether the page was resident, and evictions whether they wrote the page back.
+#[test]
+fn test_buffer_pool_events() {
+    let mut bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build();
+    // Page 2 takes the frame of page 0, the least recently used.
+    let mut pages = Vec::new();
+    for _ in 0..3 {
+        let page_id = bpm.new_page().unwrap();
+        bpm.unpin_page(&page_id, true).unwrap();
+        pages.push(page_id);
+    }
+
+    let (_, trace) = capture_trace(|| {
+        // A hit on page 2, then misses on page 0, evicting the dirty page 1, and on page 1,
+        // evicting the clean page 0.
+        for page_id in [pages[2], pages[0], pages[1]] {
+            assert!(bpm.fetch_page(&page_id).is_some());
+            bpm.unpin_page(&page_id, false).unwrap();
+        }
+    });
+    assert_eq!(3, trace.spans("fetch_page").len());
+    assert_eq!(1, trace.events("fetch_hit").len());
+    assert_eq!(2, trace.events("fetch_miss").len());
+    let evictions: Vec<_> = trace
+        .events("evict")
+        .iter()
+        .map(|event| (event.field("page_id"), event.field("dirty")))
+        .collect();
+    let page_id = |i: usize| pages[i].to_string();
+    assert_eq!(
+        vec![
+            (Some(page_id(1).as_str()), Some("true")),
+            (Some(page_id(0).as_str()), Some("false")),
+        ],
+        evictions
+    );
+    assert_eq!(1, trace.spans("write_page").len());
+}
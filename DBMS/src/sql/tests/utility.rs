@@ -77,13 +77,11 @@ impl<'a> SqlStudentRunner<'a> {
 /// Create a heap file based storage engine utilizing a memory buffered disk storage access.
 pub fn create_storage_engine() -> HeapTableManager {
     let disk_manager = DiskManager::new("sql-test-file");
-    let bpm = Arc::new(RwLock::new(
-        BufferPoolManager::builder()
-            .disk_manager(Arc::new(RwLock::new(disk_manager)))
-            .pool_size(500)
-            .replacer_k(5)
-            .build(),
-    ));
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(500)
+        .replacer_k(5)
+        .build_with_handle();
     HeapTableManager::new(&bpm)
 }
 
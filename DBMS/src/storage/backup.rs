@@ -0,0 +1,83 @@
+//! Online backups, see [`Simple::backup`](crate::storage::simple::Simple::backup).
+//!
+//! A backup is a directory holding a copy of the database file, the write-ahead log recovery
+//! needs to bring the copy up to the backup point, and a manifest. The catalog is only kept in
+//! memory, so the manifest records it: each table's schema along with the pages of its chain.
+//! The manifest is written last, so a directory without one holds an incomplete backup.
+use crate::common::Result;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::simple::TxnId;
+use crate::storage::wal::Lsn;
+use crate::types::Table;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+/// The name of the copy of the database file in a backup directory. Its write-ahead log lives
+/// next to it, at `data.wal`, like any database file's.
+pub const DATA_FILE: &str = "data";
+
+/// The name of the manifest in a backup directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Describes a backup, and the database it restores to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The checkpoint recovery starts from.
+    pub checkpoint_lsn: Lsn,
+    /// The newest record in the backup's log: recovery brings the database up to it.
+    pub end_lsn: Lsn,
+    /// The id the next transaction to begin is given. Tuples keep the ids of the transactions
+    /// that wrote them, which the log may no longer mention.
+    pub next_txn_id: TxnId,
+    /// Every table, in name order.
+    pub tables: Vec<ManifestTable>,
+}
+
+/// A table of a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestTable {
+    pub schema: Table,
+    /// The ids of the pages holding the table's tuples, in chain order.
+    pub page_ids: Vec<PageId>,
+}
+
+impl Manifest {
+    /// Returns whether `dir` holds a complete backup.
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(MANIFEST_FILE).is_file()
+    }
+
+    /// Reads the manifest of the backup in `dir`.
+    pub fn read(dir: &Path) -> Result<Self> {
+        let file = File::open(dir.join(MANIFEST_FILE))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Durably writes the manifest into `dir`. It is written to a temporary file that then
+    /// replaces the old one, so a crash midway leaves either manifest behind.
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(MANIFEST_FILE);
+        let temp_path = path.with_extension("json.tmp");
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&serde_json::to_vec_pretty(self)?)?;
+        temp.sync_all()?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// What [`Simple::backup`](crate::storage::simple::Simple::backup) copied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// The checkpoint the backup started with, which recovery starts from.
+    pub checkpoint_lsn: Lsn,
+    /// The newest log record copied. Opening the backup recovers the database to the point it
+    /// was logged at: transactions that committed by then are there, and no others.
+    pub end_lsn: Lsn,
+    /// Pages of the database file copied, including its header.
+    pub pages: u64,
+    /// Bytes of write-ahead log copied.
+    pub log_bytes: u64,
+}
@@ -1,24 +1,59 @@
 use crate::common::constants::NO_CORRESPONDING_FRAME_ID_MSG;
+use crate::common::sched::sched_point;
+use crate::common::{BufferPoolStats, Error, Metrics, Result};
+use crate::errdata;
 use crate::storage::buffer::lru_k_replacer::{AccessType, LRUKReplacer};
-use crate::storage::disk::disk_manager::{DiskManager, PageId};
-use crate::storage::page::{Page, TablePage, TablePageHandle};
-use std::collections::{HashMap, VecDeque};
-use std::io::Write;
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use crate::storage::buffer::replacer::{Policy, SharedReplacer};
+use crate::storage::disk::disk_manager::{DiskManager, DiskStats, Durability, PageId};
+use crate::storage::page::{
+    BPlusTreeInternalPageBuilder, BPlusTreeInternalPageHandle, BPlusTreeLeafPageBuilder,
+    BPlusTreeLeafPageHandle, Page, PageHandle, TablePage, TablePageHandle,
+};
+use crate::storage::wal::{GroupCommit, LogManager, Lsn, SyncPolicy};
+use crate::{trace_event, trace_span};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Debug};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 pub type FrameId = usize;
 
+/// Makes the replacer of a shard with the given number of frames, see
+/// [`BufferPoolManagerBuilder::replacer`].
+type NewReplacer = dyn Fn(usize) -> SharedReplacer;
+
+/// A hook called with each table page the pool is about to write back, e.g. to force the log up
+/// to the page's LSN. An error aborts the write.
+pub type PreFlushHook = Box<PreFlushFn>;
+
+type PreFlushFn = dyn Fn(&TablePage) -> Result<()> + Send + Sync;
+
+/// A [`PreFlushHook`] the pool can share with the threads writing pages back.
+#[derive(Clone)]
+struct SharedPreFlushHook(Arc<PreFlushFn>);
+
+impl Debug for SharedPreFlushHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PreFlushHook")
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct FrameMetadata {
     frame_id: FrameId,
     pin_count: usize,
+    /// The frame's generation when the page was placed in it, see [`Frames::generations`].
+    generation: u64,
 }
 
 impl FrameMetadata {
-    pub fn new(frame_id: FrameId) -> Self {
+    pub fn new(frame_id: FrameId, generation: u64) -> Self {
         Self {
             frame_id,
             pin_count: 0,
+            generation,
         }
     }
 
@@ -29,11 +64,12 @@ impl FrameMetadata {
     pub fn increment_pin_count(&mut self) {
         self.pin_count += 1;
     }
-    pub fn decrement_pin_count(&mut self) {
+    pub fn decrement_pin_count(&mut self) -> Result<()> {
         if self.pin_count == 0 {
-            panic!("Pin count already at zero, cannot decrement.");
+            return errdata!("frame {} is not pinned", self.frame_id);
         }
         self.pin_count -= 1;
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -42,27 +78,217 @@ impl FrameMetadata {
     }
 }
 
+/// A table page handle, along with the frame it was fetched from and the frame's generation at
+/// the time, so that it can tell once the page has left the frame. A plain [`TablePageHandle`]
+/// kept past its unpin still reads the old page after it is evicted, but writes through it are
+/// lost, since the pool no longer tracks it.
+#[derive(Clone, Debug)]
+pub struct VersionedPageHandle {
+    page: TablePageHandle,
+    page_id: PageId,
+    frame: FrameId,
+    generation: u64,
+}
+
+impl VersionedPageHandle {
+    pub fn page(&self) -> &TablePageHandle {
+        &self.page
+    }
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+    pub fn frame(&self) -> FrameId {
+        self.frame
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns whether the page is still in the frame it was fetched from, see
+    /// [`BufferPoolManager::validate_handle`].
+    pub fn is_valid(&self, bpm: &BufferPoolManager) -> bool {
+        bpm.validate_handle(self)
+    }
+}
+
+/// The dirty page table at a checkpoint, split by whether the pages were written back.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckpointInfo {
+    /// The dirty pages that were written back, with their LSNs, ordered by page ID.
+    pub flushed: Vec<(PageId, Lsn)>,
+    /// The dirty pages that were pinned, and left dirty, with their LSNs, ordered by page ID.
+    pub skipped: Vec<(PageId, Lsn)>,
+}
+
+/// The frames of a buffer pool and the pages they hold, latched together by the pool.
+#[derive(Debug)]
+pub(crate) struct Frames {
+    /// The page held by each frame, of any page type, indexed by frame ID. Has a slot for every
+    /// frame of the pool, empty while the frame is free.
+    pub(crate) pages: Vec<Option<PageHandle>>,
+    /// HashMap that maps page IDs to frame IDs (offsets in `page`).
+    pub(crate) page_table: HashMap<PageId, FrameMetadata>,
+    /// List of free frames that don't have any page on them.
+    pub(crate) free_list: VecDeque<FrameId>,
+    /// The pins and unpins of each frame over the pool's lifetime, indexed by frame ID.
+    pub(crate) frame_pins: Vec<FramePins>,
+    /// The generation of each frame, indexed by frame ID, bumped whenever its page is evicted or
+    /// deleted, so that handles fetched before can tell they are stale.
+    pub(crate) generations: Vec<u64>,
+}
+
+impl Frames {
+    /// Returns the page held by a frame, if it isn't free.
+    pub(crate) fn frame(&self, frame_id: FrameId) -> Option<&PageHandle> {
+        self.pages.get(frame_id)?.as_ref()
+    }
+
+    /// Pins a resident page, counting the pin against its frame.
+    fn pin(&mut self, page_id: &PageId) {
+        let frame_metadata = self.page_table.get_mut(page_id).unwrap();
+        frame_metadata.increment_pin_count();
+        self.frame_pins[frame_metadata.frame_id].pins += 1;
+    }
+
+    /// Unpins a resident page, counting the unpin against its frame.
+    ///
+    /// # Returns
+    /// - The page's frame, and what its pin count is now.
+    ///
+    /// # Errors
+    /// - [`Error::PageNotInPool`]: If the page is not in the buffer pool.
+    /// - [`Error::PageNotPinned`]: If the page's pin count was already zero.
+    fn unpin(&mut self, page_id: &PageId) -> Result<(FrameId, usize)> {
+        let frame_metadata =
+            (self.page_table.get_mut(page_id)).ok_or(Error::PageNotInPool(*page_id))?;
+        if frame_metadata.pin_count == 0 {
+            return Err(Error::PageNotPinned(*page_id));
+        }
+        frame_metadata.decrement_pin_count()?;
+        self.frame_pins[frame_metadata.frame_id].unpins += 1;
+        Ok((frame_metadata.frame_id, frame_metadata.pin_count))
+    }
+}
+
+/// How many times a frame was pinned and unpinned, whichever pages it held. Once its page is
+/// unpinned, the two are equal, unless the page was deleted or evicted in between.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FramePins {
+    pub(crate) pins: u64,
+    pub(crate) unpins: u64,
+}
+
+/// A partition of the buffer pool, with frames, a page table, a free list and a replacer of its
+/// own. Frame IDs are the shard's own, from 0 to its size.
+#[derive(Debug)]
+pub(crate) struct Shard {
+    /// Number of frames in the shard.
+    pub(crate) pool_size: usize,
+    /// The frames, page table and free list, locked with [`Self::frames`].
+    pub(crate) frames: Mutex<Frames>,
+    /// Replacer to find unpinned page for replacement. Only locked while holding the frames.
+    pub(crate) replacer: SharedReplacer,
+}
+
+impl Shard {
+    fn new(pool_size: usize, replacer: SharedReplacer) -> Self {
+        Self {
+            pool_size,
+            frames: Mutex::new(Frames {
+                pages: vec![None; pool_size],
+                page_table: HashMap::new(),
+                free_list: (0..pool_size).collect(),
+                frame_pins: vec![FramePins::default(); pool_size],
+                generations: vec![0; pool_size],
+            }),
+            replacer,
+        }
+    }
+
+    /// Locks the frames, page table and free list. A simulated thread yields while another one
+    /// holds them, rather than block.
+    pub(crate) fn frames(&self) -> MutexGuard<'_, Frames> {
+        #[cfg(test)]
+        if crate::common::sched::is_simulated() {
+            use std::sync::TryLockError;
+            loop {
+                match self.frames.try_lock() {
+                    Ok(frames) => return frames,
+                    Err(TryLockError::WouldBlock) => {
+                        crate::common::sched::yield_point("bpm.frames")
+                    }
+                    Err(TryLockError::Poisoned(error)) => panic!("{error}"),
+                }
+            }
+        }
+        self.frames.lock().unwrap()
+    }
+
+    /// Records an access to the frame of a page just pinned, and keeps the frame from being
+    /// evicted.
+    fn pin_frame(&self, frame_id: &FrameId, access_type: AccessType) {
+        let mut replacer = self.replacer.write().unwrap();
+        log_replacer_error(
+            (replacer.record_access(frame_id, access_type))
+                .and_then(|()| replacer.set_evictable(frame_id, false)),
+        );
+    }
+}
+
+/// Reports an error of a shard's replacer, where the pool can't pass it on. The pool only gives
+/// the replacer frames of the shard, which it tracks from their first access, so an error means
+/// the two disagree: it is logged, rather than panic with the shard locked.
+fn log_replacer_error(result: Result<()>) {
+    if let Err(error) = result {
+        log::error!("buffer pool replacer: {error}");
+    }
+}
+
+/// A buffer pool, shared between threads as is: every operation locks the frames of the shard
+/// owning its page for as long as it takes, and the pages themselves are latched by their own
+/// `RwLock`s.
+///
+/// The pool has a single shard unless built with [`BufferPoolManagerBuilder::shards`], which
+/// splits its frames between shards, each page going to shard `page_id % shards`. Operations on
+/// pages of different shards then don't wait on each other, but a page can only evict the pages
+/// of its own shard.
 #[derive(Debug)]
 pub struct BufferPoolManager {
     /// Number of page in the buffer pool.
     pub(crate) pool_size: usize,
-    /// Array of buffer pool page.
-    pub(crate) pages: Vec<TablePageHandle>,
-    /// HashMap that maps page IDs to frame IDs (offsets in `page`).
-    pub(crate) page_table: HashMap<PageId, FrameMetadata>,
+    /// The partitions of the pool's frames. Operations touching more than one lock them in order.
+    pub(crate) shards: Vec<Shard>,
     /// Manages reads and writes of page on disk.
     pub(crate) disk_manager: Arc<RwLock<DiskManager>>,
-    /// Replacer to find unpinned page for replacement.
-    pub(crate) replacer: Arc<RwLock<LRUKReplacer>>,
-    /// List of free frames that don't have any page on them.
-    pub(crate) free_list: VecDeque<FrameId>,
+    /// Write-ahead log that must be flushed up to a page's LSN before the page is written.
+    pub(crate) log_manager: Arc<LogManager>,
+    /// Counts page fetches, evictions and flushes.
+    pub(crate) metrics: Arc<Metrics>,
+    /// The buffer pool counters as of the last [`Self::reset_stats`], which [`Self::stats`]
+    /// counts from.
+    stats_baseline: Mutex<BufferPoolStats>,
+    /// The thread writing dirty pages back in the background, if started by
+    /// [`Self::start_background_flusher`].
+    flusher: Mutex<Option<BackgroundFlusher>>,
+    /// Whether dropping the pool with pages still pinned panics, in debug builds, rather than
+    /// only log a warning.
+    panic_on_pin_leak: bool,
+    /// Called before each table page is written back, see [`Self::set_pre_flush_hook`].
+    pre_flush_hook: RwLock<Option<SharedPreFlushHook>>,
 }
 
 #[derive(Default)]
 pub struct BufferPoolManagerBuilder {
     pool_size: Option<usize>,
     replacer_k: Option<usize>,
+    shards: Option<usize>,
     disk_manager: Option<Arc<RwLock<DiskManager>>>,
+    sync_policy: Option<SyncPolicy>,
+    group_commit: Option<GroupCommit>,
+    metrics: Option<Arc<Metrics>>,
+    panic_on_pin_leak: bool,
+    pre_flush_hook: Option<SharedPreFlushHook>,
+    replacer: Option<Box<NewReplacer>>,
 }
 
 impl BufferPoolManagerBuilder {
@@ -74,27 +300,108 @@ impl BufferPoolManagerBuilder {
         self.replacer_k = Some(replacer_k);
         self
     }
+    /// Gives each shard the replacer `new_replacer` makes for the shard's number of frames,
+    /// rather than an LRU-K replacer, in which case `replacer_k` needn't be set. E.g. for plain
+    /// LRU, `|frames| Arc::new(RwLock::new(LRUReplacer::new(frames)))`.
+    pub fn replacer(
+        &mut self,
+        new_replacer: impl Fn(usize) -> SharedReplacer + 'static,
+    ) -> &mut Self {
+        self.replacer = Some(Box::new(new_replacer));
+        self
+    }
+    /// Gives each shard a replacer of the given policy, as [`Self::replacer`] does. With
+    /// `Policy::LruK { k }`, `k` takes the place of `replacer_k`.
+    pub fn replacement_policy(&mut self, policy: Policy) -> &mut Self {
+        self.replacer(move |frames| policy.new_replacer(frames))
+    }
+    /// Splits the pool's frames between `shards` shards, each with a page table and replacer of
+    /// its own, so that threads working on pages of different shards don't contend for a lock.
+    /// Defaults to a single shard; there can't be more shards than frames.
+    pub fn shards(&mut self, shards: usize) -> &mut Self {
+        self.shards = Some(shards);
+        self
+    }
     pub fn disk_manager(&mut self, disk_manager: Arc<RwLock<DiskManager>>) -> &mut Self {
         self.disk_manager = Some(disk_manager);
         self
     }
+    pub fn sync_policy(&mut self, sync_policy: SyncPolicy) -> &mut Self {
+        self.sync_policy = Some(sync_policy);
+        self
+    }
+    pub fn group_commit(&mut self, group_commit: GroupCommit) -> &mut Self {
+        self.group_commit = Some(group_commit);
+        self
+    }
+    /// Counts the buffer pool's activity, and that of its replacer and disk manager, in the
+    /// given registry.
+    pub fn metrics(&mut self, metrics: Arc<Metrics>) -> &mut Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    /// Makes dropping the pool panic if any page is still pinned, rather than only log a warning
+    /// with [`BufferPoolManager::pin_leak_report`]. Only in debug builds; release builds always
+    /// just log.
+    pub fn panic_on_pin_leak(&mut self, panic_on_pin_leak: bool) -> &mut Self {
+        self.panic_on_pin_leak = panic_on_pin_leak;
+        self
+    }
+    /// Calls `hook` before each table page is written back, see
+    /// [`BufferPoolManager::set_pre_flush_hook`].
+    pub fn pre_flush_hook(&mut self, hook: PreFlushHook) -> &mut Self {
+        self.pre_flush_hook = Some(SharedPreFlushHook(Arc::from(hook)));
+        self
+    }
     pub fn build(&self) -> BufferPoolManager {
         let pool_size = self
             .pool_size
             .expect("`pool_size` not initialized before build.");
-        let replacer_k = self
-            .replacer_k
-            .expect("`replacer_k` not initialized before build.");
         let disk_manager = self
             .disk_manager
             .clone()
             .expect("`disk_manager` not initialized before build.");
 
-        BufferPoolManager::new(pool_size, replacer_k, disk_manager)
+        let shards = self.shards.unwrap_or(1);
+        assert!(
+            shards == 1 || (1..=pool_size).contains(&shards),
+            "`shards` must be between 1 and `pool_size`."
+        );
+
+        let disk = Arc::clone(&disk_manager);
+        let mut bpm = match &self.replacer {
+            Some(new_replacer) => {
+                BufferPoolManager::with_shards(pool_size, new_replacer, disk, shards)
+            }
+            None => {
+                let replacer_k = self
+                    .replacer_k
+                    .expect("`replacer_k` not initialized before build.");
+                let new_replacer = BufferPoolManager::new_lru_k_replacer(replacer_k);
+                BufferPoolManager::with_shards(pool_size, &new_replacer, disk, shards)
+            }
+        };
+        bpm.panic_on_pin_leak = self.panic_on_pin_leak;
+        bpm.pre_flush_hook = RwLock::new(self.pre_flush_hook.clone());
+        if self.sync_policy.is_some() || self.group_commit.is_some() {
+            bpm.log_manager = Arc::new(
+                LogManager::new(disk_manager, self.sync_policy.unwrap_or_default())
+                    .with_group_commit(self.group_commit.unwrap_or_default()),
+            );
+        }
+        if let Some(metrics) = &self.metrics {
+            for shard in &bpm.shards {
+                shard.replacer.write().unwrap().set_metrics(Arc::clone(metrics));
+            }
+            bpm.disk_manager.write().unwrap().set_metrics(Arc::clone(metrics));
+            bpm.metrics = Arc::clone(metrics);
+            bpm.stats_baseline = Mutex::new(metrics.snapshot().buffer_pool);
+        }
+        bpm
     }
 
-    pub fn build_with_handle(&self) -> Arc<RwLock<BufferPoolManager>> {
-        Arc::new(RwLock::new(self.build()))
+    pub fn build_with_handle(&self) -> Arc<BufferPoolManager> {
+        Arc::new(self.build())
     }
 }
 
@@ -103,14 +410,42 @@ impl BufferPoolManager {
         pool_size: usize,
         replacer_k: usize,
         disk_manager: Arc<RwLock<DiskManager>>,
+    ) -> Self {
+        let new_replacer = Self::new_lru_k_replacer(replacer_k);
+        Self::with_shards(pool_size, &new_replacer, disk_manager, 1)
+    }
+
+    /// Makes LRU-K replacers, the pool's default.
+    fn new_lru_k_replacer(replacer_k: usize) -> impl Fn(usize) -> SharedReplacer {
+        move |frames| Arc::new(RwLock::new(LRUKReplacer::new(frames, replacer_k)))
+    }
+
+    /// Splits `pool_size` frames evenly between `shards` shards, each with a replacer made by
+    /// `new_replacer`.
+    fn with_shards(
+        pool_size: usize,
+        new_replacer: &NewReplacer,
+        disk_manager: Arc<RwLock<DiskManager>>,
+        shards: usize,
     ) -> Self {
         BufferPoolManager {
             pool_size,
-            pages: Vec::with_capacity(pool_size),
-            page_table: HashMap::new(),
+            shards: (0..shards)
+                .map(|shard| {
+                    let frames = pool_size / shards + usize::from(shard < pool_size % shards);
+                    Shard::new(frames, new_replacer(frames))
+                })
+                .collect(),
+            log_manager: Arc::new(LogManager::new(
+                Arc::clone(&disk_manager),
+                SyncPolicy::default(),
+            )),
             disk_manager,
-            replacer: Arc::new(RwLock::new(LRUKReplacer::new(pool_size, replacer_k))),
-            free_list: (0..pool_size).collect(),
+            metrics: Arc::default(),
+            stats_baseline: Mutex::default(),
+            flusher: Mutex::default(),
+            panic_on_pin_leak: false,
+            pre_flush_hook: RwLock::default(),
             // Initialize other fields here
         }
     }
@@ -119,72 +454,178 @@ impl BufferPoolManager {
         pool_size: usize,
         replacer_k: usize,
         disk_manager: Arc<RwLock<DiskManager>>,
-    ) -> Arc<RwLock<Self>> {
-        Arc::new(RwLock::new(Self::new(pool_size, replacer_k, disk_manager)))
+    ) -> Arc<Self> {
+        Arc::new(Self::new(pool_size, replacer_k, disk_manager))
     }
 
     pub fn builder() -> BufferPoolManagerBuilder {
         BufferPoolManagerBuilder::default()
     }
 
+    /// Returns the index of the shard owning a page.
+    pub(crate) fn shard_index(&self, page_id: &PageId) -> usize {
+        *page_id as usize % self.shards.len()
+    }
+
+    /// Returns the shard owning a page.
+    pub(crate) fn shard(&self, page_id: &PageId) -> &Shard {
+        &self.shards[self.shard_index(page_id)]
+    }
+
+    /// Groups the positions of `page_ids` by the shard owning the page, for the shards owning
+    /// any.
+    fn route(&self, page_ids: &[PageId]) -> Vec<(&Shard, Vec<usize>)> {
+        let mut positions = vec![Vec::new(); self.shards.len()];
+        for (position, page_id) in page_ids.iter().enumerate() {
+            positions[self.shard_index(page_id)].push(position);
+        }
+        (self.shards.iter().zip(positions))
+            .filter(|(_, positions)| !positions.is_empty())
+            .collect()
+    }
+
     /// Creates a new page in the buffer pool.
     ///
-    /// This method allocates a new page and returns its identifier. If all
-    /// frames are in use and cannot be evicted, it returns `None`.
+    /// This method allocates a new page and returns its identifier.
     ///
     /// The frame should be pinned to prevent eviction, and its access history
     /// recorded.
     ///
     /// # Returns
-    /// - `Some(PageId)`: The identifier of the newly created page if successful.
-    /// - `None`: If no new page could be created due to all frames being in use.
-    pub fn new_page(&mut self) -> Option<PageId> {
-        if let Some(frame_id) = self.free_list.pop_front() {
-            let mut disk_binding = self.disk_manager.write().unwrap();
-            let new_page_id = disk_binding.allocate_new_page();
-            let new_page = disk_binding.read_page(&new_page_id);
-            let new_page_handle = Arc::new(RwLock::new(new_page));
-
-            self.pages.insert(frame_id, new_page_handle);
-
-            let mut frame_metadata = FrameMetadata::new(frame_id);
-            frame_metadata.increment_pin_count();
-            self.page_table.insert(new_page_id, frame_metadata);
-
-            let mut replacer = self.replacer.write().unwrap();
-            replacer.record_access(&frame_id, AccessType::Lookup);
-            replacer.set_evictable(&frame_id, false);
-
-
-            Some(new_page_id)
-        } else {
-            let mut replacer = self.replacer.write().unwrap();
-            let evicted_frame_id = replacer.evict()?;
-
-            drop(replacer);
-
-            // Flush the evicted page if it is dirty
-            let evict_page_id = self.pages.get(evicted_frame_id).unwrap().read().unwrap().page_id;
-            let is_dirty = self.pages.get(evicted_frame_id).unwrap().read().unwrap().is_dirty;
-            if is_dirty {
-                self.flush_page(&evict_page_id);
-            }
+    /// - The identifier of the newly created page.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If no frame is free, and none can be evicted.
+    /// - [`Error::ChecksumMismatch`]: If the page allocated doesn't read back as written.
+    pub fn new_page(&self) -> Result<PageId> {
+        sched_point!("bpm.new_page");
+        let (new_page_id, shard, mut frames, frame_id) = self.allocate_page()?;
+        let new_page = self.read_into_frame(&mut frames, frame_id, &new_page_id)?;
+        let new_page_handle = Arc::new(RwLock::new(new_page));
+
+        self.install_frame(
+            shard,
+            &mut frames,
+            frame_id,
+            new_page_id,
+            new_page_handle.into(),
+            AccessType::Lookup,
+        );
+        self.metrics.buffer_pool_pages_created.incr();
+        Ok(new_page_id)
+    }
+
+    /// Creates a new B+tree leaf page in the buffer pool, built by `builder` with the new page's
+    /// id. Like [`Self::new_page`], the page is pinned. The page starts out dirty, so it reaches
+    /// disk even if it is never changed.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If no frame is free, and none can be evicted.
+    /// - [`Error::IO`]: If the page can't be allocated on disk.
+    pub fn new_leaf_page(
+        &self,
+        builder: &mut BPlusTreeLeafPageBuilder,
+    ) -> Result<BPlusTreeLeafPageHandle> {
+        let page = self.new_page_handle(|page_id| {
+            Arc::new(RwLock::new(builder.page_id(page_id).build())).into()
+        })?;
+        let page_id = page.page_id();
+        page.as_leaf().ok_or_else(|| errdata!("page {page_id} is not a leaf page"))
+    }
 
-            // Read the new page from disk
-            let mut disk_binding = self.disk_manager.write().unwrap();
-            let new_page = disk_binding.read_page(&evict_page_id);
-            let new_page_handle = Arc::new(RwLock::new(new_page));
+    /// Creates a new B+tree internal page in the buffer pool, see [`Self::new_leaf_page`].
+    pub fn new_internal_page(
+        &self,
+        builder: &mut BPlusTreeInternalPageBuilder,
+    ) -> Result<BPlusTreeInternalPageHandle> {
+        let page = self.new_page_handle(|page_id| {
+            Arc::new(RwLock::new(builder.page_id(page_id).build())).into()
+        })?;
+        let page_id = page.page_id();
+        page.as_internal().ok_or_else(|| errdata!("page {page_id} is not an internal page"))
+    }
 
-            // Update the page table with the new page
-            self.page_table.insert(evict_page_id, FrameMetadata::new(evicted_frame_id));
-            self.pages.insert(evicted_frame_id, new_page_handle.clone());
+    /// Creates a new page of any type in the buffer pool, built by `build` with the new page's
+    /// id. Like [`Self::new_page`], the page is pinned, and it starts out dirty like with
+    /// [`Self::new_leaf_page`].
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If no frame is free, and none can be evicted.
+    /// - [`Error::IO`]: If the page can't be allocated on disk.
+    pub fn new_page_as<P>(&self, build: impl FnOnce(PageId) -> P) -> Result<Arc<RwLock<P>>>
+    where
+        P: Page<ConcretePageType = P> + Debug + Send + Sync + 'static,
+    {
+        let page = self.new_page_handle(|page_id| PageHandle::new(build(page_id)))?;
+        let page_id = page.page_id();
+        page.as_page().ok_or_else(|| errdata!("page {page_id} is not of the type it was built as"))
+    }
 
-            self.free_list.push_back(evicted_frame_id);
+    fn new_page_handle(&self, build: impl FnOnce(PageId) -> PageHandle) -> Result<PageHandle> {
+        let (page_id, shard, mut frames, frame_id) = self.allocate_page()?;
+        let page_handle = build(page_id);
+        page_handle.set_is_dirty(true);
+        self.install_frame(
+            shard,
+            &mut frames,
+            frame_id,
+            page_id,
+            page_handle.clone(),
+            AccessType::Lookup,
+        );
+        self.metrics.buffer_pool_pages_created.incr();
+        Ok(page_handle)
+    }
 
-            drop(disk_binding);
+    /// Allocates a page on disk, and claims a frame for it in the shard owning it, which is left
+    /// locked. With a single shard, the frame is claimed first, so that no page is allocated if
+    /// there is no frame for it. Otherwise, the shard is only known once the page is allocated,
+    /// and the page is deallocated again if its shard has no frame to spare.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If the shard has no frame to spare.
+    /// - [`Error::IO`]: If the page can't be allocated, or deallocated again.
+    fn allocate_page(&self) -> Result<(PageId, &Shard, MutexGuard<'_, Frames>, FrameId)> {
+        if let [shard] = self.shards.as_slice() {
+            let mut frames = shard.frames();
+            let frame_id = (self.claim_frame(shard, &mut frames))
+                .ok_or_else(|| Self::no_evictable_frame(shard, &frames))?;
+            let page_id = match self.disk_manager.write().unwrap().allocate_new_page() {
+                Ok(page_id) => page_id,
+                Err(error) => {
+                    frames.free_list.push_back(frame_id);
+                    return Err(error);
+                }
+            };
+            return Ok((page_id, shard, frames, frame_id));
+        }
+        let page_id = self.disk_manager.write().unwrap().allocate_new_page()?;
+        let shard = self.shard(&page_id);
+        let mut frames = shard.frames();
+        let Some(frame_id) = self.claim_frame(shard, &mut frames) else {
+            self.disk_manager.write().unwrap().deallocate_page(&page_id)?;
+            return Err(Self::no_evictable_frame(shard, &frames));
+        };
+        Ok((page_id, shard, frames, frame_id))
+    }
 
-            self.page_table.remove(&evict_page_id);
-            self.new_page()
+    /// Describes a shard whose frames are all taken, for a page that found none to claim: how
+    /// many of them are pinned, and in debug builds, by which pages. A frame may also be kept
+    /// from being claimed by its page failing to be written back.
+    fn no_evictable_frame(shard: &Shard, frames: &Frames) -> Error {
+        let mut pinned_pages: Vec<PageId> = (frames.page_table.iter())
+            .filter(|(_, frame_metadata)| frame_metadata.pin_count > 0)
+            .map(|(page_id, _)| *page_id)
+            .collect();
+        pinned_pages.sort_unstable();
+        let pinned = pinned_pages.len();
+        if !cfg!(debug_assertions) {
+            pinned_pages.clear();
+        }
+        Error::NoEvictableFrame {
+            pinned,
+            pool_size: shard.pool_size,
+            pinned_pages,
         }
     }
 
@@ -192,7 +633,7 @@ impl BufferPoolManager {
     ///
     /// This method attempts to retrieve the page identified by `page_id` from
     /// the buffer pool. If the page is not in the pool and all frames are
-    /// currently in use and non-evictable (i.e., pinned), it returns `None`.
+    /// currently in use and non-evictable (i.e., pinned), it returns an error.
     ///
     /// The function first searches for the `page_id` in the buffer pool. If
     /// the page is not found, it selects a frame from the free list or, if
@@ -200,137 +641,578 @@ impl BufferPoolManager {
     /// the buffer pool.
     ///
     /// Additionally, eviction is disabled for the frame, and its access history
-    /// is recorded similarly to `NewPage`.
-    ///
-    /// Note: it is undefined behavior to call `fetch_page` on a `page_id` that
-    /// does not exist in the page.
+    /// is recorded similarly to `NewPage`. A page that fails to be read from
+    /// disk leaves the frame it was to be read into free.
     ///
     /// # Parameters
     /// - `page_id`: The identifier of the page to be fetched.
     ///
     /// # Returns
-    /// - `Some(&mut TablePage)`: A mutable reference to the page if it is
-    ///   successfully fetched.
-    /// - `None`: If the `page_id` cannot be fetched due to all frames being
-    ///   in use and non-evictable.
-    pub fn fetch_page(&mut self, page_id: &PageId) -> Option<TablePageHandle> {
+    /// - A handle to the page, pinned.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If the page isn't resident, and no frame is free, or can be
+    ///   evicted, to read it into.
+    /// - [`Error::InvalidData`]: If the page resident isn't a table page.
+    /// - [`Error::PageNotAllocated`]: If the page isn't resident, and was never allocated on
+    ///   disk, or was deallocated since.
+    /// - [`Error::ChecksumMismatch`]: If the page read from disk isn't what was written to it.
+    pub fn fetch_page(&self, page_id: &PageId) -> Result<TablePageHandle> {
+        self.fetch_page_with_access(page_id, AccessType::Lookup)
+    }
 
-        // Check Buffer Pool
-        if let Some(frame_metadata) = self.page_table.get(page_id).copied() {
-            let frame_id = frame_metadata.frame_id();
+    /// Fetches a page to modify it: like [`Self::fetch_page`], but marks the page dirty right
+    /// away, so that the changes are written back even if the page is unpinned with `is_dirty`
+    /// unset.
+    pub fn fetch_page_write(&self, page_id: &PageId) -> Result<TablePageHandle> {
+        let page_handle = self.fetch_page(page_id)?;
+        // Pinned by the fetch, so the page stays resident.
+        self.set_is_dirty(page_id, true);
+        Ok(page_handle)
+    }
+
+    /// Fetches a page to read it: like [`Self::fetch_page`], leaving the dirty flag alone.
+    pub fn fetch_page_read(&self, page_id: &PageId) -> Result<TablePageHandle> {
+        self.fetch_page(page_id)
+    }
 
-            let mut replacer = self.replacer.write().unwrap();
-            replacer.record_access(&frame_id, AccessType::Lookup);
-            replacer.set_evictable(&frame_id, false);
+    /// Fetches a page like [`Self::fetch_page`], with a handle that remembers the frame it came
+    /// from, and can be checked with [`Self::validate_handle`] before it is written through.
+    pub fn fetch_page_versioned(&self, page_id: &PageId) -> Result<VersionedPageHandle> {
+        let page = self.fetch_page(page_id)?;
+        // Pinned by the fetch, so the page is still in the same frame.
+        let frames = self.shard(page_id).frames();
+        let frame_metadata = frames.page_table[page_id];
+        Ok(VersionedPageHandle {
+            page,
+            page_id: *page_id,
+            frame: frame_metadata.frame_id,
+            generation: frame_metadata.generation,
+        })
+    }
 
-            drop(replacer);
+    /// Returns whether a handle's page is still in the frame it was fetched from, i.e. whether
+    /// the frame has neither been evicted nor had its page deleted since. A stale handle still
+    /// reads the page as it was, but nothing written through it reaches disk.
+    pub fn validate_handle(&self, handle: &VersionedPageHandle) -> bool {
+        let frames = self.shard(&handle.page_id).frames();
+        frames.page_table.get(&handle.page_id).is_some_and(|frame_metadata| {
+            frame_metadata.frame_id == handle.frame
+                && frame_metadata.generation == handle.generation
+        })
+    }
 
-            {
-                self.page_table.get_mut(page_id).unwrap().increment_pin_count();
+    /// Like [`Self::fetch_page`], but records the access as the given type, e.g. `Scan` for the
+    /// pages of a sequential scan.
+    pub fn fetch_page_with_access(
+        &self,
+        page_id: &PageId,
+        access_type: AccessType,
+    ) -> Result<TablePageHandle> {
+        trace_span!("fetch_page", page_id);
+        sched_point!("bpm.fetch_page");
+        let shard = self.shard(page_id);
+        let mut frames = shard.frames();
+        // Check Buffer Pool
+        if frames.page_table.contains_key(page_id) {
+            return match self.pin_resident(shard, &mut frames, page_id, access_type) {
+                Some(page_handle) => Ok(page_handle),
+                None => errdata!("page {page_id} is not a table page"),
+            };
+        }
+        trace_event!(name: "fetch_miss", page_id);
+        self.metrics.buffer_pool_misses.incr();
+
+        // Take a free frame, or evict a page to free one up
+        let frame_id = (self.claim_frame(shard, &mut frames))
+            .ok_or_else(|| Self::no_evictable_frame(shard, &frames))?;
+        let new_page = self.read_into_frame(&mut frames, frame_id, page_id)?;
+        let new_page_handle = Arc::new(RwLock::new(new_page));
+
+        // Put the page in the frame, pinned like any fetched page
+        let page_handle = new_page_handle.clone().into();
+        self.install_frame(shard, &mut frames, frame_id, *page_id, page_handle, access_type);
+        Ok(new_page_handle)
+    }
+
+    /// Fetches the pages of a sequential scan, like [`Self::fetch_page`] does one at a time, with
+    /// their accesses recorded as a `Scan`. The resident pages are pinned first, then the rest are
+    /// read from disk together, with the disk manager locked once, and a single read for each run
+    /// of consecutive pages. A sharded pool does so one shard at a time.
+    ///
+    /// # Returns
+    /// - A handle for each page, in the order asked for, or `None` for the pages that have no
+    ///   frame to be read into, fail their checksum, or aren't table pages. Only the pages
+    ///   returned are pinned, once for each time they are asked for.
+    pub fn fetch_pages(&self, page_ids: &[PageId]) -> Vec<Option<TablePageHandle>> {
+        trace_span!("fetch_pages", pages = page_ids.len());
+        sched_point!("bpm.fetch_pages");
+        let mut pages = vec![None; page_ids.len()];
+        for (shard, positions) in self.route(page_ids) {
+            let shard_page_ids: Vec<PageId> = positions.iter().map(|i| page_ids[*i]).collect();
+            let fetched = self.fetch_shard_pages(shard, &shard_page_ids);
+            for (position, page) in positions.into_iter().zip(fetched) {
+                pages[position] = page;
             }
+        }
+        pages
+    }
 
-            let page_handle = self.pages.get(*frame_id).unwrap();
-            return Some(Arc::clone(&page_handle));
+    /// Fetches pages of a single shard, for [`Self::fetch_pages`].
+    fn fetch_shard_pages(
+        &self,
+        shard: &Shard,
+        page_ids: &[PageId],
+    ) -> Vec<Option<TablePageHandle>> {
+        let mut frames = shard.frames();
+        let mut pages: Vec<Option<TablePageHandle>> = page_ids
+            .iter()
+            .map(|page_id| self.pin_resident(shard, &mut frames, page_id, AccessType::Scan))
+            .collect();
+
+        // Claim a frame for each missing page, as long as there are frames to claim.
+        let mut missing: Vec<PageId> = Vec::new();
+        for page_id in page_ids {
+            if !frames.page_table.contains_key(page_id) && !missing.contains(page_id) {
+                missing.push(*page_id);
+            }
+        }
+        let claimed = self.claim_frames(shard, &mut frames, missing.len());
+        missing.truncate(claimed.len());
+        self.metrics.buffer_pool_misses.add(missing.len() as u64);
+        #[cfg(feature = "trace")]
+        for page_id in &missing {
+            trace_event!(name: "fetch_miss", page_id);
+        }
+        let read = self.disk_manager.write().unwrap().read_pages(&missing);
+        let mut fetched = HashMap::new();
+        for ((page_id, frame_id), page) in missing.into_iter().zip(claimed).zip(read) {
+            let page = match page {
+                Ok(page) => page,
+                Err(error) => {
+                    log::error!("buffer pool: {error}");
+                    frames.free_list.push_back(frame_id);
+                    continue;
+                }
+            };
+            let page_handle = Arc::new(RwLock::new(page));
+            let installed = page_handle.clone().into();
+            let access_type = AccessType::Scan;
+            self.install_frame(shard, &mut frames, frame_id, page_id, installed, access_type);
+            fetched.insert(page_id, Some(page_handle));
         }
 
-        // Check Free Frames
-        if let Some(free_frame) = self.free_list.pop_front() {
-            let page_handle = Arc::new(RwLock::new(TablePage::builder().build()));
+        // A page read is pinned once by being installed, and again for each other time it was
+        // asked for.
+        for (page, page_id) in pages.iter_mut().zip(page_ids) {
+            if let Some(first) = fetched.get_mut(page_id) {
+                *page = match first.take() {
+                    Some(page_handle) => Some(page_handle),
+                    None => self.pin_resident(shard, &mut frames, page_id, AccessType::Scan),
+                };
+            }
+        }
+        pages
+    }
 
-            let mut disk_manager = self.disk_manager.write().unwrap();
-            disk_manager.read_page(page_id);
+    /// Pins a resident table page, recording the access. Returns `None`, pinning nothing, if the
+    /// page isn't resident or isn't a table page.
+    fn pin_resident(
+        &self,
+        shard: &Shard,
+        frames: &mut Frames,
+        page_id: &PageId,
+        access_type: AccessType,
+    ) -> Option<TablePageHandle> {
+        let frame_id = frames.page_table.get(page_id)?.frame_id;
+        trace_event!(name: "fetch_hit", page_id);
+        self.metrics.buffer_pool_hits.incr();
+        let page_handle = frames.frame(frame_id).unwrap().as_table()?;
+
+        shard.pin_frame(&frame_id, access_type);
+        frames.pin(page_id);
+        Some(page_handle)
+    }
 
-            let frame_metadata = FrameMetadata::new(free_frame);
-            self.page_table.insert(*page_id, frame_metadata);
+    /// Fetches a B+tree page from the buffer pool, reading it from disk if it isn't resident.
+    ///
+    /// The page is pinned like with [`Self::fetch_page`], and is of whichever type it was created
+    /// as; see [`PageHandle::as_leaf`] and [`PageHandle::as_internal`].
+    ///
+    /// # Errors
+    /// - [`Error::InvalidData`]: If the page isn't a B+tree page.
+    /// - Otherwise as for [`Self::fetch_page_as`].
+    pub fn fetch_index_page(&self, page_id: &PageId) -> Result<PageHandle> {
+        trace_span!("fetch_page", page_id, index = true);
+        let is_index = |page_handle: &PageHandle| {
+            page_handle.as_leaf().is_some() || page_handle.as_internal().is_some()
+        };
+        self.fetch_page_handle(page_id, is_index, PageHandle::from_index_bytes)
+    }
 
-            let mut replacer = self.replacer.write().unwrap();
-            replacer.record_access(&free_frame, AccessType::Lookup);
-            replacer.set_evictable(&free_frame, true);
+    /// Fetches a page of any type from the buffer pool, deserializing it as a `P` if it isn't
+    /// resident. The page is pinned like with [`Self::fetch_page`], and evicted and flushed like
+    /// pages of any other type.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidData`]: If the page resident is of another type than `P`.
+    /// - [`Error::NoEvictableFrame`]: If the page isn't resident, and no frame is free, or can be
+    ///   evicted, to read it into.
+    /// - [`Error::PageNotAllocated`], [`Error::ChecksumMismatch`] or [`Error::IO`]: If the page
+    ///   can't be read from disk.
+    pub fn fetch_page_as<P>(&self, page_id: &PageId) -> Result<Arc<RwLock<P>>>
+    where
+        P: Page<ConcretePageType = P> + Debug + Send + Sync + 'static,
+    {
+        trace_span!("fetch_page", page_id);
+        let is_page = |page_handle: &PageHandle| page_handle.as_page::<P>().is_some();
+        let read = |buffer: &[u8]| Some(PageHandle::new(P::deserialize(buffer)));
+        let page = self.fetch_page_handle(page_id, is_page, read)?;
+        page.as_page().ok_or_else(|| errdata!("page {page_id} is not of the type fetched"))
+    }
 
-            drop(replacer);
+    /// Pins a resident page if `accept` takes it, or reads the page with `read` into a free or
+    /// evicted frame if it isn't resident. Nothing is pinned if an error is returned, which is
+    /// [`Error::InvalidData`] if `accept` or `read` turns the page down, and otherwise as for
+    /// [`Self::fetch_page_as`].
+    fn fetch_page_handle(
+        &self,
+        page_id: &PageId,
+        accept: impl FnOnce(&PageHandle) -> bool,
+        read: impl FnOnce(&[u8]) -> Option<PageHandle>,
+    ) -> Result<PageHandle> {
+        let shard = self.shard(page_id);
+        let mut frames = shard.frames();
+        if let Some(frame_metadata) = frames.page_table.get(page_id).copied() {
+            trace_event!(name: "fetch_hit", page_id);
+            self.metrics.buffer_pool_hits.incr();
+            let frame_id = *frame_metadata.frame_id();
+            let page_handle = frames.frame(frame_id).unwrap().clone();
+            if !accept(&page_handle) {
+                return errdata!("page {page_id} is not of the type fetched");
+            }
 
-            {
-                self.page_table.get_mut(page_id).unwrap().increment_pin_count();
+            shard.pin_frame(&frame_id, AccessType::Lookup);
+            frames.pin(page_id);
+            return Ok(page_handle);
+        }
+        trace_event!(name: "fetch_miss", page_id);
+        self.metrics.buffer_pool_misses.incr();
+
+        let frame_id = (self.claim_frame(shard, &mut frames))
+            .ok_or_else(|| Self::no_evictable_frame(shard, &frames))?;
+        let buffer = self.disk_manager.write().unwrap().read_page_bytes(page_id);
+        let page_handle = match buffer.map(|buffer| read(&buffer)) {
+            Ok(Some(page_handle)) => page_handle,
+            Ok(None) => {
+                frames.free_list.push_back(frame_id);
+                return errdata!("page {page_id} is not of the type fetched");
+            }
+            Err(error) => {
+                frames.free_list.push_back(frame_id);
+                return Err(error);
             }
+        };
+        let installed = page_handle.clone();
+        let access_type = AccessType::Lookup;
+        self.install_frame(shard, &mut frames, frame_id, *page_id, installed, access_type);
+        Ok(page_handle)
+    }
 
-            self.pages.insert(free_frame, page_handle.clone());
+    /// Reads a table page from disk for the frame claimed for it, which goes back on the free
+    /// list if the read fails.
+    fn read_into_frame(
+        &self,
+        frames: &mut Frames,
+        frame_id: FrameId,
+        page_id: &PageId,
+    ) -> Result<TablePage> {
+        let page = self.disk_manager.write().unwrap().read_page(page_id);
+        if page.is_err() {
+            frames.free_list.push_back(frame_id);
+        }
+        page
+    }
 
-            return Some(page_handle);
+    /// Takes a frame off the free list, or frees one up by evicting its page.
+    fn claim_frame(&self, shard: &Shard, frames: &mut Frames) -> Option<FrameId> {
+        match frames.free_list.pop_front() {
+            Some(frame_id) => Some(frame_id),
+            None => self.evict_frame(shard, frames),
         }
+    }
 
-        // See if you can evict a page
-        let mut replacer = self.replacer.write().unwrap();
-        if let Some(evicted_frame_id) = replacer.evict() {
-            drop(replacer);
-
-            // Flush the evicted page if it is dirty
-            let evict_page_id = self.pages.get(evicted_frame_id).unwrap().read().unwrap().page_id;
-            let is_dirty = self.pages.get(evicted_frame_id).unwrap().read().unwrap().is_dirty;
-            if is_dirty {
-                self.flush_page(&evict_page_id);
+    /// Takes up to `n` frames off the free list, and frees up the rest by evicting their pages
+    /// together.
+    fn claim_frames(&self, shard: &Shard, frames: &mut Frames, n: usize) -> Vec<FrameId> {
+        let free = frames.free_list.len().min(n);
+        let mut claimed: Vec<FrameId> = frames.free_list.drain(..free).collect();
+        claimed.extend(self.evict_frames(shard, frames, n - free));
+        claimed
+    }
+
+    /// Evicts the page chosen by the replacer, writing it back first if it is dirty, and returns
+    /// the frame it occupied.
+    fn evict_frame(&self, shard: &Shard, frames: &mut Frames) -> Option<FrameId> {
+        self.evict_frames(shard, frames, 1).pop()
+    }
+
+    /// Evicts up to `n` pages, chosen by the replacer all at once, in the order it would have
+    /// evicted them one by one, and returns the frames they occupied. The dirty pages are written
+    /// back first; a page that can't be, stays, and its frame isn't returned.
+    fn evict_frames(&self, shard: &Shard, frames: &mut Frames, n: usize) -> Vec<FrameId> {
+        if n == 0 {
+            return Vec::new();
+        }
+        sched_point!("bpm.evict");
+        let victims = shard.replacer.write().unwrap().evict_n(n);
+        (victims.into_iter())
+            .filter(|frame_id| self.evict_page(shard, frames, *frame_id))
+            .collect()
+    }
+
+    /// Evicts the page of a frame the replacer just gave up, writing it back first if it is
+    /// dirty, or with async writes, queueing it to be, see [`DiskManager::write_page_async`].
+    /// Returns whether the page was evicted.
+    fn evict_page(&self, shard: &Shard, frames: &mut Frames, evicted_frame_id: FrameId) -> bool {
+        // Flush the evicted page if it is dirty
+        let page_handle = frames.frame(evicted_frame_id).unwrap().clone();
+        let evict_page_id = page_handle.page_id();
+        trace_event!(
+            name: "evict",
+            frame_id = evicted_frame_id,
+            page_id = evict_page_id,
+            dirty = page_handle.get_is_dirty()
+        );
+        if page_handle.get_is_dirty() {
+            let written = (self.log_manager.flush(page_handle.lsn()))
+                .and_then(|_| self.queue_write_back(&evict_page_id, &page_handle));
+            if let Err(error) = written {
+                log::error!("buffer pool: {error}");
+                // The page stays, evictable as it was, though with its access history lost.
+                let mut replacer = shard.replacer.write().unwrap();
+                log_replacer_error(
+                    (replacer.record_access(&evicted_frame_id, AccessType::Lookup))
+                        .and_then(|()| replacer.set_evictable(&evicted_frame_id, true)),
+                );
+                return false;
             }
+            self.metrics.buffer_pool_writebacks.incr();
+        }
+        self.metrics.buffer_pool_evictions.incr();
+        frames.page_table.remove(&evict_page_id);
+        frames.generations[evicted_frame_id] += 1;
+        true
+    }
 
-            // Read the new page from disk
-            let mut disk_binding = self.disk_manager.write().unwrap();
-            let new_page = disk_binding.read_page(&page_id);
-            let new_page_handle = Arc::new(RwLock::new(new_page));
+    /// Frees up to `n` frames ahead of a bulk allocation, such as a multi-page insert, by
+    /// evicting the pages the replacer picks, all at once. The dirty ones are written back, and
+    /// the frames are put on the free list, empty, for the allocations to take.
+    ///
+    /// A sharded pool evicts from its shards in turn, until it has freed `n` frames.
+    ///
+    /// # Returns
+    /// - The frames freed, in the order they were evicted; the frame IDs are those of the shard
+    ///   the frame belongs to. Fewer than `n` if not enough pages are evictable.
+    pub fn reserve_frames(&self, n: usize) -> Vec<FrameId> {
+        let mut reserved = Vec::new();
+        for shard in &self.shards {
+            let mut frames = shard.frames();
+            let evicted = self.evict_frames(shard, &mut frames, n - reserved.len());
+            for frame_id in &evicted {
+                frames.pages[*frame_id] = None;
+                frames.free_list.push_back(*frame_id);
+            }
+            reserved.extend(evicted);
+        }
+        reserved
+    }
+
+    /// Places a page in the given frame, pinned once and not evictable.
+    fn install_frame(
+        &self,
+        shard: &Shard,
+        frames: &mut Frames,
+        frame_id: FrameId,
+        page_id: PageId,
+        page_handle: PageHandle,
+        access_type: AccessType,
+    ) {
+        debug_assert_eq!(frames.pages.len(), shard.pool_size);
+        frames.pages[frame_id] = Some(page_handle);
+
+        let generation = frames.generations[frame_id];
+        frames.page_table.insert(page_id, FrameMetadata::new(frame_id, generation));
+        frames.pin(&page_id);
+        shard.pin_frame(&frame_id, access_type);
+        shard.replacer.write().unwrap().record_page(&frame_id, page_id);
+    }
+
+    /// Reads table pages into the pool ahead of a scan that is about to fetch them, so that the
+    /// fetches don't wait on disk. The pages are left unpinned and evictable, with their access
+    /// recorded as a `Scan`; pages that are already resident are left alone.
+    ///
+    /// While reading, the pages asked for are kept from being evicted, so that a scan reading
+    /// ahead never pushes out the pages it read ahead before. For the same reason, no more pages
+    /// are read than there are free or evictable frames besides theirs; the rest are only passed
+    /// on to the disk manager as a read-ahead hint.
+    ///
+    /// A sharded pool reads the pages of each shard in turn, within the frames of that shard.
+    ///
+    /// # Returns
+    /// - The number of pages read into the pool.
+    pub fn prefetch_pages(&self, page_ids: &[PageId]) -> usize {
+        (self.route(page_ids).into_iter())
+            .map(|(shard, positions)| {
+                let page_ids: Vec<PageId> = positions.iter().map(|i| page_ids[*i]).collect();
+                self.prefetch_shard_pages(shard, &page_ids)
+            })
+            .sum()
+    }
 
-            // Update the page table with the new page
-            self.page_table.insert(*page_id, FrameMetadata::new(evicted_frame_id));
-            self.pages.insert(evicted_frame_id, new_page_handle.clone());
+    /// Reads ahead pages of a single shard, for [`Self::prefetch_pages`].
+    fn prefetch_shard_pages(&self, shard: &Shard, page_ids: &[PageId]) -> usize {
+        let mut frames = shard.frames();
+        let (resident, missing): (Vec<PageId>, Vec<PageId>) = page_ids
+            .iter()
+            .copied()
+            .partition(|page_id| frames.page_table.contains_key(page_id));
+        if missing.is_empty() {
+            return 0;
+        }
+        let mut shielded: Vec<FrameId> = resident
+            .iter()
+            .map(|page_id| frames.page_table[page_id])
+            .filter(|frame_metadata| frame_metadata.pin_count == 0)
+            .map(|frame_metadata| frame_metadata.frame_id)
+            .collect();
+        let mut replacer = shard.replacer.write().unwrap();
+        for frame_id in &shielded {
+            log_replacer_error(replacer.set_evictable(frame_id, false));
+        }
+        let budget = frames.free_list.len() + replacer.size();
+        drop(replacer);
+
+        self.disk_manager.write().unwrap().read_ahead(&missing);
+        let mut prefetched = 0;
+        for page_id in missing.into_iter().take(budget) {
+            let Some(frame_id) = self.claim_frame(shard, &mut frames) else {
+                break;
+            };
+            let page = match self.read_into_frame(&mut frames, frame_id, &page_id) {
+                Ok(page) => page,
+                Err(error) => {
+                    log::error!("buffer pool: {error}");
+                    continue;
+                }
+            };
+            let page_handle = Arc::new(RwLock::new(page)).into();
+            let access_type = AccessType::Scan;
+            self.install_frame(shard, &mut frames, frame_id, page_id, page_handle, access_type);
+            // Installed with a pin just above, so there is one to take off.
+            frames.unpin(&page_id).unwrap();
+            shielded.push(frame_id);
+            prefetched += 1;
+        }
 
-            return Some(new_page_handle);
+        let mut replacer = shard.replacer.write().unwrap();
+        for frame_id in &shielded {
+            log_replacer_error(replacer.set_evictable(frame_id, true));
         }
+        prefetched
+    }
+
+    /// Warms up the pool by reading table pages into its free frames, such as the pages
+    /// [`Self::resident_page_ids`] returned before a restart. The pages are left unpinned and
+    /// evictable, with a single `Lookup` access recorded, so that they aren't the first to go.
+    ///
+    /// No page is evicted to make room: a shard stops preloading once its free list is empty.
+    /// Pages that are already resident are left alone.
+    ///
+    /// # Returns
+    /// - The number of pages read into the pool.
+    pub fn preload(&self, page_ids: &[PageId]) -> usize {
+        (self.route(page_ids).into_iter())
+            .map(|(shard, positions)| {
+                let page_ids: Vec<PageId> = positions.iter().map(|i| page_ids[*i]).collect();
+                self.preload_shard_pages(shard, &page_ids)
+            })
+            .sum()
+    }
 
-        None
+    /// Preloads pages of a single shard, for [`Self::preload`].
+    fn preload_shard_pages(&self, shard: &Shard, page_ids: &[PageId]) -> usize {
+        let mut frames = shard.frames();
+        let mut preloaded = 0;
+        for page_id in page_ids.iter().copied() {
+            if frames.page_table.contains_key(&page_id) {
+                continue;
+            }
+            let Some(frame_id) = frames.free_list.pop_front() else {
+                break;
+            };
+            let page = match self.read_into_frame(&mut frames, frame_id, &page_id) {
+                Ok(page) => page,
+                Err(error) => {
+                    log::error!("buffer pool: {error}");
+                    continue;
+                }
+            };
+            let page_handle = Arc::new(RwLock::new(page)).into();
+            let access_type = AccessType::Lookup;
+            self.install_frame(shard, &mut frames, frame_id, page_id, page_handle, access_type);
+            // Installed with a pin just above, so there is one to take off.
+            frames.unpin(&page_id).unwrap();
+            log_replacer_error(shard.replacer.write().unwrap().set_evictable(&frame_id, true));
+            preloaded += 1;
+        }
+        preloaded
     }
 
+    /// Returns the pages resident in the pool, ordered by page ID, for [`Self::preload`] to read
+    /// back in after a restart.
+    pub fn resident_page_ids(&self) -> Vec<PageId> {
+        let mut page_ids: Vec<PageId> = (self.shards.iter())
+            .flat_map(|shard| shard.frames().page_table.keys().copied().collect::<Vec<_>>())
+            .collect();
+        page_ids.sort_unstable();
+        page_ids
+    }
 
     /// Unpins a page from the buffer pool.
     ///
     /// This method attempts to unpin the page identified by `page_id` from the
-    /// buffer pool. If the page is not present in the pool, it should abort; or,
-    /// if the page's pin count is already zero, the function returns `false` to
-    /// indicate that no action was taken.
+    /// buffer pool. If the page is not present in the pool, or its pin count is
+    /// already zero, it returns an error and no action is taken.
     ///
     /// When unpinning a page, the method decrements its pin count. If the pin
     /// count drops to zero, the frame containing the page becomes eligible for
-    /// eviction by the replacer. The function also updates the page's dirty flag
-    /// based on the `is_dirty` parameter, which indicates whether the page has
-    /// been modified.
+    /// eviction by the replacer. If `is_dirty` is set, the page is marked dirty,
+    /// whatever its pin count; a clean unpin never clears the flag, since
+    /// another holder of the page may have modified it.
     ///
     /// # Parameters
     /// - `page_id`: The identifier of the page to be unpinned.
     /// - `is_dirty`: A boolean flag that specifies whether the page should be
-    ///   marked as dirty (`true`) or clean (`false`).
+    ///   marked as dirty (`true`) or left as it is (`false`).
     ///
-    /// # Returns
-    /// - `true`: If the page was successfully unpinned (i.e., it was present
-    ///   in the buffer pool and its pin count was greater than zero before this
-    ///   call).
-    /// - `false`: If the page was not in the buffer pool or its pin count was
-    ///   zero or less before this call.
-    pub fn unpin_page(&mut self, page_id: &PageId, is_dirty: bool) -> bool {
-        if let Some(framedata) = self.page_table.get_mut(page_id) {
-            return if framedata.pin_count > 0 {
-                framedata.decrement_pin_count();
-                if framedata.pin_count == 0 {
-                    if let Some(page_handle) = self.pages.get(framedata.frame_id) {
-                        let mut page = page_handle.write().unwrap();
-                        page.is_dirty = is_dirty;
-
-                        let mut replacer = self.replacer.write().unwrap();
-                        replacer.set_evictable(&framedata.frame_id, true);
-                    }
-                }
-
-                true
-            } else {
-                false
-            };
+    /// # Errors
+    /// - [`Error::PageNotInPool`]: If the page is not in the buffer pool.
+    /// - [`Error::PageNotPinned`]: If the page's pin count was already zero.
+    pub fn unpin_page(&self, page_id: &PageId, is_dirty: bool) -> Result<()> {
+        sched_point!("bpm.unpin_page");
+        let shard = self.shard(page_id);
+        let mut frames = shard.frames();
+        let (frame_id, pin_count) = frames.unpin(page_id)?;
+        if is_dirty {
+            if let Some(page_handle) = frames.frame(frame_id) {
+                page_handle.set_is_dirty(true);
+            }
         }
-
-        panic!("Page {:?} not found", page_id);
+        if pin_count == 0 {
+            shard.replacer.write().unwrap().set_evictable(&frame_id, true)?;
+        }
+        Ok(())
     }
 
     /// Flushes a page to disk.
@@ -341,42 +1223,409 @@ impl BufferPoolManager {
     /// After the page is successfully flushed, its dirty flag is reset to
     /// indicate that the page is now clean.
     ///
-    /// If the page corresponding to `page_id` does not exist in the page,
-    /// this method should abort.
+    /// Write-ahead logging requires the log to be durable up to the page's LSN
+    /// before the page itself reaches disk. If it isn't, the flush is refused and
+    /// the page is left untouched and dirty.
     ///
     /// # Parameters
     /// - `page_id`: The identifier of the page to be flushed.
-    pub fn flush_page(&mut self, page_id: &PageId) {
-        if let Some(frame_metadata) = self.page_table.get(page_id) {
-            if let Some(page_handle) = self.pages.get(frame_metadata.frame_id) {
-                let mut page = page_handle.write().unwrap();
+    ///
+    /// # Returns
+    /// - `true`: If the page was written to disk.
+    /// - `false`: If the write was blocked because the log tail covering the page isn't durable.
+    ///
+    /// The pages evictions queued are written too, see [`DiskManager::drain`].
+    ///
+    /// # Errors
+    /// - [`Error::PageNotInPool`]: If the page is not in the buffer pool.
+    /// - [`Error::IO`]: If the page can't be written, in which case it stays dirty, or if it was
+    ///   written but the disk manager, in [`Durability::Manual`] mode, can't sync it, or if the
+    ///   pages queued can't be written.
+    pub fn flush_page(&self, page_id: &PageId) -> Result<bool> {
+        trace_span!("flush_page", page_id);
+        sched_point!("bpm.flush_page");
+        let frames = self.shard(page_id).frames();
+        let page_handle = Self::resident(&frames, page_id)?;
+        let written = self.write_back(page_id, page_handle)?;
+        self.sync_after_flush()?;
+        Ok(written)
+    }
+
+    /// Writes the pages evictions queued at the end of a flush, and syncs the disk manager in
+    /// [`Durability::Manual`] mode, in which nothing else would. In the other modes, the pages
+    /// are synced as they are written, or soon after.
+    fn sync_after_flush(&self) -> Result<()> {
+        let mut disk_manager = self.disk_manager.write().unwrap();
+        disk_manager.drain()?;
+        match disk_manager.durability() {
+            Durability::Manual => disk_manager.sync(),
+            Durability::Always | Durability::Interval(_) => Ok(()),
+        }
+    }
+
+    /// Forces the pages written to disk so far to stable storage, whatever the disk manager's
+    /// [`Durability`], e.g. for a commit that must not be lost. Pages still dirty in the pool
+    /// aren't written; flush them first.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the database file can't be synced.
+    pub fn request_sync(&self) -> Result<()> {
+        self.disk_manager.write().unwrap().sync()
+    }
+
+    /// Compacts the database file, see [`DiskManager::compact_except`], refusing to move the
+    /// pages resident in the pool, which stay where they are, so that no frame is left holding
+    /// a page under an ID it no longer has. The frames of every shard stay locked throughout, so
+    /// that no page is read in meanwhile.
+    ///
+    /// # Returns
+    /// - By how many pages the file shrank.
+    ///
+    /// # Errors
+    /// Fails like [`DiskManager::compact_except`].
+    pub fn compact_disk(&self, relocations: &mut dyn FnMut(PageId, PageId)) -> Result<PageId> {
+        let shards: Vec<MutexGuard<Frames>> = self.shards.iter().map(Shard::frames).collect();
+        let resident: HashSet<PageId> =
+            (shards.iter()).flat_map(|frames| frames.page_table.keys().copied()).collect();
+        let mut disk_manager = self.disk_manager.write().unwrap();
+        disk_manager.compact_except(&|page_id| resident.contains(&page_id), relocations)
+    }
 
-                let mut disk_manager = self.disk_manager.write().unwrap();
-                disk_manager.write_page((*page).clone());
+    /// Returns the statistics of the disk manager, see [`DiskManager::stats`]. Pages still
+    /// dirty in the pool aren't written yet, so don't count.
+    pub fn disk_stats(&self) -> DiskStats {
+        self.disk_manager.read().unwrap().stats()
+    }
 
-                page.is_dirty = false;
-            } else {
-                panic!("Frame ID not found in pages.");
+    /// Returns a resident page.
+    ///
+    /// # Errors
+    /// - [`Error::PageNotInPool`]: If the page is not in the buffer pool.
+    fn resident<'a>(frames: &'a Frames, page_id: &PageId) -> Result<&'a PageHandle> {
+        (frames.page_table.get(page_id))
+            .and_then(|frame_metadata| frames.frame(frame_metadata.frame_id))
+            .ok_or(Error::PageNotInPool(*page_id))
+    }
+
+    /// Writes a page to disk if the log is durable up to its LSN, marking it clean, and returns
+    /// whether it was written. The pre-flush hook, if any, is called first.
+    ///
+    /// # Errors
+    /// - Whatever the pre-flush hook returns, in which case the page isn't written.
+    /// - [`Error::IO`]: If the page can't be written, in which case it stays dirty.
+    fn write_back(&self, page_id: &PageId, page_handle: &PageHandle) -> Result<bool> {
+        self.write_back_with(page_handle, |disk_manager, payload| {
+            disk_manager.write_page_bytes(page_id, &payload)
+        })
+    }
+
+    /// Writes back a page like [`Self::write_back`], but with async writes, only queues it to
+    /// be written, e.g. so that an eviction doesn't wait for the disk. Reads of the page see it
+    /// as queued until it is written.
+    fn queue_write_back(&self, page_id: &PageId, page_handle: &PageHandle) -> Result<bool> {
+        self.write_back_with(page_handle, |disk_manager, payload| {
+            disk_manager.write_page_bytes_async(page_id, payload)
+        })
+    }
+
+    /// Writes back a page like [`Self::write_back`], with `write`.
+    fn write_back_with(
+        &self,
+        page_handle: &PageHandle,
+        write: impl FnOnce(&mut DiskManager, Vec<u8>) -> Result<()>,
+    ) -> Result<bool> {
+        self.run_pre_flush_hook(page_handle)?;
+        let durable_lsn = self.log_manager.durable_lsn();
+        let mut result = Ok(());
+        let written = page_handle.write_back(|lsn, payload| {
+            if lsn > durable_lsn {
+                return false;
+            }
+
+            result = write(&mut self.disk_manager.write().unwrap(), payload);
+            if result.is_ok() {
+                self.metrics.buffer_pool_flushes.incr();
+            }
+            result.is_ok()
+        });
+        result.map(|()| written)
+    }
+
+    /// Writes back pages like [`Self::write_back`], but all with a single call to
+    /// [`DiskManager::write_pages_bytes`], which writes runs of consecutive pages at once. Pages
+    /// the pre-flush hook fails, or whose changes are only in the log tail, are left out. Each
+    /// page is marked clean as it is serialized, with its latch held, and dirty again if the
+    /// write fails, so no change is lost in between. Returns the number of pages written.
+    fn write_back_batch(&self, pages: &[(PageId, PageHandle)]) -> usize {
+        let durable_lsn = self.log_manager.durable_lsn();
+        let mut payloads = Vec::new();
+        let mut serialized = Vec::new();
+        for (page_id, page_handle) in pages {
+            if self.run_pre_flush_hook(page_handle).is_err() {
+                continue;
+            }
+            let cleaned = page_handle.write_back(|lsn, payload| {
+                if lsn > durable_lsn {
+                    return false;
+                }
+                payloads.push((*page_id, payload));
+                true
+            });
+            if cleaned {
+                serialized.push(page_handle);
+            }
+        }
+        if serialized.is_empty() {
+            return 0;
+        }
+
+        let result = self.disk_manager.write().unwrap().write_pages_bytes(&payloads);
+        if let Err(error) = result {
+            log::error!("buffer pool: {error}");
+            for page_handle in serialized {
+                page_handle.set_is_dirty(true);
             }
-        } else {
-            panic!("Attempted to flush a page that does not exist in the buffer pool.");
+            return 0;
         }
+        self.metrics.buffer_pool_flushes.add(serialized.len() as u64);
+        serialized.len()
     }
 
-    /// Flush all the page in the buffer pool to disk.
-    pub fn flush_all_pages(&mut self) {
-        let page_keys: Vec<PageId> = self.page_table.keys().cloned().collect();
-        for page_id in page_keys {
-            self.flush_page(&page_id);
+    /// Calls the pre-flush hook on a table page about to be written back, with the page only
+    /// read-latched, so that it can't deadlock with the write that follows.
+    fn run_pre_flush_hook(&self, page_handle: &PageHandle) -> Result<()> {
+        let hook = self.pre_flush_hook.read().unwrap().clone();
+        match (hook, page_handle.as_table()) {
+            (Some(hook), Some(page)) => (hook.0)(&page.read().unwrap()),
+            _ => Ok(()),
         }
     }
 
-    /// If the page identified by `page_id` is not in the buffer pool, this
-    /// method aborts. If the page is pinned, it returns `false`. Otherwise,
-    /// it deletes the page, updates the frame list,
-    /// ([maybe] resets the page's memory and metadata, ) and calls
-    /// [`crate::storage::disk::disk_manager::DiskManager::deallocate_page`] to free it
-    /// on disk.
+    /// Sets the hook called with each table page the pool is about to write back, whether by
+    /// [`Self::flush_page`], [`Self::flush_all_pages`], a checkpoint, the background flusher or an
+    /// eviction, replacing the hook set before, if any. Pages of other types are written without
+    /// calling it.
+    ///
+    /// If the hook returns an error, the page isn't written and stays dirty: `flush_page` returns
+    /// the error, `flush_all_pages` leaves the page out, and an eviction gives up, so the fetch or
+    /// new page needing the frame fails.
+    ///
+    /// The hook is called with the page read-latched, never write-latched, so it may read the
+    /// page. The frames of the page's shard are locked, though, as the page mustn't be evicted
+    /// while written, so it must not call back into the pool.
+    pub fn set_pre_flush_hook(&self, hook: PreFlushHook) {
+        *self.pre_flush_hook.write().unwrap() = Some(SharedPreFlushHook(Arc::from(hook)));
+    }
+
+    /// Forces the log up to the page's LSN, then flushes the page. Used when a dirty page must be
+    /// written back regardless, e.g. on eviction.
+    pub(crate) fn force_log_and_flush(&self, page_id: &PageId) -> Result<bool> {
+        let frames = self.shard(page_id).frames();
+        let page_handle = Self::resident(&frames, page_id)?;
+        self.log_manager.flush(page_handle.lsn())?;
+        self.write_back(page_id, page_handle)
+    }
+
+    /// Flushes every dirty page in the buffer pool to disk, leaving clean pages alone. Like with
+    /// [`Self::flush_page`], pages whose changes are only in the log tail are skipped, and stay
+    /// dirty, and the pages written are synced at the end in [`Durability::Manual`] mode. The
+    /// pages evictions queued are written too.
+    ///
+    /// # Returns
+    /// - The number of pages written.
+    pub fn flush_all_pages(&self) -> usize {
+        self.flush_all_pages_parallel(1)
+    }
+
+    /// Like [`Self::flush_all_pages`], but splits the dirty pages between `threads` threads,
+    /// which serialize and write their share concurrently. Writes still take turns at the disk
+    /// manager.
+    ///
+    /// # Returns
+    /// - The number of pages written.
+    pub fn flush_all_pages_parallel(&self, threads: usize) -> usize {
+        trace_span!("flush_all_pages", threads);
+        // The frames of every shard stay locked throughout, so that none of the pages is evicted
+        // mid-way.
+        let shards: Vec<MutexGuard<Frames>> = self.shards.iter().map(Shard::frames).collect();
+        let dirty_pages: Vec<(PageId, PageHandle)> = (shards.iter())
+            .flat_map(|frames| {
+                (frames.page_table.iter()).filter_map(|(page_id, frame_metadata)| {
+                    let page = frames.frame(frame_metadata.frame_id)?;
+                    page.get_is_dirty().then(|| (*page_id, page.clone()))
+                })
+            })
+            .collect();
+        if dirty_pages.is_empty() {
+            return 0;
+        }
+        let threads = threads.clamp(1, dirty_pages.len());
+        let share = dirty_pages.len().div_ceil(threads);
+        let written = match threads {
+            1 => self.write_back_batch(&dirty_pages),
+            _ => thread::scope(|scope| {
+                let flushers: Vec<_> = (dirty_pages.chunks(share))
+                    .map(|pages| scope.spawn(|| self.write_back_batch(pages)))
+                    .collect();
+                (flushers.into_iter())
+                    .map(|flusher| flusher.join().expect("Page flusher panicked"))
+                    .sum()
+            }),
+        };
+        if let Err(error) = self.sync_after_flush() {
+            log::error!("buffer pool: {error}");
+        }
+        written
+    }
+
+    /// Starts a thread that writes dirty, unpinned pages back to disk every `interval`, so that
+    /// evictions mostly find clean pages, and don't have to wait on a write. Replaces the flusher
+    /// already running, if any. The flusher runs until [`Self::stop_background_flusher`] is
+    /// called, or the pool is dropped.
+    ///
+    /// The flusher never waits on the pool: whenever the frames of a shard are locked, it leaves
+    /// the shard's pages to the next round. Each page is written with the frames of its shard
+    /// locked, so no page is pinned, or evicted by the replacer, while it is being written. Like
+    /// with [`Self::flush_all_pages`], pages whose changes are only in the log tail stay dirty.
+    pub fn start_background_flusher(bpm: &Arc<Self>, interval: Duration) {
+        let flusher = BackgroundFlusher::spawn(Arc::downgrade(bpm), interval);
+        *bpm.flusher.lock().unwrap() = Some(flusher);
+    }
+
+    /// Stops the background flusher, waiting for it to finish the page it is writing. Does
+    /// nothing if there is none.
+    pub fn stop_background_flusher(&self) {
+        let flusher = self.flusher.lock().unwrap().take();
+        drop(flusher);
+    }
+
+    /// Writes back the pages that are dirty and unpinned, for the background flusher.
+    ///
+    /// # Returns
+    /// - The number of pages written.
+    fn flush_unpinned_pages(&self) -> usize {
+        (self.shards.iter())
+            .map(|shard| self.flush_unpinned_shard_pages(shard))
+            .sum()
+    }
+
+    /// Writes back the dirty, unpinned pages of a single shard, for the background flusher.
+    fn flush_unpinned_shard_pages(&self, shard: &Shard) -> usize {
+        let Ok(frames) = shard.frames.try_lock() else {
+            return 0;
+        };
+        let candidates: Vec<PageId> = (frames.page_table.iter())
+            .filter(|(_, frame_metadata)| frame_metadata.pin_count == 0)
+            .filter(|(_, frame_metadata)| {
+                frames.frame(frame_metadata.frame_id).is_some_and(PageHandle::get_is_dirty)
+            })
+            .map(|(page_id, _)| *page_id)
+            .collect();
+        drop(frames);
+        trace_span!("background_flush", pages = candidates.len());
+
+        // The frames are locked again for each page, as it may have been pinned, or evicted,
+        // since.
+        let mut written = 0;
+        for page_id in candidates {
+            let Ok(frames) = shard.frames.try_lock() else {
+                continue;
+            };
+            let Some(frame_metadata) = frames.page_table.get(&page_id) else {
+                continue;
+            };
+            if frame_metadata.pin_count > 0 {
+                continue;
+            }
+            let page_handle = frames.frame(frame_metadata.frame_id).unwrap();
+            if page_handle.get_is_dirty()
+                && matches!(self.write_back(&page_id, page_handle), Ok(true))
+            {
+                written += 1;
+            }
+        }
+        written
+    }
+
+    /// Writes the dirty, unpinned pages back to disk for a checkpoint, forcing the log first so
+    /// each write is allowed. Dirty pages that are pinned may be in the middle of a change, so
+    /// they are skipped and stay dirty, for a later checkpoint to write.
+    ///
+    /// Every shard is locked throughout, so the dirty page table is taken at a single point,
+    /// and no page is pinned or unpinned while the checkpoint runs. A page dirtied again after
+    /// it was written back is marked dirty anew, and isn't lost to later checkpoints.
+    ///
+    /// # Returns
+    /// - The dirty page table: the pages that were written, and those skipped, with their LSNs.
+    pub fn checkpoint(&self) -> Result<CheckpointInfo> {
+        let shards: Vec<MutexGuard<Frames>> = self.shards.iter().map(Shard::frames).collect();
+        let mut info = CheckpointInfo::default();
+        for frames in &shards {
+            for (page_id, frame_metadata) in &frames.page_table {
+                let page = frames.frame(frame_metadata.frame_id).unwrap();
+                if !page.get_is_dirty() {
+                    continue;
+                }
+                match frame_metadata.pin_count {
+                    0 => info.flushed.push((*page_id, page.lsn())),
+                    _ => info.skipped.push((*page_id, page.lsn())),
+                }
+            }
+        }
+        info.flushed.sort();
+        info.skipped.sort();
+
+        if let Some(lsn) = info.flushed.iter().map(|(_, lsn)| *lsn).max() {
+            self.log_manager.flush(lsn)?;
+        }
+        for (page_id, _) in &info.flushed {
+            let frames = &shards[self.shard_index(page_id)];
+            self.write_back(page_id, Self::resident(frames, page_id)?)?;
+        }
+        // Pages evicted before the checkpoint must be on disk once it completes, too.
+        self.disk_manager.write().unwrap().drain()?;
+        Ok(info)
+    }
+
+    /// Returns the pages that are dirty, ordered by page ID.
+    pub fn dirty_page_ids(&self) -> Vec<PageId> {
+        let mut page_ids: Vec<PageId> = (self.shards.iter())
+            .flat_map(|shard| {
+                let frames = shard.frames();
+                (frames.page_table.iter())
+                    .filter(|(_, frame_metadata)| {
+                        frames.frame(frame_metadata.frame_id).unwrap().get_is_dirty()
+                    })
+                    .map(|(page_id, _)| *page_id)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        page_ids.sort_unstable();
+        page_ids
+    }
+
+    #[cfg(test)]
+    /// Simulates a crash by re-reading every resident page from disk, losing
+    /// any change that was never flushed.
+    pub(crate) fn crash_for_test(&self) {
+        for shard in &self.shards {
+            let frames = shard.frames();
+            let mut disk_manager = self.disk_manager.write().unwrap();
+            for (page_id, frame_metadata) in &frames.page_table {
+                if let Some(page_handle) = frames.frame(frame_metadata.frame_id) {
+                    page_handle.reload(&disk_manager.read_page_bytes(page_id).unwrap());
+                }
+            }
+        }
+    }
+
+    /// Deletes a page from the buffer pool and from disk.
+    ///
+    /// If the page is pinned, it returns `false` and nothing is done. Otherwise, the page is
+    /// removed from the page table, its frame is forgotten by the replacer and put back on the
+    /// free list, the page's memory and metadata are reset, and
+    /// [`crate::storage::disk::disk_manager::DiskManager::deallocate_page`] is called to free it
+    /// on disk. A page that isn't resident is only deallocated on disk.
     ///
     /// # Parameters
     /// - `page_id`: The identifier of the page to be deleted.
@@ -384,85 +1633,249 @@ impl BufferPoolManager {
     /// # Returns
     /// - `true`: If the page was successfully deleted.
     /// - `false`: If the page was found but could not be deleted (e.g., it was pinned).
-    pub fn delete_page(&mut self, page_id: PageId) -> bool {
-        if let Some(frame_metadata) = self.page_table.get(&page_id) {
-            if frame_metadata.pin_count > 0 {
-                return false;
-            }
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the page can't be deallocated on disk, after it was taken out of the
+    ///   pool.
+    pub fn delete_page(&self, page_id: PageId) -> Result<bool> {
+        let shard = self.shard(&page_id);
+        let mut frames = shard.frames();
+        if Self::pinned(&frames, &page_id) {
+            return Ok(false);
+        }
+        Self::drop_frame(shard, &mut frames, &page_id)?;
 
-            let frame_id = frame_metadata.frame_id;
+        self.disk_manager.write().unwrap().deallocate_page(&page_id)?;
+        self.metrics.buffer_pool_pages_deleted.incr();
+        Ok(true)
+    }
 
-            self.page_table.remove(&page_id);
-            if let Some(page_handle) = self.pages.get(frame_id) {
-                // reset page's memory and metadata
+    /// Deletes pages from the buffer pool and from disk, e.g. those of a table dropped, like
+    /// [`Self::delete_page`], but all at once: the frames of every shard stay locked while the
+    /// resident pages are removed, and the pages are then freed on disk with
+    /// [`DiskManager::deallocate_pages`], writing each header once.
+    ///
+    /// # Returns
+    /// - `true`: If the pages were deleted.
+    /// - `false`: If any of them is pinned, in which case none is deleted.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the pages can't be deallocated on disk, after they were taken out of
+    ///   the pool.
+    pub fn delete_pages(&self, page_ids: &[PageId]) -> Result<bool> {
+        let mut shards: Vec<MutexGuard<Frames>> = self.shards.iter().map(Shard::frames).collect();
+        let shard_of = |page_id: &PageId| self.shard_index(page_id);
+        if (page_ids.iter()).any(|page_id| Self::pinned(&shards[shard_of(page_id)], page_id)) {
+            return Ok(false);
+        }
+        for page_id in page_ids {
+            let index = shard_of(page_id);
+            Self::drop_frame(&self.shards[index], &mut shards[index], page_id)?;
+        }
+
+        self.disk_manager.write().unwrap().deallocate_pages(page_ids)?;
+        self.metrics.buffer_pool_pages_deleted.add(page_ids.len() as u64);
+        Ok(true)
+    }
+
+    /// Returns whether a page is resident and pinned.
+    fn pinned(frames: &Frames, page_id: &PageId) -> bool {
+        (frames.page_table.get(page_id)).is_some_and(|frame_metadata| frame_metadata.pin_count > 0)
+    }
+
+    /// Removes an unpinned page from the pool, if resident: it is taken out of the page table,
+    /// its frame is forgotten by the replacer and put back on the free list, and the page's
+    /// memory and metadata are reset.
+    fn drop_frame(shard: &Shard, frames: &mut Frames, page_id: &PageId) -> Result<()> {
+        let Some(frame_metadata) = frames.page_table.get(page_id).copied() else {
+            return Ok(());
+        };
+        let frame_id = frame_metadata.frame_id;
+        // Unpinned, so the frame is evictable, and can be removed from the replacer.
+        shard.replacer.write().unwrap().remove(&frame_id)?;
+        frames.page_table.remove(page_id);
+        if let Some(page_handle) = frames.pages[frame_id].take() {
+            // reset page's memory and metadata
+            if let PageHandle::Table(page_handle) = &page_handle {
                 let mut page = page_handle.write().unwrap();
                 page.data.clear(); // clear the data
                 page.tuple_info.clear(); // clear tuple info
                 page.tuple_cnt = 0;
                 page.deleted_tuple_cnt = 0;
-                page.is_dirty = false;
-
-                let mut disk_manager = self.disk_manager.write().unwrap();
-                disk_manager.deallocate_page(&page_id);
             }
-
-            self.free_list.push_back(frame_id);
-            true
-        } else {
-            // Page not found
-            panic!("Attempted to delete a page that does not exist in the buffer pool.");
+            page_handle.set_is_dirty(false);
         }
+        frames.generations[frame_id] += 1;
+        frames.free_list.push_back(frame_id);
+        Ok(())
     }
 
+    /// Returns the number of frames in the pool, across all of its shards.
     pub fn size(&self) -> usize {
         self.pool_size
     }
 
-    pub(crate) fn get_is_dirty(&self, page_id: &PageId) -> bool {
-        let frame_id = self
-            .page_table
-            .get(page_id)
-            .expect(NO_CORRESPONDING_FRAME_ID_MSG)
-            .frame_id;
-        self.pages.get(frame_id).unwrap().read().unwrap().is_dirty
+    /// Returns whether a page is resident in the pool.
+    pub fn contains_page(&self, page_id: &PageId) -> bool {
+        self.shard(page_id).frames().page_table.contains_key(page_id)
     }
 
-    pub(crate) fn get_pin_count(&self, page_id: &PageId) -> Option<usize> {
-        Some(self.page_table.get(&page_id)?.pin_count)
+    /// Returns a page's pin count, or `None` if the page isn't resident.
+    pub fn pin_count(&self, page_id: &PageId) -> Option<usize> {
+        Some(self.shard(page_id).frames().page_table.get(page_id)?.pin_count)
     }
 
-    pub(crate) fn set_is_dirty(&mut self, page_id: &PageId, is_dirty: bool) {
-        let frame_id = self
-            .page_table
-            .get(page_id)
-            .expect(NO_CORRESPONDING_FRAME_ID_MSG)
-            .frame_id;
-        self.pages
-            .get_mut(frame_id)
-            .unwrap()
-            .write()
-            .unwrap()
-            .set_is_dirty(is_dirty);
+    /// Returns whether a page is dirty, or `None` if the page isn't resident.
+    pub fn is_dirty(&self, page_id: &PageId) -> Option<bool> {
+        let frames = self.shard(page_id).frames();
+        let frame_metadata = frames.page_table.get(page_id)?;
+        Some(frames.frame(frame_metadata.frame_id)?.get_is_dirty())
     }
 
-    pub(crate) fn set_evictable(
-        &mut self,
-        page_id: &PageId,
-        is_evictable: bool,
-        replacer: &mut RwLockWriteGuard<LRUKReplacer>,
-    ) {
-        let frame_id = self
+    /// Returns the number of frames holding no page, across all of the pool's shards.
+    pub fn free_frame_count(&self) -> usize {
+        (self.shards.iter())
+            .map(|shard| shard.frames().free_list.len())
+            .sum()
+    }
+
+    /// Returns every page with a nonzero pin count, with the count, ordered by page ID.
+    pub fn pinned_pages(&self) -> Vec<(PageId, usize)> {
+        let mut pinned: Vec<(PageId, usize)> = (self.shards.iter())
+            .flat_map(|shard| {
+                (shard.frames().page_table.iter())
+                    .filter(|(_, frame_metadata)| frame_metadata.pin_count > 0)
+                    .map(|(page_id, frame_metadata)| (*page_id, frame_metadata.pin_count))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        pinned.sort_unstable();
+        pinned
+    }
+
+    /// Describes the pages still pinned, one per line, with how many times their frame was
+    /// pinned and unpinned over the pool's lifetime. Returns `None` if no page is pinned.
+    pub fn pin_leak_report(&self) -> Option<String> {
+        let mut leaks = Vec::new();
+        for (index, shard) in self.shards.iter().enumerate() {
+            let frames = shard.frames();
+            for (page_id, frame_metadata) in &frames.page_table {
+                if frame_metadata.pin_count > 0 {
+                    let frame_pins = frames.frame_pins[frame_metadata.frame_id];
+                    leaks.push((*page_id, *frame_metadata, index, frame_pins));
+                }
+            }
+        }
+        if leaks.is_empty() {
+            return None;
+        }
+        leaks.sort_unstable_by_key(|(page_id, ..)| *page_id);
+
+        let mut report = format!("{} pages of the buffer pool are still pinned:", leaks.len());
+        for (page_id, frame_metadata, shard, frame_pins) in leaks {
+            report.push_str(&format!(
+                "\n  page {page_id}: {} pins, in frame {} of shard {shard}, pinned {} and \
+                 unpinned {} times in all",
+                frame_metadata.pin_count,
+                frame_metadata.frame_id,
+                frame_pins.pins,
+                frame_pins.unpins
+            ));
+        }
+        Some(report)
+    }
+
+    /// Returns the buffer pool counters of [`Self::metrics`], counted since the last
+    /// [`Self::reset_stats`], or since the pool was built.
+    pub fn stats(&self) -> BufferPoolStats {
+        let baseline = self.stats_baseline.lock().unwrap();
+        self.metrics.snapshot().buffer_pool.since(&baseline)
+    }
+
+    /// Starts [`Self::stats`] over from zero. The registry's counters keep counting up.
+    pub fn reset_stats(&self) {
+        *self.stats_baseline.lock().unwrap() = self.metrics.snapshot().buffer_pool;
+    }
+
+    /// Returns the registry counting this buffer pool's activity.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Returns the write-ahead log guarding this buffer pool's page writes.
+    pub fn log_manager(&self) -> Arc<LogManager> {
+        Arc::clone(&self.log_manager)
+    }
+
+    /// Returns the disk manager this buffer pool reads and writes pages with.
+    pub fn disk_manager(&self) -> Arc<RwLock<DiskManager>> {
+        Arc::clone(&self.disk_manager)
+    }
+
+    pub(crate) fn set_is_dirty(&self, page_id: &PageId, is_dirty: bool) {
+        let frames = self.shard(page_id).frames();
+        let frame_id = frames
             .page_table
             .get(page_id)
             .expect(NO_CORRESPONDING_FRAME_ID_MSG)
             .frame_id;
-        replacer.set_evictable(&frame_id, is_evictable);
+        frames.frame(frame_id).unwrap().set_is_dirty(is_dirty);
     }
 }
 
 impl Drop for BufferPoolManager {
+    /// Reports the pages a caller forgot to unpin, see [`Self::pin_leak_report`].
     fn drop(&mut self) {
-        // Code to clean up resources
-        println!("BufferPoolManager is being dropped");
+        let Some(report) = self.pin_leak_report() else {
+            return;
+        };
+        if cfg!(debug_assertions) && self.panic_on_pin_leak && !thread::panicking() {
+            panic!("{report}");
+        }
+        log::warn!("{report}");
+    }
+}
+
+/// The thread started by [`BufferPoolManager::start_background_flusher`]. Dropping it stops the
+/// thread.
+#[derive(Debug)]
+struct BackgroundFlusher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundFlusher {
+    /// Flushes the pool's unpinned pages every `interval` until stopped. The pool is only held
+    /// weakly, as the pool holds the flusher.
+    fn spawn(bpm: Weak<BufferPoolManager>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            let Some(bpm) = bpm.upgrade() else {
+                return;
+            };
+            bpm.flush_unpinned_pages();
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for BackgroundFlusher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        // The pool is dropped by the flusher itself if it held the last reference, in which case
+        // the thread exits by itself, as the channel is closed.
+        if thread.thread().id() != thread::current().id() {
+            thread.join().ok();
+        }
     }
 }
@@ -2,4 +2,9 @@ mod buffer_pool_manager;
 #[cfg(test)]
 mod tests;
 
-pub use buffer_pool_manager::{BufferPoolManager, BufferPoolManagerBuilder, FrameId};
+pub use buffer_pool_manager::{
+    BufferPoolManager, BufferPoolManagerBuilder, CheckpointInfo, FrameId, PreFlushHook,
+    VersionedPageHandle,
+};
+#[cfg(test)]
+pub(crate) use buffer_pool_manager::{Frames, Shard};
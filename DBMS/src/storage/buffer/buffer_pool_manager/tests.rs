@@ -1,21 +1,39 @@
 use super::*;
-use crate::assert_errors;
+use crate::common::{Error, BufferPoolStats, Metrics, ReplacerStats, Result, StatementStats};
+use crate::common::EVICTION_DISTANCE_BUCKETS;
 use crate::common::constants::{INVALID_PID, NEW_PAGE_ERR_MSG, NO_CORRESPONDING_PAGE_MSG};
-use crate::config::config::RUST_DB_DATA_DIR;
-use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::config::config::{
+    RUST_DB_DATA_DIR, RUSTY_DB_PAGE_PAYLOAD_BYTES, RUSTY_DB_PAGE_SIZE_BYTES,
+};
+use crate::errinput;
+use crate::sim::invariants::check_pool;
+use crate::storage::buffer::lru_k_replacer::AccessType;
+use crate::storage::buffer::lru_replacer::LRUReplacer;
+use crate::storage::buffer::replacer::Policy;
+use crate::sim::SimDisk;
+use crate::storage::disk::backend::CountingBackend;
+use crate::storage::disk::fault::FaultInjector;
+use crate::storage::disk::disk_manager::{DiskManager, Durability, PageId};
 use crate::storage::page::RecordId;
-use crate::storage::page::{Page, TablePageHandle};
+use crate::storage::page::{
+    BPlusTreeInternalPage, BPlusTreeLeafPage, KeySchema, Page, TablePage, TablePageHandle,
+};
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
+use crate::types::field::Field;
+use crate::types::DataType;
 use itertools::Itertools;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
 
 #[test]
 fn test_new_page_basic() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
 
     let page_id = bpm.new_page().unwrap();
     let page = get_page_handle(&bpm, &page_id).unwrap();
@@ -27,164 +45,282 @@ fn test_new_page_basic() {
 
     // page inserted into buffer pool, and pinned to prevent eviction.
     assert!(page_in_buffer(&bpm, &page_id));
-    assert_eq!(bpm.get_pin_count(&page_id).unwrap(), 1);
+    assert_eq!(bpm.pin_count(&page_id).unwrap(), 1);
 }
 
 #[test]
 fn test_new_page_no_initial_frames() {
-    let mut bpm = get_bpm_with_pool_size(0);
-    assert!(bpm.new_page().is_none());
+    let bpm = get_bpm_with_pool_size(0);
+    assert!(bpm.new_page().is_err());
 }
 
 #[test]
 fn test_cannot_create_page_beyond_buffer_pool_size() {
-    let mut bpm = get_bpm_with_pool_size(2);
+    let bpm = get_bpm_with_pool_size(2);
 
     // Create and pin two pages.
     let page_id1 = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
     let page_id2 = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
 
-    bpm.fetch_page(&page_id1);
-    bpm.fetch_page(&page_id2);
+    bpm.fetch_page(&page_id1).unwrap();
+    bpm.fetch_page(&page_id2).unwrap();
 
     // All frames are now pinned, attempt to create another page.
     let result = bpm.new_page();
-    assert!(result.is_none());
+    assert!(matches!(result, Err(Error::NoEvictableFrame { .. })));
+}
+
+#[test]
+fn test_new_page_no_evictable_frame() {
+    let pool_size = 3_usize;
+    let bpm = get_bpm_with_pool_size(pool_size);
+    let page_ids: Vec<PageId> =
+        (0..pool_size).map(|_| bpm.new_page().expect(NEW_PAGE_ERR_MSG)).collect();
+
+    let err = bpm.new_page().unwrap_err();
+    let Error::NoEvictableFrame { pinned, pool_size: size, pinned_pages } = &err else {
+        panic!("unexpected error {err:?}");
+    };
+    assert_eq!(*pinned, pool_size);
+    assert_eq!(*size, pool_size);
+    if cfg!(debug_assertions) {
+        assert_eq!(pinned_pages, &page_ids);
+    }
+    assert!(err.to_string().starts_with("no buffer pool frame available, 3 of 3 are pinned"));
+    assert!(matches!(bpm.fetch_page(&(page_ids[2] + 1)), Err(Error::NoEvictableFrame { .. })));
+
+    // Once a page is unpinned, its frame can be reused.
+    bpm.unpin_page(&page_ids[0], false).unwrap();
+    assert!(bpm.new_page().is_ok());
 }
 
 #[test]
 fn test_new_page_evict_frame() {
     let pool_size = 3_usize;
-    let mut bpm = get_bpm_with_pool_size(pool_size);
+    let bpm = get_bpm_with_pool_size(pool_size);
 
     let mut new_page_id: Option<PageId> = None;
     for _ in 0..pool_size {
-        assert!(!bpm.free_list.is_empty());
-        new_page_id = bpm.new_page();
+        assert!(!bpm.shards[0].frames().free_list.is_empty());
+        new_page_id = bpm.new_page().ok();
         assert!(new_page_id.is_some());
     }
 
     // free list empty, and no evictable page.
-    assert!(bpm.free_list.is_empty());
-    assert!(bpm.new_page().is_none());
+    assert!(bpm.shards[0].frames().free_list.is_empty());
+    assert!(bpm.new_page().is_err());
 
     // free list empty, but there's an evictable page.
     let page_id_to_evict = &new_page_id.unwrap();
-    {
-        let binding = bpm.replacer.clone();
-        let mut replacer = binding.write().unwrap();
-        bpm.set_evictable(page_id_to_evict, true, &mut replacer);
-    }
-    assert!(bpm.free_list.is_empty());
+    bpm.unpin_page(page_id_to_evict, false).unwrap();
+    assert!(bpm.shards[0].frames().free_list.is_empty());
     let new_page_after_eviction = bpm.new_page();
-    assert!(new_page_after_eviction.is_some());
+    assert!(new_page_after_eviction.is_ok());
 
-    assert!(bpm.free_list.is_empty());
-    assert!(bpm.new_page().is_none());
+    assert!(bpm.shards[0].frames().free_list.is_empty());
+    assert!(bpm.new_page().is_err());
 }
 
 #[test]
 fn test_fetch_page_in_buffer() {
     let pool_size = 10_usize;
-    let mut bpm = get_bpm_with_pool_size(pool_size);
+    let bpm = get_bpm_with_pool_size(pool_size);
 
-    let page_ids = create_n_pages(&mut bpm, pool_size);
+    let page_ids = create_n_pages(&bpm, pool_size);
     page_ids
         .iter()
-        .for_each(|&page_id| assert_eq!(fetch_page_get_id(&page_id, &mut bpm), page_id));
+        .for_each(|&page_id| assert_eq!(fetch_page_get_id(&page_id, &bpm), page_id));
 }
 
 /// This test assumes [`super::BufferPoolManager::unpin_page`] functions properly.
 #[test]
 fn test_fetch_page_not_in_buffer() {
     let pool_size = 10_usize;
-    let mut bpm = get_bpm_with_pool_size(pool_size);
+    let bpm = get_bpm_with_pool_size(pool_size);
 
     // fill buffer pool to capacity with new page.
     let page_id_to_evict = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
-    bpm.unpin_page(&page_id_to_evict, false);
-    create_n_pages(&mut bpm, pool_size - 1);
+    bpm.unpin_page(&page_id_to_evict, false).unwrap();
+    create_n_pages(&bpm, pool_size - 1);
 
     // and add another page.
     let another_page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
-    bpm.unpin_page(&another_page_id, false); // for the fetch_page later
+    bpm.unpin_page(&another_page_id, false).unwrap(); // for the fetch_page later
 
     // verify a page was evicted for the new page.
-    assert!(!bpm.page_table.contains_key(&page_id_to_evict));
+    assert!(!bpm.shards[0].frames().page_table.contains_key(&page_id_to_evict));
 
     // ...we should still be able to fetch that evicted page (from disk).
     assert_eq!(
-        fetch_page_get_id(&page_id_to_evict, &mut bpm),
+        fetch_page_get_id(&page_id_to_evict, &bpm),
         page_id_to_evict
     );
 
     // another fetch of that page (this time from the buffer pool!)
     assert_eq!(
-        fetch_page_get_id(&page_id_to_evict, &mut bpm),
+        fetch_page_get_id(&page_id_to_evict, &bpm),
         page_id_to_evict
     );
 }
 
 #[test]
 fn test_unpin_page_changes_dirty_flag() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
+    let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+
+    assert_eq!(Some(false), bpm.is_dirty(&page_id));
+    bpm.unpin_page(&page_id, true).unwrap();
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+}
+
+/// Two executors share a page: the one that modified it unpins first, and a clean unpin by the
+/// other one mustn't lose the modification.
+#[test]
+fn test_clean_unpin_keeps_dirty_flag() {
+    let disk_manager = new_disk_manager();
+    let bpm = BufferPoolManager::new(5, 5, Arc::clone(&disk_manager));
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    bpm.unpin_page(&page_id, false).unwrap();
+    let tuple = Tuple::from(&b"Northwestern"[..]);
 
-    assert!(!bpm.get_is_dirty(&page_id));
-    assert!(bpm.unpin_page(&page_id, true));
-    assert!(bpm.get_is_dirty(&page_id));
+    // One pin each.
+    let page = bpm.fetch_page(&page_id).unwrap();
+    assert!(bpm.fetch_page(&page_id).is_ok());
+    assert_eq!(Some(2), bpm.pin_count(&page_id));
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let metadata = TupleMetadata::new(false);
+                page.write().unwrap().insert_tuple(metadata, tuple.clone());
+                bpm.unpin_page(&page_id, true).unwrap();
+            })
+            .join()
+            .unwrap();
+        scope
+            .spawn(|| {
+                bpm.unpin_page(&page_id, false).unwrap();
+            })
+            .join()
+            .unwrap();
+    });
+
+    assert_eq!(Some(0), bpm.pin_count(&page_id));
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+    assert!(bpm.flush_page(&page_id).unwrap());
+    assert_eq!(Some(false), bpm.is_dirty(&page_id));
+    let page_on_disk = disk_manager.write().unwrap().read_page(&page_id).unwrap();
+    let rid = RecordId::new(page_id, 0);
+    assert_eq!(tuple, page_on_disk.get_tuple(&rid).unwrap());
 }
 
 #[test]
 fn test_unpin_page_not_in_buffer_pool() {
-    let mut bpm = get_bpm_with_pool_size(0);
+    let bpm = get_bpm_with_pool_size(0);
     // buffer pool is empty
-    assert_errors!(bpm.unpin_page(&INVALID_PID, false));
+    assert_eq!(
+        bpm.unpin_page(&INVALID_PID, false),
+        Err(Error::PageNotInPool(INVALID_PID))
+    );
 }
 
 /// This tests assumes [`super::BufferPoolManager::delete_page`] functions properly.
 #[test]
 fn test_unpin_page_before_and_after_deletion() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
 
     // Pin count: 1
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
 
     // Pin count: 0
-    assert!(bpm.unpin_page(&page_id, false));
+    bpm.unpin_page(&page_id, false).unwrap();
 
     // Pin count: still 0
-    assert!(!bpm.unpin_page(&page_id, false));
-    assert!(bpm.delete_page(page_id));
+    assert_eq!(
+        bpm.unpin_page(&page_id, false),
+        Err(Error::PageNotPinned(page_id))
+    );
+    assert_eq!(bpm.pin_count(&page_id), Some(0));
+    assert!(bpm.delete_page(page_id).unwrap());
+
+    // The page is gone
+    assert_eq!(
+        bpm.unpin_page(&page_id, false),
+        Err(Error::PageNotInPool(page_id))
+    );
 }
 
 /// This tests assumes [`super::BufferPoolManager::fetch_page`] properly increments pin count.
 #[test]
 fn test_unpin_page_decrements_multiple_times() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
 
     // Pin count: 1
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
     // Pin count: 26
     for _ in 0..25 {
-        bpm.fetch_page(&page_id);
+        bpm.fetch_page(&page_id).unwrap();
     }
-    assert_eq!(bpm.get_pin_count(&page_id).unwrap(), 26);
+    assert_eq!(bpm.pin_count(&page_id).unwrap(), 26);
 
     // Pin count: 25 -> 24 -> ... -> 0
     for i in (0..26).rev() {
-        assert!(bpm.unpin_page(&page_id, false));
-        assert_eq!(bpm.get_pin_count(&page_id).unwrap(), i);
+        bpm.unpin_page(&page_id, false).unwrap();
+        assert_eq!(bpm.pin_count(&page_id).unwrap(), i);
     }
 }
 
+/// A page a caller forgot to unpin shows up in the pinned pages, and in the report logged when
+/// the pool is dropped, along with its frame's pins and unpins.
+#[test]
+fn test_pin_leak_report() {
+    let bpm = get_bpm_with_pool_size(4);
+    let [released, leaked, other] = create_n_pages(&bpm, 3)[..] else {
+        unreachable!();
+    };
+    bpm.unpin_page(&released, false).unwrap();
+    fetch_page(&leaked, &bpm);
+    fetch_page(&leaked, &bpm);
+    bpm.unpin_page(&leaked, false).unwrap();
+    assert_eq!(vec![(leaked, 2), (other, 1)], bpm.pinned_pages());
+
+    let report = bpm.pin_leak_report().unwrap();
+    assert!(report.starts_with("2 pages of the buffer pool are still pinned"), "{report}");
+    let line = format!("page {leaked}: 2 pins, in frame 1 of shard 0, pinned 3 and unpinned 1");
+    assert!(report.contains(&line), "{report}");
+    assert!(report.contains(&format!("page {other}: 1 pins")), "{report}");
+    assert!(!report.contains(&format!("page {released}:")), "{report}");
+
+    bpm.unpin_page(&other, false).unwrap();
+    bpm.unpin_page(&leaked, false).unwrap();
+    bpm.unpin_page(&leaked, false).unwrap();
+    assert!(bpm.pinned_pages().is_empty());
+    assert_eq!(None, bpm.pin_leak_report());
+}
+
+/// Built to, the pool panics when dropped with a page still pinned, in debug builds.
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "1 pins, in frame 0 of shard 0"))]
+fn test_panic_on_pin_leak() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .panic_on_pin_leak(true)
+        .build();
+    bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    drop(bpm);
+}
+
 #[test]
 fn test_flush_page_does_not_exist() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
     let different_page_id = page_id + 1;
 
-    assert_errors!(bpm.flush_page(&different_page_id));
+    assert_eq!(
+        bpm.flush_page(&different_page_id),
+        Err(Error::PageNotInPool(different_page_id))
+    );
 }
 
 #[test]
@@ -194,18 +330,14 @@ fn test_flush_page() {
 
     // should be able to flush page regardless of is_dirty flag
     [true, false].iter().for_each(|&is_dirty| {
-        let mut bpm = BufferPoolManager::builder()
+        let bpm = BufferPoolManager::builder()
             .pool_size(5)
             .disk_manager(disk_manager.clone())
             .replacer_k(5)
             .build();
         let unevictable_page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
         let evictable_page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
-        {
-            let binding = bpm.replacer.clone();
-            let mut replacer = binding.write().unwrap();
-            bpm.set_evictable(&evictable_page_id, true, &mut replacer);
-        }
+        bpm.unpin_page(&evictable_page_id, false).unwrap();
 
         // Insert a tuple into both pages
         let metadata = TupleMetadata::new(false);
@@ -229,12 +361,12 @@ fn test_flush_page() {
         bpm.set_is_dirty(&unevictable_page_id, is_dirty);
         bpm.set_is_dirty(&evictable_page_id, is_dirty);
 
-        bpm.flush_page(&unevictable_page_id);
-        bpm.flush_page(&evictable_page_id);
+        bpm.flush_page(&unevictable_page_id).unwrap();
+        bpm.flush_page(&evictable_page_id).unwrap();
 
         // is_dirty flag should be reset to false after page flush
-        assert!(!bpm.get_is_dirty(&unevictable_page_id));
-        assert!(!bpm.get_is_dirty(&evictable_page_id));
+        assert_eq!(Some(false), bpm.is_dirty(&unevictable_page_id));
+        assert_eq!(Some(false), bpm.is_dirty(&evictable_page_id));
 
         // Initialize another instance of disk_manager
         let disk_manager = DiskManager::new_with_handle(&file_name);
@@ -242,7 +374,7 @@ fn test_flush_page() {
         // Fetch the tuple from disk to ensure it was stored correctly
         let mut dm = disk_manager.write().unwrap();
         let record_id_unevictable = RecordId::new(unevictable_page_id, 0);
-        let retrieved_unevictable_page = dm.read_page(&unevictable_page_id);
+        let retrieved_unevictable_page = dm.read_page(&unevictable_page_id).unwrap();
         let retrieved_tuple_unevictable = retrieved_unevictable_page
             .get_tuple(&record_id_unevictable)
             .unwrap();
@@ -250,7 +382,7 @@ fn test_flush_page() {
 
         // Fetch and verify the tuple from the evictable page
         let record_id_evictable = RecordId::new(evictable_page_id, 0);
-        let retrieved_evictable_page = dm.read_page(&evictable_page_id);
+        let retrieved_evictable_page = dm.read_page(&evictable_page_id).unwrap();
         let retrieved_tuple_evictable = retrieved_evictable_page
             .get_tuple(&record_id_evictable)
             .unwrap();
@@ -264,13 +396,13 @@ fn test_flush_all_pages() {
     let file_name = create_temp_file();
 
     let disk_manager = DiskManager::new_with_handle(&file_name);
-    let mut bpm = BufferPoolManager::builder()
+    let bpm = BufferPoolManager::builder()
         .pool_size(pool_size)
         .disk_manager(disk_manager)
         .replacer_k(5)
         .build();
 
-    let page_ids: Vec<PageId> = create_n_pages(&mut bpm, pool_size);
+    let page_ids: Vec<PageId> = create_n_pages(&bpm, pool_size);
 
     let metadata = TupleMetadata::new(false);
 
@@ -281,12 +413,12 @@ fn test_flush_all_pages() {
         let _slot = page.write().unwrap().insert_tuple(metadata.clone(), tuple);
     });
 
-    set_pages_to_dirty(&mut bpm, &page_ids);
+    set_pages_to_dirty(&bpm, &page_ids);
 
     // Ensure pages are not marked as dirty after flush.
+    assert_eq!(pool_size, bpm.flush_all_pages());
     page_ids.iter().for_each(|page_id| {
-        bpm.flush_page(page_id);
-        assert!(!bpm.get_is_dirty(page_id));
+        assert_eq!(Some(false), bpm.is_dirty(page_id));
     });
 
     // Fetch the page from disk, and ensures that the tuple is correct.
@@ -295,55 +427,662 @@ fn test_flush_all_pages() {
     page_ids.iter().enumerate().for_each(|(i, page_id)| {
         let record_id = RecordId::new(*page_id, 0);
         let mut dm = disk_manager.write().unwrap();
-        let retrieved_page = dm.read_page(page_id);
+        let retrieved_page = dm.read_page(page_id).unwrap();
         let retrieved_tuple = retrieved_page.get_tuple(&record_id).unwrap();
         let expected_tuple = Tuple::from((i as u8..=(i + 4) as u8).collect_vec());
         assert_eq!(retrieved_tuple, expected_tuple);
     });
 }
 
+/// Only dirty pages are written, however many threads write them.
+#[test]
+fn test_flush_all_pages_skips_clean_pages() {
+    for threads in [1, 3, 16] {
+        let bpm = get_bpm_with_pool_size(8);
+        let metrics = bpm.metrics();
+        let page_ids = create_n_unpinned_pages(&bpm, 8);
+        let dirty: Vec<PageId> = page_ids.iter().copied().filter(page_number_is_even).collect();
+        for page_id in &dirty {
+            let page = fetch_page(page_id, &bpm);
+            let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+            page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+            bpm.unpin_page(page_id, true).unwrap();
+        }
+
+        let written = metrics.snapshot().disk.pages_written;
+        assert_eq!(dirty.len(), bpm.flush_all_pages_parallel(threads));
+        assert_eq!(
+            written + dirty.len() as u64,
+            metrics.snapshot().disk.pages_written
+        );
+        assert!(page_ids.iter().all(|page_id| bpm.is_dirty(page_id) == Some(false)));
+        for page_id in &dirty {
+            let page = bpm.disk_manager.write().unwrap().read_page(page_id).unwrap();
+            let tuple = page.get_tuple(&RecordId::new(*page_id, 0)).unwrap();
+            assert_eq!(Tuple::from(page_id.to_le_bytes().as_slice()), tuple);
+        }
+
+        // With every page clean, there's nothing left to write.
+        assert_eq!(0, bpm.flush_all_pages());
+        assert_eq!(
+            written + dirty.len() as u64,
+            metrics.snapshot().disk.pages_written
+        );
+    }
+}
+
+/// Contiguous dirty pages are flushed with a write for the lot, not one each.
+#[test]
+fn test_flush_all_pages_coalesces_writes() {
+    let backend = CountingBackend::default();
+    let writes = backend.writes();
+    let disk_manager = DiskManager::new_with_backend_for_test(Box::new(backend));
+    let bpm = BufferPoolManager::new(64, 2, disk_manager.into_handle());
+    let page_ids = create_n_unpinned_pages(&bpm, 64);
+    for page_id in &page_ids {
+        let page = fetch_page(page_id, &bpm);
+        let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+        page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+        bpm.unpin_page(page_id, true).unwrap();
+    }
+
+    let before = writes.load(Ordering::SeqCst);
+    assert_eq!(64, bpm.flush_all_pages_parallel(1));
+    assert_eq!(before + 1, writes.load(Ordering::SeqCst));
+    assert!(page_ids.iter().all(|page_id| bpm.is_dirty(page_id) == Some(false)));
+    let read = bpm.disk_manager.write().unwrap().read_pages(&page_ids);
+    for (page_id, page) in page_ids.iter().zip(read) {
+        let tuple = page.unwrap().get_tuple(&RecordId::new(*page_id, 0)).unwrap();
+        assert_eq!(Tuple::from(page_id.to_le_bytes().as_slice()), tuple);
+    }
+}
+
+/// Flushes sync the pages they write once at the end in manual mode, whereas in the other modes
+/// the disk manager syncs by itself.
+#[test]
+fn test_flush_syncs_in_manual_mode() {
+    let modes = [(Durability::Manual, [0, 1, 2]), (Durability::Always, [2, 3, 4])];
+    for (durability, expected) in modes {
+        let disk_manager = DiskManager::new_in_memory().with_durability(durability);
+        let bpm = BufferPoolManager::builder()
+            .pool_size(4)
+            .replacer_k(2)
+            .disk_manager(disk_manager.into_handle())
+            .metrics(Arc::default())
+            .build();
+        let metrics = bpm.metrics();
+        let page_ids = create_n_unpinned_pages(&bpm, 2);
+        for page_id in &page_ids {
+            let page = fetch_page(page_id, &bpm);
+            let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+            page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+            bpm.unpin_page(page_id, true).unwrap();
+        }
+
+        let syncs = || metrics.snapshot().disk.syncs;
+        assert_eq!(expected[0], syncs(), "{durability:?}");
+        assert!(bpm.flush_page(&page_ids[0]).unwrap());
+        assert_eq!(expected[1], syncs(), "{durability:?}");
+        assert_eq!(1, bpm.flush_all_pages());
+        assert_eq!(expected[2], syncs(), "{durability:?}");
+
+        // Nothing was written since, in any mode.
+        assert_eq!(0, bpm.flush_all_pages());
+        bpm.request_sync().unwrap();
+        assert_eq!(expected[2], syncs(), "{durability:?}");
+    }
+}
+
+/// With async writes, evictions queue the dirty pages, which are read back as they were evicted,
+/// and a checkpoint writes whatever is still queued.
+#[test]
+fn test_evictions_queue_async_writes() {
+    let disk_manager = DiskManager::new_in_memory().with_async_writes(16).into_handle();
+    let bpm = BufferPoolManager::new(2, 2, Arc::clone(&disk_manager));
+    let page_ids = create_n_unpinned_pages(&bpm, 6);
+    for _ in 0..3 {
+        for page_id in &page_ids {
+            let page = fetch_page(page_id, &bpm);
+            let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+            page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+            bpm.unpin_page(page_id, true).unwrap();
+        }
+    }
+    let tuples = |page: &TablePage| {
+        let slots = 0..3;
+        (slots.map(|slot| page.get_tuple(&RecordId::new(page.page_id, slot)).unwrap()))
+            .collect::<Vec<_>>()
+    };
+    let expected = |page_id: &PageId| vec![Tuple::from(page_id.to_le_bytes().as_slice()); 3];
+    for page_id in &page_ids {
+        let page = fetch_page(page_id, &bpm);
+        assert_eq!(expected(page_id), tuples(&page.read().unwrap()));
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+
+    bpm.checkpoint().unwrap();
+    let on_disk = disk_manager.write().unwrap().read_pages(&page_ids);
+    for (page_id, page) in page_ids.iter().zip(on_disk) {
+        assert_eq!(expected(page_id), tuples(&page.unwrap()));
+    }
+}
+
+/// A checkpoint writes back the dirty, unpinned pages and skips the pinned one, which stays dirty
+/// for the next checkpoint.
+#[test]
+fn test_checkpoint() {
+    let bpm = get_bpm_with_pool_size(8);
+    let page_ids = create_n_unpinned_pages(&bpm, 8);
+    let dirty = vec![page_ids[1], page_ids[2], page_ids[4], page_ids[5], page_ids[7]];
+    set_pages_to_dirty(&bpm, &dirty);
+    assert_eq!(dirty, bpm.dirty_page_ids());
+
+    let pinned = page_ids[4];
+    fetch_page(&pinned, &bpm);
+    let info = bpm.checkpoint().unwrap();
+    let flushed = info.flushed.iter().map(|(page_id, _)| *page_id).collect_vec();
+    let skipped = info.skipped.iter().map(|(page_id, _)| *page_id).collect_vec();
+    assert_eq!(vec![dirty[0], dirty[1], dirty[3], dirty[4]], flushed);
+    assert_eq!(vec![pinned], skipped);
+    for page_id in &flushed {
+        assert_eq!(Some(false), bpm.is_dirty(page_id));
+    }
+    assert_eq!(Some(true), bpm.is_dirty(&pinned));
+    assert_eq!(vec![pinned], bpm.dirty_page_ids());
+
+    // Once unpinned, the skipped page is written by the next checkpoint, along with a page dirtied
+    // since.
+    bpm.unpin_page(&pinned, false).unwrap();
+    bpm.set_is_dirty(&page_ids[0], true);
+    let info = bpm.checkpoint().unwrap();
+    let flushed = info.flushed.iter().map(|(page_id, _)| *page_id).collect_vec();
+    assert_eq!(vec![page_ids[0], pinned], flushed);
+    assert!(info.skipped.is_empty());
+    assert!(bpm.dirty_page_ids().is_empty());
+}
+
+/// The pre-flush hook sees every table page written back, by an eviction, an explicit flush or
+/// `flush_all_pages`, and a failing hook keeps the page from being written.
+#[test]
+fn test_pre_flush_hook() {
+    let hooked = Arc::new(Mutex::new(Vec::new()));
+    let record = Arc::clone(&hooked);
+    let bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .metrics(Arc::default())
+        .pre_flush_hook(Box::new(move |page| {
+            record.lock().unwrap().push(page.page_id);
+            Ok(())
+        }))
+        .build();
+    let page_ids = create_n_unpinned_pages(&bpm, 2);
+    set_pages_to_dirty(&bpm, &page_ids);
+
+    // Evicts the first page, writing it back.
+    let new_page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert_eq!(vec![page_ids[0]], *hooked.lock().unwrap());
+
+    assert!(bpm.flush_page(&page_ids[1]).unwrap());
+    assert_eq!(vec![page_ids[0], page_ids[1]], *hooked.lock().unwrap());
+
+    bpm.set_is_dirty(&page_ids[1], true);
+    bpm.set_is_dirty(&new_page_id, true);
+    hooked.lock().unwrap().clear();
+    assert_eq!(2, bpm.flush_all_pages());
+    assert_eq!(
+        vec![page_ids[1], new_page_id],
+        hooked.lock().unwrap().iter().copied().sorted().collect_vec()
+    );
+
+    // A failing hook aborts the writes, leaving the pages dirty and resident.
+    bpm.set_pre_flush_hook(Box::new(|_| Err(Error::IO("log unavailable".to_string()))));
+    bpm.set_is_dirty(&page_ids[1], true);
+    bpm.unpin_page(&new_page_id, true).unwrap();
+    assert_eq!(
+        Err(Error::IO("log unavailable".to_string())),
+        bpm.flush_page(&page_ids[1])
+    );
+    assert_eq!(0, bpm.flush_all_pages());
+    assert!(bpm.new_page().is_err());
+    assert!(page_in_buffer(&bpm, &page_ids[1]) && page_in_buffer(&bpm, &new_page_id));
+    assert_eq!(Some(true), bpm.is_dirty(&page_ids[1]));
+    assert_eq!(Some(true), bpm.is_dirty(&new_page_id));
+    assert_eq!(2, bpm.shards[0].replacer.read().unwrap().size());
+    check_pool(&bpm).unwrap();
+}
+
+/// With the disk slow to write, evictions wait on a write for every dirty page, unless the
+/// background flusher has cleaned the pages first.
+#[test]
+fn test_background_flusher_cleans_victims() {
+    for background in [false, true] {
+        let disk = SimDisk::new();
+        let bpm = BufferPoolManager::builder()
+            .pool_size(8)
+            .replacer_k(2)
+            .disk_manager(Arc::new(RwLock::new(disk.disk_manager())))
+            .metrics(Arc::default())
+            .build_with_handle();
+        let page_ids = create_n_unpinned_pages(&bpm, 16);
+        let (evicted, resident) = page_ids.split_at(8);
+        for page_id in resident {
+            let page = fetch_page(page_id, &bpm);
+            let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+            page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+            bpm.unpin_page(page_id, true).unwrap();
+        }
+        disk.set_write_latency(Duration::from_millis(2));
+
+        if background {
+            BufferPoolManager::start_background_flusher(&bpm, Duration::from_millis(1));
+            let start = Instant::now();
+            while resident.iter().any(|page_id| bpm.is_dirty(page_id) == Some(true)) {
+                assert!(start.elapsed() < Duration::from_secs(10), "Pages never flushed");
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        // The fetched pages stay pinned, so that every resident page is evicted.
+        bpm.reset_stats();
+        for page_id in evicted {
+            fetch_page(page_id, &bpm);
+        }
+
+        let stats = bpm.stats();
+        assert_eq!(8, stats.evictions);
+        assert_eq!(if background { 0 } else { 8 }, stats.writebacks);
+        for page_id in resident {
+            let page = disk.disk_manager().read_page(page_id).unwrap();
+            let tuple = page.get_tuple(&RecordId::new(*page_id, 0)).unwrap();
+            assert_eq!(Tuple::from(page_id.to_le_bytes().as_slice()), tuple);
+        }
+    }
+}
+
+/// The flusher leaves pinned pages alone, and stops when asked to or when the pool is dropped.
+#[test]
+fn test_background_flusher_stops() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let pinned = bpm.new_page().unwrap();
+    bpm.set_is_dirty(&pinned, true);
+    let unpinned = create_n_unpinned_pages(&bpm, 1)[0];
+    bpm.set_is_dirty(&unpinned, true);
+
+    BufferPoolManager::start_background_flusher(&bpm, Duration::from_millis(1));
+    let start = Instant::now();
+    while bpm.is_dirty(&unpinned) == Some(true) {
+        assert!(start.elapsed() < Duration::from_secs(10), "Page never flushed");
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(Some(true), bpm.is_dirty(&pinned));
+
+    bpm.stop_background_flusher();
+    bpm.set_is_dirty(&unpinned, true);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(Some(true), bpm.is_dirty(&unpinned));
+
+    // Dropping the pool stops its flusher, whichever thread drops it last.
+    BufferPoolManager::start_background_flusher(&bpm, Duration::from_millis(1));
+    thread::sleep(Duration::from_millis(5));
+    drop(bpm);
+}
+
+/// Test that writes failing on a read-only file come back as errors, and leave the pool as it was.
+#[test]
+fn test_read_only_disk() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let page_id = DiskManager::open(temp_file.path()).unwrap().allocate_new_page().unwrap();
+    let disk_manager = DiskManager::open_read_only_for_test(temp_file.path()).unwrap();
+    let bpm = BufferPoolManager::builder()
+        .pool_size(1)
+        .replacer_k(2)
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .build();
+
+    // Pages can be read, but not written back.
+    let page = bpm.fetch_page(&page_id).unwrap();
+    page.write().unwrap().insert_tuple(TupleMetadata::new(false), Tuple::from(vec![1, 2, 3]));
+    bpm.unpin_page(&page_id, true).unwrap();
+    assert!(matches!(bpm.flush_page(&page_id), Err(Error::IO(_))));
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+    assert_eq!(0, bpm.flush_all_pages());
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+
+    // Nor can the page be evicted for a new one, which couldn't be allocated anyway.
+    assert!(matches!(bpm.new_page(), Err(Error::NoEvictableFrame { .. })));
+    assert!(page_in_buffer(&bpm, &page_id));
+    bpm.delete_page(page_id).unwrap_err();
+    assert!(matches!(bpm.new_page(), Err(Error::IO(_))));
+    assert_eq!(bpm.size(), bpm.free_frame_count());
+}
+
+/// Test that a page the file ends partway through fails to be fetched, with its frame left free.
+#[test]
+fn test_truncated_disk() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let page_ids: Vec<PageId> = {
+        let mut disk_manager = DiskManager::open(temp_file.path()).unwrap();
+        (0..2).map(|_| disk_manager.allocate_new_page().unwrap()).collect()
+    };
+    let file = temp_file.as_file();
+    let page_size = RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let len = (page_ids[1] as u64 + 1) * page_size - page_size / 2;
+    file.set_len(len).unwrap();
+    let bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(Arc::new(RwLock::new(DiskManager::open(temp_file.path()).unwrap())))
+        .build();
+
+    let short_read = Error::ShortRead {
+        page_id: page_ids[1],
+        len: RUSTY_DB_PAGE_SIZE_BYTES / 2,
+    };
+    assert_eq!(Err(short_read), bpm.fetch_page(&page_ids[1]).map(|_| ()));
+    assert_eq!(2, bpm.free_frame_count());
+    let pages = bpm.fetch_pages(&page_ids);
+    assert!(pages[0].is_some() && pages[1].is_none());
+    assert_eq!(1, bpm.free_frame_count());
+}
+
+/// Test that the disk statistics the pool passes through count the pages it writes back and
+/// reads in.
+#[test]
+fn test_disk_stats() {
+    let bpm = get_bpm_with_pool_size(1);
+    let first = bpm.new_page().unwrap();
+    bpm.unpin_page(&first, true).unwrap();
+    let second = bpm.new_page().unwrap();
+    bpm.unpin_page(&second, true).unwrap();
+    let before = bpm.disk_stats();
+    assert_eq!(2, before.allocated_pages);
+
+    // Fetching the first page back evicts the second, written back as it is dirty.
+    bpm.fetch_page(&first).unwrap();
+    bpm.unpin_page(&first, false).unwrap();
+    let stats = bpm.disk_stats();
+    assert_eq!(before.pages_written + 1, stats.pages_written);
+    assert_eq!(before.pages_read + 1, stats.pages_read);
+    assert_eq!(before.bytes_read + RUSTY_DB_PAGE_SIZE_BYTES as u64, stats.bytes_read);
+}
+
+/// Test that deleting pages at once removes those resident from the pool before freeing them
+/// all on disk, and deletes none while any is pinned.
+#[test]
+fn test_delete_pages() {
+    let bpm = get_bpm_with_pool_size(4);
+    let page_ids: Vec<_> = (0..6)
+        .map(|_| {
+            let page_id = bpm.new_page().unwrap();
+            bpm.unpin_page(&page_id, true).unwrap();
+            page_id
+        })
+        .collect();
+    let pinned = bpm.fetch_page(&page_ids[5]).unwrap();
+    assert!(!bpm.delete_pages(&page_ids).unwrap());
+    assert!(page_in_buffer(&bpm, &page_ids[5]));
+    assert_eq!(0, bpm.disk_manager.read().unwrap().free_pages().count());
+    drop(pinned);
+    bpm.unpin_page(&page_ids[5], false).unwrap();
+
+    assert!(bpm.delete_pages(&page_ids).unwrap());
+    assert!(page_ids.iter().all(|page_id| !page_in_buffer(&bpm, page_id)));
+    let free_pages: Vec<_> = bpm.disk_manager.read().unwrap().free_pages().collect();
+    assert_eq!(page_ids, free_pages);
+    assert_eq!(page_ids[0], bpm.new_page().unwrap());
+}
+
+/// Test that compacting the disk under the pool leaves the pages resident where they are, and
+/// moves the others.
+#[test]
+fn test_compact_disk_keeps_resident_pages() {
+    let bpm = get_bpm_with_pool_size(1);
+    let page_ids: Vec<_> = (0..6)
+        .map(|_| {
+            let page_id = bpm.new_page().unwrap();
+            bpm.unpin_page(&page_id, false).unwrap();
+            page_id
+        })
+        .collect();
+    bpm.flush_all_pages();
+    bpm.delete_page(page_ids[0]).unwrap();
+    bpm.delete_page(page_ids[1]).unwrap();
+    let resident = page_ids[5];
+    let page = bpm.fetch_page(&resident).unwrap();
+    page.write().unwrap().insert_tuple(TupleMetadata::new(false), Tuple::from(&b"Stays"[..]));
+    bpm.unpin_page(&resident, true).unwrap();
+
+    // The resident page is last, so the free pages moved from before it can't be dropped.
+    let mut moves = Vec::new();
+    assert_eq!(0, bpm.compact_disk(&mut |from, to| moves.push((from, to))).unwrap());
+    assert_eq!(vec![(page_ids[4], page_ids[0]), (page_ids[3], page_ids[1])], moves);
+    assert!(page_in_buffer(&bpm, &resident));
+    let page = bpm.fetch_page(&resident).unwrap();
+    let tuple = page.read().unwrap().get_tuple(&RecordId::new(resident, 0)).unwrap();
+    assert_eq!(Tuple::from(&b"Stays"[..]), tuple);
+    bpm.unpin_page(&resident, false).unwrap();
+    assert!(bpm.fetch_page(&page_ids[0]).is_ok());
+}
+
+/// Test that a flush whose write fails returns the error, and leaves the page dirty for the next
+/// flush to write.
+#[test]
+fn test_flush_failed_write_keeps_page_dirty() {
+    let injector = FaultInjector::new();
+    let disk_manager = DiskManager::new_in_memory().with_fault_injector(injector.clone());
+    let bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .build();
+    let page_id = bpm.new_page().unwrap();
+    let page = get_page_handle(&bpm, &page_id).unwrap();
+    page.write().unwrap().insert_tuple(TupleMetadata::new(false), Tuple::from(&b"Dirty"[..]));
+    bpm.unpin_page(&page_id, true).unwrap();
+
+    injector.fail_nth_write(1);
+    assert!(matches!(bpm.flush_page(&page_id), Err(Error::IO(_))));
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+
+    let writes = injector.writes();
+    assert!(bpm.flush_page(&page_id).unwrap());
+    assert_eq!(Some(false), bpm.is_dirty(&page_id));
+    assert_eq!(writes + 1, injector.writes());
+}
+
+/// Test that a page never allocated, or deallocated since, fails to be fetched with its frame
+/// left free, while an allocated one is read back from disk whole.
+#[test]
+fn test_fetch_unallocated_page() {
+    let bpm = get_bpm_with_pool_size(1);
+    let page_id = bpm.new_page().unwrap();
+    let tuple = Tuple::from(&b"Allocated"[..]);
+    let page = get_page_handle(&bpm, &page_id).unwrap();
+    page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple.clone());
+    bpm.unpin_page(&page_id, true).unwrap();
+    // Evicts the first page, writing it back.
+    let deleted = bpm.new_page().unwrap();
+    bpm.unpin_page(&deleted, false).unwrap();
+    assert!(bpm.delete_page(deleted).unwrap());
+    assert_eq!(1, bpm.free_frame_count());
+
+    for unallocated in [deleted, deleted + 1, INVALID_PID - 1] {
+        let result = bpm.fetch_page(&unallocated).map(|_| ());
+        assert_eq!(Err(Error::PageNotAllocated(unallocated)), result);
+        assert!(!page_in_buffer(&bpm, &unallocated));
+        assert_eq!(1, bpm.free_frame_count());
+    }
+    assert!(bpm.fetch_pages(&[deleted]).iter().all(Option::is_none));
+
+    let page = bpm.fetch_page(&page_id).unwrap();
+    assert_eq!(tuple, page.read().unwrap().get_tuple(&RecordId::new(page_id, 0)).unwrap());
+}
+
 #[test]
 fn test_delete_page_does_not_exist() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
     let page_id = bpm
         .new_page()
         .expect("There was an error creating a new page.");
     let different_page_id = page_id + 1;
-    assert_errors!(bpm.delete_page(different_page_id));
+    // A page that isn't resident is still deallocated on disk.
+    assert_eq!(bpm.delete_page(different_page_id), Ok(true));
+    assert!(bpm.shards[0].frames().page_table.contains_key(&page_id));
+    assert_eq!(1, bpm.stats().pages_deleted);
 }
 
 #[test]
 fn test_cannot_delete_pinned_page() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
     // this is pinned in the buffer pool, shouldn't be able to delete
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
-    assert!(!bpm.delete_page(page_id));
+    assert!(!bpm.delete_page(page_id).unwrap());
 }
 
 /// This tests assumes [`super::BufferPoolManager::unpin_page`] properly decrements pin count.
 #[test]
 fn test_delete_evictable_page() {
-    let mut bpm = get_bpm_with_pool_size(5);
+    let bpm = get_bpm_with_pool_size(5);
     let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
 
-    bpm.unpin_page(&page_id, false);
-    assert!(bpm.delete_page(page_id));
-    assert!(!bpm.page_table.contains_key(&page_id));
+    bpm.unpin_page(&page_id, false).unwrap();
+    assert!(bpm.delete_page(page_id).unwrap());
+    assert!(!bpm.shards[0].frames().page_table.contains_key(&page_id));
+}
+
+/// A deleted page's frame is forgotten by the replacer, so it is handed out again once, from the
+/// free list, and never evicted while it is free.
+#[test]
+fn test_deleted_page_frame_is_reused_once() {
+    let bpm = get_bpm_with_pool_size(3);
+    let page_ids = create_n_unpinned_pages(&bpm, 3);
+    let frame_id = *bpm.shards[0].frames().page_table[&page_ids[1]].frame_id();
+
+    assert!(bpm.delete_page(page_ids[1]).unwrap());
+    {
+        let frames = bpm.shards[0].frames();
+        assert_eq!(vec![frame_id], frames.free_list.iter().copied().collect_vec());
+        assert!(frames.frame(frame_id).is_none());
+        let replacer = bpm.shards[0].replacer.read().unwrap();
+        assert_eq!(None, replacer.is_evictable(&frame_id));
+        assert_eq!(2, replacer.size());
+    }
+    check_pool(&bpm).unwrap();
+
+    // The first new page takes the freed frame without an eviction, and the other two evict the
+    // remaining pages, so each frame is handed out exactly once.
+    bpm.reset_stats();
+    let new_page_ids = create_n_pages(&bpm, 3);
+    let frame_ids = {
+        let frames = bpm.shards[0].frames();
+        (new_page_ids.iter())
+            .map(|page_id| *frames.page_table[page_id].frame_id())
+            .collect_vec()
+    };
+    assert_eq!(frame_id, frame_ids[0]);
+    assert_eq!(vec![0, 1, 2], frame_ids.iter().copied().sorted().collect_vec());
+    assert_eq!(2, bpm.stats().evictions);
+    assert_eq!(0, bpm.shards[0].replacer.read().unwrap().size());
+    assert!(bpm.new_page().is_err());
+    check_pool(&bpm).unwrap();
+}
+
+#[test]
+fn test_stale_handle_after_eviction() {
+    let bpm = get_bpm_with_pool_size(1);
+    let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    let handle = bpm.fetch_page_versioned(&page_id).unwrap();
+    assert!(handle.is_valid(&bpm));
+    bpm.unpin_page(&page_id, false).unwrap();
+    bpm.unpin_page(&page_id, false).unwrap();
+
+    // Still resident once unpinned, so the handle is still good.
+    assert!(bpm.validate_handle(&handle));
+
+    // Another page takes the frame.
+    let other_page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert_eq!(handle.frame(), *bpm.shards[0].frames().page_table[&other_page_id].frame_id());
+    assert!(!bpm.validate_handle(&handle));
+    bpm.unpin_page(&other_page_id, false).unwrap();
+
+    // The page comes back to the same frame, but the handle holds the page it was evicted with.
+    let reloaded = bpm.fetch_page_versioned(&page_id).unwrap();
+    assert_eq!(handle.frame(), reloaded.frame());
+    assert!(reloaded.generation() > handle.generation());
+    assert!(!Arc::ptr_eq(handle.page(), reloaded.page()));
+    assert!(!bpm.validate_handle(&handle));
+    assert!(bpm.validate_handle(&reloaded));
+    bpm.unpin_page(&page_id, false).unwrap();
+    check_pool(&bpm).unwrap();
+}
+
+#[test]
+fn test_stale_handle_after_delete() {
+    let bpm = get_bpm_with_pool_size(2);
+    let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    let handle = bpm.fetch_page_versioned(&page_id).unwrap();
+    bpm.unpin_page(&page_id, false).unwrap();
+    bpm.unpin_page(&page_id, false).unwrap();
+
+    assert!(bpm.delete_page(page_id).unwrap());
+    assert!(!handle.is_valid(&bpm));
+    check_pool(&bpm).unwrap();
+}
+
+#[test]
+fn test_reserve_frames() {
+    let bpm = get_bpm_with_pool_size(4);
+    let page_ids = create_n_unpinned_pages(&bpm, 4);
+    set_pages_to_dirty(&bpm, &page_ids[..2].to_vec());
+    fetch_page(&page_ids[3], &bpm);
+    bpm.reset_stats();
+
+    assert!(bpm.reserve_frames(0).is_empty());
+    let evictable = ReplacerStats {
+        evictable_frames: 3,
+        ..ReplacerStats::default()
+    };
+    assert_eq!(
+        BufferPoolStats {
+            replacer: evictable,
+            ..BufferPoolStats::default()
+        },
+        bpm.stats()
+    );
+
+    // Only the three unpinned pages can make room, and the dirty ones are written back first.
+    let reserved = bpm.reserve_frames(5);
+    assert_eq!(3, reserved.len());
+    assert_eq!(vec![page_ids[3]], bpm.resident_page_ids());
+    assert_eq!(3, bpm.free_frame_count());
+    assert_eq!(3, bpm.stats().evictions);
+    assert_eq!(2, bpm.stats().writebacks);
+    check_pool(&bpm).unwrap();
+
+    // The reserved frames are handed out without evicting anything else.
+    create_n_pages(&bpm, 3);
+    assert_eq!(3, bpm.stats().evictions);
+    check_pool(&bpm).unwrap();
 }
 
 /// This tests assumes [`super::BufferPoolManager::unpin_page`] properly decrements pin count.
 #[test]
 fn test_attempt_deletion_of_evictable_and_pinned_pages() {
     let pool_size = 20_usize;
-    let mut bpm = get_bpm_with_pool_size(pool_size);
-    let page_ids = create_n_pages(&mut bpm, pool_size);
+    let bpm = get_bpm_with_pool_size(pool_size);
+    let page_ids = create_n_pages(&bpm, pool_size);
 
     // set half the page to evictable; the other half remain pinned
     let evictable_page_ids =
-        set_pages_satisfying_criteria_to_evictable(&mut bpm, &page_ids, page_number_is_even);
+        set_pages_satisfying_criteria_to_evictable(&bpm, &page_ids, page_number_is_even);
 
     for page_id in page_ids {
-        let was_deleted = bpm.delete_page(page_id.clone());
+        let was_deleted = bpm.delete_page(page_id.clone()).unwrap();
         let should_have_been_deleted = evictable_page_ids.contains(&page_id);
         assert_eq!(was_deleted, should_have_been_deleted);
     }
@@ -352,7 +1091,7 @@ fn test_attempt_deletion_of_evictable_and_pinned_pages() {
 #[test]
 fn test_dirty_pages_eviction() {
     let disk_manager = new_disk_manager();
-    let mut bpm = BufferPoolManager::new(2, 5, Arc::clone(&disk_manager));
+    let bpm = BufferPoolManager::new(2, 5, Arc::clone(&disk_manager));
 
     // Create and unpin a page.
     let page_id1 = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
@@ -363,18 +1102,18 @@ fn test_dirty_pages_eviction() {
         let mut page1 = page_handle1.write().unwrap();
         page1.insert_tuple(tuple_metadata, tuple.clone());
     }
-    bpm.unpin_page(&page_id1, true);
-    bpm.unpin_page(&page_id1, true);
+    bpm.unpin_page(&page_id1, true).unwrap();
+    bpm.unpin_page(&page_id1, true).unwrap();
 
     // Create and unpin another page.
     let page_id2 = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
-    bpm.unpin_page(&page_id2, false);
+    bpm.unpin_page(&page_id2, false).unwrap();
 
     // Now the buffer pool is full. Creating a new page will cause eviction.
     let page_id3 = bpm
         .new_page()
         .expect("Should be able to create a new page after eviction");
-    bpm.unpin_page(&page_id3, true);
+    bpm.unpin_page(&page_id3, true).unwrap();
 
     let page_handle = bpm.fetch_page(&page_id1).expect("Failed to fetch page");
     let page1 = page_handle.write().unwrap();
@@ -383,7 +1122,7 @@ fn test_dirty_pages_eviction() {
 
     // The dirty page (page_id1) should have been evicted and written to disk.
     // Read the page from disk and verify its contents.
-    let page_on_disk = disk_manager.write().unwrap().read_page(&page_id1);
+    let page_on_disk = disk_manager.write().unwrap().read_page(&page_id1).unwrap();
     assert_eq!(
         page_on_disk.get_tuple(&rc1).unwrap(),
         tuple,
@@ -391,8 +1130,457 @@ fn test_dirty_pages_eviction() {
     );
 }
 
-/// This test is simulating latches and concurrent access to buffer pool manager, but it does
-/// not require the buffer pool manager to be implemented in a thread-safe manner internally.
+/// The accessors describe resident pages, and return `None`, or `false`, for pages that were
+/// evicted or never created.
+#[test]
+fn test_page_accessors() {
+    let bpm = get_bpm_with_pool_size(2);
+    assert_eq!(2, bpm.free_frame_count());
+    let evicted = create_n_unpinned_pages(&bpm, 1)[0];
+    bpm.set_is_dirty(&evicted, true);
+    let clean = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert_eq!(0, bpm.free_frame_count());
+
+    assert!(bpm.contains_page(&evicted));
+    assert_eq!(Some(0), bpm.pin_count(&evicted));
+    assert_eq!(Some(true), bpm.is_dirty(&evicted));
+    assert!(bpm.contains_page(&clean));
+    assert_eq!(Some(1), bpm.pin_count(&clean));
+    assert_eq!(Some(false), bpm.is_dirty(&clean));
+
+    let resident = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert!(!bpm.contains_page(&evicted));
+    assert_eq!(None, bpm.pin_count(&evicted));
+    assert_eq!(None, bpm.is_dirty(&evicted));
+    assert!(bpm.contains_page(&resident));
+
+    let never_created = resident + 100;
+    assert!(!bpm.contains_page(&never_created));
+    assert_eq!(None, bpm.pin_count(&never_created));
+    assert_eq!(None, bpm.is_dirty(&never_created));
+
+    bpm.unpin_page(&clean, false).unwrap();
+    assert!(bpm.delete_page(clean).unwrap());
+    assert_eq!(1, bpm.free_frame_count());
+    bpm.unpin_page(&resident, false).unwrap();
+}
+
+/// A page fetched for writing is dirty from the start, so its changes survive eviction even when
+/// it is unpinned clean, while a page fetched for reading stays clean.
+#[test]
+fn test_fetch_page_write_and_read() {
+    let disk_manager = new_disk_manager();
+    let bpm = BufferPoolManager::new(2, 5, Arc::clone(&disk_manager));
+    let page_ids = create_n_unpinned_pages(&bpm, 2);
+
+    let tuple = Tuple::from(&b"Northwestern"[..]);
+    let page_handle = bpm.fetch_page_write(&page_ids[0]).expect(NO_CORRESPONDING_PAGE_MSG);
+    let slot = (page_handle.write().unwrap())
+        .insert_tuple(TupleMetadata::new(false), tuple.clone())
+        .unwrap();
+    assert_eq!(Some(true), bpm.is_dirty(&page_ids[0]));
+    bpm.unpin_page(&page_ids[0], false).unwrap();
+
+    bpm.fetch_page_read(&page_ids[1]).expect(NO_CORRESPONDING_PAGE_MSG);
+    assert_eq!(Some(false), bpm.is_dirty(&page_ids[1]));
+    bpm.unpin_page(&page_ids[1], false).unwrap();
+
+    // Push both pages out, then read the modified one back from disk.
+    bpm.reset_stats();
+    let pinned = create_n_pages(&bpm, 2);
+    assert_eq!(2, bpm.stats().evictions);
+    assert_eq!(1, bpm.stats().writebacks);
+    pinned.iter().for_each(|page_id| bpm.unpin_page(page_id, false).unwrap());
+
+    let page_handle = bpm.fetch_page_read(&page_ids[0]).expect(NO_CORRESPONDING_PAGE_MSG);
+    let rid = RecordId::new(page_ids[0], slot);
+    assert_eq!(tuple, page_handle.read().unwrap().get_tuple(&rid).unwrap());
+    assert_eq!(Some(false), bpm.is_dirty(&page_ids[0]));
+    bpm.unpin_page(&page_ids[0], false).unwrap();
+}
+
+/// Hundreds of pages churned through a 4-frame pool, with some deleted along the way to put
+/// frames back on the free list, always come back as the page asked for, and the pool never
+/// grows past its frames.
+#[test]
+fn test_churn_keeps_frames_in_place() {
+    let pool_size = 4;
+    let bpm = get_bpm_with_pool_size(pool_size);
+    let page_ids: Vec<PageId> = (0..300)
+        .map(|_| {
+            let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+            let page = get_page_handle(&bpm, &page_id).unwrap();
+            let tuple = Tuple::from(page_id.to_le_bytes().as_slice());
+            page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+            bpm.unpin_page(&page_id, true).unwrap();
+            page_id
+        })
+        .collect();
+
+    let mut deleted = Vec::new();
+    for round in 0..3 {
+        for i in 0..page_ids.len() {
+            let page_id = page_ids[(i * 7 + round) % page_ids.len()];
+            if deleted.contains(&page_id) {
+                continue;
+            }
+            let page = fetch_page(&page_id, &bpm);
+            {
+                let page = page.read().unwrap();
+                assert_eq!(page_id, page.page_id);
+                let tuple = page.get_tuple(&RecordId::new(page_id, 0)).unwrap();
+                assert_eq!(Tuple::from(page_id.to_le_bytes().as_slice()), tuple);
+            }
+            bpm.unpin_page(&page_id, false).unwrap();
+            if round == 0 && i % 25 == 0 {
+                assert!(bpm.delete_page(page_id).unwrap());
+                deleted.push(page_id);
+            }
+            assert_eq!(pool_size, bpm.shards[0].frames().pages.len());
+            assert!(bpm.shards[0].frames().page_table.len() <= pool_size);
+        }
+    }
+}
+
+/// Creates `n` unpinned pages, of which the pool keeps the last ones it has room for.
+fn create_n_unpinned_pages(bpm: &BufferPoolManager, n: usize) -> Vec<PageId> {
+    (0..n)
+        .map(|_| {
+            let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+            bpm.unpin_page(&page_id, false).unwrap();
+            page_id
+        })
+        .collect()
+}
+
+#[test]
+fn test_fetch_pages() {
+    let bpm = get_bpm_with_pool_size(6);
+    let page_ids = create_n_unpinned_pages(&bpm, 8);
+    assert!(!page_in_buffer(&bpm, &page_ids[0]) && !page_in_buffer(&bpm, &page_ids[1]));
+    let reads = bpm.metrics().snapshot().disk.reads;
+    bpm.reset_stats();
+
+    // The two evicted pages are read together, and a page asked for twice is pinned twice.
+    let wanted = [page_ids[0], page_ids[1], page_ids[2], page_ids[2]];
+    let pages = bpm.fetch_pages(&wanted);
+    for (page, page_id) in pages.iter().zip(&wanted) {
+        assert_eq!(*page_id, page.as_ref().unwrap().read().unwrap().page_id);
+    }
+    assert_eq!(reads + 1, bpm.metrics().snapshot().disk.reads);
+    assert_eq!((2, 2), (bpm.stats().hits, bpm.stats().misses));
+    assert_eq!(Some(1), bpm.pin_count(&page_ids[0]));
+    assert_eq!(Some(1), bpm.pin_count(&page_ids[1]));
+    assert_eq!(Some(2), bpm.pin_count(&page_ids[2]));
+}
+
+/// Without frames for every missing page, the pages read are those that fit, and the rest are
+/// neither returned nor pinned.
+#[test]
+fn test_fetch_pages_without_enough_frames() {
+    let bpm = get_bpm_with_pool_size(3);
+    let page_ids = create_n_unpinned_pages(&bpm, 6);
+    // Leave a single evictable frame.
+    fetch_page(&page_ids[3], &bpm);
+    fetch_page(&page_ids[4], &bpm);
+
+    let wanted = [page_ids[0], page_ids[1], page_ids[2], page_ids[4]];
+    let pages = bpm.fetch_pages(&wanted);
+    let fetched: Vec<Option<PageId>> = pages
+        .iter()
+        .map(|page| page.as_ref().map(|page| page.read().unwrap().page_id))
+        .collect();
+    assert_eq!(vec![Some(page_ids[0]), None, None, Some(page_ids[4])], fetched);
+    assert_eq!(Some(1), bpm.pin_count(&page_ids[0]));
+    assert_eq!(Some(2), bpm.pin_count(&page_ids[4]));
+    assert!(!page_in_buffer(&bpm, &page_ids[1]) && !page_in_buffer(&bpm, &page_ids[2]));
+    assert_eq!(0, bpm.shards[0].replacer.read().unwrap().size());
+}
+
+/// A scan of evicted pages fetched one at a time locks the disk manager and reads once per
+/// page, but fetched together, once for the run.
+#[test]
+fn test_fetch_pages_batches_disk_reads() {
+    let pages = 16;
+    let bpm = get_bpm_with_pool_size(pages);
+    let page_ids = create_n_unpinned_pages(&bpm, 2 * pages);
+    let scan = &page_ids[..pages];
+    let metrics = bpm.metrics();
+    let reads = || metrics.snapshot().disk.reads;
+
+    let before = reads();
+    for page_id in scan {
+        fetch_page(page_id, &bpm);
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+    let one_at_a_time = reads() - before;
+
+    // Push the scanned pages back out, then scan them again in one go.
+    for page_id in &page_ids[pages..] {
+        fetch_page(page_id, &bpm);
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+    assert!(!page_in_buffer(&bpm, &scan[0]));
+    let before = reads();
+    assert!(bpm.fetch_pages(scan).iter().all(Option::is_some));
+    let batched = reads() - before;
+
+    assert_eq!((pages as u64, 1), (one_at_a_time, batched));
+}
+
+#[test]
+fn test_fetch_into_free_frame_reads_page() {
+    let bpm = get_bpm_with_pool_size(2);
+    let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    let tuples = [
+        Tuple::from(&b"Northwestern"[..]),
+        Tuple::from(&b"Evanston"[..]),
+    ];
+    {
+        let page_handle = get_page_handle(&bpm, &page_id).unwrap();
+        let mut page = page_handle.write().unwrap();
+        for tuple in &tuples {
+            page.insert_tuple(TupleMetadata::new(false), tuple.clone());
+        }
+    }
+    bpm.unpin_page(&page_id, true).unwrap();
+
+    // Fill the pool to evict the page, then free up a frame for it to come back to.
+    let others = create_n_pages(&bpm, 2);
+    assert!(!page_in_buffer(&bpm, &page_id));
+    bpm.unpin_page(&others[0], false).unwrap();
+    assert!(bpm.delete_page(others[0]).unwrap());
+    assert_eq!(1, bpm.shards[0].frames().free_list.len());
+
+    let page_handle = fetch_page(&page_id, &bpm);
+    let page = page_handle.read().unwrap();
+    assert_eq!(page_id, page.page_id);
+    for (slot, tuple) in tuples.iter().enumerate() {
+        let rid = RecordId::new(page_id, slot as u16);
+        assert_eq!(*tuple, page.get_tuple(&rid).unwrap());
+    }
+    assert!(Arc::ptr_eq(
+        &page_handle,
+        &get_page_handle(&bpm, &page_id).unwrap()
+    ));
+
+    // The page is pinned, so nothing can be evicted.
+    assert_eq!(1, bpm.pin_count(&page_id).unwrap());
+    assert_eq!(0, bpm.shards[0].replacer.read().unwrap().size());
+    assert!(bpm.new_page().is_err());
+}
+
+/// Threads share the pool without any lock of their own, each creating pages, writing to the
+/// pages it created, and reading the pages the others created, while the pool evicts all the
+/// while. Nothing may deadlock, no pin may be lost, and every page must hold what was written,
+/// whether the pool is split in shards or not.
+#[test]
+fn test_concurrent_fetch_unpin_new_page() {
+    for shards in [1, 4] {
+        hammer_pool(shards);
+    }
+}
+
+/// Runs [`test_concurrent_fetch_unpin_new_page`] on a pool of `shards` shards.
+fn hammer_pool(shards: usize) {
+    const THREADS: u64 = 8;
+    const OPS: usize = 400;
+    let bpm = BufferPoolManager::builder()
+        .pool_size(24)
+        .replacer_k(5)
+        .shards(shards)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let published = Arc::new(Mutex::new(Vec::<PageId>::new()));
+    let entry = |page_id: PageId, n: u32| {
+        Tuple::from([page_id.to_le_bytes(), n.to_le_bytes()].concat())
+    };
+
+    let (done, finished) = mpsc::channel();
+    for thread in 0..THREADS {
+        let (bpm, published, done) = (Arc::clone(&bpm), Arc::clone(&published), done.clone());
+        thread::spawn(move || {
+            let mut rng = ChaCha8Rng::seed_from_u64(thread);
+            let mut owned: Vec<(PageId, u32)> = Vec::new();
+            let mut held: Vec<PageId> = Vec::new();
+            for _ in 0..OPS {
+                // Hold on to a couple of pins at a time, so that other threads' pins overlap.
+                if held.len() == 2 || (!held.is_empty() && rng.gen_bool(0.3)) {
+                    bpm.unpin_page(&held.remove(0), false).unwrap();
+                    continue;
+                }
+                match rng.gen_range(0..3) {
+                    0 => {
+                        let Ok(page_id) = bpm.new_page() else {
+                            continue;
+                        };
+                        let page = bpm.fetch_page(&page_id).unwrap();
+                        let mut page = page.write().unwrap();
+                        page.insert_tuple(TupleMetadata::new(false), entry(page_id, 0)).unwrap();
+                        drop(page);
+                        bpm.unpin_page(&page_id, true).unwrap();
+                        owned.push((page_id, 1));
+                        held.push(page_id);
+                        published.lock().unwrap().push(page_id);
+                    }
+                    1 if !owned.is_empty() => {
+                        let slot = rng.gen_range(0..owned.len());
+                        let (page_id, count) = &mut owned[slot];
+                        let Ok(page) = bpm.fetch_page(page_id) else {
+                            continue;
+                        };
+                        let tuple = entry(*page_id, *count);
+                        page.write().unwrap().insert_tuple(TupleMetadata::new(false), tuple);
+                        *count += 1;
+                        bpm.unpin_page(page_id, true).unwrap();
+                    }
+                    _ => {
+                        let page_ids = published.lock().unwrap().clone();
+                        let Some(page_id) = page_ids.get(rng.gen_range(0..page_ids.len().max(1)))
+                        else {
+                            continue;
+                        };
+                        let Ok(page) = bpm.fetch_page(page_id) else {
+                            continue;
+                        };
+                        let rid = RecordId::new(*page_id, 0);
+                        let first = page.read().unwrap().get_tuple(&rid).unwrap();
+                        assert_eq!(entry(*page_id, 0), first);
+                        held.push(*page_id);
+                    }
+                }
+            }
+            for page_id in held {
+                bpm.unpin_page(&page_id, false).unwrap();
+            }
+            done.send(owned).unwrap();
+        });
+    }
+
+    let mut written = Vec::new();
+    for _ in 0..THREADS {
+        let owned = (finished.recv_timeout(Duration::from_secs(60)))
+            .expect("The threads deadlocked");
+        written.extend(owned);
+    }
+    for shard in &bpm.shards {
+        let frames = shard.frames();
+        assert!(frames.page_table.values().all(|frame| frame.pin_count() == 0));
+        assert_eq!(frames.page_table.len(), shard.replacer.read().unwrap().size());
+    }
+    for (page_id, count) in written {
+        let page = fetch_page(&page_id, &bpm);
+        let tuples: Vec<Tuple> = (0..count)
+            .map(|slot| page.read().unwrap().get_tuple(&RecordId::new(page_id, slot as u16)))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected: Vec<Tuple> = (0..count).map(|n| entry(page_id, n)).collect();
+        assert_eq!(expected, tuples, "page {page_id}");
+        bpm.unpin_page(&page_id, false).unwrap();
+    }
+}
+
+/// Pages go to the shard `page_id % shards`, and only evict the pages of their own shard, while
+/// the size and stats are those of the whole pool.
+#[test]
+fn test_sharded_pool() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(8)
+        .replacer_k(2)
+        .shards(4)
+        .disk_manager(new_disk_manager())
+        .metrics(Arc::default())
+        .build_with_handle();
+    assert_eq!(8, bpm.size());
+    assert!(bpm.shards.iter().all(|shard| shard.pool_size == 2));
+
+    // Pages are allocated one after the other, so eight pages fill every shard.
+    let page_ids = create_n_pages(&bpm, 8);
+    for (index, shard) in bpm.shards.iter().enumerate() {
+        let frames = shard.frames();
+        assert!(frames.free_list.is_empty());
+        assert!(frames.page_table.keys().all(|page_id| *page_id as usize % 4 == index));
+    }
+    assert!(bpm.new_page().is_err());
+
+    // A page unpinned in one shard makes room for a page of that shard only: of four new pages,
+    // one for each shard, only one finds a frame.
+    let unpinned = page_ids[0];
+    bpm.unpin_page(&unpinned, true).unwrap();
+    let created: Vec<PageId> = (0..4).filter_map(|_| bpm.new_page().ok()).collect();
+    assert_eq!(1, created.len());
+    assert_eq!(bpm.shard_index(&unpinned), bpm.shard_index(&created[0]));
+    assert!(!page_in_buffer(&bpm, &unpinned));
+    // Every frame of its shard is pinned again, so it can't come back.
+    assert!(bpm.fetch_page(&unpinned).is_err());
+
+    for page_id in page_ids.iter().skip(1).chain(&created) {
+        bpm.unpin_page(page_id, true).unwrap();
+    }
+    assert_eq!(8, bpm.flush_all_pages());
+    assert_eq!(
+        BufferPoolStats {
+            hits: 0,
+            misses: 1,
+            evictions: 1,
+            flushes: 9,
+            writebacks: 1,
+            pages_created: 9,
+            pages_deleted: 0,
+            replacer: ReplacerStats {
+                accesses: 9,
+                evictions: 1,
+                evictable_frames: 8,
+                cold_evictions: 1,
+                ..ReplacerStats::default()
+            },
+        },
+        bpm.stats()
+    );
+}
+
+/// Threads fetching resident pages of their own shard all hit: a pool split in shards serves
+/// the concurrent lookups without a miss, an eviction or a pin left behind.
+#[test]
+fn test_sharded_concurrent_fetch() {
+    const THREADS: usize = 4;
+    const FETCHES: usize = 2_000;
+    for shards in [1, 2, 4] {
+        let bpm = BufferPoolManager::builder()
+            .pool_size(64)
+            .replacer_k(2)
+            .shards(shards)
+            .disk_manager(new_disk_manager())
+            .metrics(Arc::default())
+            .build_with_handle();
+        let page_ids = create_n_unpinned_pages(&bpm, 64);
+        bpm.reset_stats();
+
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (bpm, page_ids) = (&bpm, &page_ids);
+                scope.spawn(move || {
+                    let own: Vec<PageId> = (page_ids.iter().copied())
+                        .filter(|page_id| *page_id as usize % THREADS == thread)
+                        .collect();
+                    for page_id in own.iter().cycle().take(FETCHES) {
+                        fetch_page(page_id, bpm);
+                        bpm.unpin_page(page_id, false).unwrap();
+                    }
+                });
+            }
+        });
+
+        let stats = bpm.stats();
+        assert_eq!((THREADS * FETCHES) as u64, stats.hits, "{shards} shards");
+        assert_eq!((0, 0), (stats.misses, stats.evictions), "{shards} shards");
+        for page_id in &page_ids {
+            assert_eq!(Some(0), bpm.pin_count(page_id), "page {page_id}");
+        }
+    }
+}
+
+/// This test is simulating latches and concurrent access to buffer pool manager.
 #[test]
 fn test_serialized_evictable() {
     const ROUNDS: usize = 50;
@@ -402,11 +1590,7 @@ fn test_serialized_evictable() {
     let disk_manager = new_disk_manager();
 
     // Only allocate 1 frame of memory to the buffer pool manager.
-    let bpm = Arc::new(RwLock::new(BufferPoolManager::new(
-        1,
-        2,
-        Arc::clone(&disk_manager),
-    )));
+    let bpm = Arc::new(BufferPoolManager::new(1, 2, Arc::clone(&disk_manager)));
 
     for i in 0..ROUNDS {
         // Use an AtomicBool for synchronization.
@@ -415,12 +1599,12 @@ fn test_serialized_evictable() {
         // Allocate pages via DiskManager.
         let winner_pid = {
             let mut disk_guard = disk_manager.write().unwrap();
-            disk_guard.allocate_new_page()
+            disk_guard.allocate_new_page().unwrap()
         };
 
         let loser_pid = {
             let mut disk_guard = disk_manager.write().unwrap();
-            disk_guard.allocate_new_page()
+            disk_guard.allocate_new_page().unwrap()
         };
 
         let mut readers = Vec::new();
@@ -440,15 +1624,14 @@ fn test_serialized_evictable() {
 
                 // Fetch and read the page.
                 {
-                    let mut bpm_guard = bpm.write().unwrap();
-                    let _page_handle = bpm_guard.fetch_page(&winner_pid).unwrap();
+                    let _page_handle = bpm.fetch_page(&winner_pid).unwrap();
 
                     // Since the only frame is pinned, no thread should be able to bring in a new page.
-                    let result = bpm_guard.fetch_page(&loser_pid);
-                    assert!(result.is_none());
+                    let result = bpm.fetch_page(&loser_pid);
+                    assert!(result.is_err());
 
                     // Unpin the page after use.
-                    bpm_guard.unpin_page(&winner_pid, false);
+                    bpm.unpin_page(&winner_pid, false).unwrap();
                 }
             });
 
@@ -457,8 +1640,7 @@ fn test_serialized_evictable() {
 
         match i % 2 {
             0 => {
-                let mut bpm_guard = bpm.write().unwrap();
-                let page_handle = bpm_guard.fetch_page(&winner_pid).unwrap();
+                let page_handle = bpm.fetch_page(&winner_pid).unwrap();
 
                 // Obtain a read lock on the page content.
                 let _page_read_lock = page_handle.read().unwrap();
@@ -470,11 +1652,10 @@ fn test_serialized_evictable() {
                 drop(_page_read_lock);
 
                 // Unpin the page.
-                bpm_guard.unpin_page(&winner_pid, false);
+                bpm.unpin_page(&winner_pid, false).unwrap();
             }
             _ => {
-                let mut bpm_guard = bpm.write().unwrap();
-                let page_handle = bpm_guard.fetch_page(&winner_pid).unwrap();
+                let page_handle = bpm.fetch_page(&winner_pid).unwrap();
 
                 // Obtain a write lock on the page content.
                 let _page_write_lock = page_handle.write().unwrap();
@@ -486,7 +1667,7 @@ fn test_serialized_evictable() {
                 drop(_page_write_lock);
 
                 // Unpin the page.
-                bpm_guard.unpin_page(&winner_pid, false);
+                bpm.unpin_page(&winner_pid, false).unwrap();
             }
         }
 
@@ -503,7 +1684,7 @@ fn page_pin_test() {
 
     // Initialize the disk manager.
     let disk_manager = new_disk_manager();
-    let mut bpm = BufferPoolManager::new(FRAMES, 2, Arc::clone(&disk_manager));
+    let bpm = BufferPoolManager::new(FRAMES, 2, Arc::clone(&disk_manager));
     let mut pages: Vec<PageId> = Vec::new();
 
     // The buffer pool is empty. We should be able to create a new page.
@@ -531,7 +1712,7 @@ fn page_pin_test() {
             assert_eq!(tuple.data, b"Hello", "Data read does not match 'Hello'.");
         }
         // Unpin the page.
-        bpm.unpin_page(&pid0, true);
+        bpm.unpin_page(&pid0, true).unwrap();
     }
 
     // We should be able to create new pages until we fill up the buffer pool.
@@ -543,7 +1724,7 @@ fn page_pin_test() {
 
     // All pin counts should be 1.
     for pid in &pages {
-        let pin_count = bpm.get_pin_count(pid).expect("Failed to get pin count.");
+        let pin_count = bpm.pin_count(pid).expect("Failed to get pin count.");
         assert_eq!(pin_count, 1, "Pin count for page {} is not 1.", pid);
     }
 
@@ -551,17 +1732,17 @@ fn page_pin_test() {
     for _ in 0..FRAMES {
         let result = bpm.new_page();
         assert!(
-            result.is_none(),
-            "Expected new_page to return None when buffer pool is full."
+            result.is_err(),
+            "Expected new_page to return an error when buffer pool is full."
         );
     }
 
     // Drop the first 5 pages to unpin them.
     for _ in 0..(FRAMES / 2) {
         let pid = pages.remove(0);
-        bpm.unpin_page(&pid, false);
+        bpm.unpin_page(&pid, false).unwrap();
         // Check that the pin count is now 0.
-        let pin_count = bpm.get_pin_count(&pid).expect("Failed to get pin count.");
+        let pin_count = bpm.pin_count(&pid).expect("Failed to get pin count.");
         assert_eq!(
             pin_count, 0,
             "Pin count for page {} is not 0 after unpinning.",
@@ -571,7 +1752,7 @@ fn page_pin_test() {
 
     // All pin counts of the pages we haven't dropped yet should still be 1.
     for pid in &pages {
-        let pin_count = bpm.get_pin_count(pid).expect("Failed to get pin count.");
+        let pin_count = bpm.pin_count(pid).expect("Failed to get pin count.");
         assert_eq!(pin_count, 1, "Pin count for page {} is not 1.", pid);
     }
 
@@ -593,7 +1774,7 @@ fn page_pin_test() {
             );
         }
         // Unpin the page
-        bpm.unpin_page(&pid0, false);
+        bpm.unpin_page(&pid0, false).unwrap();
     }
 
     // Once we unpin page 0 and then make a new page, all the buffer pages should now be pinned.
@@ -604,12 +1785,405 @@ fn page_pin_test() {
     // Try to fetch pid0 again, expecting it to fail.
     let result = bpm.fetch_page(&pid0);
     assert!(
-        result.is_none(),
-        "Expected fetch_page for pid0 to return None."
+        result.is_err(),
+        "Expected fetch_page for pid0 to return an error."
     );
 }
 
-fn create_n_pages(bpm: &mut BufferPoolManager, n: usize) -> Vec<PageId> {
+#[test]
+fn test_index_pages_survive_eviction() {
+    let bpm = get_bpm_with_pool_size(3);
+    let key_schema = KeySchema::for_type(DataType::Int);
+
+    // A table page shares the pool with the index pages, and stays pinned.
+    let table_page_id = bpm.new_page().unwrap();
+    let leaf = bpm
+        .new_leaf_page(BPlusTreeLeafPage::builder().key_schema(key_schema))
+        .unwrap();
+    let leaf_page_id = *leaf.read().unwrap().page_id();
+    for key in 0..10 {
+        leaf.write()
+            .unwrap()
+            .insert(
+                Field::Integer(key),
+                RecordId::new(table_page_id, key as u16),
+            )
+            .unwrap();
+    }
+    let expected = leaf.read().unwrap().clone();
+    let internal = bpm
+        .new_internal_page(BPlusTreeInternalPage::builder().key_schema(key_schema))
+        .unwrap();
+    let internal_page_id = *internal.read().unwrap().page_id();
+    internal
+        .write()
+        .unwrap()
+        .init_root(
+            leaf_page_id,
+            (Field::Integer(5), RecordId::new(table_page_id, 5)),
+            leaf_page_id,
+        )
+        .unwrap();
+    let fetched = bpm.fetch_index_page(&table_page_id);
+    assert!(matches!(fetched, Err(Error::InvalidData(_))));
+    assert!(bpm.fetch_page(&leaf_page_id).is_err());
+    let new_leaf = bpm.new_leaf_page(BPlusTreeLeafPage::builder().key_schema(key_schema));
+    assert!(matches!(new_leaf, Err(Error::NoEvictableFrame { pinned: 3, .. })));
+
+    // Push both index pages out of the pool, then read them back from disk.
+    bpm.unpin_page(&leaf_page_id, true).unwrap();
+    bpm.unpin_page(&internal_page_id, true).unwrap();
+    drop((leaf, internal));
+    let other = bpm
+        .new_leaf_page(BPlusTreeLeafPage::builder().key_schema(key_schema))
+        .unwrap();
+    let other_page_id = *other.read().unwrap().page_id();
+    assert!(!page_in_buffer(&bpm, &leaf_page_id));
+
+    let leaf = bpm
+        .fetch_index_page(&leaf_page_id)
+        .unwrap()
+        .as_leaf()
+        .unwrap();
+    assert_eq!(expected.entries(), leaf.read().unwrap().entries());
+    assert!(!page_in_buffer(&bpm, &internal_page_id));
+    let fetched = bpm.fetch_index_page(&internal_page_id);
+    assert!(matches!(fetched, Err(Error::NoEvictableFrame { pinned: 3, .. })));
+
+    bpm.unpin_page(&other_page_id, false).unwrap();
+    let internal = bpm
+        .fetch_index_page(&internal_page_id)
+        .unwrap()
+        .as_internal()
+        .unwrap();
+    assert_eq!(
+        leaf_page_id,
+        internal
+            .read()
+            .unwrap()
+            .lookup(&Field::Integer(7), &RecordId::new(table_page_id, 7))
+    );
+    assert_eq!(1, bpm.pin_count(&internal_page_id).unwrap());
+    assert_eq!(1, bpm.pin_count(&table_page_id).unwrap());
+}
+
+/// A page type of the tests' own shares the pool with table pages, and is pinned, evicted and
+/// flushed like them.
+#[test]
+fn test_pages_of_any_type_share_the_pool() {
+    let bpm = get_bpm_with_pool_size(2);
+    let header = bpm.new_page_as(HeaderPage::new).unwrap();
+    let header_page_id = *header.read().unwrap().page_id();
+    header.write().unwrap().tables = 3;
+    let table_page_id = bpm.new_page().unwrap();
+    let tuple = Tuple::from(vec![1, 2, 3]);
+    fetch_page(&table_page_id, &bpm)
+        .write()
+        .unwrap()
+        .insert_tuple(TupleMetadata::new(false), tuple.clone());
+    bpm.unpin_page(&table_page_id, true).unwrap();
+
+    // Neither page can be taken for the other's type, and nothing is pinned trying.
+    assert!(bpm.fetch_page(&header_page_id).is_err());
+    assert!(matches!(bpm.fetch_index_page(&header_page_id), Err(Error::InvalidData(_))));
+    let fetched = bpm.fetch_page_as::<HeaderPage>(&table_page_id);
+    assert!(matches!(fetched, Err(Error::InvalidData(_))));
+    assert_eq!(Some(1), bpm.pin_count(&header_page_id));
+    assert_eq!(Some(1), bpm.pin_count(&table_page_id));
+    assert_eq!(Some(true), bpm.is_dirty(&header_page_id));
+    assert!(bpm.flush_page(&header_page_id).unwrap());
+    assert_eq!(Some(false), bpm.is_dirty(&header_page_id));
+
+    // Push both pages out of the pool, dirty, then read them back from disk.
+    header.write().unwrap().tables = 4;
+    bpm.unpin_page(&header_page_id, true).unwrap();
+    bpm.unpin_page(&table_page_id, false).unwrap();
+    drop(header);
+    let others = create_n_pages(&bpm, 2);
+    assert!(!page_in_buffer(&bpm, &header_page_id));
+    assert!(!page_in_buffer(&bpm, &table_page_id));
+    for page_id in &others {
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+
+    let header = bpm.fetch_page_as::<HeaderPage>(&header_page_id).unwrap();
+    assert_eq!(4, header.read().unwrap().tables);
+    let table_page = bpm.fetch_page_as::<TablePage>(&table_page_id).unwrap();
+    let rid = RecordId::new(table_page_id, 0);
+    assert_eq!(tuple, table_page.read().unwrap().get_tuple(&rid).unwrap());
+    assert_eq!(Some(1), bpm.pin_count(&header_page_id));
+    assert_eq!(Some(1), bpm.pin_count(&table_page_id));
+
+    // With both frames pinned, neither another page nor one read back has a frame.
+    let new_page = bpm.new_page_as(HeaderPage::new);
+    assert!(matches!(new_page, Err(Error::NoEvictableFrame { pinned: 2, .. })));
+    let fetched = bpm.fetch_page_as::<TablePage>(&others[0]);
+    assert!(matches!(fetched, Err(Error::NoEvictableFrame { pinned: 2, .. })));
+}
+
+#[test]
+fn test_prefetch_pages() {
+    let bpm = get_bpm_with_pool_size(4);
+    let page_ids = create_n_pages(&bpm, 4);
+    set_pages_satisfying_criteria_to_evictable(&bpm, &page_ids, |_| true);
+    // Evicts the first two pages, leaving the last two unpinned next to the new, pinned ones.
+    let pinned = create_n_pages(&bpm, 2);
+
+    // Only one more page fits unpinned, next to the resident page asked for.
+    let asked = [page_ids[0], page_ids[1], page_ids[3]];
+    assert_eq!(1, bpm.prefetch_pages(&asked));
+    assert_eq!(Some(0), bpm.pin_count(&page_ids[0]));
+    assert_eq!(Some(0), bpm.pin_count(&page_ids[3]));
+    assert!(!page_in_buffer(&bpm, &page_ids[1]));
+    assert!(!page_in_buffer(&bpm, &page_ids[2]));
+    for page_id in &pinned {
+        assert_eq!(Some(1), bpm.pin_count(page_id));
+    }
+
+    // Resident pages aren't read again, and nothing is once the pool is full of them.
+    assert_eq!(0, bpm.prefetch_pages(&asked));
+    assert!(!page_in_buffer(&bpm, &page_ids[1]));
+
+    // A page read ahead is fetched like any other.
+    assert_eq!(page_ids[0], fetch_page_get_id(&page_ids[0], &bpm));
+    assert_eq!(Some(1), bpm.pin_count(&page_ids[0]));
+}
+
+/// The pages resident before a restart are preloaded into the free frames of a new pool, unpinned,
+/// and fetched from it without reading them again.
+#[test]
+fn test_preload() {
+    let disk_manager = new_disk_manager();
+    let build = |pool_size| {
+        BufferPoolManager::builder()
+            .pool_size(pool_size)
+            .replacer_k(5)
+            .disk_manager(Arc::clone(&disk_manager))
+            .metrics(Arc::default())
+            .build()
+    };
+    let bpm = build(6);
+    let page_ids = create_n_unpinned_pages(&bpm, 6);
+    bpm.flush_all_pages();
+    let hot_set = bpm.resident_page_ids();
+    assert_eq!(page_ids, hot_set);
+    drop(bpm);
+
+    let pool_size = 4;
+    let bpm = build(pool_size);
+    assert_eq!(pool_size, bpm.preload(&hot_set));
+    assert_eq!(hot_set[..pool_size], bpm.resident_page_ids());
+    assert!(bpm.pinned_pages().is_empty());
+    assert_eq!(pool_size, bpm.shards[0].replacer.read().unwrap().size());
+    check_pool(&bpm).unwrap();
+
+    // Resident pages aren't read again, and there is no free frame left for the others.
+    assert_eq!(0, bpm.preload(&hot_set));
+
+    bpm.reset_stats();
+    for page_id in &hot_set[..pool_size] {
+        assert_eq!(*page_id, fetch_page_get_id(page_id, &bpm));
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+    let stats = bpm.stats();
+    assert_eq!((pool_size as u64, 0), (stats.hits, stats.misses));
+}
+
+/// A scan through the frames left over by hot pages recycles its own frames, and leaves the hot
+/// pages resident, unless its pages are fetched as lookups.
+#[test]
+fn test_scan_keeps_hot_pages() {
+    for access_type in [AccessType::Scan, AccessType::Lookup] {
+        let bpm = get_bpm_with_pool_size(6);
+        let scanned = create_n_unpinned_pages(&bpm, 10);
+        let hot = create_n_unpinned_pages(&bpm, 4);
+        for page_id in &hot {
+            fetch_page(page_id, &bpm);
+            bpm.unpin_page(page_id, false).unwrap();
+        }
+
+        for page_id in &scanned {
+            bpm.fetch_page_with_access(page_id, access_type).expect(NO_CORRESPONDING_PAGE_MSG);
+            bpm.unpin_page(page_id, false).unwrap();
+        }
+        let hot_resident = hot.iter().filter(|page_id| bpm.contains_page(page_id)).count();
+        match access_type {
+            AccessType::Scan => assert_eq!(hot.len(), hot_resident),
+            _ => assert_eq!(0, hot_resident),
+        }
+        check_pool(&bpm).unwrap();
+    }
+}
+
+#[test]
+fn test_lru_replacer() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .shards(2)
+        .replacer(|frames| Arc::new(RwLock::new(LRUReplacer::new(frames))))
+        .disk_manager(new_disk_manager())
+        .build();
+
+    // With plain LRU, a scan pushes out pages accessed often, unlike with LRU-K.
+    let hot = create_n_unpinned_pages(&bpm, 4);
+    for page_id in &hot {
+        fetch_page(page_id, &bpm);
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+    let scanned = create_n_unpinned_pages(&bpm, 4);
+    for page_id in &scanned {
+        bpm.fetch_page_with_access(page_id, AccessType::Scan).expect(NO_CORRESPONDING_PAGE_MSG);
+        bpm.unpin_page(page_id, false).unwrap();
+    }
+    assert_eq!(scanned, bpm.resident_page_ids());
+    assert_eq!(4, bpm.stats().evictions);
+    check_pool(&bpm).unwrap();
+}
+
+#[test]
+fn test_replacement_policy() {
+    // Page a is accessed again, so LRU-K evicts b, the page with the earliest single access,
+    // where Clock evicts whatever its hand finds first once every page has been spared.
+    for (policy, evicted) in [(Policy::LruK { k: 2 }, 1), (Policy::Clock, 0)] {
+        let bpm = BufferPoolManager::builder()
+            .pool_size(3)
+            .replacement_policy(policy)
+            .disk_manager(new_disk_manager())
+            .build();
+
+        let page_ids = create_n_unpinned_pages(&bpm, 3);
+        fetch_page(&page_ids[0], &bpm);
+        bpm.unpin_page(&page_ids[0], false).unwrap();
+        bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+        assert!(!bpm.resident_page_ids().contains(&page_ids[evicted]), "{policy:?}");
+        check_pool(&bpm).unwrap();
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let metrics = Arc::new(Metrics::default());
+    let bpm = BufferPoolManager::builder()
+        .pool_size(2)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .metrics(Arc::clone(&metrics))
+        .build();
+
+    // Each new page is written to disk when allocated, and read back.
+    let page_ids = create_n_pages(&bpm, 2);
+    fetch_page(&page_ids[0], &bpm);
+    bpm.unpin_page(&page_ids[0], true).unwrap();
+    bpm.unpin_page(&page_ids[0], true).unwrap();
+
+    // The dirty page is flushed to make room for a third.
+    let page_id = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert!(bpm.fetch_page(&page_ids[0]).is_err());
+    bpm.unpin_page(&page_ids[1], false).unwrap();
+    fetch_page(&page_ids[0], &bpm);
+    assert!(!page_in_buffer(&bpm, &page_ids[1]));
+    assert!(page_in_buffer(&bpm, &page_id));
+
+    // The first page had k accesses when evicted, three accesses ago, and was brought back by
+    // evicting the second, which had a single one.
+    let mut eviction_distances = [0; EVICTION_DISTANCE_BUCKETS];
+    eviction_distances[2] = 1;
+    let replacer = ReplacerStats {
+        accesses: 5,
+        evictions: 2,
+        evictable_frames: 0,
+        cold_evictions: 1,
+        eviction_distance_sum: 3,
+        eviction_distances,
+        readmissions: 1,
+    };
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        BufferPoolStats {
+            hits: 1,
+            misses: 2,
+            evictions: 2,
+            flushes: 1,
+            writebacks: 1,
+            pages_created: 3,
+            pages_deleted: 0,
+            replacer,
+        },
+        snapshot.buffer_pool
+    );
+    assert_eq!((4, 4), (snapshot.disk.pages_read, snapshot.disk.pages_written));
+    assert_eq!(replacer, snapshot.replacer);
+    assert_eq!(Some(3.0), replacer.mean_eviction_distance());
+    assert_eq!(StatementStats::default(), snapshot.statements);
+}
+
+#[test]
+fn test_stats() {
+    let bpm = get_bpm_with_pool_size(2);
+    bpm.metrics.buffer_pool_hits.add(10);
+    bpm.reset_stats();
+    assert_eq!(BufferPoolStats::default(), bpm.stats());
+
+    let a = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    let b = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    bpm.unpin_page(&a, true).unwrap();
+    bpm.unpin_page(&b, false).unwrap();
+    // Both pages have been accessed once, so the older, dirty one makes room for a third.
+    let c = bpm.new_page().expect(NEW_PAGE_ERR_MSG);
+    assert!(!page_in_buffer(&bpm, &a));
+    bpm.unpin_page(&c, false).unwrap();
+    fetch_page(&b, &bpm);
+    bpm.unpin_page(&b, false).unwrap();
+    // Both pages are still short of k accesses, so b, first accessed before c, is evicted, clean.
+    fetch_page(&a, &bpm);
+    assert!(!page_in_buffer(&bpm, &b));
+    bpm.unpin_page(&a, false).unwrap();
+    // Deleting c frees its frame, so b comes back without an eviction.
+    assert!(bpm.delete_page(c).unwrap());
+    fetch_page(&b, &bpm);
+
+    assert_eq!(
+        BufferPoolStats {
+            hits: 1,
+            misses: 2,
+            evictions: 2,
+            flushes: 1,
+            writebacks: 1,
+            pages_created: 3,
+            pages_deleted: 1,
+            // Both pages evicted had fewer than k accesses, and both came back.
+            replacer: ReplacerStats {
+                accesses: 6,
+                evictions: 2,
+                evictable_frames: 1,
+                cold_evictions: 2,
+                readmissions: 2,
+                ..ReplacerStats::default()
+            },
+        },
+        bpm.stats()
+    );
+    assert_eq!(11, bpm.metrics().snapshot().buffer_pool.hits);
+
+    // Resetting starts the stats over, but the registry keeps counting.
+    bpm.reset_stats();
+    fetch_page(&b, &bpm);
+    assert_eq!(
+        BufferPoolStats {
+            hits: 1,
+            replacer: ReplacerStats {
+                accesses: 1,
+                evictable_frames: 1,
+                ..ReplacerStats::default()
+            },
+            ..BufferPoolStats::default()
+        },
+        bpm.stats()
+    );
+    assert_eq!(12, bpm.metrics().snapshot().buffer_pool.hits);
+}
+
+fn create_n_pages(bpm: &BufferPoolManager, n: usize) -> Vec<PageId> {
     (0..n)
         .map(|_| bpm.new_page().expect(NEW_PAGE_ERR_MSG))
         .collect()
@@ -618,7 +2192,7 @@ fn create_n_pages(bpm: &mut BufferPoolManager, n: usize) -> Vec<PageId> {
 /// Sets the subset of `page_ids` that satisfy the criteria `criteria` to evictable, and returns a
 /// list of those page ids whose corresponding page are now evictable.
 fn set_pages_satisfying_criteria_to_evictable<F>(
-    bpm: &mut BufferPoolManager,
+    bpm: &BufferPoolManager,
     page_ids: &Vec<PageId>,
     criteria: F,
 ) -> Vec<PageId>
@@ -630,10 +2204,10 @@ where
         .filter(|&page_id| criteria(page_id))
         .map(|page_id| {
             for _ in 0..bpm
-                .get_pin_count(&page_id)
+                .pin_count(&page_id)
                 .expect(NO_CORRESPONDING_PAGE_MSG)
             {
-                bpm.unpin_page(page_id, false);
+                bpm.unpin_page(page_id, false).unwrap();
             }
             page_id.clone() // Assuming PageId implements Clone
         })
@@ -644,18 +2218,20 @@ fn page_number_is_even(page_id: &PageId) -> bool {
     page_id % 2 == 0
 }
 
+/// A disk manager in memory: the pool behaves the same as over a file, see the backend parity
+/// tests in `storage::disk`.
 fn new_disk_manager() -> Arc<RwLock<DiskManager>> {
-    DiskManager::new_with_handle_for_test()
+    Arc::new(RwLock::new(DiskManager::new_in_memory()))
 }
 
-fn fetch_page_get_id(page_id: &PageId, bpm: &mut BufferPoolManager) -> PageId {
+fn fetch_page_get_id(page_id: &PageId, bpm: &BufferPoolManager) -> PageId {
     *fetch_page(&page_id, bpm)
         .read()
         .expect(NO_CORRESPONDING_PAGE_MSG)
         .page_id()
 }
 
-fn fetch_page(page_id: &PageId, bpm: &mut BufferPoolManager) -> TablePageHandle {
+fn fetch_page(page_id: &PageId, bpm: &BufferPoolManager) -> TablePageHandle {
     bpm.fetch_page(&page_id).expect(NO_CORRESPONDING_PAGE_MSG)
 }
 
@@ -663,10 +2239,11 @@ fn get_page_handle(
     buffer_pool_manager: &BufferPoolManager,
     page_id: &PageId,
 ) -> Option<TablePageHandle> {
-    buffer_pool_manager
+    let frames = buffer_pool_manager.shard(page_id).frames();
+    frames
         .page_table
         .get(page_id)
-        .map(|entry| Arc::clone(buffer_pool_manager.pages.get(*entry.frame_id()).unwrap()))
+        .and_then(|entry| frames.frame(*entry.frame_id()).unwrap().as_table())
 }
 
 fn get_bpm_with_pool_size(pool_size: usize) -> BufferPoolManager {
@@ -675,24 +2252,113 @@ fn get_bpm_with_pool_size(pool_size: usize) -> BufferPoolManager {
         .pool_size(pool_size)
         .replacer_k(5)
         .disk_manager(disk_manager)
+        .metrics(Arc::default())
         .build()
 }
 
 fn page_in_buffer(buffer_pool_manager: &BufferPoolManager, page_id: &PageId) -> bool {
-    let frame_metadata = buffer_pool_manager.page_table.get(page_id);
+    let frames = buffer_pool_manager.shard(page_id).frames();
+    let frame_metadata = frames.page_table.get(page_id);
     if frame_metadata.is_none() {
         return false;
     }
     let frame_id = frame_metadata.unwrap().frame_id();
-    !buffer_pool_manager.free_list.contains(frame_id)
+    !frames.free_list.contains(frame_id)
 }
 
-fn set_pages_to_dirty(bpm: &mut BufferPoolManager, page_ids: &Vec<PageId>) {
+fn set_pages_to_dirty(bpm: &BufferPoolManager, page_ids: &Vec<PageId>) {
     page_ids
         .iter()
         .for_each(|page_id| bpm.set_is_dirty(page_id, true));
 }
 
+/// A toy page type, e.g. the meta page of a catalog: just a count of tables.
+#[derive(Debug)]
+struct HeaderPage {
+    page_id: PageId,
+    lsn: Lsn,
+    is_dirty: bool,
+    tables: u32,
+}
+
+impl HeaderPage {
+    fn new(page_id: PageId) -> Self {
+        Self {
+            page_id,
+            lsn: 0,
+            is_dirty: false,
+            tables: 0,
+        }
+    }
+}
+
+impl Page for HeaderPage {
+    type InsertOutputType = u16;
+    type ConcretePageType = Self;
+
+    fn get_tuple(&self, _rid: &RecordId) -> Result<Tuple> {
+        errinput!("header pages hold no tuples")
+    }
+
+    fn insert_tuple(&mut self, _meta: TupleMetadata, _tuple: Tuple) -> Option<u16> {
+        None
+    }
+
+    fn get_tuple_metadata(&self, _rid: &RecordId) -> Result<TupleMetadata> {
+        errinput!("header pages hold no tuples")
+    }
+
+    fn update_tuple_metadata(&mut self, _metadata: &TupleMetadata, _rid: &RecordId) -> Result<()> {
+        errinput!("header pages hold no tuples")
+    }
+
+    fn get_is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    fn set_is_dirty(&mut self, is_dirty: bool) -> bool {
+        std::mem::replace(&mut self.is_dirty, is_dirty) != is_dirty
+    }
+
+    fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.lsn = lsn;
+    }
+
+    fn page_id(&self) -> &PageId {
+        &self.page_id
+    }
+
+    fn tuple_count(&self) -> u16 {
+        0
+    }
+
+    fn deleted_tuple_count(&self) -> u16 {
+        0
+    }
+
+    /// Layout: | page_id (4) | lsn (8) | tables (4) |
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        result[0..4].copy_from_slice(&self.page_id.to_le_bytes());
+        result[4..12].copy_from_slice(&self.lsn.to_le_bytes());
+        result[12..16].copy_from_slice(&self.tables.to_le_bytes());
+        result
+    }
+
+    fn deserialize(buffer: &[u8]) -> Self {
+        Self {
+            page_id: PageId::from_le_bytes(buffer[0..4].try_into().unwrap()),
+            lsn: Lsn::from_le_bytes(buffer[4..12].try_into().unwrap()),
+            is_dirty: false,
+            tables: u32::from_le_bytes(buffer[12..16].try_into().unwrap()),
+        }
+    }
+}
+
 fn create_temp_file() -> String {
     let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
 
@@ -0,0 +1,144 @@
+use crate::common::sched::sched_point;
+use crate::common::{Error, Metrics, Result};
+use crate::storage::buffer::buffer_pool_manager::FrameId;
+use crate::storage::buffer::lru_k_replacer::AccessType;
+use crate::storage::buffer::replacer::Replacer;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClockFrame {
+    /// Set by each access, and cleared by the hand passing over the frame, which spares it once.
+    pub(crate) referenced: bool,
+    pub(crate) is_evictable: bool,
+}
+
+/// A Clock (second-chance) replacer: a hand sweeps over the frames in a circle, evicting the
+/// first evictable frame it finds that wasn't accessed since the hand last passed it. An access
+/// only sets a bit, so it costs less than with LRU-K, at the price of a coarser order. All kinds
+/// of access count the same.
+#[derive(Debug)]
+pub struct ClockReplacer {
+    /// The frames tracked, indexed by frame ID, `None` for the others.
+    pub(crate) frames: Vec<Option<ClockFrame>>,
+    /// The frame the hand points at, to look at first on the next eviction.
+    pub(crate) hand: FrameId,
+    // Number of evictable frames in the replacer.
+    pub(crate) curr_size: usize,
+    /// Counts accesses and evictions, and tracks `curr_size`.
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+impl ClockReplacer {
+    pub fn new(num_frames: usize) -> Self {
+        Self {
+            frames: vec![None; num_frames],
+            hand: 0,
+            curr_size: 0,
+            metrics: Arc::default(),
+        }
+    }
+}
+
+impl Replacer for ClockReplacer {
+    /// Sets the frame's reference bit, whatever the type of access.
+    fn record_access(&mut self, frame_id: &FrameId, _access_type: AccessType) -> Result<()> {
+        sched_point!("replacer.record_access");
+        let Some(slot) = self.frames.get_mut(*frame_id) else {
+            return Err(Error::InvalidFrame(*frame_id));
+        };
+        self.metrics.replacer_accesses.incr();
+        match slot {
+            Some(frame) => frame.referenced = true,
+            None => {
+                *slot = Some(ClockFrame {
+                    referenced: true,
+                    is_evictable: false,
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Sweeps the hand over the frames, clearing the reference bits of the evictable ones, until
+    /// it comes to an evictable frame whose bit is already clear. Frames that aren't evictable
+    /// are passed over, and keep their bit. The hand then moves on past the frame evicted.
+    fn evict(&mut self) -> Option<FrameId> {
+        sched_point!("replacer.evict");
+        if self.curr_size == 0 {
+            return None;
+        }
+        // Every evictable frame is found unreferenced by the second time round, at the latest.
+        loop {
+            let frame_id = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+            match &mut self.frames[frame_id] {
+                Some(frame) if frame.is_evictable && frame.referenced => frame.referenced = false,
+                Some(frame) if frame.is_evictable => {
+                    self.frames[frame_id] = None;
+                    self.curr_size -= 1;
+                    self.metrics.replacer_evictable_frames.decr();
+                    self.metrics.replacer_evictions.incr();
+                    return Some(frame_id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn set_evictable(&mut self, frame_id: &FrameId, set_evictable: bool) -> Result<()> {
+        sched_point!("replacer.set_evictable");
+        let Some(slot) = self.frames.get_mut(*frame_id) else {
+            return Err(Error::InvalidFrame(*frame_id));
+        };
+        // A frame not tracked yet is registered without its reference bit, as it wasn't accessed.
+        let frame = slot.get_or_insert(ClockFrame {
+            referenced: false,
+            is_evictable: false,
+        });
+        if frame.is_evictable != set_evictable {
+            if set_evictable {
+                self.curr_size += 1;
+                self.metrics.replacer_evictable_frames.incr();
+            } else {
+                self.curr_size -= 1;
+                self.metrics.replacer_evictable_frames.decr();
+            }
+            frame.is_evictable = set_evictable;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, frame_id: &FrameId) -> Result<bool> {
+        let Some(Some(frame)) = self.frames.get(*frame_id) else {
+            return Ok(false);
+        };
+        if !frame.is_evictable {
+            return Err(Error::FrameNotEvictable(*frame_id));
+        }
+        self.frames[*frame_id] = None;
+        self.curr_size -= 1;
+        self.metrics.replacer_evictable_frames.decr();
+        Ok(true)
+    }
+
+    /// Also puts the hand back at the first frame.
+    fn clear(&mut self) {
+        self.frames.fill(None);
+        self.hand = 0;
+        self.metrics.replacer_evictable_frames.sub(self.curr_size as u64);
+        self.curr_size = 0;
+    }
+
+    fn size(&self) -> usize {
+        self.curr_size
+    }
+
+    fn is_evictable(&self, frame_id: &FrameId) -> Option<bool> {
+        Some(self.frames.get(*frame_id)?.as_ref()?.is_evictable)
+    }
+
+    fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        metrics.replacer_evictable_frames.add(self.curr_size as u64);
+        self.metrics = metrics;
+    }
+}
@@ -0,0 +1,5 @@
+mod clock_replacer;
+#[cfg(test)]
+mod tests;
+
+pub use clock_replacer::ClockReplacer;
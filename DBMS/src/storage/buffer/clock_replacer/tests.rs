@@ -0,0 +1,52 @@
+use super::*;
+use crate::storage::buffer::lru_k_replacer::AccessType;
+use crate::storage::buffer::replacer::Replacer;
+
+#[test]
+fn test_evict_sweeps_and_wraps_around() {
+    let mut replacer = ClockReplacer::new(5);
+    for frame_id in 0..5 {
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+        replacer.set_evictable(&frame_id, true).unwrap();
+    }
+
+    // Every frame is referenced, so the first sweep only clears the bits, and the hand comes back
+    // round to frame 0.
+    assert_eq!(Some(0), replacer.evict());
+    assert_eq!(1, replacer.hand);
+    assert!(replacer.frames.iter().flatten().all(|frame| !frame.referenced));
+
+    // An access spares frame 1 once more.
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    assert_eq!(Some(2), replacer.evict());
+    assert_eq!(Some(3), replacer.evict());
+    assert_eq!(Some(4), replacer.evict());
+
+    // The hand wraps past the end, and past frame 0, which is no longer tracked.
+    assert_eq!(0, replacer.hand);
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(None, replacer.evict());
+    assert_eq!(0, replacer.size());
+}
+
+#[test]
+fn test_evict_passes_over_frames_not_evictable() {
+    let mut replacer = ClockReplacer::new(4);
+    for frame_id in 0..4 {
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+    }
+    replacer.set_evictable(&1, true).unwrap();
+    replacer.set_evictable(&3, true).unwrap();
+
+    // The hand goes twice round, and frames 0 and 2 keep their bits.
+    assert_eq!(Some(1), replacer.evict());
+    assert!(replacer.frames[0].unwrap().referenced);
+    assert!(replacer.frames[2].unwrap().referenced);
+
+    // Made evictable, frame 2 still gets its second chance, where frame 3 was already passed.
+    replacer.set_evictable(&2, true).unwrap();
+    assert_eq!(Some(3), replacer.evict());
+    assert_eq!(Some(2), replacer.evict());
+    assert_eq!(None, replacer.evict());
+    assert_eq!(Some(false), replacer.is_evictable(&0));
+}
@@ -1,6 +1,11 @@
+use crate::common::sched::sched_point;
+use crate::common::{Error, Metrics, ReplacerStats, Result};
 use crate::storage::buffer::buffer_pool_manager::FrameId;
-use std::collections::{HashMap, VecDeque};
-use log::Level::Error;
+use crate::storage::buffer::lru_k_replacer::{LogicalClock, TimeSource};
+use crate::storage::buffer::replacer::Replacer;
+use crate::storage::disk::disk_manager::PageId;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum AccessType {
@@ -13,129 +18,346 @@ pub enum AccessType {
 #[derive(Debug)]
 pub struct LRUKNode {
     /// History of last seen k timestamps of this page. Least recent timestamp stored in front.
-    pub(crate) history: VecDeque<usize>,
+    /// Empty for a frame only registered by [`LRUKReplacer::set_evictable`].
+    pub(crate) history: VecDeque<u64>,
+    /// When the frame started being tracked, which stands for its history while that is empty.
+    pub(crate) registered: u64,
     pub(crate) k: usize,
     pub(crate) is_evictable: bool,
+    /// Whether the frame was only ever accessed by scans. Its history then holds just the first
+    /// of them, which protects nothing: such frames are evicted before any other.
+    pub(crate) scan_only: bool,
+    /// The page the frame holds, if the pool said, see [`Replacer::record_page`].
+    pub(crate) page_id: Option<PageId>,
 }
 
 impl LRUKNode {
-    fn new(k: usize) -> Self {
+    fn new(k: usize, registered: u64) -> Self {
         Self {
-            history: VecDeque::with_capacity(k),
+            history: VecDeque::new(),
+            registered,
             k,
             is_evictable: false,
+            scan_only: false,
+            page_id: None,
         }
     }
 
     /// # Returns
     /// - the k'th most recent timestamp's distance from the current timestamp if k accesses
-    ///   have been recorded, and `usize::MAX` otherwise
-    pub(crate) fn get_backwards_k_distance(&self, current_timestamp: usize) -> usize {
+    ///   have been recorded, and `u64::MAX` otherwise
+    pub(crate) fn get_backwards_k_distance(&self, current_timestamp: u64) -> u64 {
         if (self.has_infinite_backwards_k_distance()) {
-            usize::MAX
+            u64::MAX
         } else {
             current_timestamp - self.history.front().unwrap()
         }
     }
 
+    /// Appends an access to the history, dropping the oldest one if it already holds k of them.
+    /// The only way accesses are added, so that a history never holds more than k.
+    pub(crate) fn push_access(&mut self, timestamp: u64) {
+        if self.history.len() == self.k {
+            self.history.pop_front();
+        }
+        self.history.push_back(timestamp);
+    }
+
     pub(crate) fn has_infinite_backwards_k_distance(&self) -> bool {
         self.history.len() != self.k
     }
+
+    /// Where the frame stands in the eviction order, see [`LRUKReplacer::evict`]: frames only
+    /// ever scanned come first, then frames with an infinite backwards k-distance, then the rest,
+    /// each by their earliest recorded access, which for the rest is the largest k-distance
+    /// first, and then by their latest. A frame without any access yet counts as accessed once,
+    /// when it was registered.
+    pub(crate) fn eviction_key(&self) -> EvictionKey {
+        let class = if self.scan_only {
+            EvictionClass::ScanOnly
+        } else if self.has_infinite_backwards_k_distance() {
+            EvictionClass::Infinite
+        } else {
+            EvictionClass::Finite
+        };
+        let first = self.history.front().unwrap_or(&self.registered);
+        let last = self.history.back().unwrap_or(&self.registered);
+        (class, *first, *last)
+    }
 }
 
+/// The phases of eviction, in order: a frame of a later class is only evicted once no evictable
+/// frame of an earlier one is left, however its timestamps compare with theirs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum EvictionClass {
+    /// Only ever accessed by scans.
+    ScanOnly,
+    /// Accessed fewer than k times.
+    Infinite,
+    /// Accessed at least k times.
+    Finite,
+}
+
+/// The timestamp at which a replacer renumbers its timestamps unless built with another, far
+/// enough from `u64::MAX` that the time source can't overshoot it by much.
+const DEFAULT_COMPACTION_THRESHOLD: u64 = u64::MAX / 2;
+
+/// The rank of a frame in the eviction order, lowest first, see [`LRUKNode::eviction_key`].
+pub(crate) type EvictionKey = (EvictionClass, u64, u64);
+
 #[derive(Debug)]
 pub struct LRUKReplacer {
     pub(crate) node_store: HashMap<FrameId, LRUKNode>,
-    pub(crate) current_timestamp: usize,
+    /// The evictable frames, in the order they are to be evicted, kept up to date as frames are
+    /// accessed, so that [`Self::evict`] doesn't have to look through every frame.
+    pub(crate) eviction_order: BTreeSet<(EvictionKey, FrameId)>,
+    /// Timestamps the accesses, see [`LRUKReplacerBuilder::time_source`].
+    pub(crate) time_source: Box<dyn TimeSource>,
     // Number of evictable frames in the replacer. Note: this might not be the size of `node_store`!
     pub(crate) curr_size: usize,
     // Maximum number of frames that can be stored in the replacer.
     pub(crate) max_size: usize,
     pub(crate) k: usize,
+    /// Whether `Scan` accesses are kept from counting, see [`Self::record_access`].
+    pub(crate) scan_resistant: bool,
+    /// The timestamp at which the stored timestamps are renumbered, see
+    /// [`Self::compact_timestamps`].
+    pub(crate) compaction_threshold: u64,
+    /// The frames last evicted with a known page, and that page, oldest first. Holds at most
+    /// `max_size` of them, so that a page coming back counts as a re-admission if it does within
+    /// as many evictions as there are frames.
+    pub(crate) recently_evicted: VecDeque<(FrameId, PageId)>,
+    /// Counts accesses and evictions, and tracks `curr_size`.
+    pub(crate) metrics: Arc<Metrics>,
 }
 
 impl LRUKReplacer {
     pub fn new(num_frames: usize, k: usize) -> Self {
         Self {
             node_store: HashMap::new(),
-            current_timestamp: 0,
+            eviction_order: BTreeSet::new(),
+            time_source: Box::new(LogicalClock::default()),
             curr_size: 0,
             max_size: num_frames,
             k,
+            scan_resistant: true,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+            recently_evicted: VecDeque::new(),
+            metrics: Arc::default(),
         }
     }
 
     pub fn builder() -> LRUKReplacerBuilder {
         LRUKReplacerBuilder {
             node_store: HashMap::new(),
-            current_timestamp: 0,
+            time_source: Box::new(LogicalClock::default()),
             curr_size: 0,
             max_size: None,
             k: None,
+            scan_resistant: true,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        }
+    }
+
+    /// Returns a frame's backwards k-distance as of now, by the replacer's time source, or `None`
+    /// if the frame isn't tracked.
+    #[allow(dead_code)]
+    pub(crate) fn get_backwards_k_distance(&self, frame_id: &FrameId) -> Option<u64> {
+        let node = self.node_store.get(frame_id)?;
+        Some(node.get_backwards_k_distance(self.time_source.now()))
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn is_full_capacity(&self) -> bool {
+        self.curr_size == self.max_size
+    }
+
+    /// Returns what the replacer counted: its evictions, how many were of cold frames, and
+    /// the backwards k-distances of the others, and the pages brought back soon after being
+    /// evicted. The counts are those of its metrics registry, which the replacers of a pool
+    /// share, see [`Replacer::set_metrics`].
+    pub fn stats(&self) -> ReplacerStats {
+        self.metrics.snapshot().replacer
+    }
+
+    /// Ticks the time source after an access or registration, renumbering the timestamps if it
+    /// reached the compaction threshold.
+    fn tick(&mut self) {
+        self.time_source.tick();
+        if self.time_source.now() >= self.compaction_threshold {
+            self.compact_timestamps();
         }
     }
 
+    /// Renumbers the timestamps stored, from 0 up, in the same order, and turns the time source
+    /// back to just after the last of them, so that timestamps never run out however long the
+    /// replacer runs. The eviction order only depends on how timestamps compare, so it doesn't
+    /// change, but the backwards k-distances shrink to the number of distinct timestamps in
+    /// between.
+    pub(crate) fn compact_timestamps(&mut self) {
+        // A frame's registration only counts until its first access.
+        let mut timestamps: Vec<u64> = (self.node_store.values())
+            .flat_map(|node| {
+                if node.history.is_empty() {
+                    vec![node.registered]
+                } else {
+                    Vec::from(node.history.clone())
+                }
+            })
+            .collect();
+        timestamps.sort_unstable();
+        timestamps.dedup();
+        let renumber = |timestamp: &mut u64| {
+            *timestamp = timestamps.binary_search(timestamp).unwrap() as u64;
+        };
+        for node in self.node_store.values_mut() {
+            if node.history.is_empty() {
+                renumber(&mut node.registered);
+            }
+            node.history.iter_mut().for_each(renumber);
+        }
+        self.eviction_order = (self.node_store.iter())
+            .filter(|(_, node)| node.is_evictable)
+            .map(|(frame_id, node)| (node.eviction_key(), *frame_id))
+            .collect();
+        self.time_source.rebase(timestamps.len() as u64);
+    }
+
+    /// Drops an evictable frame, along with its access history, and returns it.
+    fn forget(&mut self, frame_id: &FrameId) -> LRUKNode {
+        let frame = self.node_store.remove(frame_id).unwrap();
+        debug_assert!(frame.is_evictable);
+        self.eviction_order.remove(&(frame.eviction_key(), *frame_id));
+        self.curr_size -= 1; // Decrement the size since a frame was removed
+        self.metrics.replacer_evictable_frames.decr();
+        self.debug_check_size();
+        frame
+    }
+
+    /// Evicts a frame, counting it as cold or by its backwards k-distance, and remembering its
+    /// page to tell if it comes back.
+    fn evict_frame(&mut self, frame_id: &FrameId) {
+        let frame = self.forget(frame_id);
+        if frame.has_infinite_backwards_k_distance() {
+            self.metrics.replacer_cold_evictions.incr();
+        } else {
+            let distance = frame.get_backwards_k_distance(self.time_source.now());
+            self.metrics.record_eviction_distance(distance);
+        }
+        if let Some(page_id) = frame.page_id {
+            if self.recently_evicted.len() == self.max_size {
+                self.recently_evicted.pop_front();
+            }
+            self.recently_evicted.push_back((*frame_id, page_id));
+        }
+    }
+
+    /// Checks, in debug builds, that `curr_size` is the number of evictable frames, after each
+    /// change to it. The eviction order holds exactly the evictable frames, see
+    /// [`check_replacer`], so its length stands for counting them, without making every change
+    /// take time linear in the number of frames.
+    ///
+    /// [`check_replacer`]: crate::sim::invariants::check_replacer
+    fn debug_check_size(&self) {
+        debug_assert_eq!(
+            self.curr_size,
+            self.eviction_order.len(),
+            "replacer size is not the number of evictable frames"
+        );
+    }
+}
+
+impl Replacer for LRUKReplacer {
     /// Evict the frame with the largest backwards k-distance. If a frame has
     /// not been accessed k times, its backwards k-distance is considered to
     /// be infinite. If there are multiple frames with infinite k-distance,
-    /// choose the one to evict based on LRU.
+    /// choose the one to evict based on LRU of their earliest recorded access.
+    /// Frames with an infinite k-distance are always evicted before the others,
+    /// and ties between equal finite k-distances go to the frame whose most
+    /// recent access is the oldest.
+    ///
+    /// Frames only ever accessed by scans come first, though, whatever the distance of the
+    /// others, so that a scan recycles its own frames rather than those of hot pages.
+    ///
+    /// The evictable frames are kept in this order as they are accessed, so this takes
+    /// logarithmic time, however many frames there are.
     ///
     /// # Returns
     /// - an Option that is either `Some(frame_id)` if a frame with id `frame_id` was evicted, and
     ///   `None` otherwise
-    pub fn evict(&mut self) -> Option<FrameId> {
-        let mut largest_k_frame: Option<FrameId> = None;
-        let mut largest_k_earliest_timestamp: usize = usize::MAX;
-        let mut largest_k_dist: usize = 0;
+    fn evict(&mut self) -> Option<FrameId> {
+        sched_point!("replacer.evict");
+        let (_, frame_id) = *self.eviction_order.first()?;
+        self.evict_frame(&frame_id);
+        self.metrics.replacer_evictions.incr();
+        Some(frame_id)
+    }
 
-        for (frame, node) in &mut self.node_store {
-            if node.is_evictable {
-                let node_k_dist = node.get_backwards_k_distance(self.current_timestamp);
-
-                if node_k_dist > largest_k_dist {
-                    largest_k_dist = node_k_dist;
-                    largest_k_frame = Some(*frame);
-                    largest_k_earliest_timestamp = *node.history.back().unwrap();
-                } else if node_k_dist == largest_k_dist {
-                    if *node.history.back().unwrap() < largest_k_earliest_timestamp {
-                        largest_k_frame = Some(*frame);
-                        largest_k_earliest_timestamp = *node.history.back().unwrap();
-                    }
-                }
-            }
+    /// Evicts the first `n` frames of the eviction order, in a single pass over it, see
+    /// [`Self::evict`].
+    fn evict_n(&mut self, n: usize) -> Vec<FrameId> {
+        sched_point!("replacer.evict");
+        let victims: Vec<FrameId> = (self.eviction_order.iter())
+            .take(n)
+            .map(|(_, frame_id)| *frame_id)
+            .collect();
+        for frame_id in &victims {
+            self.evict_frame(frame_id);
         }
-
-        self.remove(&largest_k_frame?);
-        largest_k_frame
-
+        self.metrics.replacer_evictions.add(victims.len() as u64);
+        victims
     }
 
-    /// Record an access to a frame at the current timestamp.
+    /// Record an access to a frame at the current timestamp of the replacer's time source.
     ///
-    /// This method should update the k-history of the frame and increment the current timestamp.
-    /// If the given `frame_id` is invalid (i.e. >= `max_size`), this method throws an exception.
+    /// This method should update the k-history of the frame and tick the time source.
+    /// If the given `frame_id` is invalid (i.e. >= `max_size`), this method returns
+    /// [`Error::InvalidFrame`].
     ///
     /// # Parameters
     /// - `frame_id`: The id of the frame that was accessed
     /// - `access_type`: The type of access that occurred (e.g., Lookup, Scan, Index)
-    pub fn record_access(&mut self, frame_id: &FrameId, _access_type: AccessType) {
+    ///
+    /// A `Scan` access only counts for a frame without any history yet: a sequential scan touches
+    /// each page once in passing, and shouldn't make the pages it reads look hotter than they are,
+    /// or push out the pages other accesses keep coming back to. Until another kind of access
+    /// comes, the frame is marked as only scanned, see [`Self::evict`]; that access then replaces
+    /// the scan's entry in its history. A replacer built with
+    /// [`LRUKReplacerBuilder::scan_resistant`] off counts scans like any other access.
+    ///
+    /// `Lookup`, `Index` and `Unknown` accesses all count the same.
+    fn record_access(&mut self, frame_id: &FrameId, access_type: AccessType) -> Result<()> {
+        sched_point!("replacer.record_access");
         if *frame_id >= self.max_size {
-            panic!("Invalid frame_id");
+            return Err(Error::InvalidFrame(*frame_id));
         }
+        self.metrics.replacer_accesses.incr();
 
+        let is_scan = self.scan_resistant && access_type == AccessType::Scan;
+        let timestamp = self.time_source.now();
         if let Some(node) = self.node_store.get_mut(frame_id) {
-            if node.history.len() < node.k {
-                node.history.push_back(self.current_timestamp);
-            } else {
-                node.history.pop_front();
-                node.history.push_back(self.current_timestamp);
+            if is_scan {
+                return Ok(());
+            }
+            if node.is_evictable {
+                self.eviction_order.remove(&(node.eviction_key(), *frame_id));
+            }
+            if node.scan_only {
+                node.scan_only = false;
+                node.history.clear();
+            }
+            node.push_access(timestamp);
+            if node.is_evictable {
+                self.eviction_order.insert((node.eviction_key(), *frame_id));
             }
         } else {
-            let mut new_node = LRUKNode::new(self.k);
-            new_node.history.push_back(self.current_timestamp);
+            let mut new_node = LRUKNode::new(self.k, timestamp);
+            new_node.push_access(timestamp);
+            new_node.scan_only = is_scan;
             self.node_store.insert(frame_id.clone(), new_node);
         }
-        self.current_timestamp += 1;
+        self.tick();
+        Ok(())
     }
 
     /// Set the evictable status of a frame. Note that replacer's curr_size is equal
@@ -144,26 +366,42 @@ impl LRUKReplacer {
     /// If a frame was previously evictable and is set to be non-evictable,
     /// then curr_size should decrement. If a frame was previously non-evictable and
     /// is to be set to evictable, then curr_size should increment. If the frame id is
-    /// invalid, throw an exception or abort the process.
+    /// invalid, [`Error::InvalidFrame`] is returned.
+    ///
+    /// A valid frame that isn't tracked yet is registered, with an empty history, and ticks the
+    /// time source as an access would: it has an infinite backwards k-distance, and ranks among
+    /// such frames by when it was registered, until it is accessed.
     ///
     /// For other scenarios, this function should terminate without modifying anything.
     ///
     /// # Parameters
     /// - `frame_id`: id of the frame whose 'evictable' status will be modified
     /// - `set_evictable`: whether the given frame is evictable or not
-    pub fn set_evictable(&mut self, frame_id: &FrameId, set_evictable: bool) {
-        if let Some(frame) = self.node_store.get_mut(frame_id) {
-            if frame.is_evictable != set_evictable {
-                if set_evictable {
-                    self.curr_size += 1;
-                } else {
-                    self.curr_size -=1;
-                }
-                frame.is_evictable = set_evictable;
+    fn set_evictable(&mut self, frame_id: &FrameId, set_evictable: bool) -> Result<()> {
+        sched_point!("replacer.set_evictable");
+        if *frame_id >= self.max_size {
+            return Err(Error::InvalidFrame(*frame_id));
+        }
+        if !self.node_store.contains_key(frame_id) {
+            let registered = self.time_source.now();
+            self.node_store.insert(*frame_id, LRUKNode::new(self.k, registered));
+            self.tick();
+        }
+        let frame = self.node_store.get_mut(frame_id).unwrap();
+        if frame.is_evictable != set_evictable {
+            if set_evictable {
+                self.curr_size += 1;
+                self.metrics.replacer_evictable_frames.incr();
+                self.eviction_order.insert((frame.eviction_key(), *frame_id));
+            } else {
+                self.curr_size -= 1;
+                self.metrics.replacer_evictable_frames.decr();
+                self.eviction_order.remove(&(frame.eviction_key(), *frame_id));
             }
-        } else {
-            panic!("Invalid frame ID provided");
+            frame.is_evictable = set_evictable;
+            self.debug_check_size();
         }
+        Ok(())
     }
 
     /// Remove an evictable frame from the replacer, along with its access history.
@@ -173,52 +411,69 @@ impl LRUKReplacer {
     /// with the largest backward k-distance. This function removes the specified frame id,
     /// no matter what its backward k-distance is.
     ///
-    /// If `remove` is called on a non-evictable frame, throw an exception or abort the
-    /// process.
+    /// If `remove` is called on a non-evictable frame, [`Error::FrameNotEvictable`] is returned,
+    /// and the frame is left as it is.
     ///
     /// If the specified frame is not found, directly return from this function.
     ///
     /// # Parameters
     /// - `frame_id`: id of the frame to be removed
-    pub fn remove(&mut self, frame_id: &FrameId) {
-        if let Some(frame) = self.node_store.get(frame_id) {
-            if frame.is_evictable {
-                self.node_store.remove(frame_id);
-                self.curr_size -= 1; // Decrement the size since a frame was removed
-            } else {
-                panic!("Tried to remove a non-evictable frame");
+    fn remove(&mut self, frame_id: &FrameId) -> Result<bool> {
+        match self.node_store.get(frame_id) {
+            Some(frame) if !frame.is_evictable => Err(Error::FrameNotEvictable(*frame_id)),
+            Some(_) => {
+                self.forget(frame_id);
+                Ok(true)
             }
+            None => Ok(false),
         }
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn is_full_capacity(&self) -> bool {
-        self.curr_size == self.max_size
+    /// A page that was evicted recently, from any frame, counts as re-admitted.
+    fn record_page(&mut self, frame_id: &FrameId, page_id: PageId) {
+        let Some(node) = self.node_store.get_mut(frame_id) else {
+            return;
+        };
+        node.page_id = Some(page_id);
+        let evicted = (self.recently_evicted.iter()).position(|(_, evicted)| *evicted == page_id);
+        if let Some(position) = evicted {
+            self.recently_evicted.remove(position);
+            self.metrics.replacer_readmissions.incr();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.node_store.clear();
+        self.eviction_order.clear();
+        self.recently_evicted.clear();
+        self.metrics.replacer_evictable_frames.sub(self.curr_size as u64);
+        self.curr_size = 0;
+        self.debug_check_size();
     }
 
     // Returns the number of evictable frames in the replacer.
-    pub fn size(&self) -> usize {
+    fn size(&self) -> usize {
         self.curr_size
     }
 
-    fn increment_current_size(&mut self) {
-        self.curr_size += 1;
+    fn is_evictable(&self, frame_id: &FrameId) -> Option<bool> {
+        Some(self.node_store.get(frame_id)?.is_evictable)
     }
 
-    fn decrement_current_size(&mut self) {
-        if self.curr_size == 0 {
-            panic!("Attempted to decrement current size, which is already 0");
-        }
-        self.curr_size -= 1;
+    fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        metrics.replacer_evictable_frames.add(self.curr_size as u64);
+        self.metrics = metrics;
     }
 }
 
 pub struct LRUKReplacerBuilder {
     node_store: HashMap<FrameId, LRUKNode>,
-    current_timestamp: usize,
+    time_source: Box<dyn TimeSource>,
     curr_size: usize,
     max_size: Option<usize>,
     k: Option<usize>,
+    scan_resistant: bool,
+    compaction_threshold: u64,
 }
 
 impl LRUKReplacerBuilder {
@@ -234,15 +489,45 @@ impl LRUKReplacerBuilder {
         self
     }
 
+    /// Whether `Scan` accesses are kept from counting towards a frame's history, so that a
+    /// sequential scan doesn't push hot pages out. On by default.
+    pub fn scan_resistant(mut self, scan_resistant: bool) -> Self {
+        self.scan_resistant = scan_resistant;
+        self
+    }
+
+    /// Where the timestamps of accesses come from. Defaults to a [`LogicalClock`], counting time
+    /// in accesses; a [`MonotonicClock`] ages frames by wall-clock time instead, so that frames
+    /// left idle fall behind even while nothing else is accessed.
+    ///
+    /// [`MonotonicClock`]: crate::storage::buffer::lru_k_replacer::MonotonicClock
+    pub fn time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Box::new(time_source);
+        self
+    }
+
+    /// The timestamp at which the replacer renumbers the timestamps it stores, see
+    /// [`LRUKReplacer::compact_timestamps`]. Defaults to half of `u64::MAX`; lower ones are for
+    /// tests.
+    pub fn compaction_threshold(mut self, compaction_threshold: u64) -> Self {
+        self.compaction_threshold = compaction_threshold;
+        self
+    }
+
     pub fn build(self) -> LRUKReplacer {
         LRUKReplacer {
             node_store: self.node_store,
-            current_timestamp: self.current_timestamp,
+            eviction_order: BTreeSet::new(),
+            time_source: self.time_source,
             curr_size: self.curr_size,
             max_size: self
                 .max_size
                 .expect("Replacer size was not specified before build."),
             k: self.k.expect("k was not specified before build."),
+            scan_resistant: self.scan_resistant,
+            compaction_threshold: self.compaction_threshold,
+            recently_evicted: VecDeque::new(),
+            metrics: Arc::default(),
         }
     }
 }
@@ -1,5 +1,9 @@
 mod lru_k_replacer;
 #[cfg(test)]
 mod tests;
+mod time_source;
 
 pub use lru_k_replacer::{AccessType, LRUKReplacer, LRUKReplacerBuilder};
+pub use time_source::{LogicalClock, MockTimeSource, MonotonicClock, TimeSource};
+#[cfg(test)]
+pub(crate) use lru_k_replacer::EvictionKey;
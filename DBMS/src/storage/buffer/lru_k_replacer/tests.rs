@@ -1,10 +1,14 @@
 use super::AccessType;
 use super::*;
-use crate::assert_errors;
-use crate::common::constants::INF;
+use crate::common::{Error, ReplacerStats, EVICTION_DISTANCE_BUCKETS};
+use crate::sim::invariants::check_replacer;
 use crate::storage::buffer::buffer_pool_manager::FrameId;
 use crate::storage::buffer::lru_k_replacer::lru_k_replacer::LRUKNode;
+use crate::storage::buffer::replacer::Replacer;
+use crate::storage::disk::disk_manager::PageId;
 use rand::{random, Rng};
+use std::collections::VecDeque;
+use std::time::Instant;
 
 const DUMMY_ACCESS_TYPE: AccessType = AccessType::Lookup;
 
@@ -41,7 +45,7 @@ fn test_evict_basic() {
 
     // evict based on LRU even if one of the frames has non-infinite backwards k-distance.
     let fid4 = get_new_frame_and_record_access(&mut replacer);
-    replacer.set_evictable(&fid4, true);
+    replacer.set_evictable(&fid4, true).unwrap();
     record_access_frames_n_times(&mut replacer, &vec![fid2, fid3], k);
     assert!(!get_node(&replacer, &fid2).has_infinite_backwards_k_distance());
     assert!(!get_node(&replacer, &fid3).has_infinite_backwards_k_distance());
@@ -52,10 +56,11 @@ fn test_evict_basic() {
     let fid3_k_distance = get_backwards_k_distance_for_node(&mut replacer, &fid3);
     assert!(fid2_k_distance > fid3_k_distance);
     assert_eq!(replacer.evict().unwrap(), fid2);
+    check_replacer(&replacer).unwrap();
 }
 
 #[test]
-fn test_record_access_panics_for_invalid_frame_id() {
+fn test_record_access_invalid_frame_id() {
     let replacer_size = 5_usize;
     let mut replacer = LRUKReplacer::builder()
         .max_size(replacer_size)
@@ -63,34 +68,405 @@ fn test_record_access_panics_for_invalid_frame_id() {
         .build();
 
     let invalid_frame_id = replacer_size as FrameId;
-    assert_errors!(replacer.record_access(&invalid_frame_id, AccessType::Lookup));
+    assert_eq!(
+        Err(Error::InvalidFrame(invalid_frame_id)),
+        replacer.record_access(&invalid_frame_id, AccessType::Lookup)
+    );
+    assert!(replacer.node_store.is_empty());
 }
 
 #[test]
 fn test_record_access() {
     let mut replacer = LRUKReplacer::builder().max_size(10).k(5).build();
-    let mut current_timestamp = replacer.current_timestamp;
+    let mut current_timestamp = replacer.time_source.now();
 
     // record access on a new frame will add new node with curr timestamp and increment timestamp.
     let frame_id = get_new_frame_and_record_access(&mut replacer);
     let node = get_node(&replacer, &frame_id);
     assert_eq!(replacer.node_store.len(), 1);
-    assert_eq!(replacer.current_timestamp, current_timestamp + 1);
+    assert_eq!(replacer.time_source.now(), current_timestamp + 1);
     assert_eq!(node.history.len(), 1);
     assert_eq!(*node.history.back().unwrap(), current_timestamp);
 
     current_timestamp += 1;
 
     // record access on existing frame will push current timestamp to corresponding node's history.
-    replacer.record_access(&frame_id, AccessType::Lookup);
+    replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
     let node = get_node(&replacer, &frame_id);
     assert_eq!(replacer.node_store.len(), 1);
-    assert_eq!(replacer.current_timestamp, current_timestamp + 1);
+    assert_eq!(replacer.time_source.now(), current_timestamp + 1);
     assert_eq!(node.history.len(), 2);
     assert_eq!(*node.history.back().unwrap(), current_timestamp);
     assert_eq!(*node.history.front().unwrap(), current_timestamp - 1);
 }
 
+#[test]
+fn test_scan_access_only_counts_for_new_frames() {
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(2).build();
+
+    replacer.record_access(&0, AccessType::Scan).unwrap();
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    // Scanning frame 0 over and over doesn't make it look hotter than frame 1.
+    for _ in 0..3 {
+        replacer.record_access(&0, AccessType::Scan).unwrap();
+    }
+    assert_eq!(get_node(&replacer, &0).history.len(), 1);
+
+    replacer.set_evictable(&0, true).unwrap();
+    replacer.set_evictable(&1, true).unwrap();
+    assert_eq!(Some(0), replacer.evict());
+}
+
+#[test]
+fn test_scanned_frames_are_evicted_first() {
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(2).build();
+
+    // Frame 0 has a single lookup, so an infinite distance, like the scanned frames that follow.
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    replacer.record_access(&1, AccessType::Scan).unwrap();
+    replacer.record_access(&2, AccessType::Scan).unwrap();
+    // A lookup of a scanned frame replaces the scan in its history.
+    replacer.record_access(&2, AccessType::Lookup).unwrap();
+    assert!(!get_node(&replacer, &2).scan_only);
+    assert_eq!(get_node(&replacer, &2).history.len(), 1);
+
+    for frame_id in 0..3 {
+        replacer.set_evictable(&frame_id, true).unwrap();
+    }
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(Some(0), replacer.evict());
+    assert_eq!(Some(2), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_scan_leaves_hot_frames_alone() {
+    let k = 2_usize;
+    for scan_resistant in [true, false] {
+        let mut replacer = LRUKReplacer::builder()
+            .max_size(10)
+            .k(k)
+            .scan_resistant(scan_resistant)
+            .build();
+        let hot = vec![0, 1, 2];
+        record_access_frames_n_times(&mut replacer, &hot, k);
+        let timestamp = replacer.time_source.now();
+        let k_distances = |replacer: &LRUKReplacer| {
+            (hot.iter())
+                .map(|frame_id| get_node(replacer, frame_id).get_backwards_k_distance(timestamp))
+                .collect::<Vec<_>>()
+        };
+        let hot_k_distances = k_distances(&replacer);
+
+        // A single pass over every frame, hot ones included.
+        for frame_id in 0..10 {
+            replacer.record_access(&frame_id, AccessType::Scan).unwrap();
+        }
+        set_multiple_frames_evictable(&mut replacer, &(0..10).collect());
+        if !scan_resistant {
+            assert_ne!(hot_k_distances, k_distances(&replacer));
+            assert!(replacer.node_store.values().all(|node| !node.scan_only));
+            continue;
+        }
+        assert_eq!(hot_k_distances, k_distances(&replacer));
+
+        // The scanned frames go first, in the order they were scanned, then the hot ones.
+        let evicted: Vec<FrameId> = std::iter::from_fn(|| replacer.evict()).collect();
+        assert_eq!(vec![3, 4, 5, 6, 7, 8, 9, 0, 1, 2], evicted);
+        check_replacer(&replacer).unwrap();
+    }
+}
+
+#[test]
+fn test_evict_infinite_distance_by_earliest_access() {
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(3).build();
+
+    // Two frames each with one access go in the order they were accessed.
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    set_multiple_frames_evictable(&mut replacer, &vec![1, 0]);
+    assert_eq!(Some(0), replacer.evict());
+    assert_eq!(Some(1), replacer.evict());
+
+    // Frame 2 was accessed first, and is still short of k accesses after a second one, so it
+    // goes before frame 3, though frame 3 was last accessed earlier.
+    replacer.record_access(&2, AccessType::Lookup).unwrap();
+    replacer.record_access(&3, AccessType::Lookup).unwrap();
+    replacer.record_access(&2, AccessType::Lookup).unwrap();
+    set_multiple_frames_evictable(&mut replacer, &vec![2, 3]);
+    assert_eq!(Some(2), replacer.evict());
+    assert_eq!(Some(3), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_evict_infinite_distance_before_finite() {
+    let k = 2_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+
+    // Frame 0 has k accesses, all older than frame 1's single one.
+    record_access_frame_n_times(&mut replacer, 0, k);
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    record_access_frame_n_times(&mut replacer, 2, k);
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1, 2]);
+
+    assert_eq!(Some(1), replacer.evict());
+    // Then the largest finite distance.
+    assert_eq!(Some(0), replacer.evict());
+    assert_eq!(Some(2), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_evict_cold_frame_before_enormous_finite_distance() {
+    let k = 2_usize;
+    let clock = MockTimeSource::new();
+    let mut replacer = LRUKReplacer::builder()
+        .max_size(10)
+        .k(k)
+        .time_source(clock.clone())
+        .compaction_threshold(u64::MAX)
+        .build();
+
+    // Frame 0 is warm, but so long ago that its distance is close to the largest there is.
+    record_access_frame_n_times(&mut replacer, 0, k);
+    clock.advance(u64::MAX - 1);
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1]);
+    assert_eq!(Some(u64::MAX - 1), replacer.get_backwards_k_distance(&0));
+
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(Some(0), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_evict_cold_frames_by_first_access() {
+    let k = 3_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+
+    // Frame 4 is warm and accessed first. Of the cold frames, frame 2 was first accessed
+    // earliest, though it was also accessed last, then frame 0, then frame 1.
+    record_access_frame_n_times(&mut replacer, 4, k);
+    for frame_id in [2, 0, 1, 0, 2] {
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+    }
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1, 2, 4]);
+
+    let evicted: Vec<FrameId> = std::iter::from_fn(|| replacer.evict()).collect();
+    assert_eq!(vec![2, 0, 1, 4], evicted);
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_set_evictable_before_access() {
+    let k = 2_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+
+    // Frame 3 is registered between the accesses to frames 1 and 2, and ranks among the frames
+    // with an infinite distance by when it was.
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    replacer.set_evictable(&3, true).unwrap();
+    replacer.record_access(&2, AccessType::Lookup).unwrap();
+    record_access_frame_n_times(&mut replacer, 0, k);
+    assert!(get_node(&replacer, &3).history.is_empty());
+    assert_eq!(Some(u64::MAX), replacer.get_backwards_k_distance(&3));
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1, 2]);
+    check_replacer(&replacer).unwrap();
+
+    let evicted: Vec<FrameId> = std::iter::from_fn(|| replacer.evict()).collect();
+    assert_eq!(vec![1, 3, 2, 0], evicted);
+
+    // Once accessed, a registered frame's history starts with that access.
+    replacer.set_evictable(&4, false).unwrap();
+    replacer.record_access(&5, AccessType::Lookup).unwrap();
+    replacer.record_access(&4, AccessType::Lookup).unwrap();
+    set_multiple_frames_evictable(&mut replacer, &vec![4, 5]);
+    assert_eq!(1, get_node(&replacer, &4).history.len());
+    assert_eq!(Some(5), replacer.evict());
+    assert_eq!(Some(4), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_stats() {
+    let k = 2_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(4).k(k).build();
+
+    // Frames 0 and 1 are warm, at timestamps 0 to 3, and frames 2 and 3 cold, at 4 and 5.
+    record_access_frames_n_times(&mut replacer, &vec![0, 1], k);
+    record_access_frames_n_times(&mut replacer, &vec![2, 3], 1);
+    for (frame_id, page_id) in [(0, 100), (1, 101), (2, 102), (3, 103)] {
+        replacer.record_page(&frame_id, page_id);
+    }
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1, 2, 3]);
+    let evicted: Vec<FrameId> = std::iter::from_fn(|| replacer.evict()).collect();
+    assert_eq!(vec![2, 3, 0, 1], evicted);
+
+    // Page 100 comes back in another frame, and page 104 is new.
+    replacer.record_access(&3, AccessType::Lookup).unwrap();
+    replacer.record_page(&3, 100);
+    replacer.record_access(&2, AccessType::Lookup).unwrap();
+    replacer.record_page(&2, 104);
+
+    // At timestamp 6, frame 0 had a distance of 6, and frame 1 of 4.
+    let mut eviction_distances = [0; EVICTION_DISTANCE_BUCKETS];
+    eviction_distances[3] = 2;
+    assert_eq!(
+        ReplacerStats {
+            accesses: 8,
+            evictions: 4,
+            evictable_frames: 0,
+            cold_evictions: 2,
+            eviction_distance_sum: 10,
+            eviction_distances,
+            readmissions: 1,
+        },
+        replacer.stats()
+    );
+    assert_eq!(Some(5.0), replacer.stats().mean_eviction_distance());
+
+    // A page only counts once, and not at all once enough others were evicted since.
+    replacer.record_page(&3, 100);
+    assert_eq!(1, replacer.stats().readmissions);
+    for frame_id in [2, 3] {
+        replacer.set_evictable(&frame_id, true).unwrap();
+    }
+    replacer.evict_n(2);
+    for frame_id in 0..4 {
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+        replacer.set_evictable(&frame_id, true).unwrap();
+        replacer.record_page(&frame_id, 200 + frame_id as PageId);
+    }
+    replacer.evict_n(4);
+    assert_eq!(
+        VecDeque::from([(0, 200), (1, 201), (2, 202), (3, 203)]),
+        replacer.recently_evicted
+    );
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    replacer.record_page(&0, 104);
+    assert_eq!(1, replacer.stats().readmissions);
+}
+
+#[test]
+fn test_history_bounded_by_k() {
+    let k = 3_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+    record_access_frame_n_times(&mut replacer, 0, 10);
+    let history = &get_node(&replacer, &0).history;
+    assert_eq!(VecDeque::from([7, 8, 9]), *history);
+
+    // A large k doesn't take memory up front.
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(1 << 20).build();
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    assert!(get_node(&replacer, &0).history.capacity() < 1 << 20);
+}
+
+/// Two replacers see the same random accesses, one renumbering its timestamps every few of them,
+/// and evict the same frames in the same order.
+#[test]
+fn test_compact_timestamps_keeps_eviction_order() {
+    let k = 2_usize;
+    // Ten frames hold at most twenty distinct timestamps between them.
+    let threshold = 32;
+    let mut rng = rand::thread_rng();
+    let mut compacted = LRUKReplacer::builder()
+        .max_size(10)
+        .k(k)
+        .compaction_threshold(threshold)
+        .build();
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+    for _ in 0..200 {
+        let frame_id = rng.gen_range(0..10);
+        let evictable = random_bool();
+        for replacer in [&mut compacted, &mut replacer] {
+            replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+            replacer.set_evictable(&frame_id, evictable).unwrap();
+        }
+        assert!(compacted.time_source.now() < threshold);
+        check_replacer(&compacted).unwrap();
+        if rng.gen_bool(0.1) {
+            assert_eq!(replacer.evict(), compacted.evict());
+        }
+    }
+    assert!(replacer.time_source.now() >= 200);
+
+    let evicted: Vec<FrameId> = std::iter::from_fn(|| replacer.evict()).collect();
+    let compacted_evicted: Vec<FrameId> = std::iter::from_fn(|| compacted.evict()).collect();
+    assert_eq!(evicted, compacted_evicted);
+}
+
+#[test]
+fn test_compact_timestamps() {
+    let k = 2_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    replacer.time_source.rebase(100);
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    replacer.record_access(&0, AccessType::Lookup).unwrap();
+    replacer.time_source.rebase(1_000);
+    replacer.set_evictable(&2, true).unwrap();
+
+    // The gaps close, and the clock goes on right after the last timestamp.
+    replacer.compact_timestamps();
+    assert_eq!(VecDeque::from([0, 2]), get_node(&replacer, &0).history);
+    assert_eq!(VecDeque::from([1]), get_node(&replacer, &1).history);
+    assert_eq!(3, get_node(&replacer, &2).registered);
+    assert_eq!(4, replacer.time_source.now());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_evict_equal_finite_distances() {
+    let k = 2_usize;
+    let mut replacer = LRUKReplacer::builder().max_size(10).k(k).build();
+    record_access_frames_n_times(&mut replacer, &vec![0, 1], k);
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1]);
+
+    // Give both frames the same k'th most recent access, with frame 0's latest access the more
+    // recent one.
+    set_history(&mut replacer, 0, &[0, 3]);
+    set_history(&mut replacer, 1, &[0, 2]);
+    check_replacer(&replacer).unwrap();
+    assert_eq!(
+        get_backwards_k_distance_for_node(&mut replacer, &0),
+        get_backwards_k_distance_for_node(&mut replacer, &1)
+    );
+
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(Some(0), replacer.evict());
+}
+
+/// Evicting a frame and bringing it back, as the buffer pool does when it is full, takes about as
+/// long with 100k frames as with 1k, where looking through every frame would take 100 times longer.
+#[test]
+fn test_evict_time_does_not_grow_linearly() {
+    const EVICTIONS: usize = 2_000;
+    let time_evictions = |num_frames: usize| {
+        let k = 2_usize;
+        let mut replacer = LRUKReplacer::builder().max_size(num_frames).k(k).build();
+        for frame_id in 0..num_frames {
+            record_access_frame_n_times(&mut replacer, frame_id, k);
+            replacer.set_evictable(&frame_id, true).unwrap();
+        }
+        let start = Instant::now();
+        for _ in 0..EVICTIONS {
+            let frame_id = replacer.evict().unwrap();
+            replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+            replacer.set_evictable(&frame_id, true).unwrap();
+        }
+        start.elapsed()
+    };
+
+    let small = time_evictions(1_000);
+    let large = time_evictions(100_000);
+    assert!(
+        large < small * 20,
+        "{EVICTIONS} evictions took {large:?} with 100k frames, but {small:?} with 1k"
+    );
+}
+
 #[test]
 fn test_backwards_k_distance() {
     let mut k = 5_usize;
@@ -98,22 +474,62 @@ fn test_backwards_k_distance() {
 
     let frame_id = get_new_frame_and_record_access(&mut replacer);
     while k > 2 {
-        replacer.record_access(&frame_id, AccessType::Lookup);
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
         assert_eq!(
-            get_node(&replacer, &frame_id).get_backwards_k_distance(replacer.current_timestamp),
-            INF
+            replacer.get_backwards_k_distance(&frame_id),
+            Some(u64::MAX)
         );
         k -= 1;
     }
     for _ in 0..2 {
-        replacer.record_access(&frame_id, AccessType::Lookup);
+        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
         assert_eq!(
-            get_node(&replacer, &frame_id).get_backwards_k_distance(replacer.current_timestamp),
-            5
+            replacer.get_backwards_k_distance(&frame_id),
+            Some(5)
         );
     }
 }
 
+#[test]
+fn test_mock_time_source_orders_by_time() {
+    let k = 2_usize;
+    let clock = MockTimeSource::new();
+    let mut replacer = LRUKReplacer::builder()
+        .max_size(10)
+        .k(k)
+        .time_source(clock.clone())
+        .build();
+
+    // Both frames are accessed k times, an hour apart in time but one access apart in order.
+    record_access_frame_n_times(&mut replacer, 1, k);
+    clock.advance(3_600_000_000_000);
+    record_access_frame_n_times(&mut replacer, 0, k);
+    assert_eq!(Some(3_600_000_000_000), replacer.get_backwards_k_distance(&1));
+    assert_eq!(Some(0), replacer.get_backwards_k_distance(&0));
+
+    // Idle frames keep aging without any access.
+    clock.advance(1_000);
+    assert_eq!(Some(1_000), replacer.get_backwards_k_distance(&0));
+
+    set_multiple_frames_evictable(&mut replacer, &vec![0, 1]);
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(Some(0), replacer.evict());
+    check_replacer(&replacer).unwrap();
+}
+
+#[test]
+fn test_monotonic_time_source() {
+    let mut replacer = LRUKReplacer::builder()
+        .max_size(10)
+        .k(2)
+        .time_source(MonotonicClock::new())
+        .build();
+    record_access_frame_n_times(&mut replacer, 0, 2);
+    let history = &get_node(&replacer, &0).history;
+    assert!(history.front() <= history.back());
+    assert!(replacer.get_backwards_k_distance(&0).unwrap() < u64::MAX);
+}
+
 pub(crate) fn get_new_frame_and_record_access(replacer: &mut LRUKReplacer) -> FrameId {
     if replacer.is_full_capacity() {
         panic!("Can't get new frame for replacer without evicting an existing frame.");
@@ -129,13 +545,12 @@ pub(crate) fn get_new_frame_and_record_access(replacer: &mut LRUKReplacer) -> Fr
             break;
         }
     }
-    replacer.record_access(&new_frame_id, DUMMY_ACCESS_TYPE);
+    replacer.record_access(&new_frame_id, DUMMY_ACCESS_TYPE).unwrap();
     new_frame_id
 }
 
-fn get_backwards_k_distance_for_node(replacer: &mut LRUKReplacer, frame_id: &FrameId) -> usize {
-    let node = get_node(&replacer, &frame_id);
-    node.get_backwards_k_distance(replacer.current_timestamp)
+fn get_backwards_k_distance_for_node(replacer: &mut LRUKReplacer, frame_id: &FrameId) -> u64 {
+    replacer.get_backwards_k_distance(frame_id).unwrap()
 }
 
 fn get_node<'a>(replacer: &'a LRUKReplacer, frame_id: &FrameId) -> &'a LRUKNode {
@@ -145,6 +560,14 @@ fn get_node<'a>(replacer: &'a LRUKReplacer, frame_id: &FrameId) -> &'a LRUKNode
         .expect("No node corresponding to {frame_id} exists in replacer node store.")
 }
 
+/// Overwrites the history of an evictable frame, moving it in the eviction order accordingly.
+fn set_history(replacer: &mut LRUKReplacer, frame_id: FrameId, history: &[u64]) {
+    let node = replacer.node_store.get_mut(&frame_id).unwrap();
+    replacer.eviction_order.remove(&(node.eviction_key(), frame_id));
+    node.history = VecDeque::from(history.to_vec());
+    replacer.eviction_order.insert((node.eviction_key(), frame_id));
+}
+
 fn random_bool() -> bool {
     let mut rng = rand::thread_rng();
     rng.gen_bool(2.0 / 3.0)
@@ -155,7 +578,7 @@ fn random_bool() -> bool {
 fn record_access_frames_n_times(replacer: &mut LRUKReplacer, frame_ids: &Vec<FrameId>, n: usize) {
     frame_ids.iter().for_each(|frame_id| {
         for _ in 0..n {
-            replacer.record_access(&frame_id, AccessType::Lookup);
+            replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
         }
     });
 }
@@ -167,5 +590,5 @@ fn record_access_frame_n_times(replacer: &mut LRUKReplacer, frame_id: FrameId, n
 fn set_multiple_frames_evictable(replacer: &mut LRUKReplacer, frame_ids: &Vec<FrameId>) {
     frame_ids
         .iter()
-        .for_each(|frame_id| replacer.set_evictable(&frame_id, true));
+        .for_each(|frame_id| replacer.set_evictable(&frame_id, true).unwrap());
 }
@@ -0,0 +1,108 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Where an LRU-K replacer takes the timestamps of accesses from, see
+/// [`LRUKReplacerBuilder::time_source`]. Timestamps never go down, unless the replacer turns the
+/// clock back with [`TimeSource::rebase`].
+///
+/// [`LRUKReplacerBuilder::time_source`]:
+/// crate::storage::buffer::lru_k_replacer::LRUKReplacerBuilder::time_source
+pub trait TimeSource: Debug + Send + Sync {
+    /// Returns the current timestamp.
+    fn now(&self) -> u64;
+
+    /// Called after each access is recorded. Does nothing unless time is counted in accesses.
+    fn tick(&mut self) {}
+
+    /// Turns the clock back, so that it reads `now`, lower than it reads yet, and goes on from
+    /// there. See [`LRUKReplacer::compact_timestamps`].
+    ///
+    /// [`LRUKReplacer::compact_timestamps`]:
+    /// crate::storage::buffer::lru_k_replacer::LRUKReplacer::compact_timestamps
+    fn rebase(&mut self, now: u64);
+}
+
+/// Counts time in accesses: a frame's backwards k-distance is the number of accesses to any frame
+/// since its k'th most recent one, and doesn't grow while the replacer sits idle. The default.
+#[derive(Debug, Default)]
+pub struct LogicalClock {
+    accesses: u64,
+}
+
+impl TimeSource for LogicalClock {
+    fn now(&self) -> u64 {
+        self.accesses
+    }
+
+    fn tick(&mut self) {
+        self.accesses += 1;
+    }
+
+    fn rebase(&mut self, now: u64) {
+        self.accesses = now;
+    }
+}
+
+/// Counts time in nanoseconds since the clock was made, so that the backwards k-distances of idle
+/// frames keep growing without any access.
+#[derive(Debug)]
+pub struct MonotonicClock {
+    start: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for MonotonicClock {
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+
+    fn rebase(&mut self, now: u64) {
+        // An `Instant` can't be earlier than the system's boot, or so, which a clock this young
+        // may be asked to go back past; it then starts over from zero.
+        let start = Instant::now().checked_sub(Duration::from_nanos(now));
+        self.start = start.unwrap_or_else(Instant::now);
+    }
+}
+
+/// A clock that only moves when told to, for tests to age frames without sleeping. Clones share
+/// the same time, so a test can keep one and hand the other to the replacer.
+#[derive(Clone, Debug, Default)]
+pub struct MockTimeSource {
+    now: Arc<AtomicU64>,
+}
+
+impl MockTimeSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: u64) {
+        self.now.fetch_add(duration, Ordering::SeqCst);
+    }
+}
+
+impl TimeSource for MockTimeSource {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+
+    fn rebase(&mut self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::common::sched::sched_point;
+use crate::common::{Error, Metrics, Result};
+use crate::storage::buffer::buffer_pool_manager::FrameId;
+use crate::storage::buffer::lru_k_replacer::AccessType;
+use crate::storage::buffer::replacer::Replacer;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub(crate) struct LRUNode {
+    /// Timestamp of the frame's most recent access.
+    pub(crate) last_access: usize,
+    pub(crate) is_evictable: bool,
+}
+
+/// A plain LRU replacer: evicts the evictable frame whose last access is the oldest. All kinds of
+/// access count the same.
+#[derive(Debug)]
+pub struct LRUReplacer {
+    pub(crate) node_store: HashMap<FrameId, LRUNode>,
+    pub(crate) current_timestamp: usize,
+    // Number of evictable frames in the replacer.
+    pub(crate) curr_size: usize,
+    // Maximum number of frames that can be stored in the replacer.
+    pub(crate) max_size: usize,
+    /// Counts accesses and evictions, and tracks `curr_size`.
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+impl LRUReplacer {
+    pub fn new(num_frames: usize) -> Self {
+        Self {
+            node_store: HashMap::new(),
+            current_timestamp: 0,
+            curr_size: 0,
+            max_size: num_frames,
+            metrics: Arc::default(),
+        }
+    }
+}
+
+impl Replacer for LRUReplacer {
+    /// Records an access to a frame at the current timestamp, whatever its type.
+    fn record_access(&mut self, frame_id: &FrameId, _access_type: AccessType) -> Result<()> {
+        sched_point!("replacer.record_access");
+        if *frame_id >= self.max_size {
+            return Err(Error::InvalidFrame(*frame_id));
+        }
+        self.metrics.replacer_accesses.incr();
+
+        let timestamp = self.current_timestamp;
+        (self.node_store.entry(*frame_id))
+            .and_modify(|node| node.last_access = timestamp)
+            .or_insert(LRUNode {
+                last_access: timestamp,
+                is_evictable: false,
+            });
+        self.current_timestamp += 1;
+        Ok(())
+    }
+
+    /// Evicts the evictable frame least recently accessed.
+    fn evict(&mut self) -> Option<FrameId> {
+        sched_point!("replacer.evict");
+        let frame_id = (self.node_store.iter())
+            .filter(|(_, node)| node.is_evictable)
+            .min_by_key(|(_, node)| node.last_access)
+            .map(|(frame_id, _)| *frame_id)?;
+        self.node_store.remove(&frame_id);
+        self.curr_size -= 1;
+        self.metrics.replacer_evictable_frames.decr();
+        self.metrics.replacer_evictions.incr();
+        Some(frame_id)
+    }
+
+    fn set_evictable(&mut self, frame_id: &FrameId, set_evictable: bool) -> Result<()> {
+        sched_point!("replacer.set_evictable");
+        if *frame_id >= self.max_size {
+            return Err(Error::InvalidFrame(*frame_id));
+        }
+        let node = match self.node_store.entry(*frame_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            // A frame not tracked yet is registered as last accessed now.
+            Entry::Vacant(entry) => {
+                self.current_timestamp += 1;
+                entry.insert(LRUNode {
+                    last_access: self.current_timestamp - 1,
+                    is_evictable: false,
+                })
+            }
+        };
+        if node.is_evictable != set_evictable {
+            if set_evictable {
+                self.curr_size += 1;
+                self.metrics.replacer_evictable_frames.incr();
+            } else {
+                self.curr_size -= 1;
+                self.metrics.replacer_evictable_frames.decr();
+            }
+            node.is_evictable = set_evictable;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, frame_id: &FrameId) -> Result<bool> {
+        let Some(node) = self.node_store.get(frame_id) else {
+            return Ok(false);
+        };
+        if !node.is_evictable {
+            return Err(Error::FrameNotEvictable(*frame_id));
+        }
+        self.node_store.remove(frame_id);
+        self.curr_size -= 1;
+        self.metrics.replacer_evictable_frames.decr();
+        Ok(true)
+    }
+
+    fn clear(&mut self) {
+        self.node_store.clear();
+        self.metrics.replacer_evictable_frames.sub(self.curr_size as u64);
+        self.curr_size = 0;
+    }
+
+    fn size(&self) -> usize {
+        self.curr_size
+    }
+
+    fn is_evictable(&self, frame_id: &FrameId) -> Option<bool> {
+        Some(self.node_store.get(frame_id)?.is_evictable)
+    }
+
+    fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        metrics.replacer_evictable_frames.add(self.curr_size as u64);
+        self.metrics = metrics;
+    }
+}
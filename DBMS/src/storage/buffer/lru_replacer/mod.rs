@@ -0,0 +1,5 @@
+mod lru_replacer;
+#[cfg(test)]
+mod tests;
+
+pub use lru_replacer::LRUReplacer;
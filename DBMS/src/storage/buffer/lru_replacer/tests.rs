@@ -0,0 +1,26 @@
+use super::*;
+use crate::storage::buffer::lru_k_replacer::AccessType;
+use crate::storage::buffer::replacer::Replacer;
+
+#[test]
+fn test_evict_by_last_access() {
+    let mut replacer = LRUReplacer::new(10);
+
+    // Frame 0 is accessed most often, but frame 1 most recently, so frame 0 goes first, where an
+    // LRU-K replacer would keep it.
+    for _ in 0..3 {
+        replacer.record_access(&0, AccessType::Lookup).unwrap();
+    }
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    replacer.record_access(&2, AccessType::Scan).unwrap();
+    for frame_id in 0..3 {
+        replacer.set_evictable(&frame_id, true).unwrap();
+    }
+    assert_eq!(Some(0), replacer.evict());
+
+    // Another access moves a frame to the back.
+    replacer.record_access(&1, AccessType::Lookup).unwrap();
+    assert_eq!(Some(2), replacer.evict());
+    assert_eq!(Some(1), replacer.evict());
+    assert_eq!(0, replacer.node_store.len());
+}
@@ -1,2 +1,5 @@
 pub mod buffer_pool_manager;
+pub mod clock_replacer;
 pub mod lru_k_replacer;
+pub mod lru_replacer;
+pub mod replacer;
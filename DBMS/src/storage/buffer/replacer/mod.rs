@@ -0,0 +1,5 @@
+mod replacer;
+#[cfg(test)]
+mod tests;
+
+pub use replacer::{Policy, Replacer, SharedReplacer};
@@ -0,0 +1,104 @@
+use crate::common::{Metrics, Result};
+use crate::storage::buffer::buffer_pool_manager::FrameId;
+use crate::storage::buffer::clock_replacer::ClockReplacer;
+use crate::storage::buffer::lru_k_replacer::{AccessType, LRUKReplacer};
+use crate::storage::buffer::lru_replacer::LRUReplacer;
+use crate::storage::disk::disk_manager::PageId;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+/// A replacer, shared by the buffer pool shard it picks victims for.
+pub type SharedReplacer = Arc<RwLock<dyn Replacer + Send + Sync>>;
+
+/// The eviction policies a buffer pool can be built with, see
+/// [`BufferPoolManagerBuilder::replacement_policy`].
+///
+/// [`BufferPoolManagerBuilder::replacement_policy`]:
+/// crate::storage::buffer::buffer_pool_manager::BufferPoolManagerBuilder::replacement_policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// [`LRUKReplacer`], the pool's default, looking at the last `k` accesses of each frame.
+    LruK { k: usize },
+    /// [`LRUReplacer`], looking at the last access of each frame.
+    Lru,
+    /// [`ClockReplacer`], giving each frame accessed a second chance.
+    Clock,
+}
+
+impl Policy {
+    /// Makes a replacer of this policy for `num_frames` frames.
+    pub fn new_replacer(&self, num_frames: usize) -> SharedReplacer {
+        match *self {
+            Self::LruK { k } => Arc::new(RwLock::new(LRUKReplacer::new(num_frames, k))),
+            Self::Lru => Arc::new(RwLock::new(LRUReplacer::new(num_frames))),
+            Self::Clock => Arc::new(RwLock::new(ClockReplacer::new(num_frames))),
+        }
+    }
+}
+
+/// An eviction policy of the buffer pool: tracks the frames holding pages, and picks which
+/// evictable frame gives up its page when the pool needs a frame.
+///
+/// The pool records an access whenever it pins a page, marks a frame evictable once its page is
+/// unpinned, and not evictable once it is pinned again. Pools use an LRU-K replacer unless built
+/// with another [`Policy`], or with [`BufferPoolManagerBuilder::replacer`].
+///
+/// [`BufferPoolManagerBuilder::replacer`]:
+/// crate::storage::buffer::buffer_pool_manager::BufferPoolManagerBuilder::replacer
+/// [`Error::InvalidFrame`]: crate::common::Error::InvalidFrame
+/// [`Error::FrameNotEvictable`]: crate::common::Error::FrameNotEvictable
+pub trait Replacer: Debug {
+    /// Records an access to a frame, tracking it if it isn't already, as not evictable.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidFrame`]: If `frame_id` is not below the number of frames the replacer
+    ///   was made for.
+    fn record_access(&mut self, frame_id: &FrameId, access_type: AccessType) -> Result<()>;
+
+    /// Tells the replacer which page a tracked frame was just loaded with, for replacers that
+    /// count the pages brought back soon after being evicted. Does nothing by default.
+    fn record_page(&mut self, _frame_id: &FrameId, _page_id: PageId) {}
+
+    /// Picks an evictable frame to evict, and stops tracking it.
+    ///
+    /// # Returns
+    /// - The frame evicted, or `None` if no frame is evictable.
+    fn evict(&mut self) -> Option<FrameId>;
+
+    /// Evicts up to `n` frames, in the order that many calls of [`Self::evict`] would have, and
+    /// stops tracking them. Fewer are returned if fewer are evictable, and none if `n` is 0.
+    fn evict_n(&mut self, n: usize) -> Vec<FrameId> {
+        std::iter::from_fn(|| self.evict()).take(n).collect()
+    }
+
+    /// Sets whether a frame may be evicted, tracking it if it isn't already, as though it was
+    /// accessed then, so that a frame can be kept from eviction before its first access.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidFrame`]: If `frame_id` is not below the number of frames the replacer
+    ///   was made for.
+    fn set_evictable(&mut self, frame_id: &FrameId, set_evictable: bool) -> Result<()>;
+
+    /// Stops tracking an evictable frame, along with its access history. Does nothing if the
+    /// frame isn't tracked.
+    ///
+    /// # Returns
+    /// - Whether the frame was tracked, and so removed.
+    ///
+    /// # Errors
+    /// - [`Error::FrameNotEvictable`]: If the frame is tracked, but not evictable.
+    fn remove(&mut self, frame_id: &FrameId) -> Result<bool>;
+
+    /// Stops tracking every frame, evictable or not, as when the frames it was given are gone.
+    fn clear(&mut self);
+
+    /// Returns the number of evictable frames.
+    fn size(&self) -> usize;
+
+    /// Returns whether a frame is evictable, or `None` if the frame isn't tracked.
+    fn is_evictable(&self, frame_id: &FrameId) -> Option<bool>;
+
+    /// Counts accesses and evictions in the given registry from now on. The registry's count of
+    /// evictable frames is added to, so that the replacers of a sharded pool can share it.
+    fn set_metrics(&mut self, metrics: Arc<Metrics>);
+}
@@ -0,0 +1,244 @@
+//! Tests that hold for any eviction policy, run against each [`Replacer`] implementation.
+use super::*;
+use crate::common::Error;
+use crate::storage::buffer::buffer_pool_manager::FrameId;
+use crate::storage::buffer::clock_replacer::ClockReplacer;
+use crate::storage::buffer::lru_k_replacer::{AccessType, LRUKReplacer};
+use crate::storage::buffer::lru_replacer::LRUReplacer;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+/// A replacer of each implementation, for `num_frames` frames.
+fn replacers(num_frames: usize) -> Vec<Box<dyn Replacer>> {
+    let mut replacers = lru_replacers(num_frames);
+    replacers.push(Box::new(ClockReplacer::new(num_frames)));
+    replacers
+}
+
+/// A replacer of each implementation that orders frames by their accesses, for `num_frames`
+/// frames. Clock only approximates that order.
+fn lru_replacers(num_frames: usize) -> Vec<Box<dyn Replacer>> {
+    vec![
+        Box::new(LRUKReplacer::new(num_frames, 2)),
+        Box::new(LRUReplacer::new(num_frames)),
+    ]
+}
+
+/// Records an access to each frame in order, and makes them all evictable.
+fn access_evictable(replacer: &mut dyn Replacer, frame_ids: &[FrameId]) {
+    for frame_id in frame_ids {
+        replacer.record_access(frame_id, AccessType::Lookup).unwrap();
+        replacer.set_evictable(frame_id, true).unwrap();
+    }
+}
+
+#[test]
+fn test_evict_least_recently_used() {
+    for mut replacer in lru_replacers(10) {
+        // no frames to evict.
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+
+        // none of the frames are set to evictable.
+        for frame_id in [3, 1, 2] {
+            replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+        }
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+
+        // a single access each, so the frames go in the order they were accessed.
+        for frame_id in [1, 2, 3] {
+            replacer.set_evictable(&frame_id, true).unwrap();
+        }
+        assert_eq!(replacer.evict(), Some(3), "{replacer:?}");
+        assert_eq!(replacer.evict(), Some(1), "{replacer:?}");
+        assert_eq!(replacer.evict(), Some(2), "{replacer:?}");
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+    }
+}
+
+#[test]
+fn test_set_evictable() {
+    for mut replacer in replacers(10) {
+        access_evictable(replacer.as_mut(), &[0, 1, 2]);
+        assert_eq!(replacer.size(), 3, "{replacer:?}");
+
+        // Setting a frame to what it already is changes nothing.
+        replacer.set_evictable(&1, true).unwrap();
+        assert_eq!(replacer.size(), 3, "{replacer:?}");
+
+        // A frame that isn't evictable is skipped.
+        replacer.set_evictable(&0, false).unwrap();
+        assert_eq!(replacer.size(), 2, "{replacer:?}");
+        assert_eq!(replacer.is_evictable(&0), Some(false), "{replacer:?}");
+        assert_eq!(replacer.evict(), Some(1), "{replacer:?}");
+        assert_eq!(replacer.evict(), Some(2), "{replacer:?}");
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+        assert_eq!(replacer.is_evictable(&1), None, "{replacer:?}");
+
+        // Frames the replacer doesn't have can't be set.
+        assert_eq!(Err(Error::InvalidFrame(10)), replacer.set_evictable(&10, false));
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+    }
+}
+
+#[test]
+fn test_set_evictable_registers_frame() {
+    for mut replacer in replacers(10) {
+        // A frame not accessed yet is tracked from then on.
+        replacer.set_evictable(&5, false).unwrap();
+        assert_eq!(replacer.is_evictable(&5), Some(false), "{replacer:?}");
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+
+        replacer.set_evictable(&6, true).unwrap();
+        assert_eq!(replacer.size(), 1, "{replacer:?}");
+        assert_eq!(replacer.evict(), Some(6), "{replacer:?}");
+
+        // A later access counts as usual.
+        replacer.record_access(&5, AccessType::Lookup).unwrap();
+        replacer.set_evictable(&5, true).unwrap();
+        assert_eq!(replacer.evict(), Some(5), "{replacer:?}");
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+    }
+}
+
+#[test]
+fn test_remove() {
+    for mut replacer in replacers(10) {
+        access_evictable(replacer.as_mut(), &[0, 1]);
+
+        assert_eq!(Ok(true), replacer.remove(&0), "{replacer:?}");
+        assert_eq!(replacer.size(), 1, "{replacer:?}");
+        assert_eq!(replacer.is_evictable(&0), None, "{replacer:?}");
+
+        // Removing a frame that isn't tracked does nothing.
+        assert_eq!(Ok(false), replacer.remove(&7), "{replacer:?}");
+        assert_eq!(replacer.size(), 1, "{replacer:?}");
+
+        // A frame that isn't evictable can't be removed, and stays as it was.
+        replacer.set_evictable(&1, false).unwrap();
+        assert_eq!(Err(Error::FrameNotEvictable(1)), replacer.remove(&1));
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+        assert_eq!(replacer.is_evictable(&1), Some(false), "{replacer:?}");
+    }
+}
+
+#[test]
+fn test_clear() {
+    for mut replacer in replacers(10) {
+        access_evictable(replacer.as_mut(), &[0, 1, 2]);
+        replacer.set_evictable(&1, false).unwrap();
+
+        // Evictable or not, every frame is forgotten.
+        replacer.clear();
+        assert_eq!(replacer.size(), 0, "{replacer:?}");
+        assert!((0..3).all(|frame_id| replacer.is_evictable(&frame_id).is_none()));
+        assert_eq!(replacer.evict(), None, "{replacer:?}");
+
+        // And the replacer works as a new one would.
+        access_evictable(replacer.as_mut(), &[4]);
+        assert_eq!(replacer.evict(), Some(4), "{replacer:?}");
+    }
+}
+
+/// Applies random operations to each replacer, checking after every one that its size is the
+/// number of evictable frames, and that it tracks the frames a model of it says it should.
+#[test]
+fn test_random_operations_keep_size() {
+    const NUM_FRAMES: usize = 8;
+    for seed in 0..20 {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for mut replacer in replacers(NUM_FRAMES) {
+            // Whether each tracked frame is evictable.
+            let mut model: HashMap<FrameId, bool> = HashMap::new();
+            for step in 0..500 {
+                let frame_id = rng.gen_range(0..NUM_FRAMES);
+                match rng.gen_range(0..10) {
+                    0..=3 => {
+                        replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+                        model.entry(frame_id).or_insert(false);
+                    }
+                    4..=6 => {
+                        let evictable = rng.gen_bool(0.5);
+                        replacer.set_evictable(&frame_id, evictable).unwrap();
+                        model.insert(frame_id, evictable);
+                    }
+                    7 => {
+                        if let Some(victim) = replacer.evict() {
+                            assert_eq!(Some(true), model.remove(&victim), "{replacer:?}");
+                        } else {
+                            assert!(!model.values().any(|evictable| *evictable));
+                        }
+                    }
+                    8 => match model.get(&frame_id) {
+                        Some(true) => {
+                            assert_eq!(Ok(true), replacer.remove(&frame_id));
+                            model.remove(&frame_id);
+                        }
+                        Some(false) => assert_eq!(
+                            Err(Error::FrameNotEvictable(frame_id)),
+                            replacer.remove(&frame_id)
+                        ),
+                        None => assert_eq!(Ok(false), replacer.remove(&frame_id)),
+                    },
+                    _ => {
+                        if rng.gen_bool(0.1) {
+                            replacer.clear();
+                            model.clear();
+                        }
+                    }
+                }
+
+                let evictable = model.values().filter(|evictable| **evictable).count();
+                assert_eq!(evictable, replacer.size(), "seed {seed}, step {step}: {replacer:?}");
+                for frame_id in 0..NUM_FRAMES {
+                    assert_eq!(
+                        model.get(&frame_id).copied(),
+                        replacer.is_evictable(&frame_id),
+                        "seed {seed}, step {step}: {replacer:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_record_access_invalid_frame_id() {
+    for mut replacer in replacers(5) {
+        assert_eq!(
+            Err(Error::InvalidFrame(5)),
+            replacer.record_access(&5, AccessType::Lookup)
+        );
+        assert_eq!(replacer.is_evictable(&5), None, "{replacer:?}");
+    }
+}
+
+#[test]
+fn test_evict_n() {
+    for (mut batch, mut single) in replacers(10).into_iter().zip(replacers(10)) {
+        for replacer in [&mut batch, &mut single] {
+            access_evictable(replacer.as_mut(), &[4, 0, 7, 2, 9, 1]);
+            for frame_id in [0, 2, 0, 5] {
+                replacer.record_access(&frame_id, AccessType::Lookup).unwrap();
+            }
+            replacer.set_evictable(&9, false).unwrap();
+        }
+
+        // Nothing is evicted for n = 0.
+        assert_eq!(Vec::<FrameId>::new(), batch.evict_n(0), "{batch:?}");
+        assert_eq!(5, batch.size(), "{batch:?}");
+
+        let evicted: Vec<FrameId> = (0..3).filter_map(|_| single.evict()).collect();
+        assert_eq!(evicted, batch.evict_n(3), "{batch:?}");
+        assert_eq!(2, batch.size(), "{batch:?}");
+
+        // Only the evictable frames that are left come out.
+        let evicted: Vec<FrameId> = std::iter::from_fn(|| single.evict()).collect();
+        assert_eq!(2, evicted.len(), "{single:?}");
+        assert_eq!(evicted, batch.evict_n(10), "{batch:?}");
+        assert_eq!(0, batch.size(), "{batch:?}");
+        assert_eq!(Some(false), batch.is_evictable(&9), "{batch:?}");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::common::Result;
+use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
+use crate::storage::disk::disk_file::DiskFile;
+use crate::storage::disk::disk_manager::PageId;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Where a [`DiskManager`] keeps the blocks of a database, each [`RUSTY_DB_PAGE_SIZE_BYTES`]
+/// long and holding a page. Block 0 is the header. The disk manager takes care of checksums, of
+/// which pages are free, and of reading ahead; a backend only stores bytes.
+///
+/// [`DiskManager`]: crate::storage::disk::disk_manager::DiskManager
+pub trait DiskBackend: Debug + Send + Sync {
+    /// Reads `blocks` consecutive blocks, starting at `block_id`, with a single read. Fewer
+    /// bytes come back than the blocks take up if the storage ends before they do.
+    fn read_block(&mut self, block_id: PageId, blocks: usize) -> Result<Vec<u8>>;
+
+    /// Writes `bytes`, a whole number of blocks, starting at `block_id`.
+    fn write_block(&mut self, block_id: PageId, bytes: &[u8]) -> Result<()>;
+
+    /// Reserves a block past the last one, and returns its ID. It reads as zeros until written.
+    fn allocate(&mut self) -> Result<PageId>;
+
+    /// Makes room for the first `blocks` blocks ahead of their allocation, e.g. by growing a
+    /// file to fit them, so that a file grows an extent at a time rather than a block at a time.
+    /// Blocks the room is made for aren't allocated: they read as past the end until they are.
+    /// Does nothing by default.
+    fn reserve(&mut self, _blocks: PageId) -> Result<()> {
+        Ok(())
+    }
+
+    /// Releases the storage of a block no longer used. It reads as zeros, unless the backend
+    /// keeps it as it was, until written again.
+    fn deallocate(&mut self, block_id: PageId) -> Result<()>;
+
+    /// Drops the blocks from `blocks` on, shrinking the storage to the ones before them. Does
+    /// nothing if there are no more blocks than that.
+    fn truncate(&mut self, blocks: PageId) -> Result<()>;
+
+    /// Makes the blocks written so far durable.
+    fn sync(&mut self) -> Result<()>;
+
+    /// Reads the blocks through a map of the storage into memory from now on, if the backend
+    /// can, rather than with a read each, and returns whether it does. Doesn't by default.
+    fn map_reads(&mut self) -> bool {
+        false
+    }
+
+    /// Returns the number of blocks, counting the header, whether written yet or not.
+    fn num_blocks(&self) -> PageId;
+
+    /// Returns the number of blocks the storage takes up, room reserved past the last one
+    /// included. Only the blocks by default.
+    fn reserved_blocks(&self) -> PageId {
+        self.num_blocks()
+    }
+}
+
+/// Keeps the blocks in a database file, block `n` at offset `n` times the block size. The file
+/// may be longer than its blocks, with room reserved past them, see [`DiskBackend::reserve`].
+#[derive(Debug)]
+pub struct FileBackend {
+    reader: BufReader<DiskFile>,
+    writer: BufWriter<DiskFile>,
+    /// The number of blocks, which is never less than one, for the header.
+    blocks: PageId,
+    /// The number of blocks the file has room for, allocated or not.
+    reserved: PageId,
+    /// Whether the blocks are read through [`Self::map`], see [`DiskBackend::map_reads`].
+    map_reads: bool,
+    /// The file mapped into memory, as long as it was when mapped, once read through the map.
+    /// Mapped again when a block past its end is read, and dropped before the file shrinks.
+    map: Option<Mmap>,
+}
+
+impl FileBackend {
+    /// Creates a backend for the database file `file`, with the blocks it already has. Blocks of
+    /// zeros at the end of the file are room reserved, not blocks: a block is written as soon as
+    /// it is allocated, and a block written never reads as zeros, as it has a checksum.
+    pub(crate) fn new(file: DiskFile) -> Result<Self> {
+        let reserved = PageId::try_from(file.len()?.div_ceil(RUSTY_DB_PAGE_SIZE_BYTES as u64))?;
+        let writer = file.try_clone()?;
+        let mut backend = Self {
+            reader: BufReader::new(file),
+            writer: BufWriter::new(writer),
+            blocks: reserved.max(1),
+            reserved,
+            map_reads: false,
+            map: None,
+        };
+        while backend.blocks > 1 {
+            let block = backend.read_block(backend.blocks - 1, 1)?;
+            if block.iter().any(|byte| *byte != 0) {
+                break;
+            }
+            backend.blocks -= 1;
+        }
+        Ok(backend)
+    }
+
+    fn offset(block_id: PageId) -> u64 {
+        block_id as u64 * RUSTY_DB_PAGE_SIZE_BYTES as u64
+    }
+
+    /// Copies `len` bytes, starting at block `block_id`, out of the map of the file, mapping it
+    /// again first if they are past the end of the map. Returns `None` if they are past the end
+    /// of the file, or it can't be mapped, for them to be read instead.
+    fn read_mapped(&mut self, block_id: PageId, len: usize) -> Option<Vec<u8>> {
+        let start = Self::offset(block_id) as usize;
+        let end = start + len;
+        if self.map.as_ref().is_none_or(|map| map.len() < end) {
+            self.map = None;
+            let file = self.reader.get_ref();
+            let file = file.as_file().filter(|file| {
+                file.metadata().is_ok_and(|metadata| metadata.len() >= end as u64)
+            })?;
+            // SAFETY: The file is only resized by this backend, which never shrinks it while it
+            // is mapped. Blocks written to it are seen through the map, as they go through the
+            // same page cache, but are never written through the map itself.
+            self.map = unsafe { Mmap::map(file) }.ok();
+        }
+        self.map.as_ref().map(|map| map[start..end].to_vec())
+    }
+}
+
+impl DiskBackend for FileBackend {
+    /// Room reserved past the blocks reads as past the end of the file.
+    fn read_block(&mut self, block_id: PageId, blocks: usize) -> Result<Vec<u8>> {
+        let blocks = blocks.min(self.blocks.saturating_sub(block_id) as usize);
+        let len = blocks * RUSTY_DB_PAGE_SIZE_BYTES;
+        if self.map_reads && len > 0 {
+            if let Some(buffer) = self.read_mapped(block_id, len) {
+                return Ok(buffer);
+            }
+        }
+        let mut buffer = Vec::with_capacity(len);
+        self.reader.seek(SeekFrom::Start(Self::offset(block_id)))?;
+        (&mut self.reader).take(len as u64).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn write_block(&mut self, block_id: PageId, bytes: &[u8]) -> Result<()> {
+        self.writer.seek(SeekFrom::Start(Self::offset(block_id)))?;
+        self.writer.write_all(bytes)?;
+        self.writer.flush()?;
+        let end = block_id + bytes.len().div_ceil(RUSTY_DB_PAGE_SIZE_BYTES) as PageId;
+        self.blocks = self.blocks.max(end);
+        self.reserved = self.reserved.max(end);
+        Ok(())
+    }
+
+    /// The file only grows once the block is written, unless room was reserved for it, in which
+    /// case it reads as zeros.
+    fn allocate(&mut self) -> Result<PageId> {
+        self.blocks += 1;
+        Ok(self.blocks - 1)
+    }
+
+    /// Grows the file with [`DiskFile::set_len`], which fills it with zeros, or leaves a hole
+    /// for the file system to allocate when written. The file never shrinks.
+    fn reserve(&mut self, blocks: PageId) -> Result<()> {
+        if blocks <= self.reserved {
+            return Ok(());
+        }
+        self.writer.flush()?;
+        self.writer.get_mut().set_len(Self::offset(blocks))?;
+        self.reserved = blocks;
+        Ok(())
+    }
+
+    /// Keeps the block as it was: the file doesn't shrink.
+    fn deallocate(&mut self, _block_id: PageId) -> Result<()> {
+        Ok(())
+    }
+
+    /// Shrinks the file, room reserved past the blocks included.
+    fn truncate(&mut self, blocks: PageId) -> Result<()> {
+        let blocks = blocks.max(1);
+        if blocks >= self.reserved.max(self.blocks) {
+            return Ok(());
+        }
+        self.map = None;
+        self.writer.flush()?;
+        self.writer.get_mut().set_len(Self::offset(blocks))?;
+        self.blocks = self.blocks.min(blocks);
+        self.reserved = blocks;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        Ok(self.writer.get_ref().sync_data()?)
+    }
+
+    /// Only a file on disk can be mapped.
+    fn map_reads(&mut self) -> bool {
+        self.map_reads = self.reader.get_ref().as_file().is_some();
+        self.map_reads
+    }
+
+    fn num_blocks(&self) -> PageId {
+        self.blocks
+    }
+
+    fn reserved_blocks(&self) -> PageId {
+        self.reserved.max(self.blocks)
+    }
+}
+
+/// Keeps the blocks in memory, for tests that don't need the database to outlive them. Nothing
+/// ever fails, and syncing does nothing.
+#[derive(Debug)]
+pub struct MemoryBackend {
+    blocks: HashMap<PageId, Vec<u8>>,
+    /// The number of blocks, which is never less than one, for the header.
+    num_blocks: PageId,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            num_blocks: 1,
+        }
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskBackend for MemoryBackend {
+    /// Blocks allocated but never written read as zeros, like a file's would.
+    fn read_block(&mut self, block_id: PageId, blocks: usize) -> Result<Vec<u8>> {
+        let blocks = blocks.min(self.num_blocks.saturating_sub(block_id) as usize);
+        let mut buffer = Vec::with_capacity(blocks * RUSTY_DB_PAGE_SIZE_BYTES);
+        for block_id in (block_id..).take(blocks) {
+            match self.blocks.get(&block_id) {
+                Some(block) => buffer.extend_from_slice(block),
+                None => buffer.resize(buffer.len() + RUSTY_DB_PAGE_SIZE_BYTES, 0),
+            }
+        }
+        Ok(buffer)
+    }
+
+    fn write_block(&mut self, block_id: PageId, bytes: &[u8]) -> Result<()> {
+        for (block_id, block) in (block_id..).zip(bytes.chunks(RUSTY_DB_PAGE_SIZE_BYTES)) {
+            let mut block = block.to_vec();
+            block.resize(RUSTY_DB_PAGE_SIZE_BYTES, 0);
+            self.blocks.insert(block_id, block);
+            self.num_blocks = self.num_blocks.max(block_id + 1);
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self) -> Result<PageId> {
+        self.num_blocks += 1;
+        Ok(self.num_blocks - 1)
+    }
+
+    fn deallocate(&mut self, block_id: PageId) -> Result<()> {
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+
+    fn truncate(&mut self, blocks: PageId) -> Result<()> {
+        let blocks = blocks.max(1);
+        self.blocks.retain(|block_id, _| *block_id < blocks);
+        self.num_blocks = self.num_blocks.min(blocks);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> PageId {
+        self.num_blocks
+    }
+}
+
+/// Keeps the blocks in memory like [`MemoryBackend`], and counts the writes and syncs, for tests
+/// to check how the disk manager goes about its I/O.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct CountingBackend {
+    blocks: MemoryBackend,
+    writes: Arc<AtomicUsize>,
+    syncs: Arc<AtomicUsize>,
+}
+
+#[cfg(test)]
+impl CountingBackend {
+    /// Returns the number of writes, shared with the backend, to keep once it is boxed.
+    pub(crate) fn writes(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.writes)
+    }
+
+    /// Returns the number of syncs, like [`Self::writes`].
+    pub(crate) fn syncs(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.syncs)
+    }
+}
+
+#[cfg(test)]
+impl DiskBackend for CountingBackend {
+    fn read_block(&mut self, block_id: PageId, blocks: usize) -> Result<Vec<u8>> {
+        self.blocks.read_block(block_id, blocks)
+    }
+
+    fn write_block(&mut self, block_id: PageId, bytes: &[u8]) -> Result<()> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.blocks.write_block(block_id, bytes)
+    }
+
+    fn allocate(&mut self) -> Result<PageId> {
+        self.blocks.allocate()
+    }
+
+    fn deallocate(&mut self, block_id: PageId) -> Result<()> {
+        self.blocks.deallocate(block_id)
+    }
+
+    fn truncate(&mut self, blocks: PageId) -> Result<()> {
+        self.blocks.truncate(blocks)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.syncs.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> PageId {
+        self.blocks.num_blocks()
+    }
+}
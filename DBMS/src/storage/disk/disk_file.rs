@@ -0,0 +1,116 @@
+#[cfg(test)]
+use crate::sim::SimFile;
+use std::fs::File;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+/// A file the disk manager keeps the database or its log in: a real one, one in memory, or in
+/// tests, one on a simulated disk that can lose power.
+#[derive(Debug)]
+pub(crate) enum DiskFile {
+    Real(File),
+    /// The log of a disk manager made by
+    /// [`crate::storage::disk::disk_manager::DiskManager::new_in_memory`].
+    Memory(Cursor<Vec<u8>>),
+    #[cfg(test)]
+    Sim(SimFile),
+}
+
+impl DiskFile {
+    pub(crate) fn len(&self) -> Result<u64> {
+        match self {
+            Self::Real(file) => Ok(file.metadata()?.len()),
+            Self::Memory(file) => Ok(file.get_ref().len() as u64),
+            #[cfg(test)]
+            Self::Sim(file) => file.len(),
+        }
+    }
+
+    pub(crate) fn set_len(&mut self, len: u64) -> Result<()> {
+        match self {
+            Self::Real(file) => file.set_len(len),
+            Self::Memory(file) => {
+                file.get_mut().resize(usize::try_from(len).map_err(Error::other)?, 0);
+                Ok(())
+            }
+            #[cfg(test)]
+            Self::Sim(file) => file.set_len(len),
+        }
+    }
+
+    pub(crate) fn sync_data(&self) -> Result<()> {
+        match self {
+            Self::Real(file) => file.sync_data(),
+            Self::Memory(_) => Ok(()),
+            #[cfg(test)]
+            Self::Sim(file) => file.sync_data(),
+        }
+    }
+
+    /// Returns the file on disk, if it is one, e.g. to map it into memory.
+    pub(crate) fn as_file(&self) -> Option<&File> {
+        match self {
+            Self::Real(file) => Some(file),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self> {
+        match self {
+            Self::Real(file) => Ok(Self::Real(file.try_clone()?)),
+            // A clone would have its own copy of the bytes, and not see what the other writes.
+            Self::Memory(_) => {
+                Err(Error::new(ErrorKind::Unsupported, "can't clone an in-memory file"))
+            }
+            #[cfg(test)]
+            Self::Sim(file) => Ok(Self::Sim(file.clone())),
+        }
+    }
+}
+
+impl From<File> for DiskFile {
+    fn from(file: File) -> Self {
+        Self::Real(file)
+    }
+}
+
+impl Read for DiskFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            Self::Real(file) => file.read(buf),
+            Self::Memory(file) => file.read(buf),
+            #[cfg(test)]
+            Self::Sim(file) => file.read(buf),
+        }
+    }
+}
+
+impl Write for DiskFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Self::Real(file) => file.write(buf),
+            Self::Memory(file) => file.write(buf),
+            #[cfg(test)]
+            Self::Sim(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Real(file) => file.flush(),
+            Self::Memory(_) => Ok(()),
+            #[cfg(test)]
+            Self::Sim(_) => Ok(()),
+        }
+    }
+}
+
+impl Seek for DiskFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        match self {
+            Self::Real(file) => file.seek(pos),
+            Self::Memory(file) => file.seek(pos),
+            #[cfg(test)]
+            Self::Sim(file) => file.seek(pos),
+        }
+    }
+}
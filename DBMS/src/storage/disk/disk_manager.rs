@@ -1,98 +1,1407 @@
-use crate::config::config::{RUSTY_DB_PAGE_SIZE_BYTES, RUST_DB_DATA_DIR};
-use crate::storage::page::{Page, TablePage};
+use crate::common::{Error, Metrics, Result};
+use crate::errinput;
+use crate::config::config::{
+    READ_AHEAD_PAGES, RUSTY_DB_PAGE_PAYLOAD_BYTES, RUSTY_DB_PAGE_SIZE_BYTES,
+    RUSTY_DB_PAGE_TRAILER_BYTES, RUST_DB_DATA_DIR,
+};
+#[cfg(test)]
+use crate::sim::SimDisk;
+use crate::storage::disk::backend::{DiskBackend, FileBackend, MemoryBackend};
+use crate::storage::disk::disk_file::DiskFile;
+#[cfg(any(test, feature = "fault-injection"))]
+use crate::storage::disk::fault::FaultInjector;
+use crate::storage::disk::encryption::{PageCipher, KEY_BYTES, KEY_CHECK_BYTES, TAG_BYTES};
+use crate::storage::page::{BPlusTreePageType, Page, TablePage};
+use crate::storage::wal::{Lsn, INVALID_LSN};
+use crate::trace_span;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, RwLock};
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 #[cfg(test)]
 use tempfile::NamedTempFile;
 
-/// Offset into the database file
+/// Identifies a page: the upper bits are the [`FileId`] of the tablespace the page is in, and the
+/// others its number within the tablespace's file, see [`make_page_id`]. The pages of the default
+/// tablespace, the database file, are numbered by their offset into it.
 pub type PageId = u32;
 
+/// Identifies a tablespace: a data file of its own, see [`DiskManager::create_tablespace`].
+pub type FileId = u8;
+
+/// The tablespace of the database file itself, which pages are allocated in unless told otherwise.
+pub const DEFAULT_TABLESPACE: FileId = 0;
+
+/// The bits of a [`PageId`] below its [`FileId`], for the page's number within its file.
+const PAGE_NO_BITS: u32 = PageId::BITS - FileId::BITS;
+
+/// The most tablespaces there may be, the default one included. The last file ID is left out, so
+/// that no page is numbered [`crate::common::constants::INVALID_PID`].
+const MAX_TABLESPACES: usize = FileId::MAX as usize;
+
+/// The page of each file holding its header: the LSN of the last checkpoint, followed by a bitmap
+/// of the pages deallocated since they were allocated, free to be allocated again. Its trailer
+/// holds the write counters leased and the key check, if the file is encrypted, and the header
+/// itself isn't, see [`DiskManager::open_encrypted`].
+const HEADER_PAGE_NO: PageId = 0;
+
+/// How many write counters of encrypted pages are leased at a time, see
+/// [`DiskManager::open_encrypted`].
+const COUNTER_LEASE: u64 = 1 << 16;
+
+/// The number of pages the free-page bitmap of a header covers. Pages past them are never reused
+/// once deallocated.
+const FREE_MAP_PAGES: usize = (RUSTY_DB_PAGE_PAYLOAD_BYTES - size_of::<Lsn>()) * 8;
+
+/// Returns the ID of the page numbered `page_no` in tablespace `file_id`.
+pub fn make_page_id(file_id: FileId, page_no: PageId) -> PageId {
+    debug_assert!(page_no < 1 << PAGE_NO_BITS, "page {page_no} is past the end of a file");
+    PageId::from(file_id) << PAGE_NO_BITS | page_no
+}
+
+/// Returns the tablespace a page is in, and its number within the tablespace's file.
+pub fn split_page_id(page_id: PageId) -> (FileId, PageId) {
+    ((page_id >> PAGE_NO_BITS) as FileId, page_id & ((1 << PAGE_NO_BITS) - 1))
+}
+
+/// Opens the file of a tablespace, see [`DiskManager::create_tablespace`].
+type OpenBackend = fn(&Path) -> Result<Box<dyn DiskBackend>>;
+
+/// When the pages a [`DiskManager`] writes are forced to stable storage, rather than left for the
+/// operating system to write out whenever it sees fit, see [`DiskManager::with_durability`]. The
+/// log is forced on its own, by [`DiskManager::sync_log`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Syncs after every page written, so that no page written is lost, at the price of a sync
+    /// each.
+    Always,
+    /// Syncs only when [`DiskManager::sync`] is called, e.g. by the buffer pool at the end of a
+    /// flush. The default.
+    #[default]
+    Manual,
+    /// Syncs every so often from a background thread, started by [`DiskManager::into_handle`],
+    /// if any page was written since the last sync.
+    Interval(Duration),
+}
+
+/// How the files of a [`DiskManager`] grow as pages are allocated past their end, see
+/// [`DiskManager::with_extents`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtentConfig {
+    /// The number of pages a file grows by at a time, made room for ahead of their allocation,
+    /// at least one. Files are grown to a multiple of it.
+    pub pages_per_extent: PageId,
+}
+
+impl Default for ExtentConfig {
+    /// Grows files 64 pages at a time.
+    fn default() -> Self {
+        Self {
+            pages_per_extent: 64,
+        }
+    }
+}
+
+/// How much room the files of a [`DiskManager`] take up, and how much of it is dead space, along
+/// with the I/O done since the disk manager was made, see [`DiskManager::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiskStats {
+    /// The size of the files, the database file's and the tablespaces', room reserved past their
+    /// pages included.
+    pub file_bytes: u64,
+    /// The pages allocated and not deallocated since, headers excluded.
+    pub allocated_pages: u64,
+    /// The pages deallocated and not allocated again yet, see [`DiskManager::free_pages`].
+    pub free_pages: u64,
+    /// The pages read from the files.
+    pub pages_read: u64,
+    /// The pages written to the files.
+    pub pages_written: u64,
+    /// The bytes read from the files, checksums included.
+    pub bytes_read: u64,
+    /// The bytes written to the files, checksums included.
+    pub bytes_written: u64,
+}
+
+/// The file of a tablespace.
+#[derive(Debug)]
+struct DataFile {
+    /// The tablespace's name, `default` for the database file.
+    name: String,
+    /// Where the file is, as given to [`DiskManager::create_tablespace`].
+    path: PathBuf,
+    /// Where the pages are kept, in a file unless [`DiskManager::new_in_memory`] made the manager.
+    backend: Box<dyn DiskBackend>,
+    /// The numbers of the pages deallocated, to be allocated again before the file grows, as kept
+    /// in its header.
+    free_pages: BTreeSet<PageId>,
+    /// Whether any page was written since the last sync.
+    unsynced: bool,
+}
+
+impl DataFile {
+    fn new(name: &str, path: &Path, backend: Box<dyn DiskBackend>) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            backend,
+            free_pages: BTreeSet::new(),
+            unsynced: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DiskManager {
-    current_page_no: AtomicU32,
-    writer: BufWriter<File>,
-    reader: BufReader<File>,
+    /// The files of the tablespaces, indexed by file ID, the database file's first.
+    files: Vec<DataFile>,
+    /// Opens the files of the tablespaces created.
+    open_backend: OpenBackend,
+    /// Where the tablespaces besides the default one are recorded, for a database file opened
+    /// with [`Self::open`].
+    tablespaces_path: Option<PathBuf>,
+    /// Path of the write-ahead log, which lives next to the database file.
+    log_path: PathBuf,
+    /// Handle to the write-ahead log, opened on first use.
+    log: Option<DiskFile>,
+    /// Pages read ahead of time by [`Self::read_ahead`], until they are read or overwritten.
+    read_ahead: HashMap<PageId, Vec<u8>>,
+    /// Counts the pages and log bytes read and written.
+    metrics: Arc<Metrics>,
+    /// The pages and bytes read and written since the disk manager was made, for
+    /// [`Self::stats`], which fills in the rest.
+    stats: DiskStats,
+    /// The LSN of the last completed checkpoint, as kept in the database file's header.
+    checkpoint_lsn: Lsn,
+    /// When the pages written are synced.
+    durability: Durability,
+    /// How the files grow as pages are allocated.
+    extents: ExtentConfig,
+    /// Whether the pages are read through a map of their file into memory, if asked to, see
+    /// [`Self::with_mmap_reads`]: `None` unless asked, else whether the database file is.
+    mmap_reads: Option<bool>,
+    /// Syncs the pages written every so often, with [`Durability::Interval`].
+    syncer: Option<BackgroundSyncer>,
+    /// The pages queued by [`Self::write_page_async`] and not written yet, each with the number
+    /// of its latest queueing, so that reads see them and the writer thread writes each once.
+    queued: HashMap<PageId, (u64, Vec<u8>)>,
+    /// The number of the latest page queued.
+    queue_seq: u64,
+    /// How many pages may wait for the writer thread at once, if writes are asynchronous, see
+    /// [`Self::with_async_writes`].
+    queue_capacity: Option<usize>,
+    /// Writes the pages queued, once started by [`Self::into_handle`].
+    writer: Option<AsyncWriter>,
+    /// Encrypts the pages, for a disk manager made by [`Self::open_encrypted`].
+    cipher: Option<PageCipher>,
+    /// The write counter of the last page encrypted.
+    write_counter: u64,
+    /// The write counter up to which counters may have been used, as recorded in the database
+    /// file's header. It is moved ahead before any counter past it is used.
+    counter_lease: u64,
 }
 
 impl DiskManager {
     /// Creates a new disk manager for the given database file `filename`, e.g. `example.db`
     pub fn new(filename: &str) -> Self {
         let path = Path::new(RUST_DB_DATA_DIR).join(filename);
+        Self::open(&path).expect("Unable to create or open file {path}.")
+    }
+
+    /// Opens the database file at `path`, creating it if it doesn't exist. The write-ahead log
+    /// lives next to it, at `path` with `.wal` appended, and so does the record of the
+    /// tablespaces created, with `.tablespaces` appended, whose files are opened too. New pages
+    /// are allocated from the ones the file's header lists as free, and then after the ones the
+    /// file already has.
+    ///
+    /// # Errors
+    /// - [`Error::ChecksumMismatch`]: If the header of a file isn't what was written to it.
+    /// - [`Error::InvalidInput`]: If a file is encrypted, see [`Self::open_encrypted`].
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, None)
+    }
+
+    /// Opens the database file at `path` like [`Self::open`], with its pages encrypted with
+    /// AES-256-GCM under `key`, and so are those of its tablespaces. The same key must be given
+    /// every time the file is opened; a file created without a key can't be opened with one,
+    /// nor the other way around. The log isn't encrypted.
+    ///
+    /// Each page is encrypted as it is written, with a nonce made of its page ID and a write
+    /// counter, kept in the page's trailer along with its authentication tag. Counters are
+    /// leased [`COUNTER_LEASE`] at a time in the database file's header, which is synced before
+    /// any counter past the lease is used, so that none is ever used twice. The checksum of a
+    /// page covers the bytes on disk, and is checked first, before the page is decrypted and
+    /// authenticated. Headers aren't encrypted, and hold a key check, the encryption of a
+    /// constant, to tell a wrong key from the right one.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If `key` isn't the one a file was created with, or a file
+    ///   isn't encrypted.
+    /// - [`Error::ChecksumMismatch`]: If the header of a file isn't what was written to it.
+    pub fn open_encrypted(path: &Path, key: &[u8; KEY_BYTES]) -> Result<Self> {
+        Self::open_with(path, Some(PageCipher::new(key)))
+    }
+
+    fn open_with(path: &Path, cipher: Option<PageCipher>) -> Result<Self> {
+        let mut log_path = path.as_os_str().to_owned();
+        log_path.push(".wal");
+        let mut tablespaces_path = path.as_os_str().to_owned();
+        tablespaces_path.push(".tablespaces");
+        let backend = Box::new(FileBackend::new(Self::open_file(path)?)?);
+        let mut disk_manager = Self::with_backend(backend, log_path.into(), None, cipher)?;
+        disk_manager.tablespaces_path = Some(tablespaces_path.into());
+        disk_manager.open_tablespaces()?;
+        Ok(disk_manager)
+    }
+
+    /// Creates a disk manager whose pages and log are kept in memory, and lost when it is
+    /// dropped, e.g. for tests that don't need real files. So are the pages of its tablespaces,
+    /// whatever their paths.
+    pub fn new_in_memory() -> Self {
+        let log = DiskFile::Memory(Cursor::new(Vec::new()));
+        let backend = Box::new(MemoryBackend::new());
+        let mut disk_manager =
+            Self::with_backend(backend, PathBuf::new(), Some(log), None).expect("Memory never fails");
+        disk_manager.open_backend = |_| Ok(Box::new(MemoryBackend::new()));
+        disk_manager
+    }
+
+    /// Creates a disk manager for the database file `file`, with the log as for
+    /// [`Self::with_backend`].
+    #[cfg(test)]
+    fn with_files(file: DiskFile, log_path: PathBuf, log: Option<DiskFile>) -> Result<Self> {
+        Self::with_backend(Box::new(FileBackend::new(file)?), log_path, log, None)
+    }
+
+    /// Creates a disk manager keeping its pages in `backend`, and encrypting them with `cipher`,
+    /// if any. Its log is `log`, or if `None`, the file at `log_path`, opened on first use.
+    fn with_backend(
+        backend: Box<dyn DiskBackend>,
+        log_path: PathBuf,
+        log: Option<DiskFile>,
+        cipher: Option<PageCipher>,
+    ) -> Result<Self> {
+        let mut disk_manager = DiskManager {
+            files: vec![DataFile::new("default", Path::new(""), backend)],
+            open_backend: |path| Ok(Box::new(FileBackend::new(Self::open_file(path)?)?)),
+            tablespaces_path: None,
+            log_path,
+            log,
+            read_ahead: HashMap::new(),
+            metrics: Arc::default(),
+            stats: DiskStats::default(),
+            checkpoint_lsn: INVALID_LSN,
+            durability: Durability::default(),
+            extents: ExtentConfig::default(),
+            mmap_reads: None,
+            syncer: None,
+            queued: HashMap::new(),
+            queue_seq: 0,
+            queue_capacity: None,
+            writer: None,
+            cipher,
+            write_counter: 0,
+            counter_lease: 0,
+        };
+        disk_manager.read_header(DEFAULT_TABLESPACE)?;
+        Ok(disk_manager)
+    }
+
+    /// Opens the data file at `path` for reading and writing, creating it if it doesn't exist.
+    fn open_file(path: &Path) -> Result<DiskFile> {
         let file = OpenOptions::new()
             .write(true)
             .read(true)
             .create(true)
-            .open(path)
-            .expect("Unable to create or open file {path}.");
-        let reader = file;
-        let writer = reader.try_clone().expect("Unable to clone file {filename}");
+            .truncate(false)
+            .open(path)?;
+        Ok(file.into())
+    }
+
+    /// Creates a tablespace named `name`, whose pages are kept in the file at `path`, e.g. on
+    /// another disk than the database file, and returns its ID, to allocate pages in it with
+    /// [`Self::allocate_new_page_in`]. A file that exists already is opened with the pages it
+    /// has. The tablespace is recorded next to a database file opened with [`Self::open`], to be
+    /// opened with it from then on. Backups only copy the default tablespace for now.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If a tablespace is named `name` already, the name is empty or
+    ///   has whitespace, the path isn't UTF-8 or has a line break, or there are
+    ///   [`MAX_TABLESPACES`] already.
+    /// - [`Error::IO`]: If the file can't be opened, or the tablespace can't be recorded, in
+    ///   which case it isn't created.
+    /// - [`Error::ChecksumMismatch`]: If the file's header isn't what was written to it.
+    pub fn create_tablespace(&mut self, name: &str, path: &Path) -> Result<FileId> {
+        if self.tablespace(name).is_some() {
+            return errinput!("tablespace {name} already exists");
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return errinput!("invalid tablespace name {name:?}");
+        }
+        if path.to_str().is_none_or(|path| path.contains('\n')) {
+            return errinput!("invalid tablespace path {path:?}");
+        }
+        if self.files.len() >= MAX_TABLESPACES {
+            return errinput!("there are {MAX_TABLESPACES} tablespaces already");
+        }
+        let file_id = self.files.len() as FileId;
+        let mut backend = (self.open_backend)(path)?;
+        if self.mmap_reads.is_some() {
+            backend.map_reads();
+        }
+        self.files.push(DataFile::new(name, path, backend));
+        if let Err(error) = self.read_header(file_id).and_then(|()| self.save_tablespaces()) {
+            self.files.pop();
+            return Err(error);
+        }
+        Ok(file_id)
+    }
+
+    /// Returns the ID of the tablespace named `name`, if there is one.
+    pub fn tablespace(&self, name: &str) -> Option<FileId> {
+        let file_id = self.files.iter().position(|file| file.name == name)?;
+        Some(file_id as FileId)
+    }
+
+    /// Opens the files of the tablespaces recorded next to the database file, if any.
+    fn open_tablespaces(&mut self) -> Result<()> {
+        let Some(tablespaces_path) = &self.tablespaces_path else {
+            return Ok(());
+        };
+        let text = match std::fs::read_to_string(tablespaces_path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+        for line in text.lines() {
+            let Some((name, path)) = line.split_once('\t') else {
+                return Err(Error::InvalidData(format!("bad tablespace record {line:?}")));
+            };
+            let file_id = self.files.len() as FileId;
+            let path = Path::new(path);
+            self.files.push(DataFile::new(name, path, (self.open_backend)(path)?));
+            self.read_header(file_id)?;
+        }
+        Ok(())
+    }
+
+    /// Records the tablespaces besides the default one next to the database file, a line each
+    /// with the name and path, for [`Self::open`] to open them again. Like a log with a path in
+    /// [`Self::truncate_log`], the record is rewritten to a temporary file that then replaces it.
+    fn save_tablespaces(&self) -> Result<()> {
+        let Some(tablespaces_path) = &self.tablespaces_path else {
+            return Ok(());
+        };
+        let mut text = String::new();
+        for file in &self.files[1..] {
+            text += &format!("{}\t{}\n", file.name, file.path.display());
+        }
+        let temp_path = tablespaces_path.with_extension("tablespaces.tmp");
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(text.as_bytes())?;
+        temp.sync_data()?;
+        std::fs::rename(&temp_path, tablespaces_path)?;
+        Ok(())
+    }
 
-        DiskManager {
-            current_page_no: AtomicU32::new(0),
-            writer: BufWriter::new(writer),
-            reader: BufReader::new(reader),
+    /// Returns the file of tablespace `file_id`.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If there is no such tablespace.
+    fn file(&mut self, file_id: FileId) -> Result<&mut DataFile> {
+        match self.files.get_mut(file_id as usize) {
+            Some(file) => Ok(file),
+            None => errinput!("no tablespace {file_id}"),
         }
     }
     pub fn new_with_handle(filename: &str) -> Arc<RwLock<Self>> {
-        Arc::new(RwLock::new(Self::new(filename)))
+        Self::new(filename).into_handle()
     }
 
-    pub fn allocate_new_page(&mut self) -> PageId {
-        let page_id = self.increment_and_fetch_page_no();
+    /// Sets when the pages written are synced, e.g.
+    /// `DiskManager::open(path)?.with_durability(Durability::Always)`. [`Durability::Manual`]
+    /// unless set.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Returns when the pages written are synced.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Sets how many pages the files grow by at a time as pages are allocated past their end,
+    /// e.g. `DiskManager::open(path)?.with_extents(ExtentConfig { pages_per_extent: 256 })`, so
+    /// that bulk inserts don't grow them, and fragment them, a page at a time. Pages made room
+    /// for aren't allocated until they are handed out by [`Self::allocate_new_page_in`], nor
+    /// counted by [`Self::num_pages`]. 64 pages unless set.
+    pub fn with_extents(mut self, extents: ExtentConfig) -> Self {
+        self.extents = ExtentConfig {
+            pages_per_extent: extents.pages_per_extent.max(1),
+        };
+        self
+    }
+
+    /// Returns how the files grow as pages are allocated.
+    pub fn extents(&self) -> ExtentConfig {
+        self.extents
+    }
+
+    /// Reads the pages through a map of their file into memory, rather than with a read each,
+    /// e.g. `DiskManager::open(path)?.with_mmap_reads()`, for read-heavy workloads on a database
+    /// the operating system caches whole. Pages are copied out of the map, and checked against
+    /// their checksums, like those read. Writes go through the same page cache the map does, so
+    /// reads see them right away; a file that grows is mapped again once read past the end of
+    /// the map, and one that shrinks is let go of first. Files that can't be mapped, e.g. those
+    /// of [`Self::new_in_memory`], are read as before. Not unless set.
+    pub fn with_mmap_reads(mut self) -> Self {
+        let mapped: Vec<bool> =
+            self.files.iter_mut().map(|file| file.backend.map_reads()).collect();
+        self.mmap_reads = Some(mapped[DEFAULT_TABLESPACE as usize]);
+        self
+    }
+
+    /// Returns whether the database file is read through a map into memory, i.e. whether
+    /// [`Self::with_mmap_reads`] was set, and its file can be mapped.
+    pub fn mmap_reads(&self) -> bool {
+        self.mmap_reads == Some(true)
+    }
+
+    /// Injects the faults programmed into `injector` into the database file's backend, e.g.
+    /// failed or torn writes, and power cuts, to test how the code above the disk manager copes
+    /// with them. The files of the tablespaces are left alone. Only with the `fault-injection`
+    /// feature, or in tests.
+    #[cfg(any(test, feature = "fault-injection"))]
+    pub fn with_fault_injector(mut self, injector: FaultInjector) -> Self {
+        let file = &mut self.files[DEFAULT_TABLESPACE as usize];
+        let backend = std::mem::replace(&mut file.backend, Box::new(MemoryBackend::new()));
+        file.backend = injector.wrap(backend);
+        self
+    }
+
+    /// Makes [`Self::write_page_async`] queue the pages for a thread of their own to write,
+    /// `capacity` of them at most waiting at a time, e.g.
+    /// `DiskManager::open(path)?.with_async_writes(64)`. The thread is started by
+    /// [`Self::into_handle`]; until then, and unless set, the pages are written right away.
+    /// Dropping the disk manager writes the pages still queued, on the writer thread if the last
+    /// handle is let go of while the thread holds it.
+    pub fn with_async_writes(mut self, capacity: usize) -> Self {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Wraps the disk manager in a handle to share, e.g. with a buffer pool. With
+    /// [`Durability::Interval`], starts the thread syncing it, and with async writes, the one
+    /// writing the pages queued, which stop once the last handle is dropped; until then, the
+    /// disk manager only syncs when told to.
+    pub fn into_handle(self) -> Arc<RwLock<Self>> {
+        let durability = self.durability;
+        let queue_capacity = self.queue_capacity;
+        let disk_manager = Arc::new(RwLock::new(self));
+        if let Durability::Interval(interval) = durability {
+            let syncer = BackgroundSyncer::spawn(Arc::downgrade(&disk_manager), interval);
+            disk_manager.write().unwrap().syncer = Some(syncer);
+        }
+        if let Some(capacity) = queue_capacity {
+            let writer = AsyncWriter::spawn(Arc::downgrade(&disk_manager), capacity);
+            disk_manager.write().unwrap().writer = Some(writer);
+        }
+        disk_manager
+    }
+
+    /// Counts I/O in the given registry from now on.
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = metrics;
+    }
+
+    /// Allocates an empty table page in the default tablespace, like
+    /// [`Self::allocate_new_page_in`].
+    pub fn allocate_new_page(&mut self) -> Result<PageId> {
+        self.allocate_new_page_in(DEFAULT_TABLESPACE)
+    }
+
+    /// Allocates an empty table page in tablespace `file_id`, reusing the lowest page deallocated
+    /// in it if there is any, and growing its file otherwise, by an extent at a time, see
+    /// [`Self::with_extents`].
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If there is no such tablespace, or its file is as large as a
+    ///   file may be.
+    /// - [`Error::IO`]: If the page, or the header it is taken off the free pages in, can't be
+    ///   written. A page taken off the free pages is put back on them.
+    pub fn allocate_new_page_in(&mut self, file_id: FileId) -> Result<PageId> {
+        let extent = self.extents.pages_per_extent;
+        let file = self.file(file_id)?;
+        let (page_no, reused) = match file.free_pages.pop_first() {
+            Some(page_no) => {
+                // Taken off the free pages on disk first, so that it is never handed out twice.
+                if let Err(error) = self.write_header(file_id) {
+                    self.files[file_id as usize].free_pages.insert(page_no);
+                    return Err(error);
+                }
+                (page_no, true)
+            }
+            None if file.backend.num_blocks() >= 1 << PAGE_NO_BITS => {
+                return errinput!("tablespace {} is full", file.name);
+            }
+            None => {
+                let blocks = (file.backend.num_blocks() + 1).div_ceil(extent).saturating_mul(extent);
+                file.backend.reserve(blocks.min(1 << PAGE_NO_BITS))?;
+                (file.backend.allocate()?, false)
+            }
+        };
+        let page_id = make_page_id(file_id, page_no);
         let new_page = TablePage::builder().page_id(page_id).build();
 
-        self.write_page(new_page);
-        page_id
+        if let Err(error) = self.write_page(new_page) {
+            if reused {
+                self.free_again(file_id, [page_no]);
+            }
+            return Err(error);
+        }
+        Ok(page_id)
+    }
+
+    /// Allocates `n` empty table pages with consecutive IDs in the default tablespace, like
+    /// [`Self::allocate_contiguous_in`].
+    pub fn allocate_contiguous(&mut self, n: PageId) -> Result<Vec<PageId>> {
+        self.allocate_contiguous_in(DEFAULT_TABLESPACE, n)
+    }
+
+    /// Allocates `n` empty table pages with consecutive IDs in tablespace `file_id`, e.g. for a
+    /// bulk load to write in runs, and returns their IDs in order. The lowest run of `n` pages
+    /// deallocated in it is reused if there is one, and its file grows by the pages otherwise,
+    /// an extent at a time. The pages are written with a single write.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If there is no such tablespace, or its file has no room for
+    ///   the pages.
+    /// - [`Error::IO`]: If the pages, or the header they are taken off the free pages in, can't
+    ///   be written. Pages taken off the free pages are put back on them.
+    pub fn allocate_contiguous_in(&mut self, file_id: FileId, n: PageId) -> Result<Vec<PageId>> {
+        let extent = self.extents.pages_per_extent;
+        let file = self.file(file_id)?;
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let (first, reused) = match Self::free_run(&file.free_pages, n) {
+            Some(first) => {
+                // Taken off the free pages on disk first, like a page allocated on its own.
+                file.free_pages.retain(|page_no| !(first..first + n).contains(page_no));
+                if let Err(error) = self.write_header(file_id) {
+                    self.files[file_id as usize].free_pages.extend(first..first + n);
+                    return Err(error);
+                }
+                (first, true)
+            }
+            None if u64::from(file.backend.num_blocks()) + u64::from(n) > 1 << PAGE_NO_BITS => {
+                return errinput!("tablespace {} has no room for {n} pages", file.name);
+            }
+            None => {
+                let first = file.backend.num_blocks();
+                let blocks = (first + n).div_ceil(extent).saturating_mul(extent);
+                file.backend.reserve(blocks.min(1 << PAGE_NO_BITS))?;
+                for _ in 0..n {
+                    file.backend.allocate()?;
+                }
+                (first, false)
+            }
+        };
+        let page_ids: Vec<PageId> =
+            (first..first + n).map(|page_no| make_page_id(file_id, page_no)).collect();
+        let pages = (page_ids.iter()).map(|page_id| TablePage::builder().page_id(*page_id).build());
+        if let Err(error) = self.write_pages(pages.collect()) {
+            if reused {
+                self.free_again(file_id, first..first + n);
+            }
+            return Err(error);
+        }
+        Ok(page_ids)
+    }
+
+    /// Puts pages of tablespace `file_id` that failed to be written back on its free pages, on
+    /// disk too. If the header can't be written either, they are only free until the file is
+    /// reopened, and never handed out again after.
+    fn free_again(&mut self, file_id: FileId, page_nos: impl IntoIterator<Item = PageId>) {
+        self.files[file_id as usize].free_pages.extend(page_nos);
+        _ = self.write_header(file_id);
+    }
+
+    /// Returns the first of the lowest run of `n` consecutive pages in `free_pages`, if any.
+    fn free_run(free_pages: &BTreeSet<PageId>, n: PageId) -> Option<PageId> {
+        let (mut first, mut len) = (0, 0);
+        for &page_no in free_pages {
+            if len > 0 && page_no == first + len {
+                len += 1;
+            } else {
+                (first, len) = (page_no, 1);
+            }
+            if len == n {
+                return Some(first);
+            }
+        }
+        None
+    }
+
+    /// Frees a page for [`Self::allocate_new_page_in`] to hand out again, like
+    /// [`Self::deallocate_pages`].
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the header can't be written, in which case the page isn't freed.
+    pub fn deallocate_page(&mut self, page_id: &PageId) -> Result<()> {
+        self.deallocate_pages(&[*page_id])
+    }
+
+    /// Frees the given pages for [`Self::allocate_new_page_in`] to hand out again, e.g. those of
+    /// a table dropped, recording them in the headers of their tablespaces' files, each written
+    /// once whatever the number of its pages freed. Headers, pages past the end of their file,
+    /// or of the ones the header has room for, and pages of tablespaces that don't exist, are
+    /// left alone.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If a header can't be written, in which case the pages of its file, and
+    ///   of the files after it, aren't freed. Those of the files before it are.
+    pub fn deallocate_pages(&mut self, page_ids: &[PageId]) -> Result<()> {
+        let mut freed: BTreeMap<FileId, Vec<PageId>> = BTreeMap::new();
+        for page_id in page_ids {
+            let (file_id, page_no) = split_page_id(*page_id);
+            let Some(file) = self.files.get_mut(file_id as usize) else {
+                continue;
+            };
+            let in_file = (1..file.backend.num_blocks()).contains(&page_no);
+            if in_file && (page_no as usize) < FREE_MAP_PAGES && file.free_pages.insert(page_no) {
+                self.read_ahead.remove(page_id);
+                self.queued.remove(page_id);
+                freed.entry(file_id).or_default().push(page_no);
+            }
+        }
+        let freed: Vec<(FileId, Vec<PageId>)> = freed.into_iter().collect();
+        for (i, (file_id, _)) in freed.iter().enumerate() {
+            if let Err(error) = self.write_header(*file_id) {
+                for (file_id, page_nos) in &freed[i..] {
+                    let free_pages = &mut self.files[*file_id as usize].free_pages;
+                    page_nos.iter().for_each(|page_no| _ = free_pages.remove(page_no));
+                }
+                return Err(error);
+            }
+        }
+        for (file_id, page_nos) in freed {
+            for page_no in page_nos {
+                self.files[file_id as usize].backend.deallocate(page_no)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the pages deallocated and not allocated again yet, in order.
+    pub fn free_pages(&self) -> impl Iterator<Item = PageId> + '_ {
+        (self.files.iter().enumerate()).flat_map(|(file_id, file)| {
+            let file_id = file_id as FileId;
+            file.free_pages.iter().map(move |page_no| make_page_id(file_id, *page_no))
+        })
+    }
+
+    /// Returns how big the files are, how many of their pages are allocated and how many free,
+    /// and the pages and bytes read and written since the disk manager was made, those of the
+    /// headers included, and those of the log not. Pages read ahead count when read ahead.
+    pub fn stats(&self) -> DiskStats {
+        let mut stats = self.stats;
+        for file in &self.files {
+            let free_pages = file.free_pages.len() as u64;
+            let blocks = file.backend.reserved_blocks() as u64;
+            stats.file_bytes += blocks * RUSTY_DB_PAGE_SIZE_BYTES as u64;
+            stats.allocated_pages += file.backend.num_blocks() as u64 - 1 - free_pages;
+            stats.free_pages += free_pages;
+        }
+        stats
+    }
+
+    /// Compacts the database file, like [`Self::compact_except`], moving any page that needs to
+    /// be. No buffer pool may hold pages of the file meanwhile, as the pages it holds may be
+    /// moved; see [`BufferPoolManager::compact_disk`] to compact the file under one.
+    ///
+    /// [`BufferPoolManager::compact_disk`]:
+    /// crate::storage::buffer::buffer_pool_manager::BufferPoolManager::compact_disk
+    pub fn compact(&mut self, relocations: &mut dyn FnMut(PageId, PageId)) -> Result<PageId> {
+        self.compact_except(&|_| false, relocations)
+    }
+
+    /// Reclaims the space of the pages deallocated in the database file: moves the last pages
+    /// of the file into the lowest free pages, for as long as there is a free page before them,
+    /// then truncates the file after the last page still allocated, and returns by how many
+    /// pages it shrank. Pages `stay` holds to are left where they are, e.g. those resident in a
+    /// buffer pool. The pages queued are written first. The tablespaces' files are left alone.
+    ///
+    /// `relocations` is called with the old and new ID of each page moved, once they are all
+    /// synced at their new IDs, for the layers above to update the references to them. The
+    /// page's own ID in its contents is rewritten as it is moved, see [`Self::relocate_page`].
+    /// Until the header is
+    /// written with the old IDs free, the pages can still be read at those too, so a crash
+    /// before that leaves the file as it was, with copies of pages on free pages.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If a page can't be moved, in which case none are reported moved, and
+    ///   the file is left as it was, or if the header can't be written or the file truncated,
+    ///   in which case the pages moved were reported, and the file may not have shrunk.
+    /// - [`Error::ChecksumMismatch`]: If a page to move isn't what was written to it.
+    pub fn compact_except(
+        &mut self,
+        stay: &dyn Fn(PageId) -> bool,
+        relocations: &mut dyn FnMut(PageId, PageId),
+    ) -> Result<PageId> {
+        trace_span!("compact");
+        self.drain()?;
+        self.read_ahead.clear();
+        let file = &self.files[DEFAULT_TABLESPACE as usize];
+        let num_blocks = file.backend.num_blocks();
+        let mut free = file.free_pages.clone();
+        let mut moves = Vec::new();
+        for page_no in (HEADER_PAGE_NO + 1..num_blocks).rev() {
+            if free.contains(&page_no) || stay(page_no) {
+                continue;
+            }
+            match free.first() {
+                Some(&target) if target < page_no => {
+                    free.remove(&target);
+                    free.insert(page_no);
+                    moves.push((page_no, target));
+                }
+                _ => break,
+            }
+        }
+        for (from, to) in &moves {
+            let mut payload = self.read_page_bytes(from)?;
+            Self::relocate_page(&mut payload, *from, *to);
+            self.write_page_bytes(to, &payload)?;
+        }
+        self.sync()?;
+        for (from, to) in &moves {
+            relocations(*from, *to);
+        }
+
+        // The free pages at the end of the file go with it.
+        let mut end = num_blocks;
+        while end > HEADER_PAGE_NO + 1 && free.contains(&(end - 1)) {
+            end -= 1;
+        }
+        free.retain(|page_no| *page_no < end);
+        let file = &mut self.files[DEFAULT_TABLESPACE as usize];
+        let previous = std::mem::replace(&mut file.free_pages, free);
+        if let Err(error) = self.write_header(DEFAULT_TABLESPACE).and_then(|()| self.sync()) {
+            self.files[DEFAULT_TABLESPACE as usize].free_pages = previous;
+            return Err(error);
+        }
+        let file = &mut self.files[DEFAULT_TABLESPACE as usize];
+        file.backend.truncate(end)?;
+        file.unsynced = true;
+        self.sync()?;
+        Ok(num_blocks - end)
+    }
+
+    /// Rewrites the ID the page `payload` holds of itself from `from` to `to`: at its start for
+    /// table and overflow pages, and after the page type and key schema for B+tree pages. A page
+    /// whose ID isn't `from` where its kind keeps it, e.g. one never written, is left as is.
+    fn relocate_page(payload: &mut [u8], from: PageId, to: PageId) {
+        let id_at = |at: usize| payload[at..(at + size_of::<PageId>())] == from.to_le_bytes();
+        // A table or overflow page with a B+tree page's type as its first byte has the next
+        // page's ID where a B+tree page has its own, which is never its own.
+        let at = match BPlusTreePageType::of(payload) {
+            Some(_) if id_at(4) => 4,
+            _ if id_at(0) => 0,
+            _ => return,
+        };
+        payload[at..(at + size_of::<PageId>())].copy_from_slice(&to.to_le_bytes());
+    }
+
+    /// Reads the table page at `page_id`.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the file can't be read.
+    /// - [`Error::PageNotAllocated`]: If the page is past the last one allocated in its file, is
+    ///   free, having been deallocated, or its tablespace doesn't exist.
+    /// - [`Error::ShortRead`]: If the file ends partway through the page, e.g. as it was
+    ///   truncated.
+    /// - [`Error::ChecksumMismatch`]: If the page isn't what was written to disk.
+    pub fn read_page(&mut self, page_id: &PageId) -> Result<TablePage> {
+        Ok(TablePage::deserialize(&self.read_page_bytes(page_id)?))
     }
 
-    /// No-op for now; a little out of scope for this project :)
-    pub fn deallocate_page(&mut self, _page_id: &PageId) {
-        // no-op
+    /// Reads the serialized page at `page_id`, whatever its type, checked against its checksum.
+    /// It is [`RUSTY_DB_PAGE_PAYLOAD_BYTES`] long, read from a whole page on disk. A page queued
+    /// by [`Self::write_page_async`] is read as last queued. Fails like [`Self::read_page`].
+    pub fn read_page_bytes(&mut self, page_id: &PageId) -> Result<Vec<u8>> {
+        trace_span!("read_page", page_id);
+        if let Some(payload) = self.queued_page(page_id) {
+            return Ok(payload);
+        }
+        self.check_allocated(page_id)?;
+        let buffer = match self.read_ahead.remove(page_id) {
+            Some(buffer) => buffer,
+            None => self.read_run(&[*page_id])?,
+        };
+        self.unseal_page(page_id, buffer)
+    }
+
+    /// Reads the given pages, in order, once each however many times they are asked for. Like
+    /// with [`Self::read_ahead`], runs of consecutive pages of a file are read with a single read
+    /// each, the others one by one, and pages read ahead or queued are taken from memory, like
+    /// with [`Self::read_page_bytes`]. Each page fails
+    /// on its own, like with [`Self::read_page`], e.g. if it is past the end of its file.
+    pub fn read_pages(&mut self, page_ids: &[PageId]) -> Vec<Result<TablePage>> {
+        trace_span!("read_pages", pages = page_ids.len());
+        let mut payloads = HashMap::new();
+        for page_id in page_ids {
+            if let Some(payload) = self.queued_page(page_id) {
+                payloads.insert(*page_id, Ok(payload));
+            } else if let Err(error) = self.check_allocated(page_id) {
+                payloads.insert(*page_id, Err(error));
+            } else if let Some(buffer) = self.read_ahead.remove(page_id) {
+                payloads.insert(*page_id, self.unseal_page(page_id, buffer));
+            }
+        }
+        let mut missing: Vec<PageId> = page_ids
+            .iter()
+            .copied()
+            .filter(|page_id| !payloads.contains_key(page_id))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        for run in missing.chunk_by(Self::consecutive) {
+            match self.read_run(run) {
+                Ok(buffer) => {
+                    // Pages the file ends before come out short, or empty.
+                    let mut pages = buffer.chunks(RUSTY_DB_PAGE_SIZE_BYTES);
+                    for page_id in run {
+                        let page = pages.next().unwrap_or_default();
+                        payloads.insert(*page_id, self.unseal_page(page_id, page.to_vec()));
+                    }
+                }
+                Err(error) => {
+                    payloads.extend(run.iter().map(|page_id| (*page_id, Err(error.clone()))));
+                }
+            }
+        }
+        page_ids
+            .iter()
+            .map(|page_id| Ok(TablePage::deserialize(&payloads[page_id].clone()?)))
+            .collect()
+    }
+
+    /// Hints that the given pages are about to be read. Runs of consecutive pages are read with a
+    /// single read each, and kept in memory until they are read or overwritten. At most
+    /// [`READ_AHEAD_PAGES`] pages are kept; hints beyond that drop the older ones. Pages that
+    /// can't be read are skipped, and fail once they are read for real, and so do checksums.
+    pub fn read_ahead(&mut self, page_ids: &[PageId]) {
+        trace_span!("read_ahead", pages = page_ids.len());
+        let mut page_ids: Vec<PageId> = page_ids
+            .iter()
+            .copied()
+            .filter(|page_id| !self.read_ahead.contains_key(page_id))
+            .take(READ_AHEAD_PAGES)
+            .collect();
+        if self.read_ahead.len() + page_ids.len() > READ_AHEAD_PAGES {
+            self.read_ahead.clear();
+        }
+        page_ids.sort_unstable();
+        page_ids.dedup();
+        for run in page_ids.chunk_by(Self::consecutive) {
+            let Ok(buffer) = self.read_run(run) else {
+                continue;
+            };
+            for (page_id, page) in run.iter().zip(buffer.chunks_exact(RUSTY_DB_PAGE_SIZE_BYTES)) {
+                self.read_ahead.insert(*page_id, page.to_vec());
+            }
+        }
+    }
+
+    /// Checks that page `page_id` is allocated: that its tablespace exists, and that it is one
+    /// of the pages of its file, and not one of those deallocated.
+    ///
+    /// # Errors
+    /// - [`Error::PageNotAllocated`]: If it isn't.
+    fn check_allocated(&self, page_id: &PageId) -> Result<()> {
+        let (file_id, page_no) = split_page_id(*page_id);
+        let allocated = self.files.get(file_id as usize).is_some_and(|file| {
+            page_no < file.backend.num_blocks() && !file.free_pages.contains(&page_no)
+        });
+        match allocated {
+            true => Ok(()),
+            false => Err(Error::PageNotAllocated(*page_id)),
+        }
+    }
+
+    /// Returns whether page `b` follows page `a` in the same file.
+    fn consecutive(a: &PageId, b: &PageId) -> bool {
+        *b == a + 1 && split_page_id(*a).0 == split_page_id(*b).0
+    }
+
+    /// Reads a run of consecutive pages of a file with a single read. Fewer bytes come back than
+    /// the pages take up if the file ends before they do.
+    fn read_run(&mut self, run: &[PageId]) -> Result<Vec<u8>> {
+        let (file_id, page_no) = split_page_id(run[0]);
+        let buffer = self.file(file_id)?.backend.read_block(page_no, run.len())?;
+        self.metrics.disk_pages_read.add(run.len() as u64);
+        self.metrics.disk_reads.incr();
+        self.stats.pages_read += run.len() as u64;
+        self.stats.bytes_read += buffer.len() as u64;
+        Ok(buffer)
+    }
+
+    /// Returns the number of pages allocated in the database file, counting the header as page
+    /// 0, whatever room the file has past them.
+    pub fn num_pages(&self) -> PageId {
+        self.files[DEFAULT_TABLESPACE as usize].backend.num_blocks()
+    }
+
+    /// Reads the given pages straight from the database file, e.g. to copy them elsewhere. Pages
+    /// read ahead are left alone, and so are the I/O counters. A header never written yet comes
+    /// back empty. Pages come with their checksums, unchecked.
+    pub fn read_page_range(&mut self, pages: Range<PageId>) -> Result<Vec<u8>> {
+        let backend = &mut self.files[DEFAULT_TABLESPACE as usize].backend;
+        backend.read_block(pages.start, pages.len())
+    }
+
+    /// Writes a page to its page ID, in the file of its tablespace.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If the page's tablespace doesn't exist.
+    /// - [`Error::IO`]: If the file can't be written, e.g. if the disk is full.
+    pub fn write_page<P: Page>(&mut self, page: P) -> Result<()> {
+        self.write_page_bytes(page.page_id(), &page.serialize())
+    }
+
+    /// Writes a serialized page of any type to `page_id`, padded with zeros to
+    /// [`RUSTY_DB_PAGE_PAYLOAD_BYTES`] and followed by a CRC32 of those bytes. Fails like
+    /// [`Self::write_page`].
+    pub fn write_page_bytes(&mut self, page_id: &PageId, payload: &[u8]) -> Result<()> {
+        trace_span!("write_page", page_id);
+        self.read_ahead.remove(page_id);
+        self.queued.remove(page_id);
+        let buffer = self.seal_page(*page_id, payload)?;
+        self.write_run(*page_id, &buffer)?;
+        if self.durability == Durability::Always {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the given pages, each to its page ID. Runs of consecutive pages of a file are
+    /// written with a single write each, the others one by one. Of pages with the same ID, the
+    /// last one is written. With [`Durability::Always`], the pages are synced once, at the end.
+    ///
+    /// # Errors
+    /// Fails like [`Self::write_page`], in which case the runs before the one that failed are
+    /// written, and the others aren't.
+    pub fn write_pages<P: Page>(&mut self, pages: Vec<P>) -> Result<()> {
+        let pages: Vec<_> = pages.iter().map(|page| (*page.page_id(), page.serialize())).collect();
+        self.write_pages_bytes(&pages)
+    }
+
+    /// Writes serialized pages of any type, each to its page ID, like [`Self::write_pages`] and
+    /// [`Self::write_page_bytes`].
+    pub fn write_pages_bytes(&mut self, pages: &[(PageId, Vec<u8>)]) -> Result<()> {
+        trace_span!("write_pages", pages = pages.len());
+        let pages: BTreeMap<PageId, &[u8]> =
+            (pages.iter()).map(|(page_id, payload)| (*page_id, payload.as_slice())).collect();
+        let page_ids: Vec<PageId> = pages.keys().copied().collect();
+        for run in page_ids.chunk_by(Self::consecutive) {
+            let mut buffer = Vec::with_capacity(run.len() * RUSTY_DB_PAGE_SIZE_BYTES);
+            for page_id in run {
+                self.read_ahead.remove(page_id);
+                self.queued.remove(page_id);
+                buffer.extend(self.seal_page(*page_id, pages[page_id])?);
+            }
+            self.write_run(run[0], &buffer)?;
+        }
+        if self.durability == Durability::Always {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Queues a page to be written to its page ID by the writer thread, and returns without
+    /// waiting for it, with async writes, see [`Self::with_async_writes`]. Reads see the page
+    /// until it is written, and the page is only written as last queued. Without async writes,
+    /// or if the queue is full, the page is written right away.
+    ///
+    /// # Errors
+    /// Fails like [`Self::write_page`], if the page is written right away. Pages the writer
+    /// thread fails to write stay queued, for [`Self::drain`] to try again.
+    pub fn write_page_async<P: Page>(&mut self, page: P) -> Result<()> {
+        self.write_page_bytes_async(page.page_id(), page.serialize())
+    }
+
+    /// Queues a serialized page of any type, like [`Self::write_page_async`].
+    pub fn write_page_bytes_async(&mut self, page_id: &PageId, payload: Vec<u8>) -> Result<()> {
+        let Some(writer) = &self.writer else {
+            return self.write_page_bytes(page_id, &payload);
+        };
+        self.queue_seq += 1;
+        let seq = self.queue_seq;
+        if writer.queue(*page_id, seq) {
+            self.read_ahead.remove(page_id);
+            self.queued.insert(*page_id, (seq, payload));
+            Ok(())
+        } else {
+            self.write_page_bytes(page_id, &payload)
+        }
+    }
+
+    /// Writes page `page_id` now if it is queued, and syncs it, unless the writer thread wrote
+    /// it already. Fails like [`Self::drain`].
+    pub fn wait_for_write(&mut self, page_id: &PageId) -> Result<()> {
+        let Some((seq, _)) = self.queued.get(page_id) else {
+            return Ok(());
+        };
+        self.write_queued(&[(*page_id, *seq)])?;
+        self.sync()
+    }
+
+    /// Writes all the pages queued now, rather than waiting for the writer thread to, and syncs
+    /// them, e.g. before a checkpoint. Does nothing if none are queued.
+    ///
+    /// # Errors
+    /// Fails like [`Self::write_pages`], in which case the pages not written stay queued.
+    pub fn drain(&mut self) -> Result<()> {
+        if self.queued.is_empty() {
+            return Ok(());
+        }
+        trace_span!("drain", pages = self.queued.len());
+        let writes: Vec<(PageId, u64)> =
+            (self.queued.iter()).map(|(page_id, (seq, _))| (*page_id, *seq)).collect();
+        self.write_queued(&writes)?;
+        self.sync()
+    }
+
+    /// Writes the pages queued as numbered, those queued again since being left for their
+    /// latest queueing, and those written since, left alone.
+    fn write_queued(&mut self, writes: &[(PageId, u64)]) -> Result<()> {
+        let mut pages = Vec::with_capacity(writes.len());
+        let mut seqs = Vec::with_capacity(writes.len());
+        for (page_id, seq) in writes {
+            if self.queued.get(page_id).is_some_and(|(queued_seq, _)| queued_seq == seq) {
+                pages.push((*page_id, self.queued.remove(page_id).unwrap().1));
+                seqs.push(*seq);
+            }
+        }
+        let result = self.write_pages_bytes(&pages);
+        if result.is_err() {
+            // Queued again, as which of the pages were written isn't known.
+            for ((page_id, payload), seq) in pages.into_iter().zip(seqs) {
+                self.queued.insert(page_id, (seq, payload));
+            }
+        }
+        result
+    }
+
+    /// Returns page `page_id` as it is queued, padded like [`Self::read_page_bytes`] returns it,
+    /// if it is.
+    fn queued_page(&self, page_id: &PageId) -> Option<Vec<u8>> {
+        let (_, payload) = self.queued.get(page_id)?;
+        let mut payload = payload.clone();
+        payload.resize(RUSTY_DB_PAGE_PAYLOAD_BYTES, 0);
+        Some(payload)
+    }
+
+    /// Lays a serialized page out as it is kept on disk: padded with zeros to
+    /// [`RUSTY_DB_PAGE_PAYLOAD_BYTES`] and encrypted, if pages are, then followed by its trailer
+    /// and a CRC32 of all that. The trailer of an encrypted page holds its write counter and its
+    /// authentication tag, that of a header what [`Self::read_header`] reads, and is zeros
+    /// otherwise.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the counters leased are used up, and the header can't be written or
+    ///   synced to lease more.
+    fn seal_page(&mut self, page_id: PageId, payload: &[u8]) -> Result<Vec<u8>> {
+        assert!(
+            payload.len() <= RUSTY_DB_PAGE_PAYLOAD_BYTES,
+            "a page holds at most {RUSTY_DB_PAGE_PAYLOAD_BYTES} bytes, not {}",
+            payload.len()
+        );
+        let mut buffer = vec![0; RUSTY_DB_PAGE_SIZE_BYTES];
+        buffer[..payload.len()].copy_from_slice(payload);
+        let (body, rest) = buffer.split_at_mut(RUSTY_DB_PAGE_PAYLOAD_BYTES);
+        let (trailer, checksum) = rest.split_at_mut(RUSTY_DB_PAGE_TRAILER_BYTES);
+        let (counter, tag) = trailer.split_at_mut(size_of::<u64>());
+        let (file_id, page_no) = split_page_id(page_id);
+        match &self.cipher {
+            Some(cipher) if page_no == HEADER_PAGE_NO => {
+                if file_id == DEFAULT_TABLESPACE {
+                    counter.copy_from_slice(&self.counter_lease.to_le_bytes());
+                }
+                tag.copy_from_slice(&cipher.key_check());
+            }
+            Some(_) => {
+                let write_counter = self.next_write_counter()?;
+                let cipher = self.cipher.as_ref().unwrap();
+                tag.copy_from_slice(&cipher.encrypt(page_id, write_counter, body));
+                counter.copy_from_slice(&write_counter.to_le_bytes());
+            }
+            None => {}
+        }
+        let len = RUSTY_DB_PAGE_SIZE_BYTES - checksum.len();
+        let crc = crc32fast::hash(&buffer[..len]);
+        buffer[len..].copy_from_slice(&crc.to_le_bytes());
+        Ok(buffer)
+    }
+
+    /// Returns the write counter to encrypt the next page with, leasing more first, and syncing
+    /// the header they are recorded in, if those leased are used up.
+    fn next_write_counter(&mut self) -> Result<u64> {
+        if self.write_counter == self.counter_lease {
+            self.counter_lease += COUNTER_LEASE;
+            if let Err(error) = self.write_header(DEFAULT_TABLESPACE).and_then(|()| self.sync()) {
+                self.counter_lease -= COUNTER_LEASE;
+                return Err(error);
+            }
+        }
+        self.write_counter += 1;
+        Ok(self.write_counter)
+    }
+
+    /// Checks a page as read from disk against its checksum, then decrypts it, if pages are
+    /// encrypted, and returns its contents, [`RUSTY_DB_PAGE_PAYLOAD_BYTES`] long.
+    ///
+    /// # Errors
+    /// - [`Error::ShortRead`], [`Error::ChecksumMismatch`]: See [`Self::check_page`].
+    /// - [`Error::DecryptionFailed`]: If the page doesn't authenticate, e.g. as it was tampered
+    ///   with, checksum included, or was never written.
+    fn unseal_page(&self, page_id: &PageId, buffer: Vec<u8>) -> Result<Vec<u8>> {
+        let mut body = Self::check_page(page_id, buffer)?;
+        let trailer = body.split_off(RUSTY_DB_PAGE_PAYLOAD_BYTES);
+        let Some(cipher) = &self.cipher else {
+            return Ok(body);
+        };
+        if split_page_id(*page_id).1 == HEADER_PAGE_NO {
+            return Ok(body);
+        }
+        let (counter, tag) = trailer.split_at(size_of::<u64>());
+        let counter = u64::from_le_bytes(counter.try_into().unwrap());
+        let tag: &[u8; TAG_BYTES] = tag.try_into().unwrap();
+        cipher.decrypt(*page_id, counter, &mut body, tag)?;
+        Ok(body)
+    }
+
+    /// Writes a run of consecutive sealed pages of a file, starting at `page_id`, with a single
+    /// write.
+    fn write_run(&mut self, page_id: PageId, buffer: &[u8]) -> Result<()> {
+        let (file_id, page_no) = split_page_id(page_id);
+        let file = self.file(file_id)?;
+        file.backend.write_block(page_no, buffer)?;
+        file.unsynced = true;
+        let pages = buffer.len() / RUSTY_DB_PAGE_SIZE_BYTES;
+        self.metrics.disk_pages_written.add(pages as u64);
+        self.metrics.disk_writes.incr();
+        self.stats.pages_written += pages as u64;
+        self.stats.bytes_written += buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Forces the pages written since the last sync to stable storage, syncing each file written
+    /// to. Does nothing if there are none.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If a file can't be synced, in which case the next sync tries again.
+    pub fn sync(&mut self) -> Result<()> {
+        if !self.files.iter().any(|file| file.unsynced) {
+            return Ok(());
+        }
+        trace_span!("sync");
+        for file in self.files.iter_mut().filter(|file| file.unsynced) {
+            file.backend.sync()?;
+            file.unsynced = false;
+            self.metrics.disk_syncs.incr();
+        }
+        Ok(())
     }
 
-    pub fn read_page(&mut self, page_id: &PageId) -> TablePage {
-        let offset = Self::calculate_offset(page_id);
-        self.reader
-            .seek(SeekFrom::Start(offset as u64))
-            .expect("Unable to access offset {offset}.");
+    /// Appends raw bytes to the end of the write-ahead log. The bytes are not guaranteed to be
+    /// durable until [`Self::sync_log`] is called.
+    pub fn append_log(&mut self, bytes: &[u8]) -> Result<()> {
+        trace_span!("append_log", bytes = bytes.len());
+        let log = self.log_file()?;
+        log.seek(SeekFrom::End(0))?;
+        log.write_all(bytes)?;
+        self.metrics.disk_log_bytes_appended.add(bytes.len() as u64);
+        Ok(())
+    }
+
+    /// Forces previously appended log bytes to stable storage.
+    pub fn sync_log(&mut self) -> Result<()> {
+        trace_span!("sync_log");
+        self.log_file()?.sync_data()?;
+        self.metrics.disk_log_syncs.incr();
+        Ok(())
+    }
 
-        let mut buffer = [0; RUSTY_DB_PAGE_SIZE_BYTES];
-        self.reader
-            .read_exact(&mut buffer[..])
-            .expect("Unable to read page from disk.");
+    /// Reads the entire contents of the write-ahead log.
+    pub fn read_log(&mut self) -> Result<Vec<u8>> {
+        let log = self.log_file()?;
+        let mut bytes = Vec::new();
+        log.seek(SeekFrom::Start(0))?;
+        log.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
 
-        TablePage::deserialize(&buffer)
+    /// Returns the size of the write-ahead log in bytes.
+    pub fn log_size(&mut self) -> Result<u64> {
+        Ok(self.log_file()?.len()?)
     }
 
-    pub fn write_page(&mut self, page: TablePage) {
-        let page_id = page.page_id();
-        let offset = Self::calculate_offset(page_id);
-        let payload = page.serialize();
+    /// Discards the first `offset` bytes of the write-ahead log, which must fall on a record
+    /// boundary. A log with a path is rewritten to a temporary file that then replaces it, so a
+    /// crash midway leaves either the old or the new log behind.
+    pub fn truncate_log(&mut self, offset: u64) -> Result<()> {
+        let mut bytes = self.read_log()?;
+        let offset = usize::try_from(offset)?.min(bytes.len());
+        bytes.drain(..offset);
+
+        if self.log_path.as_os_str().is_empty() {
+            let log = self.log_file()?;
+            log.set_len(0)?;
+            log.seek(SeekFrom::Start(0))?;
+            log.write_all(&bytes)?;
+            log.sync_data()?;
+            return Ok(());
+        }
+        let temp_path = self.log_path.with_extension("wal.tmp");
+        let mut temp = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_path)?;
+        temp.write_all(&bytes)?;
+        temp.sync_data()?;
+        self.log = None;
+        std::fs::rename(&temp_path, &self.log_path)?;
+        Ok(())
+    }
 
-        self.writer
-            .seek(SeekFrom::Start(offset as u64))
-            .expect("Unable to access offset {offset}.");
-        self.writer
-            .write_all(&payload)
-            .expect("Unable to write payload to offset {offset}.");
-        self.writer
-            .flush()
-            .expect("Unable to flush buffer from write at offset {offset} to disk.");
+    /// Returns the LSN of the last completed checkpoint, if any. It is kept in the database
+    /// file's header, the first bytes of page 0, which is never allocated to a table.
+    pub fn checkpoint_lsn(&mut self) -> Result<Option<Lsn>> {
+        let lsn = self.checkpoint_lsn;
+        Ok((lsn != INVALID_LSN).then_some(lsn))
     }
 
-    fn calculate_offset(page_id: &PageId) -> u32 {
-        page_id * RUSTY_DB_PAGE_SIZE_BYTES as u32
+    /// Durably records `lsn` as the last completed checkpoint in the database file's header.
+    pub fn set_checkpoint_lsn(&mut self, lsn: Lsn) -> Result<()> {
+        let previous = std::mem::replace(&mut self.checkpoint_lsn, lsn);
+        if let Err(error) = self.write_header(DEFAULT_TABLESPACE) {
+            self.checkpoint_lsn = previous;
+            return Err(error);
+        }
+        self.sync()
     }
 
-    /// Increments the current value and returns the new value
-    /// # Returns
-    /// - `current_value` after the increment
-    fn increment_and_fetch_page_no(&mut self) -> u32 {
-        1 + self.current_page_no.fetch_add(1, Ordering::SeqCst)
+    /// Reads the free pages of tablespace `file_id` from its file's header, and for the database
+    /// file, the checkpoint LSN and the write counters leased too. The key the file is opened
+    /// with, if any, is checked against the header's key check, and the header of a new file is
+    /// written right away when encrypted, so that the file needs the same key from then on.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidInput`]: If the file is opened with another key than it was created
+    ///   with, or only one of them had a key.
+    fn read_header(&mut self, file_id: FileId) -> Result<()> {
+        let file = &mut self.files[file_id as usize];
+        let bytes = file.backend.read_block(HEADER_PAGE_NO, 1)?;
+        // A file too short to have the whole header never had it written.
+        if bytes.len() < RUSTY_DB_PAGE_SIZE_BYTES {
+            return match self.cipher {
+                Some(_) => self.write_header(file_id),
+                None => Ok(()),
+            };
+        }
+        let mut bytes = Self::check_page(&make_page_id(file_id, HEADER_PAGE_NO), bytes)?;
+        let trailer = bytes.split_off(RUSTY_DB_PAGE_PAYLOAD_BYTES);
+        let (lease, key_check) = trailer.split_at(size_of::<u64>());
+        let encrypted = key_check != [0; KEY_CHECK_BYTES];
+        let name = &file.name;
+        match &self.cipher {
+            None if encrypted => return errinput!("tablespace {name} is encrypted, and needs a key"),
+            Some(_) if !encrypted && file.backend.num_blocks() > 1 => {
+                return errinput!("tablespace {name} isn't encrypted");
+            }
+            Some(cipher) if encrypted && key_check != cipher.key_check() => {
+                return errinput!("wrong encryption key for tablespace {name}");
+            }
+            _ => {}
+        }
+        let (lsn, bitmap) = bytes.split_at(size_of::<Lsn>());
+        if file_id == DEFAULT_TABLESPACE {
+            self.checkpoint_lsn = Lsn::from_le_bytes(lsn.try_into().unwrap());
+            self.counter_lease = u64::from_le_bytes(lease.try_into().unwrap());
+            self.write_counter = self.counter_lease;
+        }
+        file.free_pages = (0..bitmap.len() * 8)
+            .filter(|page_no| bitmap[page_no / 8] & (1 << (page_no % 8)) != 0)
+            .map(|page_no| page_no as PageId)
+            .collect();
+        if self.cipher.is_some() && !encrypted {
+            self.write_header(file_id)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the free pages of tablespace `file_id` to its file's header, a page like any other,
+    /// so that it is checked against its checksum when read back. Only the database file's
+    /// header has the checkpoint LSN.
+    fn write_header(&mut self, file_id: FileId) -> Result<()> {
+        let mut bytes = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        let (lsn, bitmap) = bytes.split_at_mut(size_of::<Lsn>());
+        let checkpoint_lsn = match file_id {
+            DEFAULT_TABLESPACE => self.checkpoint_lsn,
+            _ => INVALID_LSN,
+        };
+        lsn.copy_from_slice(&checkpoint_lsn.to_le_bytes());
+        for page_no in &self.files[file_id as usize].free_pages {
+            bitmap[*page_no as usize / 8] |= 1 << (page_no % 8);
+        }
+        self.write_page_bytes(&make_page_id(file_id, HEADER_PAGE_NO), &bytes)
+    }
+
+    fn log_file(&mut self) -> Result<&mut DiskFile> {
+        if self.log.is_none() {
+            let log = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.log_path)?;
+            self.log = Some(log.into());
+        }
+        Ok(self.log.as_mut().unwrap())
+    }
+
+    /// Checks a page as read from disk against the CRC32 in its last 4 bytes, and returns the
+    /// bytes before them, its contents followed by its trailer. A page of zeros passes too, as
+    /// pages the file was extended past, but which were never written, read as such. A page the
+    /// file ends before is short.
+    fn check_page(page_id: &PageId, mut buffer: Vec<u8>) -> Result<Vec<u8>> {
+        if buffer.len() < RUSTY_DB_PAGE_SIZE_BYTES {
+            return Err(Error::ShortRead {
+                page_id: *page_id,
+                len: buffer.len(),
+            });
+        }
+        let stored = buffer.split_off(RUSTY_DB_PAGE_SIZE_BYTES - size_of::<u32>());
+        let expected = u32::from_le_bytes(stored.try_into().unwrap());
+        let actual = crc32fast::hash(&buffer);
+        if expected != actual && (expected != 0 || buffer.iter().any(|byte| *byte != 0)) {
+            return Err(Error::ChecksumMismatch {
+                page_id: *page_id,
+                expected,
+                actual,
+            });
+        }
+        Ok(buffer)
     }
 
     #[cfg(test)]
@@ -100,18 +1409,147 @@ impl DiskManager {
     pub fn new_for_test() -> Self {
         let temp_file =
             NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Unable to create temp file");
-        let writer = temp_file.reopen().expect("Unable to reopen temp file");
+        let log = tempfile::tempfile_in(RUST_DB_DATA_DIR).expect("Unable to create temp log file");
 
-        DiskManager {
-            current_page_no: AtomicU32::new(0),
-            writer: BufWriter::new(writer),
-            reader: BufReader::new(temp_file.into_file()),
-        }
+        let file = temp_file.into_file().into();
+        Self::with_files(file, PathBuf::new(), Some(log.into())).expect("Unable to open temp file")
+    }
+
+    #[cfg(test)]
+    /// Disk manager for the database file and log of a simulated disk.
+    pub(crate) fn new_simulated(disk: &SimDisk) -> Self {
+        let (file, log) = disk.files();
+        Self::with_files(file, PathBuf::new(), Some(log)).expect("Unable to open simulated disk")
+    }
+
+    #[cfg(test)]
+    /// Disk manager for the database file at `path`, opened read-only, so that every write to it
+    /// fails. Its log is a temporary file.
+    pub(crate) fn open_read_only_for_test(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let log = tempfile::tempfile_in(RUST_DB_DATA_DIR)?;
+        Self::with_files(file.into(), PathBuf::new(), Some(log.into()))
     }
 
     #[cfg(test)]
     /// Test-only version of `new_with_handle` that uses the test constructor.
     pub fn new_with_handle_for_test() -> Arc<RwLock<Self>> {
-        Arc::new(RwLock::new(Self::new_for_test()))
+        Self::new_for_test().into_handle()
+    }
+
+    #[cfg(test)]
+    /// Disk manager keeping its pages in `backend`, and its log in memory.
+    pub(crate) fn new_with_backend_for_test(backend: Box<dyn DiskBackend>) -> Self {
+        let log = DiskFile::Memory(Cursor::new(Vec::new()));
+        Self::with_backend(backend, PathBuf::new(), Some(log), None).expect("Unable to read header")
+    }
+}
+
+/// The thread started by [`DiskManager::into_handle`] for [`Durability::Interval`]. Dropping it
+/// stops the thread.
+#[derive(Debug)]
+struct BackgroundSyncer {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundSyncer {
+    /// Syncs the disk manager every `interval` until stopped. The disk manager is only held
+    /// weakly, as it holds the syncer.
+    fn spawn(disk_manager: Weak<RwLock<DiskManager>>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            let Some(disk_manager) = disk_manager.upgrade() else {
+                return;
+            };
+            let result = disk_manager.write().unwrap().sync();
+            if let Err(error) = result {
+                log::error!("disk manager: {error}");
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for BackgroundSyncer {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        // The disk manager is dropped by the syncer itself if it held the last reference, in
+        // which case the thread exits by itself, as the channel is closed.
+        if thread.thread().id() != thread::current().id() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for DiskManager {
+    /// Writes the pages still queued, see [`DiskManager::drain`], logging why if it can't.
+    fn drop(&mut self) {
+        if let Err(error) = self.drain() {
+            log::error!("disk manager: {error}");
+        }
+    }
+}
+
+/// The thread started by [`DiskManager::into_handle`] with async writes, writing the pages queued
+/// by [`DiskManager::write_page_async`]. Dropping it stops the thread.
+#[derive(Debug)]
+struct AsyncWriter {
+    queue: Option<SyncSender<(PageId, u64)>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncWriter {
+    /// Writes the pages queued, up to `capacity` of them waiting at a time, until stopped. Pages
+    /// queued while it writes are written along with the next one. The disk manager is only held
+    /// weakly, as it holds the writer.
+    fn spawn(disk_manager: Weak<RwLock<DiskManager>>, capacity: usize) -> Self {
+        let (queue, queued) = mpsc::sync_channel::<(PageId, u64)>(capacity);
+        let thread = thread::spawn(move || {
+            while let Ok(write) = queued.recv() {
+                let writes: Vec<_> = std::iter::once(write).chain(queued.try_iter()).collect();
+                let Some(disk_manager) = disk_manager.upgrade() else {
+                    return;
+                };
+                let result = disk_manager.write().unwrap().write_queued(&writes);
+                if let Err(error) = result {
+                    log::error!("disk manager: {error}");
+                }
+            }
+        });
+        Self {
+            queue: Some(queue),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues page `page_id`, as numbered `seq`, unless the queue is full or the thread gone, in
+    /// which case it returns false.
+    fn queue(&self, page_id: PageId, seq: u64) -> bool {
+        let queue = self.queue.as_ref().expect("queue is open until dropped");
+        queue.try_send((page_id, seq)).is_ok()
+    }
+}
+
+impl Drop for AsyncWriter {
+    fn drop(&mut self) {
+        drop(self.queue.take());
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        // Like for the syncer, the thread may hold the last reference to the disk manager.
+        if thread.thread().id() != thread::current().id() {
+            thread.join().ok();
+        }
     }
 }
@@ -0,0 +1,95 @@
+use crate::common::{Error, Result};
+use crate::storage::disk::disk_manager::PageId;
+use aes_gcm::aead::{AeadInPlace, Nonce};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Tag};
+use std::fmt::{self, Debug};
+
+/// The length of an encryption key, for AES-256.
+pub const KEY_BYTES: usize = 32;
+
+/// The length of a key-check value, see [`PageCipher::key_check`].
+pub const KEY_CHECK_BYTES: usize = 16;
+
+/// The length of the authentication tag of an encrypted page.
+pub const TAG_BYTES: usize = 16;
+
+/// What is encrypted for the key-check value.
+const KEY_CHECK_PLAINTEXT: &[u8; KEY_CHECK_BYTES] = b"rustydb-keycheck";
+
+/// Encrypts and authenticates pages with AES-256-GCM, for a disk manager opened with
+/// [`DiskManager::open_encrypted`]. The nonce of a page is its page ID followed by a counter,
+/// which the disk manager never uses twice for the same key, and keeps next to the ciphertext.
+///
+/// [`DiskManager::open_encrypted`]:
+/// crate::storage::disk::disk_manager::DiskManager::open_encrypted
+pub(crate) struct PageCipher {
+    cipher: Aes256Gcm,
+}
+
+impl PageCipher {
+    pub(crate) fn new(key: &[u8; KEY_BYTES]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    /// Returns a value derived from the key, for the header of a file to tell whether it is
+    /// opened with the key it was written with: a constant encrypted with the nonce of counter
+    /// 0, which no page is ever encrypted with.
+    pub(crate) fn key_check(&self) -> [u8; KEY_CHECK_BYTES] {
+        let mut buffer = *KEY_CHECK_PLAINTEXT;
+        let nonce = Self::nonce(0, 0);
+        self.cipher
+            .encrypt_in_place_detached(&nonce, &[], &mut buffer)
+            .expect("a constant is short enough to encrypt");
+        buffer
+    }
+
+    /// Encrypts the contents of page `page_id` in place, with write counter `counter`, and
+    /// returns the authentication tag.
+    pub(crate) fn encrypt(
+        &self,
+        page_id: PageId,
+        counter: u64,
+        body: &mut [u8],
+    ) -> [u8; TAG_BYTES] {
+        debug_assert_ne!(0, counter, "counter 0 is kept for the key check");
+        let nonce = Self::nonce(page_id, counter);
+        let tag = (self.cipher.encrypt_in_place_detached(&nonce, &[], body))
+            .expect("a page is short enough to encrypt");
+        tag.into()
+    }
+
+    /// Decrypts the contents of page `page_id` in place, as encrypted with write counter
+    /// `counter`, checking them against `tag`.
+    ///
+    /// # Errors
+    /// - [`Error::DecryptionFailed`]: If the page wasn't encrypted with this key, or was
+    ///   tampered with since.
+    pub(crate) fn decrypt(
+        &self,
+        page_id: PageId,
+        counter: u64,
+        body: &mut [u8],
+        tag: &[u8; TAG_BYTES],
+    ) -> Result<()> {
+        let nonce = Self::nonce(page_id, counter);
+        let tag = Tag::from_slice(tag);
+        (self.cipher.decrypt_in_place_detached(&nonce, &[], body, tag))
+            .map_err(|_| Error::DecryptionFailed(page_id))
+    }
+
+    fn nonce(page_id: PageId, counter: u64) -> Nonce<Aes256Gcm> {
+        let mut nonce = [0; 12];
+        nonce[..4].copy_from_slice(&page_id.to_le_bytes());
+        nonce[4..].copy_from_slice(&counter.to_le_bytes());
+        nonce.into()
+    }
+}
+
+/// Leaves the key out.
+impl Debug for PageCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageCipher").finish_non_exhaustive()
+    }
+}
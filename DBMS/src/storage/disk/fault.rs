@@ -0,0 +1,187 @@
+use crate::common::{Error, Result};
+use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
+use crate::storage::disk::backend::DiskBackend;
+use crate::storage::disk::disk_manager::PageId;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Programs the faults of the backend of a [`DiskManager`], to test how the code above it copes
+/// with a disk that fails: writes that fail, writes torn halfway, and power cuts that lose the
+/// writes not synced yet. A handle, to keep while the disk manager holds a clone, see
+/// [`DiskManager::with_fault_injector`]. Writes are numbered from 1 as the backend sees them,
+/// one for each run of pages the disk manager writes at once.
+///
+/// [`DiskManager`]: crate::storage::disk::disk_manager::DiskManager
+/// [`DiskManager::with_fault_injector`]:
+/// crate::storage::disk::disk_manager::DiskManager::with_fault_injector
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjector {
+    state: Arc<Mutex<FaultState>>,
+}
+
+#[derive(Debug, Default)]
+struct FaultState {
+    /// The backend the faults are injected into, once [`FaultInjector::wrap`] was given it.
+    backend: Option<Box<dyn DiskBackend>>,
+    /// The number of writes so far.
+    writes: usize,
+    /// The number of the write to fail, if any.
+    fail_at: Option<usize>,
+    /// The number of the write to tear, if any.
+    tear_at: Option<usize>,
+    /// The blocks written since the last sync, as they were before, oldest first, for a power
+    /// cut to put back.
+    unsynced: Vec<(PageId, Vec<u8>)>,
+    /// Whether the power was cut, after which nothing written reaches the disk.
+    powered_off: bool,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the `n`th write from now, 1 for the next one, with an [`Error::IO`], writing
+    /// nothing.
+    pub fn fail_nth_write(&self, n: usize) {
+        let mut state = self.state();
+        state.fail_at = Some(state.writes + n);
+    }
+
+    /// Tears the `n`th write from now, 1 for the next one: only the first half of its bytes
+    /// reach the disk, the rest left as they were, and the write succeeds, as if the power went
+    /// out midway through it.
+    pub fn tear_nth_write(&self, n: usize) {
+        let mut state = self.state();
+        state.tear_at = Some(state.writes + n);
+    }
+
+    /// Cuts the power: the blocks written since the last sync are put back as they were, and
+    /// the writes and syncs from now on succeed without reaching the disk, so that reads see
+    /// what was last synced. A database file reopened afterwards is as a crash would leave it.
+    ///
+    /// # Errors
+    /// - [`Error::IO`]: If the blocks can't be put back.
+    pub fn cut_power(&self) -> Result<()> {
+        let mut state = self.state();
+        state.powered_off = true;
+        let unsynced = std::mem::take(&mut state.unsynced);
+        let backend = state.backend.as_mut().expect("no backend to cut the power of");
+        for (block_id, bytes) in unsynced.into_iter().rev() {
+            backend.write_block(block_id, &bytes)?;
+        }
+        backend.sync()
+    }
+
+    /// Returns the number of writes so far.
+    pub fn writes(&self) -> usize {
+        self.state().writes
+    }
+
+    /// Returns a backend injecting the faults programmed into `backend`.
+    pub(crate) fn wrap(&self, backend: Box<dyn DiskBackend>) -> Box<dyn DiskBackend> {
+        self.state().backend = Some(backend);
+        Box::new(FaultyBackend {
+            injector: self.clone(),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, FaultState> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// The backend [`FaultInjector::wrap`] returns, sharing its state with the injector.
+#[derive(Debug)]
+struct FaultyBackend {
+    injector: FaultInjector,
+}
+
+impl FaultyBackend {
+    /// Calls `f` with the backend the faults are injected into.
+    fn with_backend<T>(&self, f: impl FnOnce(&mut dyn DiskBackend) -> T) -> T {
+        let mut state = self.injector.state();
+        f(state.backend.as_deref_mut().expect("wrapped a backend"))
+    }
+}
+
+impl DiskBackend for FaultyBackend {
+    fn read_block(&mut self, block_id: PageId, blocks: usize) -> Result<Vec<u8>> {
+        self.with_backend(|backend| backend.read_block(block_id, blocks))
+    }
+
+    fn write_block(&mut self, block_id: PageId, bytes: &[u8]) -> Result<()> {
+        let mut guard = self.injector.state();
+        let state = &mut *guard;
+        state.writes += 1;
+        let write = state.writes;
+        if state.powered_off {
+            return Ok(());
+        }
+        if state.fail_at == Some(write) {
+            return Err(Error::IO(format!("injected failure of write {write}")));
+        }
+        let backend = state.backend.as_mut().expect("wrapped a backend");
+        let blocks = bytes.len().div_ceil(RUSTY_DB_PAGE_SIZE_BYTES);
+        // Blocks past the end of the storage are put back as zeros, as if never written.
+        let mut before = backend.read_block(block_id, blocks)?;
+        before.resize(bytes.len(), 0);
+        let mut after = bytes.to_vec();
+        if state.tear_at == Some(write) {
+            let half = bytes.len() / 2;
+            after[half..].copy_from_slice(&before[half..]);
+        }
+        backend.write_block(block_id, &after)?;
+        state.unsynced.push((block_id, before));
+        Ok(())
+    }
+
+    fn allocate(&mut self) -> Result<PageId> {
+        self.with_backend(|backend| backend.allocate())
+    }
+
+    fn reserve(&mut self, blocks: PageId) -> Result<()> {
+        self.with_backend(|backend| backend.reserve(blocks))
+    }
+
+    fn deallocate(&mut self, block_id: PageId) -> Result<()> {
+        let mut guard = self.injector.state();
+        let state = &mut *guard;
+        if state.powered_off {
+            return Ok(());
+        }
+        let backend = state.backend.as_mut().expect("wrapped a backend");
+        let before = backend.read_block(block_id, 1)?;
+        backend.deallocate(block_id)?;
+        state.unsynced.push((block_id, before));
+        Ok(())
+    }
+
+    /// Truncates whether the power is cut or not, and for good: a power cut doesn't put back the
+    /// blocks dropped.
+    fn truncate(&mut self, blocks: PageId) -> Result<()> {
+        self.with_backend(|backend| backend.truncate(blocks))
+    }
+
+    /// Lies once the power is cut, syncing nothing.
+    fn sync(&mut self) -> Result<()> {
+        let mut state = self.injector.state();
+        if state.powered_off {
+            return Ok(());
+        }
+        state.backend.as_mut().expect("wrapped a backend").sync()?;
+        state.unsynced.clear();
+        Ok(())
+    }
+
+    fn map_reads(&mut self) -> bool {
+        self.with_backend(|backend| backend.map_reads())
+    }
+
+    fn num_blocks(&self) -> PageId {
+        self.with_backend(|backend| backend.num_blocks())
+    }
+
+    fn reserved_blocks(&self) -> PageId {
+        self.with_backend(|backend| backend.reserved_blocks())
+    }
+}
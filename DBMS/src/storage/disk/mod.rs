@@ -1,3 +1,8 @@
+pub mod backend;
+pub(crate) mod disk_file;
 pub mod disk_manager;
+pub mod encryption;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
 #[cfg(test)]
 mod tests;
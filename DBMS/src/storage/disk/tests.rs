@@ -1,9 +1,28 @@
-use crate::config::config::RUST_DB_DATA_DIR;
-use crate::storage::disk::disk_manager::DiskManager;
-use crate::storage::page::{Page, RecordId, TablePage};
+use crate::common::Error;
+use crate::config::config::{
+    RUSTY_DB_PAGE_PAYLOAD_BYTES, RUSTY_DB_PAGE_SIZE_BYTES, RUST_DB_DATA_DIR,
+};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::backend::{CountingBackend, DiskBackend, FileBackend};
+use crate::storage::disk::disk_manager::{
+    make_page_id, split_page_id, DiskManager, Durability, ExtentConfig, PageId,
+    DEFAULT_TABLESPACE,
+};
+use crate::storage::disk::encryption::KEY_BYTES;
+use crate::storage::disk::fault::FaultInjector;
+use crate::storage::page::{
+    BPlusTreeLeafPage, KeySchema, OverflowPage, Page, RecordId, TablePage,
+};
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::types::field::Field;
+use crate::types::DataType;
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock};
-use tempfile::NamedTempFile;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::{NamedTempFile, TempDir};
 
 #[test]
 fn test_write_and_read_page() {
@@ -11,7 +30,7 @@ fn test_write_and_read_page() {
 
     let page_id = {
         let mut dm = disk_manager.write().unwrap();
-        dm.allocate_new_page()
+        dm.allocate_new_page().unwrap()
     };
 
     let mut page = TablePage::builder().page_id(page_id).build();
@@ -26,12 +45,12 @@ fn test_write_and_read_page() {
 
     {
         let mut dm = disk_manager.write().unwrap();
-        dm.write_page(page.clone());
+        dm.write_page(page.clone()).unwrap();
     }
 
     let read_page = {
         let mut dm = disk_manager.write().unwrap();
-        dm.read_page(&page_id)
+        dm.read_page(&page_id).unwrap()
     };
 
     let retrieved_tuple = read_page
@@ -66,14 +85,14 @@ fn test_persistent_storage() {
     {
         let disk_manager = DiskManager::new_with_handle(&file_name);
         let mut dm = disk_manager.write().unwrap();
-        page_id = dm.allocate_new_page();
+        page_id = dm.allocate_new_page().unwrap();
 
         let mut page = TablePage::builder().page_id(page_id).build();
 
         page.insert_tuple(tuple_metadata, tuple.clone())
             .expect("Failed to insert tuple");
 
-        dm.write_page(page.clone());
+        dm.write_page(page.clone()).unwrap();
         // `DiskManager` goes out of scope and file is closed.
     }
 
@@ -82,7 +101,7 @@ fn test_persistent_storage() {
         let disk_manager = DiskManager::new_with_handle(&file_name);
         let read_page = {
             let mut dm = disk_manager.write().unwrap();
-            dm.read_page(&page_id)
+            dm.read_page(&page_id).unwrap()
         };
 
         assert_eq!(
@@ -104,6 +123,39 @@ fn test_persistent_storage() {
     }
 }
 
+/// Test that pages read ahead are served from memory, and never stale.
+#[test]
+fn test_read_ahead() {
+    let disk_manager = new_disk_manager();
+    let mut dm = disk_manager.write().unwrap();
+    let write = |dm: &mut DiskManager, page_id, text: &str| {
+        let mut page = TablePage::builder().page_id(page_id).build();
+        page.insert_tuple(TupleMetadata::new(false), Tuple::from(text.as_bytes()))
+            .expect("Failed to insert tuple");
+        dm.write_page(page).unwrap();
+    };
+    let read = |dm: &mut DiskManager, page_id| {
+        let page = dm.read_page(&page_id).unwrap();
+        page.get_tuple(&RecordId::new(page_id, 0))
+            .expect("Failed to retrieve tuple")
+    };
+    let page_ids: Vec<_> = (0..5).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write(&mut dm, page_id, &format!("Page number {page_id}"));
+    }
+
+    // Two runs of consecutive pages, one of which is overwritten before it is read.
+    dm.read_ahead(&[page_ids[4], page_ids[0], page_ids[1], page_ids[2]]);
+    write(&mut dm, page_ids[1], "Rewritten");
+    assert_eq!(Tuple::from(&b"Rewritten"[..]), read(&mut dm, page_ids[1]));
+    for &page_id in &page_ids {
+        let expected = format!("Page number {page_id}");
+        if page_id != page_ids[1] {
+            assert_eq!(Tuple::from(expected.as_bytes()), read(&mut dm, page_id));
+        }
+    }
+}
+
 /// Test writing and reading multiple pages to ensure each page maintains its own data.
 #[test]
 fn test_multiple_page_write_and_read() {
@@ -118,7 +170,7 @@ fn test_multiple_page_write_and_read() {
     for _ in 0..num_pages {
         let page_id = {
             let mut dm = disk_manager.write().unwrap();
-            dm.allocate_new_page()
+            dm.allocate_new_page().unwrap()
         };
         page_ids.push(page_id);
 
@@ -136,7 +188,7 @@ fn test_multiple_page_write_and_read() {
         // Write the updated page to disk.
         {
             let mut dm = disk_manager.write().unwrap();
-            dm.write_page(page.clone())
+            dm.write_page(page.clone()).unwrap()
         }
     }
 
@@ -144,7 +196,7 @@ fn test_multiple_page_write_and_read() {
     for &page_id in &page_ids {
         let read_page = {
             let mut dm = disk_manager.write().unwrap();
-            dm.read_page(&page_id)
+            dm.read_page(&page_id).unwrap()
         };
 
         let record_id = RecordId::new(page_id, 0);
@@ -167,6 +219,978 @@ fn test_multiple_page_write_and_read() {
     }
 }
 
+/// Test that a page corrupted on disk fails its checksum, read directly or ahead of time.
+#[test]
+fn test_checksum_mismatch() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    let page_ids: Vec<_> = (0..2).map(|_| dm.allocate_new_page().unwrap()).collect();
+    let mut page = TablePage::builder().page_id(page_ids[0]).build();
+    page.insert_tuple(TupleMetadata::new(false), Tuple::from(&b"Checked"[..]))
+        .expect("Failed to insert tuple");
+    dm.write_page(page).unwrap();
+    assert!(dm.read_page(&page_ids[0]).is_ok());
+
+    // Flip a byte of the tuple, at the end of the page's payload.
+    let file = OpenOptions::new().read(true).write(true).open(temp_file.path()).unwrap();
+    let page_offset = page_ids[0] as usize * RUSTY_DB_PAGE_SIZE_BYTES;
+    let offset = (page_offset + RUSTY_DB_PAGE_PAYLOAD_BYTES - 1) as u64;
+    let mut byte = [0];
+    file.read_exact_at(&mut byte, offset).unwrap();
+    file.write_all_at(&[byte[0] ^ 0xff], offset).unwrap();
+
+    let Err(Error::ChecksumMismatch { page_id, expected, actual }) = dm.read_page(&page_ids[0])
+    else {
+        panic!("Expected a checksum mismatch");
+    };
+    assert_eq!(page_ids[0], page_id);
+    assert_ne!(expected, actual);
+
+    dm.read_ahead(&page_ids);
+    let mut read = dm.read_pages(&page_ids).into_iter();
+    assert!(matches!(read.next(), Some(Err(Error::ChecksumMismatch { .. }))));
+    assert!(matches!(read.next(), Some(Ok(_))));
+}
+
+/// Test that a page filled up to the last byte of its payload reads back whole.
+#[test]
+fn test_full_page_round_trip() {
+    let disk_manager = new_disk_manager();
+    let mut dm = disk_manager.write().unwrap();
+    let page_id = dm.allocate_new_page().unwrap();
+    let (page, tuples) = full_page(page_id);
+    dm.write_page(page).unwrap();
+    assert_full_page(&dm.read_page(&page_id).unwrap(), tuples);
+}
+
+/// Returns a page filled up to the last byte of its payload, and the tuples it holds.
+fn full_page(page_id: PageId) -> (TablePage, Vec<Tuple>) {
+    let mut page = TablePage::builder().page_id(page_id).build();
+    let mut tuples = Vec::new();
+    while page.free_space() >= TablePage::SLOT_BYTES {
+        let size = (page.free_space() - TablePage::SLOT_BYTES).min(100);
+        let tuple = Tuple::from(&vec![tuples.len() as u8; size][..]);
+        page.insert_tuple(TupleMetadata::new(false), tuple.clone())
+            .expect("Failed to insert tuple");
+        tuples.push(tuple);
+    }
+    assert_eq!(RUSTY_DB_PAGE_PAYLOAD_BYTES, page.serialize().len());
+    (page, tuples)
+}
+
+fn assert_full_page(page: &TablePage, tuples: Vec<Tuple>) {
+    for (slot_id, tuple) in tuples.into_iter().enumerate() {
+        let record_id = RecordId::new(page.page_id, slot_id as u16);
+        assert_eq!(tuple, page.get_tuple(&record_id).unwrap());
+    }
+}
+
+const KEY: [u8; KEY_BYTES] = [42; KEY_BYTES];
+
+/// Pages of an encrypted database file read back whole once it is opened again with its key, and
+/// can't be read from the file itself.
+#[test]
+fn test_encryption_round_trip() {
+    let dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let db_path = dir.path().join("test.db");
+    let (page_ids, tuples) = {
+        let mut dm = DiskManager::open_encrypted(&db_path, &KEY).unwrap();
+        let page_ids: Vec<_> = (0..2).map(|_| dm.allocate_new_page().unwrap()).collect();
+        let (page, tuples) = full_page(page_ids[0]);
+        dm.write_page(page).unwrap();
+        write_text(&mut dm, page_ids[1], "Top secret");
+        assert_full_page(&dm.read_page(&page_ids[0]).unwrap(), tuples.clone());
+        (page_ids, tuples)
+    };
+    let file = std::fs::read(&db_path).unwrap();
+    assert!(!file.windows(10).any(|bytes| bytes == b"Top secret"));
+
+    let mut dm = DiskManager::open_encrypted(&db_path, &KEY).unwrap();
+    let mut read = dm.read_pages(&page_ids).into_iter();
+    assert_full_page(&read.next().unwrap().unwrap(), tuples);
+    assert_eq!(Tuple::from(&b"Top secret"[..]), text_of(&read.next().unwrap().unwrap()));
+}
+
+/// A database file opened with another key than it was created with, or only one of them with a
+/// key, fails to open, rather than reading garbage.
+#[test]
+fn test_encryption_key_checked_at_open() {
+    let dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let encrypted_path = dir.path().join("encrypted.db");
+    let plain_path = dir.path().join("plain.db");
+    for (path, key) in [(&encrypted_path, Some(&KEY)), (&plain_path, None)] {
+        let mut dm = match key {
+            Some(key) => DiskManager::open_encrypted(path, key).unwrap(),
+            None => DiskManager::open(path).unwrap(),
+        };
+        let page_id = dm.allocate_new_page().unwrap();
+        write_text(&mut dm, page_id, "Keyed");
+    }
+
+    let wrong_key = [7; KEY_BYTES];
+    let open = |path, key: Option<&[u8; KEY_BYTES]>| match key {
+        Some(key) => DiskManager::open_encrypted(path, key).map(|_| ()),
+        None => DiskManager::open(path).map(|_| ()),
+    };
+    assert!(matches!(open(&encrypted_path, Some(&wrong_key)), Err(Error::InvalidInput(_))));
+    assert!(matches!(open(&encrypted_path, None), Err(Error::InvalidInput(_))));
+    assert!(matches!(open(&plain_path, Some(&KEY)), Err(Error::InvalidInput(_))));
+    assert_eq!(Ok(()), open(&encrypted_path, Some(&KEY)));
+    assert_eq!(Ok(()), open(&plain_path, None));
+}
+
+/// A page of an encrypted file that is corrupted fails its checksum, and one tampered with,
+/// checksum included, or moved to another page, fails to decrypt.
+#[test]
+fn test_encryption_detects_tampering() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open_encrypted(temp_file.path(), &KEY).unwrap();
+    let page_ids: Vec<_> = (0..2).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, "Authentic");
+    }
+    let file = OpenOptions::new().read(true).write(true).open(temp_file.path()).unwrap();
+    let offset = |page_id: PageId| page_id as u64 * RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let mut block = vec![0; RUSTY_DB_PAGE_SIZE_BYTES];
+    file.read_exact_at(&mut block, offset(page_ids[0])).unwrap();
+
+    // Flip a bit of the ciphertext.
+    block[RUSTY_DB_PAGE_PAYLOAD_BYTES - 1] ^= 1;
+    file.write_all_at(&block, offset(page_ids[0])).unwrap();
+    let read = dm.read_page(&page_ids[0]);
+    assert!(matches!(read, Err(Error::ChecksumMismatch { .. })), "{read:?}");
+
+    // Then fix up the checksum.
+    let (bytes, checksum) = block.split_at_mut(RUSTY_DB_PAGE_SIZE_BYTES - 4);
+    checksum.copy_from_slice(&crc32fast::hash(bytes).to_le_bytes());
+    file.write_all_at(&block, offset(page_ids[0])).unwrap();
+    assert_eq!(Err(Error::DecryptionFailed(page_ids[0])), dm.read_page(&page_ids[0]).map(|_| ()));
+
+    // Copy the second page, intact, over the first.
+    file.read_exact_at(&mut block, offset(page_ids[1])).unwrap();
+    file.write_all_at(&block, offset(page_ids[0])).unwrap();
+    assert_eq!(Err(Error::DecryptionFailed(page_ids[0])), dm.read_page(&page_ids[0]).map(|_| ()));
+    assert_eq!(Tuple::from(&b"Authentic"[..]), text_of(&dm.read_page(&page_ids[1]).unwrap()));
+}
+
+/// Test that the statistics count the pages allocated and freed, and those read and written.
+#[test]
+fn test_disk_stats() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let page_size = RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    let page_ids: Vec<_> = (0..4).map(|_| dm.allocate_new_page().unwrap()).collect();
+    let allocated = dm.stats();
+    assert_eq!(4, allocated.allocated_pages);
+    assert_eq!(0, allocated.free_pages);
+    assert_eq!(64 * page_size, allocated.file_bytes);
+
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, "Counted");
+    }
+    for page_id in &page_ids[..3] {
+        dm.read_page(page_id).unwrap();
+    }
+    let stats = dm.stats();
+    assert_eq!(allocated.pages_written + 4, stats.pages_written);
+    assert_eq!(allocated.bytes_written + 4 * page_size, stats.bytes_written);
+    assert_eq!(allocated.pages_read + 3, stats.pages_read);
+    assert_eq!(allocated.bytes_read + 3 * page_size, stats.bytes_read);
+
+    // Deallocating a page rewrites the header listing it as free.
+    dm.deallocate_page(&page_ids[3]).unwrap();
+    let freed = dm.stats();
+    assert_eq!(3, freed.allocated_pages);
+    assert_eq!(1, freed.free_pages);
+    assert_eq!(stats.pages_written + 1, freed.pages_written);
+    assert_eq!(temp_file.as_file().metadata().unwrap().len(), freed.file_bytes);
+}
+
+/// Test that deallocated pages are allocated again before the file grows.
+#[test]
+fn test_reuse_deallocated_pages() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    let page_ids: Vec<_> = (0..8).map(|_| dm.allocate_new_page().unwrap()).collect();
+    let file_size = temp_file.as_file().metadata().unwrap().len();
+
+    let freed: Vec<_> = page_ids.iter().copied().step_by(2).collect();
+    for page_id in &freed {
+        dm.deallocate_page(page_id).unwrap();
+    }
+    // Deallocating twice, or the header, changes nothing.
+    dm.deallocate_page(&freed[0]).unwrap();
+    dm.deallocate_page(&0).unwrap();
+    assert_eq!(freed, dm.free_pages().collect::<Vec<_>>());
+
+    let reused: Vec<_> = (0..freed.len()).map(|_| dm.allocate_new_page().unwrap()).collect();
+    assert_eq!(freed, reused);
+    assert_eq!(0, dm.free_pages().count());
+    assert_eq!(file_size, temp_file.as_file().metadata().unwrap().len());
+
+    // A page reused is empty, and the file grows again once no page is free.
+    assert_eq!(0, dm.read_page(&freed[0]).unwrap().tuple_count());
+    assert_eq!(page_ids[7] + 1, dm.allocate_new_page().unwrap());
+}
+
+/// Test that freeing a chain of pages at once writes the header once, and that the pages are
+/// reused, in a run, once freed.
+#[test]
+fn test_deallocate_pages() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    let chain = dm.allocate_contiguous(1000).unwrap();
+    assert_eq!((1..1001).collect::<Vec<_>>(), chain);
+
+    let before = dm.stats();
+    dm.deallocate_pages(&chain).unwrap();
+    assert_eq!(1000, dm.free_pages().count());
+    assert_eq!(before.pages_written + 1, dm.stats().pages_written);
+    drop(dm);
+
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(chain, dm.free_pages().collect::<Vec<_>>());
+    assert_eq!(chain, dm.allocate_contiguous(1000).unwrap());
+    assert_eq!(0, dm.free_pages().count());
+    assert_eq!(0, dm.read_page(&chain[999]).unwrap().tuple_count());
+}
+
+/// Test that pages allocated together take the lowest run of free pages long enough, or grow
+/// the file.
+#[test]
+fn test_allocate_contiguous() {
+    let mut dm = DiskManager::new_in_memory();
+    let page_ids = dm.allocate_contiguous(6).unwrap();
+    dm.deallocate_pages(&[page_ids[1], page_ids[3], page_ids[4], page_ids[5]]).unwrap();
+    assert_eq!(vec![4, 5, 6], dm.allocate_contiguous(3).unwrap());
+    assert_eq!(vec![7, 8], dm.allocate_contiguous(2).unwrap());
+    assert_eq!(vec![2], dm.free_pages().collect::<Vec<_>>());
+    assert!(dm.allocate_contiguous(0).unwrap().is_empty());
+}
+
+/// Test that the free pages, and the checkpoint LSN kept with them, survive reopening the file.
+#[test]
+fn test_free_pages_survive_reopen() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let freed = {
+        let mut dm = DiskManager::open(temp_file.path()).unwrap();
+        let page_ids: Vec<_> = (0..6).map(|_| dm.allocate_new_page().unwrap()).collect();
+        dm.set_checkpoint_lsn(42).unwrap();
+        let freed = vec![page_ids[1], page_ids[4]];
+        for page_id in &freed {
+            dm.deallocate_page(page_id).unwrap();
+        }
+        freed
+    };
+
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(freed, dm.free_pages().collect::<Vec<_>>());
+    assert_eq!(Some(42), dm.checkpoint_lsn().unwrap());
+    assert_eq!(freed[0], dm.allocate_new_page().unwrap());
+
+    let dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(vec![freed[1]], dm.free_pages().collect::<Vec<_>>());
+}
+
+/// Test that the file grows an extent at a time, with the pages past the last one allocated
+/// reading as past the end, before and after reopening the file.
+#[test]
+fn test_allocate_in_extents() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let page_size = RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let file_size = || temp_file.as_file().metadata().unwrap().len();
+    let extents = ExtentConfig {
+        pages_per_extent: 8,
+    };
+    let mut dm = DiskManager::open(temp_file.path()).unwrap().with_extents(extents);
+    let page_ids: Vec<_> = (0..7).map(|_| dm.allocate_new_page().unwrap()).collect();
+    assert_eq!(8 * page_size, file_size());
+    assert_eq!(8, dm.num_pages());
+
+    // The next page is past the extent, and the file grows by exactly one more.
+    assert_eq!(8, dm.allocate_new_page().unwrap());
+    assert_eq!(16 * page_size, file_size());
+    assert_eq!(9, dm.num_pages());
+    assert_eq!(Err(Error::PageNotAllocated(9)), dm.read_page(&9).map(|_| ()));
+    let file = std::fs::read(temp_file.path()).unwrap();
+    assert!(file[9 * page_size as usize..].iter().all(|byte| *byte == 0));
+    drop(dm);
+
+    let mut dm = DiskManager::open(temp_file.path()).unwrap().with_extents(extents);
+    assert_eq!(9, dm.num_pages());
+    assert_eq!(0, dm.read_page(&page_ids[3]).unwrap().tuple_count());
+    assert_eq!(9, dm.allocate_new_page().unwrap());
+    assert_eq!(16 * page_size, file_size());
+}
+
+/// Test that a block allocated in the room reserved for it reads as zeros until written.
+#[test]
+fn test_reserved_blocks_read_as_zeros() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut backend = FileBackend::new(temp_file.reopen().unwrap().into()).unwrap();
+    backend.reserve(4).unwrap();
+    assert_eq!(1, backend.num_blocks());
+    let block_id = backend.allocate().unwrap();
+    assert_eq!(vec![0; RUSTY_DB_PAGE_SIZE_BYTES], backend.read_block(block_id, 1).unwrap());
+    // The blocks past it read as past the end.
+    assert_eq!(2 * RUSTY_DB_PAGE_SIZE_BYTES, backend.read_block(0, 8).unwrap().len());
+}
+
+/// Test that compacting a fragmented file shrinks it, extents reserved included, and that the
+/// pages moved are at their new IDs once it is reopened.
+#[test]
+fn test_compact_shrinks_file() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let page_size = RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let file_size = || temp_file.as_file().metadata().unwrap().len();
+    let mut moves = Vec::new();
+    {
+        let mut dm = DiskManager::open(temp_file.path()).unwrap();
+        let page_ids: Vec<_> = (0..10).map(|_| dm.allocate_new_page().unwrap()).collect();
+        for &page_id in &page_ids {
+            write_text(&mut dm, page_id, &format!("Page number {page_id}"));
+        }
+        for page_id in page_ids.iter().step_by(2) {
+            dm.deallocate_page(page_id).unwrap();
+        }
+        assert_eq!(64 * page_size, file_size());
+        assert_eq!(5, dm.compact(&mut |from, to| moves.push((from, to))).unwrap());
+        assert_eq!(6 * page_size, file_size());
+    }
+
+    // Each page moved from a page allocated to one that was free.
+    assert_eq!(vec![(10, 1), (8, 3), (6, 5)], moves);
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(6, dm.num_pages());
+    assert_eq!(0, dm.free_pages().count());
+    for (page_id, was) in [(1, 10), (2, 2), (3, 8), (4, 4), (5, 6)] {
+        let expected = Tuple::from(format!("Page number {was}").as_bytes());
+        assert_eq!(expected, read_text(&mut dm, page_id));
+    }
+}
+
+/// Test that compacting rewrites the ID each page moved holds of itself, whatever its kind, so
+/// that it reads, and is written back, at its new ID.
+#[test]
+fn test_compact_rewrites_page_ids() {
+    let mut dm = DiskManager::new_in_memory();
+    let page_ids: Vec<_> = (0..6).map(|_| dm.allocate_new_page().unwrap()).collect();
+    assert_eq!(vec![1, 2, 3, 4, 5, 6], page_ids);
+    write_text(&mut dm, 4, "Table");
+    let mut leaf = BPlusTreeLeafPage::builder()
+        .page_id(5)
+        .key_schema(KeySchema::for_type(DataType::Int))
+        .build();
+    leaf.insert(Field::Integer(7), RecordId::new(3, 0)).unwrap();
+    dm.write_page(leaf).unwrap();
+    let mut overflow = OverflowPage::new(6);
+    overflow.set_data(b"Overflow");
+    dm.write_page(overflow).unwrap();
+    for page_id in 1..=3 {
+        dm.deallocate_page(&page_id).unwrap();
+    }
+
+    let mut moves = Vec::new();
+    assert_eq!(3, dm.compact(&mut |from, to| moves.push((from, to))).unwrap());
+    assert_eq!(vec![(6, 1), (5, 2), (4, 3)], moves);
+    let overflow = OverflowPage::deserialize(&dm.read_page_bytes(&1).unwrap());
+    assert_eq!((1, &b"Overflow"[..]), (*overflow.page_id(), overflow.data()));
+    let leaf = BPlusTreeLeafPage::deserialize(&dm.read_page_bytes(&2).unwrap());
+    assert_eq!(2, *leaf.page_id());
+    assert_eq!(1, leaf.size());
+    assert_eq!(Tuple::from(&b"Table"[..]), read_text(&mut dm, 3));
+
+    // Written back, the page goes to its new ID, not to the old one, now past the end.
+    let mut page = dm.read_page(&3).unwrap();
+    page.insert_tuple(TupleMetadata::new(false), Tuple::from(&b"More"[..])).unwrap();
+    dm.write_page(page).unwrap();
+    let page = dm.read_page(&3).unwrap();
+    assert_eq!(Tuple::from(&b"More"[..]), page.get_tuple(&RecordId::new(3, 1)).unwrap());
+    assert_eq!(Err(Error::PageNotAllocated(4)), dm.read_page(&4).map(|_| ()));
+}
+
+/// Test that pages read through a map of the file see the writes made since, including to pages
+/// past the end of the map and after the file shrank.
+#[test]
+fn test_mmap_reads_see_writes() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open(temp_file.path())
+        .unwrap()
+        .with_extents(ExtentConfig { pages_per_extent: 1 })
+        .with_mmap_reads();
+    assert!(dm.mmap_reads());
+    let page_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page().unwrap()).collect();
+    write_text(&mut dm, page_ids[0], "First");
+    assert_eq!(Tuple::from(&b"First"[..]), text_of(&dm.read_page(&page_ids[0]).unwrap()));
+    write_text(&mut dm, page_ids[0], "Second");
+    assert_eq!(Tuple::from(&b"Second"[..]), text_of(&dm.read_page(&page_ids[0]).unwrap()));
+
+    // The file grows past the end of the map.
+    let page_id = dm.allocate_new_page().unwrap();
+    write_text(&mut dm, page_id, "Grown");
+    assert_eq!(Tuple::from(&b"Grown"[..]), text_of(&dm.read_page(&page_id).unwrap()));
+
+    // And shrinks, the last page moved into the first free one.
+    dm.deallocate_page(&page_ids[1]).unwrap();
+    assert_eq!(1, dm.compact(&mut |_, _| {}).unwrap());
+    assert_eq!(Tuple::from(&b"Grown"[..]), read_text(&mut dm, page_ids[1]));
+    write_text(&mut dm, page_ids[2], "Last");
+    assert_eq!(Tuple::from(&b"Last"[..]), text_of(&dm.read_page(&page_ids[2]).unwrap()));
+}
+
+/// Test that a page corrupted on disk fails its checksum read through a map of the file too.
+#[test]
+fn test_mmap_reads_checksum_mismatch() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let mut dm = DiskManager::open(temp_file.path()).unwrap().with_mmap_reads();
+    let page_id = dm.allocate_new_page().unwrap();
+    write_text(&mut dm, page_id, "Checked");
+    assert!(dm.read_page(&page_id).is_ok());
+
+    let file = OpenOptions::new().read(true).write(true).open(temp_file.path()).unwrap();
+    let page_offset = page_id as usize * RUSTY_DB_PAGE_SIZE_BYTES;
+    let offset = (page_offset + RUSTY_DB_PAGE_PAYLOAD_BYTES - 1) as u64;
+    let mut byte = [0];
+    file.read_exact_at(&mut byte, offset).unwrap();
+    file.write_all_at(&[byte[0] ^ 0xff], offset).unwrap();
+    let read = dm.read_page(&page_id);
+    assert!(matches!(read, Err(Error::ChecksumMismatch { .. })), "{read:?}");
+}
+
+/// Test that a disk manager whose storage can't be mapped reads its pages as before.
+#[test]
+fn test_mmap_reads_fall_back() {
+    let mut dm = DiskManager::new_in_memory().with_mmap_reads();
+    assert!(!dm.mmap_reads());
+    let page_id = dm.allocate_new_page().unwrap();
+    write_text(&mut dm, page_id, "Unmapped");
+    assert_eq!(Tuple::from(&b"Unmapped"[..]), text_of(&dm.read_page(&page_id).unwrap()));
+}
+
+/// Test that a write the fault injector fails writes nothing, and one it tears is caught by the
+/// page's checksum when read back.
+#[test]
+fn test_fault_injector_failed_and_torn_writes() {
+    let injector = FaultInjector::new();
+    let mut dm = DiskManager::new_for_test().with_fault_injector(injector.clone());
+    let page_id = dm.allocate_new_page().unwrap();
+    write_text(&mut dm, page_id, "Before");
+
+    injector.fail_nth_write(1);
+    let result = dm.write_page(text_page(page_id, "Failed"));
+    assert!(matches!(result, Err(Error::IO(_))), "{result:?}");
+    assert_eq!(Tuple::from(&b"Before"[..]), text_of(&dm.read_page(&page_id).unwrap()));
+
+    injector.tear_nth_write(1);
+    write_text(&mut dm, page_id, "Torn");
+    let result = dm.read_page(&page_id);
+    assert!(matches!(result, Err(Error::ChecksumMismatch { .. })), "{result:?}");
+}
+
+/// Test that a free page handed out again, but that fails to be written, goes back on the free
+/// pages, those of the reopened file too.
+#[test]
+fn test_failed_allocation_frees_page_again() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let injector = FaultInjector::new();
+    let mut dm = DiskManager::open(temp_file.path()).unwrap().with_fault_injector(injector.clone());
+    let page_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page().unwrap()).collect();
+    dm.deallocate_pages(&page_ids[..2]).unwrap();
+
+    // The first write is the header taking the page off the free pages, the second the page.
+    injector.fail_nth_write(2);
+    let result = dm.allocate_new_page();
+    assert!(matches!(result, Err(Error::IO(_))), "{result:?}");
+    assert_eq!(page_ids[..2], dm.free_pages().collect::<Vec<_>>());
+    injector.fail_nth_write(2);
+    let result = dm.allocate_contiguous(2);
+    assert!(matches!(result, Err(Error::IO(_))), "{result:?}");
+    assert_eq!(page_ids[..2], dm.free_pages().collect::<Vec<_>>());
+    drop(dm);
+
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(page_ids[..2], dm.free_pages().collect::<Vec<_>>());
+    assert_eq!(page_ids[..2], dm.allocate_contiguous(2).unwrap());
+}
+
+/// Test that a power cut loses the writes since the last sync, those after it too, and that the
+/// file reopened afterwards has the pages as last synced.
+#[test]
+fn test_fault_injector_power_cut() {
+    let temp_file = NamedTempFile::new_in(RUST_DB_DATA_DIR).expect("Failed to create temp file");
+    let injector = FaultInjector::new();
+    let mut dm = DiskManager::open(temp_file.path()).unwrap().with_fault_injector(injector.clone());
+    let page_id = dm.allocate_new_page().unwrap();
+    write_text(&mut dm, page_id, "Synced");
+    dm.sync().unwrap();
+    write_text(&mut dm, page_id, "Not synced");
+    let unsynced = dm.allocate_new_page().unwrap();
+
+    injector.cut_power().unwrap();
+    assert_eq!(Tuple::from(&b"Synced"[..]), text_of(&dm.read_page(&page_id).unwrap()));
+    write_text(&mut dm, page_id, "After the cut");
+    dm.sync().unwrap();
+    drop(dm);
+
+    let mut dm = DiskManager::open(temp_file.path()).unwrap();
+    assert_eq!(Tuple::from(&b"Synced"[..]), text_of(&dm.read_page(&page_id).unwrap()));
+    assert_eq!(Err(Error::PageNotAllocated(unsynced)), dm.read_page(&unsynced).map(|_| ()));
+}
+
+/// Test that each durability mode syncs as often as it should for the same workload: after every
+/// page written, or only when told to.
+#[test]
+fn test_durability_syncs() {
+    let syncs = |durability| {
+        let backend = CountingBackend::default();
+        let syncs = backend.syncs();
+        let metrics = Arc::default();
+        let mut dm = DiskManager::new_with_backend_for_test(Box::new(backend))
+            .with_durability(durability);
+        dm.set_metrics(Arc::clone(&metrics));
+        sync_workload(&mut dm);
+        let before = syncs.load(Ordering::SeqCst);
+        dm.sync().unwrap();
+        // Nothing was written since, so there is nothing to sync.
+        dm.sync().unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst) as u64, metrics.snapshot().disk.syncs);
+        (before, syncs.load(Ordering::SeqCst))
+    };
+    // Three pages allocated, then each written twice.
+    assert_eq!((9, 9), syncs(Durability::Always));
+    assert_eq!((0, 1), syncs(Durability::Manual));
+}
+
+/// Test that the interval mode syncs in the background, only when pages were written since the
+/// last sync.
+#[test]
+fn test_interval_durability() {
+    let backend = CountingBackend::default();
+    let syncs = backend.syncs();
+    let interval = Duration::from_millis(10);
+    let disk_manager = DiskManager::new_with_backend_for_test(Box::new(backend))
+        .with_durability(Durability::Interval(interval))
+        .into_handle();
+    let wait_for = |count: usize| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while syncs.load(Ordering::SeqCst) < count && Instant::now() < deadline {
+            thread::sleep(interval);
+        }
+        thread::sleep(interval * 5);
+        assert_eq!(count, syncs.load(Ordering::SeqCst));
+    };
+
+    sync_workload(&mut disk_manager.write().unwrap());
+    wait_for(1);
+    let page_id = disk_manager.write().unwrap().allocate_new_page().unwrap();
+    wait_for(2);
+
+    write_text(&mut disk_manager.write().unwrap(), page_id, "Synced");
+    wait_for(3);
+
+    // The syncer doesn't keep the disk manager alive.
+    let dropped = Arc::downgrade(&disk_manager);
+    drop(disk_manager);
+    assert!(dropped.upgrade().is_none());
+}
+
+/// Pages written asynchronously are read back as last written, whether one at a time or
+/// several at once, before and after the writer thread gets to them, and are on disk once
+/// drained.
+#[test]
+fn test_async_writes_read_your_writes() {
+    let disk_manager = DiskManager::new_in_memory().with_async_writes(4).into_handle();
+    let page_ids: Vec<_> = {
+        let mut dm = disk_manager.write().unwrap();
+        (0..3).map(|_| dm.allocate_new_page().unwrap()).collect()
+    };
+    let read_back = |page_id: &PageId| {
+        let mut dm = disk_manager.write().unwrap();
+        let page = dm.read_page(page_id).unwrap();
+        for other in dm.read_pages(&[*page_id, page_ids[0], *page_id]).into_iter().step_by(2) {
+            assert_eq!(text_of(&page), text_of(&other.unwrap()));
+        }
+        text_of(&page)
+    };
+    for i in 0..60 {
+        let page_id = page_ids[i % page_ids.len()];
+        let text = format!("Write {i}");
+        disk_manager.write().unwrap().write_page_async(text_page(page_id, &text)).unwrap();
+        assert_eq!(Tuple::from(text.as_bytes()), read_back(&page_id));
+        if i % 7 == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    disk_manager.write().unwrap().drain().unwrap();
+    for (i, page_id) in (57..60).zip(&page_ids) {
+        assert_eq!(Tuple::from(format!("Write {i}").as_bytes()), read_back(page_id));
+    }
+
+    // A page written synchronously supersedes the one queued before it.
+    let mut dm = disk_manager.write().unwrap();
+    dm.write_page_async(text_page(page_ids[0], "Queued")).unwrap();
+    write_text(&mut dm, page_ids[0], "Written");
+    dm.drain().unwrap();
+    assert_eq!(Tuple::from(&b"Written"[..]), text_of(&dm.read_page(&page_ids[0]).unwrap()));
+}
+
+/// The pages still queued when the disk manager is dropped are written.
+#[test]
+fn test_async_writes_drain_on_drop() {
+    let dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let db_path = dir.path().join("test.db");
+    let page_ids: Vec<_> = {
+        let mut disk_manager =
+            DiskManager::open(&db_path).unwrap().with_async_writes(64).into_handle();
+        let page_ids: Vec<_> = {
+            let mut dm = disk_manager.write().unwrap();
+            let page_ids: Vec<_> = (0..32).map(|_| dm.allocate_new_page().unwrap()).collect();
+            for &page_id in &page_ids {
+                dm.write_page_async(text_page(page_id, &format!("Page {page_id}"))).unwrap();
+            }
+            page_ids
+        };
+        // Dropped here rather than by the writer thread, which may be holding it for a moment,
+        // so that it is gone before the file is opened again.
+        let dm = loop {
+            match Arc::try_unwrap(disk_manager) {
+                Ok(dm) => break dm,
+                Err(handle) => disk_manager = handle,
+            }
+            thread::yield_now();
+        };
+        drop(dm);
+        page_ids
+    };
+
+    let mut dm = DiskManager::open(&db_path).unwrap();
+    for (page_id, page) in page_ids.iter().zip(dm.read_pages(&page_ids)) {
+        assert_eq!(Tuple::from(format!("Page {page_id}").as_bytes()), text_of(&page.unwrap()));
+    }
+}
+
+/// Allocates three pages, and writes each twice.
+fn sync_workload(dm: &mut DiskManager) {
+    let page_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for text in ["First", "Second"] {
+        for &page_id in &page_ids {
+            write_text(dm, page_id, text);
+        }
+    }
+}
+
 fn new_disk_manager() -> Arc<RwLock<DiskManager>> {
     DiskManager::new_with_handle_for_test()
 }
+
+/// Runs each scenario below against a disk manager over a temporary file, and against one in
+/// memory, so that both backends are held to the same behavior.
+macro_rules! backend_parity_tests {
+    ($($scenario:ident),* $(,)?) => {
+        mod file_backend {
+            $(
+                #[test]
+                fn $scenario() {
+                    super::$scenario(super::DiskManager::new_for_test());
+                }
+            )*
+        }
+
+        mod memory_backend {
+            $(
+                #[test]
+                fn $scenario() {
+                    super::$scenario(super::DiskManager::new_in_memory());
+                }
+            )*
+        }
+    };
+}
+
+backend_parity_tests!(
+    parity_write_and_read,
+    parity_read_past_end,
+    parity_reuse_deallocated_pages,
+    parity_log,
+    parity_buffer_pool,
+    parity_tablespaces,
+    parity_write_pages,
+    parity_read_pages_twice,
+    parity_compact,
+);
+
+fn write_text(dm: &mut DiskManager, page_id: PageId, text: &str) {
+    dm.write_page(text_page(page_id, text)).unwrap();
+}
+
+fn text_page(page_id: PageId, text: &str) -> TablePage {
+    let mut page = TablePage::builder().page_id(page_id).build();
+    page.insert_tuple(TupleMetadata::new(false), Tuple::from(text.as_bytes()))
+        .expect("Failed to insert tuple");
+    page
+}
+
+fn text_of(page: &TablePage) -> Tuple {
+    page.get_tuple(&RecordId::new(page.page_id, 0)).expect("Failed to retrieve tuple")
+}
+
+/// Reads the text written to `page_id`, which the page must hold as its own ID, e.g. once moved.
+fn read_text(dm: &mut DiskManager, page_id: PageId) -> Tuple {
+    let page = dm.read_page(&page_id).unwrap();
+    page.get_tuple(&RecordId::new(page_id, 0)).expect("Failed to retrieve tuple")
+}
+
+/// Pages read back one at a time, in runs, and ahead of time, are the ones written.
+fn parity_write_and_read(mut dm: DiskManager) {
+    assert_eq!(1, dm.num_pages());
+    let page_ids: Vec<_> = (0..4).map(|_| dm.allocate_new_page().unwrap()).collect();
+    assert_eq!(vec![1, 2, 3, 4], page_ids);
+    assert_eq!(5, dm.num_pages());
+    assert_eq!(0, dm.read_page(&page_ids[0]).unwrap().tuple_count());
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, &format!("Page number {page_id}"));
+    }
+
+    let expected = |page_id| Tuple::from(format!("Page number {page_id}").as_bytes());
+    assert_eq!(expected(page_ids[2]), text_of(&dm.read_page(&page_ids[2]).unwrap()));
+    dm.read_ahead(&page_ids[..2]);
+    for (page_id, page) in page_ids.iter().zip(dm.read_pages(&page_ids)) {
+        assert_eq!(expected(*page_id), text_of(&page.unwrap()));
+    }
+}
+
+/// Pages past the last one, or deallocated, or in no tablespace, aren't allocated, alone or in a
+/// run, and stay so when read ahead.
+fn parity_read_past_end(mut dm: DiskManager) {
+    let page_ids: Vec<_> = (0..2).map(|_| dm.allocate_new_page().unwrap()).collect();
+    write_text(&mut dm, page_ids[0], "Last page");
+    write_text(&mut dm, page_ids[1], "Deallocated");
+    dm.deallocate_page(&page_ids[1]).unwrap();
+    let past_end = dm.num_pages();
+    let no_tablespace = make_page_id(7, 1);
+
+    dm.read_ahead(&[page_ids[1], past_end]);
+    for page_id in [page_ids[1], past_end, no_tablespace] {
+        assert_eq!(Err(Error::PageNotAllocated(page_id)), dm.read_page(&page_id).map(|_| ()));
+    }
+    let mut read = dm.read_pages(&[page_ids[0], page_ids[1], past_end]).into_iter();
+    assert_eq!(Tuple::from(&b"Last page"[..]), text_of(&read.next().unwrap().unwrap()));
+    for page_id in [page_ids[1], past_end] {
+        let result = read.next().unwrap().map(|_| ());
+        assert_eq!(Err(Error::PageNotAllocated(page_id)), result);
+    }
+}
+
+/// Deallocated pages are handed out again, emptied, before any new page.
+fn parity_reuse_deallocated_pages(mut dm: DiskManager) {
+    let page_ids: Vec<_> = (0..4).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, "Used");
+    }
+    dm.deallocate_page(&page_ids[1]).unwrap();
+    dm.deallocate_page(&page_ids[3]).unwrap();
+    dm.set_checkpoint_lsn(7).unwrap();
+    assert_eq!(vec![page_ids[1], page_ids[3]], dm.free_pages().collect::<Vec<_>>());
+
+    assert_eq!(page_ids[1], dm.allocate_new_page().unwrap());
+    assert_eq!(page_ids[3], dm.allocate_new_page().unwrap());
+    assert_eq!(0, dm.read_page(&page_ids[3]).unwrap().tuple_count());
+    assert_eq!(page_ids[3] + 1, dm.allocate_new_page().unwrap());
+    assert_eq!(Some(7), dm.checkpoint_lsn().unwrap());
+}
+
+/// Pages written together take a write for each run of consecutive pages, the last one written of
+/// those with the same ID wins, and none of them is read ahead stale.
+fn parity_write_pages(mut dm: DiskManager) {
+    let metrics = Arc::default();
+    dm.set_metrics(Arc::clone(&metrics));
+    let page_ids: Vec<_> = (0..8).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, "Old");
+    }
+    dm.read_ahead(&page_ids);
+
+    let page = |page_id, text: &str| {
+        let mut page = TablePage::builder().page_id(page_id).build();
+        page.insert_tuple(TupleMetadata::new(false), Tuple::from(text.as_bytes()))
+            .expect("Failed to insert tuple");
+        page
+    };
+    let written = metrics.snapshot().disk;
+    let order = [3, 1, 2, 0, 6, 7, 2];
+    let mut pages: Vec<_> = order.iter().map(|i| page(page_ids[*i], "Batch")).collect();
+    *pages.last_mut().unwrap() = page(page_ids[2], "Last");
+    dm.write_pages(pages).unwrap();
+    write_text(&mut dm, page_ids[7], "Single");
+    let disk = metrics.snapshot().disk;
+    // Two runs, 0 to 3 and 6 to 7, and then the single page.
+    assert_eq!(written.writes + 3, disk.writes);
+    assert_eq!(written.pages_written + 7, disk.pages_written);
+
+    let texts = ["Batch", "Batch", "Last", "Batch", "Old", "Old", "Batch", "Single"];
+    for (page, text) in dm.read_pages(&page_ids).into_iter().zip(texts) {
+        assert_eq!(Tuple::from(text.as_bytes()), text_of(&page.unwrap()));
+    }
+}
+
+/// Pages asked for more than once are read once, and returned each time.
+fn parity_read_pages_twice(mut dm: DiskManager) {
+    let metrics = Arc::default();
+    dm.set_metrics(Arc::clone(&metrics));
+    let page_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, &format!("Page number {page_id}"));
+    }
+    dm.read_ahead(&page_ids[..1]);
+
+    let read = metrics.snapshot().disk.pages_read;
+    let asked = [page_ids[0], page_ids[2], page_ids[0], page_ids[1], page_ids[2]];
+    for (page_id, page) in asked.iter().zip(dm.read_pages(&asked)) {
+        let expected = Tuple::from(format!("Page number {page_id}").as_bytes());
+        assert_eq!(expected, text_of(&page.unwrap()));
+    }
+    assert_eq!(read + 2, metrics.snapshot().disk.pages_read);
+}
+
+/// Pages are allocated, written, read and deallocated in the tablespace of their ID, apart from
+/// those of other tablespaces.
+fn parity_tablespaces(mut dm: DiskManager) {
+    let dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let cold = dm.create_tablespace("cold", &dir.path().join("cold.db")).unwrap();
+    assert_eq!(Some(cold), dm.tablespace("cold"));
+    assert_eq!(Some(DEFAULT_TABLESPACE), dm.tablespace("default"));
+    let result = dm.create_tablespace("cold", &dir.path().join("other.db"));
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+    assert!(matches!(dm.allocate_new_page_in(cold + 1), Err(Error::InvalidInput(_))));
+
+    let hot_ids: Vec<_> = (0..2).map(|_| dm.allocate_new_page().unwrap()).collect();
+    let cold_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page_in(cold).unwrap()).collect();
+    assert_eq!(vec![1, 2], hot_ids);
+    assert_eq!((1..4).map(|page_no| make_page_id(cold, page_no)).collect::<Vec<_>>(), cold_ids);
+    assert_eq!((cold, 2), split_page_id(cold_ids[1]));
+    assert_eq!(3, dm.num_pages());
+    for &page_id in hot_ids.iter().chain(&cold_ids) {
+        write_text(&mut dm, page_id, &format!("Page number {page_id}"));
+    }
+
+    // Runs of consecutive page IDs are split where the file changes.
+    let page_ids = [hot_ids[1], cold_ids[0], cold_ids[2], hot_ids[0], cold_ids[1]];
+    dm.read_ahead(&page_ids[..2]);
+    for (page_id, page) in page_ids.iter().zip(dm.read_pages(&page_ids)) {
+        let expected = Tuple::from(format!("Page number {page_id}").as_bytes());
+        assert_eq!(expected, text_of(&page.unwrap()));
+    }
+
+    dm.deallocate_page(&cold_ids[1]).unwrap();
+    dm.deallocate_page(&make_page_id(cold, 0)).unwrap();
+    dm.deallocate_page(&make_page_id(cold + 1, 1)).unwrap();
+    assert_eq!(vec![cold_ids[1]], dm.free_pages().collect::<Vec<_>>());
+    assert_eq!(3, dm.allocate_new_page().unwrap());
+    assert_eq!(cold_ids[1], dm.allocate_new_page_in(cold).unwrap());
+    assert_eq!(0, dm.read_page(&cold_ids[1]).unwrap().tuple_count());
+    assert_eq!(make_page_id(cold, 4), dm.allocate_new_page_in(cold).unwrap());
+}
+
+/// Test that tablespaces, with the pages allocated and deallocated in them, are opened again with
+/// the database file, and that their pages live in their own files.
+#[test]
+fn test_tablespaces_survive_reopen() {
+    let hot_dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let cold_dir = TempDir::new_in(RUST_DB_DATA_DIR).unwrap();
+    let db_path = hot_dir.path().join("test.db");
+    let cold_path = cold_dir.path().join("cold.db");
+    let (cold, page_ids) = {
+        let mut dm = DiskManager::open(&db_path).unwrap();
+        let cold = dm.create_tablespace("cold", &cold_path).unwrap();
+        let page_ids: Vec<_> = (0..3).map(|_| dm.allocate_new_page_in(cold).unwrap()).collect();
+        for &page_id in &page_ids {
+            write_text(&mut dm, page_id, "Cold");
+        }
+        dm.deallocate_page(&page_ids[1]).unwrap();
+        (cold, page_ids)
+    };
+    let page_size = RUSTY_DB_PAGE_SIZE_BYTES as u64;
+    let extent = ExtentConfig::default().pages_per_extent as u64;
+    assert_eq!(extent * page_size, cold_path.metadata().unwrap().len());
+    assert!(db_path.metadata().unwrap().len() <= page_size);
+
+    let mut dm = DiskManager::open(&db_path).unwrap();
+    assert_eq!(Some(cold), dm.tablespace("cold"));
+    assert_eq!(vec![page_ids[1]], dm.free_pages().collect::<Vec<_>>());
+    assert_eq!(Tuple::from(&b"Cold"[..]), text_of(&dm.read_page(&page_ids[2]).unwrap()));
+    assert_eq!(1, dm.allocate_new_page().unwrap());
+    assert_eq!(page_ids[1], dm.allocate_new_page_in(cold).unwrap());
+}
+
+/// Compacting moves the last pages into the free ones before them, reports each move, and drops
+/// the pages left free at the end.
+fn parity_compact(mut dm: DiskManager) {
+    let page_ids: Vec<_> = (0..8).map(|_| dm.allocate_new_page().unwrap()).collect();
+    for &page_id in &page_ids {
+        write_text(&mut dm, page_id, &format!("Page number {page_id}"));
+    }
+    for page_id in [2, 4, 7] {
+        dm.deallocate_page(&page_id).unwrap();
+    }
+
+    let mut moves = Vec::new();
+    assert_eq!(3, dm.compact(&mut |from, to| moves.push((from, to))).unwrap());
+    assert_eq!(vec![(8, 2), (6, 4)], moves);
+    assert_eq!(6, dm.num_pages());
+    assert_eq!(0, dm.free_pages().count());
+    let expected = |page_id| Tuple::from(format!("Page number {page_id}").as_bytes());
+    for (page_id, was) in [(1, 1), (2, 8), (3, 3), (4, 6), (5, 5)] {
+        assert_eq!(expected(was), read_text(&mut dm, page_id));
+    }
+    assert_eq!(Err(Error::PageNotAllocated(8)), dm.read_page(&8).map(|_| ()));
+    assert_eq!(6, dm.allocate_new_page().unwrap());
+
+    // Nothing to compact.
+    assert_eq!(0, dm.compact(&mut |_, _| panic!("no page to move")).unwrap());
+}
+
+/// The log is appended to, read back whole, and truncated from the front.
+fn parity_log(mut dm: DiskManager) {
+    assert_eq!(0, dm.log_size().unwrap());
+    dm.append_log(b"first,").unwrap();
+    dm.append_log(b"second").unwrap();
+    dm.sync_log().unwrap();
+    assert_eq!(b"first,second".to_vec(), dm.read_log().unwrap());
+
+    dm.truncate_log(6).unwrap();
+    assert_eq!(b"second".to_vec(), dm.read_log().unwrap());
+    dm.append_log(b",third").unwrap();
+    assert_eq!(b"second,third".to_vec(), dm.read_log().unwrap());
+    assert_eq!(12, dm.log_size().unwrap());
+}
+
+/// A pool too small for its pages writes them back on eviction, and reads them in again intact.
+fn parity_buffer_pool(dm: DiskManager) {
+    let disk_manager = Arc::new(RwLock::new(dm));
+    let bpm = BufferPoolManager::new(2, 2, Arc::clone(&disk_manager));
+    let mut page_ids = Vec::new();
+    for _ in 0..5 {
+        page_ids.push(bpm.new_page().unwrap());
+        bpm.unpin_page(page_ids.last().unwrap(), false).unwrap();
+    }
+    for &page_id in &page_ids {
+        let page = bpm.fetch_page(&page_id).unwrap();
+        let text = format!("Page number {page_id}");
+        page.write().unwrap().insert_tuple(TupleMetadata::new(false), Tuple::from(text.as_bytes()));
+        bpm.unpin_page(&page_id, true).unwrap();
+    }
+
+    for &page_id in page_ids.iter().rev() {
+        let page = bpm.fetch_page(&page_id).unwrap();
+        let expected = Tuple::from(format!("Page number {page_id}").as_bytes());
+        assert_eq!(expected, text_of(&page.read().unwrap()));
+        bpm.unpin_page(&page_id, false).unwrap();
+    }
+    bpm.flush_all_pages();
+    let page = disk_manager.write().unwrap().read_page(&page_ids[0]).unwrap();
+    assert_eq!(Tuple::from(&b"Page number 1"[..]), text_of(&page));
+
+    assert!(bpm.delete_page(page_ids[2]).unwrap());
+    assert_eq!(page_ids[2], bpm.new_page().unwrap());
+}
@@ -1,9 +1,14 @@
 use crate::common::Result;
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::storage::index::TableIndex;
 use crate::storage::page::RecordId;
-use crate::storage::tuple::Tuple;
+use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::{LogManager, Lsn};
 use crate::types::Table;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
 
+#[derive(Clone, Copy)]
 pub struct Key<'a> {
     pub table_name: &'a str,
     pub record_id: &'a RecordId,
@@ -33,42 +38,146 @@ pub trait Engine: Send {
     /// Creates a table.
     fn create_table(&mut self, table: Table) -> Result<()>;
 
+    /// Creates a table over an existing chain of pages, e.g. those of a restored backup, given
+    /// their ids in chain order. The table's indexes are built from the tuples on them.
+    fn attach_table(&mut self, table: Table, page_ids: Vec<PageId>) -> Result<()>;
+
     /// Deletes a table. Returns true if it exists and false otherwise.
     fn delete_table(&mut self, table_name: &str) -> Result<bool>;
 
     /// Gets a table with the given table name.
     fn get_table(&mut self, table_name: &str) -> Result<Option<Table>>;
 
+    /// Returns the secondary indexes over the columns of a table. They are kept up to date as
+    /// the table's tuples are written, and point at every version that isn't tombstoned.
+    fn indexes(&mut self, table_name: &str) -> Result<Vec<TableIndex>>;
+
     /// Deletes a key if one exists. Otherwise, does nothing.
     fn delete(&mut self, key: Key) -> Result<()>;
 
     /// Gets a value for a key if one exists.
     fn get(&mut self, key: Key) -> Result<Tuple>;
 
+    /// Gets the metadata of the tuple stored at a key, including tombstoned ones.
+    fn get_metadata(&mut self, key: Key) -> Result<TupleMetadata>;
+
+    /// Replaces the metadata of the tuple stored at a key.
+    fn set_metadata(&mut self, key: Key, metadata: TupleMetadata) -> Result<()>;
+
     /// Inserts a new tuple value into the table with name `table_name`,
     /// and returns the resultant record id for it.
     fn insert(&mut self, table_name: &str, value: Tuple) -> Result<RecordId>;
 
-    /// Creates an iterator over the table's key/value pairs.
+    /// Inserts a tuple value at the given key, which must be the next free slot of its page.
+    /// Used to redo logged inserts.
+    fn insert_at(&mut self, key: Key, value: Tuple) -> Result<()>;
+
+    /// Returns the record id the next `insert` of `value` into the table will be assigned.
+    fn next_record_id(&mut self, table_name: &str, value: &Tuple) -> Result<RecordId>;
+
+    /// Creates an iterator over the table's non-tombstoned tuples, along with their metadata.
     fn scan(&mut self, table_name: &str) -> Self::ScanIterator<'_>
     where
         Self: Sized;
 
+    /// Like [`Self::scan`], but goes over the tuples in reverse order.
+    fn scan_rev(&mut self, table_name: &str) -> Self::ScanIterator<'_>
+    where
+        Self: Sized;
+
     /// Scan, but can be used from trait objects. This iterator uses
     /// dynamic dispatch, which incurs a runtime performance penalty.
     fn scan_dyn(&mut self) -> Box<dyn ScanIterator + '_>;
 
+    /// Returns the ids of the pages holding a table's tuples, in the order a scan visits them.
+    fn page_ids(&mut self, table_name: &str) -> Result<Vec<PageId>>;
+
+    /// Returns the non-tombstoned tuples on one page of a table, along with their metadata, like
+    /// a scan would. Lets a table be scanned a page at a time, e.g. by several threads.
+    fn scan_page(
+        &mut self,
+        table_name: &str,
+        page_id: PageId,
+    ) -> Result<Vec<(RecordId, TupleMetadata, Tuple)>>;
+
+    /// Like [`Self::scan_page`], but only returns the tuples' record ids and metadata, without
+    /// reading the tuples themselves. Lets the rows of a table be counted cheaply.
+    fn scan_page_metadata(
+        &mut self,
+        table_name: &str,
+        page_id: PageId,
+    ) -> Result<Vec<(RecordId, TupleMetadata)>>;
+
     /// Updates a tuple corresponding to the given record id with the provided value.
-    fn update(&mut self, key: Key, value: Tuple) -> Result<()>;
+    /// Returns the record id the updated tuple is stored at, which differs from the
+    /// key's when the new value could not be written in place.
+    fn update(&mut self, key: Key, value: Tuple) -> Result<RecordId>;
+
+    /// Stamps the page holding the given key with the LSN of the log record that describes the
+    /// latest change to it.
+    fn set_lsn(&mut self, key: Key, lsn: Lsn) -> Result<()>;
+
+    /// Physically removes the versions of a table that `removable` selects, and reclaims the
+    /// space of tombstoned ones. Indexes stop pointing at removed versions, and pages left
+    /// without any are freed.
+    fn vacuum(
+        &mut self,
+        table_name: &str,
+        removable: &dyn Fn(&TupleMetadata) -> bool,
+    ) -> Result<VacuumStats>;
+
+    /// Rewrites a table onto a new page chain, packing the versions `removable` doesn't select
+    /// into as few pages as they fit, and frees the pages of the old one. Record ids change, so
+    /// the table's indexes are rebuilt, and nothing may hold on to the old ones.
+    fn vacuum_full(
+        &mut self,
+        table_name: &str,
+        removable: &dyn Fn(&TupleMetadata) -> bool,
+    ) -> Result<VacuumStats>;
+
+    /// Returns the names of all tables.
+    fn table_names(&mut self) -> Result<Vec<String>>;
+
+    /// Gets the LSN the page holding the given key was last stamped with.
+    fn get_lsn(&mut self, key: Key) -> Result<Lsn>;
+
+    /// Writes every modified page back to storage, returning the pages written along with
+    /// their LSNs.
+    fn checkpoint(&mut self) -> Result<Vec<(PageId, Lsn)>>;
+
+    /// Returns the write-ahead log that modifications to this engine are recorded in.
+    fn log_manager(&self) -> Arc<LogManager>;
+
+    /// Returns the disk manager holding the database file and its write-ahead log.
+    fn disk_manager(&self) -> Arc<RwLock<DiskManager>>;
 
     /// Returns engine status.
     fn status(&mut self) -> Result<Status>;
 }
 
 /// A scan iterator over a table
-pub trait ScanIterator: Iterator<Item = Result<(RecordId, Tuple)>> {}
+pub trait ScanIterator: Iterator<Item = Result<(RecordId, TupleMetadata, Tuple)>> {}
 /// Blanket implementation of ScanIterator for any `I` satisfying the trait bound.
-impl<I: Iterator<Item = Result<(RecordId, Tuple)>>> ScanIterator for I {}
+impl<I: Iterator<Item = Result<(RecordId, TupleMetadata, Tuple)>>> ScanIterator for I {}
+
+/// What a vacuum reclaimed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VacuumStats {
+    /// Versions physically removed.
+    pub versions: u64,
+    /// Payload bytes reclaimed, including those of versions already tombstoned.
+    pub bytes: u64,
+    /// Emptied pages taken out of their table's page chain.
+    pub pages: u64,
+}
+
+impl std::ops::AddAssign for VacuumStats {
+    fn add_assign(&mut self, other: Self) {
+        self.versions += other.versions;
+        self.bytes += other.bytes;
+        self.pages += other.pages;
+    }
+}
 
 /// Engine status.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -1,29 +1,67 @@
-use crate::common::constants::{
-    COULD_NOT_UNWRAP_BPM_MSG, INVALID_PID, NEW_PAGE_ERR_MSG, TUPLE_DOESNT_FIT_MSG,
-};
+use crate::common::constants::{INVALID_PID, TUPLE_DOESNT_FIT_MSG};
 use crate::common::{Error, Result};
+use crate::config::config::SCAN_READAHEAD_PAGES;
 use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::buffer::lru_k_replacer::AccessType;
 use crate::storage::disk::disk_manager::PageId;
-use crate::storage::page::{Page, RecordId, TablePage, TablePageHandle, TablePageIterator};
+use crate::storage::engine::VacuumStats;
+use crate::storage::page::{
+    OverflowPage, OverflowStub, Page, RecordId, TablePage, TablePageHandle, TablePageIterator,
+    TablePageRevIterator,
+};
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
 use crate::types::Table;
-use std::sync::{Arc, RwLock};
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::sync::Arc;
 
-/// Represents a table stored on disk.
+/// Represents a table stored on disk, as a chain of table pages linked by their next page ids.
+///
+/// Pages are pinned in the buffer pool only while the heap is working on them, see
+/// [`PinnedTablePage`], so the pool is free to evict the rest. Inserts go to the first page of the
+/// chain with room for the tuple, leaving the headroom the table's fill factor asks for, and a new
+/// page is linked onto its end whenever no page has room.
+///
+/// The heap also keeps the chain's page ids in memory, which lets a scan read the pages ahead of
+/// it into the pool without following the links, see [`TableHeapIterator`].
+///
+/// A tuple too large for an empty page is written to a chain of [`OverflowPage`]s of its own,
+/// and its page holds an [`OverflowStub`] pointing at them in its stead. The heap reads the
+/// tuple back from the chain wherever it returns tuples, and frees the chain once the tuple is
+/// tombstoned. Like links between the heap's pages, overflow pages aren't logged, so they are
+/// flushed as they are written: recovery redoing an insert writes the tuple to a new chain,
+/// and one the insert had written before the crash is lost to the database file.
 #[derive(Debug)]
 pub struct TableHeap {
     pub(crate) page_cnt: u32,
     pub(crate) schema: Table,
     // reference to the buffer pool manager instance shared between heap files
-    pub(crate) buffer_pool_manager: Arc<RwLock<BufferPoolManager>>,
+    pub(crate) buffer_pool_manager: Arc<BufferPoolManager>,
     pub(crate) first_page_id: PageId,
     pub(crate) last_page_id: PageId,
+    /// The ids of the pages in the chain, in chain order.
+    chain: Vec<PageId>,
+    /// How many pages ahead of the page it is on a scan reads into the buffer pool.
+    readahead: usize,
+    /// The free space of each page of the chain once compacted, see
+    /// [`TablePage::free_space_compacted`], in chain order, built from the pages the first time
+    /// an insert looks for room. Inserts and deletes keep the entries of the pages they change up
+    /// to date, while other changes may leave an entry overstating its page's free space, which
+    /// is corrected when an insert finds less room on the page than it expected. Compacting pages
+    /// frees space, so vacuuming drops the entries.
+    free_space: Option<Vec<usize>>,
 }
 
 impl TableHeap {
-    pub fn new(schema: Table, bpm: &Arc<RwLock<BufferPoolManager>>) -> TableHeap {
+    pub fn new(schema: Table, bpm: &Arc<BufferPoolManager>) -> TableHeap {
         let bpm = Arc::clone(bpm);
-        let first_page_id = bpm.write().unwrap().new_page().unwrap();
+        let first_page_id = {
+            let page_id = bpm.new_page().unwrap();
+            // A new page has yet to reach disk, so it starts out dirty.
+            bpm.unpin_page(&page_id, true).unwrap();
+            page_id
+        };
 
         TableHeap {
             page_cnt: 1,
@@ -31,7 +69,46 @@ impl TableHeap {
             buffer_pool_manager: bpm,
             first_page_id,
             last_page_id: first_page_id,
+            chain: vec![first_page_id],
+            readahead: SCAN_READAHEAD_PAGES,
+            free_space: None,
+        }
+    }
+
+    /// Opens a heap over an existing chain of pages, e.g. those of a restored backup, given their
+    /// ids in chain order. The pages are linked up again in that order, since links made while
+    /// the pages were being copied may not have made it into their copies.
+    pub fn open(
+        schema: Table,
+        bpm: &Arc<BufferPoolManager>,
+        chain: Vec<PageId>,
+    ) -> Result<TableHeap> {
+        let (Some(&first_page_id), Some(&last_page_id)) = (chain.first(), chain.last()) else {
+            return Err(Error::InvalidData(format!(
+                "table {} has no pages",
+                schema.name()
+            )));
+        };
+        let heap = TableHeap {
+            page_cnt: chain.len() as u32,
+            schema,
+            buffer_pool_manager: Arc::clone(bpm),
+            first_page_id,
+            last_page_id,
+            chain,
+            readahead: SCAN_READAHEAD_PAGES,
+            free_space: None,
+        };
+        bpm.prefetch_pages(&heap.chain);
+        for pair in heap.chain.windows(2) {
+            let page = heap.fetch_page_handle(&pair[0])?;
+            let mut page_guard = page.write()?;
+            if page_guard.get_next_page_id() != pair[1] {
+                page_guard.set_next_page_id(pair[1]);
+                page_guard.set_is_dirty(true);
+            }
         }
+        Ok(heap)
     }
 
     pub fn schema(&self) -> Table {
@@ -42,135 +119,854 @@ impl TableHeap {
         self.page_cnt
     }
 
+    /// Sets how many pages ahead of the page it is on a scan reads into the buffer pool. 0 turns
+    /// read-ahead off.
+    pub fn set_readahead(&mut self, pages: usize) {
+        self.readahead = pages;
+    }
+
     /// creates a new page and updates corresponding heap metadata.
     pub fn create_new_page(&mut self) -> Result<PageId> {
-        let binding = Arc::clone(&self.buffer_pool_manager);
-        let mut bpm = binding.write().expect(COULD_NOT_UNWRAP_BPM_MSG);
+        let bpm = Arc::clone(&self.buffer_pool_manager);
 
-        let new_page_id = match bpm.new_page() {
-            Some(id) => id,
-            None => return Err(Error::CreationError),
-        };
+        // With every frame pinned, the error says how many, for the statement to fail with.
+        let new_page_id = bpm.new_page()?;
+        bpm.unpin_page(&new_page_id, true)?;
 
-        if let Some(page_handle) = bpm.fetch_page(&self.last_page_id) {
-            page_handle.write().unwrap().set_next_page_id(new_page_id);
-            self.last_page_id = new_page_id;
-            self.page_cnt += 1;
-            Ok(new_page_id)
-        } else {
-            Err(Error::CreationError)
+        let page_handle = bpm.fetch_page(&self.last_page_id)?;
+        page_handle.write().unwrap().set_next_page_id(new_page_id);
+        // The link isn't logged, so recovery can only rely on it once it is on disk.
+        let flushed = bpm.force_log_and_flush(&self.last_page_id);
+        let is_dirty = page_handle.read().unwrap().get_is_dirty();
+        bpm.unpin_page(&self.last_page_id, is_dirty)?;
+        flushed?;
+        self.last_page_id = new_page_id;
+        self.chain.push(new_page_id);
+        if let Some(free_space) = &mut self.free_space {
+            free_space.push(TablePage::EMPTY_FREE_SPACE);
         }
+        self.page_cnt += 1;
+        Ok(new_page_id)
     }
 
-    /// Fetches the tuple payload corresponding to the given record ID from the table heap.
-    pub fn delete_tuple(&self, rid: &RecordId) -> Result<()> {
-        let page = self.fetch_page_handle(&rid.page_id());
+    /// Tombstones the tuple at the given record id, retiring its slot, see
+    /// [`TablePage::retire_tuple`]. Its payload is reclaimed once its page is compacted, by an
+    /// insert needing the room or by a vacuum, but its slot is never reused by an insert: the
+    /// delete may roll back an insert, which isn't logged, so that recovery may redo the insert
+    /// at the slot before undoing it again, and a later insert logged at the same slot would
+    /// then find it taken.
+    pub fn delete_tuple(&mut self, rid: &RecordId) -> Result<()> {
+        let page = self.fetch_page_handle(&rid.page_id())?;
         let mut page_guard = page.write()?;
 
-        page_guard.update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), rid)
+        let stub = page_guard.overflow(rid)?;
+        page_guard.retire_tuple(rid)?;
+        let free = page_guard.free_space_compacted();
+        drop(page_guard);
+        drop(page);
+        self.set_free_space(rid.page_id(), free);
+        self.free_overflow(stub)
+    }
+
+    pub fn get_tuple_metadata(&self, rid: &RecordId) -> Result<TupleMetadata> {
+        let page = self.fetch_page_handle(&rid.page_id())?;
+        let page_guard = page.read()?;
+        page_guard.get_tuple_metadata(rid)
+    }
+
+    /// Sets the metadata of the tuple at `rid`. Tombstoning a tuple stored on overflow pages
+    /// frees them, since the page may reclaim its stub whenever it is compacted.
+    pub fn update_tuple_metadata(&self, rid: &RecordId, metadata: &TupleMetadata) -> Result<()> {
+        let page = self.fetch_page_handle(&rid.page_id())?;
+        let mut page_guard = page.write()?;
+        let tombstoned = metadata.is_deleted() && !page_guard.get_tuple_metadata(rid)?.is_deleted();
+        let stub = page_guard.overflow(rid)?.filter(|_| tombstoned);
+        page_guard.update_tuple_metadata(metadata, rid)?;
+        drop(page_guard);
+        drop(page);
+        self.free_overflow(stub)
     }
 
+    /// Returns the tuple at `rid`, read back from its overflow pages if it is stored on some.
     pub fn get_tuple(&self, rid: &RecordId) -> Result<Tuple> {
-        let page = self.fetch_page_handle(&rid.page_id());
+        let page = self.fetch_page_handle(&rid.page_id())?;
         let page_guard = page.read()?;
-        page_guard.get_tuple(rid)
+        let tuple = page_guard.get_tuple(rid)?;
+        let stub = page_guard.overflow(rid)?;
+        drop(page_guard);
+        drop(page);
+        match stub {
+            Some(stub) => self.read_overflow(stub),
+            None => Ok(tuple),
+        }
     }
 
-    pub fn insert_tuple(&mut self, tuple: Tuple) -> Result<RecordId> {
-        let _ = self.get_page_slot(&tuple).unwrap_or_else(|| {
-            // tuple payload won't fit in the existing page, make a new page
-            self.create_new_page().expect(NEW_PAGE_ERR_MSG);
-            self.get_page_slot(&tuple).expect(TUPLE_DOESNT_FIT_MSG)
-        });
+    /// Returns the record id that inserting `tuple` would be assigned: on the first page with
+    /// room for it, or on a new page if none has. Lets callers log an insert before performing it.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If a page to look for room on, or the new page, has no
+    ///   frame in the buffer pool.
+    pub fn next_record_id(&mut self, tuple: &Tuple) -> Result<RecordId> {
+        let tuple = &Self::placeholder(tuple);
+        let page_id = match self.page_with_room(tuple)? {
+            Some(page_id) => page_id,
+            None => {
+                // tuple payload won't fit in the existing pages, make a new page
+                self.create_new_page()?;
+                self.get_page_slot(tuple)?
+                    .ok_or_else(|| Error::InvalidData(TUPLE_DOESNT_FIT_MSG.to_string()))?;
+                self.last_page_id
+            }
+        };
 
-        let page = self.fetch_page_handle(&self.last_page_id);
-        let mut page_guard = page.write().unwrap();
-        let metadata = TupleMetadata::new(false);
+        let page = self.fetch_page_handle(&page_id)?;
+        let slot_id = page.read()?.slot_for(tuple);
+        Ok(RecordId::new(page_id, slot_id))
+    }
+
+    /// Inserts `tuple` with the given metadata on the first page with room for it, see
+    /// [`Self::next_record_id`].
+    pub fn insert_tuple(&mut self, metadata: TupleMetadata, tuple: Tuple) -> Result<RecordId> {
+        let rid = self.next_record_id(&tuple)?;
+        let stored = self.store(tuple)?;
+        self.insert_stored(rid.page_id(), stored, metadata)
+    }
 
-        let slot_id = page_guard
-            .insert_tuple(metadata, tuple)
-            .expect(TUPLE_DOESNT_FIT_MSG);
-        Ok(RecordId::new(self.last_page_id, slot_id))
+    /// Inserts `tuple` with the given metadata at the end of the heap, linking a new page onto the
+    /// chain if it doesn't fit in the last one. Pages before the last are never looked at, and
+    /// every page the heap is extended by is filled up regardless of the fill factor, which suits
+    /// loading a heap whose tuples are written once.
+    pub fn append_tuple(&mut self, metadata: TupleMetadata, tuple: Tuple) -> Result<RecordId> {
+        if self.get_page_slot(&Self::placeholder(&tuple))?.is_none() {
+            self.create_new_page()?;
+        }
+        let stored = self.store(tuple)?;
+        self.insert_stored(self.last_page_id, stored, metadata)
     }
 
-    pub fn update_tuple(&self, rid: &RecordId, payload: Tuple) -> Result<()> {
-        let page_id = rid.page_id();
+    /// Inserts a tuple readied by [`Self::store`] on the page `page_id`, recording the page's
+    /// free space left. The tuple's overflow pages, if any, are freed again if it can't be
+    /// inserted, e.g. because it doesn't fit on the page.
+    fn insert_stored(
+        &mut self,
+        page_id: PageId,
+        stored: Stored,
+        metadata: TupleMetadata,
+    ) -> Result<RecordId> {
+        let inserted = (|| {
+            let page = self.fetch_page_handle(&page_id)?;
+            let mut page_guard = page.write()?;
+            let slot_id = (stored.clone().insert_into(&mut page_guard, metadata))
+                .ok_or_else(|| Error::InvalidData(TUPLE_DOESNT_FIT_MSG.to_string()))?;
+            Ok((slot_id, page_guard.free_space_compacted()))
+        })();
+        match inserted {
+            Ok((slot_id, free)) => {
+                self.set_free_space(page_id, free);
+                Ok(RecordId::new(page_id, slot_id))
+            }
+            Err(err) => {
+                self.free_overflow(stored.stub())?;
+                Err(err)
+            }
+        }
+    }
 
-        let page = self.fetch_page_handle(&page_id);
-        let mut page_guard = page.write().unwrap();
-        let metadata = page_guard.get_tuple_metadata(rid)?;
+    /// Inserts `tuple` at `rid`, which must be the slot its page would assign it, see
+    /// [`TablePage::slot_for`]. Used to redo a logged insert on the page it was originally made
+    /// to.
+    pub fn insert_tuple_at(&mut self, rid: &RecordId, tuple: Tuple) -> Result<()> {
+        let page = self.fetch_page_handle(&rid.page_id())?;
+        let mut page_guard = page.write()?;
+        let slot_id = page_guard.slot_for(&Self::placeholder(&tuple));
+        if slot_id != rid.slot_id() {
+            return Err(Error::InvalidData(format!(
+                "cannot insert at {}, the free slot of its page for it is {slot_id}",
+                rid.to_string()
+            )));
+        }
+        let stored = self.store(tuple)?;
+        let inserted = stored.clone().insert_into(&mut page_guard, TupleMetadata::new(false));
+        if inserted.is_none() {
+            drop(page_guard);
+            drop(page);
+            self.free_overflow(stored.stub())?;
+            return Err(Error::InvalidData(TUPLE_DOESNT_FIT_MSG.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Updates the tuple at `rid`, returning the record id the new payload is stored at. That is
+    /// `rid` unless the payload grew out of the page's free space, see
+    /// [`TablePage::update_tuple`], in which case it is inserted anew on the first page with
+    /// room for it, see [`Self::insert_tuple`], and the old tuple deleted, see
+    /// [`Self::delete_tuple`]. The old tuple's overflow pages, if any, are freed.
+    pub fn update_tuple(&mut self, rid: &RecordId, payload: Tuple) -> Result<RecordId> {
+        let stored = self.store(payload)?;
+        let updated = (|| {
+            let page = self.fetch_page_handle(&rid.page_id())?;
+            let mut page_guard = page.write()?;
+            let metadata = page_guard.get_tuple_metadata(rid)?;
+            let old_stub = page_guard.overflow(rid)?;
+            stored.update_in(&mut page_guard, metadata, rid)?;
+            Ok(old_stub)
+        })();
+        let moved = match updated {
+            Ok(old_stub) => {
+                self.free_overflow(old_stub)?;
+                return Ok(rid.clone());
+            }
+            Err(Error::TupleTooLarge { .. }) => (|| {
+                let metadata = self.get_tuple_metadata(rid)?;
+                Ok((metadata, self.next_record_id(&stored.placeholder())?))
+            })(),
+            Err(err) => Err(err),
+        };
+        let (metadata, new_rid) = match moved {
+            Ok(moved) => moved,
+            Err(err) => {
+                self.free_overflow(stored.stub())?;
+                return Err(err);
+            }
+        };
+        let new_rid = self.insert_stored(new_rid.page_id(), stored, metadata)?;
+        self.delete_tuple(rid)?;
+        Ok(new_rid)
+    }
+
+    /// Tombstones the live versions `removable` selects and compacts every page to reclaim the
+    /// payloads of all tombstones. Pages left without live versions are taken out of the chain
+    /// and handed back to the buffer pool, except for the first and last ones, which anchor it.
+    /// Returns the record ids of the removed versions.
+    pub fn vacuum(
+        &mut self,
+        removable: &dyn Fn(&TupleMetadata) -> bool,
+    ) -> Result<(Vec<RecordId>, VacuumStats)> {
+        let mut removed = Vec::new();
+        let mut stats = VacuumStats::default();
+        let mut prev_page_id = INVALID_PID;
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PID {
+            let page = self.fetch_page_handle(&page_id)?;
+            let mut page_guard = page.write()?;
+            let mut stubs = Vec::new();
+            for slot_id in 0..page_guard.next_slot_id() {
+                let rid = RecordId::new(page_id, slot_id);
+                let metadata = page_guard.get_tuple_metadata(&rid)?;
+                if !metadata.is_deleted() && removable(&metadata) {
+                    page_guard
+                        .update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), &rid)?;
+                    removed.push(rid.clone());
+                }
+                // Compacting reclaims the stubs of all tombstones, so their chains go too.
+                if page_guard.get_tuple_metadata(&rid)?.is_deleted() {
+                    stubs.extend(page_guard.overflow(&rid)?);
+                }
+            }
+            stats.bytes += page_guard.compact() as u64;
+            let emptied = page_guard.tuple_count() == 0;
+            let next_page_id = page_guard.get_next_page_id();
+            drop(page_guard);
+            // Unpinned, the page can be deleted from the buffer pool if it was emptied.
+            drop(page);
+            for stub in stubs {
+                self.free_overflow(Some(stub))?;
+            }
 
-        // If the tuple has a variable length field and the size of the updated tuple is different
-        // from the existing tuple, delete the existing tuple and insert the new tuple.
-        let existing_size = page_guard.get_tuple(rid)?.data.len();
-        match existing_size == payload.data.len() {
-            true => page_guard.update_tuple_in_place_unchecked(metadata, payload, rid),
-            false => {
-                page_guard
-                    .update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), rid)?;
-                page_guard.insert_tuple(TupleMetadata::new(false), payload);
-                Ok(())
+            if emptied && page_id != self.first_page_id && page_id != self.last_page_id {
+                self.unlink_page(prev_page_id, page_id, next_page_id)?;
+                stats.pages += 1;
+            } else {
+                prev_page_id = page_id;
             }
+            page_id = next_page_id;
         }
+        stats.versions = removed.len() as u64;
+        self.free_space = None;
+        Ok((removed, stats))
     }
 
+    /// Builds a heap with the same schema and read-ahead as this one, on a fresh page chain, and
+    /// appends the given versions to it in order, so that every page but the last is filled up,
+    /// see [`Self::append_tuple`].
+    /// Returns the new heap along with the record id each version was given. The first error
+    /// among the versions, or appending them, is returned instead, the new heap's pages freed.
+    pub fn rebuild(
+        &self,
+        versions: impl IntoIterator<Item = Result<(TupleMetadata, Tuple)>>,
+    ) -> Result<(TableHeap, Vec<RecordId>)> {
+        let mut heap = TableHeap::new(self.schema(), &self.buffer_pool_manager);
+        heap.set_readahead(self.readahead);
+        let rids = (versions.into_iter())
+            .map(|version| version.and_then(|(metadata, tuple)| heap.append_tuple(metadata, tuple)))
+            .collect::<Result<_>>();
+        match rids {
+            Ok(rids) => Ok((heap, rids)),
+            Err(err) => {
+                heap.free_pages();
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the number of bytes the payloads on the heap's pages take up, tombstoned ones
+    /// included until they are compacted away.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If a page of the chain has no frame to be read into.
+    pub fn payload_bytes(&self) -> Result<u64> {
+        let mut bytes = 0;
+        for page_id in &self.chain {
+            let page = self.fetch_page_handle(page_id)?;
+            let page_guard = page.read()?;
+            bytes += (page_guard.tuple_info.iter()).map(|info| info.size_bytes as u64).sum::<u64>();
+        }
+        Ok(bytes)
+    }
+
+    /// Deletes every page of the heap from the buffer pool, which hands them back to the disk
+    /// manager, along with the overflow pages of its tuples. Returns the number of pages of the
+    /// chain deleted: one something still has pinned is skipped.
+    pub fn free_pages(self) -> u64 {
+        let bpm = &self.buffer_pool_manager;
+        let mut freed = 0;
+        for page_id in &self.chain {
+            // Only pages in the pool can be deleted, so bring each in first.
+            let Ok(page) = bpm.fetch_page(page_id) else {
+                continue;
+            };
+            let stubs: Vec<_> = {
+                let page_guard = page.read().unwrap();
+                let rids = (0..page_guard.next_slot_id()).map(|slot| RecordId::new(*page_id, slot));
+                rids.filter_map(|rid| page_guard.overflow(&rid).ok().flatten()).collect()
+            };
+            for stub in stubs {
+                self.free_overflow(Some(stub)).ok();
+            }
+            if bpm.unpin_page(page_id, false).is_ok() && bpm.delete_page(*page_id) == Ok(true) {
+                freed += 1;
+            }
+        }
+        freed
+    }
+
+    /// Takes a page out of the chain by linking its predecessor to its successor, and deletes it
+    /// from the buffer pool.
+    fn unlink_page(&mut self, prev_page_id: PageId, page_id: PageId, next_page_id: PageId) -> Result<()> {
+        let bpm = Arc::clone(&self.buffer_pool_manager);
+        let prev = bpm.fetch_page(&prev_page_id)?;
+        prev.write()?.set_next_page_id(next_page_id);
+        // Like in `create_new_page`, the link isn't logged, so it must reach disk right away.
+        let flushed = bpm.force_log_and_flush(&prev_page_id);
+        let is_dirty = prev.read()?.get_is_dirty();
+        bpm.unpin_page(&prev_page_id, is_dirty)?;
+        flushed?;
+        // The page stays cached while something still has it pinned, and there's nothing to
+        // delete if it was already evicted.
+        bpm.delete_page(page_id).ok();
+        self.chain.retain(|id| *id != page_id);
+        self.page_cnt -= 1;
+        Ok(())
+    }
+
+    /// Stamps the page with the LSN of the log record describing the latest change made to it.
+    pub fn set_page_lsn(&self, page_id: &PageId, lsn: Lsn) -> Result<()> {
+        let page = self.fetch_page_handle(page_id)?;
+        page.write()?.set_lsn(lsn);
+        Ok(())
+    }
+
+    /// Returns the LSN of the log record describing the latest change made to the page.
+    pub fn page_lsn(&self, page_id: &PageId) -> Result<Lsn> {
+        let page = self.fetch_page_handle(page_id)?;
+        let lsn = page.read()?.lsn();
+        Ok(lsn)
+    }
+
+    /// Returns an iterator over the heap's live tuples, in page chain order. Only the page it is
+    /// on is kept pinned.
     pub fn iter(&self) -> TableHeapIterator {
-        let current_page_id = self.first_page_id;
-        let current_page_iterator = TablePage::iter(self.fetch_page_handle(&current_page_id));
+        let mut iter = TableHeapIterator {
+            heap_file: self,
+            reverse: false,
+            position: 0,
+            prefetch_at: 0,
+            current_page: None,
+            current_page_iterator: None,
+            error: None,
+        };
+        iter.enter_page(Some(0));
+        iter
+    }
 
-        TableHeapIterator {
+    /// Returns an iterator over the heap's live tuples in reverse: from the last page of the chain
+    /// to the first, and on each page from the highest slot down. Only the page it is on is kept
+    /// pinned.
+    pub fn iter_rev(&self) -> TableHeapIterator<'_> {
+        let last = self.chain.len().checked_sub(1);
+        let mut iter = TableHeapIterator {
             heap_file: self,
-            current_page_id,
-            current_page_iterator,
+            reverse: true,
+            position: last.unwrap_or(0),
+            prefetch_at: last.unwrap_or(0),
+            current_page: None,
+            current_page_iterator: None,
+            error: None,
+        };
+        iter.enter_page(last);
+        iter
+    }
+
+    /// Returns the ids of the heap's pages, in chain order.
+    pub fn page_ids(&self) -> Vec<PageId> {
+        self.chain.clone()
+    }
+
+    /// Returns the non-tombstoned tuples on one page of the heap, along with their metadata.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If the page, or an overflow page of one of its tuples, has
+    ///   no frame to be read into.
+    pub fn page_versions(&self, page_id: &PageId) -> Result<Vec<(RecordId, TupleMetadata, Tuple)>> {
+        let page = self.fetch_page_handle(page_id)?;
+        let mut iter = TablePage::iter(Arc::clone(&page));
+        std::iter::from_fn(|| iter.next_with_metadata())
+            .map(|(rid, metadata, tuple)| {
+                let tuple = self.resolve(&page, &rid, tuple)?;
+                Ok((rid, metadata, tuple))
+            })
+            .collect()
+    }
+
+    /// Returns the record ids and metadata of the non-tombstoned tuples on one page of the heap,
+    /// read from the page's slot array without copying out the tuples.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If the page has no frame to be read into.
+    pub fn page_metadata(&self, page_id: &PageId) -> Result<Vec<(RecordId, TupleMetadata)>> {
+        let page = self.fetch_page_handle(page_id)?;
+        let page = page.read()?;
+        let mut metadata = Vec::with_capacity(page.tuple_cnt as usize);
+        for (slot, info) in page.tuple_info.iter().enumerate() {
+            if !info.metadata.is_deleted() {
+                metadata.push((RecordId::new(*page_id, slot as u16), info.metadata));
+            }
         }
+        Ok(metadata)
     }
 
-    pub(crate) fn fetch_page_handle(&self, page_id: &PageId) -> TablePageHandle {
-        let mut bpm = self
-            .buffer_pool_manager
-            .write()
-            .expect(COULD_NOT_UNWRAP_BPM_MSG);
-        bpm.fetch_page(page_id).unwrap()
+    /// Like [`Self::iter`], but also yields each tuple's metadata.
+    pub fn versions(&self) -> TableHeapVersions<'_> {
+        TableHeapVersions { inner: self.iter() }
     }
 
-    pub(crate) fn get_page_slot(&self, payload: &Tuple) -> Option<u16> {
-        let page = self.fetch_page_handle(&self.last_page_id);
-        let offset = page.read().unwrap().get_next_tuple_offset(payload);
-        offset
+    /// Like [`Self::iter_rev`], but also yields each tuple's metadata.
+    pub fn versions_rev(&self) -> TableHeapVersions<'_> {
+        TableHeapVersions {
+            inner: self.iter_rev(),
+        }
+    }
+
+    /// Fetches a page of the heap, which stays pinned until the returned handle is dropped.
+    ///
+    /// # Errors
+    /// - [`Error::NoEvictableFrame`]: If the page has no frame to be read into.
+    pub(crate) fn fetch_page_handle(&self, page_id: &PageId) -> Result<PinnedTablePage<'_>> {
+        self.fetch_page_handle_with(page_id, AccessType::Lookup)
+    }
+
+    /// Like [`Self::fetch_page_handle`], recording the access as the given type.
+    fn fetch_page_handle_with(
+        &self,
+        page_id: &PageId,
+        access_type: AccessType,
+    ) -> Result<PinnedTablePage<'_>> {
+        Ok(PinnedTablePage {
+            buffer_pool_manager: &self.buffer_pool_manager,
+            page_id: *page_id,
+            page: (self.buffer_pool_manager).fetch_page_with_access(page_id, access_type)?,
+        })
+    }
+
+    /// Returns the first page of the chain with room for `tuple`, see [`Self::has_room`], if any.
+    /// Pages are looked at only if the free space map says they have room, and the map's entries
+    /// are corrected from the pages looked at.
+    fn page_with_room(&mut self, tuple: &Tuple) -> Result<Option<PageId>> {
+        let size = tuple.data.len();
+        let mut free_space = match self.free_space.take() {
+            Some(free_space) => free_space,
+            None => self.measure_free_space()?,
+        };
+        let mut found = None;
+        for (position, page_id) in self.chain.iter().enumerate() {
+            if !self.has_room(free_space[position], size) {
+                continue;
+            }
+            let page = self.fetch_page_handle(page_id)?;
+            free_space[position] = page.read()?.free_space_compacted();
+            if self.has_room(free_space[position], size) {
+                found = Some(*page_id);
+                break;
+            }
+        }
+        self.free_space = Some(free_space);
+        Ok(found)
+    }
+
+    /// Returns whether a page with `free` bytes of free space has room for a tuple of `size`
+    /// bytes with the table's fill factor, see [`TablePage::has_room`]. The heap's pages keep
+    /// the default fill factor of 100, which lets [`Self::append_tuple`] fill them up, so the
+    /// table's is taken from its schema and never from the pages.
+    fn has_room(&self, free: usize, size: usize) -> bool {
+        TablePage::has_room(free, size, self.schema.fill_factor())
+    }
+
+    /// Reads the free space of every page of the chain.
+    fn measure_free_space(&self) -> Result<Vec<usize>> {
+        self.chain
+            .iter()
+            .map(|page_id| Ok(self.fetch_page_handle(page_id)?.read()?.free_space_compacted()))
+            .collect()
+    }
+
+    /// Records the free space of a page in the free space map, if it has been built.
+    fn set_free_space(&mut self, page_id: PageId, free: usize) {
+        let Some(free_space) = &mut self.free_space else {
+            return;
+        };
+        if let Some(position) = self.chain.iter().rposition(|id| *id == page_id) {
+            free_space[position] = free;
+        }
+    }
+
+    /// Returns whether `tuple` is too large for an empty page, see [`TablePage::fits`], and so
+    /// is stored on overflow pages.
+    fn overflows(tuple: &Tuple) -> bool {
+        tuple.data.len() + TablePage::SLOT_BYTES > TablePage::EMPTY_FREE_SPACE
+    }
+
+    /// Returns a tuple of the size `tuple` takes up on a page, to find it a page and slot with:
+    /// itself, or one of the size of a stub if it overflows.
+    fn placeholder(tuple: &Tuple) -> Cow<'_, Tuple> {
+        match Self::overflows(tuple) {
+            true => Cow::Owned(Tuple::from(vec![0; OverflowStub::BYTES])),
+            false => Cow::Borrowed(tuple),
+        }
+    }
+
+    /// Readies `tuple` to be put on a page, writing it to overflow pages if it overflows.
+    fn store(&self, tuple: Tuple) -> Result<Stored> {
+        match Self::overflows(&tuple) {
+            true => Ok(Stored::Overflow(self.write_overflow(&tuple.data)?)),
+            false => Ok(Stored::Inline(tuple)),
+        }
+    }
+
+    /// Writes `payload` to a new chain of overflow pages, back to front so that each page is
+    /// written knowing the next, and returns the stub pointing at it. The pages written are
+    /// freed again if one fails to be.
+    fn write_overflow(&self, payload: &[u8]) -> Result<OverflowStub> {
+        let mut page_ids = Vec::new();
+        let mut next_page_id = INVALID_PID;
+        for piece in payload.chunks(OverflowPage::CAPACITY).rev() {
+            match self.write_overflow_page(piece, next_page_id) {
+                Ok(page_id) => next_page_id = page_id,
+                Err(err) => {
+                    self.buffer_pool_manager.delete_pages(&page_ids).ok();
+                    return Err(err);
+                }
+            }
+            page_ids.push(next_page_id);
+        }
+        Ok(OverflowStub {
+            first_page_id: next_page_id,
+            len: payload.len() as u32,
+        })
+    }
+
+    /// Writes a piece of a payload to a new overflow page linked to `next_page_id`, and flushes
+    /// it, since it isn't logged.
+    fn write_overflow_page(&self, piece: &[u8], next_page_id: PageId) -> Result<PageId> {
+        let bpm = &self.buffer_pool_manager;
+        let page = bpm.new_page_as(OverflowPage::new)?;
+        let page_id = {
+            let mut page = page.write()?;
+            page.set_data(piece);
+            page.set_next_page_id(next_page_id);
+            *page.page_id()
+        };
+        let flushed = bpm.force_log_and_flush(&page_id);
+        let is_dirty = page.read()?.get_is_dirty();
+        bpm.unpin_page(&page_id, is_dirty)?;
+        flushed?;
+        Ok(page_id)
+    }
+
+    /// Returns the ids of the overflow pages of the chain `stub` points at, in order, along with
+    /// the payload they hold if `read` is set.
+    fn overflow_chain(&self, stub: OverflowStub, read: bool) -> Result<(Vec<PageId>, Vec<u8>)> {
+        let bpm = &self.buffer_pool_manager;
+        let (mut page_ids, mut payload) = (Vec::new(), Vec::new());
+        let mut page_id = stub.first_page_id;
+        while page_id != INVALID_PID {
+            let page = bpm.fetch_page_as::<OverflowPage>(&page_id)?;
+            let page_guard = page.read()?;
+            if read {
+                payload.extend_from_slice(page_guard.data());
+            }
+            page_ids.push(page_id);
+            let next_page_id = page_guard.next_page_id();
+            drop(page_guard);
+            bpm.unpin_page(&page_id, false)?;
+            page_id = next_page_id;
+        }
+        Ok((page_ids, payload))
+    }
+
+    /// Reads back the tuple stored on the overflow pages `stub` points at.
+    fn read_overflow(&self, stub: OverflowStub) -> Result<Tuple> {
+        let (_, payload) = self.overflow_chain(stub, true)?;
+        if payload.len() != stub.len as usize {
+            return Err(Error::InvalidData(format!(
+                "the overflow pages from {} hold {} bytes rather than {}",
+                stub.first_page_id,
+                payload.len(),
+                stub.len
+            )));
+        }
+        Ok(Tuple::from(payload))
+    }
+
+    /// Deletes the overflow pages `stub` points at, if any, from the buffer pool and from disk.
+    /// They are left be if something has any of them pinned.
+    fn free_overflow(&self, stub: Option<OverflowStub>) -> Result<()> {
+        let Some(stub) = stub else {
+            return Ok(());
+        };
+        let (page_ids, _) = self.overflow_chain(stub, false)?;
+        self.buffer_pool_manager.delete_pages(&page_ids)?;
+        Ok(())
+    }
+
+    /// Returns `tuple`, read from `rid` on `page`, or the tuple stored on overflow pages it is
+    /// the stub of.
+    fn resolve(&self, page: &TablePageHandle, rid: &RecordId, tuple: Tuple) -> Result<Tuple> {
+        let stub = page.read()?.overflow(rid)?;
+        match stub {
+            Some(stub) => self.read_overflow(stub),
+            None => Ok(tuple),
+        }
+    }
+
+    /// Returns the offset `payload` would be put at on the last page of the chain, if it fits.
+    pub(crate) fn get_page_slot(&self, payload: &Tuple) -> Result<Option<u16>> {
+        let page = self.fetch_page_handle(&self.last_page_id)?;
+        let offset = page.read()?.get_next_tuple_offset(payload);
+        Ok(offset)
+    }
+}
+
+/// The form a tuple takes on its page: the tuple itself, or the stub of the overflow pages it
+/// was written to, see [`TableHeap::store`].
+#[derive(Clone)]
+enum Stored {
+    Inline(Tuple),
+    Overflow(OverflowStub),
+}
+
+impl Stored {
+    fn insert_into(self, page: &mut TablePage, metadata: TupleMetadata) -> Option<u16> {
+        match self {
+            Self::Inline(tuple) => page.insert_tuple(metadata, tuple),
+            Self::Overflow(stub) => page.insert_overflow(metadata, stub),
+        }
+    }
+
+    fn update_in(
+        &self,
+        page: &mut TablePage,
+        metadata: TupleMetadata,
+        rid: &RecordId,
+    ) -> Result<()> {
+        match self {
+            Self::Inline(tuple) => page.update_tuple(metadata, tuple.clone(), rid),
+            Self::Overflow(stub) => page.update_overflow(metadata, *stub, rid),
+        }
+    }
+
+    /// Returns a tuple of the size this takes up on a page, see [`TableHeap::placeholder`].
+    fn placeholder(&self) -> Cow<'_, Tuple> {
+        match self {
+            Self::Inline(tuple) => Cow::Borrowed(tuple),
+            Self::Overflow(_) => Cow::Owned(Tuple::from(vec![0; OverflowStub::BYTES])),
+        }
+    }
+
+    fn stub(&self) -> Option<OverflowStub> {
+        match self {
+            Self::Inline(_) => None,
+            Self::Overflow(stub) => Some(*stub),
+        }
+    }
+}
+
+/// A table page pinned in the buffer pool, which is unpinned when this is dropped, dirty if the
+/// page was changed in the meantime. Dereferences to the page's handle; its latches must be
+/// released before this is dropped.
+pub(crate) struct PinnedTablePage<'a> {
+    buffer_pool_manager: &'a BufferPoolManager,
+    page_id: PageId,
+    page: TablePageHandle,
+}
+
+impl Deref for PinnedTablePage<'_> {
+    type Target = TablePageHandle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.page
+    }
+}
+
+impl Drop for PinnedTablePage<'_> {
+    fn drop(&mut self) {
+        let is_dirty = self.page.read().unwrap().get_is_dirty();
+        self.buffer_pool_manager
+            .unpin_page(&self.page_id, is_dirty)
+            .ok();
     }
 }
 
-/// Iterator that sequentially iterates over all the tuples in a heap file.
+/// Iterator that sequentially iterates over all the tuples in a heap file, forwards or in reverse.
 /// It does not outlive the lifetime of its underlying heap file.
+///
+/// The pages are fetched as `Scan` accesses, and the iterator reads the pages up to the heap's
+/// read-ahead distance beyond the one it is on, in the direction it goes, into the buffer pool,
+/// unpinned, so that they are there by the time it gets to them. It does so in batches, each time
+/// it has used up half of the pages it read ahead, and the buffer pool holds back on reading ahead
+/// when it is short of unpinned frames, see [`BufferPoolManager::prefetch_pages`].
 pub struct TableHeapIterator<'a> {
     heap_file: &'a TableHeap,
-    current_page_id: PageId,
-    current_page_iterator: TablePageIterator,
+    /// Whether the iterator walks the page chain and each page's slots backwards.
+    reverse: bool,
+    /// The position in the page chain of the page being iterated over.
+    position: usize,
+    /// The position in the page chain at which to read the next batch of pages ahead.
+    prefetch_at: usize,
+    /// Keeps the page being iterated over pinned, until the iterator moves past it.
+    current_page: Option<PinnedTablePage<'a>>,
+    current_page_iterator: Option<PageTuples>,
+    /// Why the page the iterator moved on to couldn't be pinned, e.g. as every frame of the
+    /// buffer pool is, which it yields before it is done.
+    error: Option<Error>,
 }
 
-impl Iterator for TableHeapIterator<'_> {
-    type Item = (RecordId, Tuple);
+/// The tuples on the page a [`TableHeapIterator`] is on, in the direction it goes.
+enum PageTuples {
+    Forward(TablePageIterator),
+    Reverse(TablePageRevIterator),
+}
 
-    /// Returns `Some(tuple)` if a tuple exists at the iterator's current slot in the page, and
-    /// `None` if the iterator is at the end of the page and there aren't anymore tuples.
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.current_page_id <= self.heap_file.last_page_id {
+impl PageTuples {
+    fn next_with_metadata(&mut self) -> Option<(RecordId, TupleMetadata, Tuple)> {
+        match self {
+            Self::Forward(iter) => iter.next_with_metadata(),
+            Self::Reverse(iter) => iter.next_with_metadata(),
+        }
+    }
+}
+
+impl TableHeapIterator<'_> {
+    /// Returns the next non-tombstoned tuple along with its metadata, or the error a page
+    /// couldn't be read with, after which the iterator is done.
+    fn next_with_metadata(&mut self) -> Option<Result<(RecordId, TupleMetadata, Tuple)>> {
+        while let Some(page_iterator) = &mut self.current_page_iterator {
             // our page iterator produced a valid tuple!
-            if let Some(item) = self.current_page_iterator.next() {
-                return Some(item);
-            }
-            let next_page_id = self.current_page_iterator.next_page_id();
-            match next_page_id {
-                // that was the last page in the heap file
-                INVALID_PID => break,
-                // or, there's another page to iterate through!
-                _ => {
-                    self.current_page_id = next_page_id;
-                    self.current_page_iterator =
-                        TablePage::iter(self.heap_file.fetch_page_handle(&next_page_id));
-                }
+            if let Some((rid, metadata, tuple)) = page_iterator.next_with_metadata() {
+                let page = self.current_page.as_ref().unwrap();
+                let tuple = self.heap_file.resolve(page, &rid, tuple);
+                return Some(tuple.map(|tuple| (rid, metadata, tuple)));
+            }
+            // the page is done with, so unpin it before pinning the next one, if there's any.
+            let next = match self.reverse {
+                false => Some(self.position + 1),
+                true => self.position.checked_sub(1),
+            };
+            self.enter_page(next);
+        }
+        self.error.take().map(Err)
+    }
+
+    /// Moves on to the page at the given position in the chain, pinning it and reading the
+    /// pages beyond it ahead if it is time to. Past either end of the chain, the iterator is done.
+    fn enter_page(&mut self, position: Option<usize>) {
+        self.current_page_iterator = None;
+        self.current_page = None;
+        let heap_file = self.heap_file;
+        let Some((position, page_id)) =
+            position.and_then(|position| Some((position, heap_file.chain.get(position)?)))
+        else {
+            return;
+        };
+        self.position = position;
+        let page = match heap_file.fetch_page_handle_with(page_id, AccessType::Scan) {
+            Ok(page) => page,
+            Err(err) => {
+                self.error = Some(err);
+                return;
+            }
+        };
+        self.current_page_iterator = Some(match self.reverse {
+            false => PageTuples::Forward(TablePage::iter(Arc::clone(&page))),
+            true => PageTuples::Reverse(TablePage::iter_rev(Arc::clone(&page))),
+        });
+        self.current_page = Some(page);
+
+        let readahead = heap_file.readahead;
+        if readahead == 0 {
+            return;
+        }
+        let ahead = match self.reverse {
+            false if position >= self.prefetch_at => {
+                let end = heap_file.chain.len().min(position + 1 + readahead);
+                self.prefetch_at = position + readahead.div_ceil(2);
+                &heap_file.chain[position + 1..end]
+            }
+            true if position <= self.prefetch_at => {
+                let start = position.saturating_sub(readahead);
+                self.prefetch_at = position.saturating_sub(readahead.div_ceil(2));
+                &heap_file.chain[start..position]
             }
+            _ => return,
+        };
+        if !ahead.is_empty() {
+            heap_file.buffer_pool_manager.prefetch_pages(ahead);
         }
-        None
+    }
+}
+
+impl Iterator for TableHeapIterator<'_> {
+    type Item = Result<(RecordId, Tuple)>;
+
+    /// Returns `Some(tuple)` if a tuple exists at the iterator's current slot in the page, and
+    /// `None` if the iterator is at the end of the page and there aren't anymore tuples. A page
+    /// that can't be read yields its error instead, and ends the iteration.
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next_with_metadata()?;
+        Some(next.map(|(rid, _, tuple)| (rid, tuple)))
+    }
+}
+
+/// Iterator over all the tuple versions in a heap file, yielding each tuple's metadata alongside
+/// it so callers can decide which versions are visible.
+pub struct TableHeapVersions<'a> {
+    inner: TableHeapIterator<'a>,
+}
+
+impl Iterator for TableHeapVersions<'_> {
+    type Item = Result<(RecordId, TupleMetadata, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_with_metadata()
     }
 }
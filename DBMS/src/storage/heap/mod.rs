@@ -2,4 +2,4 @@ mod heap;
 #[cfg(test)]
 mod tests;
 
-pub use heap::{TableHeap, TableHeapIterator};
+pub use heap::{TableHeap, TableHeapIterator, TableHeapVersions};
@@ -1,11 +1,12 @@
-use crate::common::constants::NEW_PAGE_ERR_MSG;
-use crate::common::{utility, Result};
+use crate::common::constants::{INVALID_PID, NEW_PAGE_ERR_MSG};
+use crate::common::{utility, Error, Result};
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
 use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
-use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
 use crate::storage::heap::TableHeap;
 use crate::storage::page::{Page, RecordId, TablePage, TablePageHandle};
-use crate::storage::tuple::Row;
-use crate::types::Table;
+use crate::storage::tuple::{Row, Tuple, TupleMetadata};
+use crate::types::{DataType, Table};
 use rand::Rng;
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 
@@ -36,7 +37,10 @@ fn test_insert_tuple() {
 
     let tuple = create_row(&table_schema);
     let rid = heap_file
-        .insert_tuple(tuple.to_tuple(&table_schema).unwrap())
+        .insert_tuple(
+            TupleMetadata::new(false),
+            tuple.to_tuple(&table_schema).unwrap(),
+        )
         .unwrap();
 
     let current_page = get_current_page_handle(&heap_file);
@@ -61,7 +65,7 @@ fn test_insert_many_tuples() {
         &table_schema,
     );
     rows.iter().for_each(|(rid, tuple)| {
-        let page = heap_file.fetch_page_handle(&rid.page_id());
+        let page = heap_file.fetch_page_handle(&rid.page_id()).unwrap();
         let retrieved_tuple =
             get_tuple_from_page(&page.read().unwrap(), &table_schema, rid).unwrap();
         assert_eq!(*tuple, retrieved_tuple);
@@ -76,7 +80,10 @@ fn test_get_tuple() {
 
     let row = create_row(&table_schema);
     let rid = heap_file
-        .insert_tuple(row.to_tuple(&table_schema).unwrap())
+        .insert_tuple(
+            TupleMetadata::new(false),
+            row.to_tuple(&table_schema).unwrap(),
+        )
         .unwrap();
     assert_eq!(row, get_row(&heap_file, &table_schema, &rid).unwrap());
 }
@@ -105,7 +112,10 @@ fn test_update_tuple() {
 
     let tuple1 = create_row_with_seed(&table_schema, 1);
     let rid = heap_file
-        .insert_tuple(tuple1.to_tuple(&table_schema).unwrap())
+        .insert_tuple(
+            TupleMetadata::new(false),
+            tuple1.to_tuple(&table_schema).unwrap(),
+        )
         .unwrap();
     assert_eq!(tuple1, get_row(&heap_file, &table_schema, &rid).unwrap());
 
@@ -120,6 +130,84 @@ fn test_update_tuple() {
     assert_ne!(tuple1, tuple);
 }
 
+/// Test that a tuple keeps its record id when updated to a payload of another length, unless it
+/// no longer fits on its page.
+#[test]
+fn test_update_tuple_resized() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let rids: Vec<_> = (0..2).map(|_| insert_bytes(&mut heap_file, 50)).collect();
+    for len in [20, 80] {
+        let tuple = Tuple::from(vec![1; len]);
+        assert_eq!(rids[0], heap_file.update_tuple(&rids[0], tuple.clone()).unwrap());
+        assert_eq!(tuple, heap_file.get_tuple(&rids[0]).unwrap());
+    }
+    assert_eq!(Tuple::from(vec![0; 50]), heap_file.get_tuple(&rids[1]).unwrap());
+}
+
+/// Test that a tuple grown out of its full page is moved to a page with room for it, and the old
+/// version deleted.
+#[test]
+fn test_update_tuple_moves_off_full_page() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let mut rids = Vec::new();
+    while heap_file.num_pages() < 2 {
+        rids.push(insert_bytes(&mut heap_file, 100));
+    }
+    let first_page_id = heap_file.first_page_id;
+    assert_eq!(first_page_id, rids[0].page_id());
+
+    let tuple = Tuple::from(vec![1; 300]);
+    let rid = heap_file.update_tuple(&rids[0], tuple.clone()).unwrap();
+    assert_ne!(first_page_id, rid.page_id());
+    assert_eq!(tuple, heap_file.get_tuple(&rid).unwrap());
+    assert!(heap_file.get_tuple_metadata(&rids[0]).unwrap().is_deleted());
+    assert_eq!(rids.len(), heap_file.iter().count());
+}
+
+/// Test that a tuple three times the size of a page is written to overflow pages, read back
+/// whole, and that its overflow pages are freed once it is deleted.
+#[test]
+fn test_overflow_tuple() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let bpm = Arc::clone(&heap_file.buffer_pool_manager);
+    let small = insert_bytes(&mut heap_file, 50);
+    let allocated = bpm.disk_stats().allocated_pages;
+
+    let bytes = (0..3 * RUSTY_DB_PAGE_PAYLOAD_BYTES).map(|i| i as u8);
+    let large = Tuple::from(bytes.collect::<Vec<_>>());
+    let rid = heap_file.insert_tuple(TupleMetadata::new(false), large.clone()).unwrap();
+    assert_eq!(small.page_id(), rid.page_id());
+    assert_eq!(1, heap_file.num_pages());
+    assert_eq!(allocated + 4, bpm.disk_stats().allocated_pages);
+    assert_eq!(large, heap_file.get_tuple(&rid).unwrap());
+    let tuples: Vec<_> = heap_file.iter().map(|item| item.unwrap().1).collect();
+    assert_eq!(vec![Tuple::from(vec![0; 50]), large.clone()], tuples);
+    assert_eq!(large, heap_file.page_versions(&rid.page_id()).unwrap()[1].2);
+
+    heap_file.delete_tuple(&rid).unwrap();
+    assert!(heap_file.get_tuple(&rid).is_err());
+    assert_eq!(allocated, bpm.disk_stats().allocated_pages);
+    assert!(bpm.pinned_pages().is_empty());
+}
+
+/// Test that updating a tuple to or from one stored on overflow pages keeps its record id, and
+/// frees the overflow pages it no longer needs.
+#[test]
+fn test_update_tuple_overflow() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let bpm = Arc::clone(&heap_file.buffer_pool_manager);
+    let rid = insert_bytes(&mut heap_file, 50);
+    let allocated = bpm.disk_stats().allocated_pages;
+
+    let page_bytes = RUSTY_DB_PAGE_PAYLOAD_BYTES;
+    for (len, overflow_pages) in [(2 * page_bytes, 3), (5 * page_bytes / 2, 3), (40, 0)] {
+        let tuple = Tuple::from(vec![len as u8; len]);
+        assert_eq!(rid, heap_file.update_tuple(&rid, tuple.clone()).unwrap());
+        assert_eq!(tuple, heap_file.get_tuple(&rid).unwrap());
+        assert_eq!(allocated + overflow_pages, bpm.disk_stats().allocated_pages);
+    }
+}
+
 /// This test assumes that [`TableHeap::insert_tuple`] and [`TableHeap::get_tuple`] work as intended.
 #[test]
 fn test_delete_tuple() {
@@ -128,7 +216,10 @@ fn test_delete_tuple() {
 
     let tuple = create_row(&table_schema);
     let rid = heap_file
-        .insert_tuple(tuple.to_tuple(&table_schema).unwrap())
+        .insert_tuple(
+            TupleMetadata::new(false),
+            tuple.to_tuple(&table_schema).unwrap(),
+        )
         .unwrap();
     assert_eq!(tuple, get_row(&heap_file, &table_schema, &rid).unwrap());
 
@@ -152,7 +243,7 @@ fn test_iter() {
     // Iterator should output tuples in sequential order...
     rows.iter().for_each(|(_rid, row)| {
         assert_eq!(
-            Row::from_tuple(it.next().unwrap().1, &table_schema).unwrap(),
+            Row::from_tuple(it.next().unwrap().unwrap().1, &table_schema).unwrap(),
             *row
         )
     });
@@ -161,15 +252,474 @@ fn test_iter() {
     assert!(it.next().is_none());
 }
 
+/// Inserts enough rows to fill several pages, checking that they're linked into one chain, that
+/// iteration follows it, and that no page is left pinned.
+#[test]
+fn test_insert_across_pages() {
+    let mut heap_file = create_random_heap_file();
+    let table_schema = Arc::new(heap_file.schema().clone());
+
+    let mut rows = Vec::new();
+    while heap_file.num_pages() < 4 {
+        let row = create_row(&table_schema);
+        let tuple = row.to_tuple(&table_schema).unwrap();
+        let rid = heap_file
+            .insert_tuple(TupleMetadata::new(false), tuple)
+            .unwrap();
+        rows.push((rid, row));
+    }
+
+    // The pages rows went to, in insertion order, are the page chain.
+    let mut page_ids: Vec<PageId> = rows.iter().map(|(rid, _)| rid.page_id()).collect();
+    page_ids.dedup();
+    let mut chain = vec![heap_file.first_page_id];
+    while let Some(&page_id) = chain.last() {
+        let next_page_id = heap_file
+            .fetch_page_handle(&page_id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .get_next_page_id();
+        if next_page_id == INVALID_PID {
+            break;
+        }
+        chain.push(next_page_id);
+    }
+    assert_eq!(page_ids, chain);
+    assert_eq!(heap_file.last_page_id, *chain.last().unwrap());
+    assert_pages_unpinned(&heap_file, &chain);
+
+    // Only the page being iterated over is pinned.
+    let mut it = heap_file.iter();
+    let first_page_rows = page_ids[0];
+    let skip = rows
+        .iter()
+        .filter(|(rid, _)| rid.page_id() == first_page_rows)
+        .count();
+    for (rid, row) in rows.iter().take(skip + 1) {
+        let (it_rid, tuple) = it.next().unwrap().unwrap();
+        assert_eq!(
+            (rid, row),
+            (&it_rid, &Row::from_tuple(tuple, &table_schema).unwrap())
+        );
+    }
+    assert_pages_unpinned(&heap_file, &[chain[0], chain[2], chain[3]]);
+    assert_eq!(Some(1), pin_count(&heap_file, chain[1]));
+    drop(it);
+    assert_pages_unpinned(&heap_file, &chain);
+
+    let scanned: Vec<(RecordId, Row)> = heap_file
+        .iter()
+        .map(Result::unwrap)
+        .map(|(rid, tuple)| (rid, Row::from_tuple(tuple, &table_schema).unwrap()))
+        .collect();
+    assert_eq!(rows, scanned);
+    assert_pages_unpinned(&heap_file, &chain);
+}
+
+/// Scans a table many times larger than the buffer pool, which only works if pages are unpinned
+/// as the scan moves past them.
+#[test]
+fn test_scan_larger_than_pool() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let mut heap_file = TableHeap::new(utility::create_table_definition(8, "test"), &bpm);
+    let table_schema = Arc::new(heap_file.schema().clone());
+
+    let mut rows = Vec::new();
+    while heap_file.num_pages() < 100 {
+        let row = create_row(&table_schema);
+        let tuple = row.to_tuple(&table_schema).unwrap();
+        let rid = heap_file
+            .insert_tuple(TupleMetadata::new(false), tuple)
+            .unwrap();
+        rows.push((rid, row));
+    }
+
+    for _ in 0..2 {
+        let scanned: Vec<(RecordId, Row)> = heap_file
+            .iter()
+            .map(Result::unwrap)
+            .map(|(rid, tuple)| (rid, Row::from_tuple(tuple, &table_schema).unwrap()))
+            .collect();
+        assert_eq!(rows, scanned);
+    }
+    assert!(bpm.pinned_pages().is_empty());
+}
+
+/// A scan reads the pages ahead of it into the buffer pool unpinned, and holds back when the pool
+/// can't hold them.
+/// Inserts and scans fail with the reason, rather than panic, when every frame of the buffer pool
+/// is pinned, and work again once one is unpinned.
+#[test]
+fn test_every_frame_pinned() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let mut heap_file = TableHeap::new(utility::create_table_definition(8, "test"), &bpm);
+    let table_schema = Arc::new(heap_file.schema().clone());
+    let tuple = create_row(&table_schema).to_tuple(&table_schema).unwrap();
+    let mut inserted = 0;
+    while get_current_page_handle(&heap_file).read().unwrap().fits(&tuple) {
+        heap_file.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap();
+        inserted += 1;
+    }
+    let first_page_id = heap_file.first_page_id;
+
+    // The full page is resident, but the new page the insert needs has no frame.
+    bpm.fetch_page(&first_page_id).unwrap();
+    let mut pinned: Vec<PageId> = (1..4).map(|_| bpm.new_page().unwrap()).collect();
+    let result = heap_file.insert_tuple(TupleMetadata::new(false), tuple.clone());
+    assert!(matches!(result, Err(Error::NoEvictableFrame { .. })));
+    assert_eq!(1, heap_file.num_pages());
+
+    // Once evicted, the page can't be read back in for a scan.
+    bpm.unpin_page(&first_page_id, false).unwrap();
+    pinned.push(bpm.new_page().unwrap());
+    let mut it = heap_file.iter();
+    assert!(matches!(it.next(), Some(Err(Error::NoEvictableFrame { .. }))));
+    assert!(it.next().is_none());
+    drop(it);
+
+    bpm.unpin_page(&pinned[0], true).unwrap();
+    assert_eq!(inserted, heap_file.iter().map(Result::unwrap).count());
+    let rid = heap_file.insert_tuple(TupleMetadata::new(false), tuple).unwrap();
+    assert_ne!(first_page_id, rid.page_id());
+}
+
+/// Test that a tuple whose overflow pages have no frame reports why, and leaves no pages behind.
+#[test]
+fn test_overflow_tuple_without_frame() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let mut heap_file = TableHeap::new(utility::create_table_definition(8, "test"), &bpm);
+    let first_page_id = heap_file.first_page_id;
+    bpm.fetch_page(&first_page_id).unwrap();
+    let pinned: Vec<PageId> = (1..4).map(|_| bpm.new_page().unwrap()).collect();
+    let allocated = bpm.disk_stats().allocated_pages;
+
+    let tuple = Tuple::from(vec![1; 2 * RUSTY_DB_PAGE_PAYLOAD_BYTES]);
+    let result = heap_file.insert_tuple(TupleMetadata::new(false), tuple.clone());
+    assert!(matches!(result, Err(Error::NoEvictableFrame { pinned: 4, .. })));
+    assert_eq!(allocated, bpm.disk_stats().allocated_pages);
+
+    bpm.unpin_page(&pinned[0], false).unwrap();
+    let rid = heap_file.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap();
+    assert_eq!(tuple, heap_file.get_tuple(&rid).unwrap());
+}
+
+#[test]
+fn test_scan_reads_ahead() {
+    for (pool_size, readahead) in [(16, 4), (4, 16)] {
+        let bpm = BufferPoolManager::builder()
+            .pool_size(pool_size)
+            .replacer_k(2)
+            .disk_manager(new_disk_manager())
+            .build_with_handle();
+        let mut heap_file = TableHeap::new(utility::create_table_definition(8, "test"), &bpm);
+        heap_file.set_readahead(readahead);
+        let table_schema = Arc::new(heap_file.schema().clone());
+        let mut rows = Vec::new();
+        while heap_file.num_pages() < 30 {
+            let row = create_row(&table_schema);
+            let tuple = row.to_tuple(&table_schema).unwrap();
+            let rid = heap_file
+                .insert_tuple(TupleMetadata::new(false), tuple)
+                .unwrap();
+            rows.push((rid, row));
+        }
+        let chain = heap_file.page_ids();
+
+        let mut it = heap_file.iter();
+        it.next().unwrap().unwrap();
+        assert_eq!(Some(1), pin_count(&heap_file, chain[0]));
+        let ahead = &chain[1..=readahead.min(pool_size - 1)];
+        assert_pages_unpinned(&heap_file, ahead);
+        assert_eq!(vec![(chain[0], 1)], bpm.pinned_pages());
+
+        let scanned: Vec<(RecordId, Row)> = heap_file
+            .iter()
+            .map(Result::unwrap)
+            .map(|(rid, tuple)| (rid, Row::from_tuple(tuple, &table_schema).unwrap()))
+            .collect();
+        assert_eq!(rows, scanned);
+        drop(it);
+        assert!(bpm.pinned_pages().is_empty());
+    }
+}
+
+/// Iterating in reverse yields exactly the live tuples forward iteration does, in reverse, reading
+/// pages ahead backwards through a pool smaller than the table.
+#[test]
+fn test_iter_rev() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let mut heap_file = TableHeap::new(utility::create_table_definition(8, "test"), &bpm);
+    assert!(heap_file.iter_rev().next().is_none());
+
+    heap_file.set_readahead(2);
+    let table_schema = Arc::new(heap_file.schema().clone());
+    let mut rids = Vec::new();
+    while heap_file.num_pages() < 10 {
+        let tuple = create_row(&table_schema).to_tuple(&table_schema).unwrap();
+        let rid = heap_file
+            .insert_tuple(TupleMetadata::new(false), tuple)
+            .unwrap();
+        rids.push(rid);
+    }
+    // Tombstone the first and last tuples, and others in between.
+    for rid in rids.iter().step_by(7).chain(rids.last()) {
+        heap_file.delete_tuple(rid).unwrap();
+    }
+
+    let mut forward: Vec<(RecordId, Vec<u8>)> = heap_file
+        .iter()
+        .map(Result::unwrap)
+        .map(|(rid, tuple)| (rid, tuple.data))
+        .collect();
+    let reverse: Vec<(RecordId, Vec<u8>)> = heap_file
+        .iter_rev()
+        .map(Result::unwrap)
+        .map(|(rid, tuple)| (rid, tuple.data))
+        .collect();
+    assert!(forward.len() < rids.len());
+    forward.reverse();
+    assert_eq!(forward, reverse);
+    assert!(bpm.pinned_pages().is_empty());
+}
+
+/// Test that an insert takes the slot of a tuple tombstoned with its payload, at the record id
+/// [`TableHeap::next_record_id`] gives, but not that of a tuple deleted, whose slot is retired.
+#[test]
+fn test_insert_reuses_tombstoned_slot() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let rids: Vec<_> = (0..3).map(|_| insert_bytes(&mut heap_file, 50)).collect();
+    heap_file.delete_tuple(&rids[0]).unwrap();
+    let deleted = TupleMetadata::deleted_payload_metadata();
+    heap_file.update_tuple_metadata(&rids[1], &deleted).unwrap();
+
+    let tuple = Tuple::from(vec![1; 40]);
+    assert_eq!(rids[1], heap_file.next_record_id(&tuple).unwrap());
+    assert_eq!(rids[1], heap_file.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap());
+    assert_eq!(tuple, heap_file.get_tuple(&rids[1]).unwrap());
+    assert!(heap_file.get_tuple(&rids[0]).is_err());
+    assert_ne!(rids[0], insert_bytes(&mut heap_file, 50));
+}
+
+/// Deletes leave their payloads on the page, which an insert needing the room reclaims by
+/// compacting the page, at a slot of its own.
+#[test]
+fn test_insert_compacts_after_deletes() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let mut rids = Vec::new();
+    while heap_file.num_pages() < 2 {
+        rids.push(insert_bytes(&mut heap_file, 100));
+    }
+    let first_page_id = heap_file.first_page_id;
+    let on_first: Vec<_> = rids.iter().filter(|rid| rid.page_id() == first_page_id).collect();
+    let free_space = |heap_file: &TableHeap| {
+        let page = heap_file.fetch_page_handle(&first_page_id).unwrap();
+        let page_guard = page.read().unwrap();
+        (page_guard.free_space(), page_guard.reclaimable_bytes())
+    };
+    let (free, _) = free_space(&heap_file);
+    for rid in &on_first[..on_first.len() / 2] {
+        heap_file.delete_tuple(rid).unwrap();
+    }
+    assert_eq!((free, on_first.len() / 2 * 100), free_space(&heap_file));
+
+    let rid = insert_bytes(&mut heap_file, 100);
+    assert_eq!(RecordId::new(first_page_id, on_first.len() as u16), rid);
+    let (free_after, reclaimable) = free_space(&heap_file);
+    assert!(free_after > free);
+    assert_eq!(0, reclaimable);
+}
+
+/// Once vacuuming reclaims space on the first pages of the chain, inserts fill it up, in chain
+/// order, before going to the last page.
+#[test]
+fn test_insert_reuses_freed_space() {
+    let mut heap_file = create_fixed_size_heap_file(100);
+    let mut rids = Vec::new();
+    while heap_file.num_pages() < 4 {
+        rids.push(insert_bytes(&mut heap_file, 100));
+    }
+    let chain = heap_file.page_ids();
+    for rid in rids
+        .iter()
+        .filter(|rid| rid.page_id() != chain[3])
+        .step_by(2)
+    {
+        heap_file.delete_tuple(rid).unwrap();
+    }
+    heap_file.vacuum(&|_| false).unwrap();
+
+    let mut positions = Vec::new();
+    while heap_file.num_pages() < 5 {
+        let rid = insert_bytes(&mut heap_file, 100);
+        positions.push(chain.iter().position(|page_id| *page_id == rid.page_id()));
+    }
+    positions.pop();
+    assert!(positions.is_sorted());
+    for position in 0..4 {
+        assert!(positions.contains(&Some(position)), "page {position}");
+    }
+    // Every page of the chain was filled up before a new one was added.
+    for page_id in &chain {
+        let free = heap_file
+            .fetch_page_handle(page_id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .free_space();
+        assert!(free <= 104, "page {page_id} has {free} bytes free");
+    }
+}
+
+/// Inserts leave the share of each page the fill factor reserves free, which an update growing a
+/// tuple can use, while appending fills pages up.
+#[test]
+fn test_fill_factor() {
+    let reserved = |fill_factor: usize| RUSTY_DB_PAGE_PAYLOAD_BYTES * (100 - fill_factor) / 100;
+    for fill_factor in [100, 70, 10] {
+        let mut heap_file = create_fixed_size_heap_file(fill_factor as u8);
+        let mut rids = Vec::new();
+        while heap_file.num_pages() < 3 {
+            rids.push(insert_bytes(&mut heap_file, 100));
+        }
+        let first_page_id = heap_file.first_page_id;
+        let free = heap_file
+            .fetch_page_handle(&first_page_id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .free_space();
+        assert!(free >= reserved(fill_factor), "fill factor {fill_factor}");
+        assert!(
+            free < reserved(fill_factor) + 104,
+            "fill factor {fill_factor}"
+        );
+
+        // A tuple of the full first page grown stays on it only if the fill factor left room.
+        let result = heap_file.update_tuple(&rids[0], Tuple::from(vec![1; 300]));
+        match fill_factor {
+            100 => assert_ne!(first_page_id, result.unwrap().page_id()),
+            _ => assert_eq!(first_page_id, result.unwrap().page_id()),
+        }
+
+        let tuple = Tuple::from(vec![0; 100]);
+        let versions = (0..100).map(|_| Ok((TupleMetadata::new(false), tuple.clone())));
+        let (packed, _) = heap_file.rebuild(versions).unwrap();
+        let free = packed
+            .fetch_page_handle(&packed.first_page_id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .free_space();
+        assert!(free <= 104, "fill factor {fill_factor}");
+    }
+}
+
+/// A table whose fill factor was set above 100 without the planner's check fills its pages up,
+/// as with a fill factor of 100.
+#[test]
+fn test_fill_factor_above_100() {
+    let mut heap_file = create_fixed_size_heap_file(u8::MAX);
+    while heap_file.num_pages() < 2 {
+        insert_bytes(&mut heap_file, 100);
+    }
+    let free = heap_file
+        .fetch_page_handle(&heap_file.first_page_id)
+        .unwrap()
+        .read()
+        .unwrap()
+        .free_space();
+    assert!(free < 104);
+}
+
+/// Pages read back from disk leave the share the table's fill factor reserves free just like
+/// resident ones: inserts into a heap reopened over evicted pages land on its last page, or a
+/// new one, and not in the reserve of the pages before.
+#[test]
+fn test_fill_factor_after_eviction() {
+    let reserved = RUSTY_DB_PAGE_PAYLOAD_BYTES * 30 / 100;
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let schema = Table::builder()
+        .name("test")
+        .column("id", DataType::Int, false, None, None)
+        .fill_factor(70)
+        .build();
+    let mut heap_file = TableHeap::new(schema.clone(), &bpm);
+    let mut rids = Vec::new();
+    while heap_file.num_pages() < 8 {
+        rids.push(insert_bytes(&mut heap_file, 100));
+    }
+
+    // The free space map is measured again from the pages, which don't all fit in the pool.
+    let mut reopened = TableHeap::open(schema, &bpm, heap_file.page_ids()).unwrap();
+    let page_ids = reopened.page_ids();
+    let full_pages = &page_ids[..page_ids.len() - 1];
+    for _ in 0..20 {
+        let rid = insert_bytes(&mut reopened, 100);
+        assert!(!full_pages.contains(&rid.page_id()));
+    }
+    for page_id in full_pages {
+        let free = reopened.fetch_page_handle(page_id).unwrap().read().unwrap().free_space();
+        assert!(free >= reserved, "page {page_id}");
+    }
+
+    // The reserve is still there for an update growing a tuple of the first page.
+    let rid = reopened.update_tuple(&rids[0], Tuple::from(vec![1; 300])).unwrap();
+    assert_eq!(page_ids[0], rid.page_id());
+}
+
 pub fn create_random_heap_file() -> TableHeap {
     let disk_manager = new_disk_manager();
-    let bpm = Arc::new(RwLock::new(BufferPoolManager::new(50, 5, disk_manager)));
+    let bpm = BufferPoolManager::new_with_handle(50, 5, disk_manager);
     let mut rng = rand::thread_rng();
     let schema = utility::create_table_definition(rng.gen_range(5..25), "test");
 
     TableHeap::new(schema, &bpm)
 }
 
+/// Creates a heap with the given fill factor, in a pool big enough to hold all of its pages.
+fn create_fixed_size_heap_file(fill_factor: u8) -> TableHeap {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(16)
+        .replacer_k(2)
+        .disk_manager(new_disk_manager())
+        .build_with_handle();
+    let schema = Table::builder()
+        .name("test")
+        .column("id", DataType::Int, false, None, None)
+        .fill_factor(fill_factor)
+        .build();
+    TableHeap::new(schema, &bpm)
+}
+
+/// Inserts a tuple of the given size. Tuples of the heap aren't decoded, so their bytes don't
+/// have to match its schema.
+fn insert_bytes(heap_file: &mut TableHeap, size: usize) -> RecordId {
+    heap_file
+        .insert_tuple(TupleMetadata::new(false), Tuple::from(vec![0; size]))
+        .unwrap()
+}
+
 fn new_disk_manager() -> Arc<RwLock<DiskManager>> {
     DiskManager::new_with_handle_for_test()
 }
@@ -183,11 +733,11 @@ pub fn create_row_with_seed(table_schema: &Arc<Table>, seed: u64) -> Row {
 }
 
 fn get_bpm_page_capacity(heap_file: &TableHeap) -> usize {
-    heap_file.buffer_pool_manager.read().unwrap().size()
+    heap_file.buffer_pool_manager.size()
 }
 
 fn get_current_page_handle(heap_file: &TableHeap) -> TablePageHandle {
-    Arc::clone(&heap_file.fetch_page_handle(&heap_file.last_page_id))
+    Arc::clone(&heap_file.fetch_page_handle(&heap_file.last_page_id).unwrap())
 }
 
 fn get_tuple_from_page(
@@ -201,3 +751,13 @@ fn get_tuple_from_page(
 fn get_row(heap_file: &TableHeap, schema: &Table, rid: &RecordId) -> Result<Row> {
     Row::from_tuple(heap_file.get_tuple(rid)?, schema)
 }
+
+fn pin_count(heap_file: &TableHeap, page_id: PageId) -> Option<usize> {
+    heap_file.buffer_pool_manager.pin_count(&page_id)
+}
+
+fn assert_pages_unpinned(heap_file: &TableHeap, page_ids: &[PageId]) {
+    for page_id in page_ids {
+        assert_eq!(Some(0), pin_count(heap_file, *page_id), "page {page_id}");
+    }
+}
@@ -0,0 +1,605 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::index::bplus_tree::IndexIterator;
+use crate::storage::page::{
+    BPlusTreeInternalPage, BPlusTreeLeafPage, KeySchema, PageHandle, RecordId, Separator,
+};
+use crate::types::field::Field;
+use crate::{errdata, errinput};
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, RwLock};
+
+/// A B+tree index from keys to the record ids of the rows holding them, stored in B+tree pages
+/// of the buffer pool.
+///
+/// Entries are ordered by key, then by record id, and internal pages route on the same composite
+/// order, so any number of rows can share a key. Each (key, record id) entry is unique.
+///
+/// Concurrency is handled with a single tree-level latch rather than latch crabbing: lookups hold
+/// it shared and inserts and deletes hold it exclusively, for the whole operation. The latch also
+/// guards the root page id, which changes as the tree grows and shrinks. Page latches are only
+/// held while a page is read or changed, never across a call into the buffer pool, so locks are
+/// always taken in the order tree latch, buffer pool, page.
+#[derive(Debug)]
+pub struct BPlusTree {
+    pub(crate) buffer_pool_manager: Arc<BufferPoolManager>,
+    pub(crate) key_schema: KeySchema,
+    pub(crate) leaf_max_size: Option<u16>,
+    pub(crate) internal_max_size: Option<u16>,
+    /// The tree-level latch, over the root page id, or `INVALID_PID` while the tree is empty.
+    pub(crate) root_page_id: RwLock<PageId>,
+}
+
+impl BPlusTree {
+    pub fn builder() -> BPlusTreeBuilder {
+        BPlusTreeBuilder::default()
+    }
+
+    pub fn key_schema(&self) -> KeySchema {
+        self.key_schema
+    }
+
+    /// Returns the page id of the root page, which reopens the tree with
+    /// [`BPlusTreeBuilder::root_page_id`]. `INVALID_PID` if the tree is empty.
+    pub fn root_page_id(&self) -> PageId {
+        *self.root_page_id.read().unwrap()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root_page_id() == INVALID_PID
+    }
+
+    /// Returns the record ids of every entry with the given key, in record id order.
+    pub fn get(&self, key: &Field) -> Result<Vec<RecordId>> {
+        self.range((Bound::Included(key), Bound::Included(key)))
+    }
+
+    /// Returns the record ids of every entry with a key in `range`, in (key, record id) order.
+    pub fn range<R: RangeBounds<Field>>(&self, range: R) -> Result<Vec<RecordId>> {
+        let root = self.root_page_id.read().unwrap();
+        if *root == INVALID_PID {
+            return Ok(Vec::new());
+        }
+        let mut page_id = match range.start_bound() {
+            // The smallest record id sorts before every other entry with the key.
+            Bound::Included(key) | Bound::Excluded(key) => {
+                self.find_leaf(*root, |page| page.lookup(key, &RecordId::new(0, 0)))?
+            }
+            Bound::Unbounded => self.find_leaf(*root, |page| page.child_at(0))?,
+        };
+
+        let mut rids = Vec::new();
+        while page_id != INVALID_PID {
+            let page = self.fetch(page_id)?;
+            let leaf = page.leaf()?.read().unwrap();
+            for (key, rid) in leaf.entries() {
+                let past_end = match range.end_bound() {
+                    Bound::Included(end) => key > end,
+                    Bound::Excluded(end) => key >= end,
+                    Bound::Unbounded => false,
+                };
+                if past_end {
+                    return Ok(rids);
+                }
+                if range.contains(key) {
+                    rids.push(rid.clone());
+                }
+            }
+            page_id = leaf.next_page_id();
+        }
+        Ok(rids)
+    }
+
+    /// Returns an iterator over the record ids of every entry with a key in `range`, in (key,
+    /// record id) order, or in reverse if `reverse` is set. Unlike [`Self::range`], entries are
+    /// read from the tree one leaf at a time as the iterator is advanced.
+    pub fn scan<R: RangeBounds<Field>>(self: &Arc<Self>, range: R, reverse: bool) -> IndexIterator {
+        IndexIterator::new(Arc::clone(self), range, reverse)
+    }
+
+    /// Inserts an entry. Fails if the key doesn't match the tree's key schema, or the entry is
+    /// already in the tree.
+    pub fn insert(&self, key: Field, rid: RecordId) -> Result<()> {
+        self.key_schema.check(&key)?;
+        let mut root = self.root_page_id.write().unwrap();
+        if *root == INVALID_PID {
+            *root = self.new_leaf()?.page_id();
+        }
+
+        let page = self.fetch(self.find_leaf(*root, |page| page.lookup(&key, &rid))?)?;
+        let leaf = page.leaf()?;
+        let mut guard = leaf.write().unwrap();
+        let index = match guard.position(&key, &rid) {
+            Ok(_) => return errinput!("entry ({key}, {}) already exists", rid.to_string()),
+            Err(index) => index,
+        };
+        if !guard.is_full() {
+            guard.insert(key, rid)?;
+            return Ok(());
+        }
+        drop(guard);
+
+        // Split the full leaf, then hand the new right half's first entry up to the parent as the
+        // separator between the two.
+        let sibling = self.new_leaf()?;
+        let (separator, parent_page_id, next_page_id) = {
+            let mut left = leaf.write().unwrap();
+            let mut right = sibling.leaf()?.write().unwrap();
+            let at = split_point(left.size(), index);
+            left.split_into(at, &mut right);
+            if index < at {
+                left.insert(key, rid)?;
+            } else {
+                right.insert(key, rid)?;
+            }
+            right.set_parent_page_id(left.parent_page_id());
+            (
+                right.entries()[0].clone(),
+                left.parent_page_id(),
+                right.next_page_id(),
+            )
+        };
+        if next_page_id != INVALID_PID {
+            let next = self.fetch(next_page_id)?;
+            next.leaf()?
+                .write()
+                .unwrap()
+                .set_prev_page_id(sibling.page_id());
+        }
+
+        let (left_page_id, right_page_id) = (page.page_id(), sibling.page_id());
+        drop((page, sibling));
+        self.insert_into_parent(
+            &mut root,
+            left_page_id,
+            parent_page_id,
+            separator,
+            right_page_id,
+        )
+    }
+
+    /// Deletes an entry, returning whether it was in the tree.
+    pub fn delete(&self, key: &Field, rid: &RecordId) -> Result<bool> {
+        let mut root = self.root_page_id.write().unwrap();
+        if *root == INVALID_PID {
+            return Ok(false);
+        }
+
+        let page = self.fetch(self.find_leaf(*root, |page| page.lookup(key, rid))?)?;
+        if !page.leaf()?.write().unwrap().remove(key, rid) {
+            return Ok(false);
+        }
+        self.rebalance(&mut root, page)?;
+        Ok(true)
+    }
+
+    /// Descends from `root` to a leaf, picking the child to follow on each internal page with
+    /// `child`. Returns the leaf's page id.
+    pub(super) fn find_leaf(
+        &self,
+        root: PageId,
+        child: impl Fn(&BPlusTreeInternalPage) -> PageId,
+    ) -> Result<PageId> {
+        let mut page_id = root;
+        loop {
+            let page = self.fetch(page_id)?;
+            if page.is_leaf() {
+                return Ok(page_id);
+            }
+            page_id = child(&page.internal()?.read().unwrap());
+        }
+    }
+
+    /// Links `right_page_id`, just split off from `left_page_id`, into their parent after `left`,
+    /// splitting the parent in turn if it is full. A new root is created when the root splits.
+    fn insert_into_parent(
+        &self,
+        root: &mut PageId,
+        left_page_id: PageId,
+        parent_page_id: PageId,
+        separator: Separator,
+        right_page_id: PageId,
+    ) -> Result<()> {
+        if parent_page_id == INVALID_PID {
+            let new_root = self.new_internal()?;
+            new_root.internal()?.write().unwrap().init_root(
+                left_page_id,
+                separator,
+                right_page_id,
+            )?;
+            let new_root_page_id = new_root.page_id();
+            drop(new_root);
+            self.set_parent(left_page_id, new_root_page_id)?;
+            self.set_parent(right_page_id, new_root_page_id)?;
+            *root = new_root_page_id;
+            return Ok(());
+        }
+
+        let page = self.fetch(parent_page_id)?;
+        let parent = page.internal()?;
+        let mut guard = parent.write().unwrap();
+        let Some(index) = guard.child_index_of(left_page_id).map(|index| index + 1) else {
+            return errdata!("page {left_page_id} is not a child of page {parent_page_id}");
+        };
+        if !guard.is_full() {
+            guard.insert(index, separator, right_page_id)?;
+            drop(guard);
+            return self.set_parent(right_page_id, parent_page_id);
+        }
+        drop(guard);
+
+        // Split the full parent, and push the separator between its halves up another level.
+        let sibling = self.new_internal()?;
+        let (pushed_up, grandparent_page_id, moved_children) = {
+            let mut left = parent.write().unwrap();
+            let mut right = sibling.internal()?.write().unwrap();
+            let at = split_point(left.size(), index);
+            let pushed_up = left.split_into(at, &mut right);
+            let pushed_up = match index.cmp(&at) {
+                Ordering::Less => {
+                    left.insert(index, separator, right_page_id)?;
+                    pushed_up
+                }
+                // The new child becomes the first child of the right half, so its separator is
+                // the one between the halves.
+                Ordering::Equal => {
+                    right.push_front(right_page_id, pushed_up)?;
+                    separator
+                }
+                Ordering::Greater => {
+                    right.insert(index - at, separator, right_page_id)?;
+                    pushed_up
+                }
+            };
+            right.set_parent_page_id(left.parent_page_id());
+            (pushed_up, left.parent_page_id(), right.children().to_vec())
+        };
+
+        let sibling_page_id = sibling.page_id();
+        drop((page, sibling));
+        for child in &moved_children {
+            self.set_parent(*child, sibling_page_id)?;
+        }
+        if !moved_children.contains(&right_page_id) {
+            self.set_parent(right_page_id, parent_page_id)?;
+        }
+        self.insert_into_parent(
+            root,
+            parent_page_id,
+            grandparent_page_id,
+            pushed_up,
+            sibling_page_id,
+        )
+    }
+
+    /// Restores the minimum size of a page that lost an entry or child, by borrowing one from a
+    /// sibling or merging with it. A merge removes a child from the parent, which is rebalanced in
+    /// turn.
+    fn rebalance(&self, root: &mut PageId, page: PinnedPage) -> Result<()> {
+        if page.page_id() == *root {
+            return self.shrink_root(root, page);
+        }
+        let (size, min_size, parent_page_id) = if page.is_leaf() {
+            let leaf = page.leaf()?.read().unwrap();
+            (leaf.size(), leaf.min_size(), leaf.parent_page_id())
+        } else {
+            let internal = page.internal()?.read().unwrap();
+            (
+                internal.size(),
+                internal.min_size(),
+                internal.parent_page_id(),
+            )
+        };
+        if size >= min_size {
+            return Ok(());
+        }
+
+        // Pair the page up with its left sibling, or its right one if it is the first child.
+        let parent = self.fetch(parent_page_id)?;
+        let (index, sibling_page_id) = {
+            let guard = parent.internal()?.read().unwrap();
+            let Some(index) = guard.child_index_of(page.page_id()) else {
+                return errdata!(
+                    "page {} is not a child of page {parent_page_id}",
+                    page.page_id()
+                );
+            };
+            let sibling_index = if index > 0 { index - 1 } else { index + 1 };
+            (index, guard.child_at(sibling_index))
+        };
+        let sibling = self.fetch(sibling_page_id)?;
+        let (left, right, right_index) = match index {
+            0 => (page, sibling, 1),
+            _ => (sibling, page, index),
+        };
+
+        let merged = if left.is_leaf() {
+            self.rebalance_leaves(&parent, &left, &right, right_index)?
+        } else {
+            self.rebalance_internals(&parent, &left, &right, right_index)?
+        };
+        drop(left);
+        if !merged {
+            return Ok(());
+        }
+        right.delete()?;
+        self.rebalance(root, parent)
+    }
+
+    /// Evens out two sibling leaves by moving the entry at their boundary over, or merges them if
+    /// their entries fit on one page. Returns whether they were merged, leaving `right` empty.
+    fn rebalance_leaves(
+        &self,
+        parent: &PinnedPage,
+        left: &PinnedPage,
+        right: &PinnedPage,
+        right_index: usize,
+    ) -> Result<bool> {
+        let mut parent_guard = parent.internal()?.write().unwrap();
+        let mut left_guard = left.leaf()?.write().unwrap();
+        let mut right_guard = right.leaf()?.write().unwrap();
+
+        if left_guard.size() + right_guard.size() > left_guard.max_size() {
+            if left_guard.size() > right_guard.size() {
+                let last = left_guard.size() - 1;
+                let (key, rid) = left_guard.remove_at(last);
+                right_guard.insert(key, rid)?;
+            } else {
+                let (key, rid) = right_guard.remove_at(0);
+                left_guard.insert(key, rid)?;
+            }
+            parent_guard.set_key_at(right_index, right_guard.entries()[0].clone())?;
+            return Ok(false);
+        }
+
+        left_guard.merge_from(&mut right_guard)?;
+        parent_guard.remove_at(right_index);
+        let next_page_id = left_guard.next_page_id();
+        drop((parent_guard, left_guard, right_guard));
+        if next_page_id != INVALID_PID {
+            let next = self.fetch(next_page_id)?;
+            next.leaf()?
+                .write()
+                .unwrap()
+                .set_prev_page_id(left.page_id());
+        }
+        Ok(true)
+    }
+
+    /// Evens out two sibling internal pages by rotating the child at their boundary through the
+    /// parent, or merges them, pulling down the parent's separator, if their children fit on one
+    /// page. Returns whether they were merged, leaving `right` empty.
+    fn rebalance_internals(
+        &self,
+        parent: &PinnedPage,
+        left: &PinnedPage,
+        right: &PinnedPage,
+        right_index: usize,
+    ) -> Result<bool> {
+        let mut parent_guard = parent.internal()?.write().unwrap();
+        let mut left_guard = left.internal()?.write().unwrap();
+        let mut right_guard = right.internal()?.write().unwrap();
+        let separator = parent_guard.key_at(right_index).clone();
+
+        if left_guard.size() + right_guard.size() > left_guard.max_size() {
+            let (child, new_parent_page_id, new_separator) =
+                if left_guard.size() > right_guard.size() {
+                    let last = left_guard.size() - 1;
+                    let (new_separator, child) = left_guard.remove_at(last);
+                    right_guard.push_front(child, separator)?;
+                    (child, right.page_id(), new_separator)
+                } else {
+                    let (new_separator, child) = right_guard.remove_at(0);
+                    let index = left_guard.size();
+                    left_guard.insert(index, separator, child)?;
+                    (child, left.page_id(), new_separator)
+                };
+            parent_guard.set_key_at(right_index, new_separator)?;
+            drop((parent_guard, left_guard, right_guard));
+            self.set_parent(child, new_parent_page_id)?;
+            return Ok(false);
+        }
+
+        let moved_children = right_guard.children().to_vec();
+        left_guard.merge_from(separator, &mut right_guard)?;
+        parent_guard.remove_at(right_index);
+        drop((parent_guard, left_guard, right_guard));
+        for child in moved_children {
+            self.set_parent(child, left.page_id())?;
+        }
+        Ok(true)
+    }
+
+    /// Removes the root once it no longer holds anything: an empty root leaf empties the tree,
+    /// and a root internal page with a single child hands the root over to that child.
+    fn shrink_root(&self, root: &mut PageId, page: PinnedPage) -> Result<()> {
+        if page.is_leaf() {
+            if !page.leaf()?.read().unwrap().is_empty() {
+                return Ok(());
+            }
+            *root = INVALID_PID;
+        } else {
+            let child = {
+                let guard = page.internal()?.read().unwrap();
+                if guard.size() > 1 {
+                    return Ok(());
+                }
+                guard.child_at(0)
+            };
+            self.set_parent(child, INVALID_PID)?;
+            *root = child;
+        }
+        page.delete()
+    }
+
+    fn set_parent(&self, page_id: PageId, parent_page_id: PageId) -> Result<()> {
+        let page = self.fetch(page_id)?;
+        if page.is_leaf() {
+            page.leaf()?
+                .write()
+                .unwrap()
+                .set_parent_page_id(parent_page_id);
+        } else {
+            page.internal()?
+                .write()
+                .unwrap()
+                .set_parent_page_id(parent_page_id);
+        }
+        Ok(())
+    }
+
+    pub(super) fn fetch(&self, page_id: PageId) -> Result<PinnedPage<'_>> {
+        let page = self.buffer_pool_manager.fetch_index_page(&page_id)?;
+        Ok(PinnedPage::new(&self.buffer_pool_manager, page))
+    }
+
+    fn new_leaf(&self) -> Result<PinnedPage<'_>> {
+        let mut builder = BPlusTreeLeafPage::builder();
+        builder.key_schema(self.key_schema);
+        if let Some(max_size) = self.leaf_max_size {
+            builder.max_size(max_size);
+        }
+        let page = self.buffer_pool_manager.new_leaf_page(&mut builder)?;
+        Ok(PinnedPage::new(&self.buffer_pool_manager, page.into()))
+    }
+
+    fn new_internal(&self) -> Result<PinnedPage<'_>> {
+        let mut builder = BPlusTreeInternalPage::builder();
+        builder.key_schema(self.key_schema);
+        if let Some(max_size) = self.internal_max_size {
+            builder.max_size(max_size);
+        }
+        let page = self.buffer_pool_manager.new_internal_page(&mut builder)?;
+        Ok(PinnedPage::new(&self.buffer_pool_manager, page.into()))
+    }
+}
+
+/// Returns where to split a full page of `size` entries or children, so that the halves are as
+/// even as possible once one more is inserted at `index`.
+fn split_point(size: usize, index: usize) -> usize {
+    let left_size = (size + 2) / 2;
+    if index < left_size {
+        left_size - 1
+    } else {
+        left_size
+    }
+}
+
+/// A B+tree page pinned in the buffer pool, which is unpinned when this is dropped.
+pub(super) struct PinnedPage<'a> {
+    buffer_pool_manager: &'a BufferPoolManager,
+    page_id: PageId,
+    page: PageHandle,
+    pinned: bool,
+}
+
+impl<'a> PinnedPage<'a> {
+    fn new(buffer_pool_manager: &'a BufferPoolManager, page: PageHandle) -> Self {
+        Self {
+            buffer_pool_manager,
+            page_id: page.page_id(),
+            page,
+            pinned: true,
+        }
+    }
+
+    fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(self.page, PageHandle::BPlusTreeLeaf(_))
+    }
+
+    pub(super) fn leaf(&self) -> Result<&RwLock<BPlusTreeLeafPage>> {
+        match &self.page {
+            PageHandle::BPlusTreeLeaf(page) => Ok(page),
+            _ => errdata!("page {} is not a B+tree leaf page", self.page_id),
+        }
+    }
+
+    pub(super) fn internal(&self) -> Result<&RwLock<BPlusTreeInternalPage>> {
+        match &self.page {
+            PageHandle::BPlusTreeInternal(page) => Ok(page),
+            _ => errdata!("page {} is not a B+tree internal page", self.page_id),
+        }
+    }
+
+    /// Unpins the page and deletes it from the buffer pool, once it is no longer in the tree.
+    fn delete(mut self) -> Result<()> {
+        self.pinned = false;
+        self.buffer_pool_manager.unpin_page(&self.page_id, false)?;
+        self.buffer_pool_manager.delete_page(self.page_id)?;
+        Ok(())
+    }
+}
+
+impl Drop for PinnedPage<'_> {
+    fn drop(&mut self) {
+        if self.pinned {
+            let is_dirty = self.page.get_is_dirty();
+            self.buffer_pool_manager
+                .unpin_page(&self.page_id, is_dirty)
+                .ok();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BPlusTreeBuilder {
+    buffer_pool_manager: Option<Arc<BufferPoolManager>>,
+    key_schema: Option<KeySchema>,
+    leaf_max_size: Option<u16>,
+    internal_max_size: Option<u16>,
+    root_page_id: Option<PageId>,
+}
+
+impl BPlusTreeBuilder {
+    pub fn buffer_pool_manager(
+        &mut self,
+        buffer_pool_manager: Arc<BufferPoolManager>,
+    ) -> &mut Self {
+        self.buffer_pool_manager = Some(buffer_pool_manager);
+        self
+    }
+    pub fn key_schema(&mut self, key_schema: KeySchema) -> &mut Self {
+        self.key_schema = Some(key_schema);
+        self
+    }
+    /// Caps the number of entries on a leaf below what fits on a page, e.g. to exercise splits
+    /// and merges in tests. Must be at least 2.
+    pub fn leaf_max_size(&mut self, max_size: u16) -> &mut Self {
+        self.leaf_max_size = Some(max_size);
+        self
+    }
+    /// Caps the number of children of an internal page below what fits on a page. Must be at
+    /// least 3.
+    pub fn internal_max_size(&mut self, max_size: u16) -> &mut Self {
+        self.internal_max_size = Some(max_size);
+        self
+    }
+    /// Opens an existing tree rooted at the given page, instead of starting out empty.
+    pub fn root_page_id(&mut self, root_page_id: PageId) -> &mut Self {
+        self.root_page_id = Some(root_page_id);
+        self
+    }
+    pub fn build(&self) -> BPlusTree {
+        assert!(self.leaf_max_size.is_none_or(|size| size >= 2));
+        assert!(self.internal_max_size.is_none_or(|size| size >= 3));
+        BPlusTree {
+            buffer_pool_manager: self
+                .buffer_pool_manager
+                .clone()
+                .expect("Cannot build BPlusTree without a `buffer_pool_manager`."),
+            key_schema: self
+                .key_schema
+                .expect("Cannot build BPlusTree without a `key_schema`."),
+            leaf_max_size: self.leaf_max_size,
+            internal_max_size: self.internal_max_size,
+            root_page_id: RwLock::new(self.root_page_id.unwrap_or(INVALID_PID)),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::index::bplus_tree::BPlusTree;
+use crate::storage::page::{BPlusTreeInternalPage, RecordId};
+use crate::types::field::Field;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+/// An iterator over the record ids of a B+tree's entries with keys in a range, in (key, record
+/// id) order, or in reverse.
+///
+/// No pins or latches are held between calls. Each time the buffer runs dry, the iterator takes
+/// the tree latch, descends from the root to the last entry it buffered, and buffers the entries
+/// in range on the next leaf along, following sibling pointers past leaves with none. Resuming
+/// from an entry rather than a page means splits and merges in between can't make it skip or
+/// repeat entries: every entry in the tree for the whole scan is returned exactly once, and
+/// entries inserted or deleted during it may or may not be.
+#[derive(Clone, Debug)]
+pub struct IndexIterator {
+    tree: Arc<BPlusTree>,
+    start: Bound<Field>,
+    end: Bound<Field>,
+    reverse: bool,
+    /// Entries in range read from the tree but not returned yet.
+    buffer: VecDeque<(Field, RecordId)>,
+    /// The last entry buffered, which the next read resumes after.
+    last: Option<(Field, RecordId)>,
+    /// Whether the range has been read to its end.
+    done: bool,
+}
+
+/// Sorts before every other record id, for descending to the first entry with a key.
+const MIN_RID: RecordId = RecordId::new(0, 0);
+/// Sorts after every other record id, for descending to the last entry with a key.
+const MAX_RID: RecordId = RecordId::new(INVALID_PID, u16::MAX);
+
+impl IndexIterator {
+    pub(super) fn new<R: RangeBounds<Field>>(
+        tree: Arc<BPlusTree>,
+        range: R,
+        reverse: bool,
+    ) -> Self {
+        Self {
+            tree,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            reverse,
+            buffer: VecDeque::new(),
+            last: None,
+            done: false,
+        }
+    }
+
+    /// Buffers the entries in range on the next leaf that has any.
+    fn fill_buffer(&mut self) -> Result<()> {
+        let tree = Arc::clone(&self.tree);
+        let root = tree.root_page_id.read().unwrap();
+        if *root == INVALID_PID {
+            self.done = true;
+            return Ok(());
+        }
+        let mut page_id = tree.find_leaf(*root, |page| self.first_child(page))?;
+        while self.buffer.is_empty() && !self.done {
+            if page_id == INVALID_PID {
+                self.done = true;
+                break;
+            }
+            let page = tree.fetch(page_id)?;
+            let leaf = page.leaf()?.read().unwrap();
+            let entries: Box<dyn Iterator<Item = _>> = match self.reverse {
+                false => Box::new(leaf.entries().iter()),
+                true => Box::new(leaf.entries().iter().rev()),
+            };
+            for (key, rid) in entries {
+                if self.is_returned(key, rid) || self.is_ahead(key) {
+                    continue;
+                }
+                if self.is_behind(key) {
+                    self.done = true;
+                    break;
+                }
+                self.buffer.push_back((key.clone(), rid.clone()));
+            }
+            if let Some(last) = self.buffer.back() {
+                self.last = Some(last.clone());
+            }
+            page_id = match self.reverse {
+                false => leaf.next_page_id(),
+                true => leaf.prev_page_id(),
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the child of an internal page to descend into to find where to resume.
+    fn first_child(&self, page: &BPlusTreeInternalPage) -> PageId {
+        if let Some((key, rid)) = &self.last {
+            return page.lookup(key, rid);
+        }
+        let bound = match self.reverse {
+            false => &self.start,
+            true => &self.end,
+        };
+        match (bound, self.reverse) {
+            (Bound::Included(key), true) => page.lookup(key, &MAX_RID),
+            (Bound::Included(key) | Bound::Excluded(key), _) => page.lookup(key, &MIN_RID),
+            (Bound::Unbounded, false) => page.child_at(0),
+            (Bound::Unbounded, true) => *page.children().last().unwrap(),
+        }
+    }
+
+    /// Returns whether the entry was already buffered, i.e. is at or before the last entry
+    /// buffered in scan order.
+    fn is_returned(&self, key: &Field, rid: &RecordId) -> bool {
+        let Some((last_key, last_rid)) = &self.last else {
+            return false;
+        };
+        let ordering = key.cmp(last_key).then_with(|| rid.cmp(last_rid));
+        match self.reverse {
+            false => ordering != Ordering::Greater,
+            true => ordering != Ordering::Less,
+        }
+    }
+
+    /// Returns whether the key comes before the range in scan order.
+    fn is_ahead(&self, key: &Field) -> bool {
+        match self.reverse {
+            false => Self::below(key, &self.start),
+            true => Self::above(key, &self.end),
+        }
+    }
+
+    /// Returns whether the key comes after the range in scan order.
+    fn is_behind(&self, key: &Field) -> bool {
+        match self.reverse {
+            false => Self::above(key, &self.end),
+            true => Self::below(key, &self.start),
+        }
+    }
+
+    /// Returns whether the key is below a lower bound.
+    fn below(key: &Field, start: &Bound<Field>) -> bool {
+        match start {
+            Bound::Included(start) => key < start,
+            Bound::Excluded(start) => key <= start,
+            Bound::Unbounded => false,
+        }
+    }
+
+    /// Returns whether the key is above an upper bound.
+    fn above(key: &Field, end: &Bound<Field>) -> bool {
+        match end {
+            Bound::Included(end) => key > end,
+            Bound::Excluded(end) => key >= end,
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+impl Iterator for IndexIterator {
+    type Item = Result<RecordId>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            if let Err(error) = self.fill_buffer() {
+                self.done = true;
+                return Some(Err(error));
+            }
+        }
+        self.buffer.pop_front().map(|(_, rid)| Ok(rid))
+    }
+}
@@ -0,0 +1,9 @@
+mod bplus_tree;
+mod iterator;
+#[cfg(test)]
+mod tests;
+mod verify;
+
+pub use bplus_tree::{BPlusTree, BPlusTreeBuilder};
+pub use iterator::IndexIterator;
+pub use verify::{IndexCheckReport, IndexProblem, IndexStats};
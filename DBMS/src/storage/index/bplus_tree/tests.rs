@@ -0,0 +1,583 @@
+use super::*;
+use crate::common::constants::INVALID_PID;
+use crate::common::{Error, Result};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::storage::page::{KeySchema, PageHandle, RecordId, Separator};
+use crate::types::field::Field;
+use crate::types::DataType;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeSet;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+#[test]
+fn test_insert_and_get_duplicates() {
+    let tree = int_tree(64);
+    for key in 0..300 {
+        // Insert each key for three rows, out of record id order.
+        for slot_id in [2, 0, 1] {
+            tree.insert(Field::Integer(key), RecordId::new(1, slot_id))
+                .unwrap();
+        }
+        check_invariants(&tree);
+    }
+    assert!(tree_height(&tree) > 2);
+
+    for key in 0..300 {
+        assert_eq!(
+            (0..3)
+                .map(|slot_id| RecordId::new(1, slot_id))
+                .collect::<Vec<_>>(),
+            tree.get(&Field::Integer(key)).unwrap(),
+            "key {key}"
+        );
+    }
+    assert!(tree.get(&Field::Integer(300)).unwrap().is_empty());
+    assert!(matches!(
+        tree.insert(Field::Integer(7), RecordId::new(1, 1)),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        tree.insert(Field::from("7"), RecordId::new(1, 1)),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn test_range_bounds() {
+    let tree = int_tree(64);
+    for key in (0..1000).step_by(2) {
+        tree.insert(Field::Integer(key), RecordId::new(2, key as u16))
+            .unwrap();
+    }
+    let keys =
+        |rids: Vec<RecordId>| -> Vec<i32> { rids.iter().map(|rid| rid.slot_id() as i32).collect() };
+
+    let (ten, twenty) = (Field::Integer(10), Field::Integer(20));
+    assert_eq!(
+        vec![10, 12, 14, 16, 18],
+        keys(tree.range(ten.clone()..twenty.clone()).unwrap())
+    );
+    assert_eq!(
+        vec![10, 12, 14, 16, 18, 20],
+        keys(tree.range(ten.clone()..=twenty.clone()).unwrap())
+    );
+    assert_eq!(
+        vec![12, 14, 16, 18, 20],
+        keys(
+            tree.range((Bound::Excluded(ten), Bound::Included(twenty)))
+                .unwrap()
+        )
+    );
+    assert_eq!(
+        vec![0, 2, 4],
+        keys(tree.range(..Field::Integer(5)).unwrap())
+    );
+    assert_eq!(
+        vec![994, 996, 998],
+        keys(tree.range(Field::Integer(993)..).unwrap())
+    );
+    assert_eq!(500, tree.range(..).unwrap().len());
+    assert!(tree
+        .range(Field::Integer(11)..Field::Integer(12))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_scan_bounds_and_direction() {
+    let tree = Arc::new(int_tree(64));
+    let mut oracle = BTreeSet::new();
+    for key in (0..400).step_by(2) {
+        // Every key is shared by two rows.
+        for slot_id in [1, 0] {
+            tree.insert(Field::Integer(key), RecordId::new(slot_id, key as u16))
+                .unwrap();
+            oracle.insert((key, RecordId::new(slot_id, key as u16)));
+        }
+    }
+
+    let bounds = |key: Option<i32>, inclusive: bool| match key {
+        Some(key) if inclusive => Bound::Included(key),
+        Some(key) => Bound::Excluded(key),
+        None => Bound::Unbounded,
+    };
+    for (start, end) in [
+        (Some(10), Some(20)),
+        (Some(11), Some(21)),
+        (Some(-5), Some(3)),
+        (Some(390), Some(500)),
+        (None, Some(7)),
+        (Some(393), None),
+        (None, None),
+        (Some(20), Some(20)),
+        (Some(11), Some(11)),
+    ] {
+        for (start_inclusive, end_inclusive) in [(true, true), (true, false), (false, true)] {
+            let (start, end) = (bounds(start, start_inclusive), bounds(end, end_inclusive));
+            let range = (start.map(Field::Integer), end.map(Field::Integer));
+            let expected: Vec<_> = oracle
+                .iter()
+                .filter(|(key, _)| (start, end).contains(key))
+                .map(|(_, rid)| rid.clone())
+                .collect();
+            let forward: Vec<_> = tree
+                .scan(range.clone(), false)
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(expected, forward, "{range:?}");
+            let mut backward: Vec<_> = tree
+                .scan(range.clone(), true)
+                .collect::<Result<_>>()
+                .unwrap();
+            backward.reverse();
+            assert_eq!(expected, backward, "{range:?} reversed");
+        }
+    }
+    assert_eq!(
+        0,
+        tree.scan(Field::Integer(20)..Field::Integer(10), false)
+            .count()
+    );
+    assert_eq!(
+        0,
+        tree.scan(Field::Integer(20)..Field::Integer(10), true)
+            .count()
+    );
+    assert_eq!(0, Arc::new(int_tree(8)).scan(.., false).count());
+}
+
+#[test]
+fn test_scan_across_interleaved_changes() {
+    let tree = Arc::new(int_tree(64));
+    for key in (0..1000).step_by(2) {
+        tree.insert(Field::Integer(key), RecordId::new(1, 0))
+            .unwrap();
+    }
+
+    // Entries in the tree throughout are returned exactly once and in order, however the pages
+    // split and merge between calls.
+    for reverse in [false, true] {
+        let mut iter = tree.scan(.., reverse);
+        let mut rids = Vec::new();
+        for step in 0..500 {
+            rids.push(iter.next().unwrap().unwrap());
+            let odd = if reverse {
+                999 - 2 * step
+            } else {
+                2 * step + 1
+            };
+            tree.insert(Field::Integer(odd), RecordId::new(2, 0))
+                .unwrap();
+            tree.delete(&Field::Integer(odd), &RecordId::new(2, 0))
+                .unwrap();
+            if step % 50 == 0 {
+                for key in (1..1000).step_by(2) {
+                    tree.insert(Field::Integer(key), RecordId::new(3, 0))
+                        .unwrap();
+                }
+                for key in (1..1000).step_by(2) {
+                    tree.delete(&Field::Integer(key), &RecordId::new(3, 0))
+                        .unwrap();
+                }
+            }
+        }
+        assert!(iter.next().is_none());
+        assert_eq!(vec![RecordId::new(1, 0); 500], rids);
+        check_invariants(&tree);
+    }
+}
+
+#[test]
+fn test_delete_collapses_to_empty_tree() {
+    let tree = int_tree(64);
+    let mut rng = ChaCha8Rng::seed_from_u64(14);
+    let mut keys: Vec<i32> = (0..2000).collect();
+    for key in &keys {
+        tree.insert(Field::Integer(*key), RecordId::new(1, 0))
+            .unwrap();
+    }
+    assert!(tree_height(&tree) > 3);
+
+    for i in (1..keys.len()).rev() {
+        keys.swap(i, rng.gen_range(0..=i));
+    }
+    for key in keys {
+        assert!(tree
+            .delete(&Field::Integer(key), &RecordId::new(1, 0))
+            .unwrap());
+        assert!(!tree
+            .delete(&Field::Integer(key), &RecordId::new(1, 0))
+            .unwrap());
+        check_invariants(&tree);
+    }
+    assert!(tree.is_empty());
+    assert_eq!(INVALID_PID, tree.root_page_id());
+    assert!(tree.range(..).unwrap().is_empty());
+
+    // The tree grows again from scratch.
+    tree.insert(Field::Integer(1), RecordId::new(1, 0)).unwrap();
+    assert_eq!(
+        vec![RecordId::new(1, 0)],
+        tree.get(&Field::Integer(1)).unwrap()
+    );
+}
+
+#[test]
+fn test_randomized_against_oracle() {
+    let tree = int_tree(1024);
+    let mut oracle = BTreeSet::new();
+    let mut rng = ChaCha8Rng::seed_from_u64(2968);
+
+    // Grow the tree, then shrink it again, with every key shared by a handful of rows.
+    for op in 0..12_000 {
+        let insert_ratio = if (op / 3_000) % 2 == 0 { 0.7 } else { 0.3 };
+        let key = rng.gen_range(0..100);
+        let rid = RecordId::new(rng.gen_range(0..2), rng.gen_range(0..4));
+        if rng.gen_bool(insert_ratio) {
+            let inserted = oracle.insert((key, rid.clone()));
+            assert_eq!(inserted, tree.insert(Field::Integer(key), rid).is_ok());
+        } else {
+            let deleted = oracle.remove(&(key, rid.clone()));
+            assert_eq!(deleted, tree.delete(&Field::Integer(key), &rid).unwrap());
+        }
+
+        let expected: Vec<_> = oracle
+            .range((key, RecordId::new(0, 0))..)
+            .take_while(|(k, _)| *k == key)
+            .map(|(_, rid)| rid.clone())
+            .collect();
+        assert_eq!(expected, tree.get(&Field::Integer(key)).unwrap(), "op {op}");
+        if cfg!(debug_assertions) {
+            assert_eq!(oracle.len(), check_invariants(&tree), "op {op}");
+        }
+    }
+
+    let all: Vec<_> = oracle.iter().map(|(_, rid)| rid.clone()).collect();
+    assert_eq!(all, tree.range(..).unwrap());
+    for (key, rid) in oracle {
+        assert!(tree.delete(&Field::Integer(key), &rid).unwrap());
+    }
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn test_randomized_large_tree() {
+    let tree = int_tree(64);
+    let mut oracle = BTreeSet::new();
+    let mut rng = ChaCha8Rng::seed_from_u64(29680);
+
+    // Fill the tree with tens of thousands of entries in random order, then empty it again.
+    let mut entries: Vec<_> = (0..40_000)
+        .map(|i| {
+            (
+                rng.gen_range(0..20_000),
+                RecordId::new(i / 1000, (i % 1000) as u16),
+            )
+        })
+        .collect();
+    for (i, (key, rid)) in entries.iter().enumerate() {
+        tree.insert(Field::Integer(*key), rid.clone()).unwrap();
+        oracle.insert((*key, rid.clone()));
+        if i % 10_000 == 0 {
+            assert_eq!(oracle.len(), check_invariants(&tree));
+        }
+    }
+    assert_eq!(oracle.len(), check_invariants(&tree));
+
+    for _ in 0..100 {
+        let (start, end) = (rng.gen_range(0..20_000), rng.gen_range(0..20_000));
+        let expected: Vec<_> = oracle
+            .range((start, RecordId::new(0, 0))..)
+            .take_while(|(key, _)| *key < end)
+            .map(|(_, rid)| rid.clone())
+            .collect();
+        assert_eq!(
+            expected,
+            tree.range(Field::Integer(start)..Field::Integer(end))
+                .unwrap()
+        );
+    }
+
+    for i in (1..entries.len()).rev() {
+        entries.swap(i, rng.gen_range(0..=i));
+    }
+    for (i, (key, rid)) in entries.iter().enumerate() {
+        assert!(tree.delete(&Field::Integer(*key), rid).unwrap());
+        oracle.remove(&(*key, rid.clone()));
+        if i % 10_000 == 0 {
+            assert_eq!(oracle.len(), check_invariants(&tree));
+        }
+    }
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn test_reopen_from_root_page_id() {
+    let tree = int_tree(8);
+    for key in 0..500 {
+        tree.insert(Field::Integer(key), RecordId::new(3, key as u16))
+            .unwrap();
+    }
+
+    // Every page is unpinned, and the tree can be picked up again from its root alone.
+    let reopened = BPlusTree::builder()
+        .buffer_pool_manager(Arc::clone(&tree.buffer_pool_manager))
+        .key_schema(tree.key_schema())
+        .leaf_max_size(4)
+        .internal_max_size(4)
+        .root_page_id(tree.root_page_id())
+        .build();
+    assert_eq!(500, check_invariants(&reopened));
+    assert_eq!(
+        vec![RecordId::new(3, 321)],
+        reopened.get(&Field::Integer(321)).unwrap()
+    );
+}
+
+#[test]
+fn test_verify_healthy_tree() {
+    let tree = int_tree(64);
+    assert_eq!(IndexCheckReport::default(), tree.verify().unwrap());
+
+    for key in 0..500 {
+        tree.insert(Field::Integer(key), RecordId::new(1, 0))
+            .unwrap();
+    }
+    let report = tree.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    let stats = report.stats;
+    assert_eq!(500, stats.entries);
+    assert_eq!(tree_height(&tree), stats.height);
+    assert!(stats.leaf_pages >= 125 && stats.internal_pages > 0);
+    assert!(stats.average_fill >= 0.5 && stats.average_fill <= 1.0);
+
+    // Entries are handed to the deep check in order.
+    let mut keys = Vec::new();
+    let report = tree
+        .verify_with(|key, _| {
+            keys.push(key.clone());
+            (*key == Field::Integer(42)).then(|| "bad entry".to_string())
+        })
+        .unwrap();
+    assert_eq!((0..500).map(Field::Integer).collect::<Vec<_>>(), keys);
+    assert_eq!(1, report.problems.len());
+    assert_eq!("bad entry", report.problems[0].description);
+}
+
+#[test]
+fn test_verify_reports_corrupted_pages() {
+    let corrupted = |corrupt: fn(&BPlusTree, PageId, PageId)| {
+        let tree = int_tree(64);
+        for key in 0..100 {
+            tree.insert(Field::Integer(key), RecordId::new(1, 0))
+                .unwrap();
+        }
+        assert!(tree.verify().unwrap().is_ok());
+        let root = tree.root_page_id();
+        let mut leaf = root;
+        while let Ok(internal) = tree.fetch(leaf).unwrap().internal() {
+            leaf = internal.read().unwrap().child_at(0);
+        }
+        corrupt(&tree, root, leaf);
+        let report = tree.verify().unwrap();
+        (root, leaf, report)
+    };
+
+    // A broken sibling link.
+    let (_, leaf, report) = corrupted(|tree, _, leaf| {
+        let page = tree.fetch(leaf).unwrap();
+        page.leaf()
+            .unwrap()
+            .write()
+            .unwrap()
+            .set_next_page_id(INVALID_PID);
+    });
+    assert_eq!(BTreeSet::from([leaf]), report.problem_pages());
+
+    // A wrong parent pointer.
+    let (_, leaf, report) = corrupted(|tree, root, leaf| {
+        let page = tree.fetch(leaf).unwrap();
+        page.leaf()
+            .unwrap()
+            .write()
+            .unwrap()
+            .set_parent_page_id(leaf + root);
+    });
+    assert_eq!(BTreeSet::from([leaf]), report.problem_pages());
+
+    // An entry outside the range the parent gives the leaf.
+    let (_, leaf, report) = corrupted(|tree, _, leaf| {
+        let page = tree.fetch(leaf).unwrap();
+        let mut leaf = page.leaf().unwrap().write().unwrap();
+        leaf.remove_at(0);
+        leaf.insert(Field::Integer(1000), RecordId::new(1, 0))
+            .unwrap();
+    });
+    assert_eq!(BTreeSet::from([leaf]), report.problem_pages());
+    assert!(report.problems[0].description.contains("lies outside"));
+
+    // Separators out of order, which also puts the entries below them out of range.
+    let (root, _, report) = corrupted(|tree, root, _| {
+        let page = tree.fetch(root).unwrap();
+        let mut internal = page.internal().unwrap().write().unwrap();
+        let (first, second) = (internal.key_at(1).clone(), internal.key_at(2).clone());
+        internal.set_key_at(1, second).unwrap();
+        internal.set_key_at(2, first).unwrap();
+    });
+    assert_eq!(root, report.problems[0].page_id);
+    assert!(report.problems[0].description.contains("not ordered"));
+    assert!(report.problem_pages().len() > 1);
+}
+
+/// Returns a tree of integer keys with tiny pages, so that a few hundred entries already take
+/// several levels, over a buffer pool of the given size.
+fn int_tree(pool_size: usize) -> BPlusTree {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(pool_size)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    BPlusTree::builder()
+        .buffer_pool_manager(bpm)
+        .key_schema(KeySchema::for_type(DataType::Int))
+        .leaf_max_size(4)
+        .internal_max_size(4)
+        .build()
+}
+
+fn tree_height(tree: &BPlusTree) -> usize {
+    let mut height = 1;
+    let mut page_id = tree.root_page_id();
+    while let Some(internal) = fetch(tree, page_id).as_internal() {
+        page_id = internal.read().unwrap().child_at(0);
+        height += 1;
+    }
+    height
+}
+
+/// Fetches a page of the tree, which no operation may have left pinned, and unpins it again
+/// straight away.
+fn fetch(tree: &BPlusTree, page_id: PageId) -> PageHandle {
+    let bpm = &tree.buffer_pool_manager;
+    assert!(
+        matches!(bpm.pin_count(&page_id), None | Some(0)),
+        "page {page_id} was left pinned"
+    );
+    let page = bpm.fetch_index_page(&page_id).unwrap();
+    bpm.unpin_page(&page_id, page.get_is_dirty()).unwrap();
+    page
+}
+
+/// Walks the whole tree, checking that entries are ordered within and across pages, that pages
+/// are neither overfull nor underfull, that parent pointers and the leaf sibling chain are
+/// consistent, and that every leaf is at the same depth. Returns the number of entries.
+fn check_invariants(tree: &BPlusTree) -> usize {
+    let root = tree.root_page_id();
+    if root == INVALID_PID {
+        return 0;
+    }
+    let mut leaves = Vec::new();
+    check_page(tree, root, INVALID_PID, None, None, 0, &mut leaves);
+
+    let depths: BTreeSet<_> = leaves.iter().map(|leaf| leaf.3).collect();
+    assert_eq!(1, depths.len(), "leaves at different depths");
+    for (i, (page_id, prev, next, _, _)) in leaves.iter().enumerate() {
+        let expected_prev = if i == 0 { INVALID_PID } else { leaves[i - 1].0 };
+        let expected_next = leaves.get(i + 1).map_or(INVALID_PID, |leaf| leaf.0);
+        assert_eq!(
+            (expected_prev, expected_next),
+            (*prev, *next),
+            "sibling links of leaf {page_id}"
+        );
+    }
+    leaves.iter().map(|leaf| leaf.4).sum()
+}
+
+/// Checks the subtree at `page_id`, whose entries must lie in `[lower, upper)`, collecting its
+/// leaves as (page id, prev, next, depth, size).
+fn check_page(
+    tree: &BPlusTree,
+    page_id: PageId,
+    parent_page_id: PageId,
+    lower: Option<&Separator>,
+    upper: Option<&Separator>,
+    depth: usize,
+    leaves: &mut Vec<(PageId, PageId, PageId, usize, usize)>,
+) {
+    let in_bounds = |entry: &Separator| {
+        lower.map_or(true, |lower| lower <= entry) && upper.map_or(true, |upper| entry < upper)
+    };
+    let is_root = parent_page_id == INVALID_PID;
+    let page = fetch(tree, page_id);
+
+    if let Some(leaf) = page.as_leaf() {
+        let leaf = leaf.read().unwrap();
+        assert_eq!(
+            parent_page_id,
+            leaf.parent_page_id(),
+            "parent of leaf {page_id}"
+        );
+        assert!(leaf.size() <= leaf.max_size(), "leaf {page_id} overfull");
+        let min_size = if is_root { 1 } else { leaf.min_size() };
+        assert!(leaf.size() >= min_size, "leaf {page_id} underfull");
+        assert!(
+            leaf.entries().windows(2).all(|pair| pair[0] < pair[1]),
+            "leaf {page_id} out of order"
+        );
+        assert!(
+            leaf.entries().iter().all(in_bounds),
+            "leaf {page_id} out of bounds"
+        );
+        leaves.push((
+            page_id,
+            leaf.prev_page_id(),
+            leaf.next_page_id(),
+            depth,
+            leaf.size(),
+        ));
+        return;
+    }
+
+    let internal = page.as_internal().unwrap();
+    let internal = internal.read().unwrap().clone();
+    assert_eq!(
+        parent_page_id,
+        internal.parent_page_id(),
+        "parent of page {page_id}"
+    );
+    assert!(
+        internal.size() <= internal.max_size(),
+        "page {page_id} overfull"
+    );
+    let min_size = if is_root { 2 } else { internal.min_size() };
+    assert!(internal.size() >= min_size, "page {page_id} underfull");
+    assert_eq!(internal.keys().len() + 1, internal.size());
+    assert!(
+        internal.keys().windows(2).all(|pair| pair[0] < pair[1]),
+        "page {page_id} out of order"
+    );
+    assert!(
+        internal.keys().iter().all(in_bounds),
+        "page {page_id} out of bounds"
+    );
+    for (i, child) in internal.children().iter().enumerate() {
+        let child_lower = if i == 0 {
+            lower
+        } else {
+            Some(internal.key_at(i))
+        };
+        let child_upper = internal.keys().get(i).or(upper);
+        check_page(
+            tree,
+            *child,
+            page_id,
+            child_lower,
+            child_upper,
+            depth + 1,
+            leaves,
+        );
+    }
+}
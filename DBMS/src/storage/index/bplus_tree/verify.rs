@@ -0,0 +1,326 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::index::bplus_tree::BPlusTree;
+use crate::storage::page::{RecordId, Separator};
+use crate::types::field::Field;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Statistics about the shape of a B+tree, gathered by [`BPlusTree::verify`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexStats {
+    /// The number of levels of pages, 0 for an empty tree.
+    pub height: usize,
+    pub leaf_pages: usize,
+    pub internal_pages: usize,
+    pub entries: usize,
+    /// The fraction of its capacity the average page fills, between 0 and 1.
+    pub average_fill: f64,
+}
+
+/// A problem found on a page of a B+tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexProblem {
+    pub page_id: PageId,
+    pub description: String,
+}
+
+impl Display for IndexProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "page {}: {}", self.page_id, self.description)
+    }
+}
+
+/// The outcome of checking a B+tree: its statistics, and every problem found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexCheckReport {
+    pub stats: IndexStats,
+    pub problems: Vec<IndexProblem>,
+}
+
+impl IndexCheckReport {
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns the ids of the pages problems were found on.
+    pub fn problem_pages(&self) -> BTreeSet<PageId> {
+        self.problems
+            .iter()
+            .map(|problem| problem.page_id)
+            .collect()
+    }
+
+    fn problem(&mut self, page_id: PageId, description: String) {
+        self.problems.push(IndexProblem {
+            page_id,
+            description,
+        });
+    }
+}
+
+impl BPlusTree {
+    /// Walks the whole tree, checking its structure and gathering statistics. See
+    /// [`Self::verify_with`].
+    pub fn verify(&self) -> Result<IndexCheckReport> {
+        self.verify_with(|_, _| None)
+    }
+
+    /// Walks the whole tree, checking that:
+    ///
+    /// * Pages can be read, and are B+tree pages reached exactly once.
+    /// * Pages are neither overfull nor underfull, and point back at their parent.
+    /// * Entries and separators are ordered within each page, and lie within the range the
+    ///   parent's separators give the page.
+    /// * Keys match the tree's key schema.
+    /// * Leaves are all at the same depth, and chained to their siblings in order.
+    ///
+    /// `check_entry` is called on every entry, e.g. to check it against the table, and returns a
+    /// description of what is wrong with it, if anything. It is called with no page latched.
+    ///
+    /// The tree latch is held throughout, so the tree doesn't change underneath the check.
+    /// Problems are collected in the report rather than returned as errors, which are left to
+    /// failures of the buffer pool itself.
+    pub fn verify_with(
+        &self,
+        check_entry: impl FnMut(&Field, &RecordId) -> Option<String>,
+    ) -> Result<IndexCheckReport> {
+        let root = self.root_page_id.read().unwrap();
+        let mut verifier = Verifier {
+            tree: self,
+            check_entry,
+            report: IndexCheckReport::default(),
+            visited: HashSet::new(),
+            leaves: Vec::new(),
+            fill: 0.0,
+        };
+        if *root != INVALID_PID {
+            verifier.check_page(*root, INVALID_PID, None, None, 0)?;
+            verifier.check_leaves();
+        }
+        Ok(verifier.finish())
+    }
+}
+
+/// A leaf reached by the walk, in key order.
+struct LeafLinks {
+    page_id: PageId,
+    prev_page_id: PageId,
+    next_page_id: PageId,
+    depth: usize,
+}
+
+/// The state of a walk over a tree.
+struct Verifier<'a, F> {
+    tree: &'a BPlusTree,
+    check_entry: F,
+    report: IndexCheckReport,
+    visited: HashSet<PageId>,
+    leaves: Vec<LeafLinks>,
+    /// The sum of the pages' fill fractions.
+    fill: f64,
+}
+
+impl<F: FnMut(&Field, &RecordId) -> Option<String>> Verifier<'_, F> {
+    /// Checks the subtree at `page_id`, whose entries must lie in `[lower, upper)`.
+    fn check_page(
+        &mut self,
+        page_id: PageId,
+        parent_page_id: PageId,
+        lower: Option<&Separator>,
+        upper: Option<&Separator>,
+        depth: usize,
+    ) -> Result<()> {
+        if !self.visited.insert(page_id) {
+            let description = format!("reached again from page {parent_page_id}");
+            self.report.problem(page_id, description);
+            return Ok(());
+        }
+        let page = match self.tree.fetch(page_id) {
+            Ok(page) => page,
+            Err(error) => {
+                self.report
+                    .problem(page_id, format!("can't be read: {error}"));
+                return Ok(());
+            }
+        };
+        let in_bounds = |entry: &Separator| {
+            lower.is_none_or(|lower| lower <= entry) && upper.is_none_or(|upper| entry < upper)
+        };
+        let is_root = parent_page_id == INVALID_PID;
+        let mut problems = Vec::new();
+
+        if let Ok(leaf) = page.leaf() {
+            let leaf = leaf.read().unwrap();
+            if leaf.parent_page_id() != parent_page_id {
+                problems.push(format!(
+                    "points at parent {} instead of {parent_page_id}",
+                    leaf.parent_page_id()
+                ));
+            }
+            let min_size = if is_root { 1 } else { leaf.min_size() };
+            problems.extend(size_problem(leaf.size(), min_size, leaf.max_size()));
+            if let Some(pair) = leaf.entries().windows(2).find(|pair| pair[0] >= pair[1]) {
+                problems.push(format!(
+                    "entry {} is not ordered before {}",
+                    entry(&pair[0]),
+                    entry(&pair[1])
+                ));
+            }
+            if let Some(outside) = leaf.entries().iter().find(|e| !in_bounds(e)) {
+                problems.push(format!(
+                    "entry {} lies outside {}",
+                    entry(outside),
+                    range(lower, upper)
+                ));
+            }
+            for (key, _) in leaf.entries() {
+                if let Err(error) = self.tree.key_schema.check(key) {
+                    problems.push(error.to_string());
+                }
+            }
+            self.fill += leaf.size() as f64 / leaf.max_size() as f64;
+            self.report.stats.leaf_pages += 1;
+            self.report.stats.entries += leaf.size();
+            self.leaves.push(LeafLinks {
+                page_id,
+                prev_page_id: leaf.prev_page_id(),
+                next_page_id: leaf.next_page_id(),
+                depth,
+            });
+            let entries = leaf.entries().to_vec();
+            drop(leaf);
+            drop(page);
+            for (key, rid) in &entries {
+                problems.extend((self.check_entry)(key, rid));
+            }
+            self.report_all(page_id, problems);
+            return Ok(());
+        }
+
+        let Ok(internal) = page.internal() else {
+            self.report
+                .problem(page_id, "is not a B+tree page".to_string());
+            return Ok(());
+        };
+        let internal = internal.read().unwrap().clone();
+        drop(page);
+        if internal.parent_page_id() != parent_page_id {
+            problems.push(format!(
+                "points at parent {} instead of {parent_page_id}",
+                internal.parent_page_id()
+            ));
+        }
+        let min_size = if is_root { 2 } else { internal.min_size() };
+        problems.extend(size_problem(internal.size(), min_size, internal.max_size()));
+        if internal.keys().len() + 1 != internal.size() {
+            problems.push(format!(
+                "has {} separators for {} children",
+                internal.keys().len(),
+                internal.size()
+            ));
+        }
+        let keys = internal.keys();
+        if let Some(pair) = keys.windows(2).find(|pair| pair[0] >= pair[1]) {
+            problems.push(format!(
+                "separator {} is not ordered before {}",
+                entry(&pair[0]),
+                entry(&pair[1])
+            ));
+        }
+        if let Some(outside) = keys.iter().find(|key| !in_bounds(key)) {
+            problems.push(format!(
+                "separator {} lies outside {}",
+                entry(outside),
+                range(lower, upper)
+            ));
+        }
+        self.fill += internal.size() as f64 / internal.max_size() as f64;
+        self.report.stats.internal_pages += 1;
+        self.report_all(page_id, problems);
+
+        for (i, child) in internal.children().iter().enumerate() {
+            let child_lower = match i {
+                0 => lower,
+                _ => keys.get(i - 1),
+            };
+            let child_upper = keys.get(i).or(upper);
+            self.check_page(*child, page_id, child_lower, child_upper, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the leaves are all at the same depth, and that each links to the leaves
+    /// before and after it.
+    fn check_leaves(&mut self) {
+        let depth = self.leaves.iter().map(|leaf| leaf.depth).max().unwrap_or(0);
+        self.report.stats.height = depth + 1;
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            let mut problems = Vec::new();
+            if leaf.depth != depth {
+                problems.push(format!(
+                    "is a leaf at depth {}, others are at depth {depth}",
+                    leaf.depth
+                ));
+            }
+            let prev = match i {
+                0 => INVALID_PID,
+                _ => self.leaves[i - 1].page_id,
+            };
+            let next = self
+                .leaves
+                .get(i + 1)
+                .map_or(INVALID_PID, |leaf| leaf.page_id);
+            if (leaf.prev_page_id, leaf.next_page_id) != (prev, next) {
+                problems.push(format!(
+                    "links to siblings {} and {} instead of {prev} and {next}",
+                    leaf.prev_page_id, leaf.next_page_id
+                ));
+            }
+            for description in problems {
+                self.report.problems.push(IndexProblem {
+                    page_id: leaf.page_id,
+                    description,
+                });
+            }
+        }
+    }
+
+    fn report_all(&mut self, page_id: PageId, problems: Vec<String>) {
+        for description in problems {
+            self.report.problem(page_id, description);
+        }
+    }
+
+    fn finish(mut self) -> IndexCheckReport {
+        let stats = &mut self.report.stats;
+        let pages = stats.leaf_pages + stats.internal_pages;
+        if pages > 0 {
+            stats.average_fill = self.fill / pages as f64;
+        }
+        self.report
+    }
+}
+
+/// Describes how a page's size is out of bounds, if it is.
+fn size_problem(size: usize, min_size: usize, max_size: usize) -> Option<String> {
+    if size > max_size {
+        return Some(format!("is overfull, with {size} of at most {max_size}"));
+    }
+    if size < min_size {
+        return Some(format!("is underfull, with {size} of at least {min_size}"));
+    }
+    None
+}
+
+fn entry((key, rid): &Separator) -> String {
+    format!("({key}, {})", rid.to_string())
+}
+
+fn range(lower: Option<&Separator>, upper: Option<&Separator>) -> String {
+    let lower = lower.map_or("unbounded".to_string(), entry);
+    let upper = upper.map_or("unbounded".to_string(), entry);
+    format!("[{lower}, {upper})")
+}
@@ -1,25 +1,94 @@
-use crate::storage::page::RecordId;
-use crate::storage::tuple::Tuple;
+use crate::common::Result;
+use crate::config::config::MAX_INDEX_KEY_LENGTH;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::index::bplus_tree::{BPlusTree, IndexIterator};
+use crate::storage::page::{KeySchema, RecordId};
+use crate::storage::tuple::Row;
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+use std::ops::RangeBounds;
+use std::sync::Arc;
 
-// TODO(eyoon): make this lol
-#[derive(Debug)]
-pub struct TableIndex {}
+/// A secondary index over one column of a table, backed by a B+tree from the column's values to
+/// the record ids of the tuples holding them.
+///
+/// Every version of a row is indexed, from when it is inserted until it is tombstoned or vacuumed
+/// away, so readers have to check the versions they find through the index for visibility. For the
+/// same reason, a unique index may hold several entries with the same key, and writers have to
+/// check that at most one of them is live.
+#[derive(Clone, Debug)]
+pub struct TableIndex {
+    name: String,
+    column: usize,
+    unique: bool,
+    tree: Arc<BPlusTree>,
+}
 
 impl TableIndex {
-    pub fn new() -> Self {
-        Self {}
+    /// Creates an empty index over the given column of a table.
+    pub fn new(table: &Table, column: usize, bpm: &Arc<BufferPoolManager>) -> Self {
+        let definition = table.get_column(column);
+        let key_schema = match definition.get_data_type() {
+            // Text keys are stored in fixed-size slots, so unbounded columns get a default bound.
+            DataType::Text => KeySchema::new(
+                DataType::Text,
+                match definition.get_max_str_len() {
+                    0 => MAX_INDEX_KEY_LENGTH as u16,
+                    len => len,
+                },
+            ),
+            data_type => KeySchema::for_type(data_type),
+        };
+        Self {
+            name: format!("{}_{}_idx", table.name(), definition.get_name()),
+            column,
+            unique: definition.is_unique(),
+            tree: Arc::new(
+                BPlusTree::builder()
+                    .buffer_pool_manager(Arc::clone(bpm))
+                    .key_schema(key_schema)
+                    .build(),
+            ),
+        }
     }
-    pub fn iter(&self) -> TableIndexIterator {
-        todo!();
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
-}
 
-pub struct TableIndexIterator {}
+    /// Returns the position of the indexed column in the table's schema.
+    pub fn column(&self) -> usize {
+        self.column
+    }
 
-impl Iterator for TableIndexIterator {
-    type Item = (RecordId, Tuple);
+    /// Returns whether the indexed column is declared unique.
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn tree(&self) -> &Arc<BPlusTree> {
+        &self.tree
+    }
+
+    /// Returns the key the index stores for a row. Fails if the key doesn't fit the index.
+    pub fn key(&self, row: &Row) -> Result<Field> {
+        let key = row.get_field(self.column)?;
+        self.tree.key_schema().check(&key)?;
+        Ok(key)
+    }
+
+    pub fn insert(&self, key: Field, rid: RecordId) -> Result<()> {
+        self.tree.insert(key, rid)
+    }
+
+    /// Removes an entry, returning whether it was in the index.
+    pub fn delete(&self, key: &Field, rid: &RecordId) -> Result<bool> {
+        self.tree.delete(key, rid)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        todo!()
+    /// Returns an iterator over the record ids of the entries with a key in `range`, in key order,
+    /// or in reverse if `reverse` is set.
+    pub fn scan<R: RangeBounds<Field>>(&self, range: R, reverse: bool) -> IndexIterator {
+        self.tree.scan(range, reverse)
     }
 }
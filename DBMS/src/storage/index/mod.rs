@@ -1,3 +1,4 @@
+pub mod bplus_tree;
 mod index;
 
-pub use index::{TableIndex, TableIndexIterator};
+pub use index::TableIndex;
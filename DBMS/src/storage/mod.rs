@@ -1,3 +1,4 @@
+pub mod backup;
 pub mod buffer;
 pub mod disk;
 pub mod engine;
@@ -7,6 +8,7 @@ pub mod page;
 pub mod simple;
 mod tables;
 pub mod tuple;
+pub mod wal;
 
-pub use engine::{Engine, Key, ScanIterator};
+pub use engine::{Engine, Key, ScanIterator, VacuumStats};
 pub use tables::{HeapTableManager, KeyDirectory};
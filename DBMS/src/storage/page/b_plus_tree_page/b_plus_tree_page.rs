@@ -0,0 +1,222 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
+use crate::errinput;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::RecordId;
+use crate::storage::wal::{Lsn, INVALID_LSN};
+use crate::types::field::Field;
+use crate::types::DataType;
+use std::mem;
+
+/// Size of the header shared by every B+tree page:
+/// | page_type (1) | key_type (1) | key_size (2) | page_id (4) | parent_page_id (4) | size (2) |
+/// | max_size (2) | lsn (8) |
+pub(crate) const HEADER_SIZE: usize = 1 + 1 + 2 + 4 + 4 + 2 + 2 + mem::size_of::<Lsn>();
+
+/// Size of a serialized record id: | page_id (4) | slot_id (2) |
+pub(crate) const RID_SIZE: usize = mem::size_of::<PageId>() + mem::size_of::<u16>();
+
+/// Identifies the kind of node stored on a B+tree page. Written as the first byte of the page, so
+/// a page can be told apart before it is deserialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BPlusTreePageType {
+    Leaf = 1,
+    Internal = 2,
+}
+
+impl BPlusTreePageType {
+    /// Returns the type of the serialized B+tree page in `buffer`, if it holds one.
+    pub fn of(buffer: &[u8]) -> Option<Self> {
+        match buffer.first() {
+            Some(1) => Some(Self::Leaf),
+            Some(2) => Some(Self::Internal),
+            _ => None,
+        }
+    }
+}
+
+/// The type of a B+tree's keys, and the fixed number of bytes each key takes up on a page.
+///
+/// Keys are stored in fixed-size slots: a null flag, the length of the encoded key, then the
+/// [`Field::serialize`] bytes padded to `size`. Strings longer than `size` don't fit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySchema {
+    data_type: DataType,
+    size: u16,
+}
+
+impl KeySchema {
+    /// Creates a schema for keys of the given type taking up to `size` bytes each.
+    pub fn new(data_type: DataType, size: u16) -> Self {
+        Self { data_type, size }
+    }
+
+    /// Creates a schema for keys of a fixed-size type, e.g. [`DataType::Int`].
+    pub fn for_type(data_type: DataType) -> Self {
+        Self::new(data_type, data_type.length_bytes())
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    /// Returns the largest encoded key the schema allows, in bytes.
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Returns the number of bytes a key slot takes up on a page.
+    pub fn slot_size(&self) -> usize {
+        1 + 2 + self.size as usize
+    }
+
+    /// Checks that the key has the schema's type and fits in a slot.
+    pub fn check(&self, key: &Field) -> Result<()> {
+        if key.is_null() {
+            return Ok(());
+        }
+        if key.get_type() != self.data_type {
+            return errinput!("expected a {} key, got {}", self.data_type, key.get_type());
+        }
+        if key.get_size() > self.size {
+            return errinput!("key {key} is longer than {} bytes", self.size);
+        }
+        Ok(())
+    }
+
+    /// Writes the key into the slot at the start of `buffer`.
+    pub(crate) fn encode(&self, key: &Field, buffer: &mut [u8]) {
+        let slot = &mut buffer[..self.slot_size()];
+        slot.fill(0);
+        if key.is_null() {
+            return;
+        }
+        let bytes = key.serialize();
+        slot[0] = 1;
+        slot[1..3].copy_from_slice(&(bytes.len() as u16).to_le_bytes());
+        slot[3..(3 + bytes.len())].copy_from_slice(&bytes);
+    }
+
+    /// Reads the key from the slot at the start of `buffer`.
+    pub(crate) fn decode(&self, buffer: &[u8]) -> Field {
+        if buffer[0] == 0 {
+            return Field::Null;
+        }
+        let len = u16::from_le_bytes([buffer[1], buffer[2]]) as usize;
+        Field::deserialize(&buffer[3..(3 + len)], self.data_type)
+    }
+
+    fn type_to_byte(&self) -> u8 {
+        match self.data_type {
+            DataType::Bool => 0,
+            DataType::Int => 1,
+            DataType::Float => 2,
+            DataType::Text => 3,
+            DataType::Invalid => 4,
+        }
+    }
+
+    fn type_from_byte(byte: u8) -> DataType {
+        match byte {
+            0 => DataType::Bool,
+            1 => DataType::Int,
+            2 => DataType::Float,
+            3 => DataType::Text,
+            _ => DataType::Invalid,
+        }
+    }
+}
+
+/// The state shared by leaf and internal B+tree pages, serialized at the start of the page.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BPlusTreeHeader {
+    pub(crate) page_type: BPlusTreePageType,
+    pub(crate) key_schema: KeySchema,
+    pub(crate) page_id: PageId,
+    pub(crate) parent_page_id: PageId,
+    pub(crate) max_size: u16,
+    // LSN of the last logged change applied to this page.
+    pub(crate) lsn: Lsn,
+    pub(crate) is_dirty: bool,
+}
+
+impl BPlusTreeHeader {
+    /// Creates the header of an empty page. The page holds as many entries of `entry_size` bytes
+    /// as fit after a header of `header_size` bytes, unless `max_size` asks for fewer.
+    pub(crate) fn new(
+        page_type: BPlusTreePageType,
+        key_schema: KeySchema,
+        page_id: PageId,
+        header_size: usize,
+        entry_size: usize,
+        max_size: Option<u16>,
+    ) -> Self {
+        let capacity =
+            ((RUSTY_DB_PAGE_PAYLOAD_BYTES - header_size) / entry_size).min(u16::MAX as usize);
+        let max_size = max_size.unwrap_or(capacity as u16);
+        assert!(
+            (max_size as usize) <= capacity,
+            "a page holds at most {capacity} entries of {entry_size} bytes, not {max_size}"
+        );
+        Self {
+            page_type,
+            key_schema,
+            page_id,
+            parent_page_id: INVALID_PID,
+            max_size,
+            lsn: INVALID_LSN,
+            is_dirty: false,
+        }
+    }
+
+    /// Writes the header, along with the page's current number of entries, to the start of
+    /// `buffer`.
+    pub(crate) fn serialize(&self, size: u16, buffer: &mut [u8]) {
+        buffer[0] = self.page_type as u8;
+        buffer[1] = self.key_schema.type_to_byte();
+        buffer[2..4].copy_from_slice(&self.key_schema.size.to_le_bytes());
+        buffer[4..8].copy_from_slice(&self.page_id.to_le_bytes());
+        buffer[8..12].copy_from_slice(&self.parent_page_id.to_le_bytes());
+        buffer[12..14].copy_from_slice(&size.to_le_bytes());
+        buffer[14..16].copy_from_slice(&self.max_size.to_le_bytes());
+        buffer[16..HEADER_SIZE].copy_from_slice(&self.lsn.to_le_bytes());
+    }
+
+    /// Reads a header from the start of `buffer`, returning it along with the page's number of
+    /// entries.
+    pub(crate) fn deserialize(buffer: &[u8]) -> (Self, u16) {
+        let u16_at = |at: usize| u16::from_le_bytes(buffer[at..(at + 2)].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(buffer[at..(at + 4)].try_into().unwrap());
+        let header = Self {
+            page_type: BPlusTreePageType::of(buffer).expect("not a B+tree page"),
+            key_schema: KeySchema::new(KeySchema::type_from_byte(buffer[1]), u16_at(2)),
+            page_id: u32_at(4),
+            parent_page_id: u32_at(8),
+            max_size: u16_at(14),
+            lsn: Lsn::from_le_bytes(buffer[16..HEADER_SIZE].try_into().unwrap()),
+            is_dirty: false,
+        };
+        (header, u16_at(12))
+    }
+}
+
+/// Writes the record id to the start of `buffer`.
+pub(crate) fn encode_rid(rid: &RecordId, buffer: &mut [u8]) {
+    buffer[0..4].copy_from_slice(&rid.page_id().to_le_bytes());
+    buffer[4..RID_SIZE].copy_from_slice(&rid.slot_id().to_le_bytes());
+}
+
+/// Reads a record id from the start of `buffer`.
+pub(crate) fn decode_rid(buffer: &[u8]) -> RecordId {
+    RecordId::new(
+        u32::from_le_bytes(buffer[0..4].try_into().unwrap()),
+        u16::from_le_bytes([buffer[4], buffer[5]]),
+    )
+}
+
+/// The error returned by the tuple accessors of the [`crate::storage::page::Page`] trait, which
+/// B+tree pages don't support.
+pub(crate) fn no_tuples<T>() -> Result<T> {
+    errinput!("B+tree pages store index entries, not tuples")
+}
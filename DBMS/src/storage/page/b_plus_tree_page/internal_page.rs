@@ -0,0 +1,342 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
+use crate::errinput;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::b_plus_tree_page::b_plus_tree_page::{
+    decode_rid, encode_rid, no_tuples, BPlusTreeHeader, BPlusTreePageType, KeySchema, HEADER_SIZE,
+    RID_SIZE,
+};
+use crate::storage::page::{Page, RecordId};
+use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
+use crate::types::field::Field;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+pub type BPlusTreeInternalPageHandle = Arc<RwLock<BPlusTreeInternalPage>>;
+
+/// A separator on an internal page: a key, and the record id that orders duplicates of it.
+pub type Separator = (Field, RecordId);
+
+/// An internal node of a B+tree, routing keys to the child pages below it.
+///
+/// A page with `n` children has `n - 1` separators, in order: child `i` holds the entries from
+/// separator `i - 1` (inclusive) up to separator `i` (exclusive). Separators are ordered like leaf
+/// entries, by key then by record id, so duplicates of a key can span several children. On disk,
+/// every child is stored next to a separator slot, and the first child's slot is left empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BPlusTreeInternalPage {
+    pub(crate) header: BPlusTreeHeader,
+    pub(crate) keys: Vec<Separator>,
+    pub(crate) children: Vec<PageId>,
+}
+
+impl BPlusTreeInternalPage {
+    pub fn builder() -> BPlusTreeInternalPageBuilder {
+        BPlusTreeInternalPageBuilder::default()
+    }
+
+    pub fn key_schema(&self) -> KeySchema {
+        self.header.key_schema
+    }
+
+    pub fn parent_page_id(&self) -> PageId {
+        self.header.parent_page_id
+    }
+
+    pub fn set_parent_page_id(&mut self, page_id: PageId) {
+        self.header.parent_page_id = page_id;
+        self.header.is_dirty = true;
+    }
+
+    /// Returns the number of children of the page.
+    pub fn size(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Returns the number of children the page can hold.
+    pub fn max_size(&self) -> usize {
+        self.header.max_size as usize
+    }
+
+    /// Returns the number of children below which a non-root page is underfull.
+    pub fn min_size(&self) -> usize {
+        self.max_size().div_ceil(2)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.size() >= self.max_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn keys(&self) -> &[Separator] {
+        &self.keys
+    }
+
+    pub fn children(&self) -> &[PageId] {
+        &self.children
+    }
+
+    /// Returns the separator between children `index - 1` and `index`.
+    pub fn key_at(&self, index: usize) -> &Separator {
+        &self.keys[index - 1]
+    }
+
+    pub fn set_key_at(&mut self, index: usize, separator: Separator) -> Result<()> {
+        self.header.key_schema.check(&separator.0)?;
+        self.keys[index - 1] = separator;
+        self.header.is_dirty = true;
+        Ok(())
+    }
+
+    pub fn child_at(&self, index: usize) -> PageId {
+        self.children[index]
+    }
+
+    /// Returns the index of the given child, if it is a child of this page.
+    pub fn child_index_of(&self, child: PageId) -> Option<usize> {
+        self.children.iter().position(|c| *c == child)
+    }
+
+    /// Binary searches the separators for the index of the child whose subtree covers the entry.
+    pub fn child_index(&self, key: &Field, rid: &RecordId) -> usize {
+        self.keys
+            .partition_point(|(k, r)| k.cmp(key).then_with(|| r.cmp(rid)).is_le())
+    }
+
+    /// Returns the child whose subtree covers the entry.
+    pub fn lookup(&self, key: &Field, rid: &RecordId) -> PageId {
+        self.children[self.child_index(key, rid)]
+    }
+
+    /// Turns an empty page into a new root with two children, split by `separator`.
+    pub fn init_root(&mut self, left: PageId, separator: Separator, right: PageId) -> Result<()> {
+        self.header.key_schema.check(&separator.0)?;
+        if !self.is_empty() {
+            return errinput!("internal page {} is not empty", self.header.page_id);
+        }
+        self.keys = vec![separator];
+        self.children = vec![left, right];
+        self.header.is_dirty = true;
+        Ok(())
+    }
+
+    /// Inserts a child at the given index, along with the separator to its left. Fails if the
+    /// page is full, or the key doesn't match the page's key schema.
+    pub fn insert(&mut self, index: usize, separator: Separator, child: PageId) -> Result<()> {
+        self.header.key_schema.check(&separator.0)?;
+        if self.is_full() {
+            return errinput!("internal page {} is full", self.header.page_id);
+        }
+        if index == 0 || index > self.size() {
+            return errinput!("cannot insert child at index {index}");
+        }
+        self.keys.insert(index - 1, separator);
+        self.children.insert(index, child);
+        self.header.is_dirty = true;
+        Ok(())
+    }
+
+    /// Inserts a child before the first one, along with the separator between it and the old
+    /// first child.
+    pub fn push_front(&mut self, child: PageId, separator: Separator) -> Result<()> {
+        self.header.key_schema.check(&separator.0)?;
+        if self.is_full() {
+            return errinput!("internal page {} is full", self.header.page_id);
+        }
+        self.keys.insert(0, separator);
+        self.children.insert(0, child);
+        self.header.is_dirty = true;
+        Ok(())
+    }
+
+    /// Removes the child at the given index along with an adjacent separator, returning both.
+    /// The separator to the child's left goes with it, except for the first child, which takes
+    /// the separator to its right.
+    pub fn remove_at(&mut self, index: usize) -> (Separator, PageId) {
+        let separator = self.keys.remove(index.saturating_sub(1));
+        let child = self.children.remove(index);
+        self.header.is_dirty = true;
+        (separator, child)
+    }
+
+    /// Moves the children from index `at` onwards to the empty page `other`, and returns the
+    /// separator between the two halves, which now belongs in the parent.
+    pub fn split_into(&mut self, at: usize, other: &mut Self) -> Separator {
+        other.children = self.children.split_off(at);
+        other.keys = self.keys.split_off(at);
+        self.header.is_dirty = true;
+        other.header.is_dirty = true;
+        self.keys.pop().expect("cannot split off the first child")
+    }
+
+    /// Moves every child of `other`, this page's right sibling, onto the end of this page.
+    /// `separator` is the parent's separator between the two pages. Fails if the children don't
+    /// fit.
+    pub fn merge_from(&mut self, separator: Separator, other: &mut Self) -> Result<()> {
+        if self.size() + other.size() > self.max_size() {
+            return errinput!(
+                "internal pages {} and {} don't fit on one page",
+                self.header.page_id,
+                other.header.page_id
+            );
+        }
+        self.keys.push(separator);
+        self.keys.append(&mut other.keys);
+        self.children.append(&mut other.children);
+        self.header.is_dirty = true;
+        other.header.is_dirty = true;
+        Ok(())
+    }
+
+    /// Returns the size of an entry on the page, in bytes.
+    fn entry_size(key_schema: &KeySchema) -> usize {
+        key_schema.slot_size() + RID_SIZE + mem::size_of::<PageId>()
+    }
+}
+
+impl Page for BPlusTreeInternalPage {
+    type InsertOutputType = u16;
+    type ConcretePageType = Self;
+
+    fn get_tuple(&self, _rid: &RecordId) -> Result<Tuple> {
+        no_tuples()
+    }
+
+    fn insert_tuple(
+        &mut self,
+        _meta: TupleMetadata,
+        _tuple: Tuple,
+    ) -> Option<Self::InsertOutputType> {
+        None
+    }
+
+    fn get_tuple_metadata(&self, _rid: &RecordId) -> Result<TupleMetadata> {
+        no_tuples()
+    }
+
+    fn update_tuple_metadata(&mut self, _metadata: &TupleMetadata, _rid: &RecordId) -> Result<()> {
+        no_tuples()
+    }
+
+    fn get_is_dirty(&self) -> bool {
+        self.header.is_dirty
+    }
+
+    fn set_is_dirty(&mut self, is_dirty: bool) -> bool {
+        let changed = self.header.is_dirty != is_dirty;
+        self.header.is_dirty = is_dirty;
+        changed
+    }
+
+    fn lsn(&self) -> Lsn {
+        self.header.lsn
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.header.lsn = lsn;
+    }
+
+    fn page_id(&self) -> &PageId {
+        &self.header.page_id
+    }
+
+    /// Returns the number of children of the page.
+    fn tuple_count(&self) -> u16 {
+        self.children.len() as u16
+    }
+
+    fn deleted_tuple_count(&self) -> u16 {
+        0
+    }
+
+    /// Layout: | header | (key slot, rid, child page id) ... |
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        self.header
+            .serialize(self.children.len() as u16, &mut result);
+
+        let key_schema = self.header.key_schema;
+        let mut cursor = HEADER_SIZE;
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                let (key, rid) = &self.keys[i - 1];
+                key_schema.encode(key, &mut result[cursor..]);
+                encode_rid(rid, &mut result[(cursor + key_schema.slot_size())..]);
+            }
+            cursor += key_schema.slot_size() + RID_SIZE;
+            result[cursor..(cursor + 4)].copy_from_slice(&child.to_le_bytes());
+            cursor += 4;
+        }
+        result
+    }
+
+    fn deserialize(buffer: &[u8]) -> Self::ConcretePageType {
+        let (header, size) = BPlusTreeHeader::deserialize(buffer);
+        let key_schema = header.key_schema;
+        let mut keys = Vec::with_capacity(size as usize);
+        let mut children = Vec::with_capacity(size as usize);
+        for i in 0..size as usize {
+            let cursor = HEADER_SIZE + i * Self::entry_size(&key_schema);
+            if i > 0 {
+                let key = key_schema.decode(&buffer[cursor..]);
+                let rid = decode_rid(&buffer[(cursor + key_schema.slot_size())..]);
+                keys.push((key, rid));
+            }
+            let cursor = cursor + key_schema.slot_size() + RID_SIZE;
+            children.push(u32::from_le_bytes(
+                buffer[cursor..(cursor + 4)].try_into().unwrap(),
+            ));
+        }
+
+        BPlusTreeInternalPage {
+            header,
+            keys,
+            children,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BPlusTreeInternalPageBuilder {
+    page_id: Option<PageId>,
+    key_schema: Option<KeySchema>,
+    max_size: Option<u16>,
+}
+
+impl BPlusTreeInternalPageBuilder {
+    pub fn page_id(&mut self, page_id: PageId) -> &mut Self {
+        self.page_id = Some(page_id);
+        self
+    }
+    pub fn key_schema(&mut self, key_schema: KeySchema) -> &mut Self {
+        self.key_schema = Some(key_schema);
+        self
+    }
+    /// Caps the number of children of the page below what fits, e.g. to exercise splits in tests.
+    pub fn max_size(&mut self, max_size: u16) -> &mut Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    pub fn build(&self) -> BPlusTreeInternalPage {
+        let key_schema = self
+            .key_schema
+            .expect("Cannot build BPlusTreeInternalPage without a `key_schema`.");
+        BPlusTreeInternalPage {
+            header: BPlusTreeHeader::new(
+                BPlusTreePageType::Internal,
+                key_schema,
+                self.page_id.unwrap_or(INVALID_PID),
+                HEADER_SIZE,
+                BPlusTreeInternalPage::entry_size(&key_schema),
+                self.max_size,
+            ),
+            keys: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
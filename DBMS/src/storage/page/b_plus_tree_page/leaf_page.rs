@@ -0,0 +1,342 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
+use crate::errinput;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::b_plus_tree_page::b_plus_tree_page::{
+    decode_rid, encode_rid, no_tuples, BPlusTreeHeader, BPlusTreePageType, KeySchema, HEADER_SIZE,
+    RID_SIZE,
+};
+use crate::storage::page::{Page, RecordId};
+use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
+use crate::types::field::Field;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+pub type BPlusTreeLeafPageHandle = Arc<RwLock<BPlusTreeLeafPage>>;
+
+/// Size of a leaf page's header: the shared header, then the sibling pointers.
+const LEAF_HEADER_SIZE: usize = HEADER_SIZE + 2 * mem::size_of::<PageId>();
+
+/// A leaf node of a B+tree, mapping keys to the record ids of the rows holding them.
+///
+/// Entries are kept sorted by key, then by record id, so duplicate keys are allowed as long as
+/// they point at different rows. Leaves are linked to their siblings in key order for range scans.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BPlusTreeLeafPage {
+    pub(crate) header: BPlusTreeHeader,
+    pub(crate) prev_page_id: PageId,
+    pub(crate) next_page_id: PageId,
+    pub(crate) entries: Vec<(Field, RecordId)>,
+}
+
+impl BPlusTreeLeafPage {
+    pub fn builder() -> BPlusTreeLeafPageBuilder {
+        BPlusTreeLeafPageBuilder::default()
+    }
+
+    pub fn key_schema(&self) -> KeySchema {
+        self.header.key_schema
+    }
+
+    pub fn parent_page_id(&self) -> PageId {
+        self.header.parent_page_id
+    }
+
+    pub fn set_parent_page_id(&mut self, page_id: PageId) {
+        self.header.parent_page_id = page_id;
+        self.header.is_dirty = true;
+    }
+
+    /// Returns the page id of the leaf holding the next smaller keys, if any.
+    pub fn prev_page_id(&self) -> PageId {
+        self.prev_page_id
+    }
+
+    pub fn set_prev_page_id(&mut self, page_id: PageId) {
+        self.prev_page_id = page_id;
+        self.header.is_dirty = true;
+    }
+
+    /// Returns the page id of the leaf holding the next larger keys, if any.
+    pub fn next_page_id(&self) -> PageId {
+        self.next_page_id
+    }
+
+    pub fn set_next_page_id(&mut self, page_id: PageId) {
+        self.next_page_id = page_id;
+        self.header.is_dirty = true;
+    }
+
+    /// Returns the number of entries on the page.
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of entries the page can hold.
+    pub fn max_size(&self) -> usize {
+        self.header.max_size as usize
+    }
+
+    /// Returns the number of entries below which a non-root leaf is underfull.
+    pub fn min_size(&self) -> usize {
+        self.max_size() / 2
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.size() >= self.max_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(Field, RecordId)] {
+        &self.entries
+    }
+
+    pub fn key_at(&self, index: usize) -> &Field {
+        &self.entries[index].0
+    }
+
+    pub fn rid_at(&self, index: usize) -> &RecordId {
+        &self.entries[index].1
+    }
+
+    /// Returns the index of the first entry whose key is not less than `key`, or the page's size
+    /// if there is none.
+    pub fn lower_bound(&self, key: &Field) -> usize {
+        self.entries.partition_point(|(k, _)| k < key)
+    }
+
+    /// Binary searches the page for the entry, returning its index if found, or otherwise the
+    /// index where it would be inserted.
+    pub fn position(&self, key: &Field, rid: &RecordId) -> std::result::Result<usize, usize> {
+        self.entries
+            .binary_search_by(|(k, r)| k.cmp(key).then_with(|| r.cmp(rid)))
+    }
+
+    /// Returns the record ids of every entry with the given key.
+    pub fn get(&self, key: &Field) -> Vec<RecordId> {
+        self.entries[self.lower_bound(key)..]
+            .iter()
+            .take_while(|(k, _)| k == key)
+            .map(|(_, rid)| rid.clone())
+            .collect()
+    }
+
+    /// Inserts an entry in order, returning its index. Fails if the page is full, the key doesn't
+    /// match the page's key schema, or the entry is already on the page.
+    pub fn insert(&mut self, key: Field, rid: RecordId) -> Result<usize> {
+        self.header.key_schema.check(&key)?;
+        if self.is_full() {
+            return errinput!("leaf page {} is full", self.header.page_id);
+        }
+        match self.position(&key, &rid) {
+            Ok(_) => errinput!("entry ({key}, {}) already exists", rid.to_string()),
+            Err(index) => {
+                self.entries.insert(index, (key, rid));
+                self.header.is_dirty = true;
+                Ok(index)
+            }
+        }
+    }
+
+    /// Removes an entry. Returns whether it was on the page.
+    pub fn remove(&mut self, key: &Field, rid: &RecordId) -> bool {
+        match self.position(key, rid) {
+            Ok(index) => {
+                self.remove_at(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Removes and returns the entry at the given index.
+    pub fn remove_at(&mut self, index: usize) -> (Field, RecordId) {
+        self.header.is_dirty = true;
+        self.entries.remove(index)
+    }
+
+    /// Moves the entries from index `at` onwards to the empty page `other`, and links `other` in
+    /// as this page's next sibling. The caller still has to point the old next sibling back at
+    /// `other`.
+    pub fn split_into(&mut self, at: usize, other: &mut Self) {
+        other.entries = self.entries.split_off(at);
+        other.prev_page_id = self.header.page_id;
+        other.next_page_id = self.next_page_id;
+        self.next_page_id = other.header.page_id;
+        self.header.is_dirty = true;
+        other.header.is_dirty = true;
+    }
+
+    /// Moves every entry of `other`, this page's next sibling, onto the end of this page, and
+    /// unlinks `other` from the sibling chain. Fails if the entries don't fit. The caller still
+    /// has to point the new next sibling back at this page.
+    pub fn merge_from(&mut self, other: &mut Self) -> Result<()> {
+        if self.size() + other.size() > self.max_size() {
+            return errinput!(
+                "leaf pages {} and {} don't fit on one page",
+                self.header.page_id,
+                other.header.page_id
+            );
+        }
+        self.entries.append(&mut other.entries);
+        self.next_page_id = other.next_page_id;
+        self.header.is_dirty = true;
+        other.header.is_dirty = true;
+        Ok(())
+    }
+
+    /// Returns the size of an entry on the page, in bytes.
+    fn entry_size(key_schema: &KeySchema) -> usize {
+        key_schema.slot_size() + RID_SIZE
+    }
+}
+
+impl Page for BPlusTreeLeafPage {
+    type InsertOutputType = u16;
+    type ConcretePageType = Self;
+
+    fn get_tuple(&self, _rid: &RecordId) -> Result<Tuple> {
+        no_tuples()
+    }
+
+    fn insert_tuple(
+        &mut self,
+        _meta: TupleMetadata,
+        _tuple: Tuple,
+    ) -> Option<Self::InsertOutputType> {
+        None
+    }
+
+    fn get_tuple_metadata(&self, _rid: &RecordId) -> Result<TupleMetadata> {
+        no_tuples()
+    }
+
+    fn update_tuple_metadata(&mut self, _metadata: &TupleMetadata, _rid: &RecordId) -> Result<()> {
+        no_tuples()
+    }
+
+    fn get_is_dirty(&self) -> bool {
+        self.header.is_dirty
+    }
+
+    fn set_is_dirty(&mut self, is_dirty: bool) -> bool {
+        let changed = self.header.is_dirty != is_dirty;
+        self.header.is_dirty = is_dirty;
+        changed
+    }
+
+    fn lsn(&self) -> Lsn {
+        self.header.lsn
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.header.lsn = lsn;
+    }
+
+    fn page_id(&self) -> &PageId {
+        &self.header.page_id
+    }
+
+    /// Returns the number of entries on the page.
+    fn tuple_count(&self) -> u16 {
+        self.entries.len() as u16
+    }
+
+    fn deleted_tuple_count(&self) -> u16 {
+        0
+    }
+
+    /// Layout: | header | prev_page_id (4) | next_page_id (4) | (key slot, rid) ... |
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        self.header
+            .serialize(self.entries.len() as u16, &mut result);
+
+        let mut cursor = HEADER_SIZE;
+        result[cursor..(cursor + 4)].copy_from_slice(&self.prev_page_id.to_le_bytes());
+        cursor += 4;
+        result[cursor..(cursor + 4)].copy_from_slice(&self.next_page_id.to_le_bytes());
+        cursor += 4;
+
+        let key_schema = self.header.key_schema;
+        for (key, rid) in &self.entries {
+            key_schema.encode(key, &mut result[cursor..]);
+            cursor += key_schema.slot_size();
+            encode_rid(rid, &mut result[cursor..]);
+            cursor += RID_SIZE;
+        }
+        result
+    }
+
+    fn deserialize(buffer: &[u8]) -> Self::ConcretePageType {
+        let (header, size) = BPlusTreeHeader::deserialize(buffer);
+        let u32_at = |at: usize| u32::from_le_bytes(buffer[at..(at + 4)].try_into().unwrap());
+        let prev_page_id = u32_at(HEADER_SIZE);
+        let next_page_id = u32_at(HEADER_SIZE + 4);
+
+        let key_schema = header.key_schema;
+        let entries = (0..size as usize)
+            .map(|i| {
+                let cursor = LEAF_HEADER_SIZE + i * Self::entry_size(&key_schema);
+                let key = key_schema.decode(&buffer[cursor..]);
+                (
+                    key,
+                    decode_rid(&buffer[(cursor + key_schema.slot_size())..]),
+                )
+            })
+            .collect();
+
+        BPlusTreeLeafPage {
+            header,
+            prev_page_id,
+            next_page_id,
+            entries,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BPlusTreeLeafPageBuilder {
+    page_id: Option<PageId>,
+    key_schema: Option<KeySchema>,
+    max_size: Option<u16>,
+}
+
+impl BPlusTreeLeafPageBuilder {
+    pub fn page_id(&mut self, page_id: PageId) -> &mut Self {
+        self.page_id = Some(page_id);
+        self
+    }
+    pub fn key_schema(&mut self, key_schema: KeySchema) -> &mut Self {
+        self.key_schema = Some(key_schema);
+        self
+    }
+    /// Caps the number of entries on the page below what fits, e.g. to exercise splits in tests.
+    pub fn max_size(&mut self, max_size: u16) -> &mut Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    pub fn build(&self) -> BPlusTreeLeafPage {
+        let key_schema = self
+            .key_schema
+            .expect("Cannot build BPlusTreeLeafPage without a `key_schema`.");
+        BPlusTreeLeafPage {
+            header: BPlusTreeHeader::new(
+                BPlusTreePageType::Leaf,
+                key_schema,
+                self.page_id.unwrap_or(INVALID_PID),
+                LEAF_HEADER_SIZE,
+                BPlusTreeLeafPage::entry_size(&key_schema),
+                self.max_size,
+            ),
+            prev_page_id: INVALID_PID,
+            next_page_id: INVALID_PID,
+            entries: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+mod b_plus_tree_page;
+mod internal_page;
+mod leaf_page;
+#[cfg(test)]
+mod tests;
+
+pub use b_plus_tree_page::{BPlusTreePageType, KeySchema};
+pub use internal_page::{
+    BPlusTreeInternalPage, BPlusTreeInternalPageBuilder, BPlusTreeInternalPageHandle, Separator,
+};
+pub use leaf_page::{BPlusTreeLeafPage, BPlusTreeLeafPageBuilder, BPlusTreeLeafPageHandle};
@@ -0,0 +1,308 @@
+use super::*;
+use crate::common::constants::INVALID_PID;
+use crate::common::Error;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
+use crate::storage::page::{Page, RecordId};
+use crate::types::field::Field;
+use crate::types::DataType;
+
+#[test]
+fn test_leaf_insert_keeps_entries_sorted() {
+    let mut page = int_leaf(None);
+    for key in [5, 1, 9, 3, 7] {
+        page.insert(Field::Integer(key), RecordId::new(1, key as u16))
+            .unwrap();
+    }
+    // Duplicate keys are ordered by record id.
+    page.insert(Field::Integer(5), RecordId::new(0, 0)).unwrap();
+
+    let keys: Vec<_> = page.entries().iter().map(|(key, _)| key.clone()).collect();
+    assert_eq!([1, 3, 5, 5, 7, 9].map(Field::Integer).to_vec(), keys);
+    assert_eq!(
+        vec![RecordId::new(0, 0), RecordId::new(1, 5)],
+        page.get(&Field::Integer(5))
+    );
+    assert!(page.get(&Field::Integer(4)).is_empty());
+    assert!(matches!(
+        page.insert(Field::Integer(5), RecordId::new(0, 0)),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn test_leaf_binary_search() {
+    let mut page = int_leaf(None);
+    for key in (0..100).step_by(10) {
+        page.insert(Field::Integer(key), RecordId::new(1, key as u16))
+            .unwrap();
+    }
+
+    assert_eq!(0, page.lower_bound(&Field::Integer(-1)));
+    assert_eq!(0, page.lower_bound(&Field::Integer(0)));
+    assert_eq!(3, page.lower_bound(&Field::Integer(25)));
+    assert_eq!(3, page.lower_bound(&Field::Integer(30)));
+    assert_eq!(10, page.lower_bound(&Field::Integer(91)));
+    assert_eq!(
+        Ok(4),
+        page.position(&Field::Integer(40), &RecordId::new(1, 40))
+    );
+    assert_eq!(
+        Err(5),
+        page.position(&Field::Integer(40), &RecordId::new(2, 0))
+    );
+}
+
+#[test]
+fn test_leaf_full_and_empty() {
+    // The page fits as many entries as there is room for after the header.
+    let page = int_leaf(None);
+    let entry_size = (1 + 2 + 4) + (4 + 2);
+    assert_eq!(
+        (RUSTY_DB_PAGE_PAYLOAD_BYTES - 32) / entry_size,
+        page.max_size()
+    );
+
+    let mut page = int_leaf(Some(4));
+    assert!(page.is_empty());
+    assert_eq!(2, page.min_size());
+    for key in 0..4 {
+        assert!(!page.is_full());
+        page.insert(Field::Integer(key), RecordId::new(1, 0))
+            .unwrap();
+    }
+    assert!(page.is_full());
+    assert_eq!(4, page.size());
+    assert!(matches!(
+        page.insert(Field::Integer(4), RecordId::new(1, 0)),
+        Err(Error::InvalidInput(_))
+    ));
+
+    for key in 0..4 {
+        assert!(page.remove(&Field::Integer(key), &RecordId::new(1, 0)));
+    }
+    assert!(!page.remove(&Field::Integer(0), &RecordId::new(1, 0)));
+    assert!(page.is_empty());
+}
+
+#[test]
+fn test_leaf_rejects_keys_outside_schema() {
+    let mut page = BPlusTreeLeafPage::builder()
+        .key_schema(KeySchema::new(DataType::Text, 4))
+        .build();
+    let rid = RecordId::new(1, 0);
+    assert!(page.insert(Field::Integer(1), rid.clone()).is_err());
+    assert!(page.insert(Field::from("hello"), rid.clone()).is_err());
+    page.insert(Field::from("hell"), rid.clone()).unwrap();
+    page.insert(Field::Null, rid).unwrap();
+}
+
+#[test]
+fn test_leaf_serialization_roundtrip() {
+    let mut page = BPlusTreeLeafPage::builder()
+        .page_id(3)
+        .key_schema(KeySchema::new(DataType::Text, 8))
+        .build();
+    page.set_parent_page_id(2);
+    page.set_prev_page_id(4);
+    page.set_next_page_id(5);
+    page.set_lsn(42);
+    for (slot, key) in ["pear", "apple", "", "zucchini"].iter().enumerate() {
+        page.insert(Field::from(*key), RecordId::new(7, slot as u16))
+            .unwrap();
+    }
+    page.insert(Field::Null, RecordId::new(7, 9)).unwrap();
+
+    let bytes = page.serialize();
+    assert_eq!(RUSTY_DB_PAGE_PAYLOAD_BYTES, bytes.len());
+    assert_eq!(Some(BPlusTreePageType::Leaf), BPlusTreePageType::of(&bytes));
+    let mut deserialized = BPlusTreeLeafPage::deserialize(&bytes);
+    assert!(!deserialized.get_is_dirty());
+    deserialized.set_is_dirty(true);
+    assert_eq!(page, deserialized);
+    assert_eq!(5, deserialized.tuple_count());
+}
+
+#[test]
+fn test_leaf_split_and_merge() {
+    let mut left = int_leaf(Some(6));
+    for key in 0..6 {
+        left.insert(Field::Integer(key), RecordId::new(1, 0))
+            .unwrap();
+    }
+    left.set_next_page_id(9);
+    let mut right = BPlusTreeLeafPage::builder()
+        .page_id(5)
+        .key_schema(KeySchema::for_type(DataType::Int))
+        .max_size(6)
+        .build();
+
+    left.split_into(4, &mut right);
+    assert_eq!(4, left.size());
+    assert_eq!(&Field::Integer(4), right.key_at(0));
+    assert_eq!(
+        (5, 1, 9),
+        (
+            left.next_page_id(),
+            right.prev_page_id(),
+            right.next_page_id()
+        )
+    );
+
+    left.insert(Field::Integer(-1), RecordId::new(1, 0))
+        .unwrap();
+    assert!(left.merge_from(&mut right).is_err());
+    left.remove_at(0);
+    left.remove_at(0);
+    left.merge_from(&mut right).unwrap();
+    assert!(right.is_empty());
+    assert_eq!(9, left.next_page_id());
+    let keys: Vec<_> = left.entries().iter().map(|(key, _)| key.clone()).collect();
+    assert_eq!([1, 2, 3, 4, 5].map(Field::Integer).to_vec(), keys);
+}
+
+#[test]
+fn test_internal_lookup() {
+    let mut page = int_internal(None);
+    page.init_root(10, sep(100, 0), 20).unwrap();
+    page.insert(2, sep(200, 0), 30).unwrap();
+    page.insert(1, sep(50, 0), 15).unwrap();
+    assert_eq!(&[10, 15, 20, 30], page.children());
+    assert_eq!(&sep(50, 0), page.key_at(1));
+
+    for (key, child) in [(0, 10), (49, 10), (50, 15), (99, 15), (100, 20), (250, 30)] {
+        assert_eq!(
+            child,
+            page.lookup(&Field::Integer(key), &RecordId::new(1, 0)),
+            "key {key}"
+        );
+    }
+    assert_eq!(Some(2), page.child_index_of(20));
+    assert_eq!(None, page.child_index_of(99));
+    assert!(page.init_root(1, sep(1, 0), 2).is_err());
+}
+
+#[test]
+fn test_internal_lookup_orders_duplicates_by_rid() {
+    // Duplicates of a key can span several children, split by record id.
+    let mut page = int_internal(None);
+    page.init_root(10, sep(5, 3), 20).unwrap();
+    page.insert(2, sep(5, 7), 30).unwrap();
+
+    let lookup = |key, slot| page.lookup(&Field::Integer(key), &RecordId::new(1, slot));
+    assert_eq!(10, lookup(5, 2));
+    assert_eq!(20, lookup(5, 3));
+    assert_eq!(20, lookup(5, 6));
+    assert_eq!(30, lookup(5, 7));
+    assert_eq!(10, lookup(4, 9));
+    assert_eq!(30, lookup(6, 0));
+}
+
+#[test]
+fn test_internal_full_and_remove() {
+    let mut page = int_internal(Some(3));
+    assert!(page.is_empty());
+    assert_eq!(2, page.min_size());
+    page.init_root(10, sep(100, 0), 20).unwrap();
+    page.insert(2, sep(200, 0), 30).unwrap();
+    assert!(page.is_full());
+    assert!(matches!(
+        page.insert(3, sep(300, 0), 40),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(page.push_front(5, sep(50, 0)).is_err());
+
+    // The first child takes the separator to its right with it.
+    assert_eq!((sep(100, 0), 10), page.remove_at(0));
+    assert_eq!(&[20, 30], page.children());
+    assert_eq!(&[sep(200, 0)], page.keys());
+    page.push_front(10, sep(100, 0)).unwrap();
+    assert_eq!(&[10, 20, 30], page.children());
+    assert_eq!((sep(200, 0), 30), page.remove_at(2));
+    assert_eq!(2, page.size());
+    assert_eq!(&[sep(100, 0)], page.keys());
+}
+
+#[test]
+fn test_internal_split_and_merge() {
+    let mut left = int_internal(Some(5));
+    left.init_root(10, sep(100, 0), 20).unwrap();
+    for (i, key) in [200, 300, 400].into_iter().enumerate() {
+        left.insert(i + 2, sep(key, 0), 30 + 10 * i as u32).unwrap();
+    }
+    let mut right = int_internal(Some(5));
+
+    // The separator between the halves moves up to the parent.
+    assert_eq!(sep(200, 0), left.split_into(2, &mut right));
+    assert_eq!(
+        (&[10, 20][..], &[sep(100, 0)][..]),
+        (left.children(), left.keys())
+    );
+    assert_eq!(
+        (&[30, 40, 50][..], &[sep(300, 0), sep(400, 0)][..]),
+        (right.children(), right.keys())
+    );
+
+    left.merge_from(sep(200, 0), &mut right).unwrap();
+    assert_eq!(&[10, 20, 30, 40, 50], left.children());
+    assert_eq!(4, left.keys().len());
+    assert!(right.is_empty());
+    assert!(left
+        .merge_from(sep(500, 0), &mut int_internal(Some(5)))
+        .is_ok());
+    let mut full = int_internal(Some(5));
+    full.init_root(60, sep(600, 0), 70).unwrap();
+    assert!(left.merge_from(sep(550, 0), &mut full).is_err());
+}
+
+#[test]
+fn test_internal_serialization_roundtrip() {
+    let mut page = BPlusTreeInternalPage::builder()
+        .page_id(6)
+        .key_schema(KeySchema::for_type(DataType::Int))
+        .max_size(50)
+        .build();
+    page.set_parent_page_id(1);
+    page.set_lsn(7);
+    page.init_root(10, sep(100, 4), 20).unwrap();
+    for (i, key) in (200..1000).step_by(100).enumerate() {
+        page.insert(i + 2, sep(key, i as u16), 30 + i as u32)
+            .unwrap();
+    }
+
+    let bytes = page.serialize();
+    assert_eq!(
+        Some(BPlusTreePageType::Internal),
+        BPlusTreePageType::of(&bytes)
+    );
+    let mut deserialized = BPlusTreeInternalPage::deserialize(&bytes);
+    deserialized.set_is_dirty(true);
+    assert_eq!(page, deserialized);
+    assert_eq!(50, deserialized.max_size());
+    assert_eq!(INVALID_PID, int_internal(None).parent_page_id());
+}
+
+fn int_leaf(max_size: Option<u16>) -> BPlusTreeLeafPage {
+    let mut builder = BPlusTreeLeafPage::builder();
+    builder
+        .page_id(1)
+        .key_schema(KeySchema::for_type(DataType::Int));
+    if let Some(max_size) = max_size {
+        builder.max_size(max_size);
+    }
+    builder.build()
+}
+
+fn int_internal(max_size: Option<u16>) -> BPlusTreeInternalPage {
+    let mut builder = BPlusTreeInternalPage::builder();
+    builder
+        .page_id(2)
+        .key_schema(KeySchema::for_type(DataType::Int));
+    if let Some(max_size) = max_size {
+        builder.max_size(max_size);
+    }
+    builder.build()
+}
+
+fn sep(key: i32, slot_id: u16) -> Separator {
+    (Field::Integer(key), RecordId::new(1, slot_id))
+}
@@ -1,7 +1,19 @@
+mod b_plus_tree_page;
+mod overflow_page;
 mod page;
+mod page_handle;
 mod record_id;
 mod table_page;
 
+pub use b_plus_tree_page::{
+    BPlusTreeInternalPage, BPlusTreeInternalPageBuilder, BPlusTreeInternalPageHandle,
+    BPlusTreeLeafPage, BPlusTreeLeafPageBuilder, BPlusTreeLeafPageHandle, BPlusTreePageType,
+    KeySchema, Separator,
+};
+pub use overflow_page::{OverflowPage, OverflowPageHandle, OverflowStub};
 pub use page::Page;
+pub use page_handle::PageHandle;
 pub use record_id::{RecordId, INVALID_RID};
-pub use table_page::{TablePage, TablePageBuilder, TablePageHandle, TablePageIterator};
+pub use table_page::{
+    TablePage, TablePageBuilder, TablePageHandle, TablePageIterator, TablePageRevIterator,
+};
@@ -0,0 +1,5 @@
+mod overflow_page;
+#[cfg(test)]
+mod tests;
+
+pub use overflow_page::{OverflowPage, OverflowPageHandle, OverflowStub};
@@ -0,0 +1,183 @@
+use crate::common::constants::INVALID_PID;
+use crate::common::Result;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
+use crate::errinput;
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::{Page, RecordId};
+use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::{Lsn, INVALID_LSN};
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+pub type OverflowPageHandle = Arc<RwLock<OverflowPage>>;
+
+/// A page of an overflow chain, which holds the payload of a tuple too large for a table page.
+/// The payload is split into pieces of up to [`OverflowPage::CAPACITY`] bytes, one per page,
+/// and the pages are linked in order by their next page ids; the table page keeps an
+/// [`OverflowStub`] in the tuple's slot instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverflowPage {
+    pub(crate) page_id: PageId,
+    pub(crate) next_page_id: PageId,
+    /// The piece of the payload this page holds.
+    pub(crate) data: Vec<u8>,
+    pub(crate) lsn: Lsn,
+    pub(crate) is_dirty: bool,
+}
+
+impl OverflowPage {
+    /// The size of the header: the page's ID and the next page's (4 bytes each), its LSN, and the
+    /// size of the piece it holds (2 bytes).
+    const HEADER_BYTES: usize = 4 + 4 + mem::size_of::<Lsn>() + 2;
+    /// The most bytes of a payload a page holds.
+    pub const CAPACITY: usize = RUSTY_DB_PAGE_PAYLOAD_BYTES - Self::HEADER_BYTES;
+
+    /// Creates an empty page, the last of its chain.
+    pub fn new(page_id: PageId) -> Self {
+        Self {
+            page_id,
+            next_page_id: INVALID_PID,
+            data: Vec::new(),
+            lsn: INVALID_LSN,
+            is_dirty: false,
+        }
+    }
+
+    /// Returns the next page of the chain, or [`INVALID_PID`] if this is the last.
+    pub fn next_page_id(&self) -> PageId {
+        self.next_page_id
+    }
+
+    pub fn set_next_page_id(&mut self, page_id: PageId) {
+        self.next_page_id = page_id;
+        self.is_dirty = true;
+    }
+
+    /// Returns the piece of the payload the page holds.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Replaces the piece of the payload the page holds, which must fit in
+    /// [`Self::CAPACITY`] bytes.
+    pub fn set_data(&mut self, data: &[u8]) {
+        assert!(data.len() <= Self::CAPACITY, "overflow page piece too large");
+        self.data = data.to_vec();
+        self.is_dirty = true;
+    }
+}
+
+impl Page for OverflowPage {
+    type InsertOutputType = u16;
+    type ConcretePageType = Self;
+
+    fn get_tuple(&self, _rid: &RecordId) -> Result<Tuple> {
+        no_tuples()
+    }
+
+    fn insert_tuple(
+        &mut self,
+        _meta: TupleMetadata,
+        _tuple: Tuple,
+    ) -> Option<Self::InsertOutputType> {
+        None
+    }
+
+    fn get_tuple_metadata(&self, _rid: &RecordId) -> Result<TupleMetadata> {
+        no_tuples()
+    }
+
+    fn update_tuple_metadata(&mut self, _metadata: &TupleMetadata, _rid: &RecordId) -> Result<()> {
+        no_tuples()
+    }
+
+    fn get_is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    fn set_is_dirty(&mut self, is_dirty: bool) -> bool {
+        mem::replace(&mut self.is_dirty, is_dirty) != is_dirty
+    }
+
+    fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.lsn = lsn;
+    }
+
+    fn page_id(&self) -> &PageId {
+        &self.page_id
+    }
+
+    fn tuple_count(&self) -> u16 {
+        0
+    }
+
+    fn deleted_tuple_count(&self) -> u16 {
+        0
+    }
+
+    /// Layout: | page_id (4) | next_page_id (4) | lsn (8) | size (2) | piece of the payload |
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        result[0..4].copy_from_slice(&self.page_id.to_le_bytes());
+        result[4..8].copy_from_slice(&self.next_page_id.to_le_bytes());
+        result[8..16].copy_from_slice(&self.lsn.to_le_bytes());
+        result[16..18].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
+        result[Self::HEADER_BYTES..(Self::HEADER_BYTES + self.data.len())]
+            .copy_from_slice(&self.data);
+        result
+    }
+
+    fn deserialize(buffer: &[u8]) -> Self::ConcretePageType {
+        let size = u16::from_le_bytes(buffer[16..18].try_into().unwrap()) as usize;
+        let size = size.min(Self::CAPACITY);
+        Self {
+            page_id: PageId::from_le_bytes(buffer[0..4].try_into().unwrap()),
+            next_page_id: PageId::from_le_bytes(buffer[4..8].try_into().unwrap()),
+            data: buffer[Self::HEADER_BYTES..(Self::HEADER_BYTES + size)].to_vec(),
+            lsn: Lsn::from_le_bytes(buffer[8..16].try_into().unwrap()),
+            is_dirty: false,
+        }
+    }
+}
+
+/// What a table page keeps in the slot of a tuple stored on overflow pages: the first page of
+/// the chain holding its payload, and the payload's size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OverflowStub {
+    pub first_page_id: PageId,
+    pub len: u32,
+}
+
+impl OverflowStub {
+    /// The size of a stub in a table page: the first page's ID and the payload's size, 4 bytes
+    /// each, little-endian.
+    pub const BYTES: usize = 4 + 4;
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.first_page_id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a stub written by [`Self::to_bytes`], or returns `None` if `bytes` isn't the size
+    /// of one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::BYTES {
+            return None;
+        }
+        Some(Self {
+            first_page_id: PageId::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            len: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        })
+    }
+}
+
+/// The error returned by the tuple accessors of the [`Page`] trait, which overflow pages don't
+/// support: their pieces of payload aren't tuples of their own.
+fn no_tuples<T>() -> Result<T> {
+    errinput!("overflow pages store pieces of a payload, not tuples")
+}
@@ -0,0 +1,27 @@
+use super::*;
+use crate::common::constants::INVALID_PID;
+use crate::storage::page::Page;
+
+#[test]
+pub fn test_serialize_round_trip() {
+    for len in [0, 1, 100, OverflowPage::CAPACITY] {
+        let mut page = OverflowPage::new(3);
+        page.set_next_page_id(7);
+        page.set_lsn(42);
+        page.set_data(&(0..len).map(|i| i as u8).collect::<Vec<_>>());
+
+        let read = OverflowPage::deserialize(&page.serialize());
+        assert_eq!(OverflowPage { is_dirty: false, ..page }, read);
+    }
+    assert_eq!(INVALID_PID, OverflowPage::new(3).next_page_id());
+}
+
+#[test]
+pub fn test_stub_round_trip() {
+    let stub = OverflowStub {
+        first_page_id: 12,
+        len: 70_000,
+    };
+    assert_eq!(Some(stub), OverflowStub::from_bytes(&stub.to_bytes()));
+    assert_eq!(None, OverflowStub::from_bytes(&[0; 4]));
+}
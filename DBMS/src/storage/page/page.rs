@@ -2,6 +2,7 @@ use crate::common::Result;
 use crate::storage::disk::disk_manager::PageId;
 use crate::storage::page::record_id::RecordId;
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
 
 /// Stores serialized tuples (which we will refer to as "payloads" to avoid confusion) in memory.
 pub trait Page {
@@ -27,6 +28,12 @@ pub trait Page {
     /// changed.
     fn set_is_dirty(&mut self, is_dirty: bool) -> bool;
 
+    /// Returns the LSN of the newest log record whose change has been applied to the page.
+    fn lsn(&self) -> Lsn;
+
+    /// Stamps the page with the LSN of a log record describing a change applied to it.
+    fn set_lsn(&mut self, lsn: Lsn);
+
     /// Returns the unique identifier for the page. In this DBMS, it is the page's offset into the
     /// database file on disk (see [`crate::storage::disk::disk_manager`]).
     fn page_id(&self) -> &PageId;
@@ -0,0 +1,234 @@
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::{
+    BPlusTreeInternalPage, BPlusTreeInternalPageHandle, BPlusTreeLeafPage, BPlusTreeLeafPageHandle,
+    BPlusTreePageType, Page, TablePageHandle,
+};
+use crate::storage::wal::Lsn;
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+/// A handle to a page of any type held in a buffer pool frame.
+///
+/// The buffer pool only needs a page's id, LSN, dirty flag and serialized bytes to pin, evict and
+/// flush it, which every variant provides through the [`Page`] trait. Callers get at the concrete
+/// page through [`Self::as_table`], [`Self::as_leaf`] and [`Self::as_internal`], or
+/// [`Self::as_page`] for a page of any type.
+#[derive(Clone, Debug)]
+pub enum PageHandle {
+    Table(TablePageHandle),
+    BPlusTreeLeaf(BPlusTreeLeafPageHandle),
+    BPlusTreeInternal(BPlusTreeInternalPageHandle),
+    /// A page of a type with no variant of its own, e.g. a catalog's meta page.
+    Other(Arc<dyn AnyPage>),
+}
+
+/// A latched page of some [`Page`] type, with its type erased, for [`PageHandle::Other`].
+pub trait AnyPage: Debug + Send + Sync {
+    fn page_id(&self) -> PageId;
+    fn lsn(&self) -> Lsn;
+    fn get_is_dirty(&self) -> bool;
+    fn set_is_dirty(&self, is_dirty: bool) -> bool;
+    /// See [`PageHandle::write_back`].
+    fn write_back(&self, write: &mut dyn FnMut(Lsn, Vec<u8>) -> bool) -> bool;
+    /// Replaces the page's contents with the serialized page in `buffer`.
+    fn reload(&self, buffer: &[u8]);
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<P> AnyPage for RwLock<P>
+where
+    P: Page<ConcretePageType = P> + Debug + Send + Sync + 'static,
+{
+    fn page_id(&self) -> PageId {
+        *self.read().unwrap().page_id()
+    }
+
+    fn lsn(&self) -> Lsn {
+        self.read().unwrap().lsn()
+    }
+
+    fn get_is_dirty(&self) -> bool {
+        self.read().unwrap().get_is_dirty()
+    }
+
+    fn set_is_dirty(&self, is_dirty: bool) -> bool {
+        self.write().unwrap().set_is_dirty(is_dirty)
+    }
+
+    fn write_back(&self, write: &mut dyn FnMut(Lsn, Vec<u8>) -> bool) -> bool {
+        let mut page = self.write().unwrap();
+        let written = write(page.lsn(), page.serialize());
+        if written {
+            page.set_is_dirty(false);
+        }
+        written
+    }
+
+    fn reload(&self, buffer: &[u8]) {
+        *self.write().unwrap() = P::deserialize(buffer);
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+impl PageHandle {
+    /// Wraps a page of any type, in the variant of its type if it has one.
+    pub fn new<P>(page: P) -> Self
+    where
+        P: Page<ConcretePageType = P> + Debug + Send + Sync + 'static,
+    {
+        let page = Arc::new(RwLock::new(page));
+        let any: Arc<dyn Any + Send + Sync> = page.clone();
+        let any = match any.downcast() {
+            Ok(page) => return Self::Table(page),
+            Err(any) => any,
+        };
+        let any = match any.downcast() {
+            Ok(page) => return Self::BPlusTreeLeaf(page),
+            Err(any) => any,
+        };
+        match any.downcast() {
+            Ok(page) => Self::BPlusTreeInternal(page),
+            Err(_) => Self::Other(page),
+        }
+    }
+
+    /// Deserializes a B+tree page of either type, or returns `None` if `buffer` doesn't hold one.
+    pub fn from_index_bytes(buffer: &[u8]) -> Option<Self> {
+        Some(match BPlusTreePageType::of(buffer)? {
+            BPlusTreePageType::Leaf => Self::BPlusTreeLeaf(Arc::new(RwLock::new(
+                BPlusTreeLeafPage::deserialize(buffer),
+            ))),
+            BPlusTreePageType::Internal => Self::BPlusTreeInternal(Arc::new(RwLock::new(
+                BPlusTreeInternalPage::deserialize(buffer),
+            ))),
+        })
+    }
+
+    pub fn as_table(&self) -> Option<TablePageHandle> {
+        match self {
+            Self::Table(page) => Some(Arc::clone(page)),
+            _ => None,
+        }
+    }
+
+    pub fn as_leaf(&self) -> Option<BPlusTreeLeafPageHandle> {
+        match self {
+            Self::BPlusTreeLeaf(page) => Some(Arc::clone(page)),
+            _ => None,
+        }
+    }
+
+    pub fn as_internal(&self) -> Option<BPlusTreeInternalPageHandle> {
+        match self {
+            Self::BPlusTreeInternal(page) => Some(Arc::clone(page)),
+            _ => None,
+        }
+    }
+
+    /// Returns the page as a page of type `P`, or `None` if it is of another type.
+    pub fn as_page<P: Page + Send + Sync + 'static>(&self) -> Option<Arc<RwLock<P>>> {
+        let any: Arc<dyn Any + Send + Sync> = match self {
+            Self::Table(page) => page.clone(),
+            Self::BPlusTreeLeaf(page) => page.clone(),
+            Self::BPlusTreeInternal(page) => page.clone(),
+            Self::Other(page) => Arc::clone(page).into_any(),
+        };
+        any.downcast().ok()
+    }
+
+    pub fn page_id(&self) -> PageId {
+        match self {
+            Self::Table(page) => *page.read().unwrap().page_id(),
+            Self::BPlusTreeLeaf(page) => *page.read().unwrap().page_id(),
+            Self::BPlusTreeInternal(page) => *page.read().unwrap().page_id(),
+            Self::Other(page) => page.page_id(),
+        }
+    }
+
+    pub fn lsn(&self) -> Lsn {
+        match self {
+            Self::Table(page) => page.read().unwrap().lsn(),
+            Self::BPlusTreeLeaf(page) => page.read().unwrap().lsn(),
+            Self::BPlusTreeInternal(page) => page.read().unwrap().lsn(),
+            Self::Other(page) => page.lsn(),
+        }
+    }
+
+    pub fn get_is_dirty(&self) -> bool {
+        match self {
+            Self::Table(page) => page.read().unwrap().get_is_dirty(),
+            Self::BPlusTreeLeaf(page) => page.read().unwrap().get_is_dirty(),
+            Self::BPlusTreeInternal(page) => page.read().unwrap().get_is_dirty(),
+            Self::Other(page) => page.get_is_dirty(),
+        }
+    }
+
+    pub fn set_is_dirty(&self, is_dirty: bool) -> bool {
+        match self {
+            Self::Table(page) => page.write().unwrap().set_is_dirty(is_dirty),
+            Self::BPlusTreeLeaf(page) => page.write().unwrap().set_is_dirty(is_dirty),
+            Self::BPlusTreeInternal(page) => page.write().unwrap().set_is_dirty(is_dirty),
+            Self::Other(page) => page.set_is_dirty(is_dirty),
+        }
+    }
+
+    /// Serializes the page if `write` allows its LSN, then marks it clean. Holds the page's latch
+    /// throughout, so no change can slip in between the write and the dirty flag being reset.
+    /// Returns whether the page was written.
+    pub(crate) fn write_back(&self, write: impl FnOnce(Lsn, Vec<u8>) -> bool) -> bool {
+        fn write_back<P: Page>(page: &RwLock<P>, write: impl FnOnce(Lsn, Vec<u8>) -> bool) -> bool {
+            let mut page = page.write().unwrap();
+            let written = write(page.lsn(), page.serialize());
+            if written {
+                page.set_is_dirty(false);
+            }
+            written
+        }
+        match self {
+            Self::Table(page) => write_back(page, write),
+            Self::BPlusTreeLeaf(page) => write_back(page, write),
+            Self::BPlusTreeInternal(page) => write_back(page, write),
+            Self::Other(page) => {
+                let mut write = Some(write);
+                page.write_back(&mut |lsn, payload| write.take().unwrap()(lsn, payload))
+            }
+        }
+    }
+
+    #[cfg(test)]
+    /// Replaces the page's contents with the serialized page in `buffer`, keeping its type.
+    pub(crate) fn reload(&self, buffer: &[u8]) {
+        match self {
+            Self::Table(page) => *page.write().unwrap() = super::TablePage::deserialize(buffer),
+            Self::BPlusTreeLeaf(page) => {
+                *page.write().unwrap() = BPlusTreeLeafPage::deserialize(buffer)
+            }
+            Self::BPlusTreeInternal(page) => {
+                *page.write().unwrap() = BPlusTreeInternalPage::deserialize(buffer)
+            }
+            Self::Other(page) => page.reload(buffer),
+        }
+    }
+}
+
+impl From<TablePageHandle> for PageHandle {
+    fn from(page: TablePageHandle) -> Self {
+        Self::Table(page)
+    }
+}
+
+impl From<BPlusTreeLeafPageHandle> for PageHandle {
+    fn from(page: BPlusTreeLeafPageHandle) -> Self {
+        Self::BPlusTreeLeaf(page)
+    }
+}
+
+impl From<BPlusTreeInternalPageHandle> for PageHandle {
+    fn from(page: BPlusTreeInternalPageHandle) -> Self {
+        Self::BPlusTreeInternal(page)
+    }
+}
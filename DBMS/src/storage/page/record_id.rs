@@ -17,7 +17,7 @@ pub const INVALID_RID: RecordId = RecordId {
 };
 
 impl RecordId {
-    pub fn new(page_id: PageId, sid: u16) -> RecordId {
+    pub const fn new(page_id: PageId, sid: u16) -> RecordId {
         RecordId {
             page_id,
             slot_id: sid,
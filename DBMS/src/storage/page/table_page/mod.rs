@@ -2,4 +2,6 @@ mod table_page;
 #[cfg(test)]
 mod tests;
 
-pub use table_page::{TablePage, TablePageBuilder, TablePageHandle, TablePageIterator};
+pub use table_page::{
+    TablePage, TablePageBuilder, TablePageHandle, TablePageIterator, TablePageRevIterator,
+};
@@ -1,13 +1,15 @@
 use crate::common::constants::INVALID_PID;
 use crate::common::{Error, Result};
-use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
+use crate::config::config::{DEFAULT_FILL_FACTOR, RUSTY_DB_PAGE_PAYLOAD_BYTES};
 use crate::storage::disk::disk_manager::PageId;
 use crate::storage::page::record_id::RecordId;
-use crate::storage::page::Page;
+use crate::storage::page::{OverflowStub, Page};
+use crate::storage::simple::TxnId;
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::{Lsn, INVALID_LSN};
 use std::{mem, u8};
 use std::sync::atomic::{AtomicU16, Ordering};
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::{Arc, RwLock};
 
 pub type TablePageHandle = Arc<RwLock<TablePage>>;
 
@@ -16,6 +18,10 @@ pub struct TupleInfo {
     pub(crate) offset: u16,
     pub(crate) size_bytes: u16,
     pub(crate) metadata: TupleMetadata,
+    /// Whether the payload is an [`OverflowStub`] standing in for the tuple's.
+    pub(crate) overflow: bool,
+    /// Whether the slot is never to be reused, see [`TablePage::retire_tuple`].
+    pub(crate) retired: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -29,19 +35,60 @@ pub struct TablePage {
     // Number of deleted tuples.
     pub(crate) deleted_tuple_cnt: u16,
     pub(crate) tuple_info: Vec<TupleInfo>,
+    // LSN of the last logged change applied to this page.
+    pub(crate) lsn: Lsn,
+    /// The percentage of the page [`Self::fits`] lets inserts fill, see
+    /// [`TablePageBuilder::fill_factor`].
+    pub(crate) fill_factor: u8,
     pub is_dirty: bool,
 }
 
+/// A table page is laid out as `| header | slots => free space <= tuples |`, the slots growing
+/// from the front of the page and the tuples from its end, toward each other:
+/// - The header: the page's ID and the next page's (4 bytes each), the numbers of live and of
+///   deleted tuples (2 bytes each), and the page's LSN (8 bytes), little-endian, then its fill
+///   factor (1 byte).
+/// - A slot per tuple, in order of slot ID: the offset of its payload, and its size (2 bytes
+///   each, little-endian), then a byte of flags: bit 0 is set if the tuple is deleted, bit 1 if
+///   its payload is an [`OverflowStub`] pointing at the overflow pages holding the tuple's, bit
+///   2 if the slot is never to be reused, and the others are reserved and left clear. Then the
+///   IDs of the transactions that inserted and deleted the tuple (8 bytes each, little-endian),
+///   which MVCC visibility is decided by.
+/// - The tuples' payloads, each at the offset its slot gives. Deleted tuples keep theirs.
 impl TablePage {
+    /// The size of the header of a page without slots: its page ID, the next page's, the two
+    /// tuple counts, the LSN and the fill factor.
+    const EMPTY_HEADER_BYTES: usize =
+        2 * mem::size_of::<PageId>() + 2 + 2 + mem::size_of::<Lsn>() + 1;
+    /// The size of a tuple's slot in the header: its payload's offset and size, its flags, and
+    /// the IDs of the transactions that inserted and deleted it.
+    pub(crate) const SLOT_BYTES: usize = 2 + 2 + 1 + 2 * mem::size_of::<TxnId>();
+    /// The flag of a slot set if its tuple is deleted.
+    const SLOT_DELETED: u8 = 1;
+    /// The flag of a slot set if its payload is an overflow stub.
+    const SLOT_OVERFLOW: u8 = 2;
+    /// The flag of a slot set if it is never to be reused.
+    const SLOT_RETIRED: u8 = 4;
+    /// The free space of a page without any tuples, see [`Self::free_space`].
+    pub const EMPTY_FREE_SPACE: usize = RUSTY_DB_PAGE_PAYLOAD_BYTES - Self::header_size_with(0);
+
+    /// Returns the size of the header of a page with `n_slots` slots, where its free space, and
+    /// so the tuples, must begin. Every computation of where the header ends goes through this.
+    pub(crate) const fn header_size_with(n_slots: usize) -> usize {
+        Self::EMPTY_HEADER_BYTES + n_slots * Self::SLOT_BYTES
+    }
+
     // page are in a linked list, use next_page_id to iterate through pages.
-    fn new(page_id: PageId, next_page_id: PageId) -> TablePage {
+    fn new(page_id: PageId, next_page_id: PageId, fill_factor: u8) -> TablePage {
         TablePage {
             page_id,
             next_page_id,
-            data: vec![0; RUSTY_DB_PAGE_SIZE_BYTES],
+            data: vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES],
             tuple_cnt: 0,
             deleted_tuple_cnt: 0,
             tuple_info: Vec::new(),
+            lsn: INVALID_LSN,
+            fill_factor,
             is_dirty: false,
         }
     }
@@ -68,25 +115,151 @@ impl TablePage {
         self.tuple_cnt + self.deleted_tuple_cnt
     }
 
-    pub fn get_next_tuple_offset(&self, payload: &Tuple) -> Option<u16> {
-        let tuple_size_bytes = payload.data.len();
-        let tuples_end = match self.total_tuple_count() {
-            0 => RUSTY_DB_PAGE_SIZE_BYTES,
-            _ => self.tuple_info[(self.total_tuple_count() - 1) as usize].offset as usize,
-        };
-        if tuple_size_bytes > tuples_end {
-            return None;
+    /// Returns the slot id the next tuple appended will be assigned, one past the last slot.
+    pub fn next_slot_id(&self) -> u16 {
+        self.total_tuple_count()
+    }
+
+    /// Returns the slot id inserting `tuple` will assign it: that of a deleted tuple it can take
+    /// the place of, if any, see [`Self::reusable_slot`], or else the next one.
+    pub fn slot_for(&self, tuple: &Tuple) -> u16 {
+        match self.reusable_slot(tuple.data.len()) {
+            Some(slot) => slot as u16,
+            None => self.next_slot_id(),
         }
+    }
+
+    /// Returns the slot of the deleted tuple whose bytes hold `len` most tightly, the lowest of
+    /// those that do equally, for an insert to take its place rather than append a slot. The
+    /// record id of the deleted tuple then reads the new one, so it must no longer be referred
+    /// to, e.g. by an index, once the tuple is deleted. Tombstones whose payload was reclaimed
+    /// by [`Self::compact`], and those of tuples retired by [`Self::retire_tuple`], are never
+    /// reused.
+    fn reusable_slot(&self, len: usize) -> Option<usize> {
+        let reusable =
+            |info: &TupleInfo| info.metadata.is_deleted() && !info.retired && info.size_bytes > 0;
+        (self.tuple_info.iter().enumerate())
+            .filter(|(_, info)| reusable(info) && info.size_bytes as usize >= len)
+            .min_by_key(|(_, info)| info.size_bytes)
+            .map(|(slot, _)| slot)
+    }
+
+    /// Returns the offset appending `payload` would put it at, right before the tuples, or `None`
+    /// if it doesn't fit, see [`Self::fits`].
+    pub fn get_next_tuple_offset(&self, payload: &Tuple) -> Option<u16> {
         // tuples are positioned at the end of the page growing inward, with new tuples appended to
         // the front, e.g. | ... t_{n}, t_{n-1}, ... t_{0} |.
-        let tuples_start = (tuples_end - tuple_size_bytes) as u16;
-        let header_size = 8 + (self.total_tuple_count() + 1) * 4;
+        let tuples_start = self.tuples_end().checked_sub(payload.data.len())?;
+        self.fits(payload).then_some(tuples_start as u16)
+    }
+
+    /// Returns the number of bytes between the page's header and its tuples:
+    ///
+    /// `free_space = tuples_end - header_size_with(slots)`
+    ///
+    /// where `tuples_end` is the offset of the lowest payload, the end of the page if there is
+    /// none, and `slots` the number of slots, deleted tuples' included, see
+    /// [`Self::header_size_with`]. Appending a tuple takes its size and the
+    /// [`Self::SLOT_BYTES`] of its slot out of it, see [`Self::fits`].
+    pub fn free_space(&self) -> usize {
+        let header_size = Self::header_size_with(self.total_tuple_count() as usize);
+        self.tuples_end().saturating_sub(header_size)
+    }
+
+    /// Returns whether appending `tuple` to the page leaves the share of it the page's fill
+    /// factor reserves, see [`Self::fits_with`]. The pages of a table heap are built with the
+    /// default fill factor of 100, filling up, and the heap applies the table's, see
+    /// `TableHeap::has_room`.
+    pub fn fits(&self, tuple: &Tuple) -> bool {
+        self.fits_with(tuple, self.fill_factor)
+    }
+
+    /// Returns whether appending `tuple` to the page leaves the share of it `fill_factor`
+    /// reserves for tuples to grow into, see [`Self::has_room`].
+    pub fn fits_with(&self, tuple: &Tuple, fill_factor: u8) -> bool {
+        Self::has_room(self.free_space(), tuple.data.len(), fill_factor)
+    }
+
+    /// Returns whether a page with `free_space` bytes of free space has room for a tuple of
+    /// `size` bytes and its slot with `RUSTY_DB_PAGE_PAYLOAD_BYTES * (100 - fill_factor) / 100`
+    /// bytes to spare, the share of the page `fill_factor` reserves for tuples to grow into. An
+    /// empty page takes any tuple its free space holds, so that no tuple is too large for every
+    /// page. Tuples grown by [`Self::update_tuple`] may use up the reserve. A fill factor above
+    /// 100 is taken as 100.
+    pub(crate) fn has_room(free_space: usize, size: usize, fill_factor: u8) -> bool {
+        // Its slot grows the header from `header_size_with(n)` to `header_size_with(n + 1)`.
+        let needed = size + Self::SLOT_BYTES;
+        let fill_factor = fill_factor.min(100) as usize;
+        let reserved = RUSTY_DB_PAGE_PAYLOAD_BYTES * (100 - fill_factor) / 100;
+        let empty = free_space == Self::EMPTY_FREE_SPACE;
+        needed <= free_space && (empty || free_space - needed >= reserved)
+    }
 
-        // Recall that the header and tuples are positioned on opposite sides of the page, growing
-        // inward toward each other, i.e. | header => free space <= tuples |.
-        Some(tuples_start).filter(|_| header_size < tuples_start)
+    /// Returns the offset of the first byte of the tuples, where the free space ends. That is the
+    /// payload of the last slot, unless [`Self::update_tuple`] moved another one below it.
+    fn tuples_end(&self) -> usize {
+        (self.tuple_info.iter())
+            .map(|info| info.offset as usize)
+            .min()
+            .unwrap_or(RUSTY_DB_PAGE_PAYLOAD_BYTES)
     }
 
+    /// Returns the free space the page has once compacted, see [`Self::compact`], which an
+    /// insert compacts the page for if it must.
+    pub fn free_space_compacted(&self) -> usize {
+        self.free_space() + self.reclaimable_bytes()
+    }
+
+    /// Returns the number of bytes [`Self::compact`] would reclaim: those between the free space
+    /// and the end of the page that no live tuple holds.
+    pub fn reclaimable_bytes(&self) -> usize {
+        let live: usize = (self.tuple_info.iter())
+            .filter(|info| !info.metadata.is_deleted())
+            .map(|info| info.size_bytes as usize)
+            .sum();
+        RUSTY_DB_PAGE_PAYLOAD_BYTES - self.tuples_end() - live
+    }
+
+    /// Slides the payloads of live tuples toward the end of the page, reclaiming the bytes held
+    /// by tombstoned ones. Tombstones keep their slot, with a size of zero, so the record id of
+    /// every tuple stays valid, and that of a deleted tuple never reads another: the slots
+    /// aren't freed, only the payloads. Returns the number of bytes reclaimed, by which
+    /// [`Self::free_space`] grows. Called by [`Page::insert_tuple`] for a tuple that only fits
+    /// once compacted.
+    pub fn compact(&mut self) -> u16 {
+        let tuples_end = self
+            .tuple_info
+            .iter()
+            .filter(|info| info.size_bytes > 0)
+            .map(|info| info.offset as usize)
+            .min()
+            .unwrap_or(RUSTY_DB_PAGE_PAYLOAD_BYTES);
+        let data = self.data.clone();
+        let mut cursor = RUSTY_DB_PAGE_PAYLOAD_BYTES;
+        for info in self.tuple_info.iter_mut() {
+            if info.metadata.is_deleted() {
+                info.size_bytes = 0;
+                info.overflow = false;
+            } else {
+                let (offset, size) = (info.offset as usize, info.size_bytes as usize);
+                cursor -= size;
+                self.data[cursor..(cursor + size)].copy_from_slice(&data[offset..(offset + size)]);
+            }
+            // Tombstones point at the end of the free space, where the next tuple goes.
+            info.offset = cursor as u16;
+        }
+
+        let reclaimed = cursor.saturating_sub(tuples_end);
+        if reclaimed > 0 {
+            self.data[tuples_end..cursor].fill(0);
+            self.is_dirty = true;
+        }
+        reclaimed as u16
+    }
+
+    /// Writes `tuple` over the tuple at `rid` and sets its metadata, without checking the page
+    /// ID of `rid`. Returns [`Error::InvalidInput`] if the slot doesn't exist, or if `tuple`
+    /// isn't of the old one's length, see [`Self::update_tuple`] for that.
     pub fn update_tuple_in_place_unchecked(
         &mut self,
         meta: TupleMetadata,
@@ -94,13 +267,18 @@ impl TablePage {
         rid: &RecordId,
     ) -> Result<()> {
         let slot = rid.slot_id() as usize;
-        if slot >= self.total_tuple_count() as usize {
-            panic!("Invalid slot ID");
+        if slot >= self.tuple_info.len() {
+            return Err(Error::InvalidInput("rID has invalid slot".to_string()));
         }
 
         // only support updating tuple payloads of equal length.
         let len = self.tuple_info[slot].size_bytes as usize;
-        assert_eq!(len, tuple.data.len());
+        if len != tuple.data.len() {
+            return Err(Error::InvalidInput(format!(
+                "tuple of {} bytes can't replace one of {len} in place",
+                tuple.data.len()
+            )));
+        }
 
         // Update both payload metadata.
         let old_meta = self.tuple_info[slot].metadata;
@@ -111,10 +289,119 @@ impl TablePage {
         // Along with tuple data.
         let offset = self.tuple_info[slot].offset as usize;
         self.data[offset..(offset + len)].copy_from_slice(&tuple.data);
+        self.is_dirty = true;
+
+        Ok(())
+    }
+
+    /// Replaces the tuple at `rid` and its metadata, keeping its slot, and so its record id,
+    /// whatever the new payload's length:
+    /// - One of the same length is written over the old one.
+    /// - A shorter one is written at the start of the old one, the bytes it leaves over
+    ///   reclaimable by [`Self::compact`].
+    /// - A longer one is moved to the front of the free space, compacting the page first if it
+    ///   only fits once compacted, the old one's bytes then reclaimable in turn. If it doesn't
+    ///   fit even so, the page is left as is and [`Error::TupleTooLarge`] is returned, for the
+    ///   caller to store the tuple elsewhere.
+    pub fn update_tuple(
+        &mut self,
+        meta: TupleMetadata,
+        tuple: Tuple,
+        rid: &RecordId,
+    ) -> Result<()> {
+        if rid.page_id() != self.page_id {
+            return Err(Error::InvalidInput("rID is different than this page's ID".to_string()));
+        }
+        let slot = rid.slot_id() as usize;
+        if slot >= self.tuple_info.len() {
+            return Err(Error::InvalidInput("rID has invalid slot".to_string()));
+        }
+        let len = tuple.data.len();
+        if len == self.tuple_info[slot].size_bytes as usize {
+            self.update_tuple_in_place_unchecked(meta, tuple, rid)?;
+            self.tuple_info[slot].overflow = false;
+            return Ok(());
+        }
+
+        if len > self.tuple_info[slot].size_bytes as usize {
+            let free_space = self.free_space();
+            if free_space < len && free_space + self.reclaimable_bytes() >= len {
+                self.compact();
+            }
+            let free_space = self.free_space();
+            if free_space < len {
+                return Err(Error::TupleTooLarge { len, free_space });
+            }
+            self.tuple_info[slot].offset = (self.tuples_end() - len) as u16;
+        }
+        let old_meta = self.tuple_info[slot].metadata;
+        self.update_tuple_cnt(&old_meta.is_deleted(), &meta.is_deleted());
+        let offset = self.tuple_info[slot].offset as usize;
+        self.data[offset..(offset + len)].copy_from_slice(&tuple.data);
+        self.tuple_info[slot].size_bytes = len as u16;
+        self.tuple_info[slot].metadata = meta;
+        self.tuple_info[slot].overflow = false;
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Inserts a stub for a tuple whose payload is stored on overflow pages, like
+    /// [`Page::insert_tuple`] does a tuple. The page only knows the stub as such, see
+    /// [`Self::overflow`]: reading the tuple reads the stub.
+    pub fn insert_overflow(&mut self, meta: TupleMetadata, stub: OverflowStub) -> Option<u16> {
+        let slot = self.insert_tuple(meta, Tuple::from(stub.to_bytes()))?;
+        self.tuple_info[slot as usize].overflow = true;
+        Some(slot)
+    }
 
+    /// Tombstones the tuple at `rid` for good: its slot is never reused, see
+    /// [`Self::reusable_slot`], and its payload is left for [`Self::compact`] to reclaim, by an
+    /// insert that needs the room or by a vacuum. The payload is no longer taken for an overflow
+    /// stub, whose pages the caller frees.
+    pub fn retire_tuple(&mut self, rid: &RecordId) -> Result<()> {
+        self.update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), rid)?;
+        let info = &mut self.tuple_info[rid.slot_id() as usize];
+        info.overflow = false;
+        info.retired = true;
         Ok(())
     }
 
+    /// Replaces the tuple at `rid` with a stub for a payload stored on overflow pages, like
+    /// [`Self::update_tuple`] does with a tuple.
+    pub fn update_overflow(
+        &mut self,
+        meta: TupleMetadata,
+        stub: OverflowStub,
+        rid: &RecordId,
+    ) -> Result<()> {
+        self.update_tuple(meta, Tuple::from(stub.to_bytes()), rid)?;
+        self.tuple_info[rid.slot_id() as usize].overflow = true;
+        Ok(())
+    }
+
+    /// Returns the overflow stub the tuple at `rid` was stored as, or `None` if its payload is
+    /// on the page. Deleted tuples keep theirs until the page is compacted.
+    pub fn overflow(&self, rid: &RecordId) -> Result<Option<OverflowStub>> {
+        if rid.page_id() != self.page_id {
+            return Err(Error::InvalidInput("rID is different than this page's ID".to_string()));
+        }
+        let Some(info) = self.tuple_info.get(rid.slot_id() as usize) else {
+            return Err(Error::InvalidInput("rID has invalid slot".to_string()));
+        };
+        if !info.overflow {
+            return Ok(None);
+        }
+        let (offset, size) = (info.offset as usize, info.size_bytes as usize);
+        match OverflowStub::from_bytes(&self.data[offset..(offset + size)]) {
+            Some(stub) => Ok(Some(stub)),
+            None => Err(Error::InvalidData(format!(
+                "slot {} of page {} holds no overflow stub",
+                rid.slot_id(),
+                self.page_id
+            ))),
+        }
+    }
+
     pub fn update_tuple_cnt(&mut self, old_meta_delete: &bool, new_meta_delete: &bool) {
         match (old_meta_delete, new_meta_delete) {
             (true, false) => {
@@ -139,8 +426,32 @@ impl TablePage {
         }
     }
 
+    /// Returns an iterator over all Tuples on this page, from the last slot to the first.
+    pub fn iter_rev(table_page: Arc<RwLock<Self>>) -> TablePageRevIterator {
+        let remaining = table_page.read().unwrap().total_tuple_count();
+        TablePageRevIterator {
+            page: table_page,
+            remaining,
+        }
+    }
+
+    /// Returns the tuple in a slot along with its metadata, unless it is a tombstone.
+    fn tuple_if_exists(&self, page_slot: u16) -> Option<(RecordId, TupleMetadata, Tuple)> {
+        let metadata = self.tuple_info[page_slot as usize].metadata;
+        match metadata.is_deleted() {
+            // tombstone tuple; no tuple to return.
+            true => None,
+            // tuple is not deleted; return it!
+            false => {
+                let rid = RecordId::new(self.page_id, page_slot);
+                self.get_tuple(&rid)
+                    .map_or_else(|_| None, |payload| Some((rid, metadata, payload)))
+            }
+        }
+    }
+
     pub fn create_invalid_page() -> TablePage {
-        TablePage::new(INVALID_PID, INVALID_PID)
+        TablePage::new(INVALID_PID, INVALID_PID, DEFAULT_FILL_FACTOR)
     }
 
     pub fn is_invalid(&self) -> bool {
@@ -158,7 +469,7 @@ impl Page for TablePage {
         }
 
         // check if slot id is valid
-        if rid.slot_id() > self.total_tuple_count() - 1 {
+        if rid.slot_id() as usize >= self.tuple_info.len() {
             return Result::from(Error::InvalidInput("rID has invalid slot".parse().unwrap()));
         }
 
@@ -181,50 +492,48 @@ impl Page for TablePage {
     ) -> Option<Self::InsertOutputType> {
         // update data, tuple cnt/ deleted tuple cnt depending on metadata, tuple_info, dirty bit
 
-        // check if tuple fits on page
-        let meta_space = 2 + 2 + 2 + 2 + (4 * self.total_tuple_count() as u16) as usize;
-        let data_space = match self.total_tuple_count() {
-            0 => 0,
-            _ => RUSTY_DB_PAGE_SIZE_BYTES - self.tuple_info[(self.total_tuple_count() - 1) as usize].offset as usize,
-        };
-        let available_space = RUSTY_DB_PAGE_SIZE_BYTES - (meta_space + data_space) as usize;
-
-        return if available_space < 4 + tuple.data.len() {
-            None
-        } else {
-            let from_byte = match self.total_tuple_count() {
-                0 => RUSTY_DB_PAGE_SIZE_BYTES - 1,
-                _ => (self.tuple_info[(self.total_tuple_count() - 1) as usize].offset - 1) as usize
-            };
-            let insert_info = TupleInfo {
-                offset: from_byte as u16 - tuple.data.len() as u16 + 1,
+        // take the place of a deleted tuple if one's bytes hold the tuple, the bytes it leaves
+        // over reclaimable by compaction
+        if let Some(slot) = self.reusable_slot(tuple.data.len()) {
+            let info = self.tuple_info[slot];
+            let offset = info.offset as usize;
+            self.data[offset..(offset + tuple.data.len())].copy_from_slice(&tuple.data);
+            self.update_tuple_cnt(&true, &meta.is_deleted());
+            self.tuple_info[slot] = TupleInfo {
                 size_bytes: tuple.data.len() as u16,
-                metadata: meta.clone()
+                metadata: meta,
+                overflow: false,
+                ..info
             };
-
-            // Update data
-            let start_index = insert_info.offset as usize;
-            let end_index = from_byte;
-            let mut tuple_index = 0;
-                for i in start_index..=end_index {
-                    self.data[i] = tuple.data[tuple_index];
-                    tuple_index +=  1;
-                }
-
-            // Update tuple_info
-            self.tuple_info.push(insert_info);
-
-            // Update (deleted)_tuple_count
-            if meta.is_deleted() == true {
-                self.deleted_tuple_cnt += 1;
-            } else {
-                self.tuple_cnt += 1;
-            }
-
             self.is_dirty = true;
+            return Some(slot as u16);
+        }
+
+        // compact the page if the tuple only fits once the deleted tuples' bytes are reclaimed
+        let free_compacted = self.free_space_compacted();
+        let fits_compacted = Self::has_room(free_compacted, tuple.data.len(), self.fill_factor);
+        if !self.fits(&tuple) && fits_compacted {
+            self.compact();
+        }
 
-            Some((self.tuple_info.len() - 1) as u16)
+        // place the tuple right before the others, if it fits on the page
+        let offset = self.get_next_tuple_offset(&tuple)? as usize;
+        self.data[offset..(offset + tuple.data.len())].copy_from_slice(&tuple.data);
+        if meta.is_deleted() {
+            self.deleted_tuple_cnt += 1;
+        } else {
+            self.tuple_cnt += 1;
         }
+        self.tuple_info.push(TupleInfo {
+            offset: offset as u16,
+            size_bytes: tuple.data.len() as u16,
+            metadata: meta,
+            overflow: false,
+            retired: false,
+        });
+        self.is_dirty = true;
+
+        Some((self.tuple_info.len() - 1) as u16)
     }
 
     fn get_tuple_metadata(&self, rid: &RecordId) -> Result<TupleMetadata> {
@@ -233,7 +542,7 @@ impl Page for TablePage {
         }
 
         // check if slot id is valid
-        if rid.slot_id() > self.total_tuple_count() - 1 {
+        if rid.slot_id() as usize >= self.tuple_info.len() {
             return Result::from(Error::InvalidInput("rID has invalid slot".parse().unwrap()));
         }
 
@@ -246,11 +555,14 @@ impl Page for TablePage {
         }
 
         // check if slot id is valid
-        if rid.slot_id() > self.total_tuple_count() - 1 {
+        if rid.slot_id() as usize >= self.tuple_info.len() {
             return Result::from(Error::InvalidInput("rID has invalid slot".parse().unwrap()));
         }
 
+        let old_meta = self.tuple_info[rid.slot_id() as usize].metadata;
+        self.update_tuple_cnt(&old_meta.is_deleted(), &metadata.is_deleted());
         self.tuple_info[rid.slot_id() as usize].metadata = metadata.clone();
+        self.is_dirty = true;
         return Ok(());
     }
 
@@ -265,6 +577,14 @@ impl Page for TablePage {
         }
     }
 
+    fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+
+    fn set_lsn(&mut self, lsn: Lsn) {
+        self.lsn = lsn;
+    }
+
     fn page_id(&self) -> &PageId {
         &self.page_id
     }
@@ -278,112 +598,85 @@ impl Page for TablePage {
     }
 
     /// Note: data: Vec<u8> remains serialized in the TablePage; serialization happens incrementally
-    /// in [`Self::insert_tuple`]
+    /// in [`Self::insert_tuple`]. Only the header and slots are written here, ahead of the
+    /// tuples, laid out as described on [`TablePage`].
     fn serialize(&self) -> Vec<u8> {
-        // Copy out tuple contents.
-        let mut result = self.data.clone();
+        let header_size = Self::header_size_with(self.tuple_info.len());
+        let overlaps =
+            |info: &TupleInfo| info.size_bytes > 0 && (info.offset as usize) < header_size;
+        debug_assert!(
+            !self.tuple_info.iter().any(overlaps),
+            "the slots of page {} overlap its tuples",
+            self.page_id
+        );
+        // Copy out tuple contents, past the header.
+        let mut result = vec![0; RUSTY_DB_PAGE_PAYLOAD_BYTES];
+        result[header_size..].copy_from_slice(&self.data[header_size..]);
 
         let mut cursor = 0;
-        // page_id: PageId,
-        let page_id_size = mem::size_of::<PageId>();
-        let page_id_bytes = bincode::serialize(&self.page_id).unwrap();
-        result[cursor..(cursor + page_id_size)].copy_from_slice(&page_id_bytes[..]);
-        cursor += page_id_size;
-
-        // next_page_id: u32
-        let next_page_id_bytes = self.next_page_id.to_le_bytes();
-        result[cursor..(cursor + 4)].copy_from_slice(&next_page_id_bytes);
-        cursor += 4;
-
-        // tuple_cnt: u16,
-        let tuple_cnt_bytes = self.tuple_cnt.to_le_bytes();
-        result[cursor..(cursor + 2)].copy_from_slice(&tuple_cnt_bytes);
-        cursor += 2;
-
-        // deleted_tuple_cnt: u16
-        let deleted_tuple_cnt_bytes = self.deleted_tuple_cnt.to_le_bytes();
-        result[cursor..(cursor + 2)].copy_from_slice(&deleted_tuple_cnt_bytes);
-        cursor += 2;
-
-        // tuple_info: Vec<TupleInfo>
-        self.tuple_info.iter().for_each(|info| {
-            match info.metadata.is_deleted() {
-                true => {
-                    // this slot is vacant
-                    result[cursor..(cursor + 4)].fill(0);
-                    cursor += 4;
-                }
-                false => {
-                    let offset_bytes = info.offset.to_le_bytes();
-                    result[cursor..(cursor + 2)].copy_from_slice(&offset_bytes);
-                    cursor += 2;
-
-                    let size_bytes = info.size_bytes.to_le_bytes();
-                    result[cursor..(cursor + 2)].copy_from_slice(&size_bytes);
-                    cursor += 2;
-                }
-            }
-        });
+        let mut write = |bytes: &[u8]| {
+            result[cursor..(cursor + bytes.len())].copy_from_slice(bytes);
+            cursor += bytes.len();
+        };
+        write(&self.page_id.to_le_bytes());
+        write(&self.next_page_id.to_le_bytes());
+        write(&self.tuple_cnt.to_le_bytes());
+        write(&self.deleted_tuple_cnt.to_le_bytes());
+        write(&self.lsn.to_le_bytes());
+        write(&[self.fill_factor]);
+        for info in &self.tuple_info {
+            write(&info.offset.to_le_bytes());
+            write(&info.size_bytes.to_le_bytes());
+            let deleted = if info.metadata.is_deleted() { Self::SLOT_DELETED } else { 0 };
+            let overflow = if info.overflow { Self::SLOT_OVERFLOW } else { 0 };
+            let retired = if info.retired { Self::SLOT_RETIRED } else { 0 };
+            write(&[deleted | overflow | retired]);
+            write(&info.metadata.insert_txn_id().to_le_bytes());
+            write(&info.metadata.delete_txn_id().to_le_bytes());
+        }
 
         result
     }
 
     // deserialize buffer to self thereby reinitializing the page
     /// Note: data: Vec<u8> remains serialized in the TablePage; deserialization happens on-demand;
-    ///       see [`crate::storage::tuple::row::get_field`]
+    ///       see [`crate::storage::tuple::row::get_field`]. The header and slots are read into
+    ///       the page, and cleared from its data, which only holds the tuples.
     fn deserialize(buffer: &[u8]) -> Self::ConcretePageType {
         let mut page = TablePage::builder().page_id(0).build();
-        page.data = buffer.to_vec();
         let mut cursor = 0;
-
-        // page_id: PageId
-        let page_id_size = mem::size_of::<PageId>();
-        let page_id_bytes = &buffer[cursor..(cursor + page_id_size)];
-        page.page_id = bincode::deserialize(&page_id_bytes).unwrap();
-        cursor += page_id_size;
-
-        // next_page_id: u32
-        let next_page_id_bytes = buffer[cursor..(cursor + 4)].to_vec();
-        page.next_page_id = u32::from_le_bytes(next_page_id_bytes.try_into().unwrap());
-        cursor += 4;
-
-        // tuple_cnt: u16
-        let tuple_cnt_bytes = buffer[cursor..(cursor + 2)].to_vec();
-        page.tuple_cnt = u16::from_le_bytes(tuple_cnt_bytes.try_into().unwrap());
-        cursor += 2;
-
-        // deleted_tuple_cnt: u16
-        let deleted_tuple_cnt_bytes = buffer[cursor..(cursor + 2)].to_vec();
-        page.deleted_tuple_cnt = u16::from_le_bytes(deleted_tuple_cnt_bytes.try_into().unwrap());
-        cursor += 2;
-
-        // tuple_info: Vec<TupleInfo>
-        (0..(page.tuple_cnt + page.deleted_tuple_cnt)).for_each(|_| {
-            let offset_bytes = buffer[cursor..(cursor + 2)].to_vec();
-            let offset = u16::from_le_bytes(offset_bytes.try_into().unwrap());
-            cursor += 2;
-
-            let size_bytes = buffer[cursor..(cursor + 2)].to_vec();
-            let size = u16::from_le_bytes(size_bytes.try_into().unwrap());
-            cursor += 2;
-
-            let mut deleted = false;
-            if size == 0 && offset == 0 {
-                deleted = true;
-            }
-
-            let meta = TupleMetadata::new(deleted);
-            let tuple_info = TupleInfo {
+        let mut read = |len: usize| {
+            let bytes = &buffer[cursor..(cursor + len)];
+            cursor += len;
+            bytes
+        };
+        page.page_id = PageId::from_le_bytes(read(4).try_into().unwrap());
+        page.next_page_id = u32::from_le_bytes(read(4).try_into().unwrap());
+        page.tuple_cnt = u16::from_le_bytes(read(2).try_into().unwrap());
+        page.deleted_tuple_cnt = u16::from_le_bytes(read(2).try_into().unwrap());
+        page.lsn = Lsn::from_le_bytes(read(mem::size_of::<Lsn>()).try_into().unwrap());
+        page.fill_factor = read(1)[0];
+        for _ in 0..(page.tuple_cnt + page.deleted_tuple_cnt) {
+            let offset = u16::from_le_bytes(read(2).try_into().unwrap());
+            let size_bytes = u16::from_le_bytes(read(2).try_into().unwrap());
+            let flags = read(1)[0];
+            let mut metadata = TupleMetadata::new(flags & Self::SLOT_DELETED != 0);
+            let mut read_txn_id =
+                || TxnId::from_le_bytes(read(mem::size_of::<TxnId>()).try_into().unwrap());
+            metadata.set_insert_txn_id(read_txn_id());
+            metadata.set_delete_txn_id(read_txn_id());
+            page.tuple_info.push(TupleInfo {
                 offset,
-                size_bytes: size,
-                metadata: meta,
-            };
-            page.tuple_info.push(tuple_info);
-        });
+                size_bytes,
+                metadata,
+                overflow: flags & Self::SLOT_OVERFLOW != 0,
+                retired: flags & Self::SLOT_RETIRED != 0,
+            });
+        }
 
         // tuple data: Vec<u8>
-        let tuple_data = buffer[0..RUSTY_DB_PAGE_SIZE_BYTES].to_vec();
-        page.data = tuple_data;
+        page.data = buffer[0..RUSTY_DB_PAGE_PAYLOAD_BYTES].to_vec();
+        page.data[..cursor].fill(0);
 
         page
     }
@@ -399,33 +692,8 @@ impl TablePageIterator {
         self.page.read().unwrap().get_next_page_id()
     }
 
-    /// Returns the next tuple payload on the table, if one exists.
-    fn tuple_if_exists(
-        &self,
-        page_slot: u16,
-        page_guard: &RwLockReadGuard<TablePage>,
-    ) -> Option<(RecordId, Tuple)> {
-        match page_guard.tuple_info[page_slot as usize]
-            .metadata
-            .is_deleted()
-        {
-            // tombstone tuple; no tuple to return.
-            true => None,
-            // tuple is not deleted; return it!
-            false => {
-                let rid = RecordId::new(page_guard.page_id, page_slot);
-                page_guard
-                    .get_tuple(&rid)
-                    .map_or_else(|_| None, |payload| Some((rid, payload)))
-            }
-        }
-    }
-}
-
-impl Iterator for TablePageIterator {
-    type Item = (RecordId, Tuple);
-
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Returns the next non-tombstoned tuple on the page along with its metadata.
+    pub fn next_with_metadata(&mut self) -> Option<(RecordId, TupleMetadata, Tuple)> {
         let page_guard = self.page.read().unwrap();
 
         // Use a loop to skip deleted tuples and find the next valid one.
@@ -436,11 +704,19 @@ impl Iterator for TablePageIterator {
                 return None;
             }
             // Return non-deleted tuple, if encountered.
-            if let Some(item) = self.tuple_if_exists(page_slot, &page_guard) {
+            if let Some(item) = page_guard.tuple_if_exists(page_slot) {
                 return Some(item);
             }
         }
     }
+}
+
+impl Iterator for TablePageIterator {
+    type Item = (RecordId, Tuple);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_metadata().map(|(rid, _, tuple)| (rid, tuple))
+    }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
         self.index.fetch_add(n as u16, Ordering::SeqCst);
@@ -448,9 +724,40 @@ impl Iterator for TablePageIterator {
     }
 }
 
+/// The reverse counterpart of [`TablePageIterator`], iterating over the tuples on a page from the
+/// highest slot down. The slots taken when it is created are the ones it goes over.
+pub struct TablePageRevIterator {
+    page: Arc<RwLock<TablePage>>,
+    /// The number of slots left to go over, i.e. one past the next slot.
+    remaining: u16,
+}
+
+impl TablePageRevIterator {
+    /// Returns the next non-tombstoned tuple on the page, going down, along with its metadata.
+    pub fn next_with_metadata(&mut self) -> Option<(RecordId, TupleMetadata, Tuple)> {
+        let page_guard = self.page.read().unwrap();
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            if let Some(item) = page_guard.tuple_if_exists(self.remaining) {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for TablePageRevIterator {
+    type Item = (RecordId, Tuple);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_metadata().map(|(rid, _, tuple)| (rid, tuple))
+    }
+}
+
 pub struct TablePageBuilder {
     page_id: Option<PageId>,
     next_page_id: Option<PageId>,
+    fill_factor: Option<u8>,
 }
 
 impl TablePageBuilder {
@@ -458,6 +765,7 @@ impl TablePageBuilder {
         TablePageBuilder {
             page_id: None,
             next_page_id: None,
+            fill_factor: None,
         }
     }
 
@@ -469,11 +777,20 @@ impl TablePageBuilder {
         self.next_page_id = Some(next_page_id);
         self
     }
+    /// Sets the percentage of the page inserts fill, [`DEFAULT_FILL_FACTOR`] unless set, see
+    /// [`TablePage::fits`]. It is kept in the page's header.
+    pub fn fill_factor(&mut self, percent: u8) -> &mut Self {
+        self.fill_factor = Some(percent);
+        self
+    }
     pub fn build(&self) -> TablePage {
+        let fill_factor = self.fill_factor.unwrap_or(DEFAULT_FILL_FACTOR);
+        assert!(fill_factor <= 100, "Cannot build TablePage with a fill factor above 100.");
         TablePage::new(
             self.page_id
                 .expect("Cannot build TablePage without a `page_id`."),
             self.next_page_id.unwrap_or(INVALID_PID),
+            fill_factor,
         )
     }
 }
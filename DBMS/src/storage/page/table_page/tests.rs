@@ -1,12 +1,18 @@
 use super::*;
+use crate::common::Error;
 use crate::common::utility::{
     create_random_full_page, create_random_row, create_table_definition_mixed_fields,
 };
-use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
+use crate::config::config::RUSTY_DB_PAGE_PAYLOAD_BYTES;
 use crate::storage::page::record_id::RecordId;
-use crate::storage::page::Page;
+use crate::storage::page::{OverflowStub, Page};
+use crate::storage::simple::INVALID_TXN_ID;
 use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::wal::Lsn;
 use crate::types::{DataType, Table};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::mem;
 use std::sync::{Arc, RwLock};
 
 #[test]
@@ -24,6 +30,355 @@ pub fn test_insert_tuple() {
     assert_eq!(tuple, page.get_tuple(&rid).unwrap());
 }
 
+#[test]
+pub fn test_insert_tuple_offsets() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let mut expected = RUSTY_DB_PAGE_PAYLOAD_BYTES;
+    for (slot, len) in [10, 1, 25].into_iter().enumerate() {
+        let tuple = Tuple::from(vec![slot as u8 + 1; len]);
+        expected -= len;
+        assert_eq!(Some(expected as u16), page.get_next_tuple_offset(&tuple));
+        assert_eq!(Some(slot as u16), page.insert_tuple(TupleMetadata::new(false), tuple));
+        assert_eq!(expected as u16, page.tuple_info[slot].offset);
+    }
+    // The tuples end at the page's last byte and abut each other.
+    assert_eq!(1, page.data[RUSTY_DB_PAGE_PAYLOAD_BYTES - 1]);
+    assert_eq!(2, page.data[RUSTY_DB_PAGE_PAYLOAD_BYTES - 11]);
+    assert_eq!(3, page.data[RUSTY_DB_PAGE_PAYLOAD_BYTES - 12]);
+}
+
+#[test]
+pub fn test_insert_tuple_of_page_capacity() {
+    let capacity = TablePage::EMPTY_FREE_SPACE - TablePage::SLOT_BYTES;
+    let mut page = TablePage::builder().page_id(0).build();
+    assert!(!page.fits(&Tuple::from(vec![1; capacity + 1])));
+
+    let tuple = Tuple::from(vec![1; capacity]);
+    let slot = page.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap();
+    assert_eq!(TablePage::header_size_with(1), page.tuple_info[0].offset as usize);
+    assert_eq!(0, page.free_space());
+    assert_eq!(tuple, page.get_tuple(&RecordId::new(0, slot)).unwrap());
+    assert_eq!(None, page.insert_tuple(TupleMetadata::new(false), Tuple::from(vec![1])));
+}
+
+#[test]
+pub fn test_slot_out_of_bounds() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let meta = TupleMetadata::new(false);
+    let empty = RecordId::new(0, 0);
+    assert!(matches!(page.get_tuple(&empty), Err(Error::InvalidInput(_))));
+    assert!(matches!(page.get_tuple_metadata(&empty), Err(Error::InvalidInput(_))));
+    assert!(matches!(page.update_tuple_metadata(&meta, &empty), Err(Error::InvalidInput(_))));
+
+    let tuple = Tuple::from(vec![1_u8, 2_u8]);
+    page.insert_tuple(meta, tuple.clone()).unwrap();
+    let last = page.insert_tuple(meta, tuple.clone()).unwrap();
+    // The slot equal to the count is one past the last.
+    let past = RecordId::new(0, last + 1);
+    assert!(matches!(page.get_tuple(&past), Err(Error::InvalidInput(_))));
+    assert!(matches!(page.get_tuple_metadata(&past), Err(Error::InvalidInput(_))));
+    assert!(matches!(page.update_tuple_metadata(&meta, &past), Err(Error::InvalidInput(_))));
+
+    let last = RecordId::new(0, last);
+    assert_eq!(tuple, page.get_tuple(&last).unwrap());
+    assert_eq!(meta, page.get_tuple_metadata(&last).unwrap());
+    page.update_tuple_metadata(&TupleMetadata::new(true), &last).unwrap();
+    assert_eq!(1, page.deleted_tuple_count());
+}
+
+#[test]
+pub fn test_compact() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let tuple = |n: u8| Tuple::from(vec![n; 10 * n as usize]);
+    let rids: Vec<_> = (1..=6_u8)
+        .map(|n| RecordId::new(0, page.insert_tuple(TupleMetadata::new(false), tuple(n)).unwrap()))
+        .collect();
+    for rid in [&rids[1], &rids[4]] {
+        page.update_tuple_metadata(&TupleMetadata::new(true), rid).unwrap();
+    }
+    assert_eq!(20 + 50, page.reclaimable_bytes());
+
+    let free_space = page.free_space();
+    assert_eq!(20 + 50, page.compact());
+    assert_eq!(free_space + 20 + 50, page.free_space());
+    assert_eq!(0, page.reclaimable_bytes());
+    assert_eq!(0, page.compact());
+    for (n, rid) in (1..=6_u8).zip(&rids) {
+        match n {
+            2 | 5 => assert!(page.get_tuple(rid).is_err()),
+            _ => assert_eq!(tuple(n), page.get_tuple(rid).unwrap()),
+        }
+    }
+}
+
+#[test]
+pub fn test_insert_compacts_full_page() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let tuple = |n: u8| Tuple::from(vec![n; 100]);
+    let mut rids = Vec::new();
+    while let Some(slot) = page.insert_tuple(TupleMetadata::new(false), tuple(rids.len() as u8)) {
+        rids.push(RecordId::new(0, slot));
+    }
+    for rid in &rids[..2] {
+        page.update_tuple_metadata(&TupleMetadata::new(true), rid).unwrap();
+    }
+
+    // The tuple only fits once the deleted tuples' bytes are reclaimed, as neither holds it.
+    let larger = Tuple::from(vec![u8::MAX; 150]);
+    let slot = page.insert_tuple(TupleMetadata::new(false), larger.clone()).unwrap();
+    assert_eq!(rids.len() as u16, slot);
+    assert_eq!(larger, page.get_tuple(&RecordId::new(0, slot)).unwrap());
+    for (n, rid) in rids.iter().enumerate().skip(2) {
+        assert_eq!(tuple(n as u8), page.get_tuple(rid).unwrap());
+    }
+}
+
+#[test]
+pub fn test_insert_reuses_deleted_slot() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let tuple = |n: u8, len: usize| Tuple::from(vec![n; len]);
+    let rids: Vec<_> = [30, 20, 10]
+        .map(|len| page.insert_tuple(TupleMetadata::new(false), tuple(0, len)).unwrap())
+        .map(|slot| RecordId::new(0, slot))
+        .to_vec();
+    for rid in &rids[..2] {
+        page.update_tuple_metadata(&TupleMetadata::new(true), rid).unwrap();
+    }
+    assert_eq!((1, 2), (page.tuple_count(), page.deleted_tuple_count()));
+
+    // The smallest deleted tuple holding the new one gives up its slot, the rest of its bytes
+    // left to compaction.
+    let free_space = page.free_space();
+    assert_eq!(1, page.slot_for(&tuple(1, 15)));
+    assert_eq!(Some(1), page.insert_tuple(TupleMetadata::new(false), tuple(1, 15)));
+    assert_eq!((2, 1), (page.tuple_count(), page.deleted_tuple_count()));
+    assert_eq!(tuple(1, 15), page.get_tuple(&rids[1]).unwrap());
+    assert_eq!(tuple(0, 10), page.get_tuple(&rids[2]).unwrap());
+    assert_eq!(free_space, page.free_space());
+    assert_eq!(30 + 5, page.reclaimable_bytes());
+
+    // Nor is a slot reused once its tuple's bytes are reclaimed.
+    page.compact();
+    assert_eq!(3, page.slot_for(&tuple(2, 10)));
+    assert_eq!(Some(3), page.insert_tuple(TupleMetadata::new(false), tuple(2, 10)));
+    assert_eq!((3, 1), (page.tuple_count(), page.deleted_tuple_count()));
+}
+
+#[test]
+pub fn test_retired_slot_never_reused() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let meta = TupleMetadata::new(false);
+    let stub = OverflowStub { first_page_id: 1, len: 10_000 };
+    let slots = [page.insert_overflow(meta, stub), page.insert_tuple(meta, vec![0; 30].into())];
+    let rids = slots.map(|slot| RecordId::new(0, slot.unwrap()));
+    let free_space = page.free_space();
+
+    // Retiring a tuple keeps its payload, but not as a stub, until the page is compacted.
+    page.retire_tuple(&rids[0]).unwrap();
+    page.retire_tuple(&rids[1]).unwrap();
+    assert_eq!((0, 2), (page.tuple_count(), page.deleted_tuple_count()));
+    assert_eq!(free_space, page.free_space());
+    assert_eq!(OverflowStub::BYTES + 30, page.reclaimable_bytes());
+    assert_eq!(None, page.overflow(&rids[0]).unwrap());
+
+    // Neither slot is taken by an insert its payload holds, even once read back from disk.
+    let mut page = TablePage::deserialize(&page.serialize());
+    let tuple = Tuple::from(vec![1; 10]);
+    assert_eq!(2, page.slot_for(&tuple));
+    assert_eq!(Some(2), page.insert_tuple(meta, tuple));
+}
+
+#[test]
+pub fn test_update_tuple() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let tuple = |n: u8, len: usize| Tuple::from(vec![n; len]);
+    let meta = TupleMetadata::new(false);
+    let rids: Vec<_> = (0..3)
+        .map(|n| RecordId::new(0, page.insert_tuple(meta, tuple(n, 20)).unwrap()))
+        .collect();
+    let free_space = page.free_space();
+
+    // The same length is written over the old tuple, and a shorter one at its start.
+    page.update_tuple(meta, tuple(3, 20), &rids[0]).unwrap();
+    assert_eq!(tuple(3, 20), page.get_tuple(&rids[0]).unwrap());
+    page.update_tuple(meta, tuple(4, 5), &rids[1]).unwrap();
+    assert_eq!(tuple(4, 5), page.get_tuple(&rids[1]).unwrap());
+    assert_eq!(free_space, page.free_space());
+    assert_eq!(15, page.reclaimable_bytes());
+
+    // A longer one moves to the front of the free space, below the tuples after it.
+    page.update_tuple(meta, tuple(5, 50), &rids[0]).unwrap();
+    assert_eq!(tuple(5, 50), page.get_tuple(&rids[0]).unwrap());
+    assert_eq!(free_space - 50, page.free_space());
+    assert_eq!(15 + 20, page.reclaimable_bytes());
+    let slot = page.insert_tuple(meta, tuple(6, 10)).unwrap();
+    assert_eq!(3, slot);
+
+    // Nothing else moved, nor was lost in compacting or serializing the page.
+    let expected = [tuple(5, 50), tuple(4, 5), tuple(2, 20), tuple(6, 10)];
+    let read = |page: &TablePage| -> Vec<Tuple> {
+        (0..4).map(|slot| page.get_tuple(&RecordId::new(0, slot)).unwrap()).collect()
+    };
+    assert_eq!(expected.to_vec(), read(&page));
+    assert_eq!(35, page.compact());
+    assert_eq!(expected.to_vec(), read(&page));
+    assert_eq!(expected.to_vec(), read(&TablePage::deserialize(&page.serialize())));
+    assert_eq!((4, 0), (page.tuple_count(), page.deleted_tuple_count()));
+}
+
+#[test]
+pub fn test_update_tuple_in_place_invalid() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let meta = TupleMetadata::new(false);
+    let rid = RecordId::new(0, page.insert_tuple(meta, Tuple::from(vec![0; 20])).unwrap());
+    let update = |page: &mut TablePage, len: usize, rid: &RecordId| {
+        page.update_tuple_in_place_unchecked(meta, Tuple::from(vec![1; len]), rid)
+    };
+    assert!(matches!(update(&mut page, 20, &RecordId::new(0, 1)), Err(Error::InvalidInput(_))));
+    assert!(matches!(update(&mut page, 21, &rid), Err(Error::InvalidInput(_))));
+    assert_eq!(Tuple::from(vec![0; 20]), page.get_tuple(&rid).unwrap());
+    update(&mut page, 20, &rid).unwrap();
+    assert_eq!(Tuple::from(vec![1; 20]), page.get_tuple(&rid).unwrap());
+}
+
+#[test]
+pub fn test_update_tuple_too_large() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let meta = TupleMetadata::new(false);
+    let rid = RecordId::new(0, page.insert_tuple(meta, Tuple::from(vec![0; 100])).unwrap());
+    let other = RecordId::new(0, page.insert_tuple(meta, Tuple::from(vec![1; 100])).unwrap());
+    let filler = page.free_space() - TablePage::SLOT_BYTES - 10;
+    page.insert_tuple(meta, Tuple::from(vec![2; filler])).unwrap();
+    assert_eq!(10, page.free_space());
+
+    // Growing past the free space fails, leaving the page as it was.
+    let before = page.clone();
+    let err = page.update_tuple(meta, Tuple::from(vec![3; 120]), &rid);
+    assert_eq!(Err(Error::TupleTooLarge { len: 120, free_space: 10 }), err);
+    assert_eq!(before.serialize(), page.serialize());
+    assert_eq!(before.is_dirty, page.is_dirty);
+
+    // Unless compacting the page makes room, here by reclaiming a deleted tuple's bytes.
+    page.update_tuple_metadata(&TupleMetadata::new(true), &other).unwrap();
+    page.update_tuple(meta, Tuple::from(vec![3; 105]), &rid).unwrap();
+    assert_eq!(Tuple::from(vec![3; 105]), page.get_tuple(&rid).unwrap());
+    assert_eq!(5, page.free_space());
+    assert_eq!(100, page.reclaimable_bytes());
+}
+
+#[test]
+pub fn test_fits() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut page = TablePage::builder().page_id(0).build();
+    let mut rejected = Vec::new();
+    while rejected.len() < 20 {
+        let tuple = Tuple::from(vec![1; rng.gen_range(1..200)]);
+        let fits = page.fits(&tuple);
+        assert_eq!(fits, page.get_next_tuple_offset(&tuple).is_some());
+        let free_space = page.free_space();
+        match page.insert_tuple(TupleMetadata::new(false), tuple.clone()) {
+            Some(_) => {
+                assert!(fits);
+                let taken = tuple.data.len() + TablePage::SLOT_BYTES;
+                assert_eq!(free_space - taken, page.free_space());
+            }
+            None => {
+                assert!(!fits);
+                rejected.push(tuple.data.len());
+            }
+        }
+    }
+    let smallest_rejected = rejected.into_iter().min().unwrap();
+    assert!(page.free_space() < smallest_rejected + TablePage::SLOT_BYTES);
+}
+
+/// Test that however tuples of random sizes fill a page, its slots never run into the payloads
+/// and the payloads never run into each other.
+#[test]
+pub fn test_slots_never_overlap_tuples() {
+    for seed in 0..16 {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut page = TablePage::builder().page_id(0).build();
+        loop {
+            let tuple = Tuple::from(vec![seed as u8; rng.gen_range(0..300)]);
+            if page.insert_tuple(TupleMetadata::new(false), tuple).is_none() {
+                break;
+            }
+
+            let header_size = TablePage::header_size_with(page.tuple_info.len());
+            let mut payloads: Vec<(usize, usize)> = page
+                .tuple_info
+                .iter()
+                .filter(|info| info.size_bytes > 0)
+                .map(|info| (info.offset as usize, info.size_bytes as usize))
+                .collect();
+            payloads.sort();
+            for (offset, size) in &payloads {
+                assert!(*offset >= header_size);
+                assert!(offset + size <= RUSTY_DB_PAGE_PAYLOAD_BYTES);
+            }
+            for pair in payloads.windows(2) {
+                assert!(pair[0].0 + pair[0].1 <= pair[1].0);
+            }
+            let tuples_end = payloads.first().map_or(RUSTY_DB_PAGE_PAYLOAD_BYTES, |p| p.0);
+            assert!(page.free_space() <= tuples_end - header_size);
+
+            // Writing the header doesn't clobber any payload.
+            let copy = TablePage::deserialize(&page.serialize());
+            assert_eq!(page.data[header_size..], copy.data[header_size..]);
+        }
+    }
+}
+
+#[test]
+pub fn test_fill_factor() {
+    let reserved = RUSTY_DB_PAGE_PAYLOAD_BYTES * 30 / 100;
+    let mut page = TablePage::builder().page_id(0).fill_factor(70).build();
+    // An empty page takes a tuple larger than its fill factor lets it hold.
+    let large = Tuple::from(vec![1; TablePage::EMPTY_FREE_SPACE - TablePage::SLOT_BYTES]);
+    assert!(page.fits(&large));
+    page.insert_tuple(TupleMetadata::new(false), Tuple::from(vec![1; 100])).unwrap();
+
+    let tuple = Tuple::from(vec![1; 100]);
+    while page.fits(&tuple) {
+        page.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap();
+    }
+    assert!(page.free_space() >= reserved);
+    assert!(page.free_space() < reserved + 100 + TablePage::SLOT_BYTES);
+    assert_eq!(None, page.insert_tuple(TupleMetadata::new(false), tuple.clone()));
+    // A fill factor read back from disk, or given explicitly, applies the same.
+    let mut page = TablePage::deserialize(&page.serialize());
+    assert_eq!(70, page.fill_factor);
+    assert!(!page.fits(&tuple));
+    assert!(!page.fits_with(&tuple, 70));
+    assert!(page.fits_with(&tuple, 100));
+
+    // Updates may grow tuples into the reserve.
+    let rid = RecordId::new(0, 0);
+    page.update_tuple(TupleMetadata::new(false), Tuple::from(vec![2; 300]), &rid).unwrap();
+    assert!(page.free_space() < reserved);
+}
+
+/// Fill factors above 100 leave no reserve, like 100, rather than underflow.
+#[test]
+pub fn test_fill_factor_above_100() {
+    let mut page = TablePage::builder().page_id(0).build();
+    let tuple = Tuple::from(vec![1; 100]);
+    while page.fits(&tuple) {
+        page.insert_tuple(TupleMetadata::new(false), tuple.clone()).unwrap();
+        for fill_factor in [101, 150, u8::MAX] {
+            assert_eq!(page.fits_with(&tuple, 100), page.fits_with(&tuple, fill_factor));
+        }
+    }
+    assert!(!page.fits_with(&tuple, u8::MAX));
+}
+
+#[test]
+#[should_panic(expected = "fill factor above 100")]
+pub fn test_build_fill_factor_above_100() {
+    TablePage::builder().page_id(0).fill_factor(101).build();
+}
+
 #[test]
 pub fn test_overfull_page() {
     let schema = Table::builder()
@@ -35,21 +390,22 @@ pub fn test_overfull_page() {
         .build_with_handle();
 
     let mut page = TablePage::builder().page_id(0).build();
-    // cost of next_page_id (u32) + tuple_cnt (u16) + deleted_tuple_cnt (u16) = 8 bytes.
-    let mut page_size: usize = 8;
+    // cost of page_id (u32) + next_page_id (u32) + tuple_cnt (u16) + deleted_tuple_cnt (u16)
+    // = 12 bytes, plus the page LSN.
+    let mut page_size: usize = 12 + mem::size_of::<Lsn>();
 
     loop {
         let tuple = create_random_row(&schema, None).to_tuple(&schema).unwrap();
         let tuple_size = tuple.data.len();
 
         // Adding tuple would make page overfull.
-        if page_size + tuple_size + 4 > RUSTY_DB_PAGE_SIZE_BYTES {
+        if page_size + tuple_size + TablePage::SLOT_BYTES > RUSTY_DB_PAGE_PAYLOAD_BYTES {
             assert!(page.get_next_tuple_offset(&tuple).is_none());
             break;
         }
         page.insert_tuple(TupleMetadata::new(false), tuple);
-        // 4 bytes for tuple metadata.
-        page_size += tuple_size + 4;
+        // The tuple's slot.
+        page_size += tuple_size + TablePage::SLOT_BYTES;
     }
 }
 
@@ -62,3 +418,48 @@ pub fn test_iterate_page() {
     let page_guard = page.read().unwrap();
     assert_eq!(iter.count(), page_guard.tuple_count() as usize);
 }
+
+/// Test that any sequence of inserts, deletes and undeletes round-trips through serialization to
+/// an identical page, deleted tuples and the transactions of every tuple included.
+#[test]
+pub fn test_serialize_round_trip() {
+    for seed in 0..64 {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut page = TablePage::builder().page_id(7).next_page_id(8).build();
+        page.set_lsn(seed + 1);
+        for _ in 0..rng.gen_range(0..200) {
+            let slots = page.next_slot_id();
+            let mut meta = TupleMetadata::new(false);
+            meta.set_insert_txn_id(rng.gen_range(1..u64::MAX));
+            if slots > 0 && rng.gen_bool(0.3) {
+                let rid = RecordId::new(7, rng.gen_range(0..slots));
+                meta.set_deleted(rng.gen_bool(0.8));
+                meta.set_delete_txn_id(rng.gen());
+                page.update_tuple_metadata(&meta, &rid).unwrap();
+            } else {
+                let len = rng.gen_range(1..64);
+                let tuple = Tuple::from((0..len).map(|_| rng.gen()).collect::<Vec<u8>>());
+                meta.set_deleted(rng.gen_bool(0.2));
+                page.insert_tuple(meta, tuple);
+            }
+        }
+
+        let copy = TablePage::deserialize(&page.serialize());
+        assert_eq!(page.page_id, copy.page_id);
+        assert_eq!(page.next_page_id, copy.next_page_id);
+        assert_eq!(page.lsn, copy.lsn);
+        assert_eq!(page.tuple_cnt, copy.tuple_cnt);
+        assert_eq!(page.deleted_tuple_cnt, copy.deleted_tuple_cnt);
+        assert_eq!(page.tuple_info, copy.tuple_info);
+        assert_eq!(page.data, copy.data);
+        for slot in 0..page.next_slot_id() {
+            let rid = RecordId::new(7, slot);
+            assert_eq!(page.get_tuple(&rid).ok(), copy.get_tuple(&rid).ok());
+            let (meta, copied) = (page.get_tuple_metadata(&rid), copy.get_tuple_metadata(&rid));
+            let (meta, copied) = (meta.unwrap(), copied.unwrap());
+            assert_ne!(INVALID_TXN_ID, copied.insert_txn_id());
+            assert_eq!(meta.insert_txn_id(), copied.insert_txn_id());
+            assert_eq!(meta.delete_txn_id(), copied.delete_txn_id());
+        }
+    }
+}
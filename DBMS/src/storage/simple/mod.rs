@@ -0,0 +1,8 @@
+mod simple;
+#[cfg(test)]
+mod tests;
+
+pub use simple::{
+    BackgroundTask, IndexScanIterator, ParallelScanIterator, PreparedTransaction, RecoveryStats,
+    ScanIterator, ScanMap, Simple, Snapshot, Transaction, TxnId, INVALID_TXN_ID,
+};
@@ -0,0 +1,2093 @@
+use crate::common::{Error, Result};
+use crate::concurrency::{
+    IsolationLevel, LockManager, TransactionEntry, TransactionManager, TransactionState,
+};
+use crate::config::config::BACKUP_CHUNK_PAGES;
+use crate::storage::backup::{BackupInfo, Manifest, ManifestTable, DATA_FILE};
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::engine::{Engine, VacuumStats};
+use crate::storage::index::bplus_tree::{IndexCheckReport, IndexIterator};
+use crate::storage::page::RecordId;
+use crate::storage::tuple::{Row, Tuple, TupleMetadata};
+use crate::storage::wal::{LogManager, LogRecord, LogRecordBody, Lsn, INVALID_LSN};
+use crate::storage::Key;
+use crate::types::field::Field;
+use crate::types::Table;
+use crate::{errdata, errinput};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::{Range, RangeBounds};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// A transaction id. Ids are assigned in increasing order as transactions begin.
+pub type TxnId = u64;
+
+/// The transaction id of tuples written outside of any transaction, and the
+/// delete id of tuples that have not been deleted.
+pub const INVALID_TXN_ID: TxnId = 0;
+
+/// The record ids of the versions a transaction deleted or replaced, by table.
+pub type WriteSet = BTreeMap<String, BTreeSet<RecordId>>;
+
+/// A multi-version transactional key-value engine. It wraps an
+/// underlying storage engine for raw key-value storage.
+///
+/// Operations of different transactions may interleave, but each one runs
+/// serially under the engine mutex. Transactions are isolated from each other
+/// by versioning: every tuple records the transactions that inserted and
+/// deleted it, and readers only see versions visible to their snapshot.
+///
+/// Transactions run under snapshot isolation by default, see
+/// [`IsolationLevel`] for the others. Writers take an exclusive row lock on
+/// each version before changing it, so concurrent writers of the same version
+/// queue up behind each other. Once the first one commits, the others fail
+/// with [`Error::Serialization`].
+///
+/// Read-only transactions skip the write bookkeeping: they log nothing and
+/// take no locks, and since they never write a version, other transactions
+/// leave them out of their snapshots.
+pub struct Simple<E: Engine> {
+    pub engine: Arc<Mutex<E>>,
+    /// Transaction bookkeeping shared by all transactions.
+    txns: Arc<Mutex<TxnState>>,
+    /// Row locks shared by all transactions.
+    locks: Arc<LockManager>,
+    /// The registry of running transactions.
+    registry: Arc<TransactionManager>,
+    /// The storage engine's write-ahead log.
+    log: Arc<LogManager>,
+    /// Held by a backup while it copies the database. Checkpoints wait for
+    /// it, since they discard log the backup has yet to copy, and so do
+    /// vacuums, which change pages unlogged.
+    backup: Arc<Mutex<()>>,
+}
+
+/// Tracks transaction ids, which transactions are still running, and what
+/// recently committed transactions wrote.
+#[derive(Debug)]
+struct TxnState {
+    /// The id handed to the next transaction to begin.
+    next_id: TxnId,
+    /// Transactions that have begun but not yet committed or rolled back,
+    /// mapped to the oldest transaction id their snapshot can't see.
+    active: BTreeMap<TxnId, TxnId>,
+    /// Read-only transactions that have begun but not yet finished, mapped
+    /// like `active`. They write nothing, so snapshots don't track them, but
+    /// vacuum must keep the versions they can see.
+    readers: BTreeMap<TxnId, TxnId>,
+    /// The LSN of each active transaction's begin record. A checkpoint must
+    /// keep the log from the oldest of them on, for recovery to undo them.
+    begin_lsns: BTreeMap<TxnId, Lsn>,
+    /// Write sets of committed transactions that some active transaction's
+    /// snapshot can't see yet, used for first-committer-wins checks.
+    committed: BTreeMap<TxnId, WriteSet>,
+    /// Transactions prepared for two-phase commit. They stay active until the
+    /// coordinator commits or rolls them back.
+    prepared: BTreeMap<TxnId, Prepared>,
+}
+
+/// What a prepared transaction needs to be committed or rolled back later.
+#[derive(Debug, Default)]
+struct Prepared {
+    /// The coordinator's global id for the transaction.
+    gid: String,
+    /// The versions the transaction deleted or replaced.
+    write_set: WriteSet,
+    /// How to reverse each of its writes, oldest first.
+    undo: Vec<Undo>,
+}
+
+impl Prepared {
+    /// Rebuilds the write set and undo log entries of a logged tuple change.
+    fn restore(&mut self, record: &LogRecord) {
+        match &record.body {
+            LogRecordBody::Insert { table, rid, .. } => self.undo.push(Undo::Insert {
+                table: table.clone(),
+                rid: rid.clone(),
+            }),
+            LogRecordBody::Update {
+                table,
+                rid,
+                new_rid,
+                ..
+            } => {
+                self.restore_delete(table, rid);
+                self.undo.push(Undo::Insert {
+                    table: table.clone(),
+                    rid: new_rid.clone(),
+                });
+            }
+            LogRecordBody::Delete { table, rid, .. } => self.restore_delete(table, rid),
+            _ => {}
+        }
+    }
+
+    /// Rebuilds a delete. Prepare takes over every delete mark on the write
+    /// set, so the mark is the transaction's own.
+    fn restore_delete(&mut self, table: &str, rid: &RecordId) {
+        let rids = self.write_set.entry(table.to_string()).or_default();
+        rids.insert(rid.clone());
+        self.undo.push(Undo::Delete {
+            table: table.to_string(),
+            rid: rid.clone(),
+            marked: true,
+        });
+    }
+}
+
+impl TxnState {
+    /// Takes a snapshot for the given transaction of the transactions that
+    /// have committed so far.
+    fn snapshot(&self, id: TxnId) -> Snapshot {
+        Snapshot {
+            id,
+            next_id: self.next_id,
+            active: self.active.keys().copied().filter(|t| *t != id).collect(),
+        }
+    }
+
+    /// Returns the oldest transaction id some active snapshot can't see.
+    /// Every transaction below it has finished, and is visible to all
+    /// current and future snapshots if it committed.
+    fn horizon(&self) -> TxnId {
+        let horizons = self.active.values().chain(self.readers.values());
+        horizons.min().copied().unwrap_or(self.next_id)
+    }
+
+    /// Returns whether the transaction has committed, or passed its commit
+    /// checks and is about to.
+    fn has_committed(&self, id: TxnId) -> bool {
+        !self.active.contains_key(&id) || self.committed.contains_key(&id)
+    }
+
+    /// Removes the prepared transaction with the given global id.
+    fn take_prepared(&mut self, gid: &str) -> Result<(TxnId, Prepared)> {
+        let id = self.prepared.iter().find(|(_, p)| p.gid == gid);
+        let id = id.map(|(id, _)| *id);
+        match id.and_then(|id| Some((id, self.prepared.remove(&id)?))) {
+            Some(prepared) => Ok(prepared),
+            None => errinput!("prepared transaction {gid} does not exist"),
+        }
+    }
+
+    /// Removes a finished transaction, and forgets commits that are now
+    /// visible to every active transaction.
+    fn finish(&mut self, id: TxnId) {
+        self.active.remove(&id);
+        self.readers.remove(&id);
+        self.begin_lsns.remove(&id);
+        let horizon = self.horizon();
+        self.committed = self.committed.split_off(&horizon);
+    }
+}
+
+impl<E: Engine> Simple<E> {
+    /// Creates a new simple engine with the given storage engine.
+    pub fn new(engine: E) -> Self {
+        Self::new_with_lock_manager(engine, LockManager::default())
+    }
+
+    /// Creates a new simple engine with the given storage engine, whose
+    /// transactions take row locks from the given lock manager.
+    pub fn new_with_lock_manager(engine: E, locks: LockManager) -> Self {
+        let log = engine.log_manager();
+        Self {
+            engine: Arc::new(Mutex::new(engine)),
+            txns: Arc::new(Mutex::new(TxnState {
+                next_id: INVALID_TXN_ID + 1,
+                active: BTreeMap::new(),
+                readers: BTreeMap::new(),
+                begin_lsns: BTreeMap::new(),
+                committed: BTreeMap::new(),
+                prepared: BTreeMap::new(),
+            })),
+            locks: Arc::new(locks),
+            registry: Arc::new(TransactionManager::default()),
+            log,
+            backup: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Returns the lock manager shared by all transactions.
+    pub fn lock_manager(&self) -> Arc<LockManager> {
+        Arc::clone(&self.locks)
+    }
+
+    /// Returns the registry of running transactions.
+    pub fn transaction_manager(&self) -> Arc<TransactionManager> {
+        Arc::clone(&self.registry)
+    }
+
+    /// Begins a new read-write transaction at the default isolation level.
+    pub fn begin(&self) -> Result<Transaction<E>> {
+        self.begin_with(IsolationLevel::default())
+    }
+
+    /// Begins a new read-write transaction at the given isolation level.
+    pub fn begin_with(&self, isolation: IsolationLevel) -> Result<Transaction<E>> {
+        self.begin_txn(isolation, false)
+    }
+
+    /// Begins a new read-only transaction at the default isolation level.
+    pub fn begin_read_only(&self) -> Result<Transaction<E>> {
+        self.begin_read_only_with(IsolationLevel::default())
+    }
+
+    /// Begins a new read-only transaction at the given isolation level. Its
+    /// writes fail with [`Error::ReadOnly`].
+    pub fn begin_read_only_with(&self, isolation: IsolationLevel) -> Result<Transaction<E>> {
+        self.begin_txn(isolation, true)
+    }
+
+    /// Begins a new transaction, registering it as running.
+    fn begin_txn(&self, isolation: IsolationLevel, read_only: bool) -> Result<Transaction<E>> {
+        let mut txns = self.txns.lock()?;
+        let id = txns.next_id;
+        txns.next_id += 1;
+        let snapshot = txns.snapshot(id);
+        let horizon = snapshot.active.first().copied().unwrap_or(id);
+        match read_only {
+            true => txns.readers.insert(id, horizon),
+            false => txns.active.insert(id, horizon),
+        };
+        drop(txns);
+        Transaction::begin(self, snapshot, isolation, read_only)
+    }
+
+    /// Returns the transactions prepared for two-phase commit that are
+    /// waiting for the coordinator's decision, in id order. This includes
+    /// those restored as in doubt by recovery.
+    pub fn list_prepared(&self) -> Result<Vec<PreparedTransaction>> {
+        let txns = self.txns.lock()?;
+        let prepared = txns
+            .prepared
+            .iter()
+            .map(|(id, prepared)| PreparedTransaction {
+                id: *id,
+                gid: prepared.gid.clone(),
+            });
+        Ok(prepared.collect())
+    }
+
+    /// Commits the prepared transaction with the given global id. It passed
+    /// the conflict checks when prepared, so this can't fail with a conflict.
+    pub fn commit_prepared(&self, gid: &str) -> Result<()> {
+        let mut txns = self.txns.lock()?;
+        let (id, prepared) = txns.take_prepared(gid)?;
+        if !prepared.write_set.is_empty() {
+            txns.committed.insert(id, prepared.write_set);
+        }
+        drop(txns);
+        self.log.commit(id)?;
+        self.finish_prepared(id)
+    }
+
+    /// Rolls back the prepared transaction with the given global id,
+    /// reversing all of its writes.
+    pub fn rollback_prepared(&self, gid: &str) -> Result<()> {
+        let mut engine = self.engine.lock()?;
+        let (id, prepared) = self.txns.lock()?.take_prepared(gid)?;
+        for undo in prepared.undo.into_iter().rev() {
+            undo.apply(&mut *engine, id)?;
+        }
+        drop(engine);
+        self.log.append(id, LogRecordBody::Abort)?;
+        self.finish_prepared(id)
+    }
+
+    /// Removes a committed or rolled back prepared transaction from the
+    /// bookkeeping of running transactions, and releases its locks.
+    fn finish_prepared(&self, id: TxnId) -> Result<()> {
+        self.txns.lock()?.finish(id);
+        self.registry.unregister(id)?;
+        self.locks.unlock_all(id)
+    }
+
+    /// Takes a checkpoint: writes every modified page back to storage, then
+    /// logs the active transactions and the pages written. Recovery starts
+    /// from the checkpoint, and the log before the oldest record it may still
+    /// need is discarded. Returns the checkpoint record's LSN.
+    pub fn checkpoint(&self) -> Result<Lsn> {
+        let _backup = self.backup.lock()?;
+        // Holding the engine lock keeps pages from changing until the
+        // checkpoint record is in the log.
+        let mut engine = self.engine.lock()?;
+        self.checkpoint_with(&mut engine)
+    }
+
+    /// Takes a checkpoint, see [`Self::checkpoint`], with the engine lock
+    /// held by the caller.
+    fn checkpoint_with(&self, engine: &mut E) -> Result<Lsn> {
+        let active = self.txns.lock()?.begin_lsns.clone().into_iter().collect();
+        let dirty_pages = engine.checkpoint()?;
+        engine.log_manager().checkpoint(active, dirty_pages)
+    }
+
+    /// Physically removes the versions of the given table, or of every table,
+    /// that no current or future transaction can see: those deleted by a
+    /// transaction that committed below the horizon, and tombstones.
+    ///
+    /// It ends with a checkpoint. Compacting pages isn't logged, and inserts
+    /// may reuse the space it frees, which recovery can only redo on the
+    /// compacted pages.
+    pub fn vacuum(&self, table: Option<&str>) -> Result<VacuumStats> {
+        let _backup = self.backup.lock()?;
+        let mut engine = self.engine.lock()?;
+        let horizon = self.txns.lock()?.horizon();
+        let tables = match table {
+            Some(table) if engine.get_table(table)?.is_none() => {
+                return errinput!("table {table} does not exist")
+            }
+            Some(table) => vec![table.to_string()],
+            None => engine.table_names()?,
+        };
+        // A delete mark below the horizon belongs to a finished transaction,
+        // and since rollbacks clear their marks, to a committed one.
+        let removable = |metadata: &TupleMetadata| {
+            metadata.delete_txn_id() != INVALID_TXN_ID && metadata.delete_txn_id() < horizon
+        };
+        let mut stats = VacuumStats::default();
+        for table in tables {
+            stats += engine.vacuum(&table, &removable)?;
+        }
+        self.checkpoint_with(&mut engine)?;
+        Ok(stats)
+    }
+
+    /// Rewrites the given table, or every table, into as few pages as its
+    /// live versions fit, removing the rest like [`Self::vacuum`] does.
+    ///
+    /// Record ids change, which transactions hold on to in their scans, write
+    /// sets and locks. Rather than keep the old pages until every snapshot
+    /// that may read them is gone, this takes the database for itself: it
+    /// fails if any other transaction is running, and keeps new ones from
+    /// beginning until it is done. It ends with a checkpoint, since the log
+    /// refers to the old record ids, which recovery must not redo.
+    pub fn vacuum_full(&self, table: Option<&str>) -> Result<VacuumStats> {
+        let _backup = self.backup.lock()?;
+        let mut engine = self.engine.lock()?;
+        let txns = self.txns.lock()?;
+        if !txns.active.is_empty() || !txns.readers.is_empty() {
+            return errinput!("VACUUM FULL can't run while other transactions are running");
+        }
+        let tables = match table {
+            Some(table) if engine.get_table(table)?.is_none() => {
+                return errinput!("table {table} does not exist")
+            }
+            Some(table) => vec![table.to_string()],
+            None => engine.table_names()?,
+        };
+        // With no transaction running, every delete mark belongs to a
+        // committed transaction.
+        let removable = |metadata: &TupleMetadata| metadata.delete_txn_id() != INVALID_TXN_ID;
+        let mut stats = VacuumStats::default();
+        for table in tables {
+            stats += engine.vacuum_full(&table, &removable)?;
+        }
+        let dirty_pages = engine.checkpoint()?;
+        engine.log_manager().checkpoint(Vec::new(), dirty_pages)?;
+        Ok(stats)
+    }
+
+    /// Checks the structure of a table's index, see
+    /// [`BPlusTree::verify_with`](crate::storage::index::bplus_tree::BPlusTree::verify_with).
+    /// With `deep` set, also checks that every entry points at a tuple that
+    /// isn't tombstoned and holds the entry's key. Writes wait for the check,
+    /// so it sees the index and the table in step.
+    pub fn check_index(&self, table: &str, index: &str, deep: bool) -> Result<IndexCheckReport> {
+        let mut engine = self.engine.lock()?;
+        let Some(schema) = engine.get_table(table)? else {
+            return errinput!("table {table} does not exist");
+        };
+        let indexes = engine.indexes(table)?;
+        let Some(index) = indexes.iter().find(|i| i.name() == index) else {
+            return errinput!("index {index} does not exist on table {table}");
+        };
+        if !deep {
+            return index.tree().verify();
+        }
+        index.tree().verify_with(|key, rid| {
+            let check_entry = |engine: &mut E| -> Result<Option<String>> {
+                if engine.get_metadata(Key::new(table, rid))?.is_deleted() {
+                    return Ok(Some(format!(
+                        "entry ({key}, {}) points at a tombstone",
+                        rid.to_string()
+                    )));
+                }
+                let row = Row::from_tuple(engine.get(Key::new(table, rid))?, &schema)?;
+                let actual = index.key(&row)?;
+                Ok((actual != *key).then(|| {
+                    format!(
+                        "entry ({key}, {}) points at a tuple with key {actual}",
+                        rid.to_string()
+                    )
+                }))
+            };
+            check_entry(&mut engine).unwrap_or_else(|error| {
+                Some(format!(
+                    "entry ({key}, {}) points at no tuple: {error}",
+                    rid.to_string()
+                ))
+            })
+        })
+    }
+
+    /// Backs the database up into `dir`, which is created if it doesn't
+    /// exist, while transactions keep running. Opening the backup with
+    /// [`Self::restore`] brings the database back to the point the backup
+    /// ends at, see [`BackupInfo::end_lsn`].
+    ///
+    /// The backup starts with a checkpoint, then copies the database file a
+    /// few pages at a time, letting writes through in between. Pages may be
+    /// copied before or after they change, but the log holds every change
+    /// since the checkpoint, so the backup ends by copying it, along with the
+    /// pages allocated in the meantime, the header once more, and the
+    /// catalog. Recovery then redoes what the copied pages missed, and undoes
+    /// what was left uncommitted. Checkpoints and vacuums wait for the backup
+    /// to finish.
+    pub fn backup(&self, dir: &Path) -> Result<BackupInfo> {
+        if Manifest::exists(dir) {
+            return errinput!("{} already holds a backup", dir.display());
+        }
+        let _backup = self.backup.lock()?;
+        fs::create_dir_all(dir)?;
+        let data_path = dir.join(DATA_FILE);
+        let mut data = File::create(&data_path)?;
+        let (disk_manager, checkpoint_lsn, pages) = {
+            let mut engine = self.engine.lock()?;
+            let checkpoint_lsn = self.checkpoint_with(&mut engine)?;
+            let disk_manager = engine.disk_manager();
+            let pages = disk_manager.read()?.num_pages();
+            (disk_manager, checkpoint_lsn, pages)
+        };
+        let copy = |data: &mut File, pages: Range<PageId>| -> Result<()> {
+            for start in pages.clone().step_by(BACKUP_CHUNK_PAGES) {
+                let end = pages.end.min(start + BACKUP_CHUNK_PAGES as PageId);
+                data.write_all(&disk_manager.write()?.read_page_range(start..end)?)?;
+            }
+            Ok(())
+        };
+        copy(&mut data, 0..pages)?;
+
+        // With the engine locked, the pages on disk hold no change the log
+        // doesn't, once it is flushed.
+        let mut engine = self.engine.lock()?;
+        self.log.flush(Lsn::MAX)?;
+        let end_pages = disk_manager.read()?.num_pages();
+        copy(&mut data, pages..end_pages)?;
+        // The header's free pages may have been allocated since it was copied.
+        data.seek(SeekFrom::Start(0))?;
+        copy(&mut data, 0..1)?;
+        data.sync_all()?;
+        let log = disk_manager.write()?.read_log()?;
+        let mut log_path = data_path.into_os_string();
+        log_path.push(".wal");
+        let mut log_file = File::create(log_path)?;
+        log_file.write_all(&log)?;
+        log_file.sync_all()?;
+        let end_lsn = self.log.durable_lsn();
+        self.manifest(&mut engine, checkpoint_lsn, end_lsn)?
+            .write(dir)?;
+        Ok(BackupInfo {
+            checkpoint_lsn,
+            end_lsn,
+            pages: end_pages as u64,
+            log_bytes: log.len() as u64,
+        })
+    }
+
+    /// Restores the backup in `dir`, whose database file and log the
+    /// storage engine must have been opened on: attaches the tables the
+    /// manifest lists, then recovers, see [`Self::recover`]. Must run before
+    /// any transaction begins.
+    pub fn restore(&self, dir: &Path) -> Result<RecoveryStats> {
+        let manifest = Manifest::read(dir)?;
+        {
+            let mut engine = self.engine.lock()?;
+            for table in manifest.tables {
+                engine.attach_table(table.schema, table.page_ids)?;
+            }
+            let mut txns = self.txns.lock()?;
+            txns.next_id = txns.next_id.max(manifest.next_txn_id);
+        }
+        self.recover()
+    }
+
+    /// Takes a checkpoint, then records the catalog in the manifest of the
+    /// restored backup in `dir`, so that restoring it again picks up where
+    /// the database is now.
+    pub fn save_manifest(&self, dir: &Path) -> Result<()> {
+        let _backup = self.backup.lock()?;
+        let mut engine = self.engine.lock()?;
+        let lsn = self.checkpoint_with(&mut engine)?;
+        self.manifest(&mut engine, lsn, self.log.durable_lsn())?
+            .write(dir)
+    }
+
+    /// Describes the database as it is, with the engine lock held by the
+    /// caller.
+    fn manifest(&self, engine: &mut E, checkpoint_lsn: Lsn, end_lsn: Lsn) -> Result<Manifest> {
+        let mut tables = Vec::new();
+        for name in engine.table_names()? {
+            let Some(schema) = engine.get_table(&name)? else {
+                return errdata!("table {name} has no schema");
+            };
+            let page_ids = engine.page_ids(&name)?;
+            tables.push(ManifestTable { schema, page_ids });
+        }
+        Ok(Manifest {
+            checkpoint_lsn,
+            end_lsn,
+            next_txn_id: self.txns.lock()?.next_id,
+            tables,
+        })
+    }
+
+    /// Restores the storage engine to a consistent state after a crash.
+    ///
+    /// Changes logged since the last checkpoint are redone on every page
+    /// whose LSN shows it missed them. The changes of every transaction that
+    /// didn't commit are then undone, and transactions that were still
+    /// running are logged as aborted. Must run before any transaction begins.
+    ///
+    /// Prepared transactions are the exception: they are in doubt until the
+    /// coordinator decides, so their changes stay in place, and they are
+    /// restored as prepared, along with their row locks.
+    pub fn recover(&self) -> Result<RecoveryStats> {
+        let mut engine = self.engine.lock()?;
+        let log = engine.log_manager();
+        let records = log.records()?;
+        let checkpoint = log.last_checkpoint()?.unwrap_or(INVALID_LSN);
+        let last_lsn = records.last().map_or(INVALID_LSN, |r| r.lsn);
+        log.restart(last_lsn.max(checkpoint))?;
+
+        let mut stats = RecoveryStats {
+            redo_from: checkpoint,
+            ..RecoveryStats::default()
+        };
+        let (mut committed, mut aborted) = (BTreeSet::new(), BTreeSet::new());
+        let (mut begin_lsns, mut in_doubt) = (BTreeMap::new(), BTreeMap::new());
+        let mut last_id = INVALID_TXN_ID;
+        for record in &records {
+            last_id = last_id.max(record.txn_id);
+            match &record.body {
+                LogRecordBody::Begin => {
+                    begin_lsns.insert(record.txn_id, record.lsn);
+                }
+                LogRecordBody::Commit => {
+                    committed.insert(record.txn_id);
+                }
+                LogRecordBody::Abort => {
+                    aborted.insert(record.txn_id);
+                }
+                LogRecordBody::Prepare { gid } => {
+                    in_doubt.insert(record.txn_id, gid.clone());
+                }
+                _ => {}
+            };
+            if record.lsn < checkpoint || !Self::is_tuple_change(record) {
+                continue;
+            }
+            stats.replayed += 1;
+            if Self::redo(&mut *engine, record)? {
+                stats.redone += 1;
+            }
+        }
+
+        in_doubt.retain(|id, _| !committed.contains(id) && !aborted.contains(id));
+
+        // Rollbacks aren't logged, so the changes of aborted transactions may
+        // have just been redone too.
+        let decided = |id: &TxnId| committed.contains(id) || in_doubt.contains_key(id);
+        for record in records.iter().rev() {
+            if record.txn_id == INVALID_TXN_ID || decided(&record.txn_id) {
+                continue;
+            }
+            if Self::undo(&mut *engine, record)? {
+                stats.undone += 1;
+            }
+        }
+        let losers: BTreeSet<TxnId> = records
+            .iter()
+            .map(|r| r.txn_id)
+            .filter(|id| *id != INVALID_TXN_ID)
+            .filter(|id| !decided(id) && !aborted.contains(id))
+            .collect();
+        for id in &losers {
+            log.append(*id, LogRecordBody::Abort)?;
+            self.locks.unlock_all(*id)?;
+        }
+        log.flush(Lsn::MAX)?;
+        stats.losers = losers.len();
+
+        let mut prepared: BTreeMap<TxnId, Prepared> = BTreeMap::new();
+        for record in records.iter().filter(|r| in_doubt.contains_key(&r.txn_id)) {
+            let txn = prepared.entry(record.txn_id).or_default();
+            txn.gid.clone_from(&in_doubt[&record.txn_id]);
+            txn.restore(record);
+        }
+        let mut txns = self.txns.lock()?;
+        txns.next_id = txns.next_id.max(last_id + 1);
+        txns.active.clear();
+        txns.readers.clear();
+        txns.begin_lsns.clear();
+        txns.committed.clear();
+        txns.prepared.clear();
+        self.registry.clear()?;
+        stats.prepared = prepared.len();
+        for (id, txn) in prepared {
+            for rid in txn.write_set.values().flatten() {
+                self.locks.lock_exclusive(id, rid)?;
+            }
+            // Every transaction that was running alongside it is gone.
+            txns.active.insert(id, id);
+            if let Some(lsn) = begin_lsns.get(&id) {
+                txns.begin_lsns.insert(id, *lsn);
+            }
+            let entry = self
+                .registry
+                .register(id, IsolationLevel::default(), false)?;
+            entry.set_state(TransactionState::Prepared)?;
+            txns.prepared.insert(id, txn);
+        }
+        Ok(stats)
+    }
+
+    /// Returns whether the record describes a change to tuples.
+    fn is_tuple_change(record: &LogRecord) -> bool {
+        matches!(
+            record.body,
+            LogRecordBody::Insert { .. }
+                | LogRecordBody::Update { .. }
+                | LogRecordBody::Delete { .. }
+        )
+    }
+
+    /// Reapplies a logged tuple change to the pages that don't have it yet.
+    /// Returns whether any page did.
+    fn redo(engine: &mut E, record: &LogRecord) -> Result<bool> {
+        let (lsn, txn_id) = (record.lsn, record.txn_id);
+        match &record.body {
+            LogRecordBody::Insert { table, rid, after } => {
+                let key = Key::new(table, rid);
+                if engine.get_lsn(key)? >= lsn {
+                    return Ok(false);
+                }
+                Self::redo_insert(engine, key, after.clone(), txn_id)?;
+                engine.set_lsn(key, lsn)?;
+            }
+            LogRecordBody::Update {
+                table,
+                rid,
+                new_rid,
+                after,
+                ..
+            } => {
+                // Both versions may live on the same page, so check its LSN
+                // before stamping it.
+                let (old, new) = (Key::new(table, rid), Key::new(table, new_rid));
+                let old_missed = engine.get_lsn(old)? < lsn;
+                let new_missed = engine.get_lsn(new)? < lsn;
+                if old_missed {
+                    Self::redo_delete(engine, old, txn_id)?;
+                    engine.set_lsn(old, lsn)?;
+                }
+                if new_missed {
+                    Self::redo_insert(engine, new, after.clone(), txn_id)?;
+                    engine.set_lsn(new, lsn)?;
+                }
+                return Ok(old_missed || new_missed);
+            }
+            LogRecordBody::Delete { table, rid, .. } => {
+                let key = Key::new(table, rid);
+                if engine.get_lsn(key)? >= lsn {
+                    return Ok(false);
+                }
+                Self::redo_delete(engine, key, txn_id)?;
+                engine.set_lsn(key, lsn)?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Reinserts a logged version at its original record id.
+    fn redo_insert(engine: &mut E, key: Key, value: Tuple, txn_id: TxnId) -> Result<()> {
+        engine.insert_at(key, value)?;
+        let mut metadata = engine.get_metadata(key)?;
+        metadata.set_insert_txn_id(txn_id);
+        engine.set_metadata(key, metadata)
+    }
+
+    /// Puts a logged delete mark back on a version.
+    fn redo_delete(engine: &mut E, key: Key, txn_id: TxnId) -> Result<()> {
+        let mut metadata = engine.get_metadata(key)?;
+        metadata.set_delete_txn_id(txn_id);
+        engine.set_metadata(key, metadata)
+    }
+
+    /// Reverses a logged tuple change, like a rollback would. Returns whether
+    /// the record describes a tuple change.
+    fn undo(engine: &mut E, record: &LogRecord) -> Result<bool> {
+        let clear_mark = |engine: &mut E, key: Key| -> Result<()> {
+            let mut metadata = engine.get_metadata(key)?;
+            if metadata.delete_txn_id() == record.txn_id {
+                metadata.set_delete_txn_id(INVALID_TXN_ID);
+                engine.set_metadata(key, metadata)?;
+            }
+            Ok(())
+        };
+        match &record.body {
+            LogRecordBody::Insert { table, rid, .. } => engine.delete(Key::new(table, rid))?,
+            LogRecordBody::Update {
+                table,
+                rid,
+                new_rid,
+                ..
+            } => {
+                engine.delete(Key::new(table, new_rid))?;
+                clear_mark(engine, Key::new(table, rid))?;
+            }
+            LogRecordBody::Delete { table, rid, .. } => clear_mark(engine, Key::new(table, rid))?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl<E: Engine + 'static> Simple<E> {
+    /// Starts taking a checkpoint every `interval` in a background thread,
+    /// which runs until the returned handle is stopped or dropped.
+    pub fn start_checkpointer(&self, interval: Duration) -> BackgroundTask {
+        let simple = Simple::from(self);
+        BackgroundTask::spawn(interval, move || simple.checkpoint().map(|_| ()))
+    }
+
+    /// Starts vacuuming every table every `interval` in a background thread,
+    /// which runs until the returned handle is stopped or dropped.
+    pub fn start_vacuum(&self, interval: Duration) -> BackgroundTask {
+        let simple = Simple::from(self);
+        BackgroundTask::spawn(interval, move || simple.vacuum(None).map(|_| ()))
+    }
+}
+
+/// What [`Simple::recover`] found in the log, and did about it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryStats {
+    /// The LSN of the checkpoint replay started from, or [`INVALID_LSN`] if
+    /// there was none and the whole log was replayed.
+    pub redo_from: Lsn,
+    /// Tuple changes logged from `redo_from` on.
+    pub replayed: usize,
+    /// Of those, the ones some page had missed and were redone.
+    pub redone: usize,
+    /// Tuple changes of uncommitted transactions that were undone.
+    pub undone: usize,
+    /// Transactions that were still running at the time of the crash.
+    pub losers: usize,
+    /// Prepared transactions restored in doubt, see [`Simple::list_prepared`].
+    pub prepared: usize,
+}
+
+/// A transaction prepared for two-phase commit, as listed by
+/// [`Simple::list_prepared`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreparedTransaction {
+    pub id: TxnId,
+    /// The coordinator's global id for the transaction.
+    pub gid: String,
+}
+
+/// A handle to a background thread started by [`Simple::start_checkpointer`]
+/// or [`Simple::start_vacuum`]. Dropping it stops the thread.
+pub struct BackgroundTask {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl BackgroundTask {
+    /// Runs `task` every `interval` until stopped, or until it fails.
+    fn spawn(interval: Duration, task: impl Fn() -> Result<()> + Send + 'static) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => task()?,
+                _ => return Ok(()),
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the thread, returning the error that stopped it early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        drop(self.stop.take());
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(Error::IO("background thread panicked".to_string()))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BackgroundTask {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+impl<E: Engine> From<&Simple<E>> for Simple<E> {
+    /// Creates a new simple engine that shares the storage engine
+    /// of the given pre-existing simple engine.
+    fn from(simple: &Simple<E>) -> Self {
+        Self {
+            engine: Arc::clone(&simple.engine),
+            txns: Arc::clone(&simple.txns),
+            locks: Arc::clone(&simple.locks),
+            registry: Arc::clone(&simple.registry),
+            log: Arc::clone(&simple.log),
+            backup: Arc::clone(&simple.backup),
+        }
+    }
+}
+
+/// The set of transactions whose writes a transaction can see: itself, and
+/// every transaction that committed before the snapshot was taken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// The id of the transaction the snapshot belongs to.
+    pub id: TxnId,
+    /// Transactions with this id or higher began after the snapshot was taken.
+    pub next_id: TxnId,
+    /// Transactions that were still running when the snapshot was taken.
+    pub active: BTreeSet<TxnId>,
+}
+
+impl Snapshot {
+    /// Returns whether writes made by `txn_id` are visible to the snapshot.
+    /// Rolled-back transactions undo their writes before they leave the
+    /// active set, so any finished transaction seen here has committed.
+    pub fn is_visible(&self, txn_id: TxnId) -> bool {
+        txn_id == self.id || (txn_id < self.next_id && !self.active.contains(&txn_id))
+    }
+
+    /// Returns whether a tuple version with the given metadata is visible:
+    /// its insert is visible and its delete, if any, is not.
+    pub fn is_version_visible(&self, metadata: &TupleMetadata) -> bool {
+        let deleted =
+            metadata.delete_txn_id() != INVALID_TXN_ID && self.is_visible(metadata.delete_txn_id());
+        !metadata.is_deleted() && self.is_visible(metadata.insert_txn_id()) && !deleted
+    }
+}
+
+/// A simple transaction
+///
+/// Every write is recorded in the engine's write-ahead log before the page it
+/// touches is modified, and the page is then stamped with the record's LSN.
+/// Writes are also pushed onto an in-memory undo log, which rollback replays
+/// in reverse.
+///
+/// Writes never modify a version in place: inserts add a version tagged with
+/// the transaction's id, deletes tag the existing version with it, and updates
+/// do both.
+///
+/// Deletes and updates take an exclusive lock on the version first. New
+/// versions are invisible to everyone else until commit, so inserts need no
+/// lock. All locks are held until the transaction commits or rolls back.
+///
+/// Writes to a table with unique indexes check that no other live version
+/// holds the same key, and commit checks again, since a concurrent writer of
+/// the same key may have committed in between. Of two such writers, the one
+/// to commit second fails with [`Error::Constraint`].
+///
+/// The transaction reports its progress to the [`TransactionManager`]. Once
+/// terminated there, its next operation rolls it back and fails with
+/// [`Error::Cancelled`].
+pub struct Transaction<E: Engine> {
+    /// The underlying storage engine, shared by all transactions
+    engine: Arc<Mutex<E>>,
+    /// Transaction bookkeeping shared by all transactions.
+    txns: Arc<Mutex<TxnState>>,
+    /// Row locks shared by all transactions.
+    locks: Arc<LockManager>,
+    /// The transaction's isolation level.
+    isolation: IsolationLevel,
+    /// Whether the transaction is read-only, and skips the write bookkeeping.
+    read_only: bool,
+    /// The write-ahead log shared by all transactions.
+    log: Arc<LogManager>,
+    /// The transaction's id, recorded in each of its log records.
+    id: TxnId,
+    /// The versions this transaction can see. Replaced at the start of each
+    /// statement under read committed.
+    snapshot: Mutex<Arc<Snapshot>>,
+    /// The versions this transaction deleted or replaced. Its own writes are
+    /// invisible to it even when another transaction's delete mark is on them.
+    write_set: Arc<Mutex<WriteSet>>,
+    /// How to reverse each write made so far, oldest first.
+    undo: Mutex<Vec<Undo>>,
+    /// The registry of running transactions.
+    registry: Arc<TransactionManager>,
+    /// The transaction's entry in the registry.
+    entry: Arc<TransactionEntry>,
+    /// Whether the transaction was rolled back after being terminated.
+    cancelled: AtomicBool,
+}
+
+/// An entry in a transaction's undo log, recording how to reverse one write.
+#[derive(Clone, Debug, PartialEq)]
+enum Undo {
+    /// Tombstones a version the transaction inserted.
+    Insert { table: String, rid: RecordId },
+    /// Removes a version from the write set and, if the transaction put its
+    /// delete mark on it, clears the mark.
+    Delete {
+        table: String,
+        rid: RecordId,
+        marked: bool,
+    },
+}
+
+impl Undo {
+    /// Reverses the write of the given transaction on the given engine.
+    fn apply<E: Engine>(self, engine: &mut E, txn_id: TxnId) -> Result<()> {
+        match self {
+            Undo::Insert { table, rid } => engine.delete(Key::new(&table, &rid)),
+            Undo::Delete { table, rid, marked } => {
+                let key = Key::new(&table, &rid);
+                let mut metadata = engine.get_metadata(key)?;
+                if marked && metadata.delete_txn_id() == txn_id {
+                    metadata.set_delete_txn_id(INVALID_TXN_ID);
+                    engine.set_metadata(key, metadata)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<E: Engine> Transaction<E> {
+    /// Begins a new transaction of the given simple engine. Read-only
+    /// transactions have nothing to recover, so they aren't logged.
+    fn begin(
+        simple: &Simple<E>,
+        snapshot: Snapshot,
+        isolation: IsolationLevel,
+        read_only: bool,
+    ) -> Result<Self> {
+        if !read_only {
+            let session = simple.engine.lock()?;
+            let lsn = simple.log.append(snapshot.id, LogRecordBody::Begin)?;
+            simple.txns.lock()?.begin_lsns.insert(snapshot.id, lsn);
+            drop(session);
+        }
+        let entry = simple
+            .registry
+            .register(snapshot.id, isolation, read_only)?;
+
+        Ok(Self {
+            engine: Arc::clone(&simple.engine),
+            txns: Arc::clone(&simple.txns),
+            locks: Arc::clone(&simple.locks),
+            isolation,
+            read_only,
+            log: Arc::clone(&simple.log),
+            id: snapshot.id,
+            snapshot: Mutex::new(Arc::new(snapshot)),
+            write_set: Arc::new(Mutex::new(WriteSet::new())),
+            undo: Mutex::new(Vec::new()),
+            registry: Arc::clone(&simple.registry),
+            entry,
+            cancelled: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the transaction's id.
+    pub fn id(&self) -> TxnId {
+        self.id
+    }
+
+    /// Returns the transaction's isolation level.
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    /// Returns whether the transaction is read-only.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Errors with [`Error::ReadOnly`] if the transaction is read-only.
+    fn check_writable(&self) -> Result<()> {
+        match self.read_only {
+            true => Err(Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns whether reads take shared locks on the rows they return.
+    fn takes_read_locks(&self) -> bool {
+        !self.read_only && self.isolation.takes_read_locks()
+    }
+
+    /// Returns the transaction's current snapshot.
+    pub fn snapshot(&self) -> Result<Arc<Snapshot>> {
+        Ok(Arc::clone(&*self.snapshot.lock()?))
+    }
+
+    /// Marks the start of a statement. Under read committed, this takes a new
+    /// snapshot, so the statement sees everything committed before it.
+    pub fn start_statement(&self) -> Result<()> {
+        self.check_terminated()?;
+        if !self.isolation.snapshot_per_statement() {
+            return Ok(());
+        }
+        let snapshot = self.txns.lock()?.snapshot(self.id);
+        *self.snapshot.lock()? = Arc::new(snapshot);
+        Ok(())
+    }
+
+    /// Commits the transaction, appending a commit record that is made
+    /// durable according to the log's sync policy.
+    ///
+    /// If a transaction that committed after this one's snapshot wrote any of
+    /// the same versions, this one is rolled back instead and
+    /// [`Error::Serialization`] is returned, so the client can retry.
+    pub fn commit(self) -> Result<()> {
+        self.check_terminated()?;
+        self.entry.set_state(TransactionState::Committing)?;
+        if self.read_only {
+            return self.finish();
+        }
+        let write_set = std::mem::take(&mut *self.write_set.lock()?);
+        let wrote = !self.undo.lock()?.is_empty();
+        if wrote {
+            let mut engine = self.engine.lock()?;
+            let mut txns = self.txns.lock()?;
+            if let Err(err) = self.validate(&mut *engine, &txns, &write_set) {
+                drop(txns);
+                drop(engine);
+                *self.write_set.lock()? = write_set;
+                self.rollback()?;
+                return Err(err);
+            }
+            txns.committed.insert(self.id, write_set);
+        }
+        self.log.commit(self.id)?;
+        self.finish()
+    }
+
+    /// Prepares the transaction for two-phase commit under the given global
+    /// id. Once this returns, the transaction will commit if the coordinator
+    /// asks it to, even across a crash: it has passed its conflict checks,
+    /// its changes and the prepare are durable, and it keeps its row locks.
+    /// The coordinator finishes it with [`Simple::commit_prepared`] or
+    /// [`Simple::rollback_prepared`].
+    ///
+    /// If the transaction can't commit, it is rolled back instead.
+    pub fn prepare(self, gid: &str) -> Result<()> {
+        self.check_terminated()?;
+        if self.read_only {
+            self.rollback()?;
+            return Err(Error::ReadOnly);
+        }
+        let write_set = std::mem::take(&mut *self.write_set.lock()?);
+        let mut engine = self.engine.lock()?;
+        let mut txns = self.txns.lock()?;
+        let result = match txns.prepared.values().any(|p| p.gid == gid) {
+            true => errinput!("prepared transaction {gid} already exists"),
+            false => self.validate(&mut *engine, &txns, &write_set),
+        };
+        if let Err(err) = result {
+            drop(txns);
+            drop(engine);
+            *self.write_set.lock()? = write_set;
+            self.rollback()?;
+            return Err(err);
+        }
+        let lsn = self.log.append(
+            self.id,
+            LogRecordBody::Prepare {
+                gid: gid.to_string(),
+            },
+        )?;
+        let prepared = Prepared {
+            gid: gid.to_string(),
+            write_set,
+            undo: std::mem::take(&mut *self.undo.lock()?),
+        };
+        txns.prepared.insert(self.id, prepared);
+        drop(txns);
+        drop(engine);
+        self.entry.set_state(TransactionState::Prepared)?;
+        self.log.flush(lsn)
+    }
+
+    /// Checks that no transaction this one can't see has committed a write to
+    /// a version in its write set, returning [`Error::Serialization`] if one
+    /// has, and that the versions it inserted don't duplicate a key of a
+    /// unique index, returning [`Error::Constraint`] if one does. Otherwise
+    /// takes over the delete marks of the write set.
+    fn validate(&self, engine: &mut E, txns: &TxnState, write_set: &WriteSet) -> Result<()> {
+        let snapshot = self.snapshot()?;
+        let conflict = txns
+            .committed
+            .iter()
+            .filter(|(id, _)| !snapshot.is_visible(**id))
+            .any(|(_, other)| Self::overlaps(write_set, other));
+        if conflict {
+            return Err(Error::Serialization);
+        }
+        for undo in self.undo.lock()?.iter() {
+            let Undo::Insert { table, rid } = undo else {
+                continue;
+            };
+            if write_set.get(table).is_some_and(|rids| rids.contains(rid)) {
+                continue;
+            }
+            let value = engine.get(Key::new(table, rid))?;
+            self.check_unique(engine, txns, write_set, table, &value, Some(rid))?;
+        }
+        // Take over delete marks still held by concurrent writers of the
+        // same versions. They will fail their own check when committing.
+        for (table, rids) in write_set {
+            for rid in rids {
+                let key = Key::new(table, rid);
+                let mut metadata = engine.get_metadata(key)?;
+                if metadata.delete_txn_id() != self.id {
+                    metadata.set_delete_txn_id(self.id);
+                    engine.set_metadata(key, metadata)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether two write sets share a version.
+    fn overlaps(a: &WriteSet, b: &WriteSet) -> bool {
+        a.iter()
+            .any(|(table, rids)| b.get(table).is_some_and(|other| !rids.is_disjoint(other)))
+    }
+
+    /// Rolls back the transaction, reversing all of its writes in LIFO order.
+    /// A no-op if termination already rolled it back.
+    pub fn rollback(self) -> Result<()> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        self.abort()
+    }
+
+    /// Reverses all of the transaction's writes and removes it from the
+    /// bookkeeping of running transactions.
+    fn abort(&self) -> Result<()> {
+        self.entry.set_state(TransactionState::Aborting)?;
+        if !self.read_only {
+            self.rollback_to(0)?;
+            self.log.append(self.id, LogRecordBody::Abort)?;
+        }
+        self.finish()
+    }
+
+    /// Removes the committed or rolled back transaction from the bookkeeping
+    /// of running transactions, and releases its locks.
+    fn finish(&self) -> Result<()> {
+        self.txns.lock()?.finish(self.id);
+        self.registry.unregister(self.id)?;
+        match self.read_only {
+            true => Ok(()),
+            false => self.locks.unlock_all(self.id),
+        }
+    }
+
+    /// Errors with [`Error::Cancelled`] if the transaction has been
+    /// terminated, rolling it back the first time.
+    fn check_terminated(&self) -> Result<()> {
+        if !self.entry.is_terminated() {
+            return Ok(());
+        }
+        if !self.cancelled.swap(true, Ordering::AcqRel) {
+            self.abort()?;
+        }
+        Err(Error::Cancelled)
+    }
+
+    /// Returns a savepoint marking the transaction's writes so far. Passing it to
+    /// [`Self::rollback_to`] reverses only the writes made after this call.
+    pub fn savepoint(&self) -> Result<usize> {
+        Ok(self.undo.lock()?.len())
+    }
+
+    /// Reverses, in LIFO order, every write made since the given savepoint.
+    /// Used for statement-level atomicity as well as full rollbacks.
+    pub fn rollback_to(&self, savepoint: usize) -> Result<()> {
+        let mut undo = self.undo.lock()?;
+        let mut engine = self.engine.lock()?;
+        while undo.len() > savepoint {
+            self.apply_undo(&mut *engine, undo.pop().unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Reverses a single write on the given engine.
+    fn apply_undo(&self, engine: &mut E, undo: Undo) -> Result<()> {
+        if let Undo::Delete { table, rid, .. } = &undo {
+            if let Some(rids) = self.write_set.lock()?.get_mut(table) {
+                rids.remove(rid);
+            }
+        }
+        undo.apply(engine, self.id)
+    }
+
+    /// Returns whether the version at `key` is visible to the transaction.
+    fn is_visible(&self, key: Key, metadata: &TupleMetadata) -> Result<bool> {
+        if self.read_only {
+            return Ok(self.snapshot()?.is_version_visible(metadata));
+        }
+        let written = self
+            .write_set
+            .lock()?
+            .get(key.table_name)
+            .is_some_and(|rids| rids.contains(key.record_id));
+        Ok(!written && self.snapshot()?.is_version_visible(metadata))
+    }
+
+    /// Creates a table.
+    pub fn create_table(&self, table: Table) -> Result<()> {
+        self.check_writable()?;
+        let mut engine = self.engine.lock()?;
+        engine.create_table(table)
+    }
+
+    /// Deletes a table.
+    pub fn delete_table(&self, table_name: &str) -> Result<bool> {
+        self.check_writable()?;
+        let mut engine = self.engine.lock()?;
+        engine.delete_table(table_name)
+    }
+
+    /// Fetches a table
+    pub fn fetch_table(&self, table_name: &str) -> Result<Option<Table>> {
+        let mut engine = self.engine.lock()?;
+        engine.get_table(table_name)
+    }
+
+    /// Fetches every table, ordered by name.
+    pub fn list_tables(&self) -> Result<Vec<Table>> {
+        let mut engine = self.engine.lock()?;
+        let mut tables = Vec::new();
+        for name in engine.table_names()? {
+            tables.extend(engine.get_table(&name)?);
+        }
+        Ok(tables)
+    }
+
+    /// Deletes a key by marking its version as deleted by this transaction.
+    pub fn delete(&self, key: Key) -> Result<()> {
+        self.check_terminated()?;
+        self.check_writable()?;
+        self.locks.lock_exclusive(self.id, key.record_id)?;
+        let mut engine = self.engine.lock()?;
+        let (before, metadata) = self.get_writable(&mut *engine, key)?;
+        let lsn = self.log.append(
+            self.id,
+            LogRecordBody::Delete {
+                table: key.table_name.to_string(),
+                rid: key.record_id.clone(),
+                before,
+            },
+        )?;
+        self.mark_deleted(&mut *engine, key, metadata)?;
+        engine.set_lsn(key, lsn)?;
+        self.entry.record_writes(1);
+        Ok(())
+    }
+
+    /// Fetches a key's value; errors if no version visible to the transaction exists.
+    pub fn get(&self, key: Key) -> Result<Tuple> {
+        self.check_terminated()?;
+        if self.takes_read_locks() {
+            self.locks.lock_shared(self.id, key.record_id)?;
+        }
+        let mut engine = self.engine.lock()?;
+        let metadata = engine.get_metadata(key)?;
+        if !self.is_visible(key, &metadata)? {
+            return errinput!("no visible tuple at {}", key.record_id.to_string());
+        }
+        let tuple = engine.get(key)?;
+        self.entry.record_reads(1);
+        Ok(tuple)
+    }
+
+    /// Inserts a tuple into the table with the given `table_name`.
+    /// Returns the record id corresponding to the inserted tuple.
+    pub fn insert(&self, table_name: &str, value: Tuple) -> Result<RecordId> {
+        self.check_terminated()?;
+        self.check_writable()?;
+        let mut engine = self.engine.lock()?;
+        let rid = engine.next_record_id(table_name, &value)?;
+        self.check_unique_write(&mut *engine, table_name, &value, None)?;
+        let lsn = self.log.append(
+            self.id,
+            LogRecordBody::Insert {
+                table: table_name.to_string(),
+                rid,
+                after: value.clone(),
+            },
+        )?;
+        let rid = self.insert_version(&mut *engine, table_name, value)?;
+        engine.set_lsn(Key::new(table_name, &rid), lsn)?;
+        self.entry.record_writes(1);
+        Ok(rid)
+    }
+
+    /// Updates a key's value by writing a new version and marking the old one
+    /// as deleted. Returns the record id of the new version.
+    pub fn update(&self, key: Key, value: Tuple) -> Result<RecordId> {
+        self.check_terminated()?;
+        self.check_writable()?;
+        self.locks.lock_exclusive(self.id, key.record_id)?;
+        let mut engine = self.engine.lock()?;
+        let (before, metadata) = self.get_writable(&mut *engine, key)?;
+        let new_rid = engine.next_record_id(key.table_name, &value)?;
+        self.check_unique_write(&mut *engine, key.table_name, &value, Some(key.record_id))?;
+        let lsn = self.log.append(
+            self.id,
+            LogRecordBody::Update {
+                table: key.table_name.to_string(),
+                rid: key.record_id.clone(),
+                new_rid,
+                before,
+                after: value.clone(),
+            },
+        )?;
+        self.mark_deleted(&mut *engine, key, metadata)?;
+        engine.set_lsn(key, lsn)?;
+        let new_rid = self.insert_version(&mut *engine, key.table_name, value)?;
+        engine.set_lsn(Key::new(key.table_name, &new_rid), lsn)?;
+        self.entry.record_writes(1);
+        Ok(new_rid)
+    }
+
+    /// Fetches the version at `key` for a write, erroring if the transaction
+    /// can't see it or a transaction that committed after its snapshot has
+    /// already deleted or replaced it.
+    fn get_writable(&self, engine: &mut E, key: Key) -> Result<(Tuple, TupleMetadata)> {
+        let metadata = engine.get_metadata(key)?;
+        if !self.is_visible(key, &metadata)? {
+            return errinput!("no visible tuple at {}", key.record_id.to_string());
+        }
+        // A visible version can only carry another transaction's delete mark
+        // if that transaction is still running, or committed after our
+        // snapshot was taken. In the latter case it has already won.
+        let deleter = metadata.delete_txn_id();
+        if deleter != INVALID_TXN_ID && !self.txns.lock()?.active.contains_key(&deleter) {
+            return Err(Error::Serialization);
+        }
+        Ok((engine.get(key)?, metadata))
+    }
+
+    /// Adds the version at `key` to the write set, and tags it as deleted by
+    /// this transaction unless a concurrent writer already has. Which of the
+    /// two actually deletes it is settled when the first of them commits.
+    fn mark_deleted(&self, engine: &mut E, key: Key, mut metadata: TupleMetadata) -> Result<()> {
+        let marked = metadata.delete_txn_id() == INVALID_TXN_ID;
+        if marked {
+            metadata.set_delete_txn_id(self.id);
+            engine.set_metadata(key, metadata)?;
+        }
+        self.write_set
+            .lock()?
+            .entry(key.table_name.to_string())
+            .or_default()
+            .insert(key.record_id.clone());
+        self.undo.lock()?.push(Undo::Delete {
+            table: key.table_name.to_string(),
+            rid: key.record_id.clone(),
+            marked,
+        });
+        Ok(())
+    }
+
+    /// Checks a version about to be written against the table's unique
+    /// indexes, see [`Self::check_unique`].
+    fn check_unique_write(
+        &self,
+        engine: &mut E,
+        table_name: &str,
+        value: &Tuple,
+        skip: Option<&RecordId>,
+    ) -> Result<()> {
+        let txns = self.txns.lock()?;
+        let write_set = self.write_set.lock()?;
+        self.check_unique(engine, &txns, &write_set, table_name, value, skip)
+    }
+
+    /// Errors with [`Error::Constraint`] if a version other than `skip` holds
+    /// the key `value` has in one of the table's unique indexes. NULL keys
+    /// never conflict.
+    fn check_unique(
+        &self,
+        engine: &mut E,
+        txns: &TxnState,
+        write_set: &WriteSet,
+        table_name: &str,
+        value: &Tuple,
+        skip: Option<&RecordId>,
+    ) -> Result<()> {
+        let indexes = engine.indexes(table_name)?;
+        let mut unique = indexes.iter().filter(|index| index.is_unique()).peekable();
+        if unique.peek().is_none() {
+            return Ok(());
+        }
+        let Some(schema) = engine.get_table(table_name)? else {
+            return errinput!("table {table_name} does not exist");
+        };
+        let row = Row::from_tuple(value.clone(), &schema)?;
+        for index in unique {
+            let key = row.get_field(index.column())?;
+            if key == Field::Null {
+                continue;
+            }
+            for rid in index.scan(key.clone()..=key.clone(), false) {
+                let rid = rid?;
+                if Some(&rid) == skip {
+                    continue;
+                }
+                let metadata = engine.get_metadata(Key::new(table_name, &rid))?;
+                if self.holds_key(txns, write_set, table_name, &rid, &metadata) {
+                    return Err(Error::Constraint(format!(
+                        "duplicate key value violates unique index {}: {key}",
+                        index.name()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the version at `rid` holds its keys in the table's
+    /// unique indexes. Unlike visibility, this goes by the latest state rather
+    /// than the snapshot: the version must have been inserted by this
+    /// transaction or one that committed or prepared, and not deleted by this
+    /// transaction or one that committed. Writes of transactions still running
+    /// may yet be rolled back, so they are left to the commit checks.
+    fn holds_key(
+        &self,
+        txns: &TxnState,
+        write_set: &WriteSet,
+        table_name: &str,
+        rid: &RecordId,
+        metadata: &TupleMetadata,
+    ) -> bool {
+        let inserter = metadata.insert_txn_id();
+        let inserted = inserter == self.id
+            || txns.has_committed(inserter)
+            || txns.prepared.contains_key(&inserter);
+        let deleter = metadata.delete_txn_id();
+        let deleted = write_set
+            .get(table_name)
+            .is_some_and(|rids| rids.contains(rid))
+            || (deleter != INVALID_TXN_ID && txns.has_committed(deleter));
+        !metadata.is_deleted() && inserted && !deleted
+    }
+
+    /// Inserts a new version tagged with this transaction's id.
+    fn insert_version(&self, engine: &mut E, table_name: &str, value: Tuple) -> Result<RecordId> {
+        let rid = engine.insert(table_name, value)?;
+        let key = Key::new(table_name, &rid);
+        let mut metadata = engine.get_metadata(key)?;
+        metadata.set_insert_txn_id(self.id);
+        engine.set_metadata(key, metadata)?;
+        self.undo.lock()?.push(Undo::Insert {
+            table: table_name.to_string(),
+            rid: rid.clone(),
+        });
+        Ok(rid)
+    }
+
+    /// Returns an iterator over the key/value items of the table. If the
+    /// transaction is terminated midway, the iterator fails with
+    /// [`Error::Cancelled`], and the transaction's next operation rolls it
+    /// back.
+    pub fn scan(&self, table: &str) -> Result<ScanIterator<E>> {
+        self.scan_in(table, false)
+    }
+
+    /// Like [`Self::scan`], but iterates over the items in reverse order,
+    /// from the end of the table.
+    pub fn scan_rev(&self, table: &str) -> Result<ScanIterator<E>> {
+        self.scan_in(table, true)
+    }
+
+    fn scan_in(&self, table: &str, reverse: bool) -> Result<ScanIterator<E>> {
+        self.check_terminated()?;
+        let read_locks = self.takes_read_locks();
+        Ok(ScanIterator::new(
+            Arc::clone(&self.engine),
+            self.snapshot()?,
+            Arc::clone(&self.write_set),
+            read_locks.then(|| (Arc::clone(&self.locks), self.id)),
+            Arc::clone(&self.entry),
+            table,
+            reverse,
+        ))
+    }
+
+    /// Returns an iterator over the key/value items of the table whose value
+    /// in the given column lies in `range`, in the order of the column's
+    /// index, or in reverse if `reverse` is set. Errors if the column isn't
+    /// indexed.
+    pub fn index_scan<R: RangeBounds<Field>>(
+        &self,
+        table: &str,
+        column: usize,
+        range: R,
+        reverse: bool,
+    ) -> Result<IndexScanIterator<E>> {
+        self.check_terminated()?;
+        let indexes = self.engine.lock()?.indexes(table)?;
+        let Some(index) = indexes.into_iter().find(|index| index.column() == column) else {
+            return errinput!("column {column} of table {table} is not indexed");
+        };
+        let read_locks = self.takes_read_locks();
+        Ok(IndexScanIterator {
+            engine: Arc::clone(&self.engine),
+            snapshot: self.snapshot()?,
+            write_set: Arc::clone(&self.write_set),
+            read_locks: read_locks.then(|| (Arc::clone(&self.locks), self.id)),
+            entry: Arc::clone(&self.entry),
+            rids: index.scan(range, reverse),
+            table: table.to_string(),
+        })
+    }
+
+    /// Returns the number of rows of the table visible to the transaction,
+    /// i.e. the number of items [`Self::scan`] would return, counted from the
+    /// versions' metadata without reading their tuples. The tuple counts in
+    /// the page headers can't be used as they are, since they include versions
+    /// the snapshot can't see: ones inserted by concurrent transactions, and
+    /// ones deleted but not vacuumed yet.
+    ///
+    /// Like [`ParallelScanIterator`], this holds the engine mutex one page at
+    /// a time, and locks the visible rows without it.
+    pub fn count(&self, table: &str) -> Result<u64> {
+        self.check_terminated()?;
+        let snapshot = self.snapshot()?;
+        let read_locks = self.takes_read_locks();
+        let page_ids = self.engine.lock()?.page_ids(table)?;
+        let mut count = 0;
+        for page_id in page_ids {
+            let visible: Vec<_> = {
+                let mut engine = self.engine.lock()?;
+                let write_set = self.write_set.lock()?;
+                let written = write_set.get(table);
+                engine
+                    .scan_page_metadata(table, page_id)?
+                    .into_iter()
+                    .filter(|(rid, metadata)| {
+                        let hidden = written.is_some_and(|rids| rids.contains(rid));
+                        !hidden && snapshot.is_version_visible(metadata)
+                    })
+                    .map(|(rid, _)| rid)
+                    .collect()
+            };
+            if self.entry.is_terminated() {
+                return Err(Error::Cancelled);
+            }
+            if read_locks {
+                for rid in &visible {
+                    self.locks.lock_shared(self.id, rid)?;
+                }
+            }
+            self.entry.record_reads(visible.len() as u64);
+            count += visible.len() as u64;
+        }
+        Ok(count)
+    }
+}
+
+/// Maps a visible row found by a parallel scan to the item to emit for it, or
+/// to None to skip it. Runs on the scan's worker threads.
+pub type ScanMap<T> = Arc<dyn Fn(RecordId, Tuple) -> Result<Option<T>> + Send + Sync>;
+
+impl<E: Engine + 'static> Transaction<E> {
+    /// Like [`Self::scan`], but splits the table's pages between `workers`
+    /// threads, which apply `map` to the visible rows on their pages. Items
+    /// come in no particular order.
+    pub fn parallel_scan<T: Send + 'static>(
+        &self,
+        table: &str,
+        workers: usize,
+        map: ScanMap<T>,
+    ) -> Result<ParallelScanIterator<E, T>> {
+        self.check_terminated()?;
+        let read_locks = self.takes_read_locks();
+        Ok(ParallelScanIterator {
+            engine: Arc::clone(&self.engine),
+            snapshot: self.snapshot()?,
+            write_set: Arc::clone(&self.write_set),
+            read_locks: read_locks.then(|| (Arc::clone(&self.locks), self.id)),
+            entry: Arc::clone(&self.entry),
+            table: table.to_string(),
+            workers: workers.max(1),
+            map,
+            receiver: None,
+            handles: Vec::new(),
+            done: false,
+        })
+    }
+}
+
+/// An iterator over the live and visible key/value pairs for the txn that an
+/// index scan finds, in index order.
+///
+/// The index holds every version of a row, so each record id it returns is
+/// checked against the snapshot and write set like in [`ScanIterator`].
+/// Record ids are pulled from the index with the engine mutex held, so that a
+/// concurrent vacuum can't remove a version between the two. The index only
+/// reads one leaf at a time, so this holds the mutex about as long as a
+/// [`ScanIterator`] batch does.
+pub struct IndexScanIterator<E: Engine> {
+    /// The engine.
+    engine: Arc<Mutex<E>>,
+    /// The snapshot versions are filtered through.
+    snapshot: Arc<Snapshot>,
+    /// The transaction's write set, whose versions are hidden from it.
+    write_set: Arc<Mutex<WriteSet>>,
+    /// The lock manager and transaction id to take shared locks on emitted
+    /// rows with, if the transaction takes read locks.
+    read_locks: Option<(Arc<LockManager>, TxnId)>,
+    /// The transaction's registry entry, which counts the rows emitted.
+    entry: Arc<TransactionEntry>,
+    /// The record ids the index finds, of versions of any visibility.
+    rids: IndexIterator,
+    /// The name of the table this iterates over
+    table: String,
+}
+
+/// Implement Clone manually. Deriving it requires Engine: Clone.
+impl<E: Engine> Clone for IndexScanIterator<E> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            snapshot: self.snapshot.clone(),
+            write_set: self.write_set.clone(),
+            read_locks: self.read_locks.clone(),
+            entry: self.entry.clone(),
+            rids: self.rids.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<E: Engine> IndexScanIterator<E> {
+    /// Pulls record ids from the index until one holds a version visible to
+    /// the transaction, and returns it along with its value.
+    fn next_visible(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        let mut engine = self.engine.lock()?;
+        let write_set = self.write_set.lock()?;
+        let written = write_set.get(&self.table);
+        for rid in self.rids.by_ref() {
+            let rid = rid?;
+            let key = Key::new(&self.table, &rid);
+            let hidden = written.is_some_and(|rids| rids.contains(&rid));
+            if !hidden && self.snapshot.is_version_visible(&engine.get_metadata(key)?) {
+                let tuple = engine.get(key)?;
+                return Ok(Some((rid, tuple)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<E: Engine> Iterator for IndexScanIterator<E> {
+    type Item = Result<(RecordId, Tuple)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entry.is_terminated() {
+            return Some(Err(Error::Cancelled));
+        }
+        let (rid, tuple) = match self.next_visible() {
+            Ok(item) => item?,
+            Err(error) => return Some(Err(error)),
+        };
+        // Like in ScanIterator, the lock is taken once the engine mutex is
+        // released.
+        if let Some((locks, txn_id)) = &self.read_locks {
+            if let Err(error) = locks.lock_shared(*txn_id, &rid) {
+                return Some(Err(error));
+            }
+        }
+        self.entry.record_reads(1);
+        Some(Ok((rid, tuple)))
+    }
+}
+
+/// An iterator over the latest live and visible key/value pairs for the txn.
+///
+/// The (single-threaded) engine is protected by a mutex, and holding the mutex
+/// for the duration of the iteration can cause deadlocks (e.g. when the local
+/// SQL engine pulls from two tables concurrently during a join). Instead, we
+/// pull and buffer a batch of rows at a time, and release the mutex in between.
+///
+/// It scans either forwards or in reverse, depending on how it was created.
+/// This does not implement DoubleEndedIterator, since the SQL layer only ever
+/// scans a table in one direction at a time.
+pub struct ScanIterator<E: Engine> {
+    /// The engine.
+    engine: Arc<Mutex<E>>,
+    /// The snapshot versions are filtered through.
+    snapshot: Arc<Snapshot>,
+    /// The transaction's write set, whose versions are hidden from it.
+    write_set: Arc<Mutex<WriteSet>>,
+    /// The lock manager and transaction id to take shared locks on emitted
+    /// rows with, if the transaction takes read locks.
+    read_locks: Option<(Arc<LockManager>, TxnId)>,
+    /// The transaction's registry entry, which counts the rows emitted.
+    entry: Arc<TransactionEntry>,
+    /// A buffer of live and visible key/value pairs to emit.
+    buffer: VecDeque<(RecordId, Tuple)>,
+    /// The name of the table this iterates over
+    table: String,
+    /// Whether this iterates over the table in reverse.
+    reverse: bool,
+    /// The record id of the last version pulled from the engine, if any.
+    last: Option<RecordId>,
+    /// Whether the engine's scan has been exhausted.
+    done: bool,
+}
+
+/// Implement Clone manually. Deriving it requires Engine: Clone.
+///
+/// A clone carries on from the same position as the original. Neither holds
+/// any page pinned between calls: each refill pins one page at a time while
+/// it pulls versions from the engine, and resumes after the last one pulled.
+impl<E: Engine> Clone for ScanIterator<E> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            snapshot: self.snapshot.clone(),
+            write_set: self.write_set.clone(),
+            read_locks: self.read_locks.clone(),
+            entry: self.entry.clone(),
+            buffer: self.buffer.clone(),
+            table: self.table.clone(),
+            reverse: self.reverse,
+            last: self.last.clone(),
+            done: self.done,
+        }
+    }
+}
+
+impl<E: Engine> ScanIterator<E> {
+    /// The number of live keys to pull from the engine at a time.
+    #[cfg(not(test))]
+    const BUFFER_SIZE: usize = 1000;
+    /// Pull only 2 keys in tests, to exercise this more often.
+    #[cfg(test)]
+    const BUFFER_SIZE: usize = 4;
+
+    /// Creates a new scan iterator.
+    fn new(
+        engine: Arc<Mutex<E>>,
+        snapshot: Arc<Snapshot>,
+        write_set: Arc<Mutex<WriteSet>>,
+        read_locks: Option<(Arc<LockManager>, TxnId)>,
+        entry: Arc<TransactionEntry>,
+        table: &str,
+        reverse: bool,
+    ) -> Self {
+        let buffer = VecDeque::with_capacity(Self::BUFFER_SIZE);
+        Self {
+            engine,
+            snapshot,
+            write_set,
+            read_locks,
+            entry,
+            buffer,
+            table: table.to_string(),
+            reverse,
+            last: None,
+            done: false,
+        }
+    }
+
+    /// Fills the buffer, if there's any pending items.
+    fn fill_buffer(&mut self) -> Result<()> {
+        // Check if there's anything to buffer.
+        if self.done || self.buffer.len() >= Self::BUFFER_SIZE {
+            return Ok(());
+        }
+
+        let mut engine = self.engine.lock()?;
+        let write_set = self.write_set.lock()?;
+        let written = write_set.get(&self.table);
+        // Skip past the last version pulled, then pull versions until the
+        // buffer is full, keeping only those visible to the snapshot. Scans
+        // visit record ids in ascending order, or descending in reverse.
+        let (last, reverse) = (self.last.clone(), self.reverse);
+        let scan = match reverse {
+            false => engine.scan(&self.table),
+            true => engine.scan_rev(&self.table),
+        };
+        let mut iter = scan.skip_while(|result| match (result, &last) {
+            (Ok((rid, _, _)), Some(last)) => match reverse {
+                false => rid <= last,
+                true => rid >= last,
+            },
+            _ => false,
+        });
+        while self.buffer.len() < Self::BUFFER_SIZE {
+            let Some((rid, metadata, tuple)) = iter.next().transpose()? else {
+                self.done = true;
+                break;
+            };
+            self.last = Some(rid.clone());
+            let hidden = written.is_some_and(|rids| rids.contains(&rid));
+            if !hidden && self.snapshot.is_version_visible(&metadata) {
+                self.buffer.push_back((rid, tuple));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<E: Engine> Iterator for ScanIterator<E> {
+    type Item = Result<(RecordId, Tuple)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entry.is_terminated() {
+            return Some(Err(Error::Cancelled));
+        }
+        if self.buffer.is_empty() {
+            if let Err(error) = self.fill_buffer() {
+                return Some(Err(error));
+            }
+        }
+        let (rid, tuple) = self.buffer.pop_front()?;
+        // Rows are locked as they are emitted rather than while buffering,
+        // since waiting for a lock while holding the engine mutex would block
+        // the lock holder from ever finishing.
+        if let Some((locks, txn_id)) = &self.read_locks {
+            if let Err(error) = locks.lock_shared(*txn_id, &rid) {
+                return Some(Err(error));
+            }
+        }
+        self.entry.record_reads(1);
+        Some(Ok((rid, tuple)))
+    }
+}
+
+/// An iterator over the live and visible rows of a table, mapped by a
+/// [`ScanMap`], which worker threads scan in parallel.
+///
+/// The workers start when the iterator is first polled: the table's page ids
+/// are taken then, and split into a contiguous run for each worker. Each worker
+/// pulls the versions on one of its pages at a time with the engine mutex held,
+/// filters them through the snapshot and write set like [`ScanIterator`], and
+/// then locks, counts and maps the visible ones without the mutex, sending the
+/// items down a bounded channel. Items therefore come in no particular order,
+/// and callers that need one have to sort them.
+///
+/// An error in a worker is emitted in place of the item it failed on, and stops
+/// that worker; the others carry on. Dropping the iterator stops the workers,
+/// and waits for them to finish.
+pub struct ParallelScanIterator<E: Engine, T> {
+    /// The engine.
+    engine: Arc<Mutex<E>>,
+    /// The snapshot versions are filtered through.
+    snapshot: Arc<Snapshot>,
+    /// The transaction's write set, whose versions are hidden from it.
+    write_set: Arc<Mutex<WriteSet>>,
+    /// The lock manager and transaction id to take shared locks on visible
+    /// rows with, if the transaction takes read locks.
+    read_locks: Option<(Arc<LockManager>, TxnId)>,
+    /// The transaction's registry entry, which counts the rows read.
+    entry: Arc<TransactionEntry>,
+    /// The name of the table this iterates over.
+    table: String,
+    /// The number of worker threads to split the pages between.
+    workers: usize,
+    /// Maps each visible row to the item to emit, if any.
+    map: ScanMap<T>,
+    /// The receiving end of the workers' channel, once they are started.
+    receiver: Option<mpsc::Receiver<Result<T>>>,
+    /// The worker threads, until they are joined.
+    handles: Vec<JoinHandle<()>>,
+    /// Whether every worker has finished.
+    done: bool,
+}
+
+/// Implement Clone manually. Deriving it requires Engine: Clone.
+///
+/// The items the workers have already sent can't be shared, so unlike a
+/// [`ScanIterator`] clone, a clone starts the scan over from the beginning
+/// with workers of its own.
+impl<E: Engine, T> Clone for ParallelScanIterator<E, T> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            snapshot: self.snapshot.clone(),
+            write_set: self.write_set.clone(),
+            read_locks: self.read_locks.clone(),
+            entry: self.entry.clone(),
+            table: self.table.clone(),
+            workers: self.workers,
+            map: self.map.clone(),
+            receiver: None,
+            handles: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl<E: Engine + 'static, T: Send + 'static> ParallelScanIterator<E, T> {
+    /// The number of items the channel holds before workers wait for the
+    /// iterator to catch up.
+    const CHANNEL_SIZE: usize = 1000;
+
+    /// Splits the table's pages between the workers, and starts them.
+    fn start(&mut self) -> Result<mpsc::Receiver<Result<T>>> {
+        let page_ids = self.engine.lock()?.page_ids(&self.table)?;
+        let (sender, receiver) = mpsc::sync_channel(Self::CHANNEL_SIZE);
+        let run_size = page_ids.len().div_ceil(self.workers).max(1);
+        for run in page_ids.chunks(run_size) {
+            let worker = ScanWorker {
+                engine: Arc::clone(&self.engine),
+                snapshot: Arc::clone(&self.snapshot),
+                write_set: Arc::clone(&self.write_set),
+                read_locks: self.read_locks.clone(),
+                entry: Arc::clone(&self.entry),
+                table: self.table.clone(),
+                map: Arc::clone(&self.map),
+                sender: sender.clone(),
+            };
+            let run = run.to_vec();
+            self.handles.push(thread::spawn(move || worker.run(run)));
+        }
+        Ok(receiver)
+    }
+}
+
+impl<E: Engine, T> ParallelScanIterator<E, T> {
+    /// Waits for the workers to finish. Errors if any of them panicked.
+    fn join(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for handle in self.handles.drain(..) {
+            if handle.join().is_err() {
+                result = errdata!("a worker scanning table {} panicked", self.table);
+            }
+        }
+        result
+    }
+}
+
+impl<E: Engine + 'static, T: Send + 'static> Iterator for ParallelScanIterator<E, T> {
+    type Item = Result<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.receiver.is_none() {
+            match self.start() {
+                Ok(receiver) => self.receiver = Some(receiver),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        match self.receiver.as_ref()?.recv() {
+            Ok(item) => Some(item),
+            // Every worker has dropped its sender, i.e. finished.
+            Err(_) => {
+                self.done = true;
+                self.join().err().map(Err)
+            }
+        }
+    }
+}
+
+impl<E: Engine, T> Drop for ParallelScanIterator<E, T> {
+    /// Drops the channel, so that workers stop at their next send, and waits
+    /// for them.
+    fn drop(&mut self) {
+        self.receiver = None;
+        self.join().ok();
+    }
+}
+
+/// A worker thread of a [`ParallelScanIterator`], scanning a run of pages.
+struct ScanWorker<E: Engine, T> {
+    engine: Arc<Mutex<E>>,
+    snapshot: Arc<Snapshot>,
+    write_set: Arc<Mutex<WriteSet>>,
+    read_locks: Option<(Arc<LockManager>, TxnId)>,
+    entry: Arc<TransactionEntry>,
+    table: String,
+    map: ScanMap<T>,
+    sender: mpsc::SyncSender<Result<T>>,
+}
+
+impl<E: Engine, T> ScanWorker<E, T> {
+    /// Scans the pages in turn, until they run out, one fails, or the
+    /// iterator is dropped.
+    fn run(self, page_ids: Vec<PageId>) {
+        for page_id in page_ids {
+            match self.scan_page(page_id) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(error) => {
+                    self.sender.send(Err(error)).ok();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Sends the items for the visible rows on a page. Returns false if the
+    /// iterator was dropped.
+    fn scan_page(&self, page_id: PageId) -> Result<bool> {
+        let visible: Vec<_> = {
+            let mut engine = self.engine.lock()?;
+            let write_set = self.write_set.lock()?;
+            let written = write_set.get(&self.table);
+            engine
+                .scan_page(&self.table, page_id)?
+                .into_iter()
+                .filter(|(rid, metadata, _)| {
+                    let hidden = written.is_some_and(|rids| rids.contains(rid));
+                    !hidden && self.snapshot.is_version_visible(metadata)
+                })
+                .map(|(rid, _, tuple)| (rid, tuple))
+                .collect()
+        };
+        for (rid, tuple) in visible {
+            if self.entry.is_terminated() {
+                return Err(Error::Cancelled);
+            }
+            if let Some((locks, txn_id)) = &self.read_locks {
+                locks.lock_shared(*txn_id, &rid)?;
+            }
+            self.entry.record_reads(1);
+            if let Some(item) = (self.map)(rid, tuple)? {
+                if self.sender.send(Ok(item)).is_err() {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
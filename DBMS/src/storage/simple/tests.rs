@@ -0,0 +1,1294 @@
+use crate::common::utility;
+use crate::common::Error;
+use crate::concurrency::{IsolationLevel, LockManager, LockMode, TransactionState};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::page::RecordId;
+use crate::storage::simple::{PreparedTransaction, ScanMap, Simple, Transaction};
+use crate::storage::tables::HeapTableManager;
+use crate::storage::tuple::{Row, Tuple};
+use crate::storage::{Engine, Key, VacuumStats};
+use crate::types::field::Field;
+use crate::types::{Column, DataType, Table};
+use std::collections::BTreeSet;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait before concluding that an operation is blocked on a lock.
+const BLOCKED: Duration = Duration::from_millis(100);
+
+#[test]
+fn test_insert_rollback() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let before = scan(&simple);
+    let txn = simple.begin().unwrap();
+    for seed in 2..10 {
+        txn.insert("test", tuple(&schema, seed)).unwrap();
+    }
+    assert_eq!(9, scan_txn(&txn).len());
+    txn.rollback().unwrap();
+
+    assert_eq!(before, scan(&simple));
+}
+
+#[test]
+fn test_update_rollback_restores_bytes() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let original = tuple(&schema, 1);
+    let rid = txn.insert("test", original.clone()).unwrap();
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    txn.update(Key::new("test", &rid), tuple(&schema, 2))
+        .unwrap();
+    assert!(txn
+        .get(Key::new("test", &rid))
+        .map_or(true, |current| current != original));
+    txn.rollback().unwrap();
+
+    let txn = simple.begin().unwrap();
+    assert_eq!(original, txn.get(Key::new("test", &rid)).unwrap());
+    assert_eq!(vec![(rid, original)], scan_txn(&txn));
+}
+
+#[test]
+fn test_interleaved_rollback() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    let b = txn.insert("test", tuple(&schema, 2)).unwrap();
+    txn.commit().unwrap();
+    let before = scan(&simple);
+
+    // Each write depends on the one before it, so undoing them in any order
+    // other than LIFO would leave the wrong bytes behind.
+    let txn = simple.begin().unwrap();
+    let a = txn.update(Key::new("test", &a), tuple(&schema, 3)).unwrap();
+    txn.delete(Key::new("test", &b)).unwrap();
+    let c = txn.insert("test", tuple(&schema, 4)).unwrap();
+    let a = txn.update(Key::new("test", &a), tuple(&schema, 5)).unwrap();
+    txn.update(Key::new("test", &c), tuple(&schema, 6)).unwrap();
+    txn.delete(Key::new("test", &a)).unwrap();
+    txn.rollback().unwrap();
+
+    assert_eq!(before, scan(&simple));
+}
+
+#[test]
+fn test_rollback_to_savepoint() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    let savepoint = txn.savepoint().unwrap();
+    txn.update(Key::new("test", &a), tuple(&schema, 2)).unwrap();
+    txn.insert("test", tuple(&schema, 3)).unwrap();
+    txn.rollback_to(savepoint).unwrap();
+
+    assert_eq!(vec![(a, tuple(&schema, 1))], scan_txn(&txn));
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_read_your_own_writes() {
+    let (simple, schema) = setup();
+    let writer = simple.begin().unwrap();
+    let reader = simple.begin().unwrap();
+
+    let rid = writer.insert("test", tuple(&schema, 1)).unwrap();
+    assert_eq!(vec![(rid.clone(), tuple(&schema, 1))], scan_txn(&writer));
+    let rid = writer
+        .update(Key::new("test", &rid), tuple(&schema, 2))
+        .unwrap();
+    assert_eq!(
+        tuple(&schema, 2),
+        writer.get(Key::new("test", &rid)).unwrap()
+    );
+    assert_eq!(vec![(rid.clone(), tuple(&schema, 2))], scan_txn(&writer));
+
+    // Uncommitted writes are invisible to everyone else.
+    assert!(scan_txn(&reader).is_empty());
+    assert!(reader.get(Key::new("test", &rid)).is_err());
+
+    writer.delete(Key::new("test", &rid)).unwrap();
+    assert!(scan_txn(&writer).is_empty());
+    writer.commit().unwrap();
+    reader.commit().unwrap();
+}
+
+#[test]
+fn test_reader_never_sees_later_commits() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    let b = txn.insert("test", tuple(&schema, 2)).unwrap();
+    txn.commit().unwrap();
+    let before = scan(&simple);
+
+    // One reader starts before the writer, one while it is running.
+    let early = simple.begin().unwrap();
+    let writer = simple.begin().unwrap();
+    let late = simple.begin().unwrap();
+
+    writer
+        .update(Key::new("test", &a), tuple(&schema, 3))
+        .unwrap();
+    writer.delete(Key::new("test", &b)).unwrap();
+    writer.insert("test", tuple(&schema, 4)).unwrap();
+    assert_eq!(before, scan_txn(&early));
+    assert_eq!(before, scan_txn(&late));
+
+    writer.commit().unwrap();
+    assert_eq!(before, scan_txn(&early));
+    assert_eq!(before, scan_txn(&late));
+    assert_eq!(tuple(&schema, 2), late.get(Key::new("test", &b)).unwrap());
+    early.commit().unwrap();
+    late.commit().unwrap();
+
+    let after: Vec<Tuple> = scan(&simple).into_iter().map(|(_, t)| t).collect();
+    assert_eq!(vec![tuple(&schema, 3), tuple(&schema, 4)], after);
+}
+
+/// Test that which versions a transaction sees doesn't change once the pages holding them are
+/// evicted and read back, deleted and replaced versions included.
+#[test]
+fn test_visibility_survives_eviction() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(4)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let simple = Simple::new(HeapTableManager::new(&bpm));
+    let schema = Arc::new(utility::create_table_definition(4, "test"));
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    let rids: Vec<RecordId> = (0..2000)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+    let pages: BTreeSet<_> = rids.iter().map(|rid| rid.page_id()).collect();
+    assert!(pages.len() > 4);
+
+    let early = simple.begin().unwrap();
+    let txn = simple.begin().unwrap();
+    for rid in &rids[..10] {
+        txn.delete(Key::new("test", rid)).unwrap();
+    }
+    txn.update(Key::new("test", &rids[10]), tuple(&schema, 5000))
+        .unwrap();
+    txn.commit().unwrap();
+
+    // Every scan reads pages the previous one evicted.
+    for _ in 0..3 {
+        let rows = tuples(&simple);
+        assert_eq!(1990, rows.len());
+        assert!((0..11).all(|seed| !rows.contains(&tuple(&schema, seed))));
+        let updated = rows.iter().filter(|row| **row == tuple(&schema, 5000));
+        assert_eq!(1, updated.count());
+
+        let rows: Vec<Tuple> = scan_txn(&early).into_iter().map(|(_, t)| t).collect();
+        assert_eq!((0..2000).map(|seed| tuple(&schema, seed)).collect::<Vec<_>>(), rows);
+    }
+    early.commit().unwrap();
+}
+
+#[test]
+fn test_concurrent_writers_serialize() {
+    for first_writer_commits in [true, false] {
+        let (simple, schema) = setup();
+        let txn = simple.begin().unwrap();
+        let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+        txn.commit().unwrap();
+
+        // Both transactions read the row, then overwrite it. The second
+        // writer waits for the first one's lock.
+        let t1 = simple.begin().unwrap();
+        let t2 = simple.begin().unwrap();
+        assert_eq!(tuple(&schema, 1), t1.get(Key::new("test", &rid)).unwrap());
+        assert_eq!(tuple(&schema, 1), t2.get(Key::new("test", &rid)).unwrap());
+        t1.update(Key::new("test", &rid), tuple(&schema, 2))
+            .unwrap();
+
+        let (result, expected) = thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+            let t2 = &t2;
+            let rid = &rid;
+            let schema = &schema;
+            s.spawn(move || {
+                let result = t2.update(Key::new("test", rid), tuple(schema, 3));
+                tx.send(result.map(|_| ())).unwrap();
+            });
+            assert!(rx.recv_timeout(BLOCKED).is_err());
+            if first_writer_commits {
+                t1.commit().unwrap();
+                (rx.recv().unwrap(), tuple(schema, 2))
+            } else {
+                t1.rollback().unwrap();
+                (rx.recv().unwrap(), tuple(schema, 3))
+            }
+        });
+        if first_writer_commits {
+            // The lost update is prevented by aborting the second writer.
+            assert_eq!(Err(Error::Serialization), result);
+            t2.rollback().unwrap();
+        } else {
+            result.unwrap();
+            t2.commit().unwrap();
+        }
+
+        let rows: Vec<Tuple> = scan(&simple).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(vec![expected], rows);
+    }
+}
+
+#[test]
+fn test_serializable_reader_blocks_behind_writer() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    let b = txn.insert("test", tuple(&schema, 2)).unwrap();
+    txn.commit().unwrap();
+
+    let writer = simple.begin().unwrap();
+    writer
+        .update(Key::new("test", &a), tuple(&schema, 3))
+        .unwrap();
+
+    // Readers under snapshot isolation take no locks, so they don't wait.
+    let reader = simple.begin().unwrap();
+    assert_eq!(tuple(&schema, 1), reader.get(Key::new("test", &a)).unwrap());
+    reader.commit().unwrap();
+
+    let reader = simple.begin_with(IsolationLevel::Serializable).unwrap();
+    let read = thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        let reader = &reader;
+        let a = &a;
+        s.spawn(move || tx.send(reader.get(Key::new("test", a))).unwrap());
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+        writer.commit().unwrap();
+        rx.recv().unwrap()
+    });
+    // The reader still sees its own snapshot once the writer is gone.
+    assert_eq!(tuple(&schema, 1), read.unwrap());
+
+    // The shared locks it takes hold up writers until it finishes.
+    assert_eq!(tuple(&schema, 2), reader.get(Key::new("test", &b)).unwrap());
+    let lock_manager = simple.lock_manager();
+    assert_eq!(
+        Some(LockMode::Shared),
+        lock_manager.lock_mode(reader.id(), &b).unwrap()
+    );
+    let writer = simple.begin().unwrap();
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        let writer = &writer;
+        let b = &b;
+        s.spawn(move || tx.send(writer.delete(Key::new("test", b))).unwrap());
+        assert!(rx.recv_timeout(BLOCKED).is_err());
+        reader.commit().unwrap();
+        rx.recv().unwrap().unwrap();
+    });
+    writer.commit().unwrap();
+}
+
+#[test]
+fn test_scan_takes_read_locks() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    for seed in 1..10 {
+        txn.insert("test", tuple(&schema, seed)).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let reader = simple.begin_with(IsolationLevel::Serializable).unwrap();
+    let rows = scan_txn(&reader);
+    let lock_manager = simple.lock_manager();
+    for (rid, _) in &rows {
+        assert_eq!(
+            Some(LockMode::Shared),
+            lock_manager.lock_mode(reader.id(), rid).unwrap()
+        );
+    }
+    let id = reader.id();
+    reader.commit().unwrap();
+    for (rid, _) in &rows {
+        assert_eq!(None, lock_manager.lock_mode(id, rid).unwrap());
+    }
+}
+
+#[test]
+fn test_write_after_concurrent_commit_aborts() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let t1 = simple.begin().unwrap();
+    let t2 = simple.begin().unwrap();
+    t2.delete(Key::new("test", &rid)).unwrap();
+    t2.commit().unwrap();
+
+    // t1 still sees the row, but t2 won the race to change it.
+    assert_eq!(tuple(&schema, 1), t1.get(Key::new("test", &rid)).unwrap());
+    assert_eq!(
+        Err(Error::Serialization),
+        t1.update(Key::new("test", &rid), tuple(&schema, 2))
+            .map(|_| ())
+    );
+    t1.rollback().unwrap();
+}
+
+#[test]
+fn test_disjoint_writers_commit() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    let b = txn.insert("test", tuple(&schema, 2)).unwrap();
+    txn.commit().unwrap();
+
+    let t1 = simple.begin().unwrap();
+    let t2 = simple.begin().unwrap();
+    t1.update(Key::new("test", &a), tuple(&schema, 3)).unwrap();
+    t2.update(Key::new("test", &b), tuple(&schema, 4)).unwrap();
+    t2.insert("test", tuple(&schema, 5)).unwrap();
+    t1.commit().unwrap();
+    t2.commit().unwrap();
+
+    let mut rows: Vec<Tuple> = scan(&simple).into_iter().map(|(_, t)| t).collect();
+    let mut expected = vec![tuple(&schema, 3), tuple(&schema, 4), tuple(&schema, 5)];
+    rows.sort_by(|a, b| a.data.cmp(&b.data));
+    expected.sort_by(|a, b| a.data.cmp(&b.data));
+    assert_eq!(expected, rows);
+}
+
+#[test]
+fn test_reader_never_aborts() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let reader = simple.begin().unwrap();
+    let before = scan_txn(&reader);
+    let writer = simple.begin().unwrap();
+    writer
+        .update(Key::new("test", &rid), tuple(&schema, 2))
+        .unwrap();
+    writer.commit().unwrap();
+
+    assert_eq!(before, scan_txn(&reader));
+    reader.commit().unwrap();
+}
+
+#[test]
+fn test_read_committed_sees_commits_between_statements() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let rc = simple.begin_with(IsolationLevel::ReadCommitted).unwrap();
+    let si = simple
+        .begin_with(IsolationLevel::SnapshotIsolation)
+        .unwrap();
+    assert_eq!(IsolationLevel::ReadCommitted, rc.isolation());
+    assert_eq!(IsolationLevel::SnapshotIsolation, si.isolation());
+    for txn in [&rc, &si] {
+        txn.start_statement().unwrap();
+        assert_eq!(tuple(&schema, 1), txn.get(Key::new("test", &a)).unwrap());
+    }
+
+    let writer = simple.begin().unwrap();
+    let b = writer
+        .update(Key::new("test", &a), tuple(&schema, 2))
+        .unwrap();
+    let c = writer.insert("test", tuple(&schema, 3)).unwrap();
+
+    // Uncommitted writes are invisible at every level.
+    for txn in [&rc, &si] {
+        txn.start_statement().unwrap();
+        assert_eq!(vec![(a.clone(), tuple(&schema, 1))], scan_txn(txn));
+    }
+    writer.commit().unwrap();
+
+    // Within a statement, read committed keeps reading the same snapshot.
+    assert_eq!(tuple(&schema, 1), rc.get(Key::new("test", &a)).unwrap());
+
+    // The next statement sees the commit: the earlier read doesn't repeat.
+    rc.start_statement().unwrap();
+    assert!(rc.get(Key::new("test", &a)).is_err());
+    assert_eq!(
+        vec![
+            (b.clone(), tuple(&schema, 2)),
+            (c.clone(), tuple(&schema, 3))
+        ],
+        scan_txn(&rc)
+    );
+
+    // Snapshot isolation keeps reading the snapshot taken when it began.
+    si.start_statement().unwrap();
+    assert_eq!(tuple(&schema, 1), si.get(Key::new("test", &a)).unwrap());
+    assert_eq!(vec![(a.clone(), tuple(&schema, 1))], scan_txn(&si));
+
+    rc.commit().unwrap();
+    si.commit().unwrap();
+}
+
+#[test]
+fn test_read_uncommitted_is_read_committed() {
+    let (simple, schema) = setup();
+    let ru = simple.begin_with(IsolationLevel::ReadUncommitted).unwrap();
+    let writer = simple.begin().unwrap();
+    writer.insert("test", tuple(&schema, 1)).unwrap();
+
+    // No dirty reads, but commits show up at the next statement.
+    ru.start_statement().unwrap();
+    assert!(scan_txn(&ru).is_empty());
+    writer.commit().unwrap();
+    ru.start_statement().unwrap();
+    assert_eq!(1, scan_txn(&ru).len());
+    ru.commit().unwrap();
+}
+
+#[test]
+fn test_serializable_prevents_write_skew() {
+    for isolation in [
+        IsolationLevel::SnapshotIsolation,
+        IsolationLevel::Serializable,
+    ] {
+        let (simple, schema) = setup_with_lock_manager(LockManager::new(BLOCKED * 2));
+        let txn = simple.begin().unwrap();
+        let a = txn.insert("test", tuple(&schema, 1)).unwrap();
+        let b = txn.insert("test", tuple(&schema, 2)).unwrap();
+        txn.commit().unwrap();
+
+        // Each transaction reads both rows, then overwrites a different one.
+        let t1 = simple.begin_with(isolation).unwrap();
+        let t2 = simple.begin_with(isolation).unwrap();
+        for txn in [&t1, &t2] {
+            assert_eq!(2, scan_txn(txn).len());
+        }
+
+        if isolation == IsolationLevel::SnapshotIsolation {
+            // The writes don't overlap, so both commit: write skew.
+            t1.update(Key::new("test", &a), tuple(&schema, 3)).unwrap();
+            t2.update(Key::new("test", &b), tuple(&schema, 4)).unwrap();
+            t1.commit().unwrap();
+            t2.commit().unwrap();
+            assert_eq!(2, scan(&simple).len());
+            continue;
+        }
+
+        // Each write waits for the other's read lock. The first one to give
+        // up rolls back, letting the other through.
+        thread::scope(|s| {
+            let (t2, a, b, schema) = (&t2, &a, &b, &schema);
+            let first = s.spawn(move || {
+                let result = t1.update(Key::new("test", a), tuple(schema, 3));
+                t1.rollback().unwrap();
+                result
+            });
+            thread::sleep(BLOCKED);
+            let second = s.spawn(move || t2.update(Key::new("test", b), tuple(schema, 4)));
+            assert_eq!(Err(Error::LockTimeout), first.join().unwrap().map(|_| ()));
+            second.join().unwrap().unwrap();
+        });
+        t2.commit().unwrap();
+
+        let rows: Vec<Tuple> = scan(&simple).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(vec![tuple(&schema, 1), tuple(&schema, 4)], rows);
+    }
+}
+
+#[test]
+fn test_vacuum_removes_dead_versions() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=3)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    txn.update(Key::new("test", &rids[0]), tuple(&schema, 4))
+        .unwrap();
+    txn.delete(Key::new("test", &rids[1])).unwrap();
+    txn.commit().unwrap();
+    // A rolled back insert leaves a tombstone behind, whose payload vacuuming reclaims too.
+    let txn = simple.begin().unwrap();
+    txn.insert("test", tuple(&schema, 5)).unwrap();
+    txn.rollback().unwrap();
+
+    let before = scan(&simple);
+    let stats = simple.vacuum(Some("test")).unwrap();
+    assert_eq!(2, stats.versions);
+    let reclaimed: usize = [1, 2, 5]
+        .map(|seed| tuple(&schema, seed).data.len())
+        .iter()
+        .sum();
+    assert_eq!(reclaimed as u64, stats.bytes);
+    assert_eq!(0, stats.pages);
+    assert_eq!(before, scan(&simple));
+    for rid in &rids[..2] {
+        let metadata = simple
+            .engine
+            .lock()
+            .unwrap()
+            .get_metadata(Key::new("test", rid));
+        assert!(metadata.unwrap().is_deleted());
+    }
+
+    // Nothing is left to reclaim, and new versions still fit where they belong.
+    assert_eq!(VacuumStats::default(), simple.vacuum(None).unwrap());
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 6)).unwrap();
+    assert_eq!(tuple(&schema, 6), txn.get(Key::new("test", &rid)).unwrap());
+    assert_eq!(before.len() + 1, scan_txn(&txn).len());
+    txn.commit().unwrap();
+    assert!(simple.vacuum(Some("missing")).is_err());
+}
+
+#[test]
+fn test_vacuum_keeps_versions_a_reader_can_see() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.insert("test", tuple(&schema, 2)).unwrap();
+    txn.commit().unwrap();
+
+    let reader = simple.begin().unwrap();
+    let rows = scan_txn(&reader);
+    let writer = simple.begin().unwrap();
+    writer.delete(Key::new("test", &rid)).unwrap();
+    writer.commit().unwrap();
+
+    // The reader's snapshot predates the delete, so it protects the version.
+    assert_eq!(0, simple.vacuum(None).unwrap().versions);
+    assert_eq!(rows, scan_txn(&reader));
+    reader.commit().unwrap();
+    assert_eq!(1, simple.vacuum(None).unwrap().versions);
+    assert_eq!(rows[1..], scan(&simple));
+}
+
+#[test]
+fn test_vacuum_unlinks_emptied_pages() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let mut rids = Vec::new();
+    let mut pages = BTreeSet::new();
+    for seed in 0.. {
+        let rid = txn.insert("test", tuple(&schema, seed)).unwrap();
+        pages.insert(rid.page_id());
+        rids.push(rid);
+        if pages.len() == 4 {
+            break;
+        }
+    }
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    for rid in &rids {
+        txn.delete(Key::new("test", rid)).unwrap();
+    }
+    txn.commit().unwrap();
+
+    // The first and last pages stay to anchor the chain.
+    let stats = simple.vacuum(Some("test")).unwrap();
+    assert_eq!(rids.len() as u64, stats.versions);
+    assert_eq!(2, stats.pages);
+    assert!(scan(&simple).is_empty());
+
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    assert_eq!(vec![(rid, tuple(&schema, 1))], scan_txn(&txn));
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_vacuum_full_packs_table() {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(64)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let simple = Simple::new(HeapTableManager::new(&bpm));
+    let schema = create_indexed_table(&simple, false);
+    let row = |k, v| indexed_row(&schema, k, v);
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (0..2000)
+        .map(|v| txn.insert("indexed", row(v % 100, v)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+    let per_page = rids.iter().filter(|rid| rid.page_id() == rids[0].page_id());
+    let per_page = per_page.count();
+
+    // Leave every page with a few live rows, an updated version, and a
+    // tombstone, which a plain vacuum can't take pages out of the chain for.
+    let txn = simple.begin().unwrap();
+    for (i, rid) in rids.iter().enumerate() {
+        match i % 10 {
+            0 => txn
+                .update(Key::new("indexed", rid), row(i as i32 % 100, -1))
+                .map(|_| ())
+                .unwrap(),
+            1 | 2 => {}
+            _ => txn.delete(Key::new("indexed", rid)).unwrap(),
+        }
+    }
+    txn.commit().unwrap();
+    let txn = simple.begin().unwrap();
+    txn.insert("indexed", row(0, 0)).unwrap();
+    txn.rollback().unwrap();
+
+    let page_ids = |simple: &Simple<HeapTableManager>| {
+        simple.engine.lock().unwrap().page_ids("indexed").unwrap()
+    };
+    let index_scan = |simple: &Simple<HeapTableManager>| -> Vec<(RecordId, Tuple)> {
+        let txn = simple.begin().unwrap();
+        let range = Field::Integer(10)..Field::Integer(20);
+        let rows = txn.index_scan("indexed", 0, range, false).unwrap();
+        let rows = rows.collect::<Result<_, _>>().unwrap();
+        txn.commit().unwrap();
+        rows
+    };
+    let old_pages = page_ids(&simple);
+    let before = scan_table(&simple, "indexed");
+    let indexed: Vec<Tuple> = index_scan(&simple).into_iter().map(|(_, t)| t).collect();
+    let free_frames = bpm.shards[0].frames().free_list.len();
+
+    // Other transactions keep it from running.
+    let txn = simple.begin().unwrap();
+    assert!(matches!(
+        simple.vacuum_full(Some("indexed")),
+        Err(Error::InvalidInput(_))
+    ));
+    txn.commit().unwrap();
+
+    let stats = simple.vacuum_full(Some("indexed")).unwrap();
+    // The deleted rows, and the replaced versions of the updated ones.
+    assert_eq!(1400 + 200, stats.versions);
+    assert!(stats.bytes > 0);
+    assert_eq!(old_pages.len() as u64, stats.pages);
+    let new_pages = page_ids(&simple);
+    assert_eq!(before.len().div_ceil(per_page), new_pages.len());
+    assert!(new_pages.iter().all(|page_id| !old_pages.contains(page_id)));
+    assert!(bpm.shards[0].frames().free_list.len() > free_frames);
+
+    // The rows stay the same, in the same order, but move to new record ids,
+    // which the rebuilt index points at.
+    assert_eq!(before, scan_table(&simple, "indexed"));
+    let after = index_scan(&simple);
+    assert_eq!(
+        indexed,
+        after.iter().map(|(_, t)| t.clone()).collect::<Vec<_>>()
+    );
+    assert!(after
+        .iter()
+        .all(|(rid, _)| new_pages.contains(&rid.page_id())));
+    let report = simple
+        .check_index("indexed", "indexed_k_idx", true)
+        .unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+
+    // The table takes writes as before.
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("indexed", row(15, 1)).unwrap();
+    txn.delete(Key::new("indexed", &after[0].0)).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(before.len(), scan_table(&simple, "indexed").len());
+    assert!(index_scan(&simple).iter().any(|(r, _)| *r == rid));
+    assert!(simple.vacuum_full(Some("missing")).is_err());
+}
+
+#[test]
+fn test_index_scan_sees_snapshot() {
+    let (simple, _) = setup();
+    let schema = create_indexed_table(&simple, false);
+    let row = |k, v| indexed_row(&schema, k, v);
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (0..10)
+        .map(|k| txn.insert("indexed", row(k, 0)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+
+    let reader = simple.begin().unwrap();
+    let writer = simple.begin().unwrap();
+    writer
+        .update(Key::new("indexed", &rids[3]), row(30, 1))
+        .unwrap();
+    writer.delete(Key::new("indexed", &rids[4])).unwrap();
+    writer.insert("indexed", row(5, 1)).unwrap();
+    let index_scan = |txn: &Transaction<HeapTableManager>, reverse| -> Vec<Tuple> {
+        txn.index_scan(
+            "indexed",
+            0,
+            Field::Integer(3)..=Field::Integer(30),
+            reverse,
+        )
+        .unwrap()
+        .map(|item| item.unwrap().1)
+        .collect()
+    };
+
+    // Each transaction finds the versions it can see, once each.
+    assert_eq!(
+        [(5, 0), (5, 1), (6, 0), (7, 0), (8, 0), (9, 0), (30, 1)]
+            .map(|(k, v)| row(k, v))
+            .to_vec(),
+        index_scan(&writer, false)
+    );
+    writer.commit().unwrap();
+    let expected: Vec<_> = (3..10).rev().map(|k| row(k, 0)).collect();
+    assert_eq!(expected, index_scan(&reader, true));
+    reader.commit().unwrap();
+
+    // Vacuuming removes the dead versions from the index too.
+    assert_eq!(2, simple.vacuum(Some("indexed")).unwrap().versions);
+    let txn = simple.begin().unwrap();
+    assert_eq!(7, index_scan(&txn, false).len());
+    assert!(txn.index_scan("indexed", 1, .., false).is_err());
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_check_index() {
+    let (simple, _) = setup();
+    let schema = create_indexed_table(&simple, false);
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (0..50)
+        .map(|k| txn.insert("indexed", indexed_row(&schema, k, 0)).unwrap())
+        .collect();
+    txn.delete(Key::new("indexed", &rids[7])).unwrap();
+    txn.commit().unwrap();
+
+    let report = simple
+        .check_index("indexed", "indexed_k_idx", true)
+        .unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(50, report.stats.entries);
+
+    // An entry whose key doesn't match the tuple only shows up in a deep check.
+    let index = simple.engine.lock().unwrap().indexes("indexed").unwrap()[0].clone();
+    index.insert(Field::Integer(1000), rids[3].clone()).unwrap();
+    assert!(simple
+        .check_index("indexed", "indexed_k_idx", false)
+        .unwrap()
+        .is_ok());
+    let report = simple
+        .check_index("indexed", "indexed_k_idx", true)
+        .unwrap();
+    assert_eq!(1, report.problems.len());
+    assert!(report.problems[0]
+        .description
+        .contains("points at a tuple with key 3"));
+
+    assert!(simple.check_index("indexed", "missing_idx", true).is_err());
+    assert!(simple
+        .check_index("missing", "indexed_k_idx", true)
+        .is_err());
+}
+
+#[test]
+fn test_unique_index_rejects_duplicates() {
+    let (simple, _) = setup();
+    let schema = create_indexed_table(&simple, true);
+    let row = |k, v| indexed_row(&schema, k, v);
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("indexed", row(1, 0)).unwrap();
+    txn.insert("indexed", row(2, 0)).unwrap();
+    let Err(Error::Constraint(message)) = txn.insert("indexed", row(1, 1)) else {
+        panic!("expected a constraint violation");
+    };
+    assert_eq!(
+        "duplicate key value violates unique index indexed_k_idx: 1",
+        message
+    );
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    assert!(matches!(
+        txn.insert("indexed", row(2, 1)),
+        Err(Error::Constraint(_))
+    ));
+    // Updates may keep their key, but not take another row's.
+    let rid = txn.update(Key::new("indexed", &rid), row(1, 1)).unwrap();
+    assert!(matches!(
+        txn.update(Key::new("indexed", &rid), row(2, 1)),
+        Err(Error::Constraint(_))
+    ));
+    // A key is free again once its row is deleted, by this transaction or a
+    // committed one.
+    txn.delete(Key::new("indexed", &rid)).unwrap();
+    txn.insert("indexed", row(1, 2)).unwrap();
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("indexed", row(3, 0)).unwrap();
+    txn.delete(Key::new("indexed", &rid)).unwrap();
+    txn.commit().unwrap();
+    let txn = simple.begin().unwrap();
+    txn.insert("indexed", row(3, 1)).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(3, scan_table(&simple, "indexed").len());
+}
+
+#[test]
+fn test_unique_index_concurrent_inserts() {
+    for first_commits in [true, false] {
+        let (simple, _) = setup();
+        let schema = create_indexed_table(&simple, true);
+        // Neither sees the other's insert, so both go through, and the one to
+        // commit second fails. A rolled back insert leaves the key free.
+        let t1 = simple.begin().unwrap();
+        let t2 = simple.begin().unwrap();
+        t1.insert("indexed", indexed_row(&schema, 1, 1)).unwrap();
+        t2.insert("indexed", indexed_row(&schema, 1, 2)).unwrap();
+        let expected = match first_commits {
+            true => {
+                t1.commit().unwrap();
+                assert!(matches!(t2.commit(), Err(Error::Constraint(_))));
+                indexed_row(&schema, 1, 1)
+            }
+            false => {
+                t1.rollback().unwrap();
+                t2.commit().unwrap();
+                indexed_row(&schema, 1, 2)
+            }
+        };
+        assert_eq!(vec![expected], scan_table(&simple, "indexed"));
+    }
+
+    // Racing inserters of the same keys produce exactly one winner per key.
+    let (simple, _) = setup();
+    let schema = create_indexed_table(&simple, true);
+    let winners: Vec<usize> = thread::scope(|s| {
+        let handles: Vec<_> = (0..4)
+            .map(|writer| {
+                let (simple, schema) = (&simple, &schema);
+                s.spawn(move || {
+                    let mut won = 0;
+                    for k in 0..20 {
+                        let txn = simple.begin().unwrap();
+                        match txn.insert("indexed", indexed_row(schema, k, writer)) {
+                            Ok(_) => match txn.commit() {
+                                Ok(()) => won += 1,
+                                Err(error) => assert!(matches!(error, Error::Constraint(_))),
+                            },
+                            Err(error) => {
+                                assert!(matches!(error, Error::Constraint(_)));
+                                txn.rollback().unwrap();
+                            }
+                        }
+                    }
+                    won
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(20, winners.iter().sum::<usize>());
+    let mut keys: Vec<_> = scan_table(&simple, "indexed")
+        .iter()
+        .map(|tuple| tuple.data[..4].to_vec())
+        .collect();
+    keys.dedup();
+    assert_eq!(20, keys.len());
+}
+
+#[test]
+fn test_transaction_registry_lifecycle() {
+    let (simple, _) = setup();
+    let registry = simple.transaction_manager();
+    assert!(registry.list_transactions().unwrap().is_empty());
+
+    let a = simple.begin().unwrap();
+    let b = simple.begin_with(IsolationLevel::ReadCommitted).unwrap();
+    let txns = registry.list_transactions().unwrap();
+    assert_eq!(
+        vec![a.id(), b.id()],
+        txns.iter().map(|t| t.id).collect::<Vec<_>>()
+    );
+    assert!(txns.iter().all(|t| t.state == TransactionState::Active));
+    assert_eq!(IsolationLevel::SnapshotIsolation, txns[0].isolation);
+    assert_eq!(IsolationLevel::ReadCommitted, txns[1].isolation);
+    assert!(txns[0].started <= txns[1].started);
+
+    let id = a.id();
+    a.commit().unwrap();
+    assert_eq!(None, registry.get(id).unwrap());
+    assert_eq!(1, registry.list_transactions().unwrap().len());
+    b.rollback().unwrap();
+    assert!(registry.list_transactions().unwrap().is_empty());
+}
+
+#[test]
+fn test_transaction_registry_counts_rows() {
+    let (simple, schema) = setup();
+    let registry = simple.transaction_manager();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=3)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.get(Key::new("test", &rids[0])).unwrap();
+    assert_eq!(3, scan_txn(&txn).len());
+    txn.update(Key::new("test", &rids[1]), tuple(&schema, 4))
+        .unwrap();
+    txn.delete(Key::new("test", &rids[2])).unwrap();
+
+    let info = registry.get(txn.id()).unwrap().unwrap();
+    assert_eq!(4, info.rows_read);
+    assert_eq!(5, info.rows_written);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn test_terminate_aborts_mid_scan() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=6)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+    let before = scan(&simple);
+
+    let registry = simple.transaction_manager();
+    let txn = simple.begin().unwrap();
+    let id = txn.id();
+    txn.insert("test", tuple(&schema, 7)).unwrap();
+    txn.delete(Key::new("test", &rids[0])).unwrap();
+    let mut iter = txn.scan("test").unwrap();
+    iter.next().unwrap().unwrap();
+    iter.next().unwrap().unwrap();
+
+    assert!(registry.terminate(id).unwrap());
+    assert!(registry.get(id).unwrap().unwrap().terminated);
+    assert_eq!(Some(Err(Error::Cancelled)), iter.next());
+
+    // The next operation rolls the transaction back, releasing its locks.
+    assert_eq!(Err(Error::Cancelled), txn.get(Key::new("test", &rids[1])));
+    assert_eq!(None, registry.get(id).unwrap());
+    let locks = simple.lock_manager();
+    assert_eq!(None, locks.lock_mode(id, &rids[0]).unwrap());
+    assert_eq!(before, scan(&simple));
+    assert_eq!(Err(Error::Cancelled), txn.commit());
+
+    let other = simple.begin().unwrap();
+    other.delete(Key::new("test", &rids[0])).unwrap();
+    other.commit().unwrap();
+    assert!(!registry.terminate(id).unwrap());
+}
+
+#[test]
+fn test_parallel_scan() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=60)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+
+    // The transaction sees its own writes, but not a concurrent one's.
+    let txn = simple.begin().unwrap();
+    let other = simple.begin().unwrap();
+    txn.insert("test", tuple(&schema, 61)).unwrap();
+    txn.delete(Key::new("test", &rids[3])).unwrap();
+    txn.update(Key::new("test", &rids[40]), tuple(&schema, 62))
+        .unwrap();
+    other.insert("test", tuple(&schema, 63)).unwrap();
+    let mut expect = scan_txn(&txn);
+    expect.sort_by_key(|(rid, _)| rid.clone());
+    for workers in [1, 3, 8, 100] {
+        let map: ScanMap<_> = Arc::new(|rid, tuple| Ok(Some((rid, tuple))));
+        let mut rows = txn
+            .parallel_scan("test", workers, map)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        rows.sort_by_key(|(rid, _)| rid.clone());
+        assert_eq!(expect, rows, "{workers} workers");
+    }
+
+    // An error in a worker is emitted, and stops only that worker.
+    let failing = rids[20].clone();
+    let map: ScanMap<_> = Arc::new(move |rid, _| match rid == failing {
+        true => Err(Error::InvalidData("bad row".to_string())),
+        false => Ok(Some(rid)),
+    });
+    let results: Vec<_> = txn.parallel_scan("test", 4, map).unwrap().collect();
+    let errors: Vec<_> = results.iter().filter(|result| result.is_err()).collect();
+    assert_eq!(
+        vec![&Err(Error::InvalidData("bad row".to_string()))],
+        errors
+    );
+    assert!(results.len() > 1);
+
+    // Dropping the iterator midway stops the workers, and a clone starts over.
+    let map: ScanMap<_> = Arc::new(|rid, _| Ok(Some(rid)));
+    let mut iter = txn.parallel_scan("test", 4, map).unwrap();
+    iter.next().unwrap().unwrap();
+    assert_eq!(expect.len(), iter.clone().count());
+    drop(iter);
+
+    // Terminating the transaction cancels the scan, in every worker with pages.
+    let map: ScanMap<_> = Arc::new(|rid, _| Ok(Some(rid)));
+    let iter = txn.parallel_scan("test", 2, map).unwrap();
+    assert!(simple.transaction_manager().terminate(txn.id()).unwrap());
+    let results: Vec<_> = iter.collect();
+    assert!(!results.is_empty());
+    assert!(results
+        .iter()
+        .all(|result| result == &Err(Error::Cancelled)));
+    other.rollback().unwrap();
+}
+
+#[test]
+fn test_count() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=60)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    assert_eq!(60, txn.count("test").unwrap());
+    txn.commit().unwrap();
+
+    // The count matches a scan: the transaction's own writes are counted,
+    // a concurrent one's aren't, and nor are rolled-back inserts.
+    let rolled_back = simple.begin().unwrap();
+    rolled_back.insert("test", tuple(&schema, 64)).unwrap();
+    rolled_back.rollback().unwrap();
+    let txn = simple.begin().unwrap();
+    let other = simple.begin().unwrap();
+    txn.insert("test", tuple(&schema, 61)).unwrap();
+    txn.delete(Key::new("test", &rids[3])).unwrap();
+    txn.delete(Key::new("test", &rids[4])).unwrap();
+    txn.update(Key::new("test", &rids[40]), tuple(&schema, 62))
+        .unwrap();
+    other.insert("test", tuple(&schema, 63)).unwrap();
+    other.delete(Key::new("test", &rids[5])).unwrap();
+    assert_eq!(scan_txn(&txn).len() as u64, txn.count("test").unwrap());
+    assert_eq!(59, txn.count("test").unwrap());
+    assert_eq!(60, other.count("test").unwrap());
+
+    // Terminating the transaction cancels the count.
+    assert!(simple.transaction_manager().terminate(txn.id()).unwrap());
+    assert_eq!(Err(Error::Cancelled), txn.count("test"));
+    other.rollback().unwrap();
+}
+
+#[test]
+fn test_read_only_rejects_writes() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let txn = simple.begin_read_only().unwrap();
+    assert!(txn.read_only());
+    let key = Key::new("test", &rid);
+    assert_eq!(Err(Error::ReadOnly), txn.insert("test", tuple(&schema, 2)));
+    assert_eq!(Err(Error::ReadOnly), txn.update(key, tuple(&schema, 2)));
+    assert_eq!(Err(Error::ReadOnly), txn.delete(key));
+    assert_eq!(Err(Error::ReadOnly), txn.delete_table("test"));
+    assert_eq!(Err(Error::ReadOnly), txn.create_table((*schema).clone()));
+    assert_eq!(tuple(&schema, 1), txn.get(key).unwrap());
+    assert!(
+        simple
+            .transaction_manager()
+            .get(txn.id())
+            .unwrap()
+            .unwrap()
+            .read_only
+    );
+    txn.commit().unwrap();
+    assert_eq!(vec![(rid, tuple(&schema, 1))], scan(&simple));
+}
+
+#[test]
+fn test_read_only_sees_consistent_snapshot() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rids: Vec<RecordId> = (1..=2)
+        .map(|seed| txn.insert("test", tuple(&schema, seed)).unwrap())
+        .collect();
+    txn.commit().unwrap();
+
+    let reader = simple.begin_read_only().unwrap();
+    let before = scan_txn(&reader);
+    let writer = simple.begin().unwrap();
+    // Read-only transactions are left out of other snapshots.
+    assert!(writer.snapshot().unwrap().active.is_empty());
+    writer
+        .update(Key::new("test", &rids[0]), tuple(&schema, 3))
+        .unwrap();
+    writer.insert("test", tuple(&schema, 4)).unwrap();
+    writer.commit().unwrap();
+
+    assert_eq!(before, scan_txn(&reader));
+    assert_eq!(
+        tuple(&schema, 1),
+        reader.get(Key::new("test", &rids[0])).unwrap()
+    );
+    // Vacuum keeps the versions the reader can still see.
+    assert_eq!(0, simple.vacuum(None).unwrap().versions);
+    reader.commit().unwrap();
+    assert_eq!(1, simple.vacuum(None).unwrap().versions);
+    assert_eq!(3, scan(&simple).len());
+}
+
+#[test]
+fn test_read_only_begin_commit_is_cheaper() {
+    const TXNS: u32 = 200;
+    let (simple, _) = setup();
+    let log = simple.engine.lock().unwrap().log_manager();
+    let time = |read_only: bool| {
+        let start = Instant::now();
+        for _ in 0..TXNS {
+            let txn = match read_only {
+                true => simple.begin_read_only().unwrap(),
+                false => simple.begin().unwrap(),
+            };
+            txn.commit().unwrap();
+        }
+        start.elapsed()
+    };
+
+    let (records, commits) = (log.records().unwrap().len(), log.stats().commits);
+    let read_only = time(true);
+    assert_eq!(records, log.records().unwrap().len());
+    assert_eq!(commits, log.stats().commits);
+    let read_write = time(false);
+    assert_eq!(commits + TXNS as u64, log.stats().commits);
+    assert!(
+        read_only < read_write,
+        "read-only took {read_only:?}, read-write took {read_write:?}"
+    );
+}
+
+#[test]
+fn test_prepare_then_commit_prepared() {
+    let (simple, schema) = setup();
+    let txn = simple.begin().unwrap();
+    let rid = txn.insert("test", tuple(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let txn = simple.begin().unwrap();
+    let id = txn.id();
+    txn.update(Key::new("test", &rid), tuple(&schema, 2))
+        .unwrap();
+    txn.prepare("gid").unwrap();
+    assert_eq!(
+        vec![PreparedTransaction {
+            id,
+            gid: "gid".to_string()
+        }],
+        simple.list_prepared().unwrap()
+    );
+    let info = simple.transaction_manager().get(id).unwrap().unwrap();
+    assert_eq!(TransactionState::Prepared, info.state);
+    assert_eq!(
+        Some(LockMode::Exclusive),
+        simple.lock_manager().lock_mode(id, &rid).unwrap()
+    );
+    assert_eq!(vec![tuple(&schema, 1)], tuples(&simple));
+
+    // The global id must be unique among prepared transactions.
+    let txn = simple.begin().unwrap();
+    txn.insert("test", tuple(&schema, 3)).unwrap();
+    assert!(matches!(txn.prepare("gid"), Err(Error::InvalidInput(_))));
+
+    simple.commit_prepared("gid").unwrap();
+    assert_eq!(vec![tuple(&schema, 2)], tuples(&simple));
+    assert!(simple.list_prepared().unwrap().is_empty());
+    assert_eq!(None, simple.lock_manager().lock_mode(id, &rid).unwrap());
+    assert!(matches!(
+        simple.commit_prepared("gid"),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+fn setup() -> (Simple<HeapTableManager>, Arc<Table>) {
+    setup_with_lock_manager(LockManager::default())
+}
+
+fn setup_with_lock_manager(locks: LockManager) -> (Simple<HeapTableManager>, Arc<Table>) {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(10)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .build_with_handle();
+    let simple = Simple::new_with_lock_manager(HeapTableManager::new(&bpm), locks);
+    let schema = Arc::new(utility::create_table_definition(4, "test"));
+
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    txn.commit().unwrap();
+    (simple, schema)
+}
+
+/// Creates a table named `indexed` with an indexed integer column `k`, and an
+/// integer column `v`.
+fn create_indexed_table(simple: &Simple<HeapTableManager>, unique: bool) -> Arc<Table> {
+    let mut schema = Table::new("indexed");
+    schema.add_column(
+        &Column::builder()
+            .name("k".to_string())
+            .data_type(DataType::Int)
+            .index(true)
+            .unique(unique)
+            .build(),
+    );
+    schema.add_column(
+        &Column::builder()
+            .name("v".to_string())
+            .data_type(DataType::Int)
+            .build(),
+    );
+    let txn = simple.begin().unwrap();
+    txn.create_table(schema.clone()).unwrap();
+    txn.commit().unwrap();
+    Arc::new(schema)
+}
+
+fn indexed_row(schema: &Arc<Table>, k: i32, v: i32) -> Tuple {
+    Row::from(vec![Field::Integer(k), Field::Integer(v)])
+        .to_tuple(schema)
+        .unwrap()
+}
+
+fn scan_table(simple: &Simple<HeapTableManager>, table: &str) -> Vec<Tuple> {
+    let txn = simple.begin().unwrap();
+    let rows = txn
+        .scan(table)
+        .unwrap()
+        .map(|item| item.unwrap().1)
+        .collect();
+    txn.commit().unwrap();
+    rows
+}
+
+fn tuple(schema: &Arc<Table>, seed: u64) -> Tuple {
+    utility::create_random_row(schema, Some(seed))
+        .to_tuple(schema)
+        .unwrap()
+}
+
+fn scan(simple: &Simple<HeapTableManager>) -> Vec<(RecordId, Tuple)> {
+    let txn = simple.begin().unwrap();
+    let rows = scan_txn(&txn);
+    txn.commit().unwrap();
+    rows
+}
+
+fn tuples(simple: &Simple<HeapTableManager>) -> Vec<Tuple> {
+    scan(simple).into_iter().map(|(_, tuple)| tuple).collect()
+}
+
+fn scan_txn(txn: &Transaction<HeapTableManager>) -> Vec<(RecordId, Tuple)> {
+    txn.scan("test").unwrap().collect::<Result<_, _>>().unwrap()
+}
@@ -1,28 +1,104 @@
 use crate::common::{Error, Result};
 use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
-use crate::storage::engine::Status;
-use crate::storage::heap::{TableHeap, TableHeapIterator};
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::storage::engine::{Status, VacuumStats};
+use crate::storage::heap::{TableHeap, TableHeapVersions};
+use crate::storage::index::TableIndex;
 use crate::storage::page::RecordId;
-use crate::storage::tuple::Tuple;
+use crate::storage::tuple::{Row, Tuple, TupleMetadata};
+use crate::types::field::Field;
+use crate::storage::wal::{LogManager, Lsn};
 use crate::storage::{engine, Engine, Key};
 use crate::types::Table;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, RwLock};
 
 pub struct HeapTableManager {
     heaps: HashMap<String, TableHeap>,
-    bpm: Arc<RwLock<BufferPoolManager>>,
+    bpm: Arc<BufferPoolManager>,
     key_directory: KeyDirectory,
+    /// The secondary indexes of each table, one per indexed column.
+    indexes: HashMap<String, Vec<TableIndex>>,
 }
 
 impl HeapTableManager {
-    pub fn new(bpm: &Arc<RwLock<BufferPoolManager>>) -> Self {
+    pub fn new(bpm: &Arc<BufferPoolManager>) -> Self {
         Self {
             heaps: HashMap::new(),
             bpm: Arc::clone(bpm),
             key_directory: HashMap::new(),
+            indexes: HashMap::new(),
         }
     }
+
+    fn has_indexes(&self, table_name: &str) -> bool {
+        self.indexes.get(table_name).is_some_and(|indexes| !indexes.is_empty())
+    }
+
+    /// Returns the key each of the table's indexes stores for `tuple`, in index order. Fails if a
+    /// key doesn't fit its index, so that callers can check before writing the tuple.
+    fn index_keys(&self, table_name: &str, tuple: &Tuple) -> Result<Vec<Field>> {
+        if !self.has_indexes(table_name) {
+            return Ok(Vec::new());
+        }
+        let indexes = &self.indexes[table_name];
+        let row = Row::from_tuple(tuple.clone(), &self.heaps[table_name].schema())?;
+        indexes.iter().map(|index| index.key(&row)).collect()
+    }
+
+    /// Adds the tuple at `rid` to the table's indexes, given its keys from `index_keys`.
+    fn add_to_indexes(&self, table_name: &str, rid: &RecordId, keys: Vec<Field>) -> Result<()> {
+        let indexes = self.indexes.get(table_name).into_iter().flatten();
+        for (index, key) in indexes.zip(keys) {
+            index.insert(key, rid.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Removes the live tuple at `rid` from the table's indexes, before it is tombstoned.
+    fn remove_from_indexes(&self, table_name: &str, rid: &RecordId) -> Result<()> {
+        if !self.has_indexes(table_name) {
+            return Ok(());
+        }
+        let heap = &self.heaps[table_name];
+        if heap.get_tuple_metadata(rid)?.is_deleted() {
+            return Ok(());
+        }
+        let keys = self.index_keys(table_name, &heap.get_tuple(rid)?)?;
+        for (index, key) in self.indexes[table_name].iter().zip(keys) {
+            index.delete(&key, rid)?;
+        }
+        Ok(())
+    }
+
+    /// Builds an index over each of the given columns of a table from its tuples, inserting each
+    /// index's entries in key order.
+    fn build_indexes(&self, table_name: &str, columns: Vec<usize>) -> Result<Vec<TableIndex>> {
+        let heap = &self.heaps[table_name];
+        let schema = heap.schema();
+        let indexes: Vec<TableIndex> = columns
+            .into_iter()
+            .map(|column| TableIndex::new(&schema, column, &self.bpm))
+            .collect();
+        if indexes.is_empty() {
+            return Ok(indexes);
+        }
+        let mut entries: Vec<Vec<(Field, RecordId)>> = vec![Vec::new(); indexes.len()];
+        for version in heap.versions() {
+            let (rid, _, tuple) = version?;
+            let row = Row::from_tuple(tuple, &schema)?;
+            for (index, keyed) in indexes.iter().zip(&mut entries) {
+                keyed.push((index.key(&row)?, rid.clone()));
+            }
+        }
+        for (index, mut keyed) in indexes.iter().zip(entries) {
+            keyed.sort();
+            for (key, rid) in keyed {
+                index.insert(key, rid)?;
+            }
+        }
+        Ok(indexes)
+    }
 }
 
 /// Maps table name -> [ Map: bytestream key -> RecordId ]
@@ -41,16 +117,40 @@ impl Engine for HeapTableManager {
         }
         self.key_directory
             .insert(table.name().to_string(), BTreeMap::new());
+        let indexes = (0..table.col_count())
+            .filter(|&column| table.get_column(column).is_indexed())
+            .map(|column| TableIndex::new(&table, column, &self.bpm))
+            .collect();
+        self.indexes.insert(table.name().to_string(), indexes);
         self.heaps
             .insert(table.name().to_string(), TableHeap::new(table, &self.bpm));
         Ok(())
     }
 
+    fn attach_table(&mut self, table: Table, page_ids: Vec<PageId>) -> Result<()> {
+        if self.key_directory.contains_key(table.name()) {
+            return Result::from(Error::InvalidInput(
+                "Attempted to insert table that already exists!".to_string(),
+            ));
+        }
+        let name = table.name().to_string();
+        let columns = (0..table.col_count())
+            .filter(|&column| table.get_column(column).is_indexed())
+            .collect();
+        let heap = TableHeap::open(table, &self.bpm, page_ids)?;
+        self.heaps.insert(name.clone(), heap);
+        let indexes = self.build_indexes(&name, columns)?;
+        self.indexes.insert(name.clone(), indexes);
+        self.key_directory.insert(name, BTreeMap::new());
+        Ok(())
+    }
+
     fn delete_table(&mut self, table_name: &str) -> Result<bool> {
         if !self.key_directory.contains_key(table_name) {
             return Ok(false);
         }
         self.key_directory.remove(table_name);
+        self.indexes.remove(table_name);
         self.heaps.remove(table_name);
         Ok(true)
     }
@@ -62,11 +162,19 @@ impl Engine for HeapTableManager {
         }
     }
 
+    fn indexes(&mut self, table_name: &str) -> Result<Vec<TableIndex>> {
+        match self.indexes.get(table_name) {
+            Some(indexes) => Ok(indexes.clone()),
+            None => Err(Error::InvalidData(table_name.to_string())),
+        }
+    }
+
     fn delete(&mut self, key: Key) -> Result<()> {
-        let heap = self
-            .heaps
-            .get_mut(key.table_name)
-            .ok_or_else(|| Error::InvalidData(key.table_name.to_string()))?;
+        if !self.heaps.contains_key(key.table_name) {
+            return Err(Error::InvalidData(key.table_name.to_string()));
+        }
+        self.remove_from_indexes(key.table_name, key.record_id)?;
+        let heap = self.heaps.get_mut(key.table_name).unwrap();
         heap.delete_tuple(key.record_id)
     }
 
@@ -78,12 +186,52 @@ impl Engine for HeapTableManager {
         heap.get_tuple(key.record_id)
     }
 
+    fn get_metadata(&mut self, key: Key) -> Result<TupleMetadata> {
+        let heap = self
+            .heaps
+            .get(key.table_name)
+            .ok_or_else(|| Error::InvalidData(key.table_name.to_string()))?;
+        heap.get_tuple_metadata(key.record_id)
+    }
+
+    fn set_metadata(&mut self, key: Key, metadata: TupleMetadata) -> Result<()> {
+        let heap = self
+            .heaps
+            .get(key.table_name)
+            .ok_or_else(|| Error::InvalidData(key.table_name.to_string()))?;
+        heap.update_tuple_metadata(key.record_id, &metadata)
+    }
+
     fn insert(&mut self, table_name: &str, value: Tuple) -> Result<RecordId> {
+        if !self.heaps.contains_key(table_name) {
+            return Err(Error::InvalidData(table_name.to_string()));
+        }
+        let keys = self.index_keys(table_name, &value)?;
+        let rid = self
+            .heaps
+            .get_mut(table_name)
+            .unwrap()
+            .insert_tuple(TupleMetadata::new(false), value)?;
+        self.add_to_indexes(table_name, &rid, keys)?;
+        Ok(rid)
+    }
+
+    fn insert_at(&mut self, key: Key, value: Tuple) -> Result<()> {
+        if !self.heaps.contains_key(key.table_name) {
+            return Err(Error::InvalidData(key.table_name.to_string()));
+        }
+        let keys = self.index_keys(key.table_name, &value)?;
+        let heap = self.heaps.get_mut(key.table_name).unwrap();
+        heap.insert_tuple_at(key.record_id, value)?;
+        self.add_to_indexes(key.table_name, key.record_id, keys)
+    }
+
+    fn next_record_id(&mut self, table_name: &str, value: &Tuple) -> Result<RecordId> {
         let heap = self
             .heaps
             .get_mut(table_name)
             .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
-        heap.insert_tuple(value)
+        heap.next_record_id(value)
     }
 
     fn scan(&mut self, table_name: &str) -> Self::ScanIterator<'_>
@@ -94,19 +242,210 @@ impl Engine for HeapTableManager {
             .heaps
             .get_mut(table_name)
             .unwrap_or_else(|| panic!("Could not access table {table_name}"));
-        ScanIterator { inner: heap.iter() }
+        ScanIterator {
+            inner: heap.versions(),
+        }
+    }
+
+    fn scan_rev(&mut self, table_name: &str) -> Self::ScanIterator<'_>
+    where
+        Self: Sized,
+    {
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .unwrap_or_else(|| panic!("Could not access table {table_name}"));
+        ScanIterator {
+            inner: heap.versions_rev(),
+        }
+    }
+
+    fn page_ids(&mut self, table_name: &str) -> Result<Vec<PageId>> {
+        let heap = self
+            .heaps
+            .get(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        Ok(heap.page_ids())
+    }
+
+    fn scan_page(
+        &mut self,
+        table_name: &str,
+        page_id: PageId,
+    ) -> Result<Vec<(RecordId, TupleMetadata, Tuple)>> {
+        let heap = self
+            .heaps
+            .get(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.page_versions(&page_id)
+    }
+
+    fn scan_page_metadata(
+        &mut self,
+        table_name: &str,
+        page_id: PageId,
+    ) -> Result<Vec<(RecordId, TupleMetadata)>> {
+        let heap = self
+            .heaps
+            .get(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.page_metadata(&page_id)
     }
 
     fn scan_dyn(&mut self) -> Box<dyn engine::ScanIterator + '_> {
         todo!()
     }
 
-    fn update(&mut self, key: Key, value: Tuple) -> Result<()> {
+    fn update(&mut self, key: Key, value: Tuple) -> Result<RecordId> {
+        if !self.heaps.contains_key(key.table_name) {
+            return Err(Error::InvalidData(key.table_name.to_string()));
+        }
+        let keys = self.index_keys(key.table_name, &value)?;
+        self.remove_from_indexes(key.table_name, key.record_id)?;
+        let heap = self.heaps.get_mut(key.table_name).unwrap();
+        let rid = heap.update_tuple(key.record_id, value)?;
+        self.add_to_indexes(key.table_name, &rid, keys)?;
+        Ok(rid)
+    }
+
+    fn set_lsn(&mut self, key: Key, lsn: Lsn) -> Result<()> {
         let heap = self
             .heaps
-            .get_mut(key.table_name)
+            .get(key.table_name)
             .ok_or_else(|| Error::InvalidData(key.table_name.to_string()))?;
-        heap.update_tuple(key.record_id, value)
+        heap.set_page_lsn(&key.record_id.page_id(), lsn)
+    }
+
+    fn vacuum(
+        &mut self,
+        table_name: &str,
+        removable: &dyn Fn(&TupleMetadata) -> bool,
+    ) -> Result<VacuumStats> {
+        let indexed = self.has_indexes(table_name);
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        // Removed versions can't be read once their payload is reclaimed, so hold on to the
+        // candidates beforehand to look up their index keys.
+        let mut candidates = BTreeMap::new();
+        if indexed {
+            for version in heap.versions() {
+                let (rid, metadata, tuple) = version?;
+                if !metadata.is_deleted() && removable(&metadata) {
+                    candidates.insert(rid, tuple);
+                }
+            }
+        }
+        let (removed, stats) = heap.vacuum(removable)?;
+        for rid in &removed {
+            if let Some(tuple) = candidates.remove(rid) {
+                let keys = self.index_keys(table_name, &tuple)?;
+                for (index, key) in self.indexes[table_name].iter().zip(keys) {
+                    index.delete(&key, rid)?;
+                }
+            }
+        }
+        let removed: BTreeSet<RecordId> = removed.into_iter().collect();
+        if let Some(keys) = self.key_directory.get_mut(table_name) {
+            keys.retain(|_, rid| !removed.contains(rid));
+        }
+        Ok(stats)
+    }
+
+    fn vacuum_full(
+        &mut self,
+        table_name: &str,
+        removable: &dyn Fn(&TupleMetadata) -> bool,
+    ) -> Result<VacuumStats> {
+        let heap = self
+            .heaps
+            .get(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        let old_bytes = heap.payload_bytes()?;
+        // Stream the kept versions into the new chain, remembering where each came from.
+        let mut old_rids = Vec::new();
+        let mut stats = VacuumStats::default();
+        let kept = heap.versions().filter_map(|version| {
+            let (rid, metadata, tuple) = match version {
+                Ok(version) => version,
+                Err(err) => return Some(Err(err)),
+            };
+            if removable(&metadata) {
+                stats.versions += 1;
+                return None;
+            }
+            old_rids.push(rid);
+            Some(Ok((metadata, tuple)))
+        });
+        let (rebuilt, new_rids) = heap.rebuild(kept)?;
+        stats.bytes = match rebuilt.payload_bytes() {
+            Ok(new_bytes) => old_bytes - new_bytes,
+            Err(err) => {
+                rebuilt.free_pages();
+                return Err(err);
+            }
+        };
+        let old = self.heaps.insert(table_name.to_string(), rebuilt);
+        stats.pages = old.map_or(0, |old| old.free_pages());
+
+        let moved: HashMap<RecordId, RecordId> = old_rids.into_iter().zip(new_rids).collect();
+        if let Some(keys) = self.key_directory.get_mut(table_name) {
+            keys.retain(|_, rid| match moved.get(rid) {
+                Some(new_rid) => {
+                    *rid = new_rid.clone();
+                    true
+                }
+                None => false,
+            });
+        }
+
+        // Build each index anew, inserting its entries in key order. Like those of dropped tables,
+        // the pages of the old index trees aren't reclaimed.
+        if self.has_indexes(table_name) {
+            let columns = self.indexes[table_name].iter().map(TableIndex::column);
+            let indexes = self.build_indexes(table_name, columns.collect())?;
+            self.indexes.insert(table_name.to_string(), indexes);
+        }
+        Ok(stats)
+    }
+
+    fn table_names(&mut self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.heaps.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn get_lsn(&mut self, key: Key) -> Result<Lsn> {
+        let heap = self
+            .heaps
+            .get(key.table_name)
+            .ok_or_else(|| Error::InvalidData(key.table_name.to_string()))?;
+        heap.page_lsn(&key.record_id.page_id())
+    }
+
+    fn checkpoint(&mut self) -> Result<Vec<(PageId, Lsn)>> {
+        let info = self.bpm.checkpoint()?;
+        // The log is truncated at the checkpoint, so every dirty page must be written, pinned
+        // ones included. Holding the engine keeps them from changing while they are.
+        let mut dirty_pages = info.flushed;
+        for (page_id, lsn) in info.skipped {
+            match self.bpm.force_log_and_flush(&page_id) {
+                // An evicted page was written back as it was.
+                Ok(_) | Err(Error::PageNotInPool(_)) => dirty_pages.push((page_id, lsn)),
+                Err(error) => return Err(error),
+            }
+        }
+        dirty_pages.sort();
+        Ok(dirty_pages)
+    }
+
+    fn log_manager(&self) -> Arc<LogManager> {
+        self.bpm.log_manager()
+    }
+
+    fn disk_manager(&self) -> Arc<RwLock<DiskManager>> {
+        self.bpm.disk_manager()
     }
 
     fn status(&mut self) -> Result<Status> {
@@ -115,13 +454,13 @@ impl Engine for HeapTableManager {
 }
 
 pub struct ScanIterator<'a> {
-    inner: TableHeapIterator<'a>,
+    inner: TableHeapVersions<'a>,
 }
 
 impl Iterator for ScanIterator<'_> {
-    type Item = Result<(RecordId, Tuple)>;
+    type Item = Result<(RecordId, TupleMetadata, Tuple)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(Ok)
+        self.inner.next()
     }
 }
@@ -1,13 +1,22 @@
+use crate::storage::simple::{TxnId, INVALID_TXN_ID};
 use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug, Copy, Deserialize, Serialize)]
 pub struct TupleMetadata {
     is_deleted: bool,
+    // The transaction that created this version of the tuple.
+    insert_txn_id: TxnId,
+    // The transaction that deleted or replaced this version, if any.
+    delete_txn_id: TxnId,
 }
 
 impl TupleMetadata {
     pub fn new(is_deleted: bool) -> Self {
-        Self { is_deleted }
+        Self {
+            is_deleted,
+            insert_txn_id: INVALID_TXN_ID,
+            delete_txn_id: INVALID_TXN_ID,
+        }
     }
 
     pub fn deleted_payload_metadata() -> TupleMetadata {
@@ -22,7 +31,26 @@ impl TupleMetadata {
         self.is_deleted
     }
 
+    pub fn insert_txn_id(&self) -> TxnId {
+        self.insert_txn_id
+    }
+
+    pub fn set_insert_txn_id(&mut self, txn_id: TxnId) {
+        self.insert_txn_id = txn_id;
+    }
+
+    pub fn delete_txn_id(&self) -> TxnId {
+        self.delete_txn_id
+    }
+
+    pub fn set_delete_txn_id(&mut self, txn_id: TxnId) {
+        self.delete_txn_id = txn_id;
+    }
+
     pub fn to_string(&self) -> String {
-        format!("Deleted: {})", self.is_deleted)
+        format!(
+            "Deleted: {}, Inserted by: {}, Deleted by: {})",
+            self.is_deleted, self.insert_txn_id, self.delete_txn_id
+        )
     }
 }
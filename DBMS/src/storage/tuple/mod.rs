@@ -6,5 +6,5 @@ mod tuple;
 mod tests;
 
 pub use metadata::TupleMetadata;
-pub use row::{Row, RowIterator, Rows};
+pub use row::{field_decodes, get_field, Row, RowIterator, Rows};
 pub use tuple::Tuple;
@@ -2,10 +2,11 @@ use crate::common::{Error, Result};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Tuple;
 use crate::types::field::Field;
-use crate::types::{DataType, Table};
+use crate::types::{Column, DataType, Table};
 use dyn_clone::DynClone;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::slice::Iter;
 
 /// A row iterator.
@@ -162,40 +163,63 @@ impl Row {
     /// `bytes` contains u16 offsets for variable-length fields, followed
     /// by fixed-length fields, with variable-length fields at the end.
     pub fn deserialize(bytes: Vec<u8>, schema: &Table) -> Self {
-       // Get the offsets of the variable length text fields, if any exist.
-        let variable_field_offsets: Vec<u16> = (0..schema.variable_length_fields())
-            .map(|i| u16::from_be_bytes([bytes[2 * i], bytes[(2 * i) + 1]]))
-            .collect();
-
-        // The first byte in `bytes` of the field data
-        let field_data_start = variable_field_offsets.len() * 2;
-
         let values = schema
             .columns()
             .iter()
-            .map(|column| match column.get_data_type() {
-                DataType::Text => {
-                    // Get the index into the variable length field offset array.
-                    let offset_index = column.stored_offset() as usize;
-                    let start = *variable_field_offsets.get(offset_index).unwrap() as usize;
-                    let end = if offset_index == variable_field_offsets.len() - 1 {
-                        bytes.len()
-                    } else {
-                        *variable_field_offsets.get(offset_index + 1).unwrap() as usize
-                    };
-
-                    Field::deserialize(&bytes[start..end], DataType::Text)
-                }
-                datatype => {
-                    // Get the offset of the field in the byte stream.
-                    let start = column.stored_offset() as usize + field_data_start;
-                    let end = start + column.length_bytes() as usize;
-
-                    Field::deserialize(&bytes[start..end], datatype)
-                }
-            })
+            .map(|column| decode_field(&bytes, schema, column))
             .collect();
         Self { values }
+    }
+}
 
+thread_local! {
+    /// The number of fields decoded on this thread, see [`field_decodes`].
+    static FIELD_DECODES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the number of fields decoded from serialized rows on the current
+/// thread so far, whether by [`Row::deserialize`] or [`get_field`]. Lets tests
+/// and benchmarks measure how much decoding a query does.
+pub fn field_decodes() -> u64 {
+    FIELD_DECODES.with(|count| count.get())
+}
+
+/// Decodes the field at `index` of a serialized row, without decoding the
+/// others. See [`Row::serialize`] for the layout.
+pub fn get_field(bytes: &[u8], schema: &Table, index: usize) -> Result<Field> {
+    let column = schema.columns().get(index).ok_or(Error::OutOfBounds)?;
+    Ok(decode_field(bytes, schema, column))
+}
+
+/// Decodes a column's field of a serialized row.
+fn decode_field(bytes: &[u8], schema: &Table, column: &Column) -> Field {
+    FIELD_DECODES.with(|count| count.set(count.get() + 1));
+
+    // Gets the offset of a variable length text field.
+    let variable_fields = schema.variable_length_fields();
+    let variable_offset =
+        |i: usize| u16::from_be_bytes([bytes[2 * i], bytes[(2 * i) + 1]]) as usize;
+
+    match column.get_data_type() {
+        DataType::Text => {
+            // Get the index into the variable length field offset array.
+            let offset_index = column.stored_offset() as usize;
+            let start = variable_offset(offset_index);
+            let end = if offset_index == variable_fields - 1 {
+                bytes.len()
+            } else {
+                variable_offset(offset_index + 1)
+            };
+
+            Field::deserialize(&bytes[start..end], DataType::Text)
+        }
+        datatype => {
+            // Get the offset of the field in the byte stream, past the
+            // variable length field offsets.
+            let start = column.stored_offset() as usize + variable_fields * 2;
+            let end = start + column.length_bytes() as usize;
+
+            Field::deserialize(&bytes[start..end], datatype)
+        }
     }
 }
@@ -0,0 +1,290 @@
+use crate::common::Result;
+use crate::storage::disk::disk_manager::{DiskManager, PageId};
+use crate::storage::simple::{TxnId, INVALID_TXN_ID};
+use crate::storage::wal::{LogRecord, LogRecordBody, Lsn, INVALID_LSN};
+use crate::trace_event;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// When the log tail is forced to stable storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Flush and fsync the log tail every time a transaction commits.
+    #[default]
+    Commit,
+    /// Leave the tail buffered on commit. It is only forced when the buffer pool needs to write
+    /// a page whose changes are still in the tail, or when [`LogManager::flush`] is called.
+    Lazy,
+}
+
+/// How commits under [`SyncPolicy::Commit`] share fsyncs.
+///
+/// Committing transactions queue up behind a leader, which forces the log on behalf of every
+/// commit appended by then. Before doing so, the leader waits up to `window` for more commits to
+/// join, unless `max_size` commits are already waiting. Commits arriving while a force is under
+/// way always join the next group, so groups form even with a zero window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupCommit {
+    pub window: Duration,
+    pub max_size: usize,
+}
+
+impl Default for GroupCommit {
+    fn default() -> Self {
+        Self {
+            window: Duration::ZERO,
+            max_size: usize::MAX,
+        }
+    }
+}
+
+/// Counters for verifying how well commits are grouped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogStats {
+    /// Commit records appended.
+    pub commits: u64,
+    /// Times the log file was fsynced.
+    pub fsyncs: u64,
+}
+
+impl LogStats {
+    /// Returns the average number of commits made durable per fsync.
+    pub fn avg_group_size(&self) -> f64 {
+        self.commits as f64 / self.fsyncs.max(1) as f64
+    }
+}
+
+/// The commits waiting for the current group to be forced.
+#[derive(Debug, Default)]
+struct CommitGroup {
+    /// Whether a leader is collecting or forcing the group.
+    leader: bool,
+    /// Commits that joined since the last group was forced.
+    pending: usize,
+}
+
+/// Records that have been assigned an LSN but not yet written to the log file.
+#[derive(Debug)]
+struct LogTail {
+    next_lsn: Lsn,
+    /// The LSN of the last record in `buffer`.
+    last_lsn: Lsn,
+    buffer: Vec<u8>,
+}
+
+/// Appends log records to the disk manager's log file.
+///
+/// Records are buffered in memory until they are flushed; `durable_lsn` tracks the newest record
+/// known to be on stable storage, which the buffer pool consults before writing a page.
+#[derive(Debug)]
+pub struct LogManager {
+    disk_manager: Arc<RwLock<DiskManager>>,
+    sync_policy: SyncPolicy,
+    group_commit: GroupCommit,
+    tail: Mutex<LogTail>,
+    /// Serializes writers of the log file, so the tail can keep growing during a force.
+    flushing: Mutex<()>,
+    durable_lsn: AtomicU64,
+    group: Mutex<CommitGroup>,
+    /// Wakes the group's leader as commits join, and the group once it is durable.
+    group_changed: Condvar,
+    commits: AtomicU64,
+    fsyncs: AtomicU64,
+}
+
+impl LogManager {
+    pub fn new(disk_manager: Arc<RwLock<DiskManager>>, sync_policy: SyncPolicy) -> Self {
+        Self {
+            disk_manager,
+            sync_policy,
+            tail: Mutex::new(LogTail {
+                next_lsn: INVALID_LSN + 1,
+                last_lsn: INVALID_LSN,
+                buffer: Vec::new(),
+            }),
+            flushing: Mutex::new(()),
+            durable_lsn: AtomicU64::new(INVALID_LSN),
+            group_commit: GroupCommit::default(),
+            group: Mutex::new(CommitGroup::default()),
+            group_changed: Condvar::new(),
+            commits: AtomicU64::new(0),
+            fsyncs: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_group_commit(mut self, group_commit: GroupCommit) -> Self {
+        self.group_commit = group_commit;
+        self
+    }
+
+    pub fn sync_policy(&self) -> SyncPolicy {
+        self.sync_policy
+    }
+
+    pub fn group_commit(&self) -> GroupCommit {
+        self.group_commit
+    }
+
+    pub fn stats(&self) -> LogStats {
+        LogStats {
+            commits: self.commits.load(Ordering::SeqCst),
+            fsyncs: self.fsyncs.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Appends a record to the log tail, returning its LSN. The record is not durable until the
+    /// tail is flushed.
+    pub fn append(&self, txn_id: TxnId, body: LogRecordBody) -> Result<Lsn> {
+        let mut tail = self.tail.lock()?;
+        let lsn = tail.next_lsn;
+        let record = LogRecord { lsn, txn_id, body };
+        tail.buffer.extend(record.encode()?);
+        tail.next_lsn += 1;
+        tail.last_lsn = lsn;
+        Ok(lsn)
+    }
+
+    /// Appends a commit record for `txn_id` and, depending on the sync policy, forces it to disk
+    /// as part of a commit group. Returns only once the record is durable.
+    pub fn commit(&self, txn_id: TxnId) -> Result<Lsn> {
+        let lsn = self.append(txn_id, LogRecordBody::Commit)?;
+        self.commits.fetch_add(1, Ordering::SeqCst);
+        if self.sync_policy == SyncPolicy::Commit {
+            self.group_flush(lsn)?;
+        }
+        Ok(lsn)
+    }
+
+    /// Joins the current commit group and waits until `lsn` is durable, forcing the log as the
+    /// group's leader if there is none.
+    fn group_flush(&self, lsn: Lsn) -> Result<()> {
+        let mut group = self.group.lock()?;
+        group.pending += 1;
+        self.group_changed.notify_all();
+        while self.durable_lsn() < lsn {
+            if group.leader {
+                group = self.group_changed.wait(group)?;
+                continue;
+            }
+            group.leader = true;
+            let deadline = Instant::now() + self.group_commit.window;
+            while group.pending < self.group_commit.max_size {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                group = self.group_changed.wait_timeout(group, deadline - now)?.0;
+            }
+            // Everyone pending has appended their commit record, so one force covers them all.
+            group.pending = 0;
+            drop(group);
+            let result = self.flush(lsn);
+            group = self.group.lock()?;
+            group.leader = false;
+            self.group_changed.notify_all();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Makes every record up to and including `lsn` durable. Since the tail is written as a whole,
+    /// records appended after `lsn` may become durable as well.
+    pub fn flush(&self, lsn: Lsn) -> Result<()> {
+        if self.durable_lsn() >= lsn {
+            return Ok(());
+        }
+        let _flushing = self.flushing.lock()?;
+        // A concurrent flush may have covered `lsn` while we waited.
+        if self.durable_lsn() >= lsn {
+            return Ok(());
+        }
+        let (buffer, last_lsn) = {
+            let mut tail = self.tail.lock()?;
+            (std::mem::take(&mut tail.buffer), tail.last_lsn)
+        };
+        if !buffer.is_empty() {
+            let mut disk_manager = self.disk_manager.write()?;
+            let written = disk_manager
+                .append_log(&buffer)
+                .and_then(|_| disk_manager.sync_log());
+            if let Err(error) = written {
+                // Put the records back in front of any appended since, to retry later.
+                trace_event!(name: "log_write_retry", bytes = buffer.len(), %error);
+                let mut tail = self.tail.lock()?;
+                tail.buffer.splice(0..0, buffer);
+                return Err(error);
+            }
+            self.fsyncs.fetch_add(1, Ordering::SeqCst);
+        }
+        self.durable_lsn.store(last_lsn, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns the LSN of the newest record on stable storage.
+    pub fn durable_lsn(&self) -> Lsn {
+        self.durable_lsn.load(Ordering::SeqCst)
+    }
+
+    /// Reads back and decodes every durable record in the log file.
+    pub fn records(&self) -> Result<Vec<LogRecord>> {
+        let bytes = self.disk_manager.write()?.read_log()?;
+        LogRecord::decode_all(&bytes)
+    }
+
+    /// Logs a checkpoint taken once every dirty page was written back, and makes it the one
+    /// recovery starts from. Records before the recovery horizon, the older of the checkpoint
+    /// and the begin record of the oldest transaction in `active`, are then discarded.
+    ///
+    /// The caller must keep pages from being modified between writing them back and this call.
+    pub fn checkpoint(
+        &self,
+        active: Vec<(TxnId, Lsn)>,
+        dirty_pages: Vec<(PageId, Lsn)>,
+    ) -> Result<Lsn> {
+        let oldest = active.iter().map(|(_, lsn)| *lsn).min();
+        let lsn = self.append(
+            INVALID_TXN_ID,
+            LogRecordBody::Checkpoint {
+                active,
+                dirty_pages,
+            },
+        )?;
+        self.flush(lsn)?;
+        self.disk_manager.write()?.set_checkpoint_lsn(lsn)?;
+        self.truncate(oldest.map_or(lsn, |oldest| oldest.min(lsn)))?;
+        Ok(lsn)
+    }
+
+    /// Returns the LSN of the last completed checkpoint, if any.
+    pub fn last_checkpoint(&self) -> Result<Option<Lsn>> {
+        self.disk_manager.write()?.checkpoint_lsn()
+    }
+
+    /// Discards every durable record with an LSN below `horizon`.
+    pub fn truncate(&self, horizon: Lsn) -> Result<()> {
+        let _flushing = self.flushing.lock()?;
+        let mut disk_manager = self.disk_manager.write()?;
+        let bytes = disk_manager.read_log()?;
+        let offset = LogRecord::decode_with_offsets(&bytes)?
+            .into_iter()
+            .find(|(_, record)| record.lsn >= horizon)
+            .map_or(bytes.len(), |(offset, _)| offset);
+        if offset > 0 {
+            disk_manager.truncate_log(offset as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the records that never made it to the log file and continues numbering after
+    /// `last_lsn`, the newest durable record. Used by recovery after a crash.
+    pub fn restart(&self, last_lsn: Lsn) -> Result<()> {
+        let _flushing = self.flushing.lock()?;
+        let mut tail = self.tail.lock()?;
+        tail.buffer.clear();
+        tail.last_lsn = last_lsn;
+        tail.next_lsn = last_lsn + 1;
+        self.durable_lsn.store(last_lsn, Ordering::SeqCst);
+        Ok(())
+    }
+}
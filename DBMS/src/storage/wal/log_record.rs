@@ -0,0 +1,107 @@
+use crate::common::{Error, Result};
+use crate::storage::disk::disk_manager::PageId;
+use crate::storage::page::RecordId;
+use crate::storage::simple::TxnId;
+use crate::storage::tuple::Tuple;
+use serde::{Deserialize, Serialize};
+use std::mem::size_of;
+
+/// Log sequence number. LSNs are assigned in increasing order starting at 1.
+pub type Lsn = u64;
+
+/// The LSN of a page that has never been modified through the log.
+pub const INVALID_LSN: Lsn = 0;
+
+/// A single entry in the write-ahead log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub lsn: Lsn,
+    /// The transaction that produced the record.
+    pub txn_id: TxnId,
+    pub body: LogRecordBody,
+}
+
+/// The change described by a log record. Tuple modifications carry enough information to both
+/// redo (after image) and undo (before image) the change on the page identified by `rid`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LogRecordBody {
+    Begin,
+    Commit,
+    /// The transaction rolled back; its changes were undone in place.
+    Abort,
+    /// The transaction was prepared for two-phase commit under the coordinator's global id
+    /// `gid`. Until a commit or abort record follows, recovery must leave its changes in place.
+    Prepare {
+        gid: String,
+    },
+    Insert {
+        table: String,
+        rid: RecordId,
+        after: Tuple,
+    },
+    /// The version at `rid` was replaced by a new version at `new_rid`.
+    Update {
+        table: String,
+        rid: RecordId,
+        new_rid: RecordId,
+        before: Tuple,
+        after: Tuple,
+    },
+    Delete {
+        table: String,
+        rid: RecordId,
+        before: Tuple,
+    },
+    /// Every dirty page had been written back when the record was appended, so recovery only
+    /// needs to redo records after it. `active` lists the transactions still running, with the
+    /// LSNs of their begin records, and `dirty_pages` the pages written, with their LSNs.
+    Checkpoint {
+        active: Vec<(TxnId, Lsn)>,
+        dirty_pages: Vec<(PageId, Lsn)>,
+    },
+}
+
+impl LogRecord {
+    /// Encodes the record as a little-endian `u32` length prefix followed by the bincode payload.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let payload = bincode::serialize(self)?;
+        let mut bytes = Vec::with_capacity(size_of::<u32>() + payload.len());
+        bytes.extend_from_slice(&u32::try_from(payload.len())?.to_le_bytes());
+        bytes.extend(payload);
+        Ok(bytes)
+    }
+
+    /// Decodes every record in `bytes`, which must be a concatenation of [`Self::encode`] outputs.
+    pub fn decode_all(bytes: &[u8]) -> Result<Vec<LogRecord>> {
+        Ok(Self::decode_with_offsets(bytes)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect())
+    }
+
+    /// Like [`Self::decode_all`], but also returns the byte offset each record starts at.
+    pub fn decode_with_offsets(bytes: &[u8]) -> Result<Vec<(usize, LogRecord)>> {
+        let mut records = Vec::new();
+        let mut cursor = 0;
+        while cursor < bytes.len() {
+            let len_end = cursor + size_of::<u32>();
+            if len_end > bytes.len() {
+                return Err(Error::InvalidData(
+                    "Truncated log record header".to_string(),
+                ));
+            }
+            let len = u32::from_le_bytes(bytes[cursor..len_end].try_into()?) as usize;
+            if len_end + len > bytes.len() {
+                return Err(Error::InvalidData(
+                    "Truncated log record payload".to_string(),
+                ));
+            }
+            records.push((
+                cursor,
+                bincode::deserialize(&bytes[len_end..(len_end + len)])?,
+            ));
+            cursor = len_end + len;
+        }
+        Ok(records)
+    }
+}
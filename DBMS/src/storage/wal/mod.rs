@@ -0,0 +1,8 @@
+//! Write-ahead logging of tuple modifications.
+mod log_manager;
+mod log_record;
+#[cfg(test)]
+mod tests;
+
+pub use log_manager::{GroupCommit, LogManager, LogStats, SyncPolicy};
+pub use log_record::{LogRecord, LogRecordBody, Lsn, INVALID_LSN};
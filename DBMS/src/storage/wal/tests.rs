@@ -0,0 +1,457 @@
+use crate::common::utility;
+use crate::concurrency::LockMode;
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::page::{Page, RecordId};
+use crate::storage::simple::{PreparedTransaction, Simple, Transaction, TxnId};
+use crate::storage::tables::HeapTableManager;
+use crate::storage::tuple::Tuple;
+use crate::storage::wal::{
+    GroupCommit, LogManager, LogRecord, LogRecordBody, SyncPolicy, INVALID_LSN,
+};
+use crate::storage::Key;
+use crate::types::Table;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_log_record_roundtrip() {
+    let schema = Arc::new(utility::create_table_definition(4, "test"));
+    let tuple = utility::create_random_row(&schema, Some(7))
+        .to_tuple(&schema)
+        .unwrap();
+    let records = vec![
+        LogRecord {
+            lsn: 1,
+            txn_id: 3,
+            body: LogRecordBody::Begin,
+        },
+        LogRecord {
+            lsn: 2,
+            txn_id: 3,
+            body: LogRecordBody::Insert {
+                table: "test".to_string(),
+                rid: RecordId::new(4, 2),
+                after: tuple,
+            },
+        },
+        LogRecord {
+            lsn: 3,
+            txn_id: 3,
+            body: LogRecordBody::Prepare {
+                gid: "gid".to_string(),
+            },
+        },
+    ];
+    let bytes: Vec<u8> = records.iter().flat_map(|r| r.encode().unwrap()).collect();
+
+    assert_eq!(records, LogRecord::decode_all(&bytes).unwrap());
+    assert!(LogRecord::decode_all(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn test_workload_log_decodes_in_order() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let row = |seed| {
+        utility::create_random_row(&schema, Some(seed))
+            .to_tuple(&schema)
+            .unwrap()
+    };
+    let (first, second, updated) = (row(1), row(2), row(3));
+
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    let rid1 = txn.insert("test", first.clone()).unwrap();
+    let rid2 = txn.insert("test", second.clone()).unwrap();
+    let rid3 = txn
+        .update(Key::new("test", &rid1), updated.clone())
+        .unwrap();
+    txn.delete(Key::new("test", &rid2)).unwrap();
+    let txn_id = txn.id();
+    txn.commit().unwrap();
+
+    let log = bpm.log_manager();
+    let records = log.records().unwrap();
+    let bodies: Vec<LogRecordBody> = records.iter().map(|r| r.body.clone()).collect();
+    assert_eq!(
+        vec![
+            LogRecordBody::Begin,
+            LogRecordBody::Insert {
+                table: "test".to_string(),
+                rid: rid1.clone(),
+                after: first.clone(),
+            },
+            LogRecordBody::Insert {
+                table: "test".to_string(),
+                rid: rid2.clone(),
+                after: second.clone(),
+            },
+            LogRecordBody::Update {
+                table: "test".to_string(),
+                rid: rid1,
+                new_rid: rid3,
+                before: first,
+                after: updated,
+            },
+            LogRecordBody::Delete {
+                table: "test".to_string(),
+                rid: rid2,
+                before: second,
+            },
+            LogRecordBody::Commit,
+        ],
+        bodies
+    );
+    assert!(records.iter().all(|r| r.txn_id == txn_id));
+    assert!(records.windows(2).all(|w| w[0].lsn < w[1].lsn));
+    assert_eq!(records.last().unwrap().lsn, log.durable_lsn());
+}
+
+#[test]
+fn test_flush_blocked_until_log_durable() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Lazy);
+    let tuple = utility::create_random_row(&schema, Some(1))
+        .to_tuple(&schema)
+        .unwrap();
+
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    let rid = txn.insert("test", tuple).unwrap();
+    txn.commit().unwrap();
+
+    let page_id = rid.page_id();
+    let log = bpm.log_manager();
+    let page_lsn = bpm.fetch_page(&page_id).unwrap().read().unwrap().lsn();
+    bpm.set_is_dirty(&page_id, true);
+    assert!(page_lsn > log.durable_lsn());
+
+    // The page's changes are only in the log tail, so the page may not reach disk yet.
+    assert!(!bpm.flush_page(&page_id).unwrap());
+    assert_eq!(Some(true), bpm.is_dirty(&page_id));
+
+    log.flush(page_lsn).unwrap();
+    assert!(bpm.flush_page(&page_id).unwrap());
+    assert_eq!(Some(false), bpm.is_dirty(&page_id));
+}
+
+#[test]
+fn test_group_commit_amortizes_fsyncs() {
+    let log = group_commit_log(Duration::from_millis(1), 16);
+    let (threads, commits_per_thread) = (16, 50);
+    thread::scope(|s| {
+        for thread in 0..threads {
+            let log = &log;
+            s.spawn(move || {
+                for i in 0..commits_per_thread {
+                    let lsn = log.commit(thread * commits_per_thread + i + 1).unwrap();
+                    assert!(log.durable_lsn() >= lsn);
+                }
+            });
+        }
+    });
+
+    let stats = log.stats();
+    assert_eq!(threads * commits_per_thread, stats.commits);
+    assert!(
+        stats.fsyncs < stats.commits / 4,
+        "{} fsyncs for {} commits",
+        stats.fsyncs,
+        stats.commits
+    );
+    assert!(stats.avg_group_size() > 4.0);
+    assert_eq!(stats.commits as usize, log.records().unwrap().len());
+}
+
+#[test]
+fn test_commit_acknowledged_only_once_durable() {
+    let log = group_commit_log(Duration::from_millis(1), 4);
+    thread::scope(|s| {
+        for thread in 0..8 {
+            let log = &log;
+            s.spawn(move || {
+                for i in 0..10 {
+                    let lsn = log.commit(thread * 10 + i + 1).unwrap();
+                    // The commit record must already be in the log file.
+                    let records = log.records().unwrap();
+                    assert!(records.iter().any(|r| r.lsn == lsn));
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_lone_committer_waits_at_most_window() {
+    let window = Duration::from_millis(50);
+    let log = group_commit_log(window, 16);
+    let start = Instant::now();
+    let lsn = log.commit(1).unwrap();
+    assert!(start.elapsed() < window * 10);
+    assert_eq!(lsn, log.durable_lsn());
+
+    // A full group doesn't wait for the window at all.
+    let log = group_commit_log(Duration::from_secs(60), 1);
+    let start = Instant::now();
+    log.commit(1).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(
+        1,
+        log.stats().fsyncs,
+        "a lone commit should take a single fsync"
+    );
+}
+
+#[test]
+fn test_recovery_replays_only_after_checkpoint() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let rows: Vec<Tuple> = (0..8).map(|seed| row(&schema, seed)).collect();
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    for row in &rows[..5] {
+        txn.insert("test", row.clone()).unwrap();
+    }
+    txn.commit().unwrap();
+
+    // Without a checkpoint, recovery replays the whole log.
+    bpm.crash_for_test();
+    let stats = simple.recover().unwrap();
+    assert_eq!(INVALID_LSN, stats.redo_from);
+    assert_eq!((5, 5), (stats.replayed, stats.redone));
+    assert_eq!(rows[..5], scan(&simple.begin().unwrap()));
+
+    let checkpoint = simple.checkpoint().unwrap();
+    let txn = simple.begin().unwrap();
+    for row in &rows[5..] {
+        txn.insert("test", row.clone()).unwrap();
+    }
+    txn.commit().unwrap();
+
+    bpm.crash_for_test();
+    let stats = simple.recover().unwrap();
+    assert_eq!(checkpoint, stats.redo_from);
+    assert_eq!((3, 3), (stats.replayed, stats.redone));
+    assert_eq!(rows, scan(&simple.begin().unwrap()));
+
+    // Pages that already have a change aren't redone again.
+    let stats = simple.recover().unwrap();
+    assert_eq!((3, 0), (stats.replayed, stats.redone));
+    assert_eq!(rows, scan(&simple.begin().unwrap()));
+}
+
+#[test]
+fn test_recovery_undoes_uncommitted() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    let rid = txn.insert("test", row(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+    simple.checkpoint().unwrap();
+
+    // The loser's changes become durable along with the winner's commit.
+    let loser = simple.begin().unwrap();
+    loser.insert("test", row(&schema, 2)).unwrap();
+    loser.delete(Key::new("test", &rid)).unwrap();
+    let winner = simple.begin().unwrap();
+    winner.insert("test", row(&schema, 3)).unwrap();
+    winner.commit().unwrap();
+    let loser_id = loser.id();
+    drop(loser);
+
+    bpm.crash_for_test();
+    let stats = simple.recover().unwrap();
+    assert_eq!((3, 3), (stats.replayed, stats.redone));
+    assert_eq!((2, 1), (stats.undone, stats.losers));
+    let txn = simple.begin().unwrap();
+    assert!(txn.id() > loser_id);
+    assert_eq!(vec![row(&schema, 1), row(&schema, 3)], scan(&txn));
+
+    // The loser is logged as aborted, and its row lock released.
+    txn.delete(Key::new("test", &rid)).unwrap();
+    txn.commit().unwrap();
+    let log = bpm.log_manager();
+    assert!(log
+        .records()
+        .unwrap()
+        .iter()
+        .any(|r| r.txn_id == loser_id && r.body == LogRecordBody::Abort));
+}
+
+#[test]
+fn test_checkpoint_truncates_log() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let log = bpm.log_manager();
+    let log_size = || bpm.disk_manager.write().unwrap().log_size().unwrap();
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    txn.commit().unwrap();
+    for seed in 0..20 {
+        let txn = simple.begin().unwrap();
+        txn.insert("test", row(&schema, seed)).unwrap();
+        txn.commit().unwrap();
+    }
+    assert_eq!(None, log.last_checkpoint().unwrap());
+
+    let size = log_size();
+    let checkpoint = simple.checkpoint().unwrap();
+    assert!(log_size() < size, "{} bytes left of {size}", log_size());
+    assert_eq!(Some(checkpoint), log.last_checkpoint().unwrap());
+    let records = log.records().unwrap();
+    assert_eq!(1, records.len());
+    assert_eq!(checkpoint, records[0].lsn);
+    assert_eq!(Some(false), bpm.is_dirty(&1));
+
+    // A running transaction holds the horizon back to its begin record.
+    let active = simple.begin().unwrap();
+    active.insert("test", row(&schema, 20)).unwrap();
+    let txn = simple.begin().unwrap();
+    txn.insert("test", row(&schema, 21)).unwrap();
+    txn.commit().unwrap();
+    let checkpoint = simple.checkpoint().unwrap();
+    let records = log.records().unwrap();
+    assert_eq!(
+        (active.id(), LogRecordBody::Begin),
+        (records[0].txn_id, records[0].body.clone())
+    );
+    let LogRecordBody::Checkpoint {
+        active: running, ..
+    } = &records.last().unwrap().body
+    else {
+        panic!("expected a checkpoint record last");
+    };
+    assert_eq!(&vec![(active.id(), records[0].lsn)], running);
+    assert_eq!(checkpoint, records.last().unwrap().lsn);
+    active.commit().unwrap();
+}
+
+#[test]
+fn test_background_checkpointer() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let log = bpm.log_manager();
+    let txn = simple.begin().unwrap();
+    txn.create_table((*schema).clone()).unwrap();
+    txn.insert("test", row(&schema, 1)).unwrap();
+    txn.commit().unwrap();
+
+    let checkpointer = simple.start_checkpointer(Duration::from_millis(10));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while log.last_checkpoint().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "no checkpoint was taken");
+        thread::sleep(Duration::from_millis(10));
+    }
+    checkpointer.stop().unwrap();
+}
+
+#[test]
+fn test_prepared_commit_survives_crash() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let (id, rid) = prepare_after_checkpoint(&simple, &schema);
+
+    bpm.crash_for_test();
+    let stats = simple.recover().unwrap();
+    assert_eq!((1, 0), (stats.prepared, stats.losers));
+    assert_eq!(
+        vec![PreparedTransaction {
+            id,
+            gid: "gid".to_string()
+        }],
+        simple.list_prepared().unwrap()
+    );
+    // The in-doubt transaction keeps its changes and its locks, but stays
+    // invisible until the coordinator decides.
+    assert_eq!(
+        Some(LockMode::Exclusive),
+        simple.lock_manager().lock_mode(id, &rid).unwrap()
+    );
+    let txn = simple.begin().unwrap();
+    assert!(txn.id() > id);
+    assert_eq!(vec![row(&schema, 1)], scan(&txn));
+    txn.commit().unwrap();
+
+    simple.commit_prepared("gid").unwrap();
+    let txn = simple.begin().unwrap();
+    assert_eq!(vec![row(&schema, 2)], scan(&txn));
+    txn.commit().unwrap();
+    assert!(simple.list_prepared().unwrap().is_empty());
+}
+
+#[test]
+fn test_prepared_rollback_after_recovery() {
+    let (bpm, simple, schema) = setup(SyncPolicy::Commit);
+    let (id, rid) = prepare_after_checkpoint(&simple, &schema);
+
+    bpm.crash_for_test();
+    simple.recover().unwrap();
+    simple.rollback_prepared("gid").unwrap();
+    assert!(simple.list_prepared().unwrap().is_empty());
+    assert_eq!(None, simple.lock_manager().lock_mode(id, &rid).unwrap());
+
+    // The deleted row is back, and free to change again.
+    let txn = simple.begin().unwrap();
+    assert_eq!(vec![row(&schema, 1)], scan(&txn));
+    txn.delete(Key::new("test", &rid)).unwrap();
+    txn.commit().unwrap();
+    let log = bpm.log_manager();
+    assert!(log
+        .records()
+        .unwrap()
+        .iter()
+        .any(|r| r.txn_id == id && r.body == LogRecordBody::Abort));
+}
+
+/// Commits a row, takes a checkpoint, then prepares a transaction that
+/// deletes the row and inserts another. Returns the prepared transaction's id
+/// and the deleted row's id.
+fn prepare_after_checkpoint(
+    simple: &Simple<HeapTableManager>,
+    schema: &Arc<Table>,
+) -> (TxnId, RecordId) {
+    let txn = simple.begin().unwrap();
+    txn.create_table((**schema).clone()).unwrap();
+    let rid = txn.insert("test", row(schema, 1)).unwrap();
+    txn.commit().unwrap();
+    simple.checkpoint().unwrap();
+
+    let txn = simple.begin().unwrap();
+    let id = txn.id();
+    txn.delete(Key::new("test", &rid)).unwrap();
+    txn.insert("test", row(schema, 2)).unwrap();
+    txn.prepare("gid").unwrap();
+    (id, rid)
+}
+
+fn row(schema: &Arc<Table>, seed: u64) -> Tuple {
+    utility::create_random_row(schema, Some(seed))
+        .to_tuple(schema)
+        .unwrap()
+}
+
+fn scan(txn: &Transaction<HeapTableManager>) -> Vec<Tuple> {
+    txn.scan("test")
+        .unwrap()
+        .map(|result| result.unwrap().1)
+        .collect()
+}
+
+fn group_commit_log(window: Duration, max_size: usize) -> LogManager {
+    LogManager::new(DiskManager::new_with_handle_for_test(), SyncPolicy::Commit)
+        .with_group_commit(GroupCommit { window, max_size })
+}
+
+fn setup(
+    sync_policy: SyncPolicy,
+) -> (
+    Arc<BufferPoolManager>,
+    Simple<HeapTableManager>,
+    Arc<Table>,
+) {
+    let bpm = BufferPoolManager::builder()
+        .pool_size(10)
+        .replacer_k(2)
+        .disk_manager(DiskManager::new_with_handle_for_test())
+        .sync_policy(sync_policy)
+        .build_with_handle();
+    let simple = Simple::new(HeapTableManager::new(&bpm));
+    let schema = Arc::new(utility::create_table_definition(4, "test"));
+    (bpm, simple, schema)
+}
@@ -203,6 +203,30 @@ impl Field {
             Field::String(_) => DataType::Text,
         }
     }
+
+    /// Formats the value as a SQL expression the parser reads back as the
+    /// same value, e.g. 'it''s' for the string it's. Unlike [`Display`], which
+    /// is meant for people to read.
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Field::Null => "NULL".to_string(),
+            Field::Boolean(true) => "TRUE".to_string(),
+            Field::Boolean(false) => "FALSE".to_string(),
+            // Negative numbers are negated literals, and 2147483648 isn't one.
+            Field::Integer(i32::MIN) => format!("({} - 1)", i32::MIN + 1),
+            Field::Integer(integer) => integer.to_string(),
+            Field::Float(float) if float.is_nan() => "NAN".to_string(),
+            Field::Float(float) if float.is_infinite() && *float > 0.0 => "INFINITY".to_string(),
+            Field::Float(float) if float.is_infinite() => "-INFINITY".to_string(),
+            // Debug formatting has a fractional part or an exponent, which
+            // makes the literal a float, and enough digits to round-trip.
+            Field::Float(float) => format!("{float:?}"),
+            Field::String(string) => format!("'{}'", string.replace('\'', "''")),
+        }
+    }
+
     // size in bytes
     pub fn get_size(&self) -> u16 {
         match self {
@@ -504,6 +528,23 @@ mod tests {
         assert!(lhs > rhs);
     }
 
+    #[test]
+    pub fn test_to_sql_literal() {
+        for (field, literal) in [
+            (Field::Null, "NULL"),
+            (Field::Boolean(true), "TRUE"),
+            (Field::Integer(-7), "-7"),
+            (Field::Integer(i32::MIN), "(-2147483647 - 1)"),
+            (Field::Float(3.0), "3.0"),
+            (Field::Float(1e-7), "1e-7"),
+            (Field::Float(f32::NEG_INFINITY), "-INFINITY"),
+            (Field::Float(f32::NAN), "NAN"),
+            (Field::String("it's; 'x'".to_string()), "'it''s; ''x'''"),
+        ] {
+            assert_eq!(literal, field.to_sql_literal());
+        }
+    }
+
     #[test]
     pub fn test_serialization() {
         let v = Field::Integer(10);
@@ -1,3 +1,4 @@
+use crate::config::config::DEFAULT_FILL_FACTOR;
 use crate::types::field::Field;
 use core::ops::Deref;
 use serde::{Deserialize, Serialize};
@@ -69,6 +70,11 @@ pub struct Column {
     ///
     /// See `[crate::Row::to_bytes()]` for more detail about the data layout.
     stored_offset: u16,
+    /// Whether the column has a secondary index, see [`crate::storage::index::TableIndex`].
+    index: bool,
+    /// Whether no two live rows may hold the same non-NULL value in the column. Enforced by the
+    /// column's index, which unique columns always have.
+    unique: bool,
 }
 
 impl Column {
@@ -90,6 +96,8 @@ impl Column {
             },
             max_str_len: max_str_chars.unwrap_or(0),
             stored_offset: 0,
+            index: false,
+            unique: false,
         }
     }
 
@@ -122,6 +130,10 @@ impl Column {
         self.name.clone()
     }
 
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
     pub fn default(&self) -> Option<&Field> {
         self.default.as_ref()
     }
@@ -137,6 +149,22 @@ impl Column {
     pub fn get_max_str_len(&self) -> u16 {
         self.max_str_len
     }
+
+    pub fn is_indexed(&self) -> bool {
+        self.index || self.unique
+    }
+
+    pub fn set_indexed(&mut self, index: bool) {
+        self.index = index;
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn set_unique(&mut self, unique: bool) {
+        self.unique = unique;
+    }
 }
 
 pub struct ColumnBuilder {
@@ -145,6 +173,8 @@ pub struct ColumnBuilder {
     nullable: Option<bool>,
     default: Option<Field>,
     max_str_len: Option<u16>,
+    index: bool,
+    unique: bool,
 }
 
 impl ColumnBuilder {
@@ -155,6 +185,8 @@ impl ColumnBuilder {
             nullable: None,
             default: None,
             max_str_len: None,
+            index: false,
+            unique: false,
         }
     }
 
@@ -185,6 +217,16 @@ impl ColumnBuilder {
         self
     }
 
+    pub fn index(mut self, index: bool) -> Self {
+        self.index = index;
+        self
+    }
+
+    pub fn unique(mut self, unique: bool) -> Self {
+        self.unique = unique;
+        self
+    }
+
     pub fn build(self) -> Column {
         let nullable = self.nullable.unwrap_or(false);
         Column {
@@ -200,6 +242,8 @@ impl ColumnBuilder {
             },
             max_str_len: self.max_str_len.unwrap_or(0),
             stored_offset: 0,
+            index: self.index,
+            unique: self.unique,
         }
     }
 }
@@ -213,6 +257,8 @@ impl From<DataType> for Column {
             default: None,
             max_str_len: 0,
             stored_offset: 0,
+            index: false,
+            unique: false,
         }
     }
 }
@@ -226,6 +272,8 @@ impl From<(DataType, u16)> for Column {
             default: None,
             max_str_len: str_len,
             stored_offset: 0,
+            index: false,
+            unique: false,
         }
     }
 }
@@ -238,6 +286,8 @@ pub struct Table {
     fixed_field_size_bytes: u16,
     /// The column definitions of the table
     columns: Vec<Column>,
+    /// The percentage of each page inserts fill, leaving the rest for updates that grow tuples.
+    fill_factor: u8,
 }
 
 impl Table {
@@ -246,6 +296,7 @@ impl Table {
             name: table_name.to_string(),
             fixed_field_size_bytes: 0,
             columns: Vec::new(),
+            fill_factor: DEFAULT_FILL_FACTOR,
         }
     }
 
@@ -261,6 +312,14 @@ impl Table {
         self.name = table_name.to_string();
     }
 
+    pub fn fill_factor(&self) -> u8 {
+        self.fill_factor
+    }
+
+    pub fn set_fill_factor(&mut self, percent: u8) {
+        self.fill_factor = percent;
+    }
+
     pub fn add_column(&mut self, column: &Column) {
         let data_type = column.get_data_type();
         let mut to_push = column.clone();
@@ -412,6 +471,7 @@ impl Deref for Table {
 pub struct TableBuilder {
     name: Option<String>,
     columns: Vec<Column>,
+    fill_factor: Option<u8>,
 }
 
 impl TableBuilder {
@@ -448,6 +508,11 @@ impl TableBuilder {
         self
     }
 
+    pub fn fill_factor(&mut self, percent: u8) -> &mut Self {
+        self.fill_factor = Some(percent);
+        self
+    }
+
     pub fn build(&mut self) -> Table {
         let name = self
             .name
@@ -457,6 +522,9 @@ impl TableBuilder {
         self.columns
             .iter()
             .for_each(|column| table_definition.add_column(column));
+        if let Some(percent) = self.fill_factor {
+            table_definition.set_fill_factor(percent);
+        }
         table_definition
     }
 
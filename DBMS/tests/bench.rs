@@ -0,0 +1,98 @@
+//! Runs the rustydb-bench binary on small databases, and generates databases to compare.
+use rustydb::bench::{generate, DataSpec, WORKLOADS};
+use rustydb::sql::engine::{dump, Local};
+use rustydb::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use rustydb::storage::disk::disk_manager::DiskManager;
+use rustydb::storage::HeapTableManager;
+use serde_json::Value;
+use std::process::{Command, Output};
+use std::sync::{Arc, RwLock};
+use tempfile::TempDir;
+
+#[test]
+fn test_workload_reports() {
+    for workload in WORKLOADS {
+        let output = bench(&["--workload", workload.name, "--rows", "40", "--ops", "20"]);
+        assert!(output.status.success(), "{output:?}");
+        let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        assert_eq!(workload.name, report["workload"]);
+        assert_eq!(
+            serde_json::json!({"tables": 2, "rows": 40, "seed": 42}),
+            report["data"]
+        );
+        assert_eq!(20, report["operations"]);
+        assert!(report["ops_per_sec"].as_f64().unwrap() > 0.0);
+        let latency = &report["latency_us"];
+        let percentile = |name: &str| latency[name].as_u64().unwrap();
+        assert!(percentile("min") <= percentile("p50"));
+        assert!(percentile("p50") <= percentile("p99"));
+        assert!(percentile("p99") <= percentile("max"));
+        // Every operation commits a transaction.
+        assert_eq!(20, report["stats"]["log_commits"]);
+        assert!(report["stats"]["data_file_bytes"].as_u64().unwrap() > 0);
+    }
+}
+
+#[test]
+fn test_arguments() {
+    let output = bench(&["--list"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(WORKLOADS.len(), stdout.lines().count());
+
+    let output = bench(&["--workload", "scan", "--scale", "1", "--duration", "0.2"]);
+    assert!(output.status.success(), "{output:?}");
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(1000, report["data"]["rows"]);
+    assert!(report["elapsed_secs"].as_f64().unwrap() >= 0.2);
+
+    for args in [
+        &["--workload", "missing"][..],
+        &["--scale", "1"],
+        &["--workload", "scan", "--ops", "many"],
+        &["--workload"],
+    ] {
+        assert_eq!(Some(2), bench(args).status.code(), "{args:?}");
+    }
+    let output = bench(&["--workload", "scan", "--rows", "0"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+/// The same spec generates the same database, and another seed another one.
+#[test]
+fn test_generate_is_seeded() {
+    let spec = DataSpec {
+        tables: 3,
+        rows: 600,
+        seed: 7,
+    };
+    let script = generate_and_dump(&spec);
+    assert_eq!(3, script.matches("CREATE TABLE").count());
+    assert_eq!(script, generate_and_dump(&spec));
+    assert_ne!(script, generate_and_dump(&DataSpec { seed: 8, ..spec }));
+}
+
+fn generate_and_dump(spec: &DataSpec) -> String {
+    let dir = TempDir::new().unwrap();
+    let disk_manager = DiskManager::open(&dir.path().join("test")).unwrap();
+    let bpm = BufferPoolManager::builder()
+        .disk_manager(Arc::new(RwLock::new(disk_manager)))
+        .pool_size(50)
+        .replacer_k(2)
+        .build_with_handle();
+    let engine = Local::new(HeapTableManager::new(&bpm));
+    let mut session = engine.session();
+    generate(&mut session, spec).unwrap();
+    let mut script = Vec::new();
+    session.with_txn(|txn| dump(txn, &mut script)).unwrap();
+    String::from_utf8(script).unwrap()
+}
+
+/// Runs the binary, writing its database to a temporary directory.
+fn bench(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rustydb-bench"))
+        .args(args)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,115 @@
+//! Runs the rustydb-cli binary non-interactively, on a database in a temporary directory.
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+#[test]
+fn test_command_options() {
+    let dir = TempDir::new().unwrap();
+    let output = cli(
+        &dir,
+        &[
+            "-c",
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT); INSERT INTO t VALUES (1, 'one')",
+            "-c",
+            "INSERT INTO t VALUES (20, 'twenty'); SELECT * FROM t",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expect = "CREATE TABLE
+INSERT 1
+INSERT 1
+ id |  name
+----+--------
+  1 | one
+ 20 | twenty
+(2 rows)
+";
+    assert!(stdout.starts_with(expect), "{stdout}");
+}
+
+#[test]
+fn test_script_from_stdin() {
+    let dir = TempDir::new().unwrap();
+    let script = "CREATE TABLE t (id INT PRIMARY KEY, value INT UNIQUE);
+INSERT INTO t VALUES (1, 10),
+    (2, 20);
+SELECT missing FROM t;
+BEGIN; DELETE FROM t WHERE id = 1; SELECT COUNT(*) FROM t; ROLLBACK;
+\\dt
+\\d t
+\\d missing
+SELECT id FROM t WHERE value = 20
+";
+    let output = cli(&dir, &[], script);
+    // Errors are printed, and the script goes on.
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        2,
+        stderr.lines().filter(|l| l.starts_with("ERROR:")).count(),
+        "{stderr}"
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expect = "CREATE TABLE
+INSERT 2
+BEGIN
+DELETE 1
+ ?
+---
+ 1
+(1 row)
+ROLLBACK
+ name | columns
+------+---------
+ t    |       2
+(1 row)
+Table \"t\"
+ column | type | nullable | default | index
+--------+------+----------+---------+--------
+ id     | int  | false    |         |
+ value  | int  | false    |         | unique
+(2 rows)
+ id
+----
+  2
+(1 row)
+";
+    assert!(stdout.starts_with(expect), "{stdout}");
+}
+
+#[test]
+fn test_quit() {
+    let dir = TempDir::new().unwrap();
+    let output = cli(
+        &dir,
+        &[],
+        "CREATE TABLE t (id INT PRIMARY KEY);\n\\q\nSELECT nope;\n",
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty());
+}
+
+/// Runs the binary on a database file in `dir`, with the given arguments and standard input.
+fn cli(dir: &TempDir, args: &[&str], stdin: &str) -> Output {
+    let file = Path::new(dir.path()).join("test");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rustydb-cli"))
+        .args(args)
+        .arg(file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
@@ -0,0 +1,586 @@
+//! Uses the embeddable database API on a fresh file in a temporary directory.
+use regex::Regex;
+use rustydb::common::{Error, StatementStats};
+use rustydb::database::{from_row, Database, Options, RetryPolicy};
+use rustydb::sql::engine::StatementResult;
+use rustydb::storage::tuple::Row;
+use rustydb::storage::wal::SyncPolicy;
+use rustydb::types::field::{Field, Label};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_execute_and_query() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+
+    assert_eq!(
+        StatementResult::CreateTable {
+            name: "t".to_string()
+        },
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+            .unwrap()
+    );
+    assert!(matches!(
+        db.execute("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)"),
+        Ok(StatementResult::Insert { count: 3, .. })
+    ));
+    assert_eq!(
+        StatementResult::Update { count: 1 },
+        db.execute("UPDATE t SET value = 0 WHERE id = 1").unwrap()
+    );
+    assert_eq!(
+        StatementResult::Delete { count: 1 },
+        db.execute("DELETE FROM t WHERE id = 3").unwrap()
+    );
+
+    let result = db.query("SELECT id, value FROM t ORDER BY id").unwrap();
+    assert_eq!(
+        &[
+            Label::Qualified("t".to_string(), "id".to_string()),
+            Label::Qualified("t".to_string(), "value".to_string()),
+        ],
+        result.columns()
+    );
+    assert_eq!(2, result.len());
+    // The rows belong to the result, which outlives the database.
+    db.close().unwrap();
+    assert_eq!(vec![row(1, 0), row(2, 20)], result.collect::<Vec<_>>());
+}
+
+#[test]
+fn test_refused_statements() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+
+    // Transactions are left to Database::transaction.
+    for statement in ["BEGIN", "COMMIT", "ROLLBACK"] {
+        assert!(
+            matches!(db.execute(statement), Err(Error::InvalidInput(_))),
+            "{statement}"
+        );
+    }
+    // Queries are checked before they run.
+    assert!(matches!(
+        db.query("INSERT INTO t VALUES (1, 10)"),
+        Err(Error::InvalidInput(_))
+    ));
+    assert_eq!(0, db.query("SELECT * FROM t").unwrap().count());
+    assert!(db.query("SELECT missing FROM t").is_err());
+    assert!(db.execute("SELEC").is_err());
+}
+
+#[test]
+fn test_transaction() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+
+    // Ok commits, and returns the closure's value.
+    let count = db
+        .transaction(|txn| {
+            txn.execute("INSERT INTO t VALUES (1, 10)")?;
+            txn.execute("INSERT INTO t VALUES (2, 20)")?;
+            // A failing statement doesn't end the transaction.
+            assert!(txn.execute("INSERT INTO missing VALUES (1)").is_err());
+            Ok(txn.query("SELECT * FROM t")?.count())
+        })
+        .unwrap();
+    assert_eq!(2, count);
+    assert_eq!(2, db.query("SELECT * FROM t").unwrap().count());
+
+    // An error rolls back, and is returned.
+    let result: Result<(), Error> = db.transaction(|txn| {
+        txn.execute("DELETE FROM t")?;
+        assert_eq!(0, txn.query("SELECT * FROM t")?.count());
+        Err(Error::Abort)
+    });
+    assert_eq!(Err(Error::Abort), result);
+    assert_eq!(2, db.query("SELECT * FROM t").unwrap().count());
+
+    // The closure can't end the transaction itself.
+    let result = db.transaction(|txn| {
+        txn.execute("DELETE FROM t WHERE id = 1")?;
+        txn.execute("COMMIT")
+    });
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+    assert_eq!(2, db.query("SELECT * FROM t").unwrap().count());
+}
+
+/// Retries without waiting in between.
+const RETRY_NOW: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    initial_backoff: Duration::ZERO,
+    max_backoff: Duration::ZERO,
+};
+
+fn value_of(db: &Database, id: i32) -> Field {
+    let mut rows = db
+        .query(&format!("SELECT value FROM t WHERE id = {id}"))
+        .unwrap();
+    rows.next().unwrap().iter().next().unwrap().clone()
+}
+
+/// A write that loses to a concurrent writer runs again, this time from a snapshot that
+/// includes the winner's write.
+#[test]
+fn test_retry_write_conflict() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+    db.execute("INSERT INTO t VALUES (1, 0), (2, 0)").unwrap();
+
+    // The first attempt of the closure updates the row after another statement committed an
+    // update of it, so it loses. The second, from a newer snapshot, wins.
+    let mut attempts = 0;
+    db.transaction_with_retry(RETRY_NOW, |txn| {
+        attempts += 1;
+        if attempts == 1 {
+            db.execute("UPDATE t SET value = value + 1 WHERE id = 1")?;
+        }
+        txn.execute("UPDATE t SET value = value + 10 WHERE id = 1")
+    })
+    .unwrap();
+    assert_eq!(2, attempts);
+    assert_eq!(Field::Integer(11), value_of(&db, 1));
+
+    // An auto-commit statement waiting on the row lock of a transaction loses once it commits.
+    std::thread::scope(|scope| {
+        db.transaction(|txn| {
+            txn.execute("UPDATE t SET value = value + 10 WHERE id = 2")?;
+            let waiter = scope.spawn(|| {
+                db.execute_with_retry("UPDATE t SET value = value + 1 WHERE id = 2", RETRY_NOW)
+            });
+            std::thread::sleep(Duration::from_millis(200));
+            Ok(waiter)
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+        .unwrap();
+    });
+    assert_eq!(Field::Integer(11), value_of(&db, 2));
+    assert_eq!(2, db.metrics().snapshot().statements.retries);
+}
+
+#[test]
+fn test_retry_errors() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+    db.execute("INSERT INTO t VALUES (1, 0)").unwrap();
+
+    // Errors other than serialization failures and lock timeouts are returned as they are.
+    let statement = "UPDATE missing SET value = 1";
+    let error = db.execute(statement).unwrap_err();
+    assert_eq!(Err(error), db.execute_with_retry(statement, RETRY_NOW));
+    let mut attempts = 0;
+    let result: Result<(), Error> = db.transaction_with_retry(RETRY_NOW, |_| {
+        attempts += 1;
+        Err(Error::Abort)
+    });
+    assert_eq!((Err(Error::Abort), 1), (result, attempts));
+
+    // Retries stop at the policy's cap, returning the last error along with the attempts.
+    for error in [Error::Serialization, Error::LockTimeout] {
+        let mut attempts = 0;
+        let result: Result<(), Error> = db.transaction_with_retry(RETRY_NOW, |txn| {
+            attempts += 1;
+            txn.execute("UPDATE t SET value = value + 1")?;
+            Err(error.clone())
+        });
+        assert_eq!(
+            Err(Error::RetriesExhausted {
+                attempts: 3,
+                error: Box::new(error),
+            }),
+            result
+        );
+        assert_eq!(3, attempts);
+    }
+    // Every attempt rolled back.
+    assert_eq!(Field::Integer(0), value_of(&db, 1));
+    assert_eq!(4, db.metrics().snapshot().statements.retries);
+}
+
+/// A small buffer pool, evicting pages as rows are inserted, and lazy log syncs.
+#[test]
+fn test_options() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("test");
+    let options = Options {
+        pool_size: 8,
+        replacer_k: 2,
+        sync_policy: SyncPolicy::Lazy,
+    };
+    let db = Database::open(&path, options).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+    for batch in 0..100 {
+        let values: Vec<String> = (batch * 100..(batch + 1) * 100)
+            .map(|id| format!("({id}, {id})"))
+            .collect();
+        db.execute(&format!("INSERT INTO t VALUES {}", values.join(", ")))
+            .unwrap();
+    }
+    assert_eq!(
+        vec![Row::from(vec![Field::Integer(10000)])],
+        db.query("SELECT COUNT(*) FROM t")
+            .unwrap()
+            .collect::<Vec<_>>()
+    );
+    // The table outgrew the buffer pool.
+    assert!(std::fs::metadata(&path).unwrap().len() > 8 * 4096);
+
+    // Dropping the database takes a checkpoint, which truncates the write-ahead log.
+    let log_size = || {
+        std::fs::metadata(dir.path().join("test.wal"))
+            .unwrap()
+            .len()
+    };
+    let before = log_size();
+    drop(db);
+    assert!(log_size() < before / 100, "{} bytes left", log_size());
+
+    assert!(Database::open(
+        &path,
+        Options {
+            pool_size: 0,
+            ..options
+        }
+    )
+    .is_err());
+    assert!(Database::open(dir.path().join("missing/test"), options).is_err());
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct User {
+    id: i32,
+    name: String,
+    age: Option<u8>,
+    score: f64,
+}
+
+#[test]
+fn test_query_as() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE users (id INT PRIMARY KEY, name TEXT, age INT NULL, score FLOAT)")
+        .unwrap();
+    db.execute("INSERT INTO users VALUES (1, 'ada', 36, 1.5), (2, 'grace', 85, 2)")
+        .unwrap();
+    let ada = User {
+        id: 1,
+        name: "ada".to_string(),
+        age: Some(36),
+        score: 1.5,
+    };
+
+    // Columns map to fields by name, in any order and case.
+    let users: Vec<User> = db
+        .query_as("SELECT score, name AS NAME, id, age FROM users ORDER BY id")
+        .unwrap();
+    assert_eq!(2, users.len());
+    assert_eq!(ada, users[0]);
+
+    // Tuples, sequences and single values take the columns in order.
+    let rows: Vec<(i64, String)> = db
+        .query_as("SELECT id, name FROM users ORDER BY id")
+        .unwrap();
+    assert_eq!(vec![(1, "ada".to_string()), (2, "grace".to_string())], rows);
+    let rows: Vec<Vec<Field>> = db
+        .query_as("SELECT id, score FROM users WHERE id = 1")
+        .unwrap();
+    assert_eq!(vec![vec![Field::Integer(1), Field::Float(1.5)]], rows);
+    let counts: Vec<u32> = db.query_as("SELECT COUNT(*) FROM users").unwrap();
+    assert_eq!(vec![2], counts);
+
+    // The same in a transaction.
+    let users: Vec<User> = db
+        .transaction(|txn| txn.query_as("SELECT * FROM users WHERE id = 1"))
+        .unwrap();
+    assert_eq!(vec![ada], users);
+
+    // Errors name the row and column.
+    let result =
+        db.query_as::<User>("SELECT id, name, 2 - id * 2 AS age, score FROM users ORDER BY id");
+    assert_eq!(
+        Err(Error::InvalidData(
+            "row 2: column age: -2 is out of range for u8".to_string()
+        )),
+        result.map(|_| ())
+    );
+    let result = db.query_as::<(i32, bool)>("SELECT id, name FROM users");
+    assert_eq!(
+        Err(Error::InvalidData(
+            "row 1: column name: invalid type: string \"ada\", expected a boolean".to_string()
+        )),
+        result.map(|_| ())
+    );
+    let result = db.query_as::<User>("SELECT id, name, id AS extra FROM users");
+    assert_eq!(
+        Err(Error::InvalidData(
+            "row 1: no column for fields age, score; no field for columns extra".to_string()
+        )),
+        result.map(|_| ())
+    );
+    let result = db.query_as::<(i32, String, f64)>("SELECT id, name FROM users");
+    assert_eq!(
+        Err(Error::InvalidData(
+            "row 1: expected 3 columns, got 2".to_string()
+        )),
+        result.map(|_| ())
+    );
+}
+
+#[test]
+fn test_from_row() {
+    let columns = [
+        Label::Qualified("users".to_string(), "id".to_string()),
+        Label::Unqualified("name".to_string()),
+        Label::Unqualified("age".to_string()),
+        Label::Unqualified("score".to_string()),
+    ];
+    let row = Row::from(vec![
+        Field::Integer(1),
+        Field::String("ada".to_string()),
+        Field::Null,
+        Field::Integer(2),
+    ]);
+
+    // NULL maps to None, and integers to floats.
+    let user: User = from_row(&columns, row.clone()).unwrap();
+    assert_eq!(
+        User {
+            id: 1,
+            name: "ada".to_string(),
+            age: None,
+            score: 2.0,
+        },
+        user
+    );
+    let values: (i32, String, Option<i32>, Option<i32>) = from_row(&columns, row.clone()).unwrap();
+    assert_eq!((1, "ada".to_string(), None, Some(2)), values);
+    let fields: Vec<Field> = from_row(&columns, row.clone()).unwrap();
+    assert_eq!(row.iter().cloned().collect::<Vec<_>>(), fields);
+    let map: HashMap<String, Field> = from_row(&columns, row.clone()).unwrap();
+    assert_eq!(Some(&Field::Null), map.get("age"));
+
+    // NULL doesn't map to anything else.
+    assert_eq!(
+        Err(Error::InvalidData(
+            "column age: invalid type: NULL, expected i32".to_string()
+        )),
+        from_row::<(i32, String, i32, i32)>(&columns, row.clone())
+    );
+    assert_eq!(
+        Err(Error::InvalidData(
+            "column id: -1 is out of range for u64".to_string()
+        )),
+        from_row::<u64>(&columns[..1], Row::from(vec![Field::Integer(-1)]))
+    );
+    assert!(from_row::<u64>(&columns, row).is_err());
+}
+
+#[test]
+fn test_metrics() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    let metrics = db.metrics();
+
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+    db.execute("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)")
+        .unwrap();
+    db.execute("UPDATE t SET value = 0 WHERE id = 1").unwrap();
+    db.execute("DELETE FROM t WHERE id = 3").unwrap();
+    db.query("SELECT * FROM t").unwrap();
+    // Failing statements count as errors, however they fail.
+    db.query("SELECT * FROM missing").unwrap_err();
+    db.query("DELETE FROM t").unwrap_err();
+    db.execute("BEGIN").unwrap_err();
+    db.execute("SELEC 1").unwrap_err();
+    db.transaction(|txn| {
+        assert_eq!(1, metrics.snapshot().active_transactions);
+        txn.execute("INSERT INTO t VALUES (4, 40)")?;
+        txn.query("SELECT * FROM t")?;
+        txn.execute("UPDATE t SET value = 1")
+    })
+    .unwrap();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        StatementStats {
+            selects: 2,
+            inserts: 2,
+            updates: 2,
+            deletes: 1,
+            errors: 4,
+            retries: 0,
+        },
+        snapshot.statements
+    );
+    assert_eq!(0, snapshot.active_transactions);
+    assert!(snapshot.buffer_pool.hits > 0);
+    assert!(snapshot.disk.pages_written > 0);
+    assert!(snapshot.disk.log_bytes_appended > 0);
+    assert!(snapshot.replacer.accesses > 0);
+
+    // Every sample belongs to a family declared before it, and has a numeric value.
+    let text = snapshot.to_prometheus_text();
+    let comment = Regex::new(r"^# (HELP|TYPE) (rustydb_[a-z_]+) (.+)$").unwrap();
+    let sample = Regex::new(r#"^(rustydb_[a-z_]+)(\{[a-z_]+="[^"]*"\})? (\d+)$"#).unwrap();
+    let mut types = HashMap::new();
+    let mut samples = HashMap::new();
+    for line in text.lines() {
+        if let Some(captures) = comment.captures(line) {
+            if &captures[1] == "TYPE" {
+                assert!(["counter", "gauge", "histogram"].contains(&&captures[3]), "{line}");
+                types.insert(captures[2].to_string(), captures[3].to_string());
+            }
+            continue;
+        }
+        let captures = sample.captures(line).unwrap_or_else(|| panic!("bad line {line}"));
+        // The samples of a histogram are named after it, with a suffix.
+        let family = (["_bucket", "_sum", "_count"].iter())
+            .find_map(|suffix| captures[1].strip_suffix(suffix))
+            .filter(|family| types.get(*family).is_some_and(|kind| kind == "histogram"))
+            .unwrap_or(&captures[1]);
+        assert!(types.contains_key(family), "undeclared {line}");
+        let labels = captures.get(2).map_or("", |labels| labels.as_str());
+        let value: u64 = captures[3].parse().unwrap();
+        samples.insert(format!("{}{labels}", &captures[1]), value);
+    }
+    assert_eq!(Some(&2), samples.get("rustydb_statements_total{kind=\"select\"}"));
+    assert_eq!(Some(&4), samples.get("rustydb_statement_errors_total"));
+    assert_eq!(Some(&0), samples.get("rustydb_transactions_active"));
+    assert_eq!(
+        Some(&snapshot.buffer_pool.hits),
+        samples.get("rustydb_buffer_pool_hits_total")
+    );
+    assert_eq!(
+        samples.get("rustydb_replacer_eviction_distance_bucket{le=\"+Inf\"}"),
+        samples.get("rustydb_replacer_eviction_distance_count")
+    );
+    assert_eq!(46, samples.len());
+}
+
+/// A backup of a database nothing is writing to opens as the database, indexes included, without
+/// the rows deleted and the replaced versions of those updated, and keeps the changes made to it
+/// once reopened.
+#[test]
+fn test_backup_idle() {
+    let dir = TempDir::new().unwrap();
+    let backup = dir.path().join("backup");
+    let db = Database::open(dir.path().join("test"), Options::default()).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT INDEX)")
+        .unwrap();
+    db.execute("CREATE TABLE empty (id INT PRIMARY KEY)").unwrap();
+    db.execute("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)").unwrap();
+    db.execute("DELETE FROM t WHERE id = 2").unwrap();
+    db.execute("UPDATE t SET value = 11 WHERE id = 1").unwrap();
+
+    let info = db.backup_to(&backup).unwrap();
+    assert!(info.checkpoint_lsn <= info.end_lsn);
+    assert!(info.pages > 1);
+    assert!(db.backup_to(&backup).is_err());
+    db.execute("INSERT INTO t VALUES (4, 40)").unwrap();
+
+    let restored = Database::open(&backup, Options::default()).unwrap();
+    let rows = |db: &Database, query: &str| db.query(query).unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        vec![row(1, 11), row(3, 30)],
+        rows(&restored, "SELECT * FROM t ORDER BY id")
+    );
+    assert_eq!(vec![row(3, 30)], rows(&restored, "SELECT * FROM t WHERE value = 30"));
+    assert_eq!(vec![row(1, 11)], rows(&restored, "SELECT * FROM t WHERE value = 11"));
+    assert!(rows(&restored, "SELECT * FROM t WHERE value = 10").is_empty());
+    assert!(rows(&restored, "SELECT * FROM empty").is_empty());
+    // Each primary key has a single version, and the deleted row's none.
+    assert_eq!(vec![row(1, 11)], rows(&restored, "SELECT * FROM t WHERE id = 1"));
+    assert!(rows(&restored, "SELECT * FROM t WHERE id = 2").is_empty());
+
+    restored.execute("INSERT INTO t VALUES (2, 50)").unwrap();
+    restored.close().unwrap();
+    let reopened = Database::open(&backup, Options::default()).unwrap();
+    assert_eq!(
+        vec![row(1, 11), row(2, 50), row(3, 30)],
+        rows(&reopened, "SELECT * FROM t ORDER BY id")
+    );
+}
+
+/// Rows inserted one at a time while a backup runs: the backup holds the first of them up to
+/// some point, and none after it, and none of the rows deleted or replaced versions of the rows
+/// updated before it.
+#[test]
+fn test_backup_concurrent_inserts() {
+    let dir = TempDir::new().unwrap();
+    let backup = dir.path().join("backup");
+    let options = Options {
+        pool_size: 16,
+        ..Options::default()
+    };
+    let db = Database::open(dir.path().join("test"), options).unwrap();
+    db.execute("CREATE TABLE t (id INT PRIMARY KEY, value INT)")
+        .unwrap();
+    // Enough pages that the copy takes a while.
+    for batch in 0..50 {
+        let values: Vec<String> = (batch * 100..(batch + 1) * 100)
+            .map(|id| format!("({id}, 0)"))
+            .collect();
+        db.execute(&format!("INSERT INTO t VALUES {}", values.join(", ")))
+            .unwrap();
+    }
+    db.execute("DELETE FROM t WHERE id < 100").unwrap();
+    db.execute("UPDATE t SET value = 1 WHERE id < 1000").unwrap();
+
+    let next_id = AtomicI32::new(5000);
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            while !backup.join("manifest.json").exists() {
+                let id = next_id.load(Ordering::SeqCst);
+                db.execute(&format!("INSERT INTO t VALUES ({id}, {id})"))
+                    .unwrap();
+                next_id.store(id + 1, Ordering::SeqCst);
+            }
+        });
+        while next_id.load(Ordering::SeqCst) < 5100 {
+            std::thread::yield_now();
+        }
+        db.backup_to(&backup).unwrap();
+        writer.join().unwrap();
+    });
+    let inserted = next_id.into_inner();
+    drop(db);
+
+    let restored = Database::open(&backup, options).unwrap();
+    let rows: Vec<(i32, i32)> = restored
+        .query("SELECT id, value FROM t ORDER BY id")
+        .unwrap()
+        .map(|row| match row.iter().collect::<Vec<_>>()[..] {
+            [Field::Integer(id), Field::Integer(value)] => (*id, *value),
+            ref fields => panic!("unexpected fields {fields:?}"),
+        })
+        .collect();
+    assert!(rows.len() >= 5000 && rows.len() as i32 <= inserted - 100);
+    let expected: Vec<(i32, i32)> = (100..rows.len() as i32 + 100)
+        .map(|id| match id {
+            ..1000 => (id, 1),
+            ..5000 => (id, 0),
+            _ => (id, id),
+        })
+        .collect();
+    assert_eq!(expected, rows);
+}
+
+fn row(id: i32, value: i32) -> Row {
+    Row::from(vec![Field::Integer(id), Field::Integer(value)])
+}
@@ -0,0 +1,23 @@
+//! Runs the SQL logic test scripts of tests/slt, see [`rustydb::sql::logictest`].
+use rustydb::sql::logictest::run_file;
+use std::path::Path;
+
+#[test]
+fn test_scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/slt");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "slt"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scripts in {}", dir.display());
+
+    let mut failures = String::new();
+    for path in paths {
+        let report = run_file(&path).unwrap();
+        assert!(report.passed > 0, "{} ran no records", path.display());
+        failures.push_str(&report.to_string());
+    }
+    assert!(failures.is_empty(), "\n{failures}");
+}
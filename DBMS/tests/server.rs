@@ -0,0 +1,465 @@
+//! Runs a server in-process, on a database in a temporary directory, and talks to it over TCP.
+use rustydb::common::Error;
+use rustydb::server::{Client, ClientResult, Protocol, Server, ShutdownHandle};
+use rustydb::sql::engine::Local;
+use rustydb::storage::buffer::buffer_pool_manager::{BufferPoolManager, CheckpointInfo};
+use rustydb::storage::disk::disk_manager::DiskManager;
+use rustydb::storage::tuple::Row;
+use rustydb::storage::HeapTableManager;
+use rustydb::types::field::{Field, Label};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_statements() {
+    let server = TestServer::start();
+    let mut client = Client::connect(server.addr).unwrap();
+
+    assert_eq!(
+        done("CREATE TABLE", 0),
+        client
+            .execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+            .unwrap()
+    );
+    assert_eq!(
+        done("INSERT", 3),
+        client
+            .execute("INSERT INTO t VALUES (1, 'one'), (2, 'two'), (3, 'three')")
+            .unwrap()
+    );
+    assert_eq!(
+        done("UPDATE", 1),
+        client
+            .execute("UPDATE t SET name = 'uno' WHERE id = 1")
+            .unwrap()
+    );
+    assert_eq!(
+        done("DELETE", 1),
+        client.execute("DELETE FROM t WHERE id = 2").unwrap()
+    );
+    assert_eq!(
+        ClientResult::Rows {
+            columns: vec![
+                Label::Qualified("t".to_string(), "id".to_string()),
+                Label::Qualified("t".to_string(), "name".to_string()),
+            ],
+            rows: vec![row(1, "uno"), row(3, "three")],
+        },
+        client.execute("SELECT * FROM t ORDER BY id").unwrap()
+    );
+
+    // Errors leave the connection usable.
+    assert!(matches!(
+        client.execute("SELECT missing FROM t"),
+        Err(Error::InvalidInput(_))
+    ));
+    let ClientResult::Rows { columns, rows } = client.execute("EXPLAIN SELECT * FROM t").unwrap()
+    else {
+        panic!("expected rows");
+    };
+    assert_eq!(vec![Label::Unqualified("plan".to_string())], columns);
+    assert!(!rows.is_empty());
+    assert_eq!(
+        done("DROP TABLE", 0),
+        client.execute("DROP TABLE t").unwrap()
+    );
+}
+
+/// Each connection has a session of its own, with its own transaction.
+#[test]
+fn test_connections_have_own_sessions() {
+    let server = TestServer::start();
+    let mut a = Client::connect(server.addr).unwrap();
+    let mut b = Client::connect(server.addr).unwrap();
+    a.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+
+    a.execute("BEGIN").unwrap();
+    a.execute("INSERT INTO t VALUES (1, 'one')").unwrap();
+    assert_eq!(1, count(&mut a));
+    assert_eq!(0, count(&mut b));
+    a.execute("COMMIT").unwrap();
+    assert_eq!(1, count(&mut b));
+
+    // A connection's open transaction is rolled back when it closes.
+    b.execute("BEGIN").unwrap();
+    b.execute("INSERT INTO t VALUES (2, 'two')").unwrap();
+    drop(b);
+    // Wait for the server to notice, until the only transaction running is the one listing them.
+    let transactions = "SELECT id FROM information_schema.transactions";
+    for _ in 0..500 {
+        match a.execute(transactions).unwrap() {
+            ClientResult::Rows { rows, .. } if rows.len() == 1 => break,
+            _ => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    assert_eq!(1, count(&mut a));
+    a.execute("INSERT INTO t VALUES (2, 'two')").unwrap();
+    assert_eq!(2, count(&mut a));
+}
+
+#[test]
+fn test_concurrent_clients() {
+    let server = TestServer::start();
+    let mut client = Client::connect(server.addr).unwrap();
+    client
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            let addr = server.addr;
+            thread::spawn(move || {
+                let mut client = Client::connect(addr).unwrap();
+                for i in 0..25 {
+                    let id = thread * 100 + i;
+                    client
+                        .execute(&format!("INSERT INTO t VALUES ({id}, 'row')"))
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(100, count(&mut client));
+}
+
+/// Shutting down closes connections and writes the buffer pool's modified pages to disk.
+#[test]
+fn test_shutdown() {
+    let server = TestServer::start();
+    let mut client = Client::connect(server.addr).unwrap();
+    client
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    client.execute("INSERT INTO t VALUES (1, 'one')").unwrap();
+
+    let (addr, bpm) = (server.addr, Arc::clone(&server.bpm));
+    server.stop();
+    assert!(client.execute("SELECT * FROM t").is_err());
+    assert!(Client::connect(addr).is_err());
+    assert_eq!(CheckpointInfo::default(), bpm.checkpoint().unwrap());
+}
+
+/// psql's session: asking for SSL, starting up, and running simple queries.
+#[test]
+fn test_postgres_queries() {
+    let server = TestServer::start_with(Protocol::Postgres);
+    let mut client = PgClient::connect(server.addr);
+
+    assert_eq!(
+        vec![complete("CREATE TABLE"), ready(b'I')],
+        client.query("CREATE TABLE t (id INT PRIMARY KEY, name TEXT, ok BOOLEAN)")
+    );
+    assert_eq!(
+        vec![complete("INSERT 0 2"), ready(b'I')],
+        client.query("INSERT INTO t VALUES (1, 'one', TRUE), (2, 'two', FALSE);")
+    );
+    assert_eq!(
+        vec![
+            PgMessage::RowDescription(vec![
+                ("id".to_string(), 23),
+                ("name".to_string(), 25),
+                ("ok".to_string(), 16),
+            ]),
+            data_row(&[Some("1"), Some("one"), Some("t")]),
+            data_row(&[Some("2"), Some("two"), Some("f")]),
+            complete("SELECT 2"),
+            ready(b'I'),
+        ],
+        client.query("SELECT * FROM t ORDER BY id")
+    );
+    assert_eq!(
+        vec![
+            PgMessage::RowDescription(vec![("?column?".to_string(), 25)]),
+            data_row(&[None]),
+            complete("SELECT 1"),
+            ready(b'I'),
+        ],
+        client.query("SELECT NULL")
+    );
+
+    // A query's statements run until one fails.
+    let messages = client.query("DELETE FROM t WHERE id = 2; SELECT missing FROM t; DELETE FROM t");
+    assert_eq!(complete("DELETE 1"), messages[0]);
+    assert!(matches!(&messages[1], PgMessage::Error(code, _) if code == "42000"));
+    assert_eq!(ready(b'I'), messages[2]);
+    assert_eq!(3, messages.len());
+
+    assert_eq!(vec![PgMessage::EmptyQuery, ready(b'I')], client.query(" "));
+}
+
+/// ReadyForQuery tells whether the session has a transaction open.
+#[test]
+fn test_postgres_transaction_status() {
+    let server = TestServer::start_with(Protocol::Postgres);
+    let mut client = PgClient::connect(server.addr);
+    client.query("CREATE TABLE t (id INT PRIMARY KEY)");
+
+    assert_eq!(vec![complete("BEGIN"), ready(b'T')], client.query("BEGIN"));
+    client.query("INSERT INTO t VALUES (1)");
+    // An error doesn't end the transaction.
+    let messages = client.query("SELECT missing FROM t");
+    assert!(matches!(&messages[0], PgMessage::Error(code, _) if code == "42000"));
+    assert_eq!(ready(b'T'), messages[1]);
+    assert_eq!(
+        vec![complete("COMMIT"), ready(b'I')],
+        client.query("COMMIT")
+    );
+    assert_eq!(
+        vec![complete("BEGIN"), complete("ROLLBACK"), ready(b'I')],
+        client.query("BEGIN; ROLLBACK")
+    );
+}
+
+/// Extended query protocol messages get an error, and are ignored until the next Sync.
+#[test]
+fn test_postgres_extended_protocol() {
+    let server = TestServer::start_with(Protocol::Postgres);
+    let mut client = PgClient::connect(server.addr);
+
+    client.send(b'P', b"\0SELECT 1\0\0\0");
+    client.send(b'B', b"\0\0\0\0\0\0\0\0");
+    client.send(b'S', b"");
+    let messages = client.receive_until_ready();
+    assert!(matches!(&messages[0], PgMessage::Error(code, _) if code == "0A000"));
+    assert_eq!(ready(b'I'), messages[1]);
+    assert_eq!(2, messages.len());
+
+    // The session goes on, until the client terminates it.
+    assert_eq!(complete("SELECT 1"), client.query("SELECT 1")[2]);
+    client.send(b'X', b"");
+    assert_eq!(0, client.stream.read(&mut [0]).unwrap());
+}
+
+/// A message from a PostgreSQL server, as far as the tests look into it.
+#[derive(Debug, PartialEq)]
+enum PgMessage {
+    /// Column names and type OIDs.
+    RowDescription(Vec<(String, i32)>),
+    DataRow(Vec<Option<String>>),
+    CommandComplete(String),
+    EmptyQuery,
+    /// The SQLSTATE code and message.
+    Error(String, String),
+    ReadyForQuery(u8),
+    Other(u8),
+}
+
+/// A client speaking the PostgreSQL protocol, byte by byte.
+struct PgClient {
+    stream: TcpStream,
+}
+
+impl PgClient {
+    /// Connects the way psql does: asks for SSL, which is refused, and starts a session.
+    fn connect(addr: SocketAddr) -> Self {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(&8i32.to_be_bytes()).unwrap();
+        stream.write_all(&80877103i32.to_be_bytes()).unwrap();
+        let mut answer = [0];
+        stream.read_exact(&mut answer).unwrap();
+        assert_eq!(b'N', answer[0]);
+
+        let mut body = 196608i32.to_be_bytes().to_vec();
+        body.extend(b"user\0test\0database\0test\0\0");
+        stream
+            .write_all(&(body.len() as i32 + 4).to_be_bytes())
+            .unwrap();
+        stream.write_all(&body).unwrap();
+        let mut client = Self { stream };
+        let messages = client.receive_until_ready();
+        assert_eq!(PgMessage::Other(b'R'), messages[0]);
+        assert_eq!(Some(&ready(b'I')), messages.last());
+        client
+    }
+
+    /// Runs a simple query, returning the messages that answer it.
+    fn query(&mut self, query: &str) -> Vec<PgMessage> {
+        self.send(b'Q', format!("{query}\0").as_bytes());
+        self.receive_until_ready()
+    }
+
+    fn send(&mut self, tag: u8, body: &[u8]) {
+        self.stream.write_all(&[tag]).unwrap();
+        self.stream
+            .write_all(&(body.len() as i32 + 4).to_be_bytes())
+            .unwrap();
+        self.stream.write_all(body).unwrap();
+    }
+
+    /// Receives messages up to and including ReadyForQuery.
+    fn receive_until_ready(&mut self) -> Vec<PgMessage> {
+        let mut messages = Vec::new();
+        loop {
+            let message = self.receive();
+            let ready = matches!(message, PgMessage::ReadyForQuery(_));
+            messages.push(message);
+            if ready {
+                return messages;
+            }
+        }
+    }
+
+    fn receive(&mut self) -> PgMessage {
+        let mut header = [0; 5];
+        self.stream.read_exact(&mut header).unwrap();
+        let length = i32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+        let mut body = vec![0; length - 4];
+        self.stream.read_exact(&mut body).unwrap();
+        let mut body = body.as_slice();
+        match header[0] {
+            b'T' => {
+                let count = take_i16(&mut body);
+                let fields = (0..count)
+                    .map(|_| {
+                        let name = take_cstring(&mut body);
+                        body = &body[6..]; // table OID and column number
+                        let oid = i32::from_be_bytes(body[..4].try_into().unwrap());
+                        body = &body[12..]; // type OID, size and modifier, format
+                        (name, oid)
+                    })
+                    .collect();
+                PgMessage::RowDescription(fields)
+            }
+            b'D' => {
+                let count = take_i16(&mut body);
+                let values = (0..count)
+                    .map(|_| {
+                        let length = i32::from_be_bytes(body[..4].try_into().unwrap());
+                        body = &body[4..];
+                        let length = usize::try_from(length).ok()?;
+                        let value = String::from_utf8(body[..length].to_vec()).unwrap();
+                        body = &body[length..];
+                        Some(value)
+                    })
+                    .collect();
+                PgMessage::DataRow(values)
+            }
+            b'C' => PgMessage::CommandComplete(take_cstring(&mut body)),
+            b'I' => PgMessage::EmptyQuery,
+            b'E' => {
+                let (mut code, mut message) = (String::new(), String::new());
+                while body[0] != 0 {
+                    let field = body[0];
+                    body = &body[1..];
+                    let value = take_cstring(&mut body);
+                    match field {
+                        b'C' => code = value,
+                        b'M' => message = value,
+                        _ => {}
+                    }
+                }
+                PgMessage::Error(code, message)
+            }
+            b'Z' => PgMessage::ReadyForQuery(body[0]),
+            tag => PgMessage::Other(tag),
+        }
+    }
+}
+
+fn take_i16(body: &mut &[u8]) -> i16 {
+    let value = i16::from_be_bytes(body[..2].try_into().unwrap());
+    *body = &body[2..];
+    value
+}
+
+fn take_cstring(body: &mut &[u8]) -> String {
+    let end = body.iter().position(|b| *b == 0).unwrap();
+    let string = String::from_utf8(body[..end].to_vec()).unwrap();
+    *body = &body[end + 1..];
+    string
+}
+
+fn complete(tag: &str) -> PgMessage {
+    PgMessage::CommandComplete(tag.to_string())
+}
+
+fn ready(status: u8) -> PgMessage {
+    PgMessage::ReadyForQuery(status)
+}
+
+fn data_row(values: &[Option<&str>]) -> PgMessage {
+    PgMessage::DataRow(values.iter().map(|v| v.map(str::to_string)).collect())
+}
+
+/// A server running on a thread of its own, listening on a free port.
+struct TestServer {
+    addr: SocketAddr,
+    bpm: Arc<BufferPoolManager>,
+    shutdown: ShutdownHandle,
+    thread: Option<JoinHandle<()>>,
+    _dir: TempDir,
+}
+
+impl TestServer {
+    fn start() -> Self {
+        Self::start_with(Protocol::Native)
+    }
+
+    fn start_with(protocol: Protocol) -> Self {
+        let dir = TempDir::new().unwrap();
+        let disk_manager = DiskManager::new_with_handle(dir.path().join("test").to_str().unwrap());
+        let bpm = BufferPoolManager::builder()
+            .pool_size(50)
+            .replacer_k(2)
+            .disk_manager(disk_manager)
+            .build_with_handle();
+        let engine = Local::new(HeapTableManager::new(&bpm));
+        let server = Server::bind("127.0.0.1:0", engine)
+            .unwrap()
+            .with_protocol(protocol);
+        let addr = server.local_addr().unwrap();
+        let shutdown = server.shutdown_handle().unwrap();
+        let thread = thread::spawn(move || server.serve().unwrap());
+        Self {
+            addr,
+            bpm,
+            shutdown,
+            thread: Some(thread),
+            _dir: dir,
+        }
+    }
+
+    /// Shuts the server down, and waits for it to finish.
+    fn stop(mut self) {
+        self.shutdown.shutdown();
+        self.thread.take().unwrap().join().unwrap();
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.shutdown.shutdown();
+            thread.join().ok();
+        }
+    }
+}
+
+fn done(command: &str, count: u64) -> ClientResult {
+    ClientResult::Done {
+        command: command.to_string(),
+        count,
+    }
+}
+
+fn row(id: i32, name: &str) -> Row {
+    Row::from(vec![Field::Integer(id), Field::String(name.to_string())])
+}
+
+fn count(client: &mut Client) -> i32 {
+    match client.execute("SELECT COUNT(*) FROM t").unwrap() {
+        ClientResult::Rows { rows, .. } => match rows[0].iter().next() {
+            Some(Field::Integer(count)) => *count,
+            field => panic!("unexpected count {field:?}"),
+        },
+        result => panic!("unexpected result {result:?}"),
+    }
+}